@@ -2,13 +2,21 @@
 // Supports multiple alert channels (Email, Telegram, Webhook)
 // with configurable rules and alert aggregation
 
+use crate::anomaly::AnomalyReading;
+use crate::backup::SpaceCheck;
+use crate::blocks::{BlockState, FoundBlock};
+use crate::health::HealthStatus;
+use crate::mempool::FeeSnapshot;
+use crate::share_stats::WorkerShareStats;
+use crate::store_doctor::{DoctorStatus, DoctorVerdict};
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use tracing::{error, info, warn};
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex, RwLock};
+use tracing::{debug, error, info, warn};
 
 /// Alert severity levels
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -33,6 +41,16 @@ impl AlertLevel {
     }
 }
 
+impl AlertLevel {
+    /// Next level up, saturating at Critical
+    pub fn escalate(&self) -> Self {
+        match self {
+            Self::Info => Self::Warning,
+            Self::Warning | Self::Critical => Self::Critical,
+        }
+    }
+}
+
 impl std::fmt::Display for AlertLevel {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -73,14 +91,67 @@ pub enum AlertCondition {
     HashrateBelow { threshold: f64, duration_minutes: u64 },
     /// Hashrate above threshold (TH/s)
     HashrateAbove { threshold: f64, duration_minutes: u64 },
+    /// The latest hashrate sample is at least `sigma` standard deviations
+    /// below the pool's EWMA baseline
+    HashrateDropSigma { sigma: f64 },
+    /// The latest hashrate sample is at least `sigma` standard deviations
+    /// above the pool's EWMA baseline
+    HashrateSpikeSigma { sigma: f64 },
     /// Block not found within duration
     NoBlock { duration_minutes: u64 },
     /// Worker count below threshold
     WorkerCountBelow { threshold: u64 },
+    /// Worker count fell by at least `percent` since the previous evaluation
+    WorkerCountDrop { percent: f64 },
+    /// Combined hashrate of the farm's addresses below threshold (TH/s)
+    FarmHashrateBelow { farm: String, threshold: f64, duration_minutes: u64 },
+    /// Fewer than `threshold` workers are mining to the farm's addresses
+    FarmWorkerCountBelow { farm: String, threshold: u64 },
+    /// A worker with at least `min_shares` submitted shares had at least
+    /// `percent` of them rejected as stale, duplicate or invalid
+    WorkerRejectRatioAbove { percent: f64, min_shares: u64 },
+    /// At least `percent` of the share blocks found in the last hour were
+    /// orphaned, once `min_shares` were found; usually a sign of network latency
+    OrphanRateAbove { percent: f64, min_shares: u64 },
+    /// The stratum server reported at least `count` failed authorizations in
+    /// the last hour; usually a misconfigured farm or someone probing usernames
+    StratumAuthFailuresAbove { count: u64 },
+    /// The node's fee estimate for confirmation within 6 blocks is above
+    /// `sat_per_vb`, or the mempool won't accept less
+    FeeRateAbove { sat_per_vb: f64 },
+    /// Paying out a balance at the minimum payout would cost at least
+    /// `percent` of it in fees, so small payouts are uneconomical
+    PayoutFeeAbove { percent: f64 },
+    /// A health check component ("database", "bitcoin_node", "stratum",
+    /// "stratum_endpoint", "zmq" or "overall") is unhealthy
+    ComponentUnhealthy { component: String },
+    /// The most recent backup failed
+    BackupFailed,
+    /// The backup volume has too little free space for the next backup
+    BackupSpaceLow,
     /// Database error
     DatabaseError,
+    /// The store doctor detected corruption in the store that no diagnosis
+    /// has found fixed since
+    StoreCorrupted,
     /// API error
     ApiError,
+    /// The pool found a block; raised once per block
+    BlockFound,
+    /// A pool block reached maturity, so its reward can be paid out; raised once per block
+    BlockMatured,
+    /// A pool block left the main chain before maturing; raised once per block
+    BlockOrphaned,
+    /// A user logged in from a country none of their earlier logins came from
+    /// (requires GeoIP); raised once per login
+    LoginFromNewCountry,
+    /// Repeated failed logins locked an account or client address; raised once per lockout
+    LoginLockout,
+    /// A worker that had been mining stopped submitting shares; raised once
+    /// per worker until it submits again
+    WorkerDown,
+    /// Miners are connected from any of these countries (ISO codes, requires GeoIP)
+    ConnectionsFromCountry { countries: Vec<String> },
     /// Custom message
     Custom { message: String },
 }
@@ -104,11 +175,100 @@ pub struct AlertRule {
     pub channels: Vec<String>,
     /// Cooldown period between alerts (minutes)
     pub cooldown_minutes: u64,
+    /// Raise the level of a still-active alert after this many minutes
+    #[serde(default)]
+    pub escalate_after_minutes: Option<u64>,
     /// Last time this rule was triggered
     #[serde(skip)]
     last_triggered: Option<DateTime<Utc>>,
 }
 
+impl AlertRule {
+    /// Create an enabled rule with no channels and a 30 minute cooldown
+    pub fn new(id: &str, name: &str, condition: AlertCondition, level: AlertLevel) -> Self {
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            description: String::new(),
+            condition,
+            level,
+            enabled: true,
+            channels: Vec::new(),
+            cooldown_minutes: 30,
+            escalate_after_minutes: None,
+            last_triggered: None,
+        }
+    }
+
+    /// Deliver this rule's alerts to the named channels
+    pub fn with_channels(mut self, channels: Vec<String>) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    /// Escalate the alert level while the condition persists
+    pub fn with_escalation(mut self, minutes: u64) -> Self {
+        self.escalate_after_minutes = Some(minutes);
+        self
+    }
+}
+
+/// Live values the rule engine evaluates conditions against
+///
+/// Missing values leave rules that depend on them in their current state.
+#[derive(Clone, Debug, Default)]
+pub struct AlertInputs {
+    pub hashrate_ths: Option<f64>,
+    pub worker_count: Option<u64>,
+    pub last_block_at: Option<DateTime<Utc>>,
+    pub health: Option<HealthStatus>,
+    pub backup_failure: Option<String>,
+    /// Free space on the backup volume against what a backup needs
+    pub backup_space: Option<SpaceCheck>,
+    pub api_error: Option<String>,
+    /// Recent share outcomes keyed by `address.worker`
+    pub worker_share_stats: Option<HashMap<String, WorkerShareStats>>,
+    /// Connected miners per country code
+    pub connection_countries: Option<HashMap<String, u64>>,
+    /// Orphaned and all share blocks found in the last hour
+    pub orphaned_shares: Option<(u64, u64)>,
+    /// Failed stratum authorizations reported in the last hour
+    pub stratum_auth_failures: Option<u64>,
+    /// Latest hashrate sample against its baseline
+    pub hashrate_anomaly: Option<AnomalyReading>,
+    /// Combined hashrate (TH/s) and worker count per farm name
+    pub farms: Option<HashMap<String, (f64, u64)>>,
+    /// Latest fee estimates and mempool size from the node
+    pub fees: Option<FeeSnapshot>,
+    /// Smallest balance paid out
+    pub min_payout_sats: Option<u64>,
+    /// Store corruption and the latest diagnosis
+    pub store_doctor: Option<DoctorStatus>,
+}
+
+/// Engine state for one rule between evaluations
+#[derive(Clone, Debug, Default)]
+struct RuleState {
+    /// When the condition started holding (for duration-based conditions)
+    pending_since: Option<DateTime<Utc>>,
+    /// Whether an alert for this rule is currently firing
+    active: bool,
+    /// Level of the last notification, raised by escalation
+    level: Option<AlertLevel>,
+    /// When the alert fired or was last escalated
+    last_raised: Option<DateTime<Utc>>,
+    /// An operator acknowledged the alert; suppresses escalation
+    acknowledged: bool,
+}
+
+/// What an evaluation pass did for a rule
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RuleTransition {
+    Fired { rule_id: String, level: AlertLevel },
+    Escalated { rule_id: String, level: AlertLevel },
+    Cleared { rule_id: String },
+}
+
 /// Alert notification
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Alert {
@@ -128,8 +288,48 @@ pub struct Alert {
     pub triggered_at: DateTime<Utc>,
     /// Whether alert has been acknowledged
     pub acknowledged: bool,
+    /// User who acknowledged the alert
+    #[serde(default)]
+    pub acknowledged_by: Option<String>,
+    /// When the alert was acknowledged
+    #[serde(default)]
+    pub acknowledged_at: Option<DateTime<Utc>>,
     /// Channel that was used
     pub channel: String,
+    /// Set once the condition that raised the alert no longer holds
+    #[serde(default)]
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+/// How an alert's state changed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertChangeKind {
+    Fired,
+    Escalated,
+    Resolved,
+    Acknowledged,
+}
+
+/// An alert state change, numbered so streams can resume after a reconnect
+#[derive(Clone, Debug, Serialize)]
+pub struct AlertChange {
+    /// Increases with every change
+    pub id: u64,
+    pub kind: AlertChangeKind,
+    /// The alert as of this change
+    pub alert: Alert,
+    pub changed_at: DateTime<Utc>,
+}
+
+/// Recent state changes kept for resuming streams
+const ALERT_CHANGE_BACKLOG: usize = 256;
+
+/// Numbered recent changes and their subscribers
+struct ChangeLog {
+    next_id: u64,
+    recent: VecDeque<AlertChange>,
+    events: broadcast::Sender<AlertChange>,
 }
 
 /// Alert statistics
@@ -142,6 +342,242 @@ pub struct AlertStats {
     pub alerts_by_rule: HashMap<String, usize>,
 }
 
+/// Retry and rate limit settings for alert delivery
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeliveryPolicy {
+    /// Attempts per alert and channel, including the first
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled after every failure
+    pub initial_backoff_ms: u64,
+    /// Upper bound for the retry delay
+    pub max_backoff_ms: u64,
+    /// Alerts sent per channel per minute before further alerts are dropped
+    pub max_per_minute: usize,
+    /// Timeout for a single delivery attempt
+    pub timeout_secs: u64,
+}
+
+impl Default for DeliveryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 30_000,
+            max_per_minute: 10,
+            timeout_secs: 10,
+        }
+    }
+}
+
+impl DeliveryPolicy {
+    /// Delay before retry number `attempt` (1-based)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u64 << attempt.saturating_sub(1).min(16);
+        Duration::from_millis(self.initial_backoff_ms.saturating_mul(factor).min(self.max_backoff_ms))
+    }
+}
+
+/// Delivers alerts to channels with retry, backoff and per-channel rate limiting
+#[derive(Clone)]
+pub struct AlertDispatcher {
+    http: reqwest::Client,
+    policy: DeliveryPolicy,
+    /// Recent send times per channel name, for rate limiting
+    recent: Arc<Mutex<HashMap<String, VecDeque<DateTime<Utc>>>>>,
+}
+
+impl AlertDispatcher {
+    pub fn new(policy: DeliveryPolicy) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(policy.timeout_secs))
+            .build()
+            .unwrap_or_else(|_| reqwest::Client::new());
+        Self {
+            http,
+            policy,
+            recent: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Reserve a send slot for `channel_name`, returning false if it is rate limited
+    async fn try_acquire(&self, channel_name: &str) -> bool {
+        let now = Utc::now();
+        let window_start = now - chrono::Duration::minutes(1);
+        let mut recent = self.recent.lock().await;
+        let sends = recent.entry(channel_name.to_string()).or_default();
+        while sends.front().is_some_and(|t| *t <= window_start) {
+            sends.pop_front();
+        }
+        if sends.len() >= self.policy.max_per_minute {
+            return false;
+        }
+        sends.push_back(now);
+        true
+    }
+
+    /// Deliver an alert to one channel, retrying with exponential backoff
+    pub async fn deliver(&self, channel_name: &str, channel: &AlertChannel, alert: &Alert) -> Result<()> {
+        if !self.try_acquire(channel_name).await {
+            warn!("Alert channel '{}' rate limited, dropping: {}", channel_name, alert.title);
+            return Err(anyhow::anyhow!("Channel '{}' is rate limited", channel_name));
+        }
+
+        let max_attempts = self.policy.max_attempts.max(1);
+        let mut attempt = 1;
+        loop {
+            match self.send_once(channel, alert).await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt < max_attempts => {
+                    let delay = self.policy.backoff(attempt);
+                    warn!(
+                        "Alert delivery via '{}' failed (attempt {}/{}), retrying in {:?}: {}",
+                        channel_name, attempt, max_attempts, delay, e
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    return Err(e.context(format!(
+                        "Alert delivery via '{}' failed after {} attempts",
+                        channel_name, attempt
+                    )));
+                }
+            }
+        }
+    }
+
+    /// Single delivery attempt
+    async fn send_once(&self, channel: &AlertChannel, alert: &Alert) -> Result<()> {
+        match channel {
+            AlertChannel::Email { to_addresses, .. } => {
+                let body = format!(
+                    "{}\n\nLevel: {}\nRule: {}\nTriggered: {}\n\n{}",
+                    alert.message,
+                    alert.level,
+                    alert.rule_id,
+                    alert.triggered_at.format("%Y-%m-%d %H:%M:%S UTC"),
+                    serde_json::to_string_pretty(&alert.context).unwrap_or_default()
+                );
+                self.send_email(channel, to_addresses, &alert.title, body, None).await
+                    .context("Failed to send email alert")
+            }
+            AlertChannel::Telegram { bot_token, chat_id } => {
+                self.send_telegram_alert(bot_token, chat_id, alert).await
+            }
+            AlertChannel::Webhook { url, headers } => {
+                self.send_webhook_alert(url, headers, alert).await
+            }
+        }
+    }
+
+    /// Send an email over an email channel's SMTP server (implicit TLS on
+    /// port 465, STARTTLS otherwise) to `recipients`, with an optional HTML
+    /// alternative to the text body
+    pub async fn send_email(
+        &self,
+        channel: &AlertChannel,
+        recipients: &[String],
+        subject: &str,
+        text: String,
+        html: Option<String>,
+    ) -> Result<()> {
+        use lettre::message::MultiPart;
+        use lettre::transport::smtp::authentication::Credentials;
+        use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+        let AlertChannel::Email { smtp_server, smtp_port, username, password, from_address, .. } = channel else {
+            return Err(anyhow::anyhow!("Not an email channel"));
+        };
+        if recipients.is_empty() {
+            return Err(anyhow::anyhow!("Email has no recipients"));
+        }
+
+        let mut builder = Message::builder()
+            .from(from_address.parse().context("Invalid from address")?)
+            .subject(subject);
+        for to in recipients {
+            builder = builder.to(to.parse().with_context(|| format!("Invalid recipient address: {}", to))?);
+        }
+        let email = match html {
+            Some(html) => builder.multipart(MultiPart::alternative_plain_html(text, html)),
+            None => builder.body(text),
+        }
+        .context("Failed to build email")?;
+
+        let transport = if *smtp_port == 465 {
+            AsyncSmtpTransport::<Tokio1Executor>::relay(smtp_server)
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(smtp_server)
+        }
+        .context("Failed to create SMTP transport")?
+        .port(*smtp_port)
+        .credentials(Credentials::new(username.to_string(), password.to_string()))
+        .timeout(Some(Duration::from_secs(self.policy.timeout_secs)))
+        .build();
+
+        transport.send(email).await?;
+        Ok(())
+    }
+
+    /// Send Telegram alert
+    async fn send_telegram_alert(&self, bot_token: &str, chat_id: &str, alert: &Alert) -> Result<()> {
+        let message = format!(
+            "*{}* {}\n\n{}\n\n{}",
+            alert.level,
+            alert.title,
+            alert.message,
+            alert.triggered_at.format("%Y-%m-%d %H:%M:%S UTC")
+        );
+
+        let url = format!("https://api.telegram.org/bot{}/sendMessage", bot_token);
+
+        let response = self.http
+            .post(&url)
+            .json(&serde_json::json!({
+                "chat_id": chat_id,
+                "text": message,
+                "parse_mode": "Markdown"
+            }))
+            .send()
+            .await
+            .context("Failed to send Telegram alert")?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Telegram API error: {}", response.status()));
+        }
+
+        Ok(())
+    }
+
+    /// Send webhook alert
+    async fn send_webhook_alert(
+        &self,
+        url: &str,
+        headers: &Option<HashMap<String, String>>,
+        alert: &Alert,
+    ) -> Result<()> {
+        let mut request = self.http.post(url).json(alert);
+
+        if let Some(hdrs) = headers {
+            for (key, value) in hdrs {
+                request = request.header(key, value);
+            }
+        }
+
+        let response = request
+            .send()
+            .await
+            .context("Failed to send webhook alert")?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Webhook error: {}", response.status()));
+        }
+
+        Ok(())
+    }
+}
+
 /// Alert manager configuration
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AlertConfig {
@@ -153,6 +589,91 @@ pub struct AlertConfig {
     pub rules: Vec<AlertRule>,
     /// Maximum history size
     pub max_history: usize,
+    /// Retry and rate limit settings shared by all channels
+    #[serde(default)]
+    pub delivery: DeliveryPolicy,
+}
+
+impl AlertConfig {
+    /// Default configuration with a baseline set of health and backup rules
+    pub fn recommended() -> Self {
+        let unhealthy = |component: &str| AlertCondition::ComponentUnhealthy {
+            component: component.to_string(),
+        };
+        Self {
+            rules: vec![
+                AlertRule::new("database_unhealthy", "Database unhealthy", unhealthy("database"), AlertLevel::Critical),
+                AlertRule::new("bitcoin_node_unhealthy", "Bitcoin node unhealthy", unhealthy("bitcoin_node"), AlertLevel::Critical),
+                AlertRule::new("stratum_unhealthy", "Stratum unhealthy", unhealthy("stratum"), AlertLevel::Warning)
+                    .with_escalation(15),
+                AlertRule::new(
+                    "stratum_endpoint_unreachable",
+                    "Stratum endpoint not accepting miners",
+                    unhealthy("stratum_endpoint"),
+                    AlertLevel::Critical,
+                ),
+                AlertRule::new("store_corrupted", "Store corrupted", AlertCondition::StoreCorrupted, AlertLevel::Critical),
+                AlertRule::new("backup_failed", "Backup failed", AlertCondition::BackupFailed, AlertLevel::Warning)
+                    .with_escalation(24 * 60),
+                AlertRule::new("backup_space_low", "Backup volume low on space", AlertCondition::BackupSpaceLow, AlertLevel::Warning)
+                    .with_escalation(24 * 60),
+                AlertRule::new("block_found", "Block found", AlertCondition::BlockFound, AlertLevel::Info),
+                AlertRule::new("block_matured", "Block matured", AlertCondition::BlockMatured, AlertLevel::Info),
+                AlertRule::new("block_orphaned", "Block orphaned", AlertCondition::BlockOrphaned, AlertLevel::Critical),
+                AlertRule::new(
+                    "login_new_country",
+                    "Login from new country",
+                    AlertCondition::LoginFromNewCountry,
+                    AlertLevel::Warning,
+                ),
+                AlertRule::new("login_lockout", "Login locked out", AlertCondition::LoginLockout, AlertLevel::Warning),
+                AlertRule::new("worker_down", "Worker stopped mining", AlertCondition::WorkerDown, AlertLevel::Warning),
+                AlertRule::new(
+                    "worker_reject_ratio",
+                    "Worker reject ratio high",
+                    AlertCondition::WorkerRejectRatioAbove { percent: 5.0, min_shares: 100 },
+                    AlertLevel::Warning,
+                ),
+                AlertRule::new(
+                    "orphan_rate",
+                    "Sharechain orphan rate high",
+                    AlertCondition::OrphanRateAbove { percent: 5.0, min_shares: 100 },
+                    AlertLevel::Warning,
+                ),
+                AlertRule::new(
+                    "stratum_auth_failures",
+                    "Stratum authorization failures",
+                    AlertCondition::StratumAuthFailuresAbove { count: 100 },
+                    AlertLevel::Warning,
+                ),
+                AlertRule::new(
+                    "hashrate_drop",
+                    "Sudden pool hashrate drop",
+                    AlertCondition::HashrateDropSigma { sigma: 4.0 },
+                    AlertLevel::Warning,
+                ),
+                AlertRule::new(
+                    "hashrate_spike",
+                    "Sudden pool hashrate spike",
+                    AlertCondition::HashrateSpikeSigma { sigma: 4.0 },
+                    AlertLevel::Info,
+                ),
+                AlertRule::new(
+                    "payout_fees_high",
+                    "Payout fees high",
+                    AlertCondition::PayoutFeeAbove { percent: 5.0 },
+                    AlertLevel::Warning,
+                ),
+                AlertRule::new(
+                    "worker_count_drop",
+                    "Worker count dropped",
+                    AlertCondition::WorkerCountDrop { percent: 50.0 },
+                    AlertLevel::Warning,
+                ),
+            ],
+            ..Self::default()
+        }
+    }
 }
 
 impl Default for AlertConfig {
@@ -162,6 +683,7 @@ impl Default for AlertConfig {
             channels: HashMap::new(),
             rules: Vec::new(),
             max_history: 1000,
+            delivery: DeliveryPolicy::default(),
         }
     }
 }
@@ -170,17 +692,62 @@ impl Default for AlertConfig {
 pub struct AlertManager {
     config: Arc<RwLock<AlertConfig>>,
     history: Arc<RwLock<Vec<Alert>>>,
+    events: broadcast::Sender<Alert>,
+    changes: Mutex<ChangeLog>,
+    dispatcher: AlertDispatcher,
+    /// Rule engine state keyed by rule ID
+    rule_states: Arc<RwLock<HashMap<String, RuleState>>>,
+    /// Worker count seen by the previous evaluation
+    last_worker_count: Arc<RwLock<Option<u64>>>,
 }
 
 impl AlertManager {
     /// Create a new alert manager
     pub fn new(config: AlertConfig) -> Self {
+        let (events, _) = broadcast::channel(64);
+        let dispatcher = AlertDispatcher::new(config.delivery.clone());
         Self {
             config: Arc::new(RwLock::new(config)),
             history: Arc::new(RwLock::new(Vec::new())),
+            events,
+            changes: Mutex::new(ChangeLog {
+                next_id: 1,
+                recent: VecDeque::with_capacity(ALERT_CHANGE_BACKLOG),
+                events: broadcast::channel(64).0,
+            }),
+            dispatcher,
+            rule_states: Arc::new(RwLock::new(HashMap::new())),
+            last_worker_count: Arc::new(RwLock::new(None)),
         }
     }
 
+    /// Subscribe to alerts as they are triggered
+    pub fn subscribe(&self) -> broadcast::Receiver<Alert> {
+        self.events.subscribe()
+    }
+
+    /// Changes after `after_id` still kept and a receiver for every later change
+    ///
+    /// Nothing falls between the two, so a client resuming from an ID misses
+    /// no change unless it has aged out of the backlog.
+    pub async fn follow_changes(&self, after_id: u64) -> (Vec<AlertChange>, broadcast::Receiver<AlertChange>) {
+        let changes = self.changes.lock().await;
+        let missed = changes.recent.iter().filter(|c| c.id > after_id).cloned().collect();
+        (missed, changes.events.subscribe())
+    }
+
+    async fn record_change(&self, kind: AlertChangeKind, alert: Alert) {
+        let mut changes = self.changes.lock().await;
+        let change = AlertChange { id: changes.next_id, kind, alert, changed_at: Utc::now() };
+        changes.next_id += 1;
+        if changes.recent.len() >= ALERT_CHANGE_BACKLOG {
+            changes.recent.pop_front();
+        }
+        // No subscribers is not an error
+        let _ = changes.events.send(change.clone());
+        changes.recent.push_back(change);
+    }
+
     /// Create with default configuration
     pub fn default() -> Self {
         Self::new(AlertConfig::default())
@@ -224,10 +791,24 @@ impl AlertManager {
         rule_id: &str,
         context: serde_json::Value,
     ) -> Result<()> {
+        self.raise(rule_id, None, context, true).await.map(|_| ())
+    }
+
+    /// Raise an alert for a rule, optionally overriding its level
+    ///
+    /// Returns `None` if alerting is disabled, the rule is disabled or the
+    /// rule is still in cooldown (when `respect_cooldown` is set).
+    async fn raise(
+        &self,
+        rule_id: &str,
+        level: Option<AlertLevel>,
+        context: serde_json::Value,
+        respect_cooldown: bool,
+    ) -> Result<Option<Alert>> {
         let config = self.config.read().await;
 
         if !config.enabled {
-            return Ok(());
+            return Ok(None);
         }
 
         let rule = config.rules.iter()
@@ -235,67 +816,353 @@ impl AlertManager {
             .ok_or_else(|| anyhow::anyhow!("Rule not found: {}", rule_id))?;
 
         if !rule.enabled {
-            return Ok(());
+            return Ok(None);
         }
 
         // Check cooldown
-        if let Some(last_triggered) = rule.last_triggered {
-            let elapsed = Utc::now().signed_duration_since(last_triggered).num_minutes();
-            if elapsed < rule.cooldown_minutes as i64 {
-                return Ok(()); // Still in cooldown
+        if respect_cooldown {
+            if let Some(last_triggered) = rule.last_triggered {
+                let elapsed = Utc::now().signed_duration_since(last_triggered).num_minutes();
+                if elapsed < rule.cooldown_minutes as i64 {
+                    return Ok(None); // Still in cooldown
+                }
             }
         }
 
-        // Clone values we need after dropping config
-        let rule_name = rule.name.clone();
-        let rule_level = rule.level;
-        let rule_id_clone = rule.id.clone();
-
+        let level_override = level.is_some();
+        let level = level.unwrap_or(rule.level);
         let alert = Alert {
             id: uuid::Uuid::new_v4().to_string(),
             rule_id: rule.id.clone(),
-            level: rule.level,
-            title: format!("{} Alert: {}", rule.level, rule.name),
+            level,
+            title: format!("{} Alert: {}", level, rule.name),
             message: self.format_message(&rule.condition, &context)?,
             context,
             triggered_at: Utc::now(),
             acknowledged: false,
+            acknowledged_by: None,
+            acknowledged_at: None,
             channel: rule.channels.first().cloned().unwrap_or_default(),
+            resolved_at: None,
         };
 
-        // Send to channels
-        for channel_name in &rule.channels {
-            if let Some(channel) = config.channels.get(channel_name) {
-                if let Err(e) = self.send_alert(channel, &alert).await {
-                    error!("Failed to send alert via {}: {}", channel_name, e);
-                }
-            }
-        }
+        self.dispatch(&config, rule, &alert);
+        let max_history = config.max_history;
+        drop(config);
 
         // Add to history
         let mut history = self.history.write().await;
         history.push(alert.clone());
 
         // Trim history if needed
-        if history.len() > config.max_history {
-            let remove_count = history.len() - config.max_history;
+        if history.len() > max_history {
+            let remove_count = history.len() - max_history;
             history.drain(0..remove_count);
         }
+        drop(history);
 
         // Update last triggered time (requires write access to config)
-        drop(config);
-        drop(history);
         let mut config = self.config.write().await;
-        if let Some(rule) = config.rules.iter_mut().find(|r| r.id == rule_id_clone) {
+        let mut rule_name = String::new();
+        if let Some(rule) = config.rules.iter_mut().find(|r| r.id == rule_id) {
             rule.last_triggered = Some(Utc::now());
+            rule_name = rule.name.clone();
         }
 
-        info!("Alert triggered: {} ({})", rule_name, rule_level);
-        Ok(())
+        let kind = if level_override { AlertChangeKind::Escalated } else { AlertChangeKind::Fired };
+        self.record_change(kind, alert.clone()).await;
+
+        info!("Alert triggered: {} ({})", rule_name, level);
+        Ok(Some(alert))
+    }
+
+    /// Send an alert to live subscribers and the rule's channels
+    fn dispatch(&self, config: &AlertConfig, rule: &AlertRule, alert: &Alert) {
+        // Deliver in the background so retries don't hold up the caller
+        let targets: Vec<(String, AlertChannel)> = rule.channels.iter()
+            .filter_map(|name| match config.channels.get(name) {
+                Some(channel) => Some((name.clone(), channel.clone())),
+                None => {
+                    warn!("Alert rule '{}' references unknown channel '{}'", rule.id, name);
+                    None
+                }
+            })
+            .collect();
+        if !targets.is_empty() {
+            let dispatcher = self.dispatcher.clone();
+            let delivered = alert.clone();
+            tokio::spawn(async move {
+                for (name, channel) in targets {
+                    if let Err(e) = dispatcher.deliver(&name, &channel, &delivered).await {
+                        error!("Failed to send alert via {}: {:#}", name, e);
+                    }
+                }
+            });
+        }
+
+        // Notify live subscribers; no receivers is not an error
+        let _ = self.events.send(alert.clone());
+    }
+
+    /// Mark a rule's open alerts resolved and notify its channels
+    async fn resolve(&self, rule_id: &str) {
+        let now = Utc::now();
+        let mut resolved = None;
+        {
+            let mut history = self.history.write().await;
+            for alert in history.iter_mut().filter(|a| a.rule_id == rule_id && a.resolved_at.is_none()) {
+                alert.resolved_at = Some(now);
+                resolved = Some(alert.clone());
+            }
+        }
+        if let Some(alert) = &resolved {
+            self.record_change(AlertChangeKind::Resolved, alert.clone()).await;
+        }
+
+        let config = self.config.read().await;
+        if let (Some(mut alert), Some(rule)) = (resolved, config.rules.iter().find(|r| r.id == rule_id)) {
+            alert.id = uuid::Uuid::new_v4().to_string();
+            alert.title = format!("RESOLVED: {}", rule.name);
+            alert.message = format!("{} is no longer firing", rule.name);
+            alert.triggered_at = now;
+            self.dispatch(&config, rule, &alert);
+            info!("Alert resolved: {}", rule.name);
+        }
+    }
+
+    /// Whether a condition holds for the given inputs; `None` if the inputs
+    /// needed to decide are missing
+    fn condition_met(
+        condition: &AlertCondition,
+        inputs: &AlertInputs,
+        previous_workers: Option<u64>,
+    ) -> Option<bool> {
+        match condition {
+            AlertCondition::HashrateBelow { threshold, .. } => inputs.hashrate_ths.map(|h| h < *threshold),
+            AlertCondition::HashrateAbove { threshold, .. } => inputs.hashrate_ths.map(|h| h > *threshold),
+            AlertCondition::HashrateDropSigma { sigma } => inputs.hashrate_anomaly.as_ref().map(|a| a.sigma <= -*sigma),
+            AlertCondition::HashrateSpikeSigma { sigma } => inputs.hashrate_anomaly.as_ref().map(|a| a.sigma >= *sigma),
+            AlertCondition::NoBlock { duration_minutes } => inputs.last_block_at.map(|at| {
+                Utc::now().signed_duration_since(at).num_minutes() >= *duration_minutes as i64
+            }),
+            AlertCondition::WorkerCountBelow { threshold } => inputs.worker_count.map(|c| c < *threshold),
+            AlertCondition::FarmHashrateBelow { farm, threshold, .. } => {
+                inputs.farms.as_ref()?.get(farm).map(|(hashrate, _)| hashrate < threshold)
+            }
+            AlertCondition::FarmWorkerCountBelow { farm, threshold } => {
+                inputs.farms.as_ref()?.get(farm).map(|(_, workers)| workers < threshold)
+            }
+            AlertCondition::WorkerRejectRatioAbove { percent, min_shares } => {
+                let stats = inputs.worker_share_stats.as_ref()?;
+                Some(stats.values().any(|s| s.total() >= *min_shares && s.reject_ratio() * 100.0 >= *percent))
+            }
+            AlertCondition::OrphanRateAbove { percent, min_shares } => {
+                let (orphaned, found) = inputs.orphaned_shares?;
+                Some(found > 0 && found >= *min_shares && orphaned as f64 / found as f64 * 100.0 >= *percent)
+            }
+            AlertCondition::StratumAuthFailuresAbove { count } => {
+                inputs.stratum_auth_failures.map(|failures| failures >= *count)
+            }
+            AlertCondition::WorkerCountDrop { percent } => {
+                let (current, previous) = (inputs.worker_count?, previous_workers?);
+                if previous == 0 {
+                    return Some(false);
+                }
+                let dropped = previous.saturating_sub(current) as f64 / previous as f64 * 100.0;
+                Some(dropped >= *percent)
+            }
+            AlertCondition::FeeRateAbove { sat_per_vb } => {
+                inputs.fees.as_ref()?.payout_fee_rate().map(|rate| rate > *sat_per_vb)
+            }
+            AlertCondition::PayoutFeeAbove { percent } => {
+                let fee = inputs.fees.as_ref()?.payment_fee_sats()?;
+                let min_payout = inputs.min_payout_sats.filter(|sats| *sats > 0)?;
+                Some(fee as f64 / min_payout as f64 * 100.0 >= *percent)
+            }
+            AlertCondition::ComponentUnhealthy { component } => {
+                let health = inputs.health.as_ref()?;
+                let status = match component.as_str() {
+                    "database" => &health.database.status,
+                    "bitcoin_node" => &health.bitcoin_node.status,
+                    "stratum" => &health.stratum.status,
+                    "stratum_endpoint" => &health.stratum_endpoint.status,
+                    "zmq" => &health.zmq.status,
+                    "overall" => &health.status,
+                    _ => return None,
+                };
+                Some(status == "unhealthy")
+            }
+            AlertCondition::DatabaseError => inputs.health.as_ref().map(|h| h.database.status == "unhealthy"),
+            AlertCondition::StoreCorrupted => inputs.store_doctor.as_ref().map(|s| s.detection.is_some()),
+            AlertCondition::BackupFailed => Some(inputs.backup_failure.is_some()),
+            AlertCondition::BackupSpaceLow => inputs.backup_space.as_ref().map(|space| !space.sufficient()),
+            AlertCondition::ApiError => Some(inputs.api_error.is_some()),
+            AlertCondition::ConnectionsFromCountry { countries } => {
+                let connected = inputs.connection_countries.as_ref()?;
+                Some(countries.iter().any(|c| connected.get(&c.to_uppercase()).is_some_and(|n| *n > 0)))
+            }
+            // Event alerts are only raised explicitly
+            AlertCondition::BlockFound
+            | AlertCondition::BlockMatured
+            | AlertCondition::BlockOrphaned
+            | AlertCondition::LoginFromNewCountry
+            | AlertCondition::LoginLockout
+            | AlertCondition::WorkerDown
+            | AlertCondition::Custom { .. } => None,
+        }
+    }
+
+    /// How long a condition must hold before it fires
+    fn required_duration(condition: &AlertCondition) -> chrono::Duration {
+        match condition {
+            AlertCondition::HashrateBelow { duration_minutes, .. }
+            | AlertCondition::HashrateAbove { duration_minutes, .. }
+            | AlertCondition::FarmHashrateBelow { duration_minutes, .. } => {
+                chrono::Duration::minutes(*duration_minutes as i64)
+            }
+            _ => chrono::Duration::zero(),
+        }
+    }
+
+    /// Evaluate every enabled rule against `inputs`, firing, escalating and
+    /// clearing alerts. Active alerts are not re-sent until they clear.
+    pub async fn evaluate(&self, inputs: &AlertInputs) -> Vec<RuleTransition> {
+        let rules: Vec<AlertRule> = {
+            let config = self.config.read().await;
+            if !config.enabled {
+                return Vec::new();
+            }
+            config.rules.iter().filter(|r| r.enabled).cloned().collect()
+        };
+        let previous_workers = {
+            let mut last = self.last_worker_count.write().await;
+            let previous = *last;
+            if inputs.worker_count.is_some() {
+                *last = inputs.worker_count;
+            }
+            previous
+        };
+
+        let worst_reject_worker = inputs.worker_share_stats.as_ref().and_then(|stats| {
+            stats.iter()
+                .max_by(|a, b| a.1.reject_ratio().total_cmp(&b.1.reject_ratio()))
+                .map(|(worker, s)| serde_json::json!({
+                    "worker": worker,
+                    "reject_ratio": s.reject_ratio(),
+                    "shares": s.total(),
+                }))
+        });
+        let context = serde_json::json!({
+            "worst_reject_worker": worst_reject_worker,
+            "hashrate_ths": inputs.hashrate_ths,
+            "worker_count": inputs.worker_count,
+            "previous_worker_count": previous_workers,
+            "health": inputs.health.as_ref().map(|h| h.status.clone()),
+            "backup_failure": inputs.backup_failure,
+            "backup_space": inputs.backup_space,
+            "api_error": inputs.api_error,
+            "connection_countries": inputs.connection_countries,
+            "orphaned_shares": inputs.orphaned_shares.map(|(orphaned, _)| orphaned),
+            "found_shares": inputs.orphaned_shares.map(|(_, found)| found),
+            "stratum_auth_failures": inputs.stratum_auth_failures,
+            "hashrate_anomaly": inputs.hashrate_anomaly,
+            "farms": inputs.farms.as_ref().map(|farms| farms.iter()
+                .map(|(name, (hashrate_ths, workers))| {
+                    (name.clone(), serde_json::json!({ "hashrate_ths": hashrate_ths, "workers": workers }))
+                })
+                .collect::<serde_json::Map<_, _>>()),
+            "fees": inputs.fees.as_ref().map(|fees| serde_json::json!({
+                "payout_fee_rate": fees.payout_fee_rate(),
+                "payment_fee_sats": fees.payment_fee_sats(),
+                "mempool_txs": fees.mempool_txs,
+            })),
+            "min_payout_sats": inputs.min_payout_sats,
+            "store_doctor": inputs.store_doctor,
+        });
+
+        let now = Utc::now();
+        let mut transitions = Vec::new();
+
+        for rule in rules {
+            let met = match Self::condition_met(&rule.condition, inputs, previous_workers) {
+                Some(met) => met,
+                None => continue,
+            };
+            let mut state = self.rule_states.read().await.get(&rule.id).cloned().unwrap_or_default();
+
+            match (met, state.active) {
+                (true, false) => {
+                    let since = *state.pending_since.get_or_insert(now);
+                    if now.signed_duration_since(since) >= Self::required_duration(&rule.condition) {
+                        match self.raise(&rule.id, None, context.clone(), true).await {
+                            Ok(Some(alert)) => {
+                                state.active = true;
+                                state.level = Some(alert.level);
+                                state.last_raised = Some(now);
+                                transitions.push(RuleTransition::Fired { rule_id: rule.id.clone(), level: alert.level });
+                            }
+                            Ok(None) => {}
+                            Err(e) => error!("Failed to raise alert for rule '{}': {}", rule.id, e),
+                        }
+                    }
+                }
+                (true, true) => {
+                    let level = state.level.unwrap_or(rule.level);
+                    let due = match (rule.escalate_after_minutes, state.last_raised) {
+                        (Some(minutes), Some(at)) => {
+                            now.signed_duration_since(at).num_minutes() >= minutes as i64
+                        }
+                        _ => false,
+                    };
+                    if due && level != AlertLevel::Critical && !state.acknowledged {
+                        let next = level.escalate();
+                        match self.raise(&rule.id, Some(next), context.clone(), false).await {
+                            Ok(Some(_)) => {
+                                state.level = Some(next);
+                                state.last_raised = Some(now);
+                                transitions.push(RuleTransition::Escalated { rule_id: rule.id.clone(), level: next });
+                            }
+                            Ok(None) => {}
+                            Err(e) => error!("Failed to escalate alert for rule '{}': {}", rule.id, e),
+                        }
+                    }
+                }
+                (false, true) => {
+                    self.resolve(&rule.id).await;
+                    state = RuleState::default();
+                    transitions.push(RuleTransition::Cleared { rule_id: rule.id.clone() });
+                }
+                (false, false) => {
+                    state.pending_since = None;
+                }
+            }
+
+            self.rule_states.write().await.insert(rule.id.clone(), state);
+        }
+
+        transitions
+    }
+
+    /// Evaluate rules every `interval`, collecting fresh inputs each time
+    pub async fn run_evaluation_loop<F, Fut>(self: Arc<Self>, interval: Duration, mut collect: F)
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = AlertInputs>,
+    {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            ticker.tick().await;
+            let inputs = collect().await;
+            let transitions = self.evaluate(&inputs).await;
+            if !transitions.is_empty() {
+                debug!("Alert evaluation: {:?}", transitions);
+            }
+        }
     }
 
     /// Format alert message based on condition
-    fn format_message(&self, condition: &AlertCondition, _context: &serde_json::Value) -> Result<String> {
+    fn format_message(&self, condition: &AlertCondition, context: &serde_json::Value) -> Result<String> {
         Ok(match condition {
             AlertCondition::HashrateBelow { threshold, .. } => {
                 format!("Pool hashrate has dropped below {} TH/s", threshold)
@@ -303,98 +1170,287 @@ impl AlertManager {
             AlertCondition::HashrateAbove { threshold, .. } => {
                 format!("Pool hashrate has exceeded {} TH/s", threshold)
             }
+            AlertCondition::HashrateDropSigma { .. } | AlertCondition::HashrateSpikeSigma { .. } => {
+                let anomaly = &context["hashrate_anomaly"];
+                let cause = match anomaly["cause"].as_str() {
+                    Some("miner_left") => "; connections held steady, so a large miner likely left",
+                    Some("stratum_outage") => "; connections fell too, so check the stratum server",
+                    _ => "",
+                };
+                format!(
+                    "Pool hashrate is {:.2} TH/s, {:.1} standard deviations from its {:.2} TH/s baseline{}",
+                    anomaly["hashrate_ths"].as_f64().unwrap_or(0.0),
+                    anomaly["sigma"].as_f64().unwrap_or(0.0),
+                    anomaly["baseline_ths"].as_f64().unwrap_or(0.0),
+                    cause
+                )
+            }
             AlertCondition::NoBlock { duration_minutes } => {
                 format!("No block found in the last {} minutes", duration_minutes)
             }
             AlertCondition::WorkerCountBelow { threshold } => {
                 format!("Worker count has dropped below {}", threshold)
             }
+            AlertCondition::WorkerCountDrop { percent } => {
+                format!("Worker count has dropped by {}% or more", percent)
+            }
+            AlertCondition::FarmHashrateBelow { farm, threshold, .. } => {
+                format!(
+                    "Farm {} hashrate is {:.2} TH/s, below {} TH/s",
+                    farm,
+                    context["farms"][farm]["hashrate_ths"].as_f64().unwrap_or(0.0),
+                    threshold
+                )
+            }
+            AlertCondition::FarmWorkerCountBelow { farm, threshold } => {
+                format!(
+                    "Farm {} has {} worker(s) mining, below {}",
+                    farm,
+                    context["farms"][farm]["workers"],
+                    threshold
+                )
+            }
+            AlertCondition::FeeRateAbove { sat_per_vb } => {
+                format!(
+                    "Payout fee rate is {:.1} sat/vB, above {} sat/vB, with {} transactions in the mempool",
+                    context["fees"]["payout_fee_rate"].as_f64().unwrap_or(0.0),
+                    sat_per_vb,
+                    context["fees"]["mempool_txs"]
+                )
+            }
+            AlertCondition::PayoutFeeAbove { percent } => {
+                format!(
+                    "A payment costs {} sats in fees, {}% or more of the {} sat minimum payout",
+                    context["fees"]["payment_fee_sats"],
+                    percent,
+                    context["min_payout_sats"]
+                )
+            }
+            AlertCondition::WorkerRejectRatioAbove { percent, .. } => {
+                format!(
+                    "Worker {} has a reject ratio of {}% or more",
+                    context["worst_reject_worker"]["worker"].as_str().unwrap_or("unknown"),
+                    percent
+                )
+            }
+            AlertCondition::OrphanRateAbove { percent, .. } => {
+                format!(
+                    "{} of {} share blocks found in the last hour were orphaned ({}% or more); check the pool's network latency",
+                    context["orphaned_shares"],
+                    context["found_shares"],
+                    percent
+                )
+            }
+            AlertCondition::StratumAuthFailuresAbove { count } => {
+                format!(
+                    "Stratum server refused {} authorizations in the last hour ({} or more)",
+                    context["stratum_auth_failures"],
+                    count
+                )
+            }
+            AlertCondition::ComponentUnhealthy { component } => {
+                format!("Health check reports {} as unhealthy", component)
+            }
+            AlertCondition::BackupFailed => {
+                "The most recent backup failed".to_string()
+            }
+            AlertCondition::BackupSpaceLow => {
+                let space = &context["backup_space"];
+                format!(
+                    "Backup volume has {} bytes free, but a backup needs about {} and {} must stay free; backups are refused until space is freed",
+                    space["available_bytes"],
+                    space["estimated_bytes"],
+                    space["min_free_bytes"]
+                )
+            }
             AlertCondition::DatabaseError => {
                 "Database error detected".to_string()
             }
+            AlertCondition::StoreCorrupted => {
+                let status = &context["store_doctor"];
+                let verdict = serde_json::from_value::<DoctorVerdict>(status["latest_verdict"].clone())
+                    .map(DoctorVerdict::describe)
+                    .unwrap_or("diagnosis pending");
+                format!(
+                    "Store corruption detected: {}; {}",
+                    status["detection"]["error"].as_str().unwrap_or("unknown error"),
+                    verdict
+                )
+            }
             AlertCondition::ApiError => {
                 "API error detected".to_string()
             }
+            AlertCondition::BlockFound => {
+                format!(
+                    "Pool found block {} ({:.8} BTC), winning share by {}",
+                    context["height"],
+                    context["reward_sats"].as_u64().unwrap_or(0) as f64 / 100_000_000.0,
+                    context["winner_address"].as_str().unwrap_or("unknown")
+                )
+            }
+            AlertCondition::BlockMatured => {
+                format!(
+                    "Pool block {} ({:.8} BTC) matured with {} confirmations",
+                    context["height"],
+                    context["reward_sats"].as_u64().unwrap_or(0) as f64 / 100_000_000.0,
+                    context["confirmations"]
+                )
+            }
+            AlertCondition::BlockOrphaned => {
+                format!(
+                    "Pool block {} ({}) was orphaned; its {:.8} BTC reward is lost",
+                    context["height"],
+                    context["hash"].as_str().unwrap_or("unknown"),
+                    context["reward_sats"].as_u64().unwrap_or(0) as f64 / 100_000_000.0
+                )
+            }
+            AlertCondition::LoginFromNewCountry => {
+                format!(
+                    "User {} logged in from {} ({}); earlier logins came from {}",
+                    context["username"].as_str().unwrap_or("unknown"),
+                    context["country"].as_str().unwrap_or("unknown"),
+                    context["ip"].as_str().unwrap_or("unknown"),
+                    context["known_countries"].as_array()
+                        .map(|c| c.iter().filter_map(|v| v.as_str()).collect::<Vec<_>>().join(", "))
+                        .unwrap_or_default()
+                )
+            }
+            AlertCondition::WorkerDown => {
+                format!(
+                    "Worker {}.{} has submitted no shares since {}, after mining for {} min",
+                    context["address"].as_str().unwrap_or("unknown"),
+                    context["worker"].as_str().unwrap_or("unknown"),
+                    context["last_share_at"].as_str().unwrap_or("unknown"),
+                    context["active_minutes"]
+                )
+            }
+            AlertCondition::LoginLockout => {
+                format!(
+                    "{} locked after {} failed logins, until {}",
+                    context["target"].as_str().unwrap_or("unknown"),
+                    context["failures"],
+                    context["locked_until"].as_str().unwrap_or("unknown")
+                )
+            }
+            AlertCondition::ConnectionsFromCountry { countries } => {
+                let connected: Vec<String> = countries.iter()
+                    .map(|c| c.to_uppercase())
+                    .filter(|c| context["connection_countries"][c].as_u64().unwrap_or(0) > 0)
+                    .collect();
+                format!("Miners are connected from {}", connected.join(", "))
+            }
             AlertCondition::Custom { message } => {
                 message.clone()
             }
         })
     }
 
-    /// Send alert via a specific channel
-    async fn send_alert(&self, channel: &AlertChannel, alert: &Alert) -> Result<()> {
-        match channel {
-            AlertChannel::Email { .. } => {
-                // TODO: Implement email sending
-                warn!("Email alert not yet implemented: {}", alert.title);
-                Ok(())
-            }
-            AlertChannel::Telegram { bot_token, chat_id } => {
-                self.send_telegram_alert(bot_token, chat_id, alert).await
+    /// Raise an event alert on every rule whose condition `matches`, recorded as already resolved
+    async fn raise_event(&self, matches: fn(&AlertCondition) -> bool, context: serde_json::Value) -> Vec<Alert> {
+        let rule_ids: Vec<String> = self.config.read().await.rules.iter()
+            .filter(|r| matches(&r.condition))
+            .map(|r| r.id.clone())
+            .collect();
+
+        let mut alerts = Vec::new();
+        for rule_id in rule_ids {
+            match self.raise(&rule_id, None, context.clone(), false).await {
+                Ok(Some(mut alert)) => {
+                    alert.resolved_at = Some(alert.triggered_at);
+                    alerts.push(alert);
+                }
+                Ok(None) => {}
+                Err(e) => error!("Failed to raise event alert for rule '{}': {}", rule_id, e),
             }
-            AlertChannel::Webhook { url, headers } => {
-                self.send_webhook_alert(url, headers, alert).await
+        }
+        let mut history = self.history.write().await;
+        for alert in history.iter_mut() {
+            if let Some(raised) = alerts.iter().find(|a| a.id == alert.id) {
+                alert.resolved_at = raised.resolved_at;
             }
         }
+        alerts
     }
 
-    /// Send Telegram alert
-    async fn send_telegram_alert(&self, bot_token: &str, chat_id: &str, alert: &Alert) -> Result<()> {
-        let message = format!(
-            "*{}* {}\n\n{}\n\n{}",
-            alert.level,
-            alert.title,
-            alert.message,
-            alert.triggered_at.format("%Y-%m-%d %H:%M:%S UTC")
-        );
-
-        let url = format!("https://api.telegram.org/bot{}/sendMessage", bot_token);
-        let client = reqwest::Client::new();
-        
-        let response = client
-            .post(&url)
-            .json(&serde_json::json!({
-                "chat_id": chat_id,
-                "text": message,
-                "parse_mode": "Markdown"
-            }))
-            .send()
-            .await
-            .context("Failed to send Telegram alert")?;
+    /// Raise an alert on every `BlockFound` rule for a block the pool found
+    ///
+    /// Block alerts are events, so they are recorded as already resolved and
+    /// never show up as firing.
+    pub async fn notify_block_found(&self, block: &FoundBlock, winner_address: Option<&str>) -> Vec<Alert> {
+        let context = serde_json::json!({
+            "height": block.height,
+            "hash": block.hash,
+            "reward_sats": block.reward_sats,
+            "winner_address": winner_address.or(block.finder_address.as_deref()),
+            "found_at": block.timestamp,
+        });
+        self.raise_event(|c| matches!(c, AlertCondition::BlockFound), context).await
+    }
 
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!("Telegram API error: {}", response.status()));
+    /// Raise an alert on every `BlockMatured` or `BlockOrphaned` rule for a
+    /// block that reached that state
+    pub async fn notify_block_state(&self, block: &FoundBlock) -> Vec<Alert> {
+        let context = serde_json::json!({
+            "height": block.height,
+            "hash": block.hash,
+            "reward_sats": block.reward_sats,
+            "confirmations": block.confirmations,
+            "found_at": block.timestamp,
+        });
+        match block.state {
+            BlockState::Mature => self.raise_event(|c| matches!(c, AlertCondition::BlockMatured), context).await,
+            BlockState::Orphaned => self.raise_event(|c| matches!(c, AlertCondition::BlockOrphaned), context).await,
+            BlockState::Pending => Vec::new(),
         }
-
-        Ok(())
     }
 
-    /// Send webhook alert
-    async fn send_webhook_alert(
+    /// Raise an alert on every `LoginFromNewCountry` rule for a login from `country`
+    ///
+    /// `known_countries` are the countries of the user's earlier logins.
+    pub async fn notify_login_from_new_country(
         &self,
-        url: &str,
-        headers: &Option<HashMap<String, String>>,
-        alert: &Alert,
-    ) -> Result<()> {
-        let client = reqwest::Client::new();
-        let mut request = client.post(url).json(alert);
-
-        if let Some(hdrs) = headers {
-            for (key, value) in hdrs {
-                request = request.header(key, value);
-            }
-        }
-
-        let response = request
-            .send()
-            .await
-            .context("Failed to send webhook alert")?;
+        username: &str,
+        ip: &str,
+        country: &str,
+        known_countries: &[String],
+    ) -> Vec<Alert> {
+        let context = serde_json::json!({
+            "username": username,
+            "ip": ip,
+            "country": country,
+            "known_countries": known_countries,
+        });
+        self.raise_event(|c| matches!(c, AlertCondition::LoginFromNewCountry), context).await
+    }
 
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!("Webhook error: {}", response.status()));
-        }
+    /// Raise an alert on every `LoginLockout` rule for a lockout of `target`
+    /// (`user <name>` or `ip <address>`)
+    pub async fn notify_login_lockout(&self, target: &str, failures: u32, locked_until: DateTime<Utc>) -> Vec<Alert> {
+        let context = serde_json::json!({
+            "target": target,
+            "failures": failures,
+            "locked_until": locked_until.to_rfc3339(),
+        });
+        self.raise_event(|c| matches!(c, AlertCondition::LoginLockout), context).await
+    }
 
-        Ok(())
+    /// Raise an alert on every `WorkerDown` rule for a worker that stopped
+    /// submitting shares after mining since `active_since`
+    pub async fn notify_worker_down(
+        &self,
+        address: &str,
+        worker: &str,
+        active_since: DateTime<Utc>,
+        last_share_at: DateTime<Utc>,
+    ) -> Vec<Alert> {
+        let context = serde_json::json!({
+            "address": address,
+            "worker": worker,
+            "active_since": active_since.to_rfc3339(),
+            "last_share_at": last_share_at.to_rfc3339(),
+            "active_minutes": (last_share_at - active_since).num_minutes(),
+        });
+        self.raise_event(|c| matches!(c, AlertCondition::WorkerDown), context).await
     }
 
     /// Get alert history
@@ -412,15 +1468,61 @@ impl AlertManager {
         result
     }
 
+    /// Unresolved alerts, newest first, one per rule
+    pub async fn active_alerts(&self) -> Vec<Alert> {
+        let history = self.history.read().await;
+        let mut seen = std::collections::HashSet::new();
+        history.iter()
+            .rev()
+            .filter(|a| a.resolved_at.is_none())
+            .filter(|a| seen.insert(a.rule_id.clone()))
+            .cloned()
+            .collect()
+    }
+
+    /// Resolved alerts, newest first, optionally for a single rule
+    pub async fn resolved_history(&self, rule_id: Option<&str>, limit: Option<usize>) -> Vec<Alert> {
+        let history = self.history.read().await;
+        history.iter()
+            .rev()
+            .filter(|a| a.resolved_at.is_some())
+            .filter(|a| rule_id.is_none_or(|id| a.rule_id == id))
+            .take(limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect()
+    }
+
     /// Acknowledge an alert
-    pub async fn acknowledge_alert(&self, alert_id: &str) -> Result<bool> {
+    ///
+    /// All open alerts of the same rule are acknowledged and the rule stops
+    /// escalating until its condition clears.
+    pub async fn acknowledge_alert(&self, alert_id: &str, username: &str) -> Result<bool> {
         let mut history = self.history.write().await;
-        if let Some(alert) = history.iter_mut().find(|a| a.id == alert_id) {
-            alert.acknowledged = true;
-            info!("Alert acknowledged: {}", alert_id);
-            return Ok(true);
+        let rule_id = match history.iter().find(|a| a.id == alert_id) {
+            Some(alert) => alert.rule_id.clone(),
+            None => return Ok(false),
+        };
+        let now = Utc::now();
+        for alert in history.iter_mut()
+            .filter(|a| a.id == alert_id || (a.rule_id == rule_id && a.resolved_at.is_none()))
+        {
+            if !alert.acknowledged {
+                alert.acknowledged = true;
+                alert.acknowledged_by = Some(username.to_string());
+                alert.acknowledged_at = Some(now);
+            }
+        }
+        let acknowledged = history.iter().find(|a| a.id == alert_id).cloned();
+        drop(history);
+        if let Some(alert) = acknowledged {
+            self.record_change(AlertChangeKind::Acknowledged, alert).await;
         }
-        Ok(false)
+
+        if let Some(state) = self.rule_states.write().await.get_mut(&rule_id) {
+            state.acknowledged = true;
+        }
+        info!("Alert acknowledged: {} by {}", alert_id, username);
+        Ok(true)
     }
 
     /// Get alert statistics
@@ -457,6 +1559,35 @@ impl AlertManager {
         config.channels.clone()
     }
 
+    /// Send an email that isn't an alert, such as a report, over the SMTP server
+    /// of the email channel `channel_name`, or of the first email channel by name
+    pub async fn send_email(
+        &self,
+        channel_name: Option<&str>,
+        recipients: &[String],
+        subject: &str,
+        text: String,
+        html: Option<String>,
+    ) -> Result<()> {
+        let channel = {
+            let config = self.config.read().await;
+            match channel_name {
+                Some(name) => config.channels.get(name).cloned()
+                    .ok_or_else(|| anyhow::anyhow!("No alert channel named '{}'", name))?,
+                None => {
+                    let mut names: Vec<&String> = config.channels.iter()
+                        .filter(|(_, channel)| matches!(channel, AlertChannel::Email { .. }))
+                        .map(|(name, _)| name)
+                        .collect();
+                    names.sort();
+                    let name = names.first().ok_or_else(|| anyhow::anyhow!("No email alert channel configured"))?;
+                    config.channels[*name].clone()
+                }
+            }
+        };
+        self.dispatcher.send_email(&channel, recipients, subject, text, html).await
+    }
+
     /// Clear old history
     pub async fn cleanup_old_history(&self, keep_last: usize) -> usize {
         let mut history = self.history.write().await;
@@ -482,6 +1613,239 @@ mod tests {
         assert_eq!(AlertLevel::Critical.severity(), 3);
     }
 
+    #[test]
+    fn test_delivery_backoff_is_capped() {
+        let policy = DeliveryPolicy {
+            initial_backoff_ms: 100,
+            max_backoff_ms: 350,
+            ..Default::default()
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(350));
+        assert_eq!(policy.backoff(40), Duration::from_millis(350));
+    }
+
+    #[tokio::test]
+    async fn test_webhook_retry_and_rate_limit() {
+        use axum::{http::StatusCode, routing::post, Router};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // Webhook that fails the first request and accepts the rest
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let app = Router::new().route("/hook", post(move || {
+            let counter = counter.clone();
+            async move {
+                if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                    StatusCode::SERVICE_UNAVAILABLE
+                } else {
+                    StatusCode::OK
+                }
+            }
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let dispatcher = AlertDispatcher::new(DeliveryPolicy {
+            max_attempts: 2,
+            initial_backoff_ms: 10,
+            max_per_minute: 2,
+            ..Default::default()
+        });
+        let channel = AlertChannel::Webhook {
+            url: format!("http://{}/hook", addr),
+            headers: None,
+        };
+        let alert = Alert {
+            id: "a1".to_string(),
+            rule_id: "r1".to_string(),
+            level: AlertLevel::Warning,
+            title: "test".to_string(),
+            message: "test".to_string(),
+            context: serde_json::json!({}),
+            triggered_at: Utc::now(),
+            acknowledged: false,
+            acknowledged_by: None,
+            acknowledged_at: None,
+            channel: "hook".to_string(),
+            resolved_at: None,
+        };
+
+        dispatcher.deliver("hook", &channel, &alert).await.unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+        dispatcher.deliver("hook", &channel, &alert).await.unwrap();
+        assert!(dispatcher.deliver("hook", &channel, &alert).await.is_err());
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_rule_engine_fires_dedups_escalates_and_clears() {
+        let manager = AlertManager::default();
+        manager.add_rule(
+            AlertRule::new("backup", "Backup failed", AlertCondition::BackupFailed, AlertLevel::Info)
+                .with_escalation(0),
+        ).await;
+        manager.add_rule(AlertRule::new(
+            "drop",
+            "Workers dropped",
+            AlertCondition::WorkerCountDrop { percent: 50.0 },
+            AlertLevel::Warning,
+        )).await;
+
+        let failing = AlertInputs {
+            backup_failure: Some("disk full".to_string()),
+            worker_count: Some(10),
+            ..Default::default()
+        };
+        let fired = manager.evaluate(&failing).await;
+        assert_eq!(fired, vec![RuleTransition::Fired { rule_id: "backup".to_string(), level: AlertLevel::Info }]);
+
+        // Still failing: no duplicate, but escalation is due immediately
+        let escalated = manager.evaluate(&AlertInputs { worker_count: Some(4), ..failing.clone() }).await;
+        assert!(escalated.contains(&RuleTransition::Escalated { rule_id: "backup".to_string(), level: AlertLevel::Warning }));
+        assert!(escalated.contains(&RuleTransition::Fired { rule_id: "drop".to_string(), level: AlertLevel::Warning }));
+
+        let cleared = manager.evaluate(&AlertInputs { worker_count: Some(4), ..Default::default() }).await;
+        assert_eq!(cleared, vec![
+            RuleTransition::Cleared { rule_id: "backup".to_string() },
+            RuleTransition::Cleared { rule_id: "drop".to_string() },
+        ]);
+        assert!(manager.get_history(None).await.iter().all(|a| a.resolved_at.is_some()));
+
+        // Re-failing within the cooldown does not fire again
+        assert!(manager.evaluate(&failing).await.is_empty());
+
+        manager.add_rule(AlertRule::new("space", "Backup space", AlertCondition::BackupSpaceLow, AlertLevel::Warning)).await;
+        let space = |available_bytes| AlertInputs {
+            backup_space: Some(SpaceCheck { available_bytes, estimated_bytes: 100, min_free_bytes: 50 }),
+            ..Default::default()
+        };
+        assert!(manager.evaluate(&space(150)).await.is_empty());
+        assert_eq!(manager.evaluate(&space(149)).await, vec![
+            RuleTransition::Fired { rule_id: "space".to_string(), level: AlertLevel::Warning },
+        ]);
+
+        manager.add_rule(AlertRule::new(
+            "farm",
+            "Farm workers",
+            AlertCondition::FarmWorkerCountBelow { farm: "east".to_string(), threshold: 3 },
+            AlertLevel::Warning,
+        )).await;
+        let farm = |workers| AlertInputs {
+            farms: Some(HashMap::from([("east".to_string(), (1.5, workers))])),
+            ..space(150)
+        };
+        assert_eq!(manager.evaluate(&farm(3)).await, vec![RuleTransition::Cleared { rule_id: "space".to_string() }]);
+        assert_eq!(manager.evaluate(&farm(2)).await, vec![
+            RuleTransition::Fired { rule_id: "farm".to_string(), level: AlertLevel::Warning },
+        ]);
+        let message = &manager.get_history(None).await[0].message;
+        assert!(message.contains("Farm east has 2 worker(s)"), "{}", message);
+
+        manager.add_rule(AlertRule::new(
+            "fees",
+            "Payout fees",
+            AlertCondition::PayoutFeeAbove { percent: 5.0 },
+            AlertLevel::Warning,
+        )).await;
+        let fees = |normal_sat_vb| AlertInputs {
+            fees: Some(FeeSnapshot {
+                at: Utc::now(),
+                fast_sat_vb: None,
+                normal_sat_vb: Some(normal_sat_vb),
+                economy_sat_vb: None,
+                mempool_txs: 50_000,
+                mempool_vbytes: 0,
+                mempool_min_fee_sat_vb: 1.0,
+            }),
+            min_payout_sats: Some(10_000),
+            ..farm(2)
+        };
+        // 43 vB at 10 sat/vB is 4.3% of the minimum payout, at 20 sat/vB 8.6%
+        assert!(manager.evaluate(&fees(10.0)).await.is_empty());
+        assert_eq!(manager.evaluate(&fees(20.0)).await, vec![
+            RuleTransition::Fired { rule_id: "fees".to_string(), level: AlertLevel::Warning },
+        ]);
+        let message = &manager.get_history(None).await[0].message;
+        assert!(message.contains("costs 860 sats"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn test_block_found_alert_is_an_event() {
+        let manager = AlertManager::new(AlertConfig::recommended());
+        let block = FoundBlock {
+            height: 840_000,
+            hash: "00ab".to_string(),
+            timestamp: Utc::now(),
+            finder_address: Some("bc1qcoinbase".to_string()),
+            reward_sats: 312_500_000,
+            confirmations: 1,
+            orphaned: false,
+            state: Default::default(),
+            state_changes: Vec::new(),
+        };
+
+        let alerts = manager.notify_block_found(&block, Some("bc1qwinner")).await;
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].message, "Pool found block 840000 (3.12500000 BTC), winning share by bc1qwinner");
+        assert!(manager.active_alerts().await.is_empty());
+
+        assert!(manager.notify_block_state(&block).await.is_empty());
+        let orphaned = FoundBlock { state: BlockState::Orphaned, confirmations: -1, ..block.clone() };
+        let alerts = manager.notify_block_state(&orphaned).await;
+        assert_eq!(alerts[0].message, "Pool block 840000 (00ab) was orphaned; its 3.12500000 BTC reward is lost");
+        assert_eq!(alerts[0].level, AlertLevel::Critical);
+        assert_eq!(manager.resolved_history(Some("block_found"), None).await.len(), 1);
+
+        // Every block notifies, regardless of the rule's cooldown
+        manager.notify_block_found(&block, None).await;
+        assert_eq!(manager.resolved_history(Some("block_found"), None).await.len(), 2);
+
+        let alerts = manager.notify_login_from_new_country("admin", "203.0.113.7", "NZ", &["DE".to_string()]).await;
+        assert_eq!(alerts[0].message, "User admin logged in from NZ (203.0.113.7); earlier logins came from DE");
+        assert!(manager.active_alerts().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_acknowledge_stops_escalation() {
+        let manager = AlertManager::default();
+        manager.add_rule(
+            AlertRule::new("backup", "Backup failed", AlertCondition::BackupFailed, AlertLevel::Info)
+                .with_escalation(0),
+        ).await;
+        let failing = AlertInputs {
+            backup_failure: Some("disk full".to_string()),
+            ..Default::default()
+        };
+        manager.evaluate(&failing).await;
+
+        let (_, mut live) = manager.follow_changes(0).await;
+        let active = manager.active_alerts().await;
+        assert_eq!(active.len(), 1);
+        assert!(manager.acknowledge_alert(&active[0].id, "alice").await.unwrap());
+        assert!(!manager.acknowledge_alert("missing", "alice").await.unwrap());
+        assert!(manager.evaluate(&failing).await.is_empty());
+
+        manager.evaluate(&AlertInputs::default()).await;
+        assert!(manager.active_alerts().await.is_empty());
+        let resolved = manager.resolved_history(Some("backup"), None).await;
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].acknowledged_by.as_deref(), Some("alice"));
+        assert!(manager.resolved_history(Some("other"), None).await.is_empty());
+
+        let (changes, _) = manager.follow_changes(0).await;
+        assert_eq!(
+            changes.iter().map(|c| c.kind).collect::<Vec<_>>(),
+            vec![AlertChangeKind::Fired, AlertChangeKind::Acknowledged, AlertChangeKind::Resolved],
+        );
+        let (missed, _) = manager.follow_changes(2).await;
+        assert_eq!(missed.iter().map(|c| c.id).collect::<Vec<_>>(), vec![3]);
+        assert_eq!(live.try_recv().unwrap().kind, AlertChangeKind::Acknowledged);
+    }
+
     #[test]
     fn test_alert_level_display() {
         assert_eq!(AlertLevel::Info.to_string(), "INFO");
@@ -0,0 +1,437 @@
+// Analytics Mirror for DMPool
+// Replicates shares, blocks, payouts and audit events into SQLite or Postgres for SQL reporting
+
+use crate::audit::{AuditFilter, AuditLogger};
+use crate::blocks::BlockTracker;
+use crate::payout::PayoutEngine;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use p2poolv2_lib::store::Store;
+use serde::Serialize;
+use sqlx::any::AnyPoolOptions;
+use sqlx::{AnyPool, Row};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::info;
+
+/// Shares are read from the store in windows of this many seconds
+const SHARE_CHUNK_SECS: u64 = 3600;
+
+/// Each sync re-reads this much before the share cursor, for shares whose
+/// `ntime` lags the time they were stored
+const SHARE_OVERLAP_SECS: u64 = 600;
+
+/// Tables of the mirror, created if missing; times are Unix seconds
+pub const SCHEMA: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS shares (
+        n_time BIGINT NOT NULL,
+        user_id BIGINT NOT NULL,
+        btcaddress TEXT,
+        workername TEXT,
+        difficulty BIGINT NOT NULL,
+        job_id TEXT NOT NULL,
+        extranonce2 TEXT NOT NULL,
+        nonce TEXT NOT NULL,
+        PRIMARY KEY (n_time, user_id, job_id, extranonce2, nonce)
+    )",
+    "CREATE INDEX IF NOT EXISTS shares_btcaddress ON shares (btcaddress, n_time)",
+    "CREATE TABLE IF NOT EXISTS blocks (
+        hash TEXT PRIMARY KEY,
+        height BIGINT NOT NULL,
+        found_at BIGINT NOT NULL,
+        finder_address TEXT,
+        reward_sats BIGINT NOT NULL,
+        confirmations BIGINT NOT NULL,
+        orphaned BOOLEAN NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS block_credits (
+        block_hash TEXT NOT NULL,
+        height BIGINT NOT NULL,
+        address TEXT NOT NULL,
+        amount_sats BIGINT NOT NULL,
+        credited_at BIGINT NOT NULL,
+        PRIMARY KEY (block_hash, address)
+    )",
+    "CREATE TABLE IF NOT EXISTS payout_batches (
+        id TEXT PRIMARY KEY,
+        created_at BIGINT NOT NULL,
+        updated_at BIGINT NOT NULL,
+        status TEXT NOT NULL,
+        total_sats BIGINT NOT NULL,
+        txid TEXT,
+        confirmations BIGINT NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS payments (
+        batch_id TEXT NOT NULL,
+        address TEXT NOT NULL,
+        amount_sats BIGINT NOT NULL,
+        PRIMARY KEY (batch_id, address)
+    )",
+    "CREATE TABLE IF NOT EXISTS audit_events (
+        id TEXT PRIMARY KEY,
+        occurred_at BIGINT NOT NULL,
+        username TEXT NOT NULL,
+        action TEXT NOT NULL,
+        resource TEXT NOT NULL,
+        ip_address TEXT NOT NULL,
+        success BOOLEAN NOT NULL,
+        error TEXT,
+        request_id TEXT,
+        details TEXT NOT NULL
+    )",
+    "CREATE INDEX IF NOT EXISTS audit_events_occurred_at ON audit_events (occurred_at)",
+    "CREATE TABLE IF NOT EXISTS mirror_state (
+        source TEXT PRIMARY KEY,
+        cursor BIGINT NOT NULL,
+        synced_at BIGINT NOT NULL
+    )",
+];
+
+/// Rows written by one sync or backfill
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SyncReport {
+    pub shares: u64,
+    pub blocks: u64,
+    pub block_credits: u64,
+    pub payout_batches: u64,
+    pub audit_events: u64,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+}
+
+/// Copies pool data into a SQL database
+///
+/// Rows are keyed so that writing them again is a no-op, except for blocks
+/// and payout batches, whose confirmations and status are updated.
+pub struct AnalyticsMirror {
+    pool: AnyPool,
+    store: Option<Arc<Store>>,
+    blocks: Option<Arc<BlockTracker>>,
+    payouts: Option<Arc<PayoutEngine>>,
+    audit: Option<Arc<AuditLogger>>,
+    /// Held while syncing, so a backfill and the periodic sync can't overlap
+    syncing: Mutex<()>,
+}
+
+impl AnalyticsMirror {
+    /// Connect to a `sqlite:` or `postgres:` database URL
+    pub async fn connect(url: &str) -> Result<Self> {
+        sqlx::any::install_default_drivers();
+        let backend = match url.split(':').next().unwrap_or_default() {
+            "sqlite" => "sqlite",
+            "postgres" | "postgresql" => "postgres",
+            other => return Err(anyhow::anyhow!("Unsupported analytics database {:?}: use sqlite: or postgres:", other)),
+        };
+        // SQLite takes one writer at a time, and every connection to an
+        // in-memory database is a separate database
+        let max_connections = if backend == "sqlite" { 1 } else { 4 };
+        let pool = AnyPoolOptions::new()
+            .max_connections(max_connections)
+            .connect(url)
+            .await
+            .with_context(|| format!("Failed to connect to {} analytics database", backend))?;
+        Ok(Self {
+            pool,
+            store: None,
+            blocks: None,
+            payouts: None,
+            audit: None,
+            syncing: Mutex::new(()),
+        })
+    }
+
+    pub fn with_store(mut self, store: Arc<Store>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    pub fn with_blocks(mut self, blocks: Arc<BlockTracker>) -> Self {
+        self.blocks = Some(blocks);
+        self
+    }
+
+    pub fn with_payouts(mut self, payouts: Arc<PayoutEngine>) -> Self {
+        self.payouts = Some(payouts);
+        self
+    }
+
+    pub fn with_audit(mut self, audit: Arc<AuditLogger>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    pub fn pool(&self) -> &AnyPool {
+        &self.pool
+    }
+
+    /// Create the tables and indexes that don't exist yet
+    pub async fn apply_schema(&self) -> Result<()> {
+        for statement in SCHEMA {
+            sqlx::query(statement).execute(&self.pool).await
+                .with_context(|| format!("Failed to apply schema: {}", statement))?;
+        }
+        Ok(())
+    }
+
+    /// Copy everything new since the last sync
+    pub async fn sync(&self) -> Result<SyncReport> {
+        self.run(None).await
+    }
+
+    /// Copy shares and audit events from `since` (Unix seconds) onwards,
+    /// regardless of what was synced before
+    pub async fn backfill(&self, since: u64) -> Result<SyncReport> {
+        self.run(Some(since)).await
+    }
+
+    async fn run(&self, since: Option<u64>) -> Result<SyncReport> {
+        let _syncing = self.syncing.try_lock()
+            .map_err(|_| anyhow::anyhow!("An analytics sync is already running"))?;
+        self.apply_schema().await?;
+
+        let started_at = Utc::now();
+        let mut report = SyncReport { started_at, ..Default::default() };
+        if let Some(store) = &self.store {
+            report.shares = self.sync_shares(store, since).await?;
+        }
+        if let Some(blocks) = &self.blocks {
+            report.blocks = self.sync_blocks(blocks).await?;
+        }
+        if let Some(payouts) = &self.payouts {
+            let (credits, batches) = self.sync_payouts(payouts).await?;
+            report.block_credits = credits;
+            report.payout_batches = batches;
+        }
+        if let Some(audit) = &self.audit {
+            report.audit_events = self.sync_audit(audit, since).await?;
+        }
+        report.duration_ms = (Utc::now() - started_at).num_milliseconds().max(0) as u64;
+        info!("Analytics mirror synced: {} shares, {} blocks, {} credits, {} payout batches, {} audit events",
+            report.shares, report.blocks, report.block_credits, report.payout_batches, report.audit_events);
+        Ok(report)
+    }
+
+    async fn cursor(&self, source: &str) -> Result<Option<u64>> {
+        let row = sqlx::query("SELECT cursor FROM mirror_state WHERE source = $1")
+            .bind(source)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|row| row.get::<i64, _>(0) as u64))
+    }
+
+    async fn set_cursor(&self, source: &str, cursor: u64) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO mirror_state (source, cursor, synced_at) VALUES ($1, $2, $3)
+             ON CONFLICT (source) DO UPDATE SET cursor = excluded.cursor, synced_at = excluded.synced_at",
+        )
+        .bind(source)
+        .bind(cursor as i64)
+        .bind(Utc::now().timestamp())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn sync_shares(&self, store: &Arc<Store>, since: Option<u64>) -> Result<u64> {
+        let now = Utc::now().timestamp() as u64;
+        let mut start = match since {
+            Some(since) => since,
+            None => self.cursor("shares").await?.unwrap_or(now).saturating_sub(SHARE_OVERLAP_SECS),
+        };
+        let mut written = 0;
+        while start <= now {
+            let end = (start + SHARE_CHUNK_SECS).min(now);
+            let store = store.clone();
+            let shares = tokio::task::spawn_blocking(move || {
+                store.get_pplns_shares_filtered(None, Some(start), Some(end))
+            })
+            .await?;
+
+            let mut tx = self.pool.begin().await?;
+            for share in &shares {
+                written += sqlx::query(
+                    "INSERT INTO shares (n_time, user_id, btcaddress, workername, difficulty, job_id, extranonce2, nonce)
+                     VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT DO NOTHING",
+                )
+                .bind(share.n_time as i64)
+                .bind(share.user_id as i64)
+                .bind(share.btcaddress.clone())
+                .bind(share.workername.clone())
+                .bind(share.difficulty as i64)
+                .bind(&share.job_id)
+                .bind(&share.extranonce2)
+                .bind(&share.nonce)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+            }
+            tx.commit().await?;
+            self.set_cursor("shares", end).await?;
+            if end == now {
+                break;
+            }
+            start = end + 1;
+        }
+        Ok(written)
+    }
+
+    async fn sync_blocks(&self, blocks: &BlockTracker) -> Result<u64> {
+        let blocks = blocks.blocks().await;
+        let mut tx = self.pool.begin().await?;
+        for block in &blocks {
+            sqlx::query(
+                "INSERT INTO blocks (hash, height, found_at, finder_address, reward_sats, confirmations, orphaned)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7)
+                 ON CONFLICT (hash) DO UPDATE SET confirmations = excluded.confirmations, orphaned = excluded.orphaned",
+            )
+            .bind(&block.hash)
+            .bind(block.height as i64)
+            .bind(block.timestamp.timestamp())
+            .bind(block.finder_address.clone())
+            .bind(block.reward_sats as i64)
+            .bind(block.confirmations)
+            .bind(block.orphaned)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(blocks.len() as u64)
+    }
+
+    /// Credited block rewards and payout batches; returns how many of each were written
+    async fn sync_payouts(&self, payouts: &PayoutEngine) -> Result<(u64, u64)> {
+        let credits = payouts.credited_blocks().await;
+        let batches = payouts.batches().await;
+        let mut tx = self.pool.begin().await?;
+        let mut credited = 0;
+        for credit in &credits {
+            for (address, amount) in &credit.credits {
+                credited += sqlx::query(
+                    "INSERT INTO block_credits (block_hash, height, address, amount_sats, credited_at)
+                     VALUES ($1, $2, $3, $4, $5) ON CONFLICT DO NOTHING",
+                )
+                .bind(&credit.hash)
+                .bind(credit.height as i64)
+                .bind(address)
+                .bind(*amount as i64)
+                .bind(credit.credited_at.timestamp())
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+            }
+        }
+        for batch in &batches {
+            let status = serde_json::to_value(&batch.status)?.as_str().unwrap_or_default().to_string();
+            sqlx::query(
+                "INSERT INTO payout_batches (id, created_at, updated_at, status, total_sats, txid, confirmations)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7)
+                 ON CONFLICT (id) DO UPDATE SET updated_at = excluded.updated_at, status = excluded.status,
+                     txid = excluded.txid, confirmations = excluded.confirmations",
+            )
+            .bind(&batch.id)
+            .bind(batch.created_at.timestamp())
+            .bind(batch.updated_at.timestamp())
+            .bind(status)
+            .bind(batch.total_sats as i64)
+            .bind(batch.txid.clone())
+            .bind(batch.confirmations)
+            .execute(&mut *tx)
+            .await?;
+            for payment in &batch.payments {
+                sqlx::query(
+                    "INSERT INTO payments (batch_id, address, amount_sats) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+                )
+                .bind(&batch.id)
+                .bind(&payment.address)
+                .bind(payment.amount_sats as i64)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+        tx.commit().await?;
+        Ok((credited, batches.len() as u64))
+    }
+
+    async fn sync_audit(&self, audit: &AuditLogger, since: Option<u64>) -> Result<u64> {
+        let start = match since {
+            Some(since) => Some(since),
+            None => self.cursor("audit").await?,
+        };
+        let entries = audit.query(AuditFilter {
+            start_time: start.map(|s| s as i64),
+            limit: None,
+            ..Default::default()
+        }).await;
+
+        let mut tx = self.pool.begin().await?;
+        let mut written = 0;
+        for entry in &entries {
+            written += sqlx::query(
+                "INSERT INTO audit_events (id, occurred_at, username, action, resource, ip_address, success, error, request_id, details)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) ON CONFLICT DO NOTHING",
+            )
+            .bind(&entry.id)
+            .bind(entry.timestamp.timestamp())
+            .bind(&entry.username)
+            .bind(&entry.action)
+            .bind(&entry.resource)
+            .bind(&entry.ip_address)
+            .bind(entry.success)
+            .bind(entry.error.clone())
+            .bind(entry.request_id.clone())
+            .bind(entry.details.to_string())
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        tx.commit().await?;
+        // Entries of the cursor's second are read again and skipped by their ID
+        if let Some(latest) = entries.iter().map(|e| e.timestamp.timestamp()).max() {
+            self.set_cursor("audit", latest as u64).await?;
+        }
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payout::PayoutConfig;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_mirror_sync_is_incremental() {
+        let dir = TempDir::new().unwrap();
+        let audit = Arc::new(AuditLogger::new(100, None));
+        for action in ["login", "config_update"] {
+            audit.entry("admin".to_string(), action.to_string(), "/api/v1/config".to_string(), "127.0.0.1".to_string())
+                .details(serde_json::json!({"port": 3333}))
+                .log()
+                .await;
+        }
+        let payouts = Arc::new(PayoutEngine::new(dir.path().join("payouts.json"), PayoutConfig::default()));
+        let blocks = Arc::new(BlockTracker::new(dir.path().join("blocks.json"), "dmpool"));
+
+        let mirror = AnalyticsMirror::connect("sqlite::memory:").await.unwrap()
+            .with_audit(audit.clone())
+            .with_payouts(payouts)
+            .with_blocks(blocks);
+        let report = mirror.sync().await.unwrap();
+        assert_eq!((report.audit_events, report.blocks, report.payout_batches), (2, 0, 0));
+
+        // Only the new entry is written the second time
+        audit.entry("admin".to_string(), "ban_worker".to_string(), "worker:bc1q".to_string(), "127.0.0.1".to_string())
+            .success(false)
+            .log()
+            .await;
+        assert_eq!(mirror.sync().await.unwrap().audit_events, 1);
+        assert_eq!(mirror.backfill(0).await.unwrap().audit_events, 0);
+
+        let row = sqlx::query("SELECT COUNT(*), SUM(CASE WHEN success THEN 0 ELSE 1 END) FROM audit_events")
+            .fetch_one(mirror.pool())
+            .await
+            .unwrap();
+        assert_eq!((row.get::<i64, _>(0), row.get::<i64, _>(1)), (3, 1));
+        assert!(AnalyticsMirror::connect("mysql://localhost/pool").await.is_err());
+    }
+}
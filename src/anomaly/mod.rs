@@ -0,0 +1,181 @@
+// Hashrate Anomaly Detection for DMPool
+// EWMA baselines of pool hashrate and connections, flagging sudden drops and spikes
+
+use serde::Serialize;
+use std::sync::Mutex;
+
+/// Weight of each new sample in the baselines
+pub const DEFAULT_ALPHA: f64 = 0.1;
+/// Samples needed before deviations are reported
+pub const DEFAULT_WARMUP_SAMPLES: u64 = 12;
+/// Hashrate drops this large are an outage however many miners stay connected
+const OUTAGE_DROP_RATIO: f64 = 0.9;
+/// Deviation is never measured against less than this fraction of the baseline,
+/// so a very steady pool doesn't alert on noise
+const MIN_STDDEV_RATIO: f64 = 0.01;
+
+/// Exponentially weighted mean and variance of a series
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct Ewma {
+    pub mean: f64,
+    pub variance: f64,
+    pub samples: u64,
+}
+
+impl Ewma {
+    pub fn update(&mut self, value: f64, alpha: f64) {
+        if self.samples == 0 {
+            self.mean = value;
+        } else {
+            let diff = value - self.mean;
+            let increment = alpha * diff;
+            self.mean += increment;
+            self.variance = (1.0 - alpha) * (self.variance + diff * increment);
+        }
+        self.samples += 1;
+    }
+
+    pub fn stddev(&self) -> f64 {
+        self.variance.max(0.0).sqrt()
+    }
+}
+
+/// Likely reason for a hashrate drop, judged by how connections changed with it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DropCause {
+    /// Connections held up while hashrate fell, as when one large miner leaves
+    MinerLeft,
+    /// Connections fell with the hashrate, or shares stopped almost entirely
+    StratumOutage,
+}
+
+/// How the latest sample compares with the baselines before it
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AnomalyReading {
+    pub hashrate_ths: f64,
+    pub baseline_ths: f64,
+    pub stddev_ths: f64,
+    /// Standard deviations from the baseline; negative for drops
+    pub sigma: f64,
+    pub connections: u64,
+    pub baseline_connections: f64,
+    /// Set for drops only
+    pub cause: Option<DropCause>,
+}
+
+/// Pool hashrate and connection baselines, updated with every hashrate sample
+pub struct AnomalyDetector {
+    alpha: f64,
+    warmup_samples: u64,
+    state: Mutex<DetectorState>,
+}
+
+#[derive(Default)]
+struct DetectorState {
+    hashrate: Ewma,
+    connections: Ewma,
+    latest: Option<AnomalyReading>,
+}
+
+impl Default for AnomalyDetector {
+    fn default() -> Self {
+        Self::new(DEFAULT_ALPHA, DEFAULT_WARMUP_SAMPLES)
+    }
+}
+
+impl AnomalyDetector {
+    pub fn new(alpha: f64, warmup_samples: u64) -> Self {
+        Self {
+            alpha: alpha.clamp(0.001, 1.0),
+            warmup_samples,
+            state: Mutex::new(DetectorState::default()),
+        }
+    }
+
+    /// Compare a sample with the baselines, then fold it into them
+    ///
+    /// Returns nothing until the baselines have seen the warm-up samples.
+    pub fn observe(&self, hashrate_ths: f64, connections: u64) -> Option<AnomalyReading> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let reading = (state.hashrate.samples >= self.warmup_samples).then(|| {
+            let baseline = state.hashrate.mean;
+            let stddev = state.hashrate.stddev().max(baseline.abs() * MIN_STDDEV_RATIO);
+            let sigma = if stddev > 0.0 { (hashrate_ths - baseline) / stddev } else { 0.0 };
+            let baseline_connections = state.connections.mean;
+            let cause = (hashrate_ths < baseline).then(|| {
+                drop_cause(baseline, hashrate_ths, baseline_connections, connections as f64)
+            });
+            AnomalyReading {
+                hashrate_ths,
+                baseline_ths: baseline,
+                stddev_ths: stddev,
+                sigma,
+                connections,
+                baseline_connections,
+                cause,
+            }
+        });
+        state.hashrate.update(hashrate_ths, self.alpha);
+        state.connections.update(connections as f64, self.alpha);
+        state.latest = reading.clone();
+        reading
+    }
+
+    /// Reading of the most recent sample
+    pub fn latest(&self) -> Option<AnomalyReading> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).latest.clone()
+    }
+}
+
+/// A broken stratum server loses connections along with hashrate, while a
+/// departing large miner takes far more hashrate than connections with it
+fn drop_cause(baseline: f64, hashrate: f64, baseline_connections: f64, connections: f64) -> DropCause {
+    let hashrate_drop = if baseline > 0.0 { (baseline - hashrate) / baseline } else { 0.0 };
+    let connection_drop = if baseline_connections > 0.0 {
+        ((baseline_connections - connections) / baseline_connections).max(0.0)
+    } else {
+        0.0
+    };
+    if hashrate_drop >= OUTAGE_DROP_RATIO || connections == 0.0 || connection_drop >= hashrate_drop * 0.5 {
+        DropCause::StratumOutage
+    } else {
+        DropCause::MinerLeft
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drops_are_told_apart() {
+        let warm = || {
+            let detector = AnomalyDetector::new(0.1, 12);
+            for i in 0..30 {
+                let noise = if i % 2 == 0 { 2.0 } else { -2.0 };
+                assert_eq!(detector.observe(100.0 + noise, 50).is_some(), i >= 12);
+            }
+            detector
+        };
+
+        let steady = warm().observe(101.0, 50).unwrap();
+        assert!(steady.sigma.abs() < 1.0);
+
+        // A 40% drop while 49 of 50 connections stay: one big miner left
+        let detector = warm();
+        let left = detector.observe(60.0, 49).unwrap();
+        assert!(left.sigma < -10.0);
+        assert_eq!(left.cause, Some(DropCause::MinerLeft));
+        assert_eq!(detector.latest(), Some(left));
+
+        // The same drop with most connections gone
+        let outage = warm().observe(60.0, 10).unwrap();
+        assert_eq!(outage.cause, Some(DropCause::StratumOutage));
+        // Connections held but shares stopped
+        assert_eq!(warm().observe(2.0, 50).unwrap().cause, Some(DropCause::StratumOutage));
+
+        let spike = warm().observe(150.0, 50).unwrap();
+        assert!(spike.sigma > 10.0 && spike.cause.is_none());
+    }
+}
@@ -0,0 +1,282 @@
+// Admin UI Assets for DMPool
+// The static/admin directory embedded in the binary, served with ETags, cache headers and compression
+
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::Response,
+};
+use rust_embed::RustEmbed;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::{Arc, LazyLock, RwLock};
+
+/// Served for `/` and for paths without an extension, which the UI routes itself
+pub const INDEX: &str = "index.html";
+
+/// Files smaller than this are not worth compressing
+const MIN_COMPRESS_BYTES: usize = 256;
+
+#[derive(RustEmbed)]
+#[folder = "static/admin/"]
+struct AdminFiles;
+
+/// Compressed variants built on first request, by path
+static ASSETS: LazyLock<RwLock<HashMap<String, Arc<Asset>>>> = LazyLock::new(Default::default);
+
+/// Content encoding of a response body
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+}
+
+/// Preferred encoding of `available` in an `Accept-Encoding` header, by
+/// quality; brotli wins a tie
+pub fn negotiate(accept_encoding: &str, available: &[Encoding]) -> Option<Encoding> {
+    let mut qualities: HashMap<&str, f32> = HashMap::new();
+    for item in accept_encoding.split(',') {
+        let mut parts = item.split(';');
+        let coding = parts.next().unwrap_or("").trim();
+        let quality = parts
+            .find_map(|p| p.trim().strip_prefix("q="))
+            .and_then(|q| q.parse().ok())
+            .unwrap_or(1.0);
+        qualities.insert(coding, quality);
+    }
+    let quality = |coding: &str| qualities.get(coding).or(qualities.get("*")).copied().unwrap_or(0.0);
+    available.iter()
+        .copied()
+        .filter(|e| quality(e.as_str()) > 0.0)
+        .max_by(|a, b| quality(a.as_str()).total_cmp(&quality(b.as_str())).then(b.as_str().cmp(a.as_str())))
+}
+
+/// Content type of a file by its extension
+pub fn mime_type(path: &str) -> &'static str {
+    match path.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase()).as_deref() {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js" | "mjs") => "text/javascript; charset=utf-8",
+        Some("json" | "map") => "application/json",
+        Some("webmanifest") => "application/manifest+json",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("ico") => "image/x-icon",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        Some("wasm") => "application/wasm",
+        _ => "application/octet-stream",
+    }
+}
+
+fn is_compressible(mime: &str) -> bool {
+    mime.starts_with("text/")
+        || mime.starts_with("application/json")
+        || mime.starts_with("application/manifest+json")
+        || mime == "image/svg+xml"
+        || mime == "application/wasm"
+}
+
+/// Whether a file name carries a content hash, like `app.3f9a1c2b.js`
+///
+/// Such files never change under the same name and are cached for a year;
+/// everything else is revalidated with its ETag on every use.
+pub fn is_fingerprinted(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let mut parts: Vec<&str> = name.split('.').collect();
+    parts.pop();
+    parts.iter().skip(1).any(|part| part.len() >= 8 && part.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+struct Asset {
+    data: Cow<'static, [u8]>,
+    mime: &'static str,
+    /// SHA-256 prefix of the uncompressed file
+    hash: String,
+    brotli: Option<Vec<u8>>,
+    gzip: Option<Vec<u8>>,
+}
+
+impl Asset {
+    fn load(path: &str) -> Option<Self> {
+        let file = AdminFiles::get(path)?;
+        let mime = mime_type(path);
+        let hash = file.metadata.sha256_hash()[..8].iter().map(|b| format!("{:02x}", b)).collect();
+        let (brotli, gzip) = if is_compressible(mime) && file.data.len() >= MIN_COMPRESS_BYTES {
+            let smaller = |compressed: std::io::Result<Vec<u8>>| compressed.ok().filter(|c| c.len() < file.data.len());
+            (smaller(compress_brotli(&file.data)), smaller(compress_gzip(&file.data)))
+        } else {
+            (None, None)
+        };
+        Some(Self { data: file.data, mime, hash, brotli, gzip })
+    }
+
+    fn encodings(&self) -> Vec<Encoding> {
+        let mut encodings = Vec::new();
+        if self.brotli.is_some() {
+            encodings.push(Encoding::Brotli);
+        }
+        if self.gzip.is_some() {
+            encodings.push(Encoding::Gzip);
+        }
+        encodings
+    }
+
+    fn body(&self, encoding: Option<Encoding>) -> &[u8] {
+        match (encoding, &self.brotli, &self.gzip) {
+            (Some(Encoding::Brotli), Some(brotli), _) => brotli,
+            (Some(Encoding::Gzip), _, Some(gzip)) => gzip,
+            _ => &self.data,
+        }
+    }
+}
+
+fn compress_gzip(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+fn compress_brotli(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 11, 22);
+    encoder.write_all(data)?;
+    encoder.flush()?;
+    Ok(encoder.into_inner())
+}
+
+fn cache_poisoned() -> anyhow::Error {
+    anyhow::anyhow!("Admin asset cache is poisoned")
+}
+
+/// Asset at `path`, or `None` if there is no such file
+fn asset(path: &str) -> anyhow::Result<Option<Arc<Asset>>> {
+    if let Some(asset) = ASSETS.read().map_err(|_| cache_poisoned())?.get(path) {
+        return Ok(Some(asset.clone()));
+    }
+    let Some(asset) = Asset::load(path) else {
+        return Ok(None);
+    };
+    let asset = Arc::new(asset);
+    ASSETS.write().map_err(|_| cache_poisoned())?.insert(path.to_string(), asset.clone());
+    Ok(Some(asset))
+}
+
+/// Every embedded file
+pub fn files() -> Vec<String> {
+    AdminFiles::iter().map(|name| name.into_owned()).collect()
+}
+
+/// Response for a request of `path` under the admin UI, or `None` if there is no such file
+///
+/// Bodies are compressed as the `Accept-Encoding` header allows, and a matching
+/// `If-None-Match` gets `304 Not Modified`.
+pub fn serve(path: &str, headers: &HeaderMap) -> Option<Response> {
+    let path = path.trim_start_matches('/');
+    let path = if path.is_empty() { INDEX } else { path };
+    let found = match asset(path) {
+        // Client-side routes of the UI
+        Ok(None) if !path.rsplit('/').next().unwrap_or(path).contains('.') => asset(INDEX),
+        found => found,
+    };
+    let asset = match found {
+        Ok(Some(asset)) => asset,
+        Ok(None) => return None,
+        Err(e) => {
+            tracing::error!("Failed to serve admin asset {}: {:#}", path, e);
+            return Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::empty())
+                .ok();
+        }
+    };
+
+    let accept = headers.get(header::ACCEPT_ENCODING).and_then(|v| v.to_str().ok()).unwrap_or("");
+    let encodings = asset.encodings();
+    let encoding = negotiate(accept, &encodings);
+    let body = asset.body(encoding);
+    let etag = match encoding {
+        Some(encoding) => format!("\"{}-{}\"", asset.hash, encoding.as_str()),
+        None => format!("\"{}\"", asset.hash),
+    };
+    let cache_control = if is_fingerprinted(path) {
+        "public, max-age=31536000, immutable"
+    } else {
+        "no-cache"
+    };
+    let not_modified = headers.get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|tags| tags.split(',').any(|tag| {
+            let tag = tag.trim();
+            tag == "*" || tag.trim_start_matches("W/") == etag
+        }));
+
+    let mut response = Response::builder()
+        .header(header::ETAG, &etag)
+        .header(header::CACHE_CONTROL, cache_control);
+    if !encodings.is_empty() {
+        response = response.header(header::VARY, "Accept-Encoding");
+    }
+    let response = if not_modified {
+        response.status(StatusCode::NOT_MODIFIED).body(Body::empty())
+    } else {
+        if let Some(encoding) = encoding {
+            response = response.header(header::CONTENT_ENCODING, HeaderValue::from_static(encoding.as_str()));
+        }
+        response
+            .header(header::CONTENT_TYPE, asset.mime)
+            .body(Body::from(body.to_vec()))
+    };
+    response.ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[tokio::test]
+    async fn test_serves_compressed_cached_assets() {
+        let both = [Encoding::Brotli, Encoding::Gzip];
+        assert_eq!(negotiate("gzip, deflate, br", &both), Some(Encoding::Brotli));
+        assert_eq!(negotiate("gzip, deflate, br", &both[1..]), Some(Encoding::Gzip));
+        assert_eq!(negotiate("br;q=0.5, gzip", &both), Some(Encoding::Gzip));
+        assert_eq!(negotiate("*;q=0.1, br;q=0", &both), Some(Encoding::Gzip));
+        assert_eq!(negotiate("identity", &both), None);
+        assert!(is_fingerprinted("assets/app.3f9a1c2b.js"));
+        assert!(!is_fingerprinted("index.html") && !is_fingerprinted("deadbeefcafe.js"));
+        assert_eq!(mime_type("assets/App.CSS"), "text/css; charset=utf-8");
+        assert!(files().contains(&INDEX.to_string()));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT_ENCODING, HeaderValue::from_static("gzip"));
+        let response = serve("/", &headers).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-cache");
+        let etag = response.headers()[header::ETAG].clone();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let mut html = Vec::new();
+        flate2::read::GzDecoder::new(&body[..]).read_to_end(&mut html).unwrap();
+        assert_eq!(html, AdminFiles::get(INDEX).unwrap().data.as_ref());
+
+        headers.insert(header::IF_NONE_MATCH, etag);
+        assert_eq!(serve("/workers/abc", &headers).unwrap().status(), StatusCode::NOT_MODIFIED);
+        let plain = serve("/index.html", &HeaderMap::new()).unwrap();
+        assert!(plain.headers().get(header::CONTENT_ENCODING).is_none());
+        assert!(serve("/missing.js", &headers).is_none());
+    }
+}
@@ -0,0 +1,207 @@
+// Audit Search Index for DMPool
+// Inverted index over the in-memory audit entries, so searches only touch matching ones
+
+use super::{AuditFilter, AuditLog};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Words shorter than this aren't indexed
+const MIN_WORD_LEN: usize = 2;
+/// Trimmed entries tolerated in the posting lists before they are dropped
+const MIN_COMPACTION_LAG: u64 = 1024;
+
+/// Lowercase words of `text`, split on anything but letters and digits
+pub(super) fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= MIN_WORD_LEN)
+        .map(str::to_lowercase)
+}
+
+/// Keys and string values of a JSON document
+fn json_text(value: &serde_json::Value, out: &mut Vec<String>) {
+    match value {
+        serde_json::Value::String(s) => out.push(s.clone()),
+        serde_json::Value::Number(n) => out.push(n.to_string()),
+        serde_json::Value::Array(items) => items.iter().for_each(|item| json_text(item, out)),
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                out.push(key.clone());
+                json_text(value, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Every word an entry can be found by
+fn entry_words(entry: &AuditLog) -> BTreeSet<String> {
+    let mut text = vec![
+        entry.username.clone(),
+        entry.action.clone(),
+        entry.resource.clone(),
+        entry.ip_address.clone(),
+    ];
+    text.extend(entry.error.clone());
+    text.extend(entry.request_id.clone());
+    json_text(&entry.details, &mut text);
+    text.iter().flat_map(|t| words(t)).collect()
+}
+
+/// Numbers in both ascending lists
+fn intersect(a: &[u64], b: &[u64]) -> Vec<u64> {
+    let (mut i, mut j, mut out) = (0, 0, Vec::new());
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                out.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Posting lists of the in-memory entries, which are numbered in the order
+/// they were added; entry `n` sits at position `n - first` of the log
+#[derive(Debug, Default)]
+pub(super) struct AuditIndex {
+    /// Number of the entry at position 0
+    first: u64,
+    /// Number the next entry gets
+    next: u64,
+    /// `first` when trimmed numbers were last dropped from the lists
+    compacted: u64,
+    words: BTreeMap<String, Vec<u64>>,
+    users: HashMap<String, Vec<u64>>,
+    ips: HashMap<String, Vec<u64>>,
+    actions: HashMap<String, Vec<u64>>,
+}
+
+impl AuditIndex {
+    pub(super) fn build(entries: &[AuditLog]) -> Self {
+        let mut index = Self::default();
+        entries.iter().for_each(|entry| index.push(entry));
+        index
+    }
+
+    pub(super) fn push(&mut self, entry: &AuditLog) {
+        let n = self.next;
+        self.next += 1;
+        for word in entry_words(entry) {
+            self.words.entry(word).or_default().push(n);
+        }
+        self.users.entry(entry.username.clone()).or_default().push(n);
+        self.ips.entry(entry.ip_address.clone()).or_default().push(n);
+        self.actions.entry(entry.action.clone()).or_default().push(n);
+    }
+
+    /// Forget the `count` oldest entries, after they were drained from the log
+    pub(super) fn trim(&mut self, count: usize) {
+        self.first += count as u64;
+        // Dropping numbers from every list is costly, so it waits until the
+        // trimmed numbers are a good share of the lists
+        if self.first - self.compacted < MIN_COMPACTION_LAG.max(self.next - self.first) {
+            return;
+        }
+        let first = self.first;
+        let drop_trimmed = |list: &mut Vec<u64>| {
+            let trimmed = list.partition_point(|n| *n < first);
+            list.drain(..trimmed);
+            !list.is_empty()
+        };
+        self.words.retain(|_, list| drop_trimmed(list));
+        self.users.retain(|_, list| drop_trimmed(list));
+        self.ips.retain(|_, list| drop_trimmed(list));
+        self.actions.retain(|_, list| drop_trimmed(list));
+        self.compacted = first;
+    }
+
+    /// Entries containing a word starting with `prefix`, ascending
+    fn prefixed(&self, prefix: &str) -> Vec<u64> {
+        let mut numbers: Vec<u64> = self.words.range(prefix.to_string()..)
+            .take_while(|(word, _)| word.starts_with(prefix))
+            .flat_map(|(_, list)| list.iter().copied())
+            .collect();
+        numbers.sort_unstable();
+        numbers.dedup();
+        numbers
+    }
+
+    /// Log positions of the entries matching the indexed criteria of `filter`,
+    /// ascending, or `None` if it has none and every entry is a candidate
+    pub(super) fn candidates(&self, filter: &AuditFilter) -> Option<Vec<usize>> {
+        let mut lists = Vec::new();
+        let exact = [
+            (&self.users, &filter.username),
+            (&self.ips, &filter.ip),
+            (&self.actions, &filter.action),
+        ];
+        for (postings, wanted) in exact {
+            if let Some(wanted) = wanted {
+                lists.push(postings.get(wanted).cloned().unwrap_or_default());
+            }
+        }
+        if let Some(text) = &filter.q {
+            lists.extend(words(text).map(|word| self.prefixed(&word)));
+        }
+
+        let mut lists = lists.into_iter();
+        let first_list = lists.next()?;
+        let numbers = lists.fold(first_list, |acc, list| intersect(&acc, &list));
+        Some(numbers.into_iter()
+            .filter(|n| *n >= self.first)
+            .map(|n| (n - self.first) as usize)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use serde_json::json;
+
+    fn entry(username: &str, action: &str, details: serde_json::Value) -> AuditLog {
+        AuditLog {
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: Utc::now(),
+            username: username.to_string(),
+            action: action.to_string(),
+            resource: "/api/v1/workers".to_string(),
+            ip_address: "10.0.0.1".to_string(),
+            details,
+            success: true,
+            error: None,
+            request_id: None,
+            geo: None,
+            chain: None,
+        }
+    }
+
+    #[test]
+    fn test_index_matches_words_and_fields() {
+        let mut index = AuditIndex::build(&[
+            entry("alice", "POST /api/v1/bans", json!({ "target": "bc1qminer", "reason": "Flooding shares" })),
+            entry("bob", "POST /api/v1/bans", json!({ "target": "bc1qother" })),
+            entry("alice", "login", json!({})),
+        ]);
+        let search = |index: &AuditIndex, filter: AuditFilter| index.candidates(&filter);
+
+        assert_eq!(search(&index, AuditFilter { q: Some("flood".into()), ..Default::default() }), Some(vec![0]));
+        assert_eq!(search(&index, AuditFilter { q: Some("BANS bc1q".into()), ..Default::default() }), Some(vec![0, 1]));
+        assert_eq!(
+            search(&index, AuditFilter { q: Some("bans".into()), username: Some("alice".into()), ..Default::default() }),
+            Some(vec![0]),
+        );
+        assert_eq!(search(&index, AuditFilter { ip: Some("10.0.0.2".into()), ..Default::default() }), Some(vec![]));
+        assert_eq!(search(&index, AuditFilter::default()), None);
+
+        // Positions follow the log as old entries are drained
+        index.push(&entry("carol", "login", json!({})));
+        index.trim(2);
+        assert_eq!(search(&index, AuditFilter { action: Some("login".into()), ..Default::default() }), Some(vec![0, 1]));
+        assert_eq!(search(&index, AuditFilter { q: Some("flooding".into()), ..Default::default() }), Some(vec![]));
+    }
+}
@@ -2,16 +2,76 @@
 // Records all admin operations for security and compliance
 // Supports file-based persistence for long-term storage
 
+mod index;
+
+use crate::cron::CronExpr;
+use crate::geoip::{GeoInfo, GeoIp};
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use sha2::{Digest, Sha256};
+use utoipa::IntoParams;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::fs::{File, OpenOptions};
-use tokio::io::{AsyncWriteExt, AsyncReadExt};
-use tokio::sync::RwLock;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{Mutex, RwLock};
+use index::AuditIndex;
 use tracing::{error, info, warn};
 
+/// Body fields containing any of these words are redacted before logging
+const SENSITIVE_KEY_PARTS: &[&str] = &["password", "token", "secret", "code", "key"];
+
+/// Longest request body summary stored in an audit entry
+pub const MAX_BODY_SUMMARY_LEN: usize = 1024;
+
+/// Entries between anchors written to the anchor file
+pub const DEFAULT_ANCHOR_INTERVAL: u64 = 100;
+
+/// Summarize a request body for the audit log
+///
+/// JSON bodies are kept with sensitive fields replaced by `"[REDACTED]"` and
+/// truncated to `MAX_BODY_SUMMARY_LEN`; other bodies are recorded by size only.
+pub fn summarize_body(body: &[u8]) -> serde_json::Value {
+    if body.is_empty() {
+        return serde_json::Value::Null;
+    }
+
+    let mut value = match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(value) => value,
+        Err(_) => return serde_json::json!({ "bytes": body.len() }),
+    };
+    redact(&mut value);
+
+    let text = value.to_string();
+    if text.len() <= MAX_BODY_SUMMARY_LEN {
+        return value;
+    }
+    let mut end = MAX_BODY_SUMMARY_LEN;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    serde_json::Value::String(format!("{}...", &text[..end]))
+}
+
+fn redact(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                let key = key.to_lowercase();
+                if SENSITIVE_KEY_PARTS.iter().any(|part| key.contains(part)) {
+                    *v = serde_json::Value::String("[REDACTED]".to_string());
+                } else {
+                    redact(v);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
 /// Audit log entry
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AuditLog {
@@ -33,23 +93,210 @@ pub struct AuditLog {
     pub success: bool,
     /// Error message if failed
     pub error: Option<String>,
+    /// ID of the API request that made the change; filled in when logged
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Country and network of `ip_address`; filled in when logged, if GeoIP is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geo: Option<GeoInfo>,
+    /// Link to the previous entry; filled in when logged
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain: Option<AuditChain>,
+}
+
+/// Position of an entry in the hash chain of the audit log
+///
+/// Removing or editing an entry breaks the link of the entry after it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditChain {
+    /// Entries logged before this one, plus one
+    pub seq: u64,
+    /// `hash` of the previous entry; `None` for the first chained entry
+    pub prev_hash: Option<String>,
+    /// SHA-256 of this entry (without `chain`), `seq` and `prev_hash`
+    pub hash: String,
+}
+
+impl AuditLog {
+    /// Hash of this entry's contents at position `seq` after `prev_hash`
+    pub fn chain_hash(&self, seq: u64, prev_hash: Option<&str>) -> String {
+        let mut entry = self.clone();
+        entry.chain = None;
+        let json = serde_json::to_string(&entry).unwrap_or_default();
+        let mut hasher = Sha256::new();
+        hasher.update(format!("{}:{}:", seq, prev_hash.unwrap_or("")).as_bytes());
+        hasher.update(json.as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+/// Chain head recorded in the anchor file, which is never rotated or pruned
+///
+/// Anchors catch entries cut from the end of the log, and a chain rewritten
+/// from some entry on, as long as the anchor file itself is intact.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditAnchor {
+    pub seq: u64,
+    pub hash: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Where verification found the chain broken
+#[derive(Clone, Debug, Serialize)]
+pub struct BrokenLink {
+    /// File holding the entry, if the log is persisted
+    pub file: Option<String>,
+    /// Line in `file`, or position in memory, counting from 1
+    pub line: usize,
+    pub entry_id: Option<String>,
+    pub seq: Option<u64>,
+    pub reason: String,
+}
+
+/// Result of checking the audit log's hash chain
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ChainReport {
+    pub valid: bool,
+    /// Entries read, including any after a broken link
+    pub entries: usize,
+    /// Entries from before chaining was introduced
+    pub unchained: usize,
+    pub first_seq: Option<u64>,
+    pub last_seq: Option<u64>,
+    pub anchors_checked: usize,
+    pub broken: Option<BrokenLink>,
+}
+
+/// Check a sequence of entries, oldest first, against each other and `anchors`
+///
+/// History before the first entry may have been pruned by retention, so the
+/// first chained entry can start anywhere; anchors older than it are skipped.
+fn verify_chain(
+    entries: impl Iterator<Item = (Option<String>, usize, Result<AuditLog, String>)>,
+    anchors: &[AuditAnchor],
+) -> ChainReport {
+    let anchored: HashMap<u64, &str> = anchors.iter().map(|a| (a.seq, a.hash.as_str())).collect();
+    let mut report = ChainReport::default();
+    let mut prev: Option<(u64, String)> = None;
+
+    for (file, line, entry) in entries {
+        report.entries += 1;
+        if report.broken.is_some() {
+            continue;
+        }
+        let broken = |entry_id: Option<String>, seq: Option<u64>, reason: String| {
+            Some(BrokenLink { file: file.clone(), line, entry_id, seq, reason })
+        };
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                report.broken = broken(None, None, format!("Unreadable entry: {}", e));
+                continue;
+            }
+        };
+        let Some(chain) = entry.chain.clone() else {
+            if prev.is_some() {
+                report.broken = broken(Some(entry.id), None, "Entry without a hash inside the chain".to_string());
+            } else {
+                report.unchained += 1;
+            }
+            continue;
+        };
+        let id = Some(entry.id.clone());
+        if entry.chain_hash(chain.seq, chain.prev_hash.as_deref()) != chain.hash {
+            report.broken = broken(id, Some(chain.seq), "Entry does not match its hash".to_string());
+            continue;
+        }
+        if let Some((prev_seq, prev_hash)) = &prev {
+            if chain.seq != prev_seq + 1 || chain.prev_hash.as_ref() != Some(prev_hash) {
+                report.broken = broken(id, Some(chain.seq), format!("Entry does not follow entry {}", prev_seq));
+                continue;
+            }
+        } else {
+            report.first_seq = Some(chain.seq);
+        }
+        if let Some(expected) = anchored.get(&chain.seq) {
+            report.anchors_checked += 1;
+            if *expected != chain.hash {
+                report.broken = broken(id, Some(chain.seq), "Entry does not match its anchor".to_string());
+                continue;
+            }
+        }
+        report.last_seq = Some(chain.seq);
+        prev = Some((chain.seq, chain.hash));
+    }
+
+    if report.broken.is_none() {
+        let last = report.last_seq.unwrap_or(0);
+        let missing = anchors.iter()
+            .filter(|a| a.seq > last && report.first_seq.is_none_or(|first| a.seq >= first))
+            .min_by_key(|a| a.seq);
+        if let Some(anchor) = missing {
+            report.broken = Some(BrokenLink {
+                file: None,
+                line: report.entries,
+                entry_id: None,
+                seq: Some(anchor.seq),
+                reason: format!("Anchored entry {} is missing; the log was cut after entry {}", anchor.seq, last),
+            });
+        }
+    }
+    report.valid = report.broken.is_none();
+    report
 }
 
 /// Audit log filter options
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct AuditFilter {
     /// Filter by username
     pub username: Option<String>,
     /// Filter by action
     pub action: Option<String>,
+    /// Filter by client IP
+    pub ip: Option<String>,
+    /// Words every entry must contain, matched as prefixes across the user,
+    /// action, resource, IP, error and details
+    pub q: Option<String>,
+    /// Only successful (`true`) or failed (`false`) actions
+    pub success: Option<bool>,
     /// Filter by resource
     pub resource: Option<String>,
+    /// Filter by API request ID
+    pub request_id: Option<String>,
     /// Start time (Unix timestamp)
     pub start_time: Option<i64>,
     /// End time (Unix timestamp)
     pub end_time: Option<i64>,
     /// Maximum results to return
     pub limit: Option<usize>,
+    /// Matching entries to skip
+    pub offset: Option<usize>,
+    /// `desc` (newest first, the default) or `asc`
+    pub sort_order: Option<String>,
+}
+
+impl AuditFilter {
+    /// Whether `log` meets the criteria the index doesn't cover
+    fn matches(&self, log: &AuditLog) -> bool {
+        let start = self.start_time.map(|start| DateTime::from_timestamp(start, 0).unwrap_or_default());
+        let end = self.end_time.map(|end| DateTime::from_timestamp(end, 0).unwrap_or_else(Utc::now));
+        self.resource.as_ref().is_none_or(|resource| log.resource.contains(resource.as_str()))
+            && self.request_id.as_ref().is_none_or(|id| log.request_id.as_ref() == Some(id))
+            && self.success.is_none_or(|success| log.success == success)
+            && start.is_none_or(|start| log.timestamp >= start)
+            && end.is_none_or(|end| log.timestamp <= end)
+    }
+}
+
+/// Page of audit search results
+#[derive(Clone, Debug, Serialize)]
+pub struct AuditPage {
+    /// Entries matching the filter, across all pages
+    pub total: usize,
+    pub offset: usize,
+    pub limit: Option<usize>,
+    pub entries: Vec<AuditLog>,
 }
 
 impl Default for AuditFilter {
@@ -57,24 +304,108 @@ impl Default for AuditFilter {
         Self {
             username: None,
             action: None,
+            ip: None,
+            q: None,
+            success: None,
             resource: None,
+            request_id: None,
             start_time: None,
             end_time: None,
             limit: Some(100),
+            offset: None,
+            sort_order: None,
         }
     }
 }
 
+/// When the active file is rotated and how long archives are kept
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RotationPolicy {
+    /// Rotate once the active file grows past this size
+    pub max_file_bytes: Option<u64>,
+    /// Rotate once the active file holds this many entries
+    pub max_file_entries: Option<u64>,
+    /// Rotate once the first entry of the active file is this many hours old;
+    /// checked as entries are written
+    pub max_file_age_hours: Option<u64>,
+    /// Also rotate whenever this matches, if anything was written since
+    pub schedule: Option<CronExpr>,
+    /// Gzip rotated archives
+    pub compress: bool,
+    /// Drop entries and archives older than this many days
+    pub retention_days: Option<i64>,
+    /// Delete the oldest archives while all of them together exceed this size
+    pub max_archive_bytes: Option<u64>,
+}
+
+/// Entries written to the active file since it was started
+#[derive(Clone, Debug, Default)]
+struct ActiveFile {
+    entries: u64,
+    /// Timestamp of its first entry
+    since: Option<DateTime<Utc>>,
+}
+
+impl ActiveFile {
+    fn record(&mut self, timestamp: DateTime<Utc>) {
+        self.entries += 1;
+        self.since.get_or_insert(timestamp);
+    }
+}
+
+/// Contents of the active file or an archive, decompressed if gzipped
+async fn read_log_file(path: &Path) -> Result<Vec<u8>> {
+    let content = tokio::fs::read(path).await
+        .with_context(|| format!("Failed to read {:?}", path))?;
+    if path.extension().is_some_and(|ext| ext == "gz") {
+        use std::io::Read;
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(content.as_slice())
+            .read_to_end(&mut decoded)
+            .with_context(|| format!("Failed to decompress {:?}", path))?;
+        return Ok(decoded);
+    }
+    Ok(content)
+}
+
+/// Gzip a rotated archive next to itself and remove the original
+fn compress_archive(path: &Path) -> Result<PathBuf> {
+    let compressed = path.with_extension("jsonl.gz");
+    let mut input = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open {:?}", path))?;
+    let output = std::fs::File::create(&compressed)
+        .with_context(|| format!("Failed to create {:?}", compressed))?;
+    let mut encoder = flate2::write::GzEncoder::new(output, flate2::Compression::default());
+    std::io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?.sync_all()?;
+    std::fs::remove_file(path)
+        .with_context(|| format!("Failed to remove {:?}", path))?;
+    Ok(compressed)
+}
+
 /// Audit log manager with file persistence
 pub struct AuditLogger {
     /// In-memory cache for recent logs
     logs: Arc<RwLock<Vec<AuditLog>>>,
+    /// Search index over `logs`; only changed while `logs` is write-locked
+    index: Arc<RwLock<AuditIndex>>,
     /// Maximum number of logs to keep in memory
     max_logs: usize,
     /// Path to the audit log file (JSONL format)
     log_file: Option<PathBuf>,
     /// Whether to enable file persistence
     persistence_enabled: bool,
+    /// When the active file is rotated and archives are deleted
+    policy: RotationPolicy,
+    /// Entries in the active file; only changed while `chain_head` is held
+    active_file: std::sync::Mutex<ActiveFile>,
+    /// Sequence number and hash of the last entry; holding it serializes
+    /// appends and rotation, so lines never land in a moved file and the
+    /// files stay in chain order
+    chain_head: Arc<Mutex<Option<(u64, String)>>>,
+    /// Write an anchor every this many entries
+    anchor_interval: u64,
+    geoip: Option<Arc<GeoIp>>,
 }
 
 impl AuditLogger {
@@ -83,12 +414,52 @@ impl AuditLogger {
         let persistence_enabled = log_file.is_some();
         Self {
             logs: Arc::new(RwLock::new(Vec::new())),
+            index: Arc::new(RwLock::new(AuditIndex::default())),
             max_logs,
             log_file,
             persistence_enabled,
+            policy: RotationPolicy::default(),
+            active_file: std::sync::Mutex::new(ActiveFile::default()),
+            chain_head: Arc::new(Mutex::new(None)),
+            anchor_interval: DEFAULT_ANCHOR_INTERVAL,
+            geoip: None,
         }
     }
 
+    /// Locate the IP address of every entry with `geoip`
+    pub fn with_geoip(mut self, geoip: Arc<GeoIp>) -> Self {
+        self.geoip = Some(geoip);
+        self
+    }
+
+    /// Anchor the chain every `entries` entries instead of `DEFAULT_ANCHOR_INTERVAL`
+    pub fn with_anchor_interval(mut self, entries: u64) -> Self {
+        self.anchor_interval = entries.max(1);
+        self
+    }
+
+    /// Rotate, compress and delete files as `policy` says
+    pub fn with_rotation_policy(mut self, policy: RotationPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Rotate the active file automatically once it exceeds `bytes`
+    pub fn with_max_file_bytes(mut self, bytes: u64) -> Self {
+        self.policy.max_file_bytes = Some(bytes);
+        self
+    }
+
+    /// Keep entries and rotated archives for `days` days
+    pub fn with_retention_days(mut self, days: i64) -> Self {
+        self.policy.retention_days = Some(days);
+        self
+    }
+
+    pub fn policy(&self) -> &RotationPolicy {
+        &self.policy
+    }
+
     /// Create with default settings and no file persistence
     pub fn default() -> Self {
         Self::new(10000, None)
@@ -115,25 +486,61 @@ impl AuditLogger {
     }
 
     /// Log an action
-    pub async fn log(&self, entry: AuditLog) {
+    pub async fn log(&self, mut entry: AuditLog) {
+        if entry.request_id.is_none() {
+            entry.request_id = crate::logging::current_request_id();
+        }
+        if entry.geo.is_none() {
+            entry.geo = self.geoip.as_ref().and_then(|g| g.lookup_str(&entry.ip_address));
+        }
+
+        let mut head = self.chain_head.lock().await;
+        let seq = head.as_ref().map_or(1, |(seq, _)| seq + 1);
+        let prev_hash = head.as_ref().map(|(_, hash)| hash.clone());
+        let hash = entry.chain_hash(seq, prev_hash.as_deref());
+        entry.chain = Some(AuditChain { seq, prev_hash, hash: hash.clone() });
+
         // Write to file if persistence is enabled
+        let mut appended = true;
         if self.persistence_enabled {
             if let Some(ref log_file) = self.log_file {
                 if let Err(e) = Self::append_to_file(log_file, &entry).await {
                     error!("Failed to write audit log to file: {}", e);
+                    appended = false;
+                } else {
+                    self.active_file().record(entry.timestamp);
+                    let anchored = if seq.is_multiple_of(self.anchor_interval) {
+                        self.append_anchor(seq, &hash).await
+                    } else {
+                        Ok(())
+                    };
+                    if let Err(e) = anchored {
+                        error!("Failed to write audit anchor: {}", e);
+                    }
+                    if let Err(e) = self.rotate_if_needed(log_file, seq, &hash).await {
+                        error!("Failed to rotate audit log file: {}", e);
+                    }
                 }
             }
         }
+        // An entry missing from the file must not leave a gap in the chain
+        if appended {
+            *head = Some((seq, hash));
+        }
+        drop(head);
 
         let mut logs = self.logs.write().await;
+        let mut index = self.index.write().await;
 
         // Add log
+        index.push(&entry);
         logs.push(entry.clone());
 
         // Trim if exceeded max
         if logs.len() > self.max_logs {
             let remove_count = logs.len() - self.max_logs;
             logs.drain(0..remove_count);
+            index.trim(remove_count);
             warn!("Removed {} old audit logs to stay under limit", remove_count);
         }
 
@@ -178,7 +585,84 @@ impl AuditLogger {
         Ok(())
     }
 
-    /// Load audit logs from file on startup
+    /// Anchors are kept next to the active file
+    fn anchor_file(&self) -> Option<PathBuf> {
+        self.log_file.as_ref().map(|f| f.with_file_name("anchors.jsonl"))
+    }
+
+    /// Record the chain head in the anchor file
+    async fn append_anchor(&self, seq: u64, hash: &str) -> Result<()> {
+        let Some(path) = self.anchor_file() else {
+            return Ok(());
+        };
+        let anchor = AuditAnchor { seq, hash: hash.to_string(), timestamp: Utc::now() };
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await
+            .context("Failed to open audit anchor file")?;
+        file.write_all(format!("{}\n", serde_json::to_string(&anchor)?).as_bytes()).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    /// Anchors written so far, oldest first
+    pub async fn anchors(&self) -> Result<Vec<AuditAnchor>> {
+        let Some(path) = self.anchor_file() else {
+            return Ok(Vec::new());
+        };
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = tokio::fs::read_to_string(&path).await
+            .context("Failed to read audit anchor file")?;
+        content.lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| serde_json::from_str(l).context("Invalid audit anchor"))
+            .collect()
+    }
+
+    /// Check the hash chain of every persisted entry (or of the in-memory
+    /// entries without persistence) and the anchors
+    pub async fn verify(&self) -> Result<ChainReport> {
+        let Some(log_file) = self.log_file.as_ref().filter(|_| self.persistence_enabled) else {
+            let logs = self.logs.read().await;
+            let entries = logs.iter().enumerate().map(|(i, e)| (None, i + 1, Ok(e.clone())));
+            return Ok(verify_chain(entries, &[]));
+        };
+
+        // Hold the head so no entry or rotation lands mid-read
+        let _head = self.chain_head.lock().await;
+        let anchors = self.anchors().await?;
+        let mut files = self.archive_files().await?;
+        if log_file.exists() {
+            files.push(log_file.clone());
+        }
+        let mut entries = Vec::new();
+        for path in &files {
+            let name = path.file_name().map(|n| n.to_string_lossy().to_string());
+            let content = read_log_file(path).await?;
+            for (i, line) in content.split(|&b| b == b'\n').enumerate() {
+                if line.is_empty() {
+                    continue;
+                }
+                let entry = serde_json::from_slice::<AuditLog>(line).map_err(|e| e.to_string());
+                entries.push((name.clone(), i + 1, entry));
+            }
+        }
+        let report = verify_chain(entries.into_iter(), &anchors);
+        match &report.broken {
+            Some(link) => warn!("Audit chain broken at {:?} line {}: {}", link.file, link.line, link.reason),
+            None => info!("Audit chain verified ({} entries, {} anchors)", report.entries, report.anchors_checked),
+        }
+        Ok(report)
+    }
+
+    /// Load audit logs from the archives and active file on startup
+    ///
+    /// Entries outside the retention window are skipped and only the newest
+    /// `max_logs` are kept in memory.
     pub async fn load_from_file(&self) -> Result<usize> {
         if !self.persistence_enabled {
             return Ok(0);
@@ -187,41 +671,229 @@ impl AuditLogger {
         let log_file = self.log_file.as_ref()
             .ok_or_else(|| anyhow::anyhow!("No log file configured"))?;
 
-        if !log_file.exists() {
-            return Ok(0);
+        if let Err(e) = self.prune_archives().await {
+            warn!("Failed to prune old audit archives: {}", e);
         }
 
-        let mut file = File::open(log_file).await
-            .context("Failed to open audit log file")?;
+        let mut files = self.archive_files().await?;
+        if log_file.exists() {
+            files.push(log_file.clone());
+        }
+
+        let cutoff = self.policy.retention_days.map(|days| Utc::now() - chrono::Duration::days(days));
+        let mut loaded = Vec::new();
+        let mut last_link = None;
+        let mut active = ActiveFile::default();
+
+        for path in &files {
+            let contents = read_log_file(path).await?;
+
+            for line in contents.split(|&b| b == b'\n') {
+                if line.is_empty() {
+                    continue;
+                }
+
+                let json_str = match std::str::from_utf8(line) {
+                    Ok(s) => s,
+                    Err(_) => {
+                        warn!("Skipping invalid UTF-8 line in {:?}", path);
+                        continue;
+                    }
+                };
+
+                if let Ok(entry) = serde_json::from_str::<AuditLog>(json_str) {
+                    if path == log_file {
+                        active.record(entry.timestamp);
+                    }
+                    if let Some(chain) = &entry.chain {
+                        last_link = Some((chain.seq, chain.hash.clone()));
+                    }
+                    if !cutoff.is_some_and(|c| entry.timestamp <= c) {
+                        loaded.push(entry);
+                    }
+                }
+            }
+        }
 
-        let mut contents = Vec::new();
-        file.read_to_end(&mut contents).await
-            .context("Failed to read audit log file")?;
+        loaded.sort_by_key(|l| l.timestamp);
+        // New entries continue the chain of the files
+        if last_link.is_some() {
+            *self.chain_head.lock().await = last_link;
+        }
+        *self.active_file() = active;
 
         let mut logs = self.logs.write().await;
-        let initial_count = logs.len();
+        let loaded_count = loaded.len();
+        let mut merged = loaded;
+        merged.append(&mut logs);
+        if merged.len() > self.max_logs {
+            let remove_count = merged.len() - self.max_logs;
+            merged.drain(0..remove_count);
+        }
+        *self.index.write().await = AuditIndex::build(&merged);
+        *logs = merged;
 
-        for line in contents.split(|&b| b == b'\n') {
-            if line.is_empty() {
-                continue;
+        info!("Loaded {} audit logs from {} file(s)", loaded_count, files.len());
+
+        Ok(loaded_count)
+    }
+
+    /// Rotated archives next to the active file, oldest first
+    async fn archive_files(&self) -> Result<Vec<PathBuf>> {
+        let log_file = match self.log_file.as_ref() {
+            Some(f) => f,
+            None => return Ok(Vec::new()),
+        };
+        let dir = match log_file.parent() {
+            Some(d) if d.as_os_str().is_empty() => PathBuf::from("."),
+            Some(d) => d.to_path_buf(),
+            None => return Ok(Vec::new()),
+        };
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut archives = Vec::new();
+        let mut entries = tokio::fs::read_dir(&dir).await
+            .context("Failed to read audit log directory")?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with("audit_") && (name.ends_with(".jsonl") || name.ends_with(".jsonl.gz")) {
+                archives.push(entry.path());
+            }
+        }
+        // Archive names embed the rotation time, so name order is time order
+        archives.sort();
+        Ok(archives)
+    }
+
+    /// Delete archives whose newest entry is older than the retention window,
+    /// then the oldest archives while all of them exceed `max_archive_bytes`
+    pub async fn prune_archives(&self) -> Result<usize> {
+        let mut removed = 0;
+        if let Some(days) = self.policy.retention_days {
+            let cutoff = std::time::SystemTime::now()
+                - std::time::Duration::from_secs(days.max(0) as u64 * 86400);
+            for path in self.archive_files().await? {
+                let modified = tokio::fs::metadata(&path).await?.modified()?;
+                if modified < cutoff {
+                    tokio::fs::remove_file(&path).await
+                        .with_context(|| format!("Failed to remove audit archive {:?}", path))?;
+                    removed += 1;
+                }
+            }
+            if removed > 0 {
+                info!("Removed {} audit archive(s) older than {} days", removed, days);
             }
+        }
 
-            let json_str = std::str::from_utf8(line)
-                .context("Invalid UTF-8 in audit log")?;
+        if let Some(max) = self.policy.max_archive_bytes {
+            let mut sizes = Vec::new();
+            for path in self.archive_files().await? {
+                let size = tokio::fs::metadata(&path).await?.len();
+                sizes.push((path, size));
+            }
+            let mut total: u64 = sizes.iter().map(|(_, size)| size).sum();
+            let mut over = 0;
+            for (path, size) in sizes {
+                if total <= max {
+                    break;
+                }
+                tokio::fs::remove_file(&path).await
+                    .with_context(|| format!("Failed to remove audit archive {:?}", path))?;
+                total -= size;
+                over += 1;
+            }
+            if over > 0 {
+                info!("Removed {} audit archive(s) to stay under {} bytes", over, max);
+            }
+            removed += over;
+        }
+        Ok(removed)
+    }
 
-            if let Ok(entry) = serde_json::from_str::<AuditLog>(json_str) {
-                logs.push(entry);
+    /// Which limit of the policy the active file has reached, if any
+    async fn rotation_due(&self, log_file: &Path) -> Result<Option<String>> {
+        let active = self.active_file().clone();
+        if self.policy.max_file_entries.is_some_and(|max| active.entries >= max) {
+            return Ok(Some(format!("{} entries", active.entries)));
+        }
+        let max_age = self.policy.max_file_age_hours.map(|hours| chrono::Duration::hours(hours as i64));
+        if let Some(since) = active.since.filter(|since| max_age.is_some_and(|age| Utc::now() - *since >= age)) {
+            return Ok(Some(format!("first entry from {}", since)));
+        }
+        if let Some(max) = self.policy.max_file_bytes {
+            let size = tokio::fs::metadata(log_file).await?.len();
+            if size >= max {
+                return Ok(Some(format!("{} bytes", size)));
             }
         }
+        Ok(None)
+    }
 
-        let loaded_count = logs.len() - initial_count;
-        info!("Loaded {} audit logs from file", loaded_count);
+    /// Rotate the active file if it has reached a limit of the policy,
+    /// anchoring its last entry `seq`
+    async fn rotate_if_needed(&self, log_file: &PathBuf, seq: u64, hash: &str) -> Result<()> {
+        let Some(reason) = self.rotation_due(log_file).await? else {
+            return Ok(());
+        };
+        if !seq.is_multiple_of(self.anchor_interval) {
+            self.append_anchor(seq, hash).await?;
+        }
+        info!("Audit log reached {}, rotating", reason);
+        self.rotate_file(log_file).await?;
+        self.prune_archives().await?;
+        Ok(())
+    }
 
-        Ok(loaded_count)
+    /// Move the active file to a timestamped archive, compressed if the policy says so
+    async fn rotate_file(&self, log_file: &PathBuf) -> Result<PathBuf> {
+        if !log_file.exists() {
+            return Err(anyhow::anyhow!("Log file does not exist"));
+        }
+
+        // Create archive filename with timestamp, never overwriting an earlier archive
+        let timestamp = Utc::now().format("%Y%m%d_%H%M%S_%3f");
+        let mut archive_path = log_file.with_file_name(format!("audit_{}.jsonl", timestamp));
+        let mut seq = 1;
+        while archive_path.exists() || archive_path.with_extension("jsonl.gz").exists() {
+            archive_path = log_file.with_file_name(format!("audit_{}_{}.jsonl", timestamp, seq));
+            seq += 1;
+        }
+
+        // Move current log to archive
+        tokio::fs::rename(log_file, &archive_path).await
+            .context("Failed to rotate audit log file")?;
+        *self.active_file() = ActiveFile::default();
+
+        if self.policy.compress {
+            let plain = archive_path;
+            archive_path = tokio::task::spawn_blocking(move || compress_archive(&plain)).await??;
+        }
+        info!("Rotated audit log: {:?} -> {:?}", log_file, archive_path);
+
+        Ok(archive_path)
+    }
+
+    /// When the next scheduled rotation is due
+    pub fn next_scheduled_rotation(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.policy.schedule.as_ref()?.next_after(after)
+    }
+
+    /// Rotate on schedule, unless nothing was written since the last rotation
+    pub async fn rotate_scheduled(&self) -> Result<Option<PathBuf>> {
+        let Some(log_file) = self.log_file.as_ref().filter(|_| self.persistence_enabled) else {
+            return Ok(None);
+        };
+        let written = tokio::fs::metadata(log_file).await.is_ok_and(|m| m.len() > 0);
+        if !written {
+            return Ok(None);
+        }
+        self.rotate_logs().await.map(Some)
     }
 
     /// Create a new audit log entry builder
-    pub fn entry(&self, username: String, action: String, resource: String, ip_address: String) -> AuditLogBuilder {
+    pub fn entry(&self, username: String, action: String, resource: String, ip_address: String) -> AuditLogBuilder<'_> {
         AuditLogBuilder {
             username,
             action,
@@ -230,43 +902,41 @@ impl AuditLogger {
             details: serde_json::json!({}),
             success: true,
             error: None,
-            logger: self.logs.clone(),
+            logger: self,
         }
     }
 
     /// Query audit logs with optional filter
     pub async fn query(&self, filter: AuditFilter) -> Vec<AuditLog> {
-        let logs = self.logs.read().await;
-        let mut results = logs.clone();
-
-        // Apply filters
-        if let Some(username) = &filter.username {
-            results.retain(|log| log.username == *username);
-        }
-        if let Some(action) = &filter.action {
-            results.retain(|log| log.action == *action);
-        }
-        if let Some(resource) = &filter.resource {
-            results.retain(|log| log.resource.contains(resource));
-        }
-        if let Some(start) = filter.start_time {
-            let start_dt = DateTime::from_timestamp(start, 0).unwrap_or_default();
-            results.retain(|log| log.timestamp >= start_dt);
-        }
-        if let Some(end) = filter.end_time {
-            let end_dt = DateTime::from_timestamp(end, 0).unwrap_or_else(|| Utc::now());
-            results.retain(|log| log.timestamp <= end_dt);
-        }
+        self.search(filter).await.entries
+    }
 
-        // Reverse to show newest first
-        results.reverse();
+    /// One page of the entries matching `filter`, and how many match in all
+    ///
+    /// The user, IP, action and words are looked up in the index; the other
+    /// criteria are checked on the entries found.
+    pub async fn search(&self, filter: AuditFilter) -> AuditPage {
+        let logs = self.logs.read().await;
+        let candidates = self.index.read().await.candidates(&filter);
+        let mut positions = match candidates {
+            Some(positions) => positions,
+            None => (0..logs.len()).collect(),
+        };
+        positions.retain(|&pos| logs.get(pos).is_some_and(|log| filter.matches(log)));
 
-        // Apply limit
-        if let Some(limit) = filter.limit {
-            results.truncate(limit);
+        // Newest first unless asked otherwise
+        if filter.sort_order.as_deref() != Some("asc") {
+            positions.reverse();
         }
-
-        results
+        let total = positions.len();
+        let offset = filter.offset.unwrap_or(0);
+        let entries = positions.into_iter()
+            .skip(offset)
+            .take(filter.limit.unwrap_or(usize::MAX))
+            .map(|pos| logs[pos].clone())
+            .collect();
+
+        AuditPage { total, offset, limit: filter.limit, entries }
     }
 
     /// Get recent audit logs
@@ -289,11 +959,19 @@ impl AuditLogger {
         let mut logs = self.logs.write().await;
         let original_len = logs.len();
         logs.retain(|log| log.timestamp > cutoff);
+        *self.index.write().await = AuditIndex::build(&logs);
         Ok(original_len - logs.len())
     }
 
     /// Get statistics about audit logs
     pub async fn stats(&self) -> AuditStats {
+        let files = match self.file_stats().await {
+            Ok(files) => files,
+            Err(e) => {
+                warn!("Failed to read audit file sizes: {}", e);
+                None
+            }
+        };
         let logs = self.logs.read().await;
         let mut action_counts: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
         let mut success_count = 0;
@@ -319,7 +997,39 @@ impl AuditLogger {
             top_actions,
             oldest_log: logs.first().map(|l| l.timestamp),
             newest_log: logs.last().map(|l| l.timestamp),
+            policy: self.policy.clone(),
+            files,
+        }
+    }
+
+    /// Counts of the active file; a poisoned lock still holds usable ones
+    fn active_file(&self) -> std::sync::MutexGuard<'_, ActiveFile> {
+        self.active_file.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Sizes of the active file and archives; `None` without persistence
+    async fn file_stats(&self) -> Result<Option<AuditFileStats>> {
+        let Some(log_file) = self.log_file.as_ref().filter(|_| self.persistence_enabled) else {
+            return Ok(None);
+        };
+        let active = self.active_file().clone();
+        let mut stats = AuditFileStats {
+            active_entries: active.entries,
+            active_bytes: tokio::fs::metadata(log_file).await.map(|m| m.len()).unwrap_or(0),
+            active_since: active.since,
+            archives: 0,
+            compressed_archives: 0,
+            archive_bytes: 0,
+            next_rotation: self.next_scheduled_rotation(Utc::now()),
+        };
+        for path in self.archive_files().await? {
+            stats.archives += 1;
+            if path.extension().is_some_and(|ext| ext == "gz") {
+                stats.compressed_archives += 1;
+            }
+            stats.archive_bytes += tokio::fs::metadata(&path).await?.len();
         }
+        Ok(Some(stats))
     }
 
     /// Rotate audit log file (move current to archive and start fresh)
@@ -331,23 +1041,40 @@ impl AuditLogger {
         let log_file = self.log_file.as_ref()
             .ok_or_else(|| anyhow::anyhow!("No log file configured"))?;
 
-        if !log_file.exists() {
-            return Err(anyhow::anyhow!("Log file does not exist"));
+        let head = self.chain_head.lock().await;
+        if let Some((seq, hash)) = head.as_ref() {
+            self.append_anchor(*seq, hash).await?;
+        }
+        let archive_path = self.rotate_file(log_file).await?;
+        if let Err(e) = self.prune_archives().await {
+            warn!("Failed to prune old audit archives: {}", e);
         }
-
-        // Create archive filename with timestamp
-        let timestamp = Utc::now().format("%Y%m%d_%H%M%S");
-        let archive_path = log_file.with_file_name(format!("audit_{}.jsonl", timestamp));
-
-        // Move current log to archive
-        tokio::fs::rename(log_file, &archive_path).await
-            .context("Failed to rotate audit log file")?;
-
-        info!("Rotated audit log: {:?} -> {:?}", log_file, archive_path);
 
         Ok(archive_path)
     }
 
+    /// Finish pending writes and sync the log to disk, for a clean shutdown
+    ///
+    /// The chain head is anchored, so the last entries before the shutdown
+    /// can be verified against the anchor file.
+    pub async fn flush(&self) -> Result<()> {
+        let Some(log_file) = self.log_file.as_ref().filter(|_| self.persistence_enabled) else {
+            return Ok(());
+        };
+        // Appends hold the chain head, so none is half-written once it's taken
+        let head = self.chain_head.lock().await;
+        if let Some((seq, hash)) = head.as_ref() {
+            self.append_anchor(*seq, hash).await?;
+        }
+        for path in std::iter::once(log_file.clone()).chain(self.anchor_file()) {
+            if let Ok(file) = File::open(&path).await {
+                file.sync_all().await
+                    .with_context(|| format!("Failed to sync {}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+
     /// Export audit logs to JSON file
     pub async fn export(&self, output_path: PathBuf) -> Result<usize> {
         let logs = self.logs.read().await;
@@ -383,10 +1110,25 @@ pub struct AuditStats {
     pub top_actions: Vec<(String, usize)>,
     pub oldest_log: Option<DateTime<Utc>>,
     pub newest_log: Option<DateTime<Utc>>,
+    pub policy: RotationPolicy,
+    pub files: Option<AuditFileStats>,
+}
+
+/// Active file and archives on disk
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuditFileStats {
+    pub active_entries: u64,
+    pub active_bytes: u64,
+    /// Timestamp of the active file's first entry
+    pub active_since: Option<DateTime<Utc>>,
+    pub archives: usize,
+    pub compressed_archives: usize,
+    pub archive_bytes: u64,
+    pub next_rotation: Option<DateTime<Utc>>,
 }
 
 /// Builder for creating audit log entries
-pub struct AuditLogBuilder {
+pub struct AuditLogBuilder<'a> {
     username: String,
     action: String,
     resource: String,
@@ -394,10 +1136,10 @@ pub struct AuditLogBuilder {
     details: serde_json::Value,
     success: bool,
     error: Option<String>,
-    logger: Arc<RwLock<Vec<AuditLog>>>,
+    logger: &'a AuditLogger,
 }
 
-impl AuditLogBuilder {
+impl AuditLogBuilder<'_> {
     /// Add details to the log entry
     pub fn details(mut self, details: serde_json::Value) -> Self {
         self.details = details;
@@ -417,9 +1159,8 @@ impl AuditLogBuilder {
         self
     }
 
-    /// Build and log the entry
+    /// Build and log the entry (persisted like any other entry)
     pub async fn log(self) {
-        let error_msg = self.error.clone();
         let entry = AuditLog {
             id: uuid::Uuid::new_v4().to_string(),
             timestamp: Utc::now(),
@@ -429,31 +1170,13 @@ impl AuditLogBuilder {
             ip_address: self.ip_address,
             details: self.details,
             success: self.success,
-            error: error_msg.clone(),
+            error: self.error,
+            request_id: None,
+            geo: None,
+            chain: None,
         };
 
-        let mut logs = self.logger.write().await;
-        logs.push(entry.clone());
-
-        // Log to tracing
-        if self.success {
-            info!(
-                "AUDIT: {} {} {} from {}",
-                entry.username,
-                entry.action,
-                entry.resource,
-                entry.ip_address
-            );
-        } else {
-            warn!(
-                "AUDIT: FAILED {} {} {} from {}: {}",
-                entry.username,
-                entry.action,
-                entry.resource,
-                entry.ip_address,
-                error_msg.as_deref().unwrap_or(&"unknown".to_string())
-            );
-        }
+        self.logger.log(entry).await;
     }
 }
 
@@ -497,6 +1220,9 @@ mod tests {
             details: json!({}),
             success: true,
             error: None,
+            request_id: None,
+            geo: None,
+            chain: None,
         };
 
         logger.log(entry).await;
@@ -518,6 +1244,9 @@ mod tests {
             details: json!({}),
             success: true,
             error: None,
+            request_id: None,
+            geo: None,
+            chain: None,
         }).await;
 
         logger.log(AuditLog {
@@ -530,6 +1259,9 @@ mod tests {
             details: json!({}),
             success: true,
             error: None,
+            request_id: None,
+            geo: None,
+            chain: None,
         }).await;
 
         // Query for admin logs
@@ -558,6 +1290,9 @@ mod tests {
                 details: json!({}),
                 success: true,
                 error: None,
+                request_id: None,
+                geo: None,
+                chain: None,
             }).await;
         }
 
@@ -565,4 +1300,180 @@ mod tests {
         let all = logger.all().await;
         assert_eq!(all.len(), 5);
     }
+
+    #[test]
+    fn test_summarize_body_redacts_secrets() {
+        let body = br#"{"username":"alice","password":"hunter2","nested":{"refresh_token":"abc","role":"viewer"}}"#;
+        let summary = summarize_body(body);
+        assert_eq!(summary["username"], "alice");
+        assert_eq!(summary["password"], "[REDACTED]");
+        assert_eq!(summary["nested"]["refresh_token"], "[REDACTED]");
+        assert_eq!(summary["nested"]["role"], "viewer");
+
+        assert_eq!(summarize_body(b"not json"), json!({ "bytes": 8 }));
+        assert!(summarize_body(b"").is_null());
+
+        let long = serde_json::to_vec(&json!({ "note": "x".repeat(4000) })).unwrap();
+        assert!(summarize_body(&long).as_str().unwrap().ends_with("..."));
+    }
+
+    #[tokio::test]
+    async fn test_audit_log_survives_restart_and_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let logger = AuditLogger::with_persistence_async(100, dir.path().to_path_buf())
+            .await
+            .unwrap()
+            .with_max_file_bytes(512);
+
+        for i in 0..10 {
+            logger.entry(
+                "admin".to_string(),
+                format!("action_{}", i),
+                "/test".to_string(),
+                "127.0.0.1".to_string(),
+            ).log().await;
+        }
+        assert!(!logger.archive_files().await.unwrap().is_empty());
+
+        // A fresh logger sees every entry, across archives, in order
+        let reloaded = AuditLogger::with_persistence_async(8, dir.path().to_path_buf())
+            .await
+            .unwrap();
+        assert_eq!(reloaded.load_from_file().await.unwrap(), 10);
+        let all = reloaded.all().await;
+        assert_eq!(all.len(), 8);
+        assert_eq!(all.last().unwrap().action, "action_9");
+        assert_eq!(reloaded.stats().await.total_logs, 8);
+    }
+
+    #[tokio::test]
+    async fn test_audit_chain_detects_tampering() {
+        let dir = tempfile::tempdir().unwrap();
+        let logger = AuditLogger::with_persistence_async(100, dir.path().to_path_buf())
+            .await
+            .unwrap()
+            .with_max_file_bytes(1024)
+            .with_anchor_interval(3);
+        for i in 0..8 {
+            audit_log!(logger, "admin", format!("action_{}", i), "/test", "127.0.0.1").log().await;
+        }
+
+        // The chain continues across restarts
+        let reloaded = AuditLogger::with_persistence_async(100, dir.path().to_path_buf())
+            .await
+            .unwrap()
+            .with_anchor_interval(3);
+        reloaded.load_from_file().await.unwrap();
+        audit_log!(reloaded, "admin", "action_8", "/test", "127.0.0.1").log().await;
+        let report = reloaded.verify().await.unwrap();
+        assert!(report.valid, "{:?}", report.broken);
+        assert_eq!((report.entries, report.first_seq, report.last_seq), (9, Some(1), Some(9)));
+        assert!(report.anchors_checked >= 3);
+
+        let mut files = reloaded.archive_files().await.unwrap();
+        files.push(reloaded.log_file_path().unwrap().clone());
+        let first = std::fs::read_to_string(&files[0]).unwrap();
+
+        // Editing an entry breaks its hash
+        std::fs::write(&files[0], first.replacen("action_0", "action_X", 1)).unwrap();
+        let broken = reloaded.verify().await.unwrap().broken.unwrap();
+        assert_eq!((broken.seq, broken.line), (Some(1), 1));
+
+        // Removing entries breaks the link after them
+        let mut lines: Vec<&str> = first.lines().collect();
+        lines.remove(0);
+        lines.push("");
+        std::fs::write(&files[0], lines.join("\n")).unwrap();
+        std::fs::remove_file(&files[1]).unwrap();
+        let report = reloaded.verify().await.unwrap();
+        assert!(report.broken.unwrap().reason.contains("does not follow"));
+
+        // Cutting the end of the log is caught by the anchors
+        std::fs::write(&files[0], &first).unwrap();
+        for file in &files[1..] {
+            let _ = std::fs::remove_file(file);
+        }
+        let report = reloaded.verify().await.unwrap();
+        assert!(report.broken.unwrap().reason.contains("is missing"));
+    }
+
+    #[tokio::test]
+    async fn test_audit_retention_skips_old_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let logger = AuditLogger::with_persistence_async(100, dir.path().to_path_buf())
+            .await
+            .unwrap();
+
+        let mut old = AuditLog {
+            id: "old".to_string(),
+            timestamp: Utc::now() - chrono::Duration::days(40),
+            username: "admin".to_string(),
+            action: "login".to_string(),
+            resource: "/api/auth/login".to_string(),
+            ip_address: "127.0.0.1".to_string(),
+            details: json!({}),
+            success: true,
+            error: None,
+            request_id: None,
+            geo: None,
+            chain: None,
+        };
+        logger.log(old.clone()).await;
+        old.id = "new".to_string();
+        old.timestamp = Utc::now();
+        logger.log(old).await;
+
+        let reloaded = AuditLogger::with_persistence_async(100, dir.path().to_path_buf())
+            .await
+            .unwrap()
+            .with_retention_days(30);
+        assert_eq!(reloaded.load_from_file().await.unwrap(), 1);
+        assert_eq!(reloaded.all().await[0].id, "new");
+    }
+
+    #[tokio::test]
+    async fn test_rotation_policy_compresses_and_caps_archives() {
+        let dir = tempfile::tempdir().unwrap();
+        let policy = RotationPolicy {
+            max_file_entries: Some(3),
+            compress: true,
+            schedule: Some("0 0 * * *".parse().unwrap()),
+            ..Default::default()
+        };
+        let logger = AuditLogger::with_persistence_async(100, dir.path().to_path_buf())
+            .await
+            .unwrap()
+            .with_rotation_policy(policy.clone());
+        for i in 0..7 {
+            audit_log!(logger, "admin", format!("action_{}", i), "/test", "127.0.0.1").log().await;
+        }
+
+        let files = logger.stats().await.files.unwrap();
+        assert_eq!((files.archives, files.compressed_archives), (2, 2));
+        assert_eq!(files.active_entries, 1);
+        assert!(files.next_rotation.is_some());
+
+        // Compressed archives still load and verify
+        let reloaded = AuditLogger::with_persistence_async(100, dir.path().to_path_buf())
+            .await
+            .unwrap()
+            .with_rotation_policy(policy.clone());
+        assert_eq!(reloaded.load_from_file().await.unwrap(), 7);
+        assert_eq!(reloaded.stats().await.files.unwrap().active_entries, 1);
+        assert!(reloaded.verify().await.unwrap().valid);
+
+        // Past the size cap the oldest archive goes, and the chain still verifies
+        let capped = AuditLogger::with_persistence_async(100, dir.path().to_path_buf())
+            .await
+            .unwrap()
+            .with_rotation_policy(RotationPolicy { max_archive_bytes: Some(files.archive_bytes - 1), ..policy });
+        assert_eq!(capped.load_from_file().await.unwrap(), 4);
+        assert!(capped.rotate_scheduled().await.unwrap().is_some());
+        let files = capped.stats().await.files.unwrap();
+        assert_eq!((files.archives, files.active_entries), (2, 0));
+        assert_eq!(capped.rotate_scheduled().await.unwrap(), None);
+        let report = capped.verify().await.unwrap();
+        assert!(report.valid, "{:?}", report.broken);
+        assert_eq!(report.first_seq, Some(4));
+    }
 }
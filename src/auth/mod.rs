@@ -1,16 +1,23 @@
 // Authentication and Authorization module for DMPool Admin
 // JWT-based authentication with bcrypt password hashing
 
+use crate::geoip::GeoInfo;
+use crate::secrets::{JwtKey, JwtKeySet};
+use crate::two_factor::TwoFactorManager;
 use anyhow::{Context, Result};
 use axum::{
     extract::State,
     http::{HeaderMap, StatusCode},
     response::{IntoResponse, Json},
 };
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use jsonwebtoken::{DecodingKey, EncodingKey};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
 use std::fs;
+use std::net::IpAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -20,6 +27,39 @@ use tracing::{error, info, warn};
 const MIN_PASSWORD_LENGTH: usize = 12;
 const MAX_PASSWORD_LENGTH: usize = 128;
 
+/// Access tokens are short-lived; clients renew them with a refresh token
+pub const ACCESS_TOKEN_TTL_SECS: i64 = 15 * 60;
+/// Refresh tokens (and their sessions) expire after a week without use
+pub const REFRESH_TOKEN_TTL_SECS: i64 = 7 * 24 * 3600;
+/// Login attempts kept for the activity view
+const MAX_LOGIN_ATTEMPTS: usize = 1000;
+/// Longest user agent stored with a session or login attempt
+const MAX_USER_AGENT_LEN: usize = 256;
+
+/// Roles that can be assigned to admin panel users
+pub const VALID_ROLES: &[&str] = &["admin", "operator", "viewer"];
+
+/// Role of miner tokens; their name is the BTC address they were issued for
+pub const MINER_ROLE: &str = "miner";
+/// Read the address's own worker stats, balance and payments
+pub const SCOPE_MINER_READ: &str = "miner:read";
+/// Set the address's own payout threshold
+pub const SCOPE_MINER_PAYOUT: &str = "miner:payout";
+/// Register notification webhooks for the address
+pub const SCOPE_MINER_WEBHOOKS: &str = "miner:webhooks";
+/// Set the address's display name and notification contacts
+pub const SCOPE_MINER_SETTINGS: &str = "miner:settings";
+/// Scopes a miner token can carry
+pub const MINER_SCOPES: &[&str] = &[SCOPE_MINER_READ, SCOPE_MINER_PAYOUT, SCOPE_MINER_WEBHOOKS, SCOPE_MINER_SETTINGS];
+
+/// Miner tokens have no refresh token and last until they expire or are revoked
+pub const MINER_TOKEN_TTL_SECS: i64 = 90 * 24 * 3600;
+pub const MAX_MINER_TOKEN_TTL_SECS: i64 = 365 * 24 * 3600;
+
+/// Accounts and addresses with failed logins tracked at most, so guessed
+/// usernames can't grow memory without bound
+const MAX_TRACKED_LOGIN_FAILURES: usize = 10_000;
+
 /// Password validation result
 #[derive(Debug, Clone)]
 pub struct PasswordValidation {
@@ -43,63 +83,155 @@ impl PasswordValidation {
     }
 }
 
-/// Validate password strength
-pub fn validate_password_strength(password: &str) -> PasswordValidation {
-    let mut errors = Vec::new();
+/// Passwords refused whatever the policy, compared case-insensitively
+const COMMON_PASSWORDS: &[&str] = &[
+    "password", "Password123!", "Admin123!", "12345678", "qwerty123",
+    "letmein123", "welcome123", "monkey123", "dragon123",
+];
+
+/// Rules new passwords must follow, and how long a password lasts
+#[derive(Clone, Debug, Serialize)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub require_uppercase: bool,
+    pub require_lowercase: bool,
+    pub require_digit: bool,
+    pub require_special: bool,
+    /// Refused passwords besides the built-in common ones
+    #[serde(skip)]
+    pub banned: Vec<String>,
+    /// Days after which a password must be changed; `None` never expires
+    pub max_age_days: Option<u32>,
+}
 
-    // Check length
-    if password.len() < MIN_PASSWORD_LENGTH {
-        errors.push(format!(
-            "Password must be at least {} characters long (got {})",
-            MIN_PASSWORD_LENGTH,
-            password.len()
-        ));
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: MIN_PASSWORD_LENGTH,
+            max_length: MAX_PASSWORD_LENGTH,
+            require_uppercase: true,
+            require_lowercase: true,
+            require_digit: true,
+            require_special: true,
+            banned: Vec::new(),
+            max_age_days: None,
+        }
     }
+}
 
-    if password.len() > MAX_PASSWORD_LENGTH {
-        errors.push(format!(
-            "Password must be at most {} characters long (got {})",
-            MAX_PASSWORD_LENGTH,
-            password.len()
-        ));
+impl PasswordPolicy {
+    /// The default policy adjusted by `PASSWORD_MIN_LENGTH`, `PASSWORD_REQUIRE`
+    /// (comma-separated `upper`, `lower`, `digit`, `special`, or `none`),
+    /// `PASSWORD_BANNED_FILE` (one password per line) and `PASSWORD_MAX_AGE_DAYS`
+    pub fn from_env() -> Result<Self> {
+        let mut policy = Self::default();
+        if let Ok(value) = std::env::var("PASSWORD_MIN_LENGTH") {
+            policy.min_length = value.parse().context("Invalid PASSWORD_MIN_LENGTH")?;
+        }
+        if let Ok(value) = std::env::var("PASSWORD_REQUIRE") {
+            policy.require_uppercase = false;
+            policy.require_lowercase = false;
+            policy.require_digit = false;
+            policy.require_special = false;
+            for class in value.split(',').map(str::trim).filter(|c| !c.is_empty() && *c != "none") {
+                match class {
+                    "upper" => policy.require_uppercase = true,
+                    "lower" => policy.require_lowercase = true,
+                    "digit" => policy.require_digit = true,
+                    "special" => policy.require_special = true,
+                    other => return Err(anyhow::anyhow!("Unknown PASSWORD_REQUIRE class: {}", other)),
+                }
+            }
+        }
+        if let Ok(path) = std::env::var("PASSWORD_BANNED_FILE") {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read banned passwords from {}", path))?;
+            policy.banned = content.lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .map(str::to_string)
+                .collect();
+            info!("Loaded {} banned password(s) from {}", policy.banned.len(), path);
+        }
+        if let Ok(value) = std::env::var("PASSWORD_MAX_AGE_DAYS") {
+            let days: u32 = value.parse().context("Invalid PASSWORD_MAX_AGE_DAYS")?;
+            policy.max_age_days = (days > 0).then_some(days);
+        }
+        if policy.min_length == 0 || policy.min_length > policy.max_length {
+            return Err(anyhow::anyhow!(
+                "PASSWORD_MIN_LENGTH must be between 1 and {}", policy.max_length
+            ));
+        }
+        Ok(policy)
     }
 
-    // Check for uppercase letters
-    if !password.chars().any(|c| c.is_uppercase()) {
-        errors.push("Password must contain at least one uppercase letter".to_string());
-    }
+    /// Check a password against the policy
+    pub fn validate(&self, password: &str) -> PasswordValidation {
+        let mut errors = Vec::new();
+        let length = password.chars().count();
+
+        if length < self.min_length {
+            errors.push(format!(
+                "Password must be at least {} characters long (got {})",
+                self.min_length,
+                length
+            ));
+        }
 
-    // Check for lowercase letters
-    if !password.chars().any(|c| c.is_lowercase()) {
-        errors.push("Password must contain at least one lowercase letter".to_string());
-    }
+        if length > self.max_length {
+            errors.push(format!(
+                "Password must be at most {} characters long (got {})",
+                self.max_length,
+                length
+            ));
+        }
 
-    // Check for numbers
-    if !password.chars().any(|c| c.is_ascii_digit()) {
-        errors.push("Password must contain at least one number".to_string());
-    }
+        if self.require_uppercase && !password.chars().any(|c| c.is_uppercase()) {
+            errors.push("Password must contain at least one uppercase letter".to_string());
+        }
+
+        if self.require_lowercase && !password.chars().any(|c| c.is_lowercase()) {
+            errors.push("Password must contain at least one lowercase letter".to_string());
+        }
+
+        if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            errors.push("Password must contain at least one number".to_string());
+        }
+
+        if self.require_special && !password.chars().any(|c| !c.is_alphanumeric()) {
+            errors.push("Password must contain at least one special character (!@#$%^&*(),.?\":{}|<>])".to_string());
+        }
 
-    // Check for special characters
-    if !password.chars().any(|c| !c.is_alphanumeric()) {
-        errors.push("Password must contain at least one special character (!@#$%^&*(),.?\":{}|<>])".to_string());
+        let banned = COMMON_PASSWORDS.iter().copied()
+            .chain(self.banned.iter().map(String::as_str))
+            .any(|b| b.eq_ignore_ascii_case(password));
+        if banned {
+            errors.push("Password is too common and weak".to_string());
+        }
+
+        if errors.is_empty() {
+            PasswordValidation::valid()
+        } else {
+            PasswordValidation::invalid(errors)
+        }
     }
 
-    // Check for common weak passwords
-    let weak_passwords = [
-        "password", "Password123!", "Admin123!", "12345678", "qwerty123",
-        "letmein123", "welcome123", "monkey123", "dragon123",
-    ];
-    if weak_passwords.contains(&password) {
-        errors.push("Password is too common and weak".to_string());
+    /// When a password changed at `changed_at` expires, if it does
+    pub fn expires_at(&self, changed_at: i64) -> Option<i64> {
+        self.max_age_days.map(|days| changed_at + i64::from(days) * 24 * 3600)
     }
 
-    if errors.is_empty() {
-        PasswordValidation::valid()
-    } else {
-        PasswordValidation::invalid(errors)
+    pub fn is_expired(&self, changed_at: i64, now: i64) -> bool {
+        self.expires_at(changed_at).is_some_and(|expires| expires <= now)
     }
 }
 
+/// Validate password strength against the default policy
+pub fn validate_password_strength(password: &str) -> PasswordValidation {
+    PasswordPolicy::default().validate(password)
+}
+
 /// Claims encoded in JWT token
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
@@ -113,6 +245,167 @@ pub struct Claims {
     pub iat: i64,
     /// Expiration time
     pub exp: i64,
+    /// User must change password before using any other endpoint
+    #[serde(default)]
+    pub must_change_password: bool,
+    /// Session the token belongs to; revoking the session invalidates the token
+    #[serde(default)]
+    pub sid: String,
+    /// Scopes of a restricted token; empty for user tokens, which their role governs
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    /// User's role requires 2FA they haven't enabled yet; set on verification,
+    /// so it clears as soon as they enroll
+    #[serde(skip)]
+    pub must_enroll_2fa: bool,
+}
+
+impl Claims {
+    /// Whether the token is restricted to scopes
+    pub fn is_scoped(&self) -> bool {
+        !self.scopes.is_empty()
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+}
+
+/// Server-side login session backing a refresh token
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    pub username: String,
+    /// SHA-256 of the current refresh token; rotated on every refresh
+    pub refresh_token_hash: String,
+    pub created_at: i64,
+    pub expires_at: i64,
+    /// Set for miner token sessions, which have no refresh token
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    /// User who issued a miner token; `None` when the miner signed for it
+    #[serde(default)]
+    pub issued_by: Option<String>,
+    /// Client the session was started from
+    #[serde(default)]
+    pub ip: Option<IpAddr>,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+}
+
+/// A user's login session, without its refresh token
+#[derive(Clone, Debug, Serialize)]
+pub struct SessionInfo {
+    pub id: String,
+    pub username: String,
+    pub ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    pub issued_at: i64,
+    /// Moved forward by every refresh
+    pub expires_at: i64,
+}
+
+impl From<&Session> for SessionInfo {
+    fn from(session: &Session) -> Self {
+        Self {
+            id: session.id.clone(),
+            username: session.username.clone(),
+            ip: session.ip,
+            user_agent: session.user_agent.clone(),
+            issued_at: session.created_at,
+            expires_at: session.expires_at,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LoginResult {
+    Success,
+    /// Password accepted, waiting for a 2FA code
+    TwoFactorRequired,
+    /// Wrong username or password
+    Failed,
+    /// Wrong 2FA code
+    TwoFactorFailed,
+    /// Refused during a lockout
+    Locked,
+    /// Password accepted, but refused until the user enrolls in 2FA
+    TwoFactorEnrollmentRequired,
+}
+
+/// A login, successful or not, and where it came from
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LoginAttempt {
+    pub timestamp: i64,
+    pub username: String,
+    pub ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    pub result: LoginResult,
+    /// Country and network of `ip`, when GeoIP is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geo: Option<GeoInfo>,
+}
+
+impl LoginAttempt {
+    pub fn new(username: &str, ip: Option<IpAddr>, user_agent: Option<&str>, result: LoginResult) -> Self {
+        Self {
+            timestamp: Utc::now().timestamp(),
+            username: username.to_string(),
+            ip,
+            user_agent: user_agent.map(truncate_user_agent),
+            result,
+            geo: None,
+        }
+    }
+
+    pub fn with_geo(mut self, geo: Option<GeoInfo>) -> Self {
+        self.geo = geo;
+        self
+    }
+}
+
+fn truncate_user_agent(user_agent: &str) -> String {
+    user_agent.chars().take(MAX_USER_AGENT_LEN).collect()
+}
+
+/// A miner token, without the token itself
+#[derive(Clone, Debug, Serialize)]
+pub struct MinerTokenInfo {
+    pub id: String,
+    pub address: String,
+    pub scopes: Vec<String>,
+    pub created_at: i64,
+    pub expires_at: i64,
+    pub issued_by: Option<String>,
+}
+
+impl From<&Session> for MinerTokenInfo {
+    fn from(session: &Session) -> Self {
+        Self {
+            id: session.id.clone(),
+            address: session.username.clone(),
+            scopes: session.scopes.clone(),
+            created_at: session.created_at,
+            expires_at: session.expires_at,
+            issued_by: session.issued_by.clone(),
+        }
+    }
+}
+
+/// Access and refresh tokens issued at login or refresh
+#[derive(Clone, Debug, Serialize)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_in: u64,
+    pub refresh_expires_in: u64,
+}
+
+/// Refresh request
+#[derive(Deserialize, ToSchema)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
 }
 
 /// User record stored in database
@@ -123,48 +416,293 @@ pub struct User {
     pub role: String,
     pub created_at: i64,
     pub last_login: Option<i64>,
+    /// Disabled accounts cannot log in
+    #[serde(default)]
+    pub disabled: bool,
+    /// Set for new accounts and password resets; cleared by changing the password
+    #[serde(default)]
+    pub must_change_password: bool,
+    /// Countries the user has logged in from, when GeoIP is enabled
+    #[serde(default)]
+    pub login_countries: Vec<String>,
+    /// Last password change; accounts from before this was recorded use `created_at`
+    #[serde(default)]
+    pub password_changed_at: Option<i64>,
+}
+
+impl User {
+    /// When the current password was set
+    pub fn password_set_at(&self) -> i64 {
+        self.password_changed_at.unwrap_or(self.created_at)
+    }
+}
+
+/// User record safe to return from the API (no password hash)
+#[derive(Clone, Debug, Serialize)]
+pub struct UserSummary {
+    pub username: String,
+    pub role: String,
+    pub created_at: i64,
+    pub last_login: Option<i64>,
+    pub disabled: bool,
+    pub must_change_password: bool,
+    pub password_changed_at: i64,
+}
+
+impl From<&User> for UserSummary {
+    fn from(user: &User) -> Self {
+        Self {
+            username: user.username.clone(),
+            role: user.role.clone(),
+            created_at: user.created_at,
+            last_login: user.last_login,
+            disabled: user.disabled,
+            must_change_password: user.must_change_password,
+            password_changed_at: user.password_set_at(),
+        }
+    }
 }
 
 /// Login request
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct LoginRequest {
     pub username: String,
     pub password: String,
 }
 
 /// Login response
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct LoginResponse {
     pub token: String,
+    pub refresh_token: String,
     pub user_info: UserInfo,
     pub expires_in: u64, // seconds
+    pub refresh_expires_in: u64, // seconds
+    /// Client must call the change-password endpoint before anything else
+    pub must_change_password: bool,
+    /// Client must enroll in 2FA before anything else
+    pub must_enroll_2fa: bool,
+}
+
+impl LoginResponse {
+    /// Build the response for a freshly issued token pair
+    pub fn new(user: User, tokens: TokenPair) -> Self {
+        Self {
+            token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+            must_change_password: user.must_change_password,
+            must_enroll_2fa: false,
+            user_info: UserInfo {
+                username: user.username,
+                role: user.role,
+            },
+            expires_in: tokens.expires_in,
+            refresh_expires_in: tokens.refresh_expires_in,
+        }
+    }
 }
 
 /// User info returned after login
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct UserInfo {
     pub username: String,
     pub role: String,
 }
 
+/// When repeated failed logins lock an account or client address
+#[derive(Clone, Debug)]
+pub struct LockoutPolicy {
+    /// Consecutive failures that lock an account
+    pub max_user_failures: u32,
+    /// Consecutive failures that lock a client address, whichever accounts it tries
+    pub max_ip_failures: u32,
+    /// How long a lockout lasts; failures older than this are forgotten
+    pub lockout_secs: i64,
+}
+
+impl Default for LockoutPolicy {
+    fn default() -> Self {
+        Self {
+            max_user_failures: 5,
+            max_ip_failures: 20,
+            lockout_secs: 15 * 60,
+        }
+    }
+}
+
+/// What a lockout applies to
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum LockoutTarget {
+    User(String),
+    Ip(IpAddr),
+}
+
+impl fmt::Display for LockoutTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockoutTarget::User(username) => write!(f, "user {}", username),
+            LockoutTarget::Ip(ip) => write!(f, "ip {}", ip),
+        }
+    }
+}
+
+/// An account or address that can't log in until `locked_until`
+#[derive(Clone, Debug, Serialize)]
+pub struct Lockout {
+    pub target: LockoutTarget,
+    pub failures: u32,
+    pub locked_until: DateTime<Utc>,
+}
+
+/// Consecutive failed logins of one account or address
+#[derive(Clone, Debug, Default)]
+struct LoginFailures {
+    count: u32,
+    last_failure: i64,
+    locked_until: Option<i64>,
+}
+
+impl LoginFailures {
+    fn lockout(&self, target: &LockoutTarget, now: i64) -> Option<Lockout> {
+        let locked_until = self.locked_until.filter(|until| *until > now)?;
+        Some(Lockout {
+            target: target.clone(),
+            failures: self.count,
+            locked_until: DateTime::from_timestamp(locked_until, 0).unwrap_or_else(Utc::now),
+        })
+    }
+}
+
+/// Result of a login attempt under the lockout policy
+pub enum LoginOutcome {
+    Success(User),
+    /// Wrong credentials; lists the lockouts this failure started
+    Failed { locked: Vec<Lockout> },
+    /// Refused without checking the password
+    Locked(Lockout),
+}
+
 /// Auth state manager
 pub struct AuthManager {
-    secret: String,
+    /// Signs new tokens and validates tokens signed before a key rotation
+    jwt_keys: std::sync::RwLock<JwtKeySet>,
     users: Arc<RwLock<Vec<User>>>,
     users_file: PathBuf,
+    sessions: Arc<RwLock<HashMap<String, Session>>>,
+    lockout_policy: LockoutPolicy,
+    password_policy: PasswordPolicy,
+    login_failures: Arc<RwLock<HashMap<LockoutTarget, LoginFailures>>>,
+    /// Recent login attempts, oldest first
+    login_attempts: Arc<RwLock<VecDeque<LoginAttempt>>>,
+    /// Enforces its 2FA policy on verified tokens
+    two_factor: Option<Arc<TwoFactorManager>>,
 }
 
 impl AuthManager {
     pub fn new(secret: String) -> Self {
+        Self::from_jwt_keys(JwtKeySet::new(JwtKey::new(secret)))
+    }
+
+    /// Manager signing tokens with the current key of `jwt_keys`
+    pub fn from_jwt_keys(jwt_keys: JwtKeySet) -> Self {
         let data_dir = std::env::var("DMP_DATA_DIR").unwrap_or_else(|_| "./data".to_string());
         let users_file = PathBuf::from(data_dir).join("users.json");
         Self {
-            secret,
+            jwt_keys: std::sync::RwLock::new(jwt_keys),
             users: Arc::new(RwLock::new(Vec::new())),
             users_file,
+            sessions: Arc::new(RwLock::new(HashMap::new())),
+            lockout_policy: LockoutPolicy::default(),
+            password_policy: PasswordPolicy::default(),
+            login_failures: Arc::new(RwLock::new(HashMap::new())),
+            login_attempts: Arc::new(RwLock::new(VecDeque::new())),
+            two_factor: None,
         }
     }
 
+    pub fn jwt_keys(&self) -> JwtKeySet {
+        self.jwt_keys.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Switch to new signing keys; tokens signed with keys missing from
+    /// `keys` stop validating
+    pub fn set_jwt_keys(&self, keys: JwtKeySet) {
+        *self.jwt_keys.write().unwrap_or_else(|e| e.into_inner()) = keys;
+    }
+
+    /// Sign `claims` with the current key, naming it in the `kid` header
+    fn encode_token(&self, claims: &Claims) -> Result<String> {
+        let keys = self.jwt_keys.read().unwrap_or_else(|e| e.into_inner());
+        let header = jsonwebtoken::Header {
+            kid: Some(keys.current.kid().to_string()),
+            ..Default::default()
+        };
+        jsonwebtoken::encode(&header, claims, &EncodingKey::from_secret(keys.current.secret()))
+            .map_err(|e| anyhow::anyhow!("Failed to encode token: {}", e))
+    }
+
+    /// Claims of a token signed with a known key; tokens without a `kid`
+    /// predate key IDs and are tried against every key
+    fn decode_token(&self, token: &str) -> Result<Claims> {
+        let header = jsonwebtoken::decode_header(token)
+            .map_err(|e| anyhow::anyhow!("Invalid token: {}", e))?;
+        let keys = self.jwt_keys.read().unwrap_or_else(|e| e.into_inner());
+        let candidates: Vec<&JwtKey> = match &header.kid {
+            Some(kid) => keys.find(kid).into_iter().collect(),
+            None => keys.keys().collect(),
+        };
+        if candidates.is_empty() {
+            return Err(anyhow::anyhow!("Invalid token: unknown signing key"));
+        }
+
+        let validation = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::HS256);
+        let mut last_error = None;
+        for key in candidates {
+            match jsonwebtoken::decode::<Claims>(token, &DecodingKey::from_secret(key.secret()), &validation) {
+                Ok(decoded) => return Ok(decoded.claims),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(anyhow::anyhow!("Invalid token: {}", last_error.map(|e| e.to_string()).unwrap_or_default()))
+    }
+
+    /// Flag tokens of users who must still enroll in 2FA under `two_factor`'s policy
+    pub fn with_two_factor(mut self, two_factor: Arc<TwoFactorManager>) -> Self {
+        self.two_factor = Some(two_factor);
+        self
+    }
+
+    /// Whether `claims` belong to a user the 2FA policy holds to enrollment
+    async fn must_enroll_2fa(&self, claims: &Claims) -> bool {
+        match &self.two_factor {
+            Some(two_factor) if !claims.is_scoped() => two_factor.enrollment_required(&claims.name, &claims.role).await,
+            _ => false,
+        }
+    }
+
+    /// Store users in `path` instead of `DMP_DATA_DIR/users.json`
+    pub fn with_users_file(mut self, path: PathBuf) -> Self {
+        self.users_file = path;
+        self
+    }
+
+    /// Lock accounts and addresses according to `policy` instead of the default
+    pub fn with_lockout_policy(mut self, policy: LockoutPolicy) -> Self {
+        self.lockout_policy = policy;
+        self
+    }
+
+    /// Check new passwords against `policy` instead of the default
+    pub fn with_password_policy(mut self, policy: PasswordPolicy) -> Self {
+        self.password_policy = policy;
+        self
+    }
+
+    pub fn password_policy(&self) -> &PasswordPolicy {
+        &self.password_policy
+    }
+
     /// Load users from file
     fn load_users(&self) -> Vec<User> {
         if self.users_file.exists() {
@@ -206,22 +744,93 @@ impl AuthManager {
         Ok(())
     }
 
-    /// Initialize users from persistent storage
+    /// Sessions are stored next to the users file
+    fn sessions_file(&self) -> PathBuf {
+        self.users_file.with_file_name("sessions.json")
+    }
+
+    /// Load unexpired sessions from file
+    fn load_sessions(&self) -> HashMap<String, Session> {
+        let path = self.sessions_file();
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) => return HashMap::new(),
+        };
+        match serde_json::from_str::<Vec<Session>>(&content) {
+            Ok(sessions) => {
+                let now = Utc::now().timestamp();
+                sessions.into_iter()
+                    .filter(|s| s.expires_at > now)
+                    .map(|s| (s.id.clone(), s))
+                    .collect()
+            }
+            Err(e) => {
+                warn!("Failed to parse sessions file: {}, all sessions revoked", e);
+                HashMap::new()
+            }
+        }
+    }
+
+    /// Save sessions to file
+    fn save_sessions(&self, sessions: &HashMap<String, Session>) -> Result<()> {
+        let path = self.sessions_file();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context("Failed to create sessions directory")?;
+        }
+
+        let list: Vec<&Session> = sessions.values().collect();
+        let json = serde_json::to_string_pretty(&list)
+            .context("Failed to serialize sessions")?;
+
+        fs::write(&path, json)
+            .context("Failed to write sessions file")?;
+        Ok(())
+    }
+
+    /// Login attempts are stored next to the users file
+    fn login_attempts_file(&self) -> PathBuf {
+        self.users_file.with_file_name("login_attempts.json")
+    }
+
+    fn load_login_attempts(&self) -> VecDeque<LoginAttempt> {
+        let Ok(content) = fs::read_to_string(self.login_attempts_file()) else {
+            return VecDeque::new();
+        };
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            warn!("Failed to parse login attempts file: {}", e);
+            VecDeque::new()
+        })
+    }
+
+    fn save_login_attempts(&self, attempts: &VecDeque<LoginAttempt>) -> Result<()> {
+        let path = self.login_attempts_file();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context("Failed to create login attempts directory")?;
+        }
+        let json = serde_json::to_string(attempts)
+            .context("Failed to serialize login attempts")?;
+        fs::write(&path, json)
+            .context("Failed to write login attempts file")?;
+        Ok(())
+    }
+
+    /// Initialize users and sessions from persistent storage
     pub async fn load(&self) -> Result<()> {
         let users = self.load_users();
         *self.users.write().await = users;
+        let sessions = self.load_sessions();
+        *self.sessions.write().await = sessions;
+        let attempts = self.load_login_attempts();
+        *self.login_attempts.write().await = attempts;
         Ok(())
     }
 
     /// Initialize with default admin user
     pub async fn init_default_admin(&self, username: &str, password: &str) -> Result<()> {
         // Validate password strength
-        let validation = validate_password_strength(password);
-        if !validation.is_valid {
-            let error_msg = format!("Password validation failed: {}", validation.errors.join("; "));
-            warn!("{}", error_msg);
-            return Err(anyhow::anyhow!(error_msg)).context("Invalid password");
-        }
+        self.check_password(password)?;
 
         let mut users = self.users.write().await;
 
@@ -231,14 +840,7 @@ impl AuthManager {
             return Ok(());
         }
 
-        // Hash password using spawn_blocking to avoid blocking the tokio executor
-        let password = password.to_string();
-        let password_hash = tokio::task::spawn_blocking(move || {
-            bcrypt::hash(&password, bcrypt::DEFAULT_COST)
-                .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))
-        })
-        .await
-        .map_err(|e| anyhow::anyhow!("Join error: {}", e))??;
+        let password_hash = Self::hash_password(password).await?;
 
         let user = User {
             username: username.to_string(),
@@ -246,6 +848,10 @@ impl AuthManager {
             role: "admin".to_string(),
             created_at: Utc::now().timestamp(),
             last_login: None,
+            disabled: false,
+            must_change_password: false,
+            login_countries: Vec::new(),
+            password_changed_at: None,
         };
 
         users.push(user);
@@ -267,8 +873,13 @@ impl AuthManager {
         info!("AUTH: Got users lock, finding user");
 
         if let Some(user) = users.iter().find(|u| u.username == username) {
+            if user.disabled {
+                warn!("AUTH: Login attempt for disabled user: {}", username);
+                return Ok(None);
+            }
+
             // Clone user data to avoid holding borrow across await
-            let user_clone = user.clone();
+            let mut user_clone = user.clone();
             let password_hash = user.password_hash.clone();
             drop(users); // Release read lock before blocking operation
 
@@ -286,10 +897,16 @@ impl AuthManager {
 
             info!("AUTH: Password verification result for user {}: {}", username, is_valid);
             if is_valid {
-                // Update last login
+                // Update last login, and force a change of an expired password
+                let now = Utc::now().timestamp();
                 let mut users = self.users.write().await;
                 if let Some(u) = users.iter_mut().find(|u| u.username == username) {
-                    u.last_login = Some(Utc::now().timestamp());
+                    u.last_login = Some(now);
+                    if !u.must_change_password && self.password_policy.is_expired(u.password_set_at(), now) {
+                        info!("AUTH: Password of user {} has expired, it must be changed", username);
+                        u.must_change_password = true;
+                    }
+                    user_clone.must_change_password = u.must_change_password;
                 }
                 // Save to file (async but fire and forget)
                 let users_slice = users.as_slice();
@@ -304,11 +921,93 @@ impl AuthManager {
         Ok(None)
     }
 
-    /// Generate JWT token
-    pub fn generate_token(&self, user: &User) -> Result<String> {
+    /// Authenticate a login from `ip`, enforcing the lockout policy
+    ///
+    /// Locked accounts and addresses are refused before the password is
+    /// checked. A success clears the failures of the account and address.
+    pub async fn login(&self, username: &str, password: &str, ip: Option<IpAddr>) -> Result<LoginOutcome> {
+        let mut targets = vec![LockoutTarget::User(username.to_string())];
+        targets.extend(ip.map(LockoutTarget::Ip));
+
+        let now = Utc::now().timestamp();
+        {
+            let failures = self.login_failures.read().await;
+            let locked = targets.iter()
+                .find_map(|t| failures.get(t).and_then(|f| f.lockout(t, now)));
+            if let Some(lockout) = locked {
+                warn!("AUTH: Login refused, {} is locked until {}", lockout.target, lockout.locked_until);
+                return Ok(LoginOutcome::Locked(lockout));
+            }
+        }
+
+        match self.authenticate(username, password).await? {
+            Some(user) => {
+                let mut failures = self.login_failures.write().await;
+                for target in &targets {
+                    failures.remove(target);
+                }
+                Ok(LoginOutcome::Success(user))
+            }
+            None => Ok(LoginOutcome::Failed { locked: self.record_login_failure(&targets).await }),
+        }
+    }
+
+    /// Count a failed login against each target; returns the lockouts it started
+    async fn record_login_failure(&self, targets: &[LockoutTarget]) -> Vec<Lockout> {
+        let policy = &self.lockout_policy;
+        let now = Utc::now().timestamp();
+        let mut failures = self.login_failures.write().await;
+        failures.retain(|_, f| f.locked_until.unwrap_or(f.last_failure + policy.lockout_secs) > now);
+
+        let mut locked = Vec::new();
+        for target in targets {
+            if failures.len() >= MAX_TRACKED_LOGIN_FAILURES && !failures.contains_key(target) {
+                warn!("AUTH: Tracking too many failed logins, not counting {}", target);
+                continue;
+            }
+            let max = match target {
+                LockoutTarget::User(_) => policy.max_user_failures,
+                LockoutTarget::Ip(_) => policy.max_ip_failures,
+            };
+            let entry = failures.entry(target.clone()).or_default();
+            if entry.locked_until.is_some() {
+                // Locked by a concurrent attempt; expired lockouts were dropped above
+                continue;
+            }
+            entry.count += 1;
+            entry.last_failure = now;
+            if max > 0 && entry.count >= max {
+                entry.locked_until = Some(now + policy.lockout_secs);
+                let lockout = entry.lockout(target, now);
+                warn!("AUTH: {} locked for {}s after {} failed logins", target, policy.lockout_secs, entry.count);
+                locked.extend(lockout);
+            }
+        }
+        locked
+    }
+
+    /// Accounts and addresses currently locked out
+    pub async fn lockouts(&self) -> Vec<Lockout> {
+        let now = Utc::now().timestamp();
+        self.login_failures.read().await.iter()
+            .filter_map(|(target, f)| f.lockout(target, now))
+            .collect()
+    }
+
+    /// Lift a lockout and forget the failures of `target`; returns false if it had none
+    pub async fn unlock(&self, target: &LockoutTarget) -> bool {
+        let removed = self.login_failures.write().await.remove(target).is_some();
+        if removed {
+            info!("Cleared failed logins of {}", target);
+        }
+        removed
+    }
+
+    /// Generate a short-lived access token bound to `session_id`
+    pub fn generate_token(&self, user: &User, session_id: &str) -> Result<String> {
         let expiration = Utc::now()
-            .checked_add_signed(Duration::hours(24))
-            .unwrap_or_else(|| Utc::now() + Duration::hours(24))
+            .checked_add_signed(Duration::seconds(ACCESS_TOKEN_TTL_SECS))
+            .unwrap_or_else(|| Utc::now() + Duration::seconds(ACCESS_TOKEN_TTL_SECS))
             .timestamp();
 
         let claims = Claims {
@@ -317,43 +1016,369 @@ impl AuthManager {
             role: user.role.clone(),
             iat: Utc::now().timestamp(),
             exp: expiration,
+            must_change_password: user.must_change_password,
+            sid: session_id.to_string(),
+            scopes: Vec::new(),
+            must_enroll_2fa: false,
         };
 
-        let encoding_key = EncodingKey::from_secret(self.secret.as_ref());
-        let token = jsonwebtoken::encode(&jsonwebtoken::Header::default(), &claims, &encoding_key)
-            .map_err(|e| anyhow::anyhow!("Failed to encode token: {}", e))?;
+        self.encode_token(&claims)
+    }
 
-        Ok(token)
+    /// Verify JWT token and check that its session has not been revoked
+    pub async fn verify_token(&self, token: &str) -> Result<Claims> {
+        let mut claims = self.decode_token(token)?;
+        let valid = {
+            let sessions = self.sessions.read().await;
+            matches!(sessions.get(&claims.sid), Some(session) if session.username == claims.name
+                && session.scopes == claims.scopes
+                && session.expires_at > Utc::now().timestamp())
+        };
+        if !valid {
+            return Err(anyhow::anyhow!("Session revoked or expired"));
+        }
+        claims.must_enroll_2fa = self.must_enroll_2fa(&claims).await;
+        Ok(claims)
     }
 
-    /// Verify JWT token
-    pub fn verify_token(&self, token: &str) -> Result<Claims> {
-        let decoding_key = DecodingKey::from_secret(self.secret.as_ref());
-        let validation = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::HS256);
-        let decoded = jsonwebtoken::decode::<Claims>(token, &decoding_key, &validation)
-            .map_err(|e| anyhow::anyhow!("Invalid token: {}", e))?;
+    /// Claims for a user identified by a verified client certificate instead of a token
+    ///
+    /// No session backs these claims; they are built again for every request.
+    pub async fn certificate_claims(&self, username: &str) -> Result<Claims> {
+        let user = self.get_user(username).await
+            .ok_or_else(|| anyhow::anyhow!("Unknown user: {}", username))?;
+        if user.disabled {
+            return Err(anyhow::anyhow!("User is disabled: {}", username));
+        }
+        let now = Utc::now().timestamp();
+        let mut claims = Claims {
+            sub: user.username.clone(),
+            name: user.username.clone(),
+            role: user.role.clone(),
+            iat: now,
+            exp: now + ACCESS_TOKEN_TTL_SECS,
+            must_change_password: user.must_change_password,
+            sid: String::new(),
+            scopes: Vec::new(),
+            must_enroll_2fa: false,
+        };
+        claims.must_enroll_2fa = self.must_enroll_2fa(&claims).await;
+        Ok(claims)
+    }
+
+    /// Generate a random refresh token and its stored hash
+    fn new_refresh_token() -> (String, String) {
+        use base64::Engine;
+        use rand::RngCore;
 
-        Ok(decoded.claims)
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let token = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes);
+        let hash = Self::hash_refresh_token(&token);
+        (token, hash)
     }
 
-    /// Create user
-    pub async fn create_user(&self, username: &str, password: &str, role: &str) -> Result<()> {
-        // Validate password strength
-        let validation = validate_password_strength(password);
-        if !validation.is_valid {
-            let error_msg = format!("Password validation failed: {}", validation.errors.join("; "));
-            warn!("{}", error_msg);
-            return Err(anyhow::anyhow!(error_msg)).context("Invalid password");
+    fn hash_refresh_token(token: &str) -> String {
+        use sha2::{Digest, Sha256};
+        format!("{:x}", Sha256::digest(token.as_bytes()))
+    }
+
+    /// Issue a token pair for `session`
+    fn issue_tokens(&self, user: &User, session: &Session, refresh_token: String) -> Result<TokenPair> {
+        Ok(TokenPair {
+            access_token: self.generate_token(user, &session.id)?,
+            refresh_token,
+            expires_in: ACCESS_TOKEN_TTL_SECS as u64,
+            refresh_expires_in: (session.expires_at - Utc::now().timestamp()).max(0) as u64,
+        })
+    }
+
+    /// Start a new session for an authenticated user
+    pub async fn create_session(&self, user: &User) -> Result<TokenPair> {
+        self.create_session_from(user, None, None).await
+    }
+
+    /// Start a new session for an authenticated user, remembering the client
+    pub async fn create_session_from(&self, user: &User, ip: Option<IpAddr>, user_agent: Option<&str>) -> Result<TokenPair> {
+        let now = Utc::now().timestamp();
+        let (refresh_token, refresh_token_hash) = Self::new_refresh_token();
+        let session = Session {
+            id: uuid::Uuid::new_v4().to_string(),
+            username: user.username.clone(),
+            refresh_token_hash,
+            created_at: now,
+            expires_at: now + REFRESH_TOKEN_TTL_SECS,
+            scopes: Vec::new(),
+            issued_by: None,
+            ip,
+            user_agent: user_agent.map(truncate_user_agent),
+        };
+        let tokens = self.issue_tokens(user, &session, refresh_token)?;
+
+        let mut sessions = self.sessions.write().await;
+        sessions.retain(|_, s| s.expires_at > now);
+        sessions.insert(session.id.clone(), session);
+        if let Err(e) = self.save_sessions(&sessions) {
+            warn!("Failed to save sessions to file: {}", e);
+        }
+
+        Ok(tokens)
+    }
+
+    /// Exchange a refresh token for a new token pair, rotating the refresh token
+    pub async fn refresh_session(&self, refresh_token: &str) -> Result<(User, TokenPair)> {
+        let now = Utc::now().timestamp();
+        let hash = Self::hash_refresh_token(refresh_token);
+
+        let mut sessions = self.sessions.write().await;
+        let session_id = sessions.values()
+            .find(|s| s.scopes.is_empty() && s.refresh_token_hash == hash)
+            .map(|s| s.id.clone())
+            .ok_or_else(|| anyhow::anyhow!("Unknown refresh token"))?;
+
+        let username = sessions[&session_id].username.clone();
+        let expires_at = sessions[&session_id].expires_at;
+        let user = match self.get_user(&username).await {
+            Some(user) if !user.disabled && expires_at > now => user,
+            _ => {
+                sessions.remove(&session_id);
+                if let Err(e) = self.save_sessions(&sessions) {
+                    warn!("Failed to save sessions to file: {}", e);
+                }
+                return Err(anyhow::anyhow!("Session expired or user disabled"));
+            }
+        };
+
+        let (new_token, new_hash) = Self::new_refresh_token();
+        let session = sessions.get_mut(&session_id)
+            .ok_or_else(|| anyhow::anyhow!("Unknown refresh token"))?;
+        session.refresh_token_hash = new_hash;
+        session.expires_at = now + REFRESH_TOKEN_TTL_SECS;
+        let tokens = self.issue_tokens(&user, session, new_token)?;
+
+        if let Err(e) = self.save_sessions(&sessions) {
+            warn!("Failed to save sessions to file: {}", e);
+        }
+
+        Ok((user, tokens))
+    }
+
+    /// Revoke a single session (logout); returns false if it did not exist
+    pub async fn revoke_session(&self, session_id: &str) -> bool {
+        let mut sessions = self.sessions.write().await;
+        let removed = sessions.remove(session_id).is_some();
+        if removed {
+            if let Err(e) = self.save_sessions(&sessions) {
+                warn!("Failed to save sessions to file: {}", e);
+            }
+        }
+        removed
+    }
+
+    /// Unexpired login sessions, optionally of one user, newest first
+    ///
+    /// Miner tokens are listed separately.
+    pub async fn sessions(&self, username: Option<&str>) -> Vec<SessionInfo> {
+        let now = Utc::now().timestamp();
+        let sessions = self.sessions.read().await;
+        let mut list: Vec<SessionInfo> = sessions.values()
+            .filter(|s| s.scopes.is_empty() && s.expires_at > now)
+            .filter(|s| username.is_none_or(|u| s.username == u))
+            .map(SessionInfo::from)
+            .collect();
+        list.sort_by_key(|s| std::cmp::Reverse(s.issued_at));
+        list
+    }
+
+    /// A login session by id, unless it expired or is a miner token
+    pub async fn session(&self, session_id: &str) -> Option<SessionInfo> {
+        let now = Utc::now().timestamp();
+        self.sessions.read().await.get(session_id)
+            .filter(|s| s.scopes.is_empty() && s.expires_at > now)
+            .map(SessionInfo::from)
+    }
+
+    /// Remember a login attempt, forgetting the oldest past the history limit
+    pub async fn record_login_attempt(&self, attempt: LoginAttempt) {
+        let mut attempts = self.login_attempts.write().await;
+        attempts.push_back(attempt);
+        while attempts.len() > MAX_LOGIN_ATTEMPTS {
+            attempts.pop_front();
+        }
+        if let Err(e) = self.save_login_attempts(&attempts) {
+            warn!("Failed to save login attempts to file: {}", e);
+        }
+    }
+
+    /// Recent login attempts, optionally of one user or with one result, newest first
+    pub async fn login_attempts(&self, username: Option<&str>, result: Option<LoginResult>, limit: usize) -> Vec<LoginAttempt> {
+        self.login_attempts.read().await.iter().rev()
+            .filter(|a| username.is_none_or(|u| a.username == u))
+            .filter(|a| result.is_none_or(|r| a.result == r))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Issue a token for a BTC address, limited to `scopes`
+    ///
+    /// The caller proves the address belongs to the miner, or is an admin
+    /// (`issued_by`). Returns the token with its details.
+    pub async fn issue_miner_token(
+        &self,
+        address: &str,
+        scopes: &[String],
+        ttl_secs: i64,
+        issued_by: Option<&str>,
+    ) -> Result<(String, MinerTokenInfo)> {
+        if scopes.is_empty() {
+            return Err(anyhow::anyhow!("A miner token needs at least one scope"));
+        }
+        if let Some(scope) = scopes.iter().find(|s| !MINER_SCOPES.contains(&s.as_str())) {
+            return Err(anyhow::anyhow!("Unknown scope: {} (expected one of {})", scope, MINER_SCOPES.join(", ")));
+        }
+        if !(1..=MAX_MINER_TOKEN_TTL_SECS).contains(&ttl_secs) {
+            return Err(anyhow::anyhow!("Token lifetime must be at most {} days", MAX_MINER_TOKEN_TTL_SECS / 86400));
         }
+        let mut scopes = scopes.to_vec();
+        scopes.sort();
+        scopes.dedup();
+
+        let now = Utc::now().timestamp();
+        let session = Session {
+            id: uuid::Uuid::new_v4().to_string(),
+            username: address.to_string(),
+            refresh_token_hash: String::new(),
+            created_at: now,
+            expires_at: now + ttl_secs,
+            scopes: scopes.clone(),
+            issued_by: issued_by.map(str::to_string),
+            ip: None,
+            user_agent: None,
+        };
+        let claims = Claims {
+            sub: address.to_string(),
+            name: address.to_string(),
+            role: MINER_ROLE.to_string(),
+            iat: now,
+            exp: session.expires_at,
+            must_change_password: false,
+            sid: session.id.clone(),
+            scopes,
+            must_enroll_2fa: false,
+        };
+        let token = self.encode_token(&claims)?;
+        let info = MinerTokenInfo::from(&session);
+
+        let mut sessions = self.sessions.write().await;
+        sessions.retain(|_, s| s.expires_at > now);
+        sessions.insert(session.id.clone(), session);
+        if let Err(e) = self.save_sessions(&sessions) {
+            warn!("Failed to save sessions to file: {}", e);
+        }
+        info!("Issued miner token {} for {} ({})", info.id, address, info.scopes.join(", "));
+        Ok((token, info))
+    }
+
+    /// Unexpired miner tokens, optionally of one address, newest first
+    pub async fn miner_tokens(&self, address: Option<&str>) -> Vec<MinerTokenInfo> {
+        let now = Utc::now().timestamp();
+        let sessions = self.sessions.read().await;
+        let mut tokens: Vec<MinerTokenInfo> = sessions.values()
+            .filter(|s| !s.scopes.is_empty() && s.expires_at > now)
+            .filter(|s| address.is_none_or(|a| s.username == a))
+            .map(MinerTokenInfo::from)
+            .collect();
+        tokens.sort_by_key(|t| std::cmp::Reverse(t.created_at));
+        tokens
+    }
+
+    /// Revoke a miner token; returns false if there is none with this id
+    pub async fn revoke_miner_token(&self, id: &str) -> bool {
+        let mut sessions = self.sessions.write().await;
+        if sessions.get(id).is_none_or(|s| s.scopes.is_empty()) {
+            return false;
+        }
+        sessions.remove(id);
+        if let Err(e) = self.save_sessions(&sessions) {
+            warn!("Failed to save sessions to file: {}", e);
+        }
+        info!("Revoked miner token {}", id);
+        true
+    }
+
+    /// Revoke every session of a user, returning how many were revoked
+    ///
+    /// Miner tokens are kept even if an address happens to match the username.
+    pub async fn revoke_user_sessions(&self, username: &str) -> usize {
+        let mut sessions = self.sessions.write().await;
+        let before = sessions.len();
+        sessions.retain(|_, s| s.username != username || !s.scopes.is_empty());
+        let revoked = before - sessions.len();
+        if revoked > 0 {
+            info!("Revoked {} session(s) for user '{}'", revoked, username);
+            if let Err(e) = self.save_sessions(&sessions) {
+                warn!("Failed to save sessions to file: {}", e);
+            }
+        }
+        revoked
+    }
 
-        // Use spawn_blocking to avoid blocking the tokio executor
+    /// Hash a password using spawn_blocking to avoid blocking the tokio executor
+    async fn hash_password(password: &str) -> Result<String> {
         let password = password.to_string();
-        let password_hash = tokio::task::spawn_blocking(move || {
+        tokio::task::spawn_blocking(move || {
             bcrypt::hash(&password, bcrypt::DEFAULT_COST)
                 .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))
         })
         .await
-        .map_err(|e| anyhow::anyhow!("Join error: {}", e))??;
+        .map_err(|e| anyhow::anyhow!("Join error: {}", e))?
+    }
+
+    /// Verify a password against a hash using spawn_blocking
+    async fn verify_password(password: &str, password_hash: &str) -> Result<bool> {
+        let password = password.to_string();
+        let password_hash = password_hash.to_string();
+        tokio::task::spawn_blocking(move || bcrypt::verify(&password, &password_hash).unwrap_or(false))
+            .await
+            .map_err(|e| anyhow::anyhow!("Join error: {}", e))
+    }
+
+    /// Validate password strength, returning an error listing all problems
+    fn check_password(&self, password: &str) -> Result<()> {
+        let validation = self.password_policy.validate(password);
+        if !validation.is_valid {
+            let error_msg = format!("Password validation failed: {}", validation.errors.join("; "));
+            warn!("{}", error_msg);
+            return Err(anyhow::anyhow!(error_msg)).context("Invalid password");
+        }
+        Ok(())
+    }
+
+    /// Number of enabled admins other than `username`
+    fn other_active_admins(users: &[User], username: &str) -> usize {
+        users.iter()
+            .filter(|u| u.username != username && u.role == "admin" && !u.disabled)
+            .count()
+    }
+
+    /// Create user
+    ///
+    /// New users must change their password on first login.
+    pub async fn create_user(&self, username: &str, password: &str, role: &str) -> Result<()> {
+        if username.is_empty() || username.len() > 64 {
+            return Err(anyhow::anyhow!("Username must be 1-64 characters"));
+        }
+        if !VALID_ROLES.contains(&role) {
+            return Err(anyhow::anyhow!("Invalid role '{}', expected one of: {}", role, VALID_ROLES.join(", ")));
+        }
+        self.check_password(password)?;
+
+        if self.users.read().await.iter().any(|u| u.username == username) {
+            return Err(anyhow::anyhow!("User '{}' already exists", username));
+        }
+
+        let password_hash = Self::hash_password(password).await?;
 
         let user = User {
             username: username.to_string(),
@@ -361,9 +1386,17 @@ impl AuthManager {
             role: role.to_string(),
             created_at: Utc::now().timestamp(),
             last_login: None,
+            disabled: false,
+            must_change_password: true,
+            login_countries: Vec::new(),
+            password_changed_at: None,
         };
 
         let mut users = self.users.write().await;
+        // Re-check after hashing in case of a concurrent create
+        if users.iter().any(|u| u.username == username) {
+            return Err(anyhow::anyhow!("User '{}' already exists", username));
+        }
         users.push(user);
         info!("Created user '{}' with role '{}'", username, role);
 
@@ -381,6 +1414,125 @@ impl AuthManager {
         let users = self.users.read().await;
         users.iter().find(|u| u.username == username).cloned()
     }
+
+    /// List all users without password hashes
+    pub async fn list_users(&self) -> Vec<UserSummary> {
+        let users = self.users.read().await;
+        users.iter().map(UserSummary::from).collect()
+    }
+
+    /// Delete a user; the last enabled admin cannot be deleted
+    pub async fn delete_user(&self, username: &str) -> Result<()> {
+        let mut users = self.users.write().await;
+        let pos = users.iter().position(|u| u.username == username)
+            .ok_or_else(|| anyhow::anyhow!("User '{}' not found", username))?;
+
+        if users[pos].role == "admin" && Self::other_active_admins(&users, username) == 0 {
+            return Err(anyhow::anyhow!("Cannot delete the last active admin"));
+        }
+
+        users.remove(pos);
+        info!("Deleted user '{}'", username);
+
+        self.save_users(users.as_slice())?;
+        drop(users);
+        self.revoke_user_sessions(username).await;
+        Ok(())
+    }
+
+    /// Enable or disable a user; the last enabled admin cannot be disabled
+    pub async fn set_disabled(&self, username: &str, disabled: bool) -> Result<()> {
+        let mut users = self.users.write().await;
+
+        if disabled {
+            let user = users.iter().find(|u| u.username == username)
+                .ok_or_else(|| anyhow::anyhow!("User '{}' not found", username))?;
+            if user.role == "admin" && Self::other_active_admins(&users, username) == 0 {
+                return Err(anyhow::anyhow!("Cannot disable the last active admin"));
+            }
+        }
+
+        let user = users.iter_mut().find(|u| u.username == username)
+            .ok_or_else(|| anyhow::anyhow!("User '{}' not found", username))?;
+        user.disabled = disabled;
+        info!("{} user '{}'", if disabled { "Disabled" } else { "Enabled" }, username);
+
+        self.save_users(users.as_slice())?;
+        drop(users);
+        if disabled {
+            self.revoke_user_sessions(username).await;
+        }
+        Ok(())
+    }
+
+    /// Remember that `username` logged in from `country`
+    ///
+    /// Returns the countries seen before when this one is new to a user who
+    /// has logged in from elsewhere; a user's first country is not reported.
+    pub async fn record_login_country(&self, username: &str, country: &str) -> Result<Option<Vec<String>>> {
+        let mut users = self.users.write().await;
+        let user = users.iter_mut().find(|u| u.username == username)
+            .ok_or_else(|| anyhow::anyhow!("User '{}' not found", username))?;
+        if user.login_countries.iter().any(|c| c == country) {
+            return Ok(None);
+        }
+        let known = user.login_countries.clone();
+        user.login_countries.push(country.to_string());
+        self.save_users(users.as_slice())?;
+        Ok((!known.is_empty()).then_some(known))
+    }
+
+    /// Reset a user's password; they must change it again on next login
+    pub async fn reset_password(&self, username: &str, new_password: &str) -> Result<()> {
+        self.check_password(new_password)?;
+        if self.get_user(username).await.is_none() {
+            return Err(anyhow::anyhow!("User '{}' not found", username));
+        }
+
+        let password_hash = Self::hash_password(new_password).await?;
+
+        let mut users = self.users.write().await;
+        let user = users.iter_mut().find(|u| u.username == username)
+            .ok_or_else(|| anyhow::anyhow!("User '{}' not found", username))?;
+        user.password_hash = password_hash;
+        user.password_changed_at = Some(Utc::now().timestamp());
+        user.must_change_password = true;
+        info!("Reset password for user '{}'", username);
+
+        self.save_users(users.as_slice())?;
+        drop(users);
+        self.revoke_user_sessions(username).await;
+        Ok(())
+    }
+
+    /// Change a user's own password after verifying the current one
+    pub async fn change_password(&self, username: &str, current_password: &str, new_password: &str) -> Result<()> {
+        let user = self.get_user(username).await
+            .ok_or_else(|| anyhow::anyhow!("User '{}' not found", username))?;
+
+        if !Self::verify_password(current_password, &user.password_hash).await? {
+            return Err(anyhow::anyhow!("Current password is incorrect"));
+        }
+        if current_password == new_password {
+            return Err(anyhow::anyhow!("New password must differ from the current password"));
+        }
+        self.check_password(new_password)?;
+
+        let password_hash = Self::hash_password(new_password).await?;
+
+        let mut users = self.users.write().await;
+        let user = users.iter_mut().find(|u| u.username == username)
+            .ok_or_else(|| anyhow::anyhow!("User '{}' not found", username))?;
+        user.password_hash = password_hash;
+        user.password_changed_at = Some(Utc::now().timestamp());
+        user.must_change_password = false;
+        info!("User '{}' changed their password", username);
+
+        self.save_users(users.as_slice())?;
+        drop(users);
+        self.revoke_user_sessions(username).await;
+        Ok(())
+    }
 }
 
 /// Authenticated user extractor
@@ -413,6 +1565,7 @@ pub async fn require_auth(
 
     // Verify token
     let claims = auth.verify_token(token)
+        .await
         .map_err(|e| {
             warn!("Token verification failed: {}", e);
             StatusCode::UNAUTHORIZED
@@ -444,31 +1597,23 @@ pub async fn login(
     State(auth): State<Arc<AuthManager>>,
     Json(req): Json<LoginRequest>,
 ) -> Result<Json<LoginResponse>, StatusCode> {
-    match auth.authenticate(&req.username, &req.password).await {
-        Ok(Some(user)) => {
-            let token = auth.generate_token(&user)
+    match auth.login(&req.username, &req.password, None).await {
+        Ok(LoginOutcome::Success(user)) => {
+            let tokens = auth.create_session(&user).await
                 .map_err(|e| {
                     error!("Failed to generate token: {}", e);
                     StatusCode::INTERNAL_SERVER_ERROR
                 })?;
 
-            let expires_in = 24 * 3600; // 24 hours
-
             info!("User '{}' logged in successfully", req.username);
 
-            Ok(Json(LoginResponse {
-                token,
-                user_info: UserInfo {
-                    username: user.username,
-                    role: user.role,
-                },
-                expires_in,
-            }))
+            Ok(Json(LoginResponse::new(user, tokens)))
         }
-        Ok(None) => {
+        Ok(LoginOutcome::Failed { .. }) => {
             warn!("Failed login attempt for user '{}'", req.username);
             Err(StatusCode::UNAUTHORIZED)
         }
+        Ok(LoginOutcome::Locked(_)) => Err(StatusCode::TOO_MANY_REQUESTS),
         Err(e) => {
             error!("Authentication error: {}", e);
             Err(StatusCode::INTERNAL_SERVER_ERROR)
@@ -498,10 +1643,57 @@ mod tests {
         assert!(!bcrypt::verify("wrong", &hash).unwrap());
     }
 
-    #[test]
-    fn test_jwt_generation() {
+    #[tokio::test]
+    async fn test_password_policy_and_expiry() {
+        let policy = PasswordPolicy {
+            min_length: 8,
+            require_special: false,
+            banned: vec!["Summer2024a".to_string()],
+            max_age_days: Some(90),
+            ..PasswordPolicy::default()
+        };
+        assert!(policy.validate("Abcdefg1").is_valid);
+        assert!(!policy.validate("Abcdef1").is_valid);
+        assert!(!policy.validate("abcdefg1").is_valid);
+        assert!(!policy.validate("SUMMER2024a").is_valid);
+        assert!(!validate_password_strength("Abcdefg1").is_valid);
+        assert!(policy.is_expired(0, 90 * 24 * 3600));
+        assert!(!policy.is_expired(1, 90 * 24 * 3600));
+
+        // Logging in with an expired password forces a change
+        let dir = tempfile::tempdir().unwrap();
+        let users_file = dir.path().join("users.json");
+        let stale = User {
+            username: "old".to_string(),
+            password_hash: bcrypt::hash("Abcdefg1", 4).unwrap(),
+            role: "viewer".to_string(),
+            created_at: 0,
+            last_login: None,
+            disabled: false,
+            must_change_password: false,
+            login_countries: Vec::new(),
+            password_changed_at: None,
+        };
+        fs::write(&users_file, serde_json::to_string(&vec![stale]).unwrap()).unwrap();
+        let auth = AuthManager::new("test_secret".to_string())
+            .with_users_file(users_file)
+            .with_password_policy(policy);
+        auth.load().await.unwrap();
+        let user = auth.authenticate("old", "Abcdefg1").await.unwrap().unwrap();
+        assert!(user.must_change_password);
+
+        assert!(auth.change_password("old", "Abcdefg1", "sUMMER2024A").await.is_err());
+        auth.change_password("old", "Abcdefg1", "Newpassw0rd").await.unwrap();
+        let user = auth.authenticate("old", "Newpassw0rd").await.unwrap().unwrap();
+        assert!(!user.must_change_password);
+        assert!(user.password_changed_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_jwt_generation() {
+        let dir = tempfile::tempdir().unwrap();
         let secret = "test_secret".to_string();
-        let auth = AuthManager::new(secret);
+        let auth = AuthManager::new(secret).with_users_file(dir.path().join("users.json"));
 
         let user = User {
             username: "test".to_string(),
@@ -509,12 +1701,280 @@ mod tests {
             role: "user".to_string(),
             created_at: 0,
             last_login: None,
+            disabled: false,
+            must_change_password: false,
+            login_countries: Vec::new(),
+            password_changed_at: None,
         };
 
-        let token = auth.generate_token(&user).unwrap();
-        let claims = auth.verify_token(&token).unwrap();
+        // Tokens are only valid while their session exists
+        let token = auth.generate_token(&user, "no-such-session").unwrap();
+        assert!(auth.verify_token(&token).await.is_err());
+
+        let tokens = auth.create_session(&user).await.unwrap();
+        let claims = auth.verify_token(&tokens.access_token).await.unwrap();
 
         assert_eq!(claims.name, "test");
         assert_eq!(claims.role, "user");
+        assert!(!claims.must_change_password);
+    }
+
+    #[tokio::test]
+    async fn test_jwt_key_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let old_key = JwtKey::new("old-secret-0123456789abcdefghijklmnopq".to_string());
+        let new_key = JwtKey::new("new-secret-0123456789abcdefghijklmnopq".to_string());
+        let auth = AuthManager::from_jwt_keys(JwtKeySet::new(old_key.clone()))
+            .with_users_file(dir.path().join("users.json"));
+        let user = User {
+            username: "test".to_string(),
+            password_hash: "hash".to_string(),
+            role: "user".to_string(),
+            created_at: 0,
+            last_login: None,
+            disabled: false,
+            must_change_password: false,
+            login_countries: Vec::new(),
+            password_changed_at: None,
+        };
+        let old_token = auth.create_session(&user).await.unwrap().access_token;
+        assert_eq!(jsonwebtoken::decode_header(&old_token).unwrap().kid.as_deref(), Some(old_key.kid()));
+        // Tokens from before key IDs carry no `kid`
+        let claims = auth.verify_token(&old_token).await.unwrap();
+        let legacy = jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(), &claims, &EncodingKey::from_secret(old_key.secret()),
+        ).unwrap();
+
+        // Old tokens validate while the old key is kept, new ones use the new key
+        auth.set_jwt_keys(auth.jwt_keys().rotate_to(JwtKeySet::new(new_key.clone())));
+        let new_token = auth.create_session(&user).await.unwrap().access_token;
+        assert_eq!(jsonwebtoken::decode_header(&new_token).unwrap().kid.as_deref(), Some(new_key.kid()));
+        assert!(auth.verify_token(&old_token).await.is_ok());
+        assert!(auth.verify_token(&legacy).await.is_ok());
+        assert!(auth.verify_token(&new_token).await.is_ok());
+
+        auth.set_jwt_keys(JwtKeySet::new(new_key));
+        assert!(auth.verify_token(&old_token).await.is_err());
+        assert!(auth.verify_token(&legacy).await.is_err());
+        assert!(auth.verify_token(&new_token).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_user_management_lifecycle() {
+        let dir = tempfile::tempdir().unwrap();
+        let auth = AuthManager::new("test_secret".to_string())
+            .with_users_file(dir.path().join("users.json"));
+        auth.init_default_admin("admin", "Adm1n!Password").await.unwrap();
+
+        auth.create_user("alice", "Al1ce!Password", "operator").await.unwrap();
+        assert!(auth.create_user("alice", "Al1ce!Password", "operator").await.is_err());
+        assert!(auth.create_user("bob", "Bob!Password12", "root").await.is_err());
+
+        // New accounts must change their password on first login
+        let alice = auth.authenticate("alice", "Al1ce!Password").await.unwrap().unwrap();
+        assert!(alice.must_change_password);
+        let tokens = auth.create_session(&alice).await.unwrap();
+        let claims = auth.verify_token(&tokens.access_token).await.unwrap();
+        assert!(claims.must_change_password);
+
+        assert!(auth.change_password("alice", "wrong", "N3w!Password99").await.is_err());
+        auth.change_password("alice", "Al1ce!Password", "N3w!Password99").await.unwrap();
+        assert!(!auth.get_user("alice").await.unwrap().must_change_password);
+        // Changing the password ends existing sessions
+        assert!(auth.verify_token(&tokens.access_token).await.is_err());
+
+        auth.reset_password("alice", "R3set!Password").await.unwrap();
+        assert!(auth.get_user("alice").await.unwrap().must_change_password);
+
+        auth.set_disabled("alice", true).await.unwrap();
+        assert!(auth.authenticate("alice", "R3set!Password").await.unwrap().is_none());
+        auth.set_disabled("alice", false).await.unwrap();
+        assert!(auth.authenticate("alice", "R3set!Password").await.unwrap().is_some());
+
+        // Only a country new to a user who has logged in elsewhere is reported
+        assert_eq!(auth.record_login_country("alice", "DE").await.unwrap(), None);
+        assert_eq!(auth.record_login_country("alice", "DE").await.unwrap(), None);
+        assert_eq!(auth.record_login_country("alice", "BR").await.unwrap(), Some(vec!["DE".to_string()]));
+
+        // Changes survive a reload
+        let reloaded = AuthManager::new("test_secret".to_string())
+            .with_users_file(dir.path().join("users.json"));
+        reloaded.load().await.unwrap();
+        assert_eq!(reloaded.list_users().await.len(), 2);
+
+        auth.delete_user("alice").await.unwrap();
+        assert_eq!(auth.list_users().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_login_lockout() {
+        let dir = tempfile::tempdir().unwrap();
+        let auth = AuthManager::new("test_secret".to_string())
+            .with_users_file(dir.path().join("users.json"))
+            .with_lockout_policy(LockoutPolicy { max_user_failures: 2, max_ip_failures: 3, lockout_secs: 60 });
+        auth.init_default_admin("admin", "Adm1n!Password").await.unwrap();
+        let ip: IpAddr = "203.0.113.9".parse().unwrap();
+
+        // A success resets the count
+        assert!(matches!(auth.login("admin", "wrong", Some(ip)).await.unwrap(), LoginOutcome::Failed { ref locked } if locked.is_empty()));
+        assert!(matches!(auth.login("admin", "Adm1n!Password", Some(ip)).await.unwrap(), LoginOutcome::Success(_)));
+        assert!(matches!(auth.login("admin", "wrong", Some(ip)).await.unwrap(), LoginOutcome::Failed { ref locked } if locked.is_empty()));
+        match auth.login("admin", "wrong", Some(ip)).await.unwrap() {
+            LoginOutcome::Failed { locked } => assert_eq!(locked[0].target, LockoutTarget::User("admin".into())),
+            _ => panic!("expected a failure"),
+        }
+        // The right password is refused while locked
+        assert!(matches!(auth.login("admin", "Adm1n!Password", None).await.unwrap(), LoginOutcome::Locked(_)));
+
+        // The address is locked after failures across accounts
+        match auth.login("nobody", "wrong", Some(ip)).await.unwrap() {
+            LoginOutcome::Failed { locked } => assert_eq!(locked[0].target, LockoutTarget::Ip(ip)),
+            _ => panic!("expected a failure"),
+        }
+        assert_eq!(auth.lockouts().await.len(), 2);
+
+        assert!(auth.unlock(&LockoutTarget::User("admin".into())).await);
+        assert!(matches!(auth.login("admin", "Adm1n!Password", Some(ip)).await.unwrap(), LoginOutcome::Locked(_)));
+        assert!(auth.unlock(&LockoutTarget::Ip(ip)).await);
+        assert!(matches!(auth.login("admin", "Adm1n!Password", Some(ip)).await.unwrap(), LoginOutcome::Success(_)));
+        assert!(!auth.unlock(&LockoutTarget::Ip(ip)).await);
+    }
+
+    #[tokio::test]
+    async fn test_last_admin_is_protected() {
+        let dir = tempfile::tempdir().unwrap();
+        let auth = AuthManager::new("test_secret".to_string())
+            .with_users_file(dir.path().join("users.json"));
+        auth.init_default_admin("admin", "Adm1n!Password").await.unwrap();
+
+        assert!(auth.set_disabled("admin", true).await.is_err());
+        assert!(auth.delete_user("admin").await.is_err());
+
+        auth.create_user("second", "S3cond!Password", "admin").await.unwrap();
+        auth.delete_user("admin").await.unwrap();
+        assert!(auth.delete_user("second").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_rotation_and_revocation() {
+        let dir = tempfile::tempdir().unwrap();
+        let auth = AuthManager::new("test_secret".to_string())
+            .with_users_file(dir.path().join("users.json"));
+        auth.init_default_admin("admin", "Adm1n!Password").await.unwrap();
+        let admin = auth.get_user("admin").await.unwrap();
+
+        let first = auth.create_session(&admin).await.unwrap();
+        let (_, second) = auth.refresh_session(&first.refresh_token).await.unwrap();
+        assert_ne!(first.refresh_token, second.refresh_token);
+        // Old refresh token is single-use
+        assert!(auth.refresh_session(&first.refresh_token).await.is_err());
+
+        // Sessions survive a reload
+        let reloaded = AuthManager::new("test_secret".to_string())
+            .with_users_file(dir.path().join("users.json"));
+        reloaded.load().await.unwrap();
+        let claims = reloaded.verify_token(&second.access_token).await.unwrap();
+
+        // Logout revokes the session and its refresh token
+        assert!(auth.revoke_session(&claims.sid).await);
+        assert!(auth.verify_token(&second.access_token).await.is_err());
+        assert!(auth.refresh_session(&second.refresh_token).await.is_err());
+
+        auth.create_session(&admin).await.unwrap();
+        auth.create_session(&admin).await.unwrap();
+        assert_eq!(auth.revoke_user_sessions("admin").await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_session_and_login_activity() {
+        let dir = tempfile::tempdir().unwrap();
+        let auth = AuthManager::new("test_secret".to_string())
+            .with_users_file(dir.path().join("users.json"));
+        auth.init_default_admin("admin", "Adm1n!Password").await.unwrap();
+        let admin = auth.get_user("admin").await.unwrap();
+
+        let ip: IpAddr = "192.0.2.10".parse().unwrap();
+        let tokens = auth.create_session_from(&admin, Some(ip), Some("curl/8.5")).await.unwrap();
+        auth.issue_miner_token("bc1qminer", &[SCOPE_MINER_READ.to_string()], 3600, Some("admin")).await.unwrap();
+        let sessions = auth.sessions(None).await;
+        assert_eq!(sessions.len(), 1);
+        assert_eq!((sessions[0].ip, sessions[0].user_agent.as_deref()), (Some(ip), Some("curl/8.5")));
+        let claims = auth.verify_token(&tokens.access_token).await.unwrap();
+        assert_eq!(auth.session(&claims.sid).await.unwrap().username, "admin");
+
+        auth.record_login_attempt(LoginAttempt::new("admin", Some(ip), None, LoginResult::Failed)).await;
+        auth.record_login_attempt(LoginAttempt::new("admin", Some(ip), None, LoginResult::Success)).await;
+        auth.record_login_attempt(LoginAttempt::new("bob", None, None, LoginResult::Failed)).await;
+        let reloaded = AuthManager::new("test_secret".to_string())
+            .with_users_file(dir.path().join("users.json"));
+        reloaded.load().await.unwrap();
+        let attempts = reloaded.login_attempts(Some("admin"), None, 10).await;
+        assert_eq!(attempts.iter().map(|a| a.result).collect::<Vec<_>>(), vec![LoginResult::Success, LoginResult::Failed]);
+        assert_eq!(reloaded.login_attempts(None, Some(LoginResult::Failed), 1).await[0].username, "bob");
+    }
+
+    #[tokio::test]
+    async fn test_two_factor_policy_flags_tokens() {
+        use crate::two_factor::TwoFactorPolicy;
+        use totp_rs::{Algorithm, TOTP};
+
+        let dir = tempfile::tempdir().unwrap();
+        let two_factor = Arc::new(
+            TwoFactorManager::new(dir.path().join("2fa"), "TestApp".to_string())
+                .with_policy(TwoFactorPolicy { required_roles: vec!["admin".to_string()], ..Default::default() }),
+        );
+        two_factor.initialize().await.unwrap();
+        let auth = AuthManager::new("test_secret".to_string())
+            .with_users_file(dir.path().join("users.json"))
+            .with_two_factor(two_factor.clone());
+        auth.init_default_admin("admin", "Adm1n!Password").await.unwrap();
+        auth.create_user("watcher", "W4tcher!Password", "viewer").await.unwrap();
+
+        let admin = auth.get_user("admin").await.unwrap();
+        let tokens = auth.create_session(&admin).await.unwrap();
+        assert!(auth.verify_token(&tokens.access_token).await.unwrap().must_enroll_2fa);
+        let viewer = auth.get_user("watcher").await.unwrap();
+        let viewer_tokens = auth.create_session(&viewer).await.unwrap();
+        assert!(!auth.verify_token(&viewer_tokens.access_token).await.unwrap().must_enroll_2fa);
+
+        // Enrolling lifts the restriction from the same token
+        let setup = two_factor.generate_secret("admin").await.unwrap();
+        let secret = base32::decode(base32::Alphabet::Rfc4648 { padding: true }, &setup.secret).unwrap();
+        let code = TOTP::new(Algorithm::SHA1, 6, 1, 30, secret, None, String::new())
+            .unwrap()
+            .generate_current()
+            .unwrap();
+        assert!(two_factor.enable_2fa("admin", &code).await.unwrap());
+        assert!(!auth.verify_token(&tokens.access_token).await.unwrap().must_enroll_2fa);
+        assert!(two_factor.status_for("admin", "admin").await.required);
+    }
+
+    #[tokio::test]
+    async fn test_miner_tokens() {
+        let dir = tempfile::tempdir().unwrap();
+        let auth = AuthManager::new("test_secret".to_string())
+            .with_users_file(dir.path().join("users.json"));
+        let address = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+
+        let scopes = vec![SCOPE_MINER_READ.to_string(), SCOPE_MINER_READ.to_string()];
+        let (token, info) = auth.issue_miner_token(address, &scopes, MINER_TOKEN_TTL_SECS, Some("admin")).await.unwrap();
+        assert_eq!(info.scopes, vec![SCOPE_MINER_READ.to_string()]);
+        let claims = auth.verify_token(&token).await.unwrap();
+        assert_eq!((claims.name.as_str(), claims.role.as_str()), (address, MINER_ROLE));
+        assert!(claims.has_scope(SCOPE_MINER_READ));
+        assert!(!claims.has_scope(SCOPE_MINER_PAYOUT));
+
+        assert!(auth.issue_miner_token(address, &[], MINER_TOKEN_TTL_SECS, None).await.is_err());
+        assert!(auth.issue_miner_token(address, &["admin".to_string()], MINER_TOKEN_TTL_SECS, None).await.is_err());
+        assert!(auth.issue_miner_token(address, &scopes, MAX_MINER_TOKEN_TTL_SECS + 1, None).await.is_err());
+
+        // Not revoked with a user's sessions, and can't be refreshed
+        assert_eq!(auth.revoke_user_sessions(address).await, 0);
+        assert!(auth.refresh_session("").await.is_err());
+        assert_eq!(auth.miner_tokens(Some(address)).await.len(), 1);
+
+        assert!(auth.revoke_miner_token(&info.id).await);
+        assert!(auth.verify_token(&token).await.is_err());
+        assert!(auth.miner_tokens(None).await.is_empty());
     }
 }
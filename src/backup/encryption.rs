@@ -0,0 +1,262 @@
+// Backup Encryption for DMPool
+// AES-256-GCM over fixed-size chunks, so large archives never have to fit in memory
+
+use crate::secrets::SecretStore;
+use aes_gcm::{
+    aead::{Aead, KeyInit, OsRng},
+    Aes256Gcm, Nonce,
+};
+use anyhow::{Context, Result};
+use base64::{Engine as _, engine::general_purpose};
+use rand::RngCore;
+use std::fmt;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// First bytes of every encrypted archive
+const MAGIC: &[u8; 8] = b"DMPENC01";
+/// Plaintext bytes per chunk
+const CHUNK_SIZE: usize = 1024 * 1024;
+/// Random part of each chunk nonce; the rest is the chunk counter and a last-chunk flag
+const NONCE_PREFIX_LEN: usize = 7;
+/// GCM authentication tag
+const TAG_LEN: usize = 16;
+
+/// File name suffix of encrypted archives
+pub const ENCRYPTED_SUFFIX: &str = ".enc";
+
+/// AES-256 key for backup archives
+#[derive(Clone)]
+pub struct BackupKey {
+    key: [u8; 32],
+}
+
+impl fmt::Debug for BackupKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BackupKey({})", self.fingerprint())
+    }
+}
+
+impl BackupKey {
+    pub fn new(key: [u8; 32]) -> Self {
+        Self { key }
+    }
+
+    /// Key from base64 of 32 bytes
+    pub fn from_base64(encoded: &str) -> Result<Self> {
+        let bytes = general_purpose::STANDARD
+            .decode(encoded.trim())
+            .context("Backup encryption key is not valid base64")?;
+        let key: [u8; 32] = bytes.try_into()
+            .map_err(|_| anyhow::anyhow!("Backup encryption key must be 32 bytes (256 bits)"))?;
+        Ok(Self::new(key))
+    }
+
+    /// Key from a file holding either 32 raw bytes or their base64
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = fs::read(path)
+            .with_context(|| format!("Failed to read backup key file {:?}", path))?;
+        match <[u8; 32]>::try_from(contents.as_slice()) {
+            Ok(key) => Ok(Self::new(key)),
+            Err(_) => Self::from_base64(&String::from_utf8_lossy(&contents)),
+        }
+    }
+
+    /// Key from `BACKUP_ENCRYPTION_KEY`, or the file named by `BACKUP_ENCRYPTION_KEY_FILE`
+    ///
+    /// Returns `None` when neither is set and backups stay unencrypted.
+    pub fn from_env() -> Result<Option<Self>> {
+        if let Ok(encoded) = std::env::var("BACKUP_ENCRYPTION_KEY") {
+            return Self::from_base64(&encoded).map(Some);
+        }
+        if let Ok(path) = std::env::var("BACKUP_ENCRYPTION_KEY_FILE") {
+            return Self::from_file(Path::new(&path)).map(Some);
+        }
+        Ok(None)
+    }
+
+    /// Key from the `BACKUP_ENCRYPTION_KEY` secret of `secrets`, or the file
+    /// named by `BACKUP_ENCRYPTION_KEY_FILE`
+    pub async fn from_secrets(secrets: &SecretStore) -> Result<Option<Self>> {
+        if let Some(encoded) = secrets.get("BACKUP_ENCRYPTION_KEY").await? {
+            return Self::from_base64(&encoded).map(Some);
+        }
+        if let Ok(path) = std::env::var("BACKUP_ENCRYPTION_KEY_FILE") {
+            return Self::from_file(Path::new(&path)).map(Some);
+        }
+        Ok(None)
+    }
+
+    /// Short identifier of the key, recorded with each backup it encrypts
+    pub fn fingerprint(&self) -> String {
+        use sha2::{Digest, Sha256};
+        let digest = Sha256::digest(self.key);
+        digest[..8].iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new((&self.key).into())
+    }
+}
+
+fn chunk_nonce(prefix: &[u8; NONCE_PREFIX_LEN], counter: u32, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..NONCE_PREFIX_LEN].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_LEN..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+/// Read until `buf` is full or the input ends, returning the bytes read
+fn read_full(input: &mut impl Read, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match input.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+/// Whether `path` starts like an encrypted archive
+pub fn is_encrypted(path: &Path) -> Result<bool> {
+    let mut file = fs::File::open(path).context("Failed to open backup file")?;
+    let mut magic = [0u8; 8];
+    Ok(read_full(&mut file, &mut magic)? == MAGIC.len() && &magic == MAGIC)
+}
+
+/// Encrypt `src` into `dest`
+///
+/// Each chunk's nonce carries its position and whether it is the last one,
+/// so reordered, dropped or truncated chunks fail to decrypt.
+pub fn encrypt_file(src: &Path, dest: &Path, key: &BackupKey) -> Result<()> {
+    let cipher = key.cipher();
+    let mut prefix = [0u8; NONCE_PREFIX_LEN];
+    OsRng.fill_bytes(&mut prefix);
+
+    let mut input = fs::File::open(src).context("Failed to open archive for encryption")?;
+    let mut output = std::io::BufWriter::new(
+        fs::File::create(dest).context("Failed to create encrypted archive")?,
+    );
+    output.write_all(MAGIC)?;
+    output.write_all(&prefix)?;
+
+    let mut chunk = vec![0u8; CHUNK_SIZE];
+    let mut next = vec![0u8; CHUNK_SIZE];
+    let mut len = read_full(&mut input, &mut chunk)?;
+    let mut counter: u32 = 0;
+    loop {
+        // A full chunk may still be the last; look ahead to find out
+        let next_len = if len == CHUNK_SIZE { read_full(&mut input, &mut next)? } else { 0 };
+        let last = next_len == 0;
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&chunk_nonce(&prefix, counter, last)), &chunk[..len])
+            .map_err(|e| anyhow::anyhow!("Encryption failed: {}", e))?;
+        output.write_all(&(ciphertext.len() as u32).to_be_bytes())?;
+        output.write_all(&ciphertext)?;
+        if last {
+            break;
+        }
+        std::mem::swap(&mut chunk, &mut next);
+        len = next_len;
+        counter = counter.checked_add(1)
+            .ok_or_else(|| anyhow::anyhow!("Archive too large to encrypt"))?;
+    }
+    output.flush().context("Failed to write encrypted archive")?;
+    Ok(())
+}
+
+/// Read the length prefix of the next chunk, `None` at the end of the file
+fn read_chunk_len(input: &mut impl Read) -> Result<Option<usize>> {
+    let mut len = [0u8; 4];
+    match read_full(input, &mut len)? {
+        0 => Ok(None),
+        4 => {
+            let len = u32::from_be_bytes(len) as usize;
+            if !(TAG_LEN..=CHUNK_SIZE + TAG_LEN).contains(&len) {
+                return Err(anyhow::anyhow!("Encrypted archive is corrupted: invalid chunk length"));
+            }
+            Ok(Some(len))
+        }
+        _ => Err(anyhow::anyhow!("Encrypted archive is truncated")),
+    }
+}
+
+/// Decrypt `src`, written by [`encrypt_file`], into `dest`
+pub fn decrypt_file(src: &Path, dest: &Path, key: &BackupKey) -> Result<()> {
+    let cipher = key.cipher();
+    let mut input = std::io::BufReader::new(
+        fs::File::open(src).context("Failed to open encrypted archive")?,
+    );
+    let mut header = [0u8; 8 + NONCE_PREFIX_LEN];
+    if read_full(&mut input, &mut header)? != header.len() || &header[..8] != MAGIC {
+        return Err(anyhow::anyhow!("Not an encrypted backup archive"));
+    }
+    let prefix: [u8; NONCE_PREFIX_LEN] = header[8..].try_into()?;
+
+    let mut output = std::io::BufWriter::new(
+        fs::File::create(dest).context("Failed to create decrypted archive")?,
+    );
+    let mut len = read_chunk_len(&mut input)?
+        .ok_or_else(|| anyhow::anyhow!("Encrypted archive is truncated"))?;
+    let mut counter: u32 = 0;
+    loop {
+        let mut ciphertext = vec![0u8; len];
+        if read_full(&mut input, &mut ciphertext)? != len {
+            return Err(anyhow::anyhow!("Encrypted archive is truncated"));
+        }
+        let next_len = read_chunk_len(&mut input)?;
+        let last = next_len.is_none();
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(&chunk_nonce(&prefix, counter, last)), ciphertext.as_slice())
+            .map_err(|_| anyhow::anyhow!("Failed to decrypt backup: wrong key or corrupted archive"))?;
+        output.write_all(&plaintext)?;
+        match next_len {
+            Some(next_len) => len = next_len,
+            None => break,
+        }
+        counter = counter.checked_add(1)
+            .ok_or_else(|| anyhow::anyhow!("Encrypted archive is corrupted: too many chunks"))?;
+    }
+    output.flush().context("Failed to write decrypted archive")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_and_tamper_detection() {
+        let dir = tempfile::tempdir().unwrap();
+        let plain = dir.path().join("archive.tar.gz");
+        let encrypted = dir.path().join("archive.tar.gz.enc");
+        let decrypted = dir.path().join("decrypted");
+        // Exactly two chunks, so the last one is found by looking ahead
+        let data: Vec<u8> = (0..2 * CHUNK_SIZE).map(|i| (i % 251) as u8).collect();
+        fs::write(&plain, &data).unwrap();
+
+        let key = BackupKey::new([7u8; 32]);
+        encrypt_file(&plain, &encrypted, &key).unwrap();
+        assert!(is_encrypted(&encrypted).unwrap());
+        assert!(!is_encrypted(&plain).unwrap());
+        decrypt_file(&encrypted, &decrypted, &key).unwrap();
+        assert_eq!(fs::read(&decrypted).unwrap(), data);
+
+        let wrong = BackupKey::new([8u8; 32]);
+        assert_ne!(key.fingerprint(), wrong.fingerprint());
+        assert!(decrypt_file(&encrypted, &decrypted, &wrong).is_err());
+
+        // Dropping the last chunk must not go unnoticed
+        let bytes = fs::read(&encrypted).unwrap();
+        let first_chunk_end = 8 + NONCE_PREFIX_LEN + 4 + CHUNK_SIZE + TAG_LEN;
+        fs::write(&encrypted, &bytes[..first_chunk_end]).unwrap();
+        assert!(decrypt_file(&encrypted, &decrypted, &key).is_err());
+
+        let encoded = general_purpose::STANDARD.encode([7u8; 32]);
+        assert_eq!(BackupKey::from_base64(&encoded).unwrap().fingerprint(), key.fingerprint());
+        assert!(BackupKey::from_base64("c2hvcnQ=").is_err());
+    }
+}
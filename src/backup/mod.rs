@@ -1,13 +1,25 @@
 // Backup Module for DMPool
 // Handles database backup, compression, validation, and recovery
 
+mod encryption;
+
+pub use encryption::BackupKey;
+
+use crate::cron::CronExpr;
+use crate::jobs::Progress;
+use crate::storage::{self, ColumnFamilyCopy};
 use anyhow::{Context, Result};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Datelike, Duration, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
+use std::io::{BufRead, BufReader, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
-use tracing::info;
+use std::process::{Command, ExitStatus, Stdio};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
 
 /// Validate a path is safe for use with external commands
 fn validate_safe_path(path: &Path) -> Result<()> {
@@ -71,6 +83,61 @@ fn safe_path_str(path: &Path) -> Result<String> {
         .to_string())
 }
 
+/// How often a running tar is checked for exit and cancellation
+/// On-disk format of the share store; bump when a backup of the old format
+/// can no longer be opened as is, and register a [`BackupMigration`]
+pub const STORE_FORMAT_VERSION: u32 = 1;
+
+/// Layout of the PPLNS share data in the store
+pub const PPLNS_SCHEMA_VERSION: u32 = 1;
+
+/// Index of every backup's metadata in the backup dir, so listing reads one file
+const CATALOG_FILE: &str = "catalog.json";
+
+const TAR_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Run tar with `-v`, counting each file it lists into `progress`
+///
+/// Listed paths are looked up under `root` once tar has moved on to the next
+/// one, so each file is counted at its full size. tar is killed if the job is
+/// cancelled.
+fn run_tar(tar: &mut Command, root: &Path, progress: &Progress) -> Result<ExitStatus> {
+    let mut child = tar.stdout(Stdio::piped()).spawn().context("Failed to execute tar command")?;
+    let stdout = child.stdout.take().ok_or_else(|| anyhow::anyhow!("tar has no output"))?;
+    let count = |path: PathBuf| {
+        if let Ok(meta) = fs::metadata(&path) {
+            if meta.is_file() {
+                progress.add_file(meta.len());
+            }
+        }
+    };
+    std::thread::scope(|scope| {
+        // Read on a separate thread so a large file doesn't hold up cancellation
+        scope.spawn(|| {
+            let mut previous = None;
+            for line in BufReader::new(stdout).lines().map_while(|l| l.ok()) {
+                if let Some(done) = previous.replace(root.join(line.trim_end_matches('/'))) {
+                    count(done);
+                }
+            }
+            if let Some(done) = previous {
+                count(done);
+            }
+        });
+        loop {
+            if let Some(status) = child.try_wait().context("Failed to wait for tar")? {
+                return Ok(status);
+            }
+            if progress.is_cancelled() {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(anyhow::anyhow!("Cancelled"));
+            }
+            std::thread::sleep(TAR_POLL_INTERVAL);
+        }
+    })
+}
+
 /// Backup configuration
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BackupConfig {
@@ -78,12 +145,33 @@ pub struct BackupConfig {
     pub db_path: PathBuf,
     /// Backup directory
     pub backup_dir: PathBuf,
-    /// Number of backups to retain
-    pub retention_count: usize,
+    /// Which backups cleanup keeps
+    #[serde(default)]
+    pub retention: RetentionPolicy,
     /// Enable compression (gzip)
     pub compress: bool,
-    /// Backup interval in hours
-    pub interval_hours: u64,
+    /// Free space a backup must leave on the backup volume
+    #[serde(default)]
+    pub min_free_bytes: u64,
+    /// When backups are taken
+    #[serde(default = "default_schedules")]
+    pub schedules: Vec<BackupSchedule>,
+    /// Largest archive accepted for upload
+    #[serde(default = "default_max_upload_bytes")]
+    pub max_upload_bytes: u64,
+}
+
+/// A daily full backup at 03:00 UTC
+fn default_schedules() -> Vec<BackupSchedule> {
+    vec![BackupSchedule {
+        kind: BackupKind::Full,
+        cron: CronExpr::daily(3, 0),
+    }]
+}
+
+/// 50 GB
+fn default_max_upload_bytes() -> u64 {
+    50 * 1024 * 1024 * 1024
 }
 
 impl Default for BackupConfig {
@@ -91,13 +179,338 @@ impl Default for BackupConfig {
         Self {
             db_path: PathBuf::from("./data"),
             backup_dir: PathBuf::from("./backups"),
-            retention_count: 7,
+            retention: RetentionPolicy::default(),
+            compress: true,
+            min_free_bytes: 0,
+            schedules: default_schedules(),
+            max_upload_bytes: default_max_upload_bytes(),
+        }
+    }
+}
+
+impl BackupConfig {
+    /// Backups of `db_path` configured by `BACKUP_SCHEDULES`, `BACKUP_RETENTION`,
+    /// `BACKUP_MIN_FREE_MB` and `BACKUP_UPLOAD_MAX_MB`
+    ///
+    /// Schedules are `kind=cron` pairs separated by `;`.
+    pub fn from_env(db_path: impl Into<PathBuf>) -> Result<Self> {
+        let schedules = match std::env::var("BACKUP_SCHEDULES") {
+            Ok(schedules) => schedules
+                .split(';')
+                .filter(|s| !s.trim().is_empty())
+                .map(|s| s.parse::<BackupSchedule>())
+                .collect::<Result<Vec<_>>>()?,
+            Err(_) => default_schedules(),
+        };
+        Ok(Self {
+            db_path: db_path.into(),
+            backup_dir: PathBuf::from("./backups"),
+            retention: match std::env::var("BACKUP_RETENTION") {
+                Ok(policy) => policy.parse()?,
+                Err(_) => RetentionPolicy::default(),
+            },
             compress: true,
-            interval_hours: 24,
+            min_free_bytes: std::env::var("BACKUP_MIN_FREE_MB")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(1024)
+                * 1024
+                * 1024,
+            schedules,
+            max_upload_bytes: std::env::var("BACKUP_UPLOAD_MAX_MB")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .map(|mb| mb * 1024 * 1024)
+                .unwrap_or_else(default_max_upload_bytes),
+        })
+    }
+}
+
+/// Which backups cleanup keeps
+///
+/// The newest `keep_last` backups are always kept. The daily, weekly and
+/// monthly tiers also keep the newest backup of each of the last N days, ISO
+/// weeks and months that have one; of those extras, ones older than
+/// `max_age_days` are dropped, then the oldest until every kept backup fits in
+/// `max_total_bytes`. A full backup is kept while a kept incremental needs it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    pub keep_last: usize,
+    #[serde(default)]
+    pub keep_daily: usize,
+    #[serde(default)]
+    pub keep_weekly: usize,
+    #[serde(default)]
+    pub keep_monthly: usize,
+    #[serde(default)]
+    pub max_age_days: Option<u64>,
+    #[serde(default)]
+    pub max_total_bytes: Option<u64>,
+}
+
+impl Default for RetentionPolicy {
+    /// The 7 newest backups
+    fn default() -> Self {
+        Self {
+            keep_last: 7,
+            keep_daily: 0,
+            keep_weekly: 0,
+            keep_monthly: 0,
+            max_age_days: None,
+            max_total_bytes: None,
+        }
+    }
+}
+
+impl FromStr for RetentionPolicy {
+    type Err = anyhow::Error;
+
+    /// Parse `key=value` pairs separated by ',', e.g.
+    /// `last=3,daily=7,weekly=4,monthly=12,max_age_days=400,max_total_mb=20000`;
+    /// unset keys keep their defaults
+    fn from_str(s: &str) -> Result<Self> {
+        let mut policy = Self::default();
+        for pair in s.split(',').filter(|p| !p.trim().is_empty()) {
+            let (key, value) = pair.split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("Backup retention must look like daily=7: {}", pair))?;
+            let value: u64 = value.trim().parse()
+                .with_context(|| format!("Invalid backup retention value: {}", pair))?;
+            match key.trim() {
+                "last" => policy.keep_last = value as usize,
+                "daily" => policy.keep_daily = value as usize,
+                "weekly" => policy.keep_weekly = value as usize,
+                "monthly" => policy.keep_monthly = value as usize,
+                "max_age_days" => policy.max_age_days = Some(value),
+                "max_total_mb" => policy.max_total_bytes = Some(value * 1024 * 1024),
+                other => return Err(anyhow::anyhow!(
+                    "Unknown backup retention key: {} (use last, daily, weekly, monthly, max_age_days or max_total_mb)",
+                    other
+                )),
+            }
+        }
+        Ok(policy)
+    }
+}
+
+/// The day, ISO week or month a backup falls in
+type PeriodKey = fn(&DateTime<Utc>) -> (i32, u32, u32);
+
+impl RetentionPolicy {
+    /// IDs of the backups to keep out of `backups`, which are newest first
+    pub fn retained(&self, backups: &[BackupMetadata], now: DateTime<Utc>) -> HashSet<String> {
+        let floor = self.keep_last.min(backups.len());
+        let mut extras: Vec<&BackupMetadata> = Vec::new();
+        let tiers: [(usize, PeriodKey); 3] = [
+            (self.keep_daily, |t| (t.year(), t.month(), t.day())),
+            (self.keep_weekly, |t| (t.iso_week().year(), t.iso_week().week(), 0)),
+            (self.keep_monthly, |t| (t.year(), t.month(), 0)),
+        ];
+        for (count, period) in tiers {
+            let mut periods = HashSet::new();
+            for backup in backups {
+                if periods.len() == count {
+                    break;
+                }
+                if periods.insert(period(&backup.timestamp)) && !extras.iter().any(|b| b.id == backup.id) {
+                    extras.push(backup);
+                }
+            }
+        }
+        extras.retain(|b| !backups[..floor].iter().any(|kept| kept.id == b.id));
+        if let Some(days) = self.max_age_days {
+            let cutoff = now - Duration::days(days as i64);
+            extras.retain(|b| b.timestamp >= cutoff);
+        }
+        extras.sort_by_key(|b| std::cmp::Reverse(b.timestamp));
+
+        let mut kept: Vec<&BackupMetadata> = backups[..floor].iter().chain(extras).collect();
+        let with_bases = |kept: &[&BackupMetadata]| -> HashSet<String> {
+            let mut ids: HashSet<String> = kept.iter().map(|b| b.id.clone()).collect();
+            ids.extend(kept.iter().filter_map(|b| b.base_id.clone()));
+            ids
+        };
+        if let Some(max_bytes) = self.max_total_bytes {
+            let size = |ids: &HashSet<String>| -> u64 {
+                backups.iter().filter(|b| ids.contains(&b.id)).map(|b| b.backup_size).sum()
+            };
+            while kept.len() > floor && size(&with_bases(&kept)) > max_bytes {
+                kept.pop();
+            }
+        }
+        with_bases(&kept)
+    }
+}
+
+/// What a backup contains
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupKind {
+    /// Every database file
+    #[default]
+    Full,
+    /// Files changed since the latest full backup, which it is restored on top of
+    Incremental,
+}
+
+impl FromStr for BackupKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "full" => Ok(BackupKind::Full),
+            "incremental" => Ok(BackupKind::Incremental),
+            other => Err(anyhow::anyhow!("Unknown backup kind: {} (use full or incremental)", other)),
+        }
+    }
+}
+
+/// A recurring backup
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BackupSchedule {
+    #[serde(default)]
+    pub kind: BackupKind,
+    pub cron: CronExpr,
+}
+
+impl FromStr for BackupSchedule {
+    type Err = anyhow::Error;
+
+    /// Parse `kind=cron`, e.g. `incremental=0 * * * *`
+    fn from_str(s: &str) -> Result<Self> {
+        let (kind, cron) = s.split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Backup schedule must look like full=0 3 * * *: {}", s))?;
+        Ok(Self {
+            kind: kind.parse()?,
+            cron: cron.parse()?,
+        })
+    }
+}
+
+/// Next run of a backup schedule
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScheduledBackup {
+    pub kind: BackupKind,
+    pub cron: String,
+    pub next_run: Option<DateTime<Utc>>,
+}
+
+/// Free space on the backup volume against what a backup needs
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpaceCheck {
+    pub available_bytes: u64,
+    /// Estimated peak use of a backup: the archive, its verification copy and
+    /// the encrypted copy if backups are encrypted
+    pub estimated_bytes: u64,
+    pub min_free_bytes: u64,
+}
+
+impl SpaceCheck {
+    /// Whether a backup would leave at least `min_free_bytes` free
+    pub fn sufficient(&self) -> bool {
+        self.available_bytes >= self.estimated_bytes.saturating_add(self.min_free_bytes)
+    }
+}
+
+/// Bytes available to unprivileged users on the volume holding `path`, or its
+/// nearest existing parent
+#[allow(clippy::unnecessary_cast)]
+pub fn available_space(path: &Path) -> Result<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let existing = path.ancestors()
+        .find(|p| p.exists())
+        .unwrap_or(Path::new("."));
+    let c_path = std::ffi::CString::new(existing.as_os_str().as_bytes())
+        .context("Path contains a NUL byte")?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path is NUL-terminated and stat is a valid statvfs to fill
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("Failed to get free space of {}", existing.display()));
+    }
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// A versioned part of the backed up data
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VersionComponent {
+    StoreFormat,
+    PplnsSchema,
+}
+
+impl std::fmt::Display for VersionComponent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            VersionComponent::StoreFormat => "store format",
+            VersionComponent::PplnsSchema => "PPLNS schema",
+        })
+    }
+}
+
+/// Versions of the software and data formats a backup was taken with
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoreVersions {
+    /// Version of dmpool that took the backup
+    pub crate_version: String,
+    pub store_format: u32,
+    pub pplns_schema: u32,
+}
+
+impl Default for StoreVersions {
+    /// The versions of backups taken before they were recorded
+    fn default() -> Self {
+        Self { crate_version: String::new(), store_format: 1, pplns_schema: 1 }
+    }
+}
+
+impl StoreVersions {
+    /// The versions of this build
+    pub fn current() -> Self {
+        Self {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            store_format: STORE_FORMAT_VERSION,
+            pplns_schema: PPLNS_SCHEMA_VERSION,
+        }
+    }
+
+    pub fn get(&self, component: VersionComponent) -> u32 {
+        match component {
+            VersionComponent::StoreFormat => self.store_format,
+            VersionComponent::PplnsSchema => self.pplns_schema,
         }
     }
 }
 
+/// Upgrades a restored store from one version of a data format to the next
+///
+/// Registered with [`BackupManager::with_migration`]; a restore of an older
+/// backup runs every migration from its versions up to the current ones on
+/// the extracted files, before they replace anything.
+pub trait BackupMigration: Send + Sync {
+    fn name(&self) -> &str;
+    fn component(&self) -> VersionComponent;
+    /// Version this migration upgrades from, to `source_version() + 1`
+    fn source_version(&self) -> u32;
+    /// Upgrade the database directory at `db_path` in place
+    fn migrate(&self, db_path: &Path) -> Result<()>;
+}
+
+/// Whether this build can restore a backup
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BackupCompatibility {
+    /// Reasons the backup can't be restored without forcing it
+    pub problems: Vec<String>,
+    /// Migrations a restore runs on the backed up store, in order
+    pub migrations: Vec<String>,
+}
+
+impl BackupCompatibility {
+    pub fn compatible(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
 /// Backup metadata
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BackupMetadata {
@@ -119,6 +532,48 @@ pub struct BackupMetadata {
     pub schema_version: u32,
     /// Checksum for integrity verification
     pub checksum: String,
+    /// Per-file SHA-256 checksums of the archive contents (relative path -> hex digest)
+    #[serde(default)]
+    pub file_checksums: BTreeMap<String, String>,
+    /// Whether the archive was built from a consistent checkpoint rather than live files
+    #[serde(default)]
+    pub from_checkpoint: bool,
+    /// Fingerprint of the key the archive is encrypted with, if any
+    #[serde(default)]
+    pub encryption_key_id: Option<String>,
+    #[serde(default)]
+    pub kind: BackupKind,
+    /// Full backup an incremental backup is restored on top of
+    #[serde(default)]
+    pub base_id: Option<String>,
+    /// Files of the base backup deleted since, removed when restoring an incremental backup
+    #[serde(default)]
+    pub deleted_files: Vec<String>,
+    /// SST files of each column family at backup time, relative to the archive root;
+    /// empty if the store couldn't be read
+    #[serde(default)]
+    pub column_families: BTreeMap<String, Vec<String>>,
+    /// Versions the backup was taken with; the defaults for older backups
+    #[serde(default)]
+    pub versions: StoreVersions,
+}
+
+/// Result of verifying every file inside a backup archive
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BackupVerification {
+    pub backup_id: String,
+    /// True when the archive checksum and every file checksum match
+    pub valid: bool,
+    pub archive_checksum_ok: bool,
+    pub files_checked: usize,
+    /// Files whose content no longer matches the recorded checksum
+    pub corrupted_files: Vec<String>,
+    /// Files recorded at backup time but absent from the archive
+    pub missing_files: Vec<String>,
+    /// Files present in the archive but not recorded at backup time
+    pub unexpected_files: Vec<String>,
+    /// Set if the archive could not be extracted for inspection
+    pub error: Option<String>,
 }
 
 /// Backup statistics
@@ -129,22 +584,207 @@ pub struct BackupStats {
     pub latest_backup: Option<DateTime<Utc>>,
     pub oldest_backup: Option<DateTime<Utc>>,
     pub disk_usage_bytes: u64,
+    /// Configured schedules and when they next run
+    #[serde(default)]
+    pub schedules: Vec<ScheduledBackup>,
+    #[serde(default)]
+    pub retention: RetentionPolicy,
+    /// Free space on the backup volume; `None` if it couldn't be read
+    #[serde(default)]
+    pub space: Option<SpaceCheck>,
+}
+
+/// Differences found when rebuilding the backup catalog from the metadata files
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CatalogRepair {
+    pub total_backups: usize,
+    /// Backups on disk missing from the catalog
+    pub added: Vec<String>,
+    /// Catalog entries with no metadata file left
+    pub removed: Vec<String>,
+    /// Catalog entries that no longer matched their metadata file
+    pub updated: Vec<String>,
+}
+
+impl CatalogRepair {
+    pub fn drifted(&self) -> bool {
+        !(self.added.is_empty() && self.removed.is_empty() && self.updated.is_empty())
+    }
+}
+
+/// What is known about an uploaded archive, checked when it is imported
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BackupImport {
+    /// Expected SHA-256 of the archive as uploaded, such as the `ETag` it was downloaded with
+    #[serde(default)]
+    pub checksum: Option<String>,
+    /// When the backup was taken; the time of the import if unknown
+    #[serde(default)]
+    pub taken_at: Option<DateTime<Utc>>,
+}
+
+/// Options for restoring a backup
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RestoreOptions {
+    /// Only report what the restore would change
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Restore into this directory instead of over the live database
+    #[serde(default)]
+    pub target_dir: Option<PathBuf>,
+    /// Restore only these column families, leaving the others untouched
+    #[serde(default)]
+    pub column_families: Vec<String>,
+    /// Restore even if the backup isn't compatible with this version
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// How a restore changes a file
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileChange {
+    /// In the backup but not at the target
+    Added,
+    /// At both, with different contents
+    Modified,
+    /// At the target but not in the backup; deleted by the restore
+    Removed,
+}
+
+/// A file a restore would change
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RestoreFileChange {
+    pub path: String,
+    pub change: FileChange,
+    pub current_size: Option<u64>,
+    pub backup_size: Option<u64>,
+    /// Backup size minus current size
+    pub size_delta: i64,
+}
+
+/// What a restore changed, or would change on a dry run
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RestorePlan {
+    pub backup_id: String,
+    pub backup_timestamp: DateTime<Utc>,
+    pub dry_run: bool,
+    /// Directory the archive is extracted into
+    pub target: PathBuf,
+    pub backup_schema_version: u32,
+    pub current_schema_version: u32,
+    /// Whether this version can read the backup's schema
+    pub compatible: bool,
+    #[serde(default)]
+    pub compatibility: BackupCompatibility,
+    pub changes: Vec<RestoreFileChange>,
+    pub unchanged_files: usize,
+    /// Total size change of the restored files
+    pub size_delta: i64,
+    /// Column families replaced by a column family restore; empty for a full restore
+    #[serde(default)]
+    pub column_families: Vec<ColumnFamilyCopy>,
+}
+
+/// Source of consistent on-disk snapshots of a live database
+///
+/// Checkpoints need the read-write handle held by the pool: a read-only
+/// RocksDB instance cannot pause file deletions and will refuse.
+pub trait CheckpointSource: Send + Sync {
+    /// Write a checkpoint of the database into `dest`, which must not exist yet
+    fn create_checkpoint(&self, dest: &Path) -> Result<()>;
+}
+
+impl CheckpointSource for rocksdb::DB {
+    fn create_checkpoint(&self, dest: &Path) -> Result<()> {
+        let checkpoint = rocksdb::checkpoint::Checkpoint::new(self)
+            .context("Failed to create RocksDB checkpoint object")?;
+        checkpoint.create_checkpoint(dest)
+            .context("Failed to create RocksDB checkpoint")?;
+        Ok(())
+    }
+}
+
+/// Checkpoints a store by path, opening it read-write for each checkpoint
+///
+/// Opening fails while the pool holds the store, so backups through this
+/// source refuse to run against a live pool instead of copying its files.
+pub struct StorePathCheckpoint {
+    path: PathBuf,
+}
+
+impl StorePathCheckpoint {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl CheckpointSource for StorePathCheckpoint {
+    fn create_checkpoint(&self, dest: &Path) -> Result<()> {
+        let names = rocksdb::DB::list_cf(&rocksdb::Options::default(), &self.path)
+            .with_context(|| format!("Failed to list column families of {}", self.path.display()))?;
+        let db = rocksdb::DB::open_cf(&rocksdb::Options::default(), &self.path, &names)
+            .with_context(|| format!("Failed to open {} for a checkpoint; is the pool running?", self.path.display()))?;
+        db.create_checkpoint(dest)
+    }
 }
 
 /// Backup manager
+///
+/// The one backup API used by every binary: configured through
+/// [`BackupConfig`], with async create, verify, restore, delete and cleanup.
 pub struct BackupManager {
     config: BackupConfig,
+    checkpoint_source: Option<Arc<dyn CheckpointSource>>,
+    encryption_key: Option<BackupKey>,
+    migrations: Vec<Arc<dyn BackupMigration>>,
+    /// Error from the most recent backup attempt, cleared on success
+    last_failure: Mutex<Option<String>>,
+    /// Serializes read-modify-write cycles of the catalog file
+    catalog_lock: Mutex<()>,
+}
+
+impl Default for BackupManager {
+    /// Manager with the default configuration
+    fn default() -> Self {
+        Self::new(BackupConfig::default())
+    }
 }
 
 impl BackupManager {
     /// Create a new backup manager
     pub fn new(config: BackupConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            checkpoint_source: None,
+            encryption_key: None,
+            migrations: Vec::new(),
+            last_failure: Mutex::new(None),
+            catalog_lock: Mutex::new(()),
+        }
     }
 
-    /// Create with default configuration
-    pub fn default() -> Self {
-        Self::new(BackupConfig::default())
+    /// Take backups from checkpoints instead of copying database files; a
+    /// failed checkpoint then fails the backup
+    pub fn with_checkpoint_source(mut self, source: Arc<dyn CheckpointSource>) -> Self {
+        self.checkpoint_source = Some(source);
+        self
+    }
+
+    /// Encrypt new backups with `key`, and decrypt encrypted ones with it
+    pub fn with_encryption_key(mut self, key: BackupKey) -> Self {
+        self.encryption_key = Some(key);
+        self
+    }
+
+    /// Run `migration` when restoring backups of older data formats
+    pub fn with_migration(mut self, migration: Arc<dyn BackupMigration>) -> Self {
+        self.migrations.push(migration);
+        self
+    }
+
+    pub fn config(&self) -> &BackupConfig {
+        &self.config
     }
 
     /// Ensure backup directory exists
@@ -157,10 +797,12 @@ impl BackupManager {
     }
 
     /// Generate backup filename
-    fn generate_backup_filename(&self) -> String {
+    fn generate_backup_filename(&self, kind: BackupKind) -> String {
         let timestamp = Utc::now().format("%Y%m%d_%H%M%S");
+        let kind_suffix = if kind == BackupKind::Incremental { "_incremental" } else { "" };
         let compression_suffix = if self.config.compress { ".tar.gz" } else { ".tar" };
-        format!("dmpool_backup_{}{}", timestamp, compression_suffix)
+        let encryption_suffix = if self.encryption_key.is_some() { encryption::ENCRYPTED_SUFFIX } else { "" };
+        format!("dmpool_backup_{}{}{}{}", timestamp, kind_suffix, compression_suffix, encryption_suffix)
     }
 
     /// Get current schema version (simplified - should read from DB)
@@ -180,6 +822,87 @@ impl BackupManager {
         Ok(format!("{:x}", hasher.finalize()))
     }
 
+    /// Compute SHA-256 checksums for every file under `root`, keyed by relative path
+    fn calculate_tree_checksums(&self, root: &Path) -> Result<BTreeMap<String, String>> {
+        let mut checksums = BTreeMap::new();
+        self.collect_tree_checksums(root, root, &mut checksums)?;
+        Ok(checksums)
+    }
+
+    fn collect_tree_checksums(
+        &self,
+        root: &Path,
+        dir: &Path,
+        checksums: &mut BTreeMap<String, String>,
+    ) -> Result<()> {
+        for entry in fs::read_dir(dir).context("Failed to read directory")? {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.collect_tree_checksums(root, &path, checksums)?;
+            } else {
+                let relative = path.strip_prefix(root)
+                    .context("File outside of checksum root")?
+                    .to_string_lossy()
+                    .to_string();
+                checksums.insert(relative, self.calculate_checksum(&path)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Extract a backup archive into `dest`, decrypting it first if needed
+    fn extract_archive(&self, archive: &Path, dest: &Path, progress: &Progress) -> Result<()> {
+        let name = archive.to_string_lossy();
+        let compressed = name.trim_end_matches(encryption::ENCRYPTED_SUFFIX).ends_with(".gz");
+        let decrypted = if encryption::is_encrypted(archive)? {
+            let key = self.encryption_key.as_ref()
+                .ok_or_else(|| anyhow::anyhow!("Backup is encrypted but no encryption key is configured"))?;
+            let scratch = tempfile::NamedTempFile::new_in(&self.config.backup_dir)
+                .context("Failed to create decryption scratch file")?;
+            encryption::decrypt_file(archive, scratch.path(), key)?;
+            Some(scratch)
+        } else {
+            None
+        };
+        let archive = decrypted.as_ref().map(|f| f.path()).unwrap_or(archive);
+
+        // Paths handed to tar must be absolute, and the backup dir may be relative
+        let archive_str = safe_path_str(&std::path::absolute(archive)?)?;
+        let dest_str = safe_path_str(&std::path::absolute(dest)?)?;
+        let flags = if compressed { "-xvzf" } else { "-xvf" };
+
+        let status = run_tar(Command::new("tar").args([flags, &archive_str, "-C", &dest_str]), dest, progress)?;
+
+        if !status.success() {
+            return Err(anyhow::anyhow!("Backup extraction failed with exit code: {:?}", status.code()));
+        }
+        Ok(())
+    }
+
+    /// Extract an archive to a scratch directory and checksum its contents
+    fn calculate_archive_checksums(&self, archive: &Path) -> Result<BTreeMap<String, String>> {
+        let scratch = tempfile::Builder::new()
+            .prefix("dmpool_verify_")
+            .tempdir_in(&self.config.backup_dir)
+            .context("Failed to create verification directory")?;
+        self.extract_archive(archive, scratch.path(), &Progress::default())?;
+        self.calculate_tree_checksums(scratch.path())
+    }
+
+    /// Number and total size of the files under `path`
+    fn count_files(&self, path: &Path) -> Result<(u64, u64)> {
+        if !path.is_dir() {
+            return Ok((1, fs::metadata(path).context("Failed to read file metadata")?.len()));
+        }
+        let (mut files, mut bytes) = (0, 0);
+        for entry in fs::read_dir(path).context("Failed to read directory")? {
+            let (f, b) = self.count_files(&entry?.path())?;
+            files += f;
+            bytes += b;
+        }
+        Ok((files, bytes))
+    }
+
     /// Get directory size
     fn get_dir_size(&self, path: &Path) -> Result<u64> {
         let mut total = 0u64;
@@ -199,16 +922,127 @@ impl BackupManager {
         Ok(total)
     }
 
-    /// Create a backup
+    /// Free space on the backup volume against the estimated size of a backup
+    ///
+    /// The archive is assumed to compress as well as the latest full backup
+    /// did, or not at all without one. Incremental backups are estimated as
+    /// full ones.
+    pub fn check_space(&self) -> Result<SpaceCheck> {
+        let original_size = self.get_dir_size(&self.config.db_path)?;
+        let archive = match self.list_backups()?.into_iter().find(|b| b.kind == BackupKind::Full) {
+            Some(full) if self.config.compress && full.original_size > 0 => {
+                (original_size as f64 * (full.backup_size as f64 / full.original_size as f64).min(1.0)) as u64
+            }
+            _ => original_size,
+        };
+        let encrypted = if self.encryption_key.is_some() { archive } else { 0 };
+        Ok(SpaceCheck {
+            available_bytes: available_space(&self.config.backup_dir)?,
+            estimated_bytes: archive + original_size + encrypted,
+            min_free_bytes: self.config.min_free_bytes,
+        })
+    }
+
+    /// Error from the most recent backup attempt, if it failed
+    pub fn last_failure(&self) -> Option<String> {
+        self.last_failure_lock().clone()
+    }
+
+    /// A poisoned lock still holds the last recorded error, so it is used as is
+    fn last_failure_lock(&self) -> std::sync::MutexGuard<'_, Option<String>> {
+        self.last_failure.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Create a full backup
     pub async fn create_backup(&self) -> Result<BackupMetadata> {
+        self.create_backup_of_kind(BackupKind::Full).await
+    }
+
+    /// Create a backup of the files changed since the latest full backup
+    ///
+    /// Falls back to a full backup when there is none yet.
+    pub async fn create_incremental_backup(&self) -> Result<BackupMetadata> {
+        self.create_backup_of_kind(BackupKind::Incremental).await
+    }
+
+    pub async fn create_backup_of_kind(&self, kind: BackupKind) -> Result<BackupMetadata> {
+        self.create_backup_with_progress(kind, &Progress::default()).await
+    }
+
+    /// Create a backup, reporting files archived into `progress`
+    ///
+    /// Cancelling stops the backup and removes the partial archive; a
+    /// cancelled backup doesn't count as a failure.
+    pub async fn create_backup_with_progress(&self, kind: BackupKind, progress: &Progress) -> Result<BackupMetadata> {
+        let result = self.create_backup_inner(kind, progress).await;
+        if !progress.is_cancelled() {
+            *self.last_failure_lock() = result.as_ref().err().map(|e| format!("{:#}", e));
+        }
+        result
+    }
+
+    /// Next run of every configured schedule after `after`
+    pub fn scheduled_runs(&self, after: DateTime<Utc>) -> Vec<ScheduledBackup> {
+        self.config.schedules.iter()
+            .map(|schedule| ScheduledBackup {
+                kind: schedule.kind,
+                cron: schedule.cron.to_string(),
+                next_run: schedule.cron.next_after(after),
+            })
+            .collect()
+    }
+
+    /// Earliest scheduled backup after `after`; a full backup wins a tie
+    pub fn next_scheduled(&self, after: DateTime<Utc>) -> Option<(DateTime<Utc>, BackupKind)> {
+        self.scheduled_runs(after).into_iter()
+            .filter_map(|run| Some((run.next_run?, run.kind)))
+            .min_by_key(|(time, kind)| (*time, *kind != BackupKind::Full))
+    }
+
+    /// Shortest gap between two consecutive runs of any schedule after `after`
+    pub fn backup_interval(&self, after: DateTime<Utc>) -> Option<Duration> {
+        self.config.schedules.iter()
+            .filter_map(|schedule| {
+                let first = schedule.cron.next_after(after)?;
+                Some(schedule.cron.next_after(first)? - first)
+            })
+            .min()
+    }
+
+    async fn create_backup_inner(&self, kind: BackupKind, progress: &Progress) -> Result<BackupMetadata> {
+        progress.set_phase("preparing");
         self.ensure_backup_dir()?;
 
         if !self.config.db_path.exists() {
             return Err(anyhow::anyhow!("Database path does not exist: {:?}", self.config.db_path));
         }
 
+        // Refuse to start rather than fill the volume halfway through
+        let space = self.check_space()?;
+        if !space.sufficient() {
+            return Err(anyhow::anyhow!(
+                "Not enough free space in {}: {} bytes available, a backup needs about {} and {} must stay free",
+                self.config.backup_dir.display(),
+                space.available_bytes,
+                space.estimated_bytes,
+                space.min_free_bytes
+            ));
+        }
+
+        let base = match kind {
+            BackupKind::Full => None,
+            BackupKind::Incremental => {
+                let base = self.list_backups()?.into_iter().find(|b| b.kind == BackupKind::Full);
+                if base.is_none() {
+                    warn!("No full backup to base an incremental backup on, taking a full backup");
+                }
+                base
+            }
+        };
+
+        let kind = if base.is_some() { BackupKind::Incremental } else { BackupKind::Full };
         let backup_id = uuid::Uuid::new_v4().to_string();
-        let filename = self.generate_backup_filename();
+        let filename = self.generate_backup_filename(kind);
         let backup_path = self.config.backup_dir.join(&filename);
 
         info!("Creating backup: {}", filename);
@@ -216,12 +1050,6 @@ impl BackupManager {
         // Get original database size
         let original_size = self.get_dir_size(&self.config.db_path)?;
 
-        // Validate all paths before using them
-        let backup_path_str = safe_path_str(&backup_path)?;
-        let parent_dir = self.config.db_path.parent()
-            .unwrap_or(Path::new("."));
-        let parent_dir_str = safe_path_str(&parent_dir)?;
-
         // Use "./" prefix for file argument to prevent it from being interpreted as an option
         let db_file = self.config.db_path.file_name()
             .ok_or_else(|| anyhow::anyhow!("Database path has no file name"))?;
@@ -244,35 +1072,103 @@ impl BackupManager {
             return Err(anyhow::anyhow!("Database file name contains dangerous characters: {}", db_file_str));
         }
 
-        // Create tar archive (optionally compressed)
-        let status = if self.config.compress {
-            Command::new("tar")
-                .args([
-                    "-czf",
-                    &backup_path_str,
-                    "-C",
-                    &parent_dir_str,
-                    &db_file_safe,
-                ])
-                .status()
-                .context("Failed to execute tar command")?
-        } else {
-            Command::new("tar")
-                .args([
-                    "-cf",
-                    &backup_path_str,
-                    "-C",
-                    &parent_dir_str,
-                    &db_file_safe,
-                ])
-                .status()
-                .context("Failed to execute tar command")?
+        // Archive a checkpoint when possible so the pool can keep writing;
+        // the checkpoint keeps the database's file name so restores are unchanged
+        let checkpoint_dir = self.take_checkpoint(db_file)?;
+        let source_path = match &checkpoint_dir {
+            Some(dir) => dir.path().join(db_file),
+            None => self.config.db_path.clone(),
         };
 
+        // Validate all paths before using them
+        let backup_path_str = safe_path_str(&backup_path)?;
+        let parent_dir = source_path.parent()
+            .unwrap_or(Path::new("."));
+        let parent_dir_str = safe_path_str(&parent_dir)?;
+
+        // An incremental archive lists the changed files instead of the whole directory
+        let mut deleted_files = Vec::new();
+        let file_list = match &base {
+            Some(base) => {
+                let mut current = BTreeMap::new();
+                self.collect_tree_checksums(parent_dir, &source_path, &mut current)?;
+                deleted_files = base.file_checksums.keys()
+                    .filter(|file| !current.contains_key(*file))
+                    .cloned()
+                    .collect();
+                let mut list = tempfile::NamedTempFile::new_in(&self.config.backup_dir)
+                    .context("Failed to create backup file list")?;
+                let (mut files, mut bytes) = (0, 0);
+                for (file, digest) in &current {
+                    if base.file_checksums.get(file) != Some(digest) {
+                        writeln!(list, "./{}", file).context("Failed to write backup file list")?;
+                        files += 1;
+                        bytes += fs::metadata(parent_dir.join(file)).map(|m| m.len()).unwrap_or(0);
+                    }
+                }
+                progress.set_totals(files, bytes);
+                Some(list)
+            }
+            None => {
+                let (files, bytes) = self.count_files(&source_path)?;
+                progress.set_totals(files, bytes);
+                None
+            }
+        };
+        let file_list_str = file_list.as_ref().map(|list| safe_path_str(list.path())).transpose()?;
+
+        // Create tar archive (optionally compressed)
+        progress.set_phase("archiving");
+        let flags = if self.config.compress { "-cvzf" } else { "-cvf" };
+        let mut tar = Command::new("tar");
+        tar.args([flags, &backup_path_str, "-C", &parent_dir_str]);
+        match &file_list_str {
+            Some(list) => tar.args(["-T", list.as_str()]),
+            None => tar.arg(&db_file_safe),
+        };
+        let status = run_tar(&mut tar, parent_dir, progress);
+        drop(file_list);
+
+        let status = match status {
+            Ok(status) => status,
+            Err(e) => {
+                let _ = fs::remove_file(&backup_path);
+                return Err(e);
+            }
+        };
         if !status.success() {
             return Err(anyhow::anyhow!("Backup creation failed with exit code: {:?}", status.code()));
         }
 
+        let column_families = match storage::column_family_files(&source_path) {
+            Ok(files) => files.into_iter()
+                .map(|(name, files)| {
+                    let files = files.into_iter().map(|file| format!("{}/{}", db_file_str, file)).collect();
+                    (name, files)
+                })
+                .collect(),
+            Err(e) => {
+                warn!("Not recording column family files: {:#}", e);
+                BTreeMap::new()
+            }
+        };
+        let from_checkpoint = checkpoint_dir.is_some();
+        drop(checkpoint_dir);
+
+        // Checksum the archive contents before they are encrypted
+        progress.set_phase("checksumming");
+        self.abandon_if_cancelled(&backup_path, progress)?;
+        let file_checksums = self.calculate_archive_checksums(&backup_path)?;
+        self.abandon_if_cancelled(&backup_path, progress)?;
+        if let Some(key) = &self.encryption_key {
+            let plain = tempfile::Builder::new()
+                .prefix(".dmpool_plain_")
+                .tempfile_in(&self.config.backup_dir)
+                .context("Failed to create plaintext scratch file")?;
+            fs::rename(&backup_path, plain.path()).context("Failed to move plaintext archive")?;
+            encryption::encrypt_file(plain.path(), &backup_path, key)?;
+        }
+
         // Get backup size
         let backup_size = fs::metadata(&backup_path)
             .context("Failed to get backup file metadata")?
@@ -285,7 +1181,7 @@ impl BackupManager {
             None
         };
 
-        // Calculate checksum
+        // Checksum of the archive as stored
         let checksum = self.calculate_checksum(&backup_path)?;
 
         let metadata = BackupMetadata {
@@ -298,12 +1194,21 @@ impl BackupManager {
             validated: false,
             schema_version: self.get_schema_version(),
             checksum,
+            file_checksums,
+            from_checkpoint,
+            encryption_key_id: self.encryption_key.as_ref().map(|key| key.fingerprint()),
+            kind,
+            base_id: base.map(|b| b.id),
+            deleted_files,
+            column_families,
+            versions: StoreVersions::current(),
         };
 
         // Save metadata
         self.save_metadata(&metadata)?;
 
         // Validate the backup
+        progress.set_phase("validating");
         self.validate_backup(&metadata).await?;
 
         info!(
@@ -316,6 +1221,33 @@ impl BackupManager {
         Ok(metadata)
     }
 
+    /// Remove an unfinished archive and stop if the backup was cancelled
+    fn abandon_if_cancelled(&self, archive: &Path, progress: &Progress) -> Result<()> {
+        if progress.is_cancelled() {
+            let _ = fs::remove_file(archive);
+        }
+        progress.check_cancelled()
+    }
+
+    /// Create a checkpoint of the database in a scratch directory under the backup dir
+    ///
+    /// Returns `None` when no checkpoint source is configured, so the database
+    /// files are copied as they are. A failed checkpoint is an error rather
+    /// than a reason to copy files that may be changing.
+    fn take_checkpoint(&self, db_file: &std::ffi::OsStr) -> Result<Option<tempfile::TempDir>> {
+        let Some(source) = self.checkpoint_source.as_ref() else {
+            return Ok(None);
+        };
+        let dir = tempfile::Builder::new()
+            .prefix("dmpool_checkpoint_")
+            .tempdir_in(&self.config.backup_dir)
+            .context("Failed to create checkpoint directory")?;
+        source.create_checkpoint(&dir.path().join(db_file))
+            .context("Checkpoint failed; not copying live database files")?;
+        info!("Created database checkpoint for backup");
+        Ok(Some(dir))
+    }
+
     /// Save backup metadata to JSON file
     fn save_metadata(&self, metadata: &BackupMetadata) -> Result<()> {
         let meta_path = self.get_metadata_path(&metadata.id);
@@ -323,6 +1255,10 @@ impl BackupManager {
             .context("Failed to serialize metadata")?;
         fs::write(&meta_path, json)
             .context("Failed to write metadata file")?;
+        self.update_catalog(|backups| {
+            backups.retain(|b| b.id != metadata.id);
+            backups.push(metadata.clone());
+        });
         Ok(())
     }
 
@@ -345,18 +1281,21 @@ impl BackupManager {
     pub async fn validate_backup(&self, metadata: &BackupMetadata) -> Result<bool> {
         info!("Validating backup: {}", metadata.id);
 
-        // Check if backup file exists
-        if !metadata.file_path.exists() {
-            return Err(anyhow::anyhow!("Backup file not found: {:?}", metadata.file_path));
-        }
-
-        // Verify checksum
-        let current_checksum = self.calculate_checksum(&metadata.file_path)?;
-        if current_checksum != metadata.checksum {
+        let report = self.verify_metadata(metadata)?;
+        if !report.archive_checksum_ok {
             return Err(anyhow::anyhow!(
                 "Backup checksum mismatch: expected {}, got {}",
                 metadata.checksum,
-                current_checksum
+                self.calculate_checksum(&metadata.file_path)?
+            ));
+        }
+        if !report.valid {
+            return Err(anyhow::anyhow!(
+                "Backup file verification failed: corrupted {:?}, missing {:?}, unexpected {:?}{}",
+                report.corrupted_files,
+                report.missing_files,
+                report.unexpected_files,
+                report.error.map(|e| format!(" ({})", e)).unwrap_or_default()
             ));
         }
 
@@ -365,41 +1304,238 @@ impl BackupManager {
         updated.validated = true;
         self.save_metadata(&updated)?;
 
-        info!("Backup validated successfully: {}", metadata.id);
+        info!("Backup validated successfully: {} ({} files)", metadata.id, report.files_checked);
         Ok(true)
     }
 
-    /// List all backups
+    /// Verify every file in a backup and report which ones are corrupted
+    pub async fn verify_backup(&self, backup_id: &str) -> Result<BackupVerification> {
+        let metadata = self.load_metadata(backup_id)?;
+        let report = self.verify_metadata(&metadata)?;
+
+        if report.valid {
+            info!("Backup verified: {} ({} files)", backup_id, report.files_checked);
+        } else {
+            warn!(
+                "Backup verification failed for {}: {} corrupted, {} missing",
+                backup_id,
+                report.corrupted_files.len(),
+                report.missing_files.len()
+            );
+        }
+        Ok(report)
+    }
+
+    /// Fail early when an encrypted backup can't be decrypted with the configured key
+    fn check_encryption_key(&self, metadata: &BackupMetadata) -> Result<()> {
+        let Some(key_id) = &metadata.encryption_key_id else {
+            return Ok(());
+        };
+        match &self.encryption_key {
+            None => Err(anyhow::anyhow!("Backup {} is encrypted but no encryption key is configured", metadata.id)),
+            Some(key) if key.fingerprint() != *key_id => Err(anyhow::anyhow!(
+                "Backup {} is encrypted with key {}, but the configured key is {}",
+                metadata.id,
+                key_id,
+                key.fingerprint()
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Compare the archive and its contents against the recorded checksums
+    fn verify_metadata(&self, metadata: &BackupMetadata) -> Result<BackupVerification> {
+        // Check if backup file exists
+        if !metadata.file_path.exists() {
+            return Err(anyhow::anyhow!("Backup file not found: {:?}", metadata.file_path));
+        }
+
+        let archive_checksum_ok = self.calculate_checksum(&metadata.file_path)? == metadata.checksum;
+
+        let mut report = BackupVerification {
+            backup_id: metadata.id.clone(),
+            valid: false,
+            archive_checksum_ok,
+            files_checked: 0,
+            corrupted_files: Vec::new(),
+            missing_files: Vec::new(),
+            unexpected_files: Vec::new(),
+            error: None,
+        };
+
+        let actual = match self.check_encryption_key(metadata)
+            .and_then(|_| self.calculate_archive_checksums(&metadata.file_path))
+        {
+            Ok(actual) => actual,
+            Err(e) => {
+                report.error = Some(e.to_string());
+                report.missing_files = metadata.file_checksums.keys().cloned().collect();
+                return Ok(report);
+            }
+        };
+
+        for (file, expected) in &metadata.file_checksums {
+            match actual.get(file) {
+                Some(digest) if digest == expected => {}
+                Some(_) => report.corrupted_files.push(file.clone()),
+                None => report.missing_files.push(file.clone()),
+            }
+        }
+        // Backups taken before per-file checksums existed have nothing to compare against
+        if !metadata.file_checksums.is_empty() {
+            report.unexpected_files = actual.keys()
+                .filter(|f| !metadata.file_checksums.contains_key(*f))
+                .cloned()
+                .collect();
+        }
+
+        report.files_checked = actual.len();
+        report.valid = archive_checksum_ok
+            && report.corrupted_files.is_empty()
+            && report.missing_files.is_empty()
+            && report.unexpected_files.is_empty();
+
+        Ok(report)
+    }
+
+    /// List all backups, newest first
+    ///
+    /// Served from the catalog, which is rebuilt from the metadata files when
+    /// it is missing or lists different backups than the backup dir holds.
     pub fn list_backups(&self) -> Result<Vec<BackupMetadata>> {
-        let mut backups = Vec::new();
+        let _guard = self.lock_catalog();
+        let on_disk = self.metadata_ids()?;
+        let catalog = self.read_catalog()
+            .filter(|backups| backups.len() == on_disk.len() && backups.iter().all(|b| on_disk.contains(&b.id)));
+        if let Some(backups) = catalog {
+            return Ok(backups);
+        }
 
+        let backups = self.scan_metadata(&on_disk);
+        if !on_disk.is_empty() {
+            info!("Rebuilt backup catalog from {} metadata file(s)", backups.len());
+        }
+        self.write_catalog_or_discard(&backups);
+        Ok(backups)
+    }
+
+    /// Rebuild the catalog from the metadata files, reporting how it had drifted
+    pub fn repair_catalog(&self) -> Result<CatalogRepair> {
+        let _guard = self.lock_catalog();
+        let backups = self.scan_metadata(&self.metadata_ids()?);
+        let catalog: BTreeMap<String, serde_json::Value> = self.read_catalog()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|b| Some((b.id.clone(), serde_json::to_value(b).ok()?)))
+            .collect();
+
+        let mut repair = CatalogRepair { total_backups: backups.len(), ..CatalogRepair::default() };
+        for backup in &backups {
+            match catalog.get(&backup.id) {
+                None => repair.added.push(backup.id.clone()),
+                Some(entry) if serde_json::to_value(backup).ok().as_ref() != Some(entry) => {
+                    repair.updated.push(backup.id.clone())
+                }
+                Some(_) => {}
+            }
+        }
+        let ids: HashSet<&str> = backups.iter().map(|b| b.id.as_str()).collect();
+        repair.removed = catalog.keys().filter(|id| !ids.contains(id.as_str())).cloned().collect();
+
+        self.write_catalog(&backups)?;
+        if repair.drifted() {
+            warn!("Repaired backup catalog: {} added, {} removed, {} updated",
+                repair.added.len(), repair.removed.len(), repair.updated.len());
+        }
+        Ok(repair)
+    }
+
+    /// IDs of the metadata files in the backup dir, without reading them
+    fn metadata_ids(&self) -> Result<HashSet<String>> {
+        let mut ids = HashSet::new();
         if !self.config.backup_dir.exists() {
-            return Ok(backups);
+            return Ok(ids);
         }
 
         for entry in fs::read_dir(&self.config.backup_dir)
             .context("Failed to read backup directory")?
         {
             let entry = entry?;
-            let path = entry.path();
-
-            // Load metadata files
-            if path.extension().and_then(|s| s.to_str()) == Some("json") {
-                if let Some(name) = path.file_name().and_then(|s| s.to_str()) {
-                    if name.ends_with(".meta.json") {
-                        let backup_id = name.trim_end_matches(".meta.json");
-                        if let Ok(metadata) = self.load_metadata(backup_id) {
-                            backups.push(metadata);
-                        }
-                    }
-                }
+            if let Some(id) = entry.file_name().to_str().and_then(|name| name.strip_suffix(".meta.json")) {
+                ids.insert(id.to_string());
             }
         }
+        Ok(ids)
+    }
 
-        // Sort by timestamp (newest first)
+    /// Load the metadata of `ids`, newest first, skipping unreadable files
+    fn scan_metadata(&self, ids: &HashSet<String>) -> Vec<BackupMetadata> {
+        let mut backups: Vec<BackupMetadata> = ids.iter()
+            .filter_map(|id| match self.load_metadata(id) {
+                Ok(metadata) => Some(metadata),
+                Err(e) => {
+                    warn!("Skipping backup {}: {:#}", id, e);
+                    None
+                }
+            })
+            .collect();
         backups.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        backups
+    }
 
-        Ok(backups)
+    fn catalog_path(&self) -> PathBuf {
+        self.config.backup_dir.join(CATALOG_FILE)
+    }
+
+    /// The catalog's backups, or `None` if it is missing or unreadable
+    fn read_catalog(&self) -> Option<Vec<BackupMetadata>> {
+        let json = fs::read_to_string(self.catalog_path()).ok()?;
+        match serde_json::from_str(&json) {
+            Ok(backups) => Some(backups),
+            Err(e) => {
+                warn!("Ignoring unreadable backup catalog: {}", e);
+                None
+            }
+        }
+    }
+
+    fn write_catalog(&self, backups: &[BackupMetadata]) -> Result<()> {
+        self.ensure_backup_dir()?;
+        let path = self.catalog_path();
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec(backups)?)
+            .context("Failed to write backup catalog")?;
+        fs::rename(&tmp, &path).context("Failed to replace backup catalog")?;
+        Ok(())
+    }
+
+    /// Write the catalog, removing it on failure so the next listing rescans
+    /// instead of trusting stale entries
+    fn write_catalog_or_discard(&self, backups: &[BackupMetadata]) {
+        if let Err(e) = self.write_catalog(backups) {
+            warn!("Discarding backup catalog: {:#}", e);
+            let _ = fs::remove_file(self.catalog_path());
+        }
+    }
+
+    /// Hold the catalog for a read-modify-write cycle
+    ///
+    /// A cycle that panicked leaves at worst a stale catalog, which listings
+    /// detect and rebuild, so poisoning is ignored.
+    fn lock_catalog(&self) -> std::sync::MutexGuard<'_, ()> {
+        self.catalog_lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Apply `change` to the catalog after creating or deleting backups.
+    /// Without a catalog nothing is written; the next listing builds it.
+    fn update_catalog(&self, change: impl FnOnce(&mut Vec<BackupMetadata>)) {
+        let _guard = self.lock_catalog();
+        let Some(mut backups) = self.read_catalog() else {
+            return;
+        };
+        change(&mut backups);
+        backups.sort_by_key(|b| std::cmp::Reverse(b.timestamp));
+        self.write_catalog_or_discard(&backups);
     }
 
     /// Get backup statistics
@@ -415,16 +1551,272 @@ impl BackupManager {
             latest_backup: backups.first().map(|b| b.timestamp),
             oldest_backup: backups.last().map(|b| b.timestamp),
             disk_usage_bytes,
+            schedules: self.scheduled_runs(Utc::now()),
+            retention: self.config.retention.clone(),
+            space: self.check_space().ok(),
         })
     }
 
+    /// Newest backup taken at or before `at`, for point-in-time restores
+    pub fn backup_at(&self, at: DateTime<Utc>) -> Result<Option<BackupMetadata>> {
+        Ok(self.list_backups()?.into_iter().find(|b| b.timestamp <= at))
+    }
+
+    /// Newest backup whose checksums were verified
+    pub fn last_good_backup(&self) -> Result<Option<BackupMetadata>> {
+        Ok(self.list_backups()?.into_iter().find(|b| b.validated))
+    }
+
+    /// Keys per column family in a backup, counted in a scratch extraction
+    pub async fn record_counts(&self, backup_id: &str) -> Result<BTreeMap<String, u64>> {
+        let metadata = self.load_metadata(backup_id)?;
+        self.ensure_backup_dir()?;
+        let scratch = tempfile::Builder::new()
+            .prefix(".dmpool_count_")
+            .tempdir_in(&self.config.backup_dir)
+            .context("Failed to create scratch directory")?;
+        self.stage_backup(&metadata, scratch.path(), &Progress::default())?;
+        let db_file = self.config.db_path.file_name()
+            .ok_or_else(|| anyhow::anyhow!("Invalid database path"))?;
+        storage::key_counts(&scratch.path().join(db_file))
+    }
+
+    /// Directory under the backup dir for restoring a backup to inspect it
+    pub fn inspection_dir(&self, name: &str) -> Result<PathBuf> {
+        let valid = !name.is_empty()
+            && name.len() <= 64
+            && !name.starts_with('-')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(anyhow::anyhow!("Invalid restore directory name: {}", name));
+        }
+        Ok(self.config.backup_dir.join("restores").join(name))
+    }
+
+    /// Scratch file in the backup dir to receive an upload for [`Self::import_backup`]
+    pub fn upload_file(&self) -> Result<tempfile::NamedTempFile> {
+        self.ensure_backup_dir()?;
+        tempfile::Builder::new()
+            .prefix(".dmpool_upload_")
+            .tempfile_in(&self.config.backup_dir)
+            .context("Failed to create upload file")
+    }
+
+    /// Register an archive taken elsewhere, such as one downloaded from
+    /// another server, as a full backup
+    ///
+    /// The archive must hold the database directory under its own name, and
+    /// an encrypted one must be encrypted with the configured key. Its
+    /// contents are checksummed like those of a new backup; the data format
+    /// versions it was taken with aren't known.
+    pub async fn import_backup(&self, upload: tempfile::NamedTempFile, import: &BackupImport) -> Result<BackupMetadata> {
+        let checksum = self.calculate_checksum(upload.path())?;
+        let expected = import.checksum.as_deref().map(|expected| expected.trim_matches('"'));
+        if expected.is_some_and(|expected| !expected.eq_ignore_ascii_case(&checksum)) {
+            return Err(anyhow::anyhow!(
+                "Uploaded archive checksum mismatch: expected {}, got {}",
+                expected.unwrap_or_default(),
+                checksum
+            ));
+        }
+        let encrypted = encryption::is_encrypted(upload.path())?;
+        let encryption_key_id = match (encrypted, &self.encryption_key) {
+            (false, _) => None,
+            (true, Some(key)) => Some(key.fingerprint()),
+            (true, None) => return Err(anyhow::anyhow!("Uploaded archive is encrypted but no encryption key is configured")),
+        };
+        let compressed = self.is_gzip(upload.path(), encrypted)?;
+
+        let filename = format!(
+            "dmpool_backup_{}_upload{}{}",
+            Utc::now().format("%Y%m%d_%H%M%S"),
+            if compressed { ".tar.gz" } else { ".tar" },
+            if encrypted { encryption::ENCRYPTED_SUFFIX } else { "" }
+        );
+        let backup_path = self.config.backup_dir.join(filename);
+        upload.persist(&backup_path).context("Failed to store uploaded archive")?;
+
+        let result = self.register_import(&backup_path, checksum, encryption_key_id, import);
+        if result.is_err() {
+            let _ = fs::remove_file(&backup_path);
+        }
+        result
+    }
+
+    /// Whether the archive at `path`, decrypted if need be, is gzip compressed
+    fn is_gzip(&self, path: &Path, encrypted: bool) -> Result<bool> {
+        use std::io::Read;
+        let decrypted = match (&self.encryption_key, encrypted) {
+            (Some(key), true) => {
+                let scratch = tempfile::NamedTempFile::new_in(&self.config.backup_dir)
+                    .context("Failed to create decryption scratch file")?;
+                encryption::decrypt_file(path, scratch.path(), key)?;
+                Some(scratch)
+            }
+            _ => None,
+        };
+        let mut magic = [0u8; 2];
+        let mut file = fs::File::open(decrypted.as_ref().map(|f| f.path()).unwrap_or(path))?;
+        Ok(file.read_exact(&mut magic).is_ok() && magic == [0x1f, 0x8b])
+    }
+
+    fn register_import(
+        &self,
+        backup_path: &Path,
+        checksum: String,
+        encryption_key_id: Option<String>,
+        import: &BackupImport,
+    ) -> Result<BackupMetadata> {
+        let db_file = self.config.db_path.file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| anyhow::anyhow!("Invalid database path"))?;
+        let scratch = tempfile::Builder::new()
+            .prefix("dmpool_import_")
+            .tempdir_in(&self.config.backup_dir)
+            .context("Failed to create import directory")?;
+        self.extract_archive(backup_path, scratch.path(), &Progress::default())
+            .context("Uploaded archive can't be extracted")?;
+
+        let file_checksums = self.calculate_tree_checksums(scratch.path())?;
+        let prefix = format!("{}/", db_file);
+        if file_checksums.is_empty() || file_checksums.keys().any(|file| !file.starts_with(&prefix)) {
+            return Err(anyhow::anyhow!("Uploaded archive doesn't hold just the database directory {}", db_file));
+        }
+        let db_dir = scratch.path().join(db_file);
+        let original_size = self.get_dir_size(&db_dir)?;
+        let column_families = storage::column_family_files(&db_dir)
+            .map(|files| files.into_iter()
+                .map(|(name, files)| (name, files.into_iter().map(|file| format!("{}{}", prefix, file)).collect()))
+                .collect())
+            .unwrap_or_default();
+        let backup_size = fs::metadata(backup_path).context("Failed to get backup file metadata")?.len();
+
+        let metadata = BackupMetadata {
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: import.taken_at.unwrap_or_else(Utc::now),
+            file_path: backup_path.to_path_buf(),
+            original_size,
+            backup_size,
+            compression_ratio: None,
+            // Its contents were just checksummed from this archive
+            validated: true,
+            schema_version: self.get_schema_version(),
+            checksum,
+            file_checksums,
+            from_checkpoint: false,
+            encryption_key_id,
+            kind: BackupKind::Full,
+            base_id: None,
+            deleted_files: Vec::new(),
+            column_families,
+            versions: StoreVersions::default(),
+        };
+        self.save_metadata(&metadata)?;
+        info!("Imported backup {} ({} files, {} bytes)", metadata.id, metadata.file_checksums.len(), backup_size);
+        Ok(metadata)
+    }
+
+    /// Whether this build can restore a backup, and the migrations it would run
+    pub fn compatibility(&self, metadata: &BackupMetadata) -> BackupCompatibility {
+        self.migration_plan(metadata).0
+    }
+
+    fn migration_plan(&self, metadata: &BackupMetadata) -> (BackupCompatibility, Vec<Arc<dyn BackupMigration>>) {
+        let mut compatibility = BackupCompatibility::default();
+        let mut migrations = Vec::new();
+        let current = StoreVersions::current();
+        let backup = &metadata.versions;
+
+        if metadata.schema_version > self.get_schema_version() {
+            compatibility.problems.push(format!(
+                "schema version {} is newer than supported version {}",
+                metadata.schema_version,
+                self.get_schema_version()
+            ));
+        }
+        // Unrecorded for backups from before versions were tracked
+        let major = |version: &str| version.split('.').next().map(str::to_string);
+        if !backup.crate_version.is_empty() && major(&backup.crate_version) != major(&current.crate_version) {
+            compatibility.problems.push(format!(
+                "taken by dmpool {}, this is {}",
+                backup.crate_version, current.crate_version
+            ));
+        }
+
+        for component in [VersionComponent::StoreFormat, VersionComponent::PplnsSchema] {
+            let (from, to) = (backup.get(component), current.get(component));
+            if from > to {
+                compatibility.problems.push(format!("{} version {} is newer than {}", component, from, to));
+                continue;
+            }
+            for version in from..to {
+                let migration = self.migrations.iter()
+                    .find(|m| m.component() == component && m.source_version() == version);
+                match migration {
+                    Some(migration) => {
+                        compatibility.migrations.push(migration.name().to_string());
+                        migrations.push(migration.clone());
+                    }
+                    None => {
+                        compatibility.problems.push(format!("no {} migration from version {}", component, version));
+                        break;
+                    }
+                }
+            }
+        }
+        (compatibility, migrations)
+    }
+
     /// Restore from a backup
-    pub async fn restore_backup(&self, backup_id: &str, target_path: Option<&Path>) -> Result<()> {
+    ///
+    /// The archive is extracted next to its destination and swapped in, so the
+    /// restored database contains exactly the backed up files. With
+    /// `target_dir` the database is restored below that directory, leaving the
+    /// live one alone; with `dry_run` nothing is written.
+    pub async fn restore_backup(&self, backup_id: &str, options: &RestoreOptions) -> Result<RestorePlan> {
+        self.restore_backup_with_progress(backup_id, options, &Progress::default()).await
+    }
+
+    /// Restore from a backup, reporting files extracted into `progress`
+    ///
+    /// Cancelling takes effect until the restored files start replacing the
+    /// existing ones; after that the restore runs to completion.
+    pub async fn restore_backup_with_progress(
+        &self,
+        backup_id: &str,
+        options: &RestoreOptions,
+        progress: &Progress,
+    ) -> Result<RestorePlan> {
         let metadata = self.load_metadata(backup_id)?;
+        // Backups from before column families were recorded are checked once extracted
+        let missing = options.column_families.iter()
+            .find(|name| !metadata.column_families.is_empty() && !metadata.column_families.contains_key(*name));
+        if let Some(missing) = missing {
+            return Err(anyhow::anyhow!("Backup {} has no column family {}", backup_id, missing));
+        }
+        let (compatibility, migrations) = self.migration_plan(&metadata);
+        if !compatibility.compatible() && !options.dry_run && !options.force {
+            return Err(anyhow::anyhow!(
+                "Backup {} is not compatible with this version ({}) - restore aborted; force it to restore anyway",
+                backup_id,
+                compatibility.problems.join("; ")
+            ));
+        }
 
         info!("Restoring backup: {} from {:?}", backup_id, metadata.file_path);
 
+        // Incremental backups are extracted on top of their bases
+        let mut files_total = metadata.file_checksums.len() as u64;
+        let mut base_id = metadata.base_id.clone();
+        while let Some(id) = base_id {
+            let base = self.load_metadata(&id)?;
+            files_total += base.file_checksums.len() as u64;
+            base_id = base.base_id;
+        }
+        progress.set_totals(files_total, metadata.original_size);
+
         // Validate checksum before restore
+        progress.set_phase("verifying");
         let current_checksum = self.calculate_checksum(&metadata.file_path)?;
         if current_checksum != metadata.checksum {
             return Err(anyhow::anyhow!(
@@ -432,63 +1824,237 @@ impl BackupManager {
             ));
         }
 
-        let restore_path = target_path.unwrap_or(&self.config.db_path);
-
-        // Ensure target directory exists or create it
-        if !restore_path.exists() {
-            fs::create_dir_all(restore_path)
+        // Archives hold the database directory under its own name
+        let target = match &options.target_dir {
+            Some(dir) => dir.clone(),
+            None => self.config.db_path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+        };
+        // Stage next to the target so the swap is a rename on the same filesystem
+        let staging_dir = if options.dry_run {
+            self.ensure_backup_dir()?;
+            self.config.backup_dir.clone()
+        } else {
+            fs::create_dir_all(&target)
                 .context("Failed to create restore directory")?;
+            target.clone()
+        };
+        let scratch = tempfile::Builder::new()
+            .prefix(".dmpool_restore_")
+            .tempdir_in(&staging_dir)
+            .context("Failed to create restore staging directory")?;
+        progress.set_phase("extracting");
+        self.stage_backup(&metadata, scratch.path(), progress)?;
+        if !options.dry_run {
+            let db_file = self.config.db_path.file_name()
+                .ok_or_else(|| anyhow::anyhow!("Invalid database path"))?;
+            for migration in &migrations {
+                progress.check_cancelled()?;
+                progress.set_phase("migrating");
+                migration.migrate(&scratch.path().join(db_file))
+                    .with_context(|| format!("Migration {} failed - restore aborted", migration.name()))?;
+                info!("Applied migration {} to backup {}", migration.name(), backup_id);
+            }
+        }
+        if !options.column_families.is_empty() {
+            return self.restore_column_families(&metadata, options, compatibility, &target, scratch.path(), progress);
         }
 
-        // Extract backup
-        let status = Command::new("tar")
-            .args([
-                "-xzf",
-                metadata.file_path.to_str().unwrap(),
-                "-C",
-                restore_path.parent().unwrap_or(Path::new(".")).to_str().unwrap(),
-            ])
-            .status()
-            .context("Failed to execute tar extract command")?;
+        progress.set_phase("comparing");
+        let plan = self.plan_restore(&metadata, compatibility, &target, scratch.path(), options.dry_run)?;
+        if options.dry_run {
+            info!(
+                "Dry run restore of {}: {} file(s) would change, {:+} bytes",
+                backup_id,
+                plan.changes.len(),
+                plan.size_delta
+            );
+            return Ok(plan);
+        }
 
-        if !status.success() {
-            return Err(anyhow::anyhow!("Backup extraction failed with exit code: {:?}", status.code()));
+        progress.check_cancelled()?;
+        progress.set_phase("replacing");
+        for entry in fs::read_dir(scratch.path()).context("Failed to read restored files")? {
+            let entry = entry?;
+            let dest = target.join(entry.file_name());
+            if dest.is_dir() {
+                fs::remove_dir_all(&dest).context("Failed to remove replaced directory")?;
+            } else if dest.exists() {
+                fs::remove_file(&dest).context("Failed to remove replaced file")?;
+            }
+            fs::rename(entry.path(), &dest).context("Failed to move restored files into place")?;
         }
 
-        info!("Backup restored successfully to: {:?}", restore_path);
+        info!("Backup restored successfully to: {:?}", target);
+        Ok(plan)
+    }
+
+    /// Replace only the requested column families of the database below
+    /// `target` with those of the backup extracted into `staged`
+    fn restore_column_families(
+        &self,
+        metadata: &BackupMetadata,
+        options: &RestoreOptions,
+        compatibility: BackupCompatibility,
+        target: &Path,
+        staged: &Path,
+        progress: &Progress,
+    ) -> Result<RestorePlan> {
+        let db_file = self.config.db_path.file_name()
+            .ok_or_else(|| anyhow::anyhow!("Invalid database path"))?;
+
+        progress.check_cancelled()?;
+        progress.set_phase(if options.dry_run { "comparing" } else { "copying" });
+        let column_families = storage::copy_column_families(
+            &staged.join(db_file),
+            &target.join(db_file),
+            &options.column_families,
+            options.target_dir.is_some(),
+            options.dry_run,
+        )?;
+        info!(
+            "{} column families {} of backup {}",
+            if options.dry_run { "Dry run restore of" } else { "Restored" },
+            options.column_families.join(", "),
+            metadata.id
+        );
+
+        Ok(RestorePlan {
+            backup_id: metadata.id.clone(),
+            backup_timestamp: metadata.timestamp,
+            dry_run: options.dry_run,
+            target: target.to_path_buf(),
+            backup_schema_version: metadata.schema_version,
+            current_schema_version: self.get_schema_version(),
+            compatible: compatibility.compatible(),
+            compatibility,
+            changes: Vec::new(),
+            unchanged_files: 0,
+            size_delta: 0,
+            column_families,
+        })
+    }
+
+    /// Extract a backup into `dest`, on top of its base if it is incremental
+    fn stage_backup(&self, metadata: &BackupMetadata, dest: &Path, progress: &Progress) -> Result<()> {
+        if let Some(base_id) = &metadata.base_id {
+            let base = self.load_metadata(base_id)
+                .with_context(|| format!("Base backup {} of {} not found", base_id, metadata.id))?;
+            if self.calculate_checksum(&base.file_path)? != base.checksum {
+                return Err(anyhow::anyhow!("Base backup {} checksum mismatch - restore aborted", base_id));
+            }
+            self.stage_backup(&base, dest, progress)?;
+        }
+        self.check_encryption_key(metadata)?;
+        self.extract_archive(&metadata.file_path, dest, progress)?;
+        for file in &metadata.deleted_files {
+            let path = dest.join(file);
+            if path.exists() {
+                fs::remove_file(&path).context("Failed to remove deleted file")?;
+            }
+        }
         Ok(())
     }
 
-    /// Delete old backups based on retention policy
+    /// Compare the extracted backup in `staged` with what is at `target`
+    fn plan_restore(
+        &self,
+        metadata: &BackupMetadata,
+        compatibility: BackupCompatibility,
+        target: &Path,
+        staged: &Path,
+        dry_run: bool,
+    ) -> Result<RestorePlan> {
+        let restored = self.calculate_tree_checksums(staged)?;
+        // Only the top-level entries of the archive are replaced
+        let mut current = BTreeMap::new();
+        for entry in fs::read_dir(staged).context("Failed to read restored files")? {
+            let existing = target.join(entry?.file_name());
+            if existing.is_dir() {
+                self.collect_tree_checksums(target, &existing, &mut current)?;
+            } else if existing.exists() {
+                let relative = existing.strip_prefix(target)?.to_string_lossy().to_string();
+                current.insert(relative, self.calculate_checksum(&existing)?);
+            }
+        }
+
+        let size = |root: &Path, file: &str| fs::metadata(root.join(file)).ok().map(|m| m.len());
+        let mut changes = Vec::new();
+        let mut unchanged_files = 0;
+        for (file, digest) in &restored {
+            let change = match current.get(file) {
+                Some(existing) if existing == digest => {
+                    unchanged_files += 1;
+                    continue;
+                }
+                Some(_) => FileChange::Modified,
+                None => FileChange::Added,
+            };
+            changes.push((file.clone(), change));
+        }
+        changes.extend(current.keys()
+            .filter(|file| !restored.contains_key(*file))
+            .map(|file| (file.clone(), FileChange::Removed)));
+
+        let changes: Vec<RestoreFileChange> = changes.into_iter()
+            .map(|(path, change)| {
+                let current_size = size(target, &path);
+                let backup_size = size(staged, &path);
+                RestoreFileChange {
+                    size_delta: backup_size.unwrap_or(0) as i64 - current_size.unwrap_or(0) as i64,
+                    path,
+                    change,
+                    current_size,
+                    backup_size,
+                }
+            })
+            .collect();
+
+        Ok(RestorePlan {
+            backup_id: metadata.id.clone(),
+            backup_timestamp: metadata.timestamp,
+            dry_run,
+            target: target.to_path_buf(),
+            backup_schema_version: metadata.schema_version,
+            current_schema_version: self.get_schema_version(),
+            compatible: compatibility.compatible(),
+            compatibility,
+            size_delta: changes.iter().map(|c| c.size_delta).sum(),
+            changes,
+            unchanged_files,
+            column_families: Vec::new(),
+        })
+    }
+
+    /// Delete the backups the retention policy doesn't keep
     pub async fn cleanup_old_backups(&self) -> Result<usize> {
-        let mut backups = self.list_backups()?;
-        let deleted_count = 0;
+        let backups = self.list_backups()?;
+        let mut deleted_count = 0;
 
-        if backups.len() <= self.config.retention_count {
-            info!("No old backups to clean up ({} <= {})", backups.len(), self.config.retention_count);
+        let retained = self.config.retention.retained(&backups, Utc::now());
+        if backups.iter().all(|b| retained.contains(&b.id)) {
+            info!("No old backups to clean up ({} retained)", backups.len());
             return Ok(0);
         }
 
-        // Remove oldest backups beyond retention limit
-        while backups.len() > self.config.retention_count {
-            if let Some(backup) = backups.pop() {
-                // Delete backup file
-                if backup.file_path.exists() {
-                    fs::remove_file(&backup.file_path)
-                        .context("Failed to delete backup file")?;
-                }
-
-                // Delete metadata file
-                let meta_path = self.get_metadata_path(&backup.id);
-                if meta_path.exists() {
-                    fs::remove_file(&meta_path)
-                        .context("Failed to delete metadata file")?;
-                }
+        for backup in backups.iter().filter(|b| !retained.contains(&b.id)) {
+            // Delete backup file
+            if backup.file_path.exists() {
+                fs::remove_file(&backup.file_path)
+                    .context("Failed to delete backup file")?;
+            }
 
-                info!("Deleted old backup: {}", backup.id);
+            // Delete metadata file
+            let meta_path = self.get_metadata_path(&backup.id);
+            if meta_path.exists() {
+                fs::remove_file(&meta_path)
+                    .context("Failed to delete metadata file")?;
             }
+
+            deleted_count += 1;
+            info!("Deleted old backup: {}", backup.id);
         }
 
+        self.update_catalog(|catalog| catalog.retain(|b| retained.contains(&b.id)));
         Ok(deleted_count)
     }
 
@@ -509,7 +2075,418 @@ impl BackupManager {
                 .context("Failed to delete metadata file")?;
         }
 
+        self.update_catalog(|catalog| catalog.retain(|b| b.id != backup_id));
         info!("Deleted backup: {}", backup_id);
         Ok(true)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn manager_with_db() -> (tempfile::TempDir, BackupManager) {
+        let root = tempfile::tempdir().unwrap();
+        let db_path = root.path().join("store");
+        fs::create_dir_all(db_path.join("nested")).unwrap();
+        fs::write(db_path.join("CURRENT"), b"MANIFEST-000001\n").unwrap();
+        fs::write(db_path.join("nested").join("000001.sst"), b"sst data").unwrap();
+
+        let manager = BackupManager::new(BackupConfig {
+            db_path,
+            backup_dir: root.path().join("backups"),
+            ..BackupConfig::default()
+        });
+        (root, manager)
+    }
+
+    /// Checkpoint source that snapshots by copying, standing in for RocksDB
+    struct CopyCheckpoint {
+        db_path: PathBuf,
+    }
+
+    impl CheckpointSource for CopyCheckpoint {
+        fn create_checkpoint(&self, dest: &Path) -> Result<()> {
+            fs::create_dir_all(dest)?;
+            fs::copy(self.db_path.join("CURRENT"), dest.join("CURRENT"))?;
+            Ok(())
+        }
+    }
+
+    struct FailingCheckpoint;
+
+    impl CheckpointSource for FailingCheckpoint {
+        fn create_checkpoint(&self, _dest: &Path) -> Result<()> {
+            Err(anyhow::anyhow!("read-only database"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_backup_uses_checkpoint_source() {
+        let (_root, manager) = manager_with_db();
+        let db_path = manager.config.db_path.clone();
+        let manager = manager.with_checkpoint_source(Arc::new(CopyCheckpoint { db_path }));
+
+        let metadata = manager.create_backup().await.unwrap();
+        assert!(metadata.from_checkpoint);
+        // Only what the checkpoint captured is archived, under the original name
+        assert_eq!(metadata.file_checksums.keys().collect::<Vec<_>>(), vec!["store/CURRENT"]);
+    }
+
+    #[tokio::test]
+    async fn test_backup_fails_when_checkpoint_fails() {
+        let (_root, manager) = manager_with_db();
+        let manager = manager.with_checkpoint_source(Arc::new(FailingCheckpoint));
+
+        let err = manager.create_backup().await.unwrap_err();
+        assert!(format!("{:#}", err).contains("not copying live database files"));
+        assert!(manager.list_backups().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_backup_refuses_without_free_space() {
+        let (_root, manager) = manager_with_db();
+        let space = manager.check_space().unwrap();
+        assert!(space.available_bytes > 0 && space.sufficient());
+        // The database, archived uncompressed and extracted to verify
+        assert_eq!(space.estimated_bytes, 2 * (16 + 8));
+
+        let manager = BackupManager::new(BackupConfig {
+            min_free_bytes: space.available_bytes,
+            ..manager.config.clone()
+        });
+        let err = manager.create_backup().await.unwrap_err();
+        assert!(err.to_string().contains("Not enough free space"));
+        assert!(manager.list_backups().unwrap().is_empty());
+        assert!(manager.last_failure().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_backup_records_per_file_checksums() {
+        let (_root, manager) = manager_with_db();
+        let metadata = manager.create_backup().await.unwrap();
+
+        assert!(metadata.file_checksums.contains_key("store/CURRENT"));
+        assert!(metadata.file_checksums.contains_key("store/nested/000001.sst"));
+
+        let report = manager.verify_backup(&metadata.id).await.unwrap();
+        assert!(report.valid);
+        assert_eq!(report.files_checked, 2);
+    }
+
+    #[tokio::test]
+    async fn test_progress_and_cancellation() {
+        let (_root, manager) = manager_with_db();
+        let progress = Progress::default();
+        let metadata = manager.create_backup_with_progress(BackupKind::Full, &progress).await.unwrap();
+        let report = progress.report(1.0);
+        assert_eq!((report.files_done, report.files_total), (2, 2));
+        assert_eq!((report.bytes_done, report.bytes_total), (24, 24));
+        assert_eq!(report.percent, Some(100.0));
+
+        let progress = Progress::default();
+        let inspect = manager.inspection_dir("progress").unwrap();
+        let options = RestoreOptions { dry_run: false, target_dir: Some(inspect), ..RestoreOptions::default() };
+        manager.restore_backup_with_progress(&metadata.id, &options, &progress).await.unwrap();
+        assert_eq!(progress.report(1.0).files_done, 2);
+
+        // A cancelled backup leaves no archive and isn't a failure
+        let progress = Progress::default();
+        progress.cancel();
+        assert!(manager.create_backup_with_progress(BackupKind::Full, &progress).await.is_err());
+        assert_eq!(manager.list_backups().unwrap().len(), 1);
+        assert!(manager.last_failure().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_restore_dry_run_and_alternate_directory() {
+        let (root, manager) = manager_with_db();
+        let metadata = manager.create_backup().await.unwrap();
+        let db_path = manager.config.db_path.clone();
+        fs::write(db_path.join("CURRENT"), b"MANIFEST-000002\n").unwrap();
+        fs::write(db_path.join("000002.log"), b"log").unwrap();
+
+        let dry_run = RestoreOptions { dry_run: true, target_dir: None, ..RestoreOptions::default() };
+        let plan = manager.restore_backup(&metadata.id, &dry_run).await.unwrap();
+        assert!(plan.compatible);
+        assert_eq!(plan.unchanged_files, 1);
+        let changes: Vec<_> = plan.changes.iter().map(|c| (c.path.as_str(), c.change)).collect();
+        assert_eq!(changes, vec![("store/CURRENT", FileChange::Modified), ("store/000002.log", FileChange::Removed)]);
+        assert_eq!(plan.size_delta, -3);
+        // Nothing was touched, and no staging directory is left behind
+        assert!(db_path.join("000002.log").exists());
+        assert_eq!(fs::read_dir(root.path()).unwrap().count(), 2);
+
+        let inspect = manager.inspection_dir("inspect").unwrap();
+        let options = RestoreOptions { dry_run: false, target_dir: Some(inspect.clone()), ..RestoreOptions::default() };
+        let plan = manager.restore_backup(&metadata.id, &options).await.unwrap();
+        assert_eq!(plan.changes.len(), 2);
+        assert_eq!(fs::read(inspect.join("store/CURRENT")).unwrap(), b"MANIFEST-000001\n");
+        assert_eq!(fs::read(db_path.join("CURRENT")).unwrap(), b"MANIFEST-000002\n");
+
+        manager.restore_backup(&metadata.id, &RestoreOptions::default()).await.unwrap();
+        assert_eq!(fs::read(db_path.join("CURRENT")).unwrap(), b"MANIFEST-000001\n");
+        assert!(!db_path.join("000002.log").exists());
+
+        assert_eq!(manager.backup_at(Utc::now()).unwrap().unwrap().id, metadata.id);
+        assert!(manager.backup_at(metadata.timestamp - chrono::Duration::seconds(1)).unwrap().is_none());
+        assert!(manager.inspection_dir("../store").is_err());
+
+        // Column families the backup doesn't have are refused before extracting
+        let mut metadata = metadata;
+        metadata.column_families.insert("shares".to_string(), vec!["store/000003.sst".to_string()]);
+        manager.save_metadata(&metadata).unwrap();
+        let options = RestoreOptions { column_families: vec!["users".to_string()], ..RestoreOptions::default() };
+        let err = manager.restore_backup(&metadata.id, &options).await.unwrap_err();
+        assert!(err.to_string().contains("no column family users"));
+    }
+
+    #[tokio::test]
+    async fn test_incremental_backup_restores_on_top_of_full() {
+        let (_root, manager) = manager_with_db();
+        let db_path = manager.config.db_path.clone();
+        let full = manager.create_backup().await.unwrap();
+
+        fs::write(db_path.join("000002.sst"), b"new sst").unwrap();
+        fs::remove_file(db_path.join("nested").join("000001.sst")).unwrap();
+        let incremental = manager.create_incremental_backup().await.unwrap();
+        assert_eq!(incremental.kind, BackupKind::Incremental);
+        assert_eq!(incremental.base_id.as_deref(), Some(full.id.as_str()));
+        assert_eq!(incremental.file_checksums.keys().collect::<Vec<_>>(), vec!["store/000002.sst"]);
+        assert_eq!(incremental.deleted_files, vec!["store/nested/000001.sst".to_string()]);
+        assert!(manager.verify_backup(&incremental.id).await.unwrap().valid);
+
+        let inspect = manager.inspection_dir("incremental").unwrap();
+        let options = RestoreOptions { dry_run: false, target_dir: Some(inspect.clone()), ..RestoreOptions::default() };
+        manager.restore_backup(&incremental.id, &options).await.unwrap();
+        assert_eq!(fs::read(inspect.join("store/CURRENT")).unwrap(), b"MANIFEST-000001\n");
+        assert_eq!(fs::read(inspect.join("store/000002.sst")).unwrap(), b"new sst");
+        assert!(!inspect.join("store/nested/000001.sst").exists());
+
+        // The full backup stays while a retained incremental needs it
+        let manager = BackupManager::new(BackupConfig {
+            retention: RetentionPolicy { keep_last: 1, ..RetentionPolicy::default() },
+            ..manager.config.clone()
+        });
+        assert_eq!(manager.cleanup_old_backups().await.unwrap(), 0);
+
+        let schedules = vec![
+            "incremental=0 * * * *".parse().unwrap(),
+            "full=0 3 * * *".parse().unwrap(),
+        ];
+        let manager = BackupManager::new(BackupConfig { schedules, ..BackupConfig::default() });
+        let at = |h, m| Utc.with_ymd_and_hms(2026, 1, 1, h, m, 0).unwrap();
+        assert_eq!(manager.next_scheduled(at(1, 30)), Some((at(2, 0), BackupKind::Incremental)));
+        assert_eq!(manager.next_scheduled(at(2, 30)), Some((at(3, 0), BackupKind::Full)));
+        assert!("weekly=0 3 * * *".parse::<BackupSchedule>().is_err());
+    }
+
+    #[test]
+    fn test_retention_policy_tiers_and_limits() {
+        let backup = |id: &str, at: DateTime<Utc>, size: u64, base: Option<&str>| BackupMetadata {
+            id: id.to_string(),
+            timestamp: at,
+            file_path: PathBuf::from(format!("/backups/{}.tar.gz", id)),
+            original_size: size,
+            backup_size: size,
+            compression_ratio: None,
+            validated: true,
+            schema_version: 1,
+            checksum: String::new(),
+            file_checksums: BTreeMap::new(),
+            from_checkpoint: false,
+            encryption_key_id: None,
+            kind: if base.is_some() { BackupKind::Incremental } else { BackupKind::Full },
+            base_id: base.map(str::to_string),
+            deleted_files: Vec::new(),
+            column_families: BTreeMap::new(),
+            versions: StoreVersions::default(),
+        };
+        let now = Utc.with_ymd_and_hms(2026, 3, 31, 12, 0, 0).unwrap();
+        // Two backups a day for 90 days, newest first
+        let backups: Vec<_> = (0..180)
+            .map(|i| backup(&format!("b{}", i), now - Duration::hours(12 * i), 10, None))
+            .collect();
+        let kept = |policy: &str| {
+            let mut ids: Vec<_> = policy.parse::<RetentionPolicy>().unwrap()
+                .retained(&backups, now)
+                .into_iter()
+                .map(|id| id.trim_start_matches('b').parse::<i64>().unwrap())
+                .collect();
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(kept(""), (0..7).collect::<Vec<_>>());
+        assert_eq!(kept("last=2,daily=3"), vec![0, 1, 2, 4]);
+        // 12 monthlies span back past the 90 days of backups
+        assert_eq!(kept("last=0,monthly=12").len(), 3);
+        assert_eq!(kept("last=1,weekly=4,max_age_days=15"), vec![0, 4, 18]);
+        assert_eq!(kept("last=2,daily=10,max_total_mb=0"), vec![0, 1]);
+
+        // An incremental keeps its base, which counts toward the size limit
+        let backups = vec![
+            backup("inc", now, 10, Some("full")),
+            backup("full", now - Duration::days(1), 100, None),
+            backup("old", now - Duration::days(2), 10, None),
+        ];
+        let policy = RetentionPolicy { keep_last: 1, keep_daily: 3, max_total_bytes: Some(115), ..RetentionPolicy::default() };
+        let retained = policy.retained(&backups, now);
+        assert!(retained.contains("inc") && retained.contains("full") && !retained.contains("old"));
+
+        assert!("daily=seven".parse::<RetentionPolicy>().is_err());
+        assert!("yearly=1".parse::<RetentionPolicy>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_backup_verifies_and_restores() {
+        let (_root, manager) = manager_with_db();
+        let config = manager.config.clone();
+        let manager = manager.with_encryption_key(BackupKey::new([3u8; 32]));
+        let metadata = manager.create_backup().await.unwrap();
+        assert!(metadata.file_path.to_string_lossy().ends_with(".tar.gz.enc"));
+        assert!(metadata.file_checksums.contains_key("store/CURRENT"));
+        assert!(!fs::read(&metadata.file_path).unwrap().windows(15).any(|w| w == b"MANIFEST-000001"));
+        // Only the encrypted archive and its metadata are left behind, besides the catalog
+        let leftovers = fs::read_dir(&config.backup_dir).unwrap()
+            .filter(|entry| entry.as_ref().unwrap().file_name() != CATALOG_FILE)
+            .count();
+        assert_eq!(leftovers, 2);
+        assert!(manager.verify_backup(&metadata.id).await.unwrap().valid);
+
+        let inspect = manager.inspection_dir("encrypted").unwrap();
+        let options = RestoreOptions { dry_run: false, target_dir: Some(inspect.clone()), ..RestoreOptions::default() };
+        manager.restore_backup(&metadata.id, &options).await.unwrap();
+        assert_eq!(fs::read(inspect.join("store/CURRENT")).unwrap(), b"MANIFEST-000001\n");
+
+        let without_key = BackupManager::new(config.clone());
+        let report = without_key.verify_backup(&metadata.id).await.unwrap();
+        assert!(!report.valid);
+        assert!(report.error.unwrap().contains("no encryption key"));
+        let wrong_key = BackupManager::new(config).with_encryption_key(BackupKey::new([4u8; 32]));
+        assert!(wrong_key.restore_backup(&metadata.id, &options).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_verify_reports_corrupted_files() {
+        let (_root, manager) = manager_with_db();
+        let mut metadata = manager.create_backup().await.unwrap();
+
+        metadata.file_checksums.insert("store/CURRENT".to_string(), "0".repeat(64));
+        metadata.file_checksums.insert("store/LOCK".to_string(), "0".repeat(64));
+        manager.save_metadata(&metadata).unwrap();
+
+        let report = manager.verify_backup(&metadata.id).await.unwrap();
+        assert!(!report.valid);
+        assert!(report.archive_checksum_ok);
+        assert_eq!(report.corrupted_files, vec!["store/CURRENT".to_string()]);
+        assert_eq!(report.missing_files, vec!["store/LOCK".to_string()]);
+        assert!(manager.validate_backup(&metadata).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_catalog_tracks_and_repairs_backups() {
+        let (_root, manager) = manager_with_db();
+        let first = manager.create_backup().await.unwrap();
+        assert_eq!(manager.list_backups().unwrap().len(), 1);
+        assert!(manager.catalog_path().exists());
+
+        // New backups are added to the existing catalog
+        let second = manager.create_incremental_backup().await.unwrap();
+        assert_eq!(manager.read_catalog().unwrap().len(), 2);
+        let ids: Vec<String> = manager.list_backups().unwrap().into_iter().map(|b| b.id).collect();
+        assert_eq!(ids, vec![second.id.clone(), first.id.clone()]);
+
+        // A metadata file removed behind the manager's back triggers a rescan
+        fs::remove_file(manager.get_metadata_path(&first.id)).unwrap();
+        let backups = manager.list_backups().unwrap();
+        assert_eq!(backups.len(), 1);
+        assert!(backups[0].validated);
+
+        // Edits the lazy check can't see are found by a repair
+        let mut edited = second.clone();
+        edited.validated = false;
+        fs::write(manager.get_metadata_path(&second.id), serde_json::to_string(&edited).unwrap()).unwrap();
+        let repair = manager.repair_catalog().unwrap();
+        assert_eq!(repair.updated, vec![second.id.clone()]);
+        assert!(repair.added.is_empty() && repair.removed.is_empty());
+        assert!(!manager.list_backups().unwrap()[0].validated);
+
+        manager.delete_backup(&second.id).await.unwrap();
+        assert!(manager.read_catalog().unwrap().is_empty());
+    }
+
+    /// Migration marking the stores it upgrades
+    struct MarkerMigration;
+
+    impl BackupMigration for MarkerMigration {
+        fn name(&self) -> &str {
+            "store_format_0_to_1"
+        }
+        fn component(&self) -> VersionComponent {
+            VersionComponent::StoreFormat
+        }
+        fn source_version(&self) -> u32 {
+            0
+        }
+        fn migrate(&self, db_path: &Path) -> Result<()> {
+            fs::write(db_path.join("MIGRATED"), b"")?;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_restore_checks_versions_and_migrates() {
+        let (_root, manager) = manager_with_db();
+        let mut metadata = manager.create_backup().await.unwrap();
+        assert_eq!(metadata.versions, StoreVersions::current());
+        assert!(manager.compatibility(&metadata).compatible());
+        let db_path = manager.config.db_path.clone();
+
+        // An older store format needs a migration
+        metadata.versions.store_format = 0;
+        manager.save_metadata(&metadata).unwrap();
+        let err = manager.restore_backup(&metadata.id, &RestoreOptions::default()).await.unwrap_err();
+        assert!(err.to_string().contains("no store format migration from version 0"));
+        let manager = manager.with_migration(Arc::new(MarkerMigration));
+        let plan = manager.restore_backup(&metadata.id, &RestoreOptions::default()).await.unwrap();
+        assert_eq!(plan.compatibility.migrations, vec!["store_format_0_to_1".to_string()]);
+        assert!(db_path.join("MIGRATED").exists());
+
+        // Backups of another major version are only restored when forced
+        metadata.versions = StoreVersions { crate_version: "99.0.0".to_string(), ..StoreVersions::current() };
+        manager.save_metadata(&metadata).unwrap();
+        assert!(manager.restore_backup(&metadata.id, &RestoreOptions::default()).await.is_err());
+        let forced = RestoreOptions { force: true, ..RestoreOptions::default() };
+        let plan = manager.restore_backup(&metadata.id, &forced).await.unwrap();
+        assert!(!plan.compatible);
+        assert!(!db_path.join("MIGRATED").exists());
+    }
+
+    #[tokio::test]
+    async fn test_import_uploaded_archive() {
+        let (_root, source) = manager_with_db();
+        let original = source.create_backup().await.unwrap();
+        let (_other, manager) = manager_with_db();
+
+        let upload = manager.upload_file().unwrap();
+        fs::copy(&original.file_path, upload.path()).unwrap();
+        let import = BackupImport { checksum: Some(format!("\"{}\"", original.checksum)), taken_at: Some(original.timestamp) };
+        let imported = manager.import_backup(upload, &import).await.unwrap();
+        assert_eq!(imported.file_checksums, original.file_checksums);
+        assert_eq!(imported.timestamp, original.timestamp);
+        assert!(imported.file_path.to_string_lossy().ends_with("_upload.tar.gz"));
+        assert!(manager.verify_backup(&imported.id).await.unwrap().valid);
+        assert_eq!(manager.list_backups().unwrap().len(), 1);
+
+        let upload = manager.upload_file().unwrap();
+        fs::copy(&original.file_path, upload.path()).unwrap();
+        let import = BackupImport { checksum: Some("0".repeat(64)), ..BackupImport::default() };
+        assert!(manager.import_backup(upload, &import).await.is_err());
+        let upload = manager.upload_file().unwrap();
+        fs::write(upload.path(), b"not an archive").unwrap();
+        assert!(manager.import_backup(upload, &BackupImport::default()).await.is_err());
+        assert_eq!(manager.list_backups().unwrap().len(), 1);
+    }
+}
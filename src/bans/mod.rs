@@ -0,0 +1,274 @@
+// Ban List for DMPool
+// Bans by BTC address, IP address or CIDR range, with optional expiry
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration as StdDuration;
+use tokio::sync::RwLock;
+use tracing::info;
+
+/// What a ban applies to
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum BanTarget {
+    Address(String),
+    Ip(IpAddr),
+    Cidr(IpNet),
+}
+
+impl BanTarget {
+    /// Whether requests from `ip` are covered by this ban
+    pub fn matches_ip(&self, ip: &IpAddr) -> bool {
+        match self {
+            BanTarget::Ip(banned) => banned == ip,
+            BanTarget::Cidr(net) => net.contains(ip),
+            BanTarget::Address(_) => false,
+        }
+    }
+}
+
+impl FromStr for BanTarget {
+    type Err = anyhow::Error;
+
+    /// Parse an IP address, a CIDR range (`10.0.0.0/8`) or a BTC address
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(anyhow::anyhow!("Ban target is empty"));
+        }
+        if let Ok(ip) = s.parse::<IpAddr>() {
+            return Ok(BanTarget::Ip(ip));
+        }
+        if s.contains('/') {
+            let net = s.parse::<IpNet>()
+                .map_err(|e| anyhow::anyhow!("Invalid CIDR range {}: {}", s, e))?;
+            // Store the canonical network so 10.1.2.3/8 and 10.0.0.0/8 are the same ban
+            return Ok(BanTarget::Cidr(net.trunc()));
+        }
+        if !s.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(anyhow::anyhow!("Invalid ban target: {}", s));
+        }
+        Ok(BanTarget::Address(s.to_string()))
+    }
+}
+
+impl fmt::Display for BanTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BanTarget::Address(address) => write!(f, "{}", address),
+            BanTarget::Ip(ip) => write!(f, "{}", ip),
+            BanTarget::Cidr(net) => write!(f, "{}", net),
+        }
+    }
+}
+
+/// A ban entry
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Ban {
+    pub id: String,
+    pub target: BanTarget,
+    pub reason: Option<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    /// `None` for permanent bans
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Ban {
+    /// Whether the ban is still in force at `now`
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_none_or(|expires| expires > now)
+    }
+}
+
+/// Ban list persisted as JSON
+pub struct BanManager {
+    path: PathBuf,
+    bans: RwLock<Vec<Ban>>,
+}
+
+impl BanManager {
+    /// Create a ban list stored at `path`
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            bans: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Load bans from disk, if present; expired bans are dropped
+    pub async fn load(&self) -> Result<usize> {
+        if !self.path.exists() {
+            return Ok(0);
+        }
+        let content = tokio::fs::read_to_string(&self.path).await
+            .context("Failed to read ban list")?;
+        let mut bans: Vec<Ban> = serde_json::from_str(&content)
+            .context("Failed to parse ban list")?;
+        let now = Utc::now();
+        bans.retain(|b| b.is_active(now));
+        let count = bans.len();
+        *self.bans.write().await = bans;
+        Ok(count)
+    }
+
+    async fn save(&self, bans: &[Ban]) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let content = serde_json::to_string_pretty(bans)?;
+        tokio::fs::write(&self.path, content).await
+            .context("Failed to write ban list")?;
+        Ok(())
+    }
+
+    /// Ban `target`, replacing any existing ban on it
+    ///
+    /// Without a duration the ban is permanent.
+    pub async fn ban(
+        &self,
+        target: BanTarget,
+        reason: Option<String>,
+        duration: Option<StdDuration>,
+        created_by: &str,
+    ) -> Result<Ban> {
+        let now = Utc::now();
+        let expires_at = match duration {
+            Some(d) if d.is_zero() => return Err(anyhow::anyhow!("Ban duration must be positive")),
+            Some(d) => Some(Duration::from_std(d).ok()
+                .and_then(|d| now.checked_add_signed(d))
+                .ok_or_else(|| anyhow::anyhow!("Ban duration is too long"))?),
+            None => None,
+        };
+        let ban = Ban {
+            id: uuid::Uuid::new_v4().to_string(),
+            target,
+            reason,
+            created_by: created_by.to_string(),
+            created_at: now,
+            expires_at,
+        };
+
+        let mut bans = self.bans.write().await;
+        bans.retain(|b| b.is_active(now) && b.target != ban.target);
+        bans.push(ban.clone());
+        self.save(&bans).await?;
+        info!("Banned {} by {} until {:?}", ban.target, created_by, ban.expires_at);
+        Ok(ban)
+    }
+
+    /// Lift a ban by id; returns the removed ban
+    pub async fn unban(&self, id: &str) -> Result<Option<Ban>> {
+        let mut bans = self.bans.write().await;
+        let Some(pos) = bans.iter().position(|b| b.id == id) else {
+            return Ok(None);
+        };
+        let ban = bans.remove(pos);
+        self.save(&bans).await?;
+        info!("Unbanned {}", ban.target);
+        Ok(Some(ban))
+    }
+
+    /// Lift the ban on `target`, if any
+    pub async fn unban_target(&self, target: &BanTarget) -> Result<Option<Ban>> {
+        let id = self.bans.read().await.iter()
+            .find(|b| &b.target == target)
+            .map(|b| b.id.clone());
+        match id {
+            Some(id) => self.unban(&id).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Bans currently in force, newest first
+    pub async fn active(&self) -> Vec<Ban> {
+        let now = Utc::now();
+        self.bans.read().await.iter().rev()
+            .filter(|b| b.is_active(now))
+            .cloned()
+            .collect()
+    }
+
+    /// Whether a BTC address is banned
+    pub async fn is_address_banned(&self, address: &str) -> bool {
+        let now = Utc::now();
+        self.bans.read().await.iter()
+            .any(|b| b.is_active(now) && matches!(&b.target, BanTarget::Address(a) if a == address))
+    }
+
+    /// BTC addresses with an active ban
+    pub async fn banned_addresses(&self) -> HashSet<String> {
+        let now = Utc::now();
+        self.bans.read().await.iter()
+            .filter(|b| b.is_active(now))
+            .filter_map(|b| match &b.target {
+                BanTarget::Address(address) => Some(address.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Active ban covering `ip`, directly or through a CIDR range
+    pub async fn ip_ban(&self, ip: &IpAddr) -> Option<Ban> {
+        let now = Utc::now();
+        self.bans.read().await.iter()
+            .find(|b| b.is_active(now) && b.target.matches_ip(ip))
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_targets() {
+        assert_eq!("192.0.2.7".parse::<BanTarget>().unwrap(), BanTarget::Ip("192.0.2.7".parse().unwrap()));
+        assert_eq!(
+            "10.1.2.3/8".parse::<BanTarget>().unwrap(),
+            BanTarget::Cidr("10.0.0.0/8".parse().unwrap())
+        );
+        assert_eq!(
+            "bc1qexample".parse::<BanTarget>().unwrap(),
+            BanTarget::Address("bc1qexample".to_string())
+        );
+        assert!("10.0.0.0/40".parse::<BanTarget>().is_err());
+        assert!("bad target".parse::<BanTarget>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ip_and_cidr_bans_expire_and_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bans.json");
+        let manager = BanManager::new(path.clone());
+
+        manager.ban("203.0.113.0/24".parse().unwrap(), Some("abuse".into()), None, "admin").await.unwrap();
+        manager.ban("2001:db8::1".parse().unwrap(), None, Some(StdDuration::from_secs(3600)), "admin").await.unwrap();
+        let address = manager.ban("bc1qexample".parse().unwrap(), None, None, "admin").await.unwrap();
+
+        assert!(manager.ip_ban(&"203.0.113.9".parse().unwrap()).await.is_some());
+        assert!(manager.ip_ban(&"203.0.114.9".parse().unwrap()).await.is_none());
+        assert!(manager.ip_ban(&"2001:db8::1".parse().unwrap()).await.is_some());
+        assert!(manager.is_address_banned("bc1qexample").await);
+        assert!(manager.ban("192.0.2.1".parse().unwrap(), None, Some(StdDuration::ZERO), "admin").await.is_err());
+
+        // Expired bans no longer apply
+        manager.bans.write().await[1].expires_at = Some(Utc::now() - Duration::seconds(1));
+        assert!(manager.ip_ban(&"2001:db8::1".parse().unwrap()).await.is_none());
+        assert_eq!(manager.active().await.len(), 2);
+
+        assert!(manager.unban(&address.id).await.unwrap().is_some());
+        assert!(!manager.is_address_banned("bc1qexample").await);
+
+        let reloaded = BanManager::new(path);
+        assert_eq!(reloaded.load().await.unwrap(), 1);
+        assert_eq!(reloaded.active().await[0].reason.as_deref(), Some("abuse"));
+    }
+}
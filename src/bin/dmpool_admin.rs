@@ -1,33 +1,210 @@
 // DMPool Admin Server
 // Standalone admin web interface for pool management
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use axum::{
-    extract::{Path, Query, State, Request},
-    http::StatusCode,
+    body::Body,
+    extract::{Extension, MatchedPath, Path, Query, State, Request},
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    http::{header, HeaderMap, Method, StatusCode, Uri},
     middleware::Next,
+    response::sse::{Event, KeepAlive, Sse},
     response::{Html, IntoResponse, Json, Response},
     routing::{get, post},
     Router,
     middleware,
 };
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use p2poolv2_lib::accounting::simple_pplns::SimplePplnsShare;
 use p2poolv2_lib::config::Config;
 use p2poolv2_lib::shares::chain::chain_store::ChainStore;
 use p2poolv2_lib::shares::share_block::ShareBlock;
 use p2poolv2_lib::store::Store;
-use dmpool::auth::{AuthManager, LoginRequest, LoginResponse, UserInfo};
-use dmpool::audit::{AuditLogger, AuditFilter};
-use dmpool::backup::{BackupManager, BackupConfig, BackupMetadata, BackupStats};
+use dmpool::assets;
+use dmpool::alert::{AlertChange, AlertConfig, AlertInputs, AlertManager};
+use dmpool::anomaly::{self, AnomalyDetector};
+use dmpool::auth::{self, AuthManager, Claims, LockoutPolicy, LockoutTarget, LoginAttempt, LoginOutcome, LoginRequest, LoginResponse, LoginResult, PasswordPolicy, RefreshRequest, SessionInfo, User};
+use dmpool::audit::{self, summarize_body, AuditLog, AuditLogger, AuditFilter, RotationPolicy};
+use dmpool::backup::{BackupConfig, BackupImport, BackupKey, BackupKind, BackupManager, CatalogRepair, RestoreOptions, StorePathCheckpoint};
+use dmpool::bans::{BanManager, BanTarget};
+use dmpool::blocks::{BlockState, BlockTracker, FoundBlock};
+use dmpool::bootstrap::{self, StartupError, StartupSettings};
+use dmpool::config_mgt::{self, ConfigManager, ConfigSchema, ConfigVersion, VersionConflict};
+use dmpool::config_watcher::ConfigWatcher;
+use dmpool::cron::CronExpr;
 use dmpool::confirmation::ConfigConfirmation;
-use dmpool::health::HealthChecker;
-use dmpool::rate_limit::{RateLimiterState, RateLimitConfig, rate_limit_middleware, login_rate_limit_middleware};
+use dmpool::connections::SocketTableCounter;
+use dmpool::estimate::{self, hashrate_ths, EstimateParams, NetworkStats};
+use dmpool::export::{ExportEncoder, ExportFormat};
+use dmpool::farms::{self, AddressWorkers, Farm, FarmStore};
+use dmpool::fees::{FeeLedger, FeeRange};
+use dmpool::geoip::{GeoInfo, GeoIp, GeoSummary};
+use dmpool::payout::{PayoutBatch, PayoutConfig, PayoutEngine, PayoutStatus};
+use dmpool::pplns_validator::{PayoutPreview, PplnsSimulator, ReplayBlock, ReplayParams};
+use dmpool::health::{self, HealthChecker, Readiness, ReadinessCheck};
+use dmpool::ingest::{self, AuthFailure, StratumEvent, StratumIngest};
+use dmpool::instances::{self, InstanceRegistry, PoolInstance};
+use dmpool::jobs::{Job, JobKind, JobRegistry, JobSettings, Progress};
+use dmpool::live_feed::{LiveEvent, LiveFeed, WorkerPresence};
+use dmpool::logging::{self, current_request_id, request_id_middleware, LogBuffer, LogEntry, LogFormat, LogQuery};
+use dmpool::maintenance::{MaintenanceInfo, MaintenanceMode};
+use dmpool::mempool::{FeeMonitor, FeeSnapshot};
+use dmpool::miner_access::{self, ChallengeStore, MinerEvent, MinerWebhooks};
+use dmpool::miner_settings::{MinerSettings, MinerSettingsStore};
+use dmpool::query_cache::{CacheSettings, QueryCache};
+use dmpool::outbox::{DeliveryStatus, Outbox, OutboxEvent, RetryPolicy};
+use dmpool::reports::{PoolReport, ReportInputs, ReportPeriod, ReportSettings, ReportSubscriptions, WorkerLine};
+use dmpool::restart::RestartCoordinator;
+use dmpool::secrets::{JwtKey, JwtKeySet, SecretStore};
+use dmpool::shutdown::{Shutdown, ShutdownSettings};
+use dmpool::storage::{CompactionTrigger, StoreMaintenance};
+use dmpool::store_doctor::{DoctorTrigger, StoreDoctor};
+use dmpool::timeseries::{self, TimeSeriesStore, POOL_SERIES, REJECT_RATE_SERIES, SHARE_RATE_SERIES, WORKERS_SERIES};
+use dmpool::tls::{self, ClientCertificate, TlsSettings};
+use dmpool::vardiff::{self, VardiffParams};
+use dmpool::two_factor::{
+    AssertionCredential, AssertionResponse, AttestationResponse, RegistrationCredential, RelyingParty,
+    SecurityKeyLogin, TwoFactorEnforcement, TwoFactorLogin, TwoFactorManager, TwoFactorPolicy,
+};
+use dmpool::versioning::{request_path, unversioned_path, versioned_router, ApiVersion};
+use dmpool::wallet::{PayoutWallet, WalletMode};
+use dmpool::worker_watch::{WatchDefaults, WorkerWatch, WorkerWatchEvent, WorkerWatchSettings};
+use dmpool::zmq_monitor::ZmqMonitor;
+use dmpool::share_stats::{self, OrphanTracker, ShareOutcome, ShareStatsTracker, WorkerShareStats};
+use dmpool::safety::{SafetyAnalyzer, UnsafeChange};
+use dmpool::cors::{cors_middleware, CorsPolicy};
+use dmpool::rate_limit::{RateLimiterState, RateLimitConfig, RateLimitRule, client_ip_middleware, extract_client_ip, rate_limit_middleware, login_rate_limit_middleware, ingest_rate_limit_middleware};
+#[cfg(feature = "redis")]
+use dmpool::rate_limit::RedisRateLimitStore;
+#[cfg(feature = "analytics")]
+use dmpool::analytics::AnalyticsMirror;
 use serde::{Deserialize, Serialize};
 use serde_json;
 use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::process::ExitCode;
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use tracing::{error, info, warn, Level};
+use tokio::sync::{broadcast, Notify, RwLock};
+use tracing::{debug, error, info, warn};
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{IntoParams, Modify, OpenApi, ToSchema};
+
+/// How often the live feed polls the store for new shares and metrics
+const LIVE_FEED_INTERVAL_SECS: u64 = 5;
+
+/// A worker with no shares in this window is reported as disconnected
+const LIVE_WORKER_WINDOW_SECS: u64 = 600;
+/// Network difficulty for estimates is fetched from the node at most this often
+const NETWORK_STATS_CACHE_SECS: u64 = 60;
+/// Recent pool blocks averaged for the transaction fees of an estimate
+const ESTIMATE_FEE_BLOCKS: usize = 10;
+/// Seconds between scans of the Bitcoin node for pool-found blocks
+const BLOCK_SCAN_INTERVAL_SECS: u64 = 60;
+/// Seconds between confirmation checks of found blocks that haven't matured
+const BLOCK_MATURITY_INTERVAL_SECS: u64 = 60;
+/// Reward assumed by payout previews before the pool has found a block (3.125 BTC)
+const DEFAULT_PREVIEW_REWARD_SATS: u64 = 312_500_000;
+/// Period a PPLNS replay covers when no start is given
+const PPLNS_REPLAY_DEFAULT_SECS: u64 = 30 * 24 * 3600;
+/// Longest PPLNS window a replay may rebuild per block
+const MAX_REPLAY_TTL_DAYS: u64 = 90;
+/// Seconds between checks for matured blocks and due payout batches
+const PAYOUT_CHECK_INTERVAL_SECS: u64 = 60;
+/// Fee snapshots returned by the mempool endpoint by default
+const FEE_HISTORY_DEFAULT_LIMIT: usize = 48;
+/// Recorded as the author of changes picked up from the config file
+const CONFIG_FILE_USER: &str = "config-file";
+/// Seconds between alert rule evaluations
+const ALERT_EVAL_INTERVAL_SECS: u64 = 60;
+/// Period the sharechain orphan rate alert looks at
+const ORPHAN_ALERT_WINDOW_SECS: u64 = 3600;
+/// Window of the stratum authorization failure alert
+const AUTH_FAILURE_ALERT_WINDOW_SECS: u64 = 3600;
+/// How often workers are checked for having stopped mining
+const WORKER_WATCH_INTERVAL_SECS: u64 = 60;
+/// Seconds between attempts of due webhook deliveries
+const OUTBOX_INTERVAL_SECS: u64 = 5;
+/// How often finished jobs past their retention are removed
+const JOB_CLEANUP_INTERVAL_SECS: u64 = 600;
+/// Seconds between checks for store corruption awaiting a diagnosis
+const STORE_DOCTOR_INTERVAL_SECS: u64 = 60;
+/// Hashrate points a summary report averages over
+const REPORT_HASHRATE_POINTS: u64 = 288;
+/// Window over which the public miner stats report hashrate
+const MINER_HASHRATE_WINDOW_SECS: u64 = 3600;
+/// Blocks and payments listed by the public stats API
+const PUBLIC_RECENT_ITEMS: usize = 20;
+/// Most points a hashrate history query may return
+const MAX_HASHRATE_POINTS: u64 = 2000;
+/// Longest window of the difficulty distribution
+const MAX_DIFFICULTY_WINDOW_SECS: u64 = 24 * 3600;
+/// Span of shares read from the store per chunk of a streamed export
+const EXPORT_CHUNK_SECS: u64 = 3600;
+/// Longest range an export may cover unless `EXPORT_MAX_RANGE_SECS` is set
+const DEFAULT_EXPORT_MAX_RANGE_SECS: u64 = 31 * 24 * 3600;
+/// Columns of the share export
+const SHARE_EXPORT_COLUMNS: &[&str] = &[
+    "timestamp", "time", "address", "worker", "difficulty", "user_id", "job_id", "extranonce2", "nonce",
+];
+/// Columns of the worker export
+const WORKER_EXPORT_COLUMNS: &[&str] = &[
+    "address", "worker", "shares", "total_difficulty", "hashrate_ths", "first_share", "last_share", "banned",
+];
+
+/// How long entering maintenance waits for running writes to finish
+const MAINTENANCE_DRAIN_SECS: u64 = 30;
+/// Mutating routes still served in maintenance mode, as unversioned paths
+const MAINTENANCE_ALLOWED_ROUTES: &[&str] = &[
+    "/api/maintenance/enter",
+    "/api/maintenance/exit",
+    "/api/backup/:id/restore",
+];
+
+/// Swagger UI page, loading its pinned, embedded assets and the spec from this server
+const SWAGGER_UI_HTML: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <title>DMPool Admin API</title>
+  <link rel="stylesheet" href="/swagger-ui/5.17.14/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="/swagger-ui/5.17.14/swagger-ui-bundle.js"></script>
+  <script>
+    SwaggerUIBundle({ url: "/api/v1/openapi.json", dom_id: "#swagger-ui" });
+  </script>
+</body>
+</html>"##;
+
+/// Largest request body the audit middleware will buffer
+const MAX_AUDITED_BODY_BYTES: usize = 1024 * 1024;
+
+/// Change-password route; `/auth/password` is kept as an alias
+const CHANGE_PASSWORD_PATH: &str = "/users/me/password";
+
+/// Only routes reachable while a user still has to change their password,
+/// as unversioned paths
+const PASSWORD_CHANGE_ALLOWED_PATHS: &[&str] = &["/api/users/me/password", "/api/auth/password", "/api/auth/logout"];
+/// Paths usable by users who must still enroll in 2FA, besides the password ones
+const TWO_FACTOR_ENROLL_ALLOWED_PATHS: &[&str] = &[
+    "/api/auth/2fa",
+    "/api/auth/2fa/setup",
+    "/api/auth/2fa/enable",
+    "/api/auth/webauthn/keys",
+    "/api/auth/webauthn/register/start",
+    "/api/auth/webauthn/register/finish",
+];
+
+/// Paths miner tokens may use, with the scope each needs
+const MINER_SCOPED_PATHS: &[(&str, &str)] = &[
+    ("/api/miner/stats", auth::SCOPE_MINER_READ),
+    ("/api/miner/payout-threshold", auth::SCOPE_MINER_PAYOUT),
+    ("/api/miner/webhooks", auth::SCOPE_MINER_WEBHOOKS),
+    ("/api/miner/worker-watch", auth::SCOPE_MINER_WEBHOOKS),
+    ("/api/miner/settings", auth::SCOPE_MINER_SETTINGS),
+];
 
 /// Admin state
 #[derive(Clone)]
@@ -38,23 +215,112 @@ struct AdminState {
     chain_store: Arc<ChainStore>,
     health_checker: Arc<HealthChecker>,
     auth_manager: Arc<AuthManager>,
+    two_factor: Arc<TwoFactorManager>,
+    /// Where JWT signing keys are reloaded from
+    secrets: SecretStore,
     rate_limiter: Arc<RateLimiterState>,
     audit_logger: Arc<AuditLogger>,
     config_confirmation: Arc<ConfigConfirmation>,
+    config_manager: Arc<ConfigManager>,
     backup_manager: Arc<BackupManager>,
+    /// Long-running operations of every instance: backups, restores,
+    /// exports, compactions and replays
+    jobs: Arc<JobRegistry>,
+    /// Statistics and compaction of the pool's store
+    storage: Arc<StoreMaintenance>,
+    /// Corruption checks and repair attempts of the pool's store
+    store_doctor: Arc<StoreDoctor>,
+    alert_manager: Arc<AlertManager>,
+    block_tracker: Arc<BlockTracker>,
+    payout_engine: Arc<PayoutEngine>,
+    /// Wakes the payout scheduler early, e.g. when a block matures
+    payout_wakeup: Arc<Notify>,
+    /// Fee and donation withheld per credited block
+    fee_ledger: Arc<FeeLedger>,
+    /// Node fee estimates and mempool size, used to defer uneconomical payouts
+    fee_monitor: Arc<FeeMonitor>,
+    /// Longest time range a share or worker export may cover
+    export_max_range_secs: u64,
+    /// Signed-message challenges answered for miner tokens
+    miner_challenges: Arc<ChallengeStore>,
+    miner_webhooks: Arc<MinerWebhooks>,
+    miner_settings: Arc<MinerSettingsStore>,
+    /// Admin-defined groups of addresses
+    farms: Arc<FarmStore>,
+    /// Operators' daily and weekly summary emails
+    report_subscriptions: Arc<ReportSubscriptions>,
+    report_settings: Arc<ReportSettings>,
+    /// Workers that stopped mining, and per-worker detection settings
+    worker_watch: Arc<WorkerWatch>,
+    /// Webhooks of pool events and their delivery queue
+    outbox: Arc<Outbox>,
+    /// Settings waiting for a pool restart, and how to restart it
+    restart: Arc<RestartCoordinator>,
+    /// Country and network lookups; disabled unless databases are configured
+    geoip: Arc<GeoIp>,
+    payout_wallet: Arc<PayoutWallet>,
+    hashrate_history: Arc<TimeSeriesStore>,
+    /// Pool hashrate and connection baselines of the hashrate sampler
+    hashrate_anomaly: Arc<AnomalyDetector>,
+    live_feed: Arc<LiveFeed>,
+    dashboard_cache: Arc<QueryCache<(), DashboardMetrics>>,
+    /// Recent shares by `(limit, window_secs)`, shared by the workers and stats endpoints
+    share_cache: Arc<QueryCache<(Option<usize>, u64), Vec<SimplePplnsShare>>>,
+    /// Payout previews of the PPLNS window by block reward, for public miner stats
+    preview_cache: Arc<QueryCache<u64, PayoutPreview>>,
+    /// Chain tip and difficulty for earnings estimates
+    network_cache: Arc<RwLock<Option<(std::time::Instant, NetworkStats)>>>,
     start_time: std::time::Instant,
-    banned_workers: Arc<RwLock<HashSet<String>>>,
+    ban_manager: Arc<BanManager>,
     worker_tags: Arc<RwLock<HashMap<String, Vec<String>>>>,
+    /// Share outcomes reported by the stratum server
+    share_stats: Arc<ShareStatsTracker>,
+    /// Orphaned and uncle share blocks reported by an in-process sharechain
+    orphans: Arc<OrphanTracker>,
+    /// Events pushed by the stratum server
+    ingest: Arc<StratumIngest>,
+    /// Pauses writes while a restore runs
+    maintenance: MaintenanceMode,
+    /// Recent log lines of this process and the pool
+    log_buffer: LogBuffer,
+    /// Every managed pool; the fields above hold the pool this state serves
+    instances: Arc<InstanceRegistry>,
+}
+
+impl AdminState {
+    /// This state serving `instance` instead, for its `/instances/<name>` routes
+    fn for_instance(&self, instance: &PoolInstance) -> AdminState {
+        AdminState {
+            config_path: instance.config_path.clone(),
+            config: instance.config.clone(),
+            store: instance.store.clone(),
+            chain_store: instance.chain_store.clone(),
+            health_checker: instance.health_checker.clone(),
+            backup_manager: instance.backup_manager.clone(),
+            storage: instance.storage.clone(),
+            store_doctor: instance.store_doctor.clone(),
+            block_tracker: instance.block_tracker.clone(),
+            dashboard_cache: Arc::new(QueryCache::new(self.dashboard_cache.settings())),
+            share_cache: Arc::new(QueryCache::new(self.share_cache.settings())),
+            preview_cache: Arc::new(QueryCache::new(self.preview_cache.settings())),
+            network_cache: Arc::new(RwLock::new(None)),
+            ..self.clone()
+        }
+    }
 }
 
 // ===== Response Types =====
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
+#[aliases(ApiEnvelope = ApiResponse<serde_json::Value>)]
 struct ApiResponse<T> {
     status: String,
     data: Option<T>,
     message: Option<String>,
     timestamp: u64,
+    /// Same as the `X-Request-Id` response header
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
 }
 
 impl<T: Serialize> ApiResponse<T> {
@@ -67,6 +333,7 @@ impl<T: Serialize> ApiResponse<T> {
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap_or_default()
                 .as_secs(),
+            request_id: current_request_id(),
         }
     }
 
@@ -79,19 +346,29 @@ impl<T: Serialize> ApiResponse<T> {
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap_or_default()
                 .as_secs(),
+            request_id: current_request_id(),
         }
     }
 }
 
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 struct DashboardMetrics {
     pool_hashrate_ths: f64,
     active_workers: u64,
+    connected_miners: u32,
+    /// Shares in the store, counted over the PPLNS window
     total_shares: u64,
     blocks_found: u64,
+    share_chain_height: u64,
     uptime_seconds: u64,
     pplns_window_shares: u64,
     current_difficulty: f64,
+    /// Countries and networks of connected miners, when GeoIP is enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    geography: Option<GeoSummary>,
+    /// Latest fee estimates and mempool size, once polled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fees: Option<FeeSnapshot>,
 }
 
 #[derive(Serialize)]
@@ -107,21 +384,8 @@ struct ConfigView {
     ignore_difficulty: bool,
     donation: Option<u16>,
     fee: Option<u16>,
-}
-
-#[derive(Serialize)]
-struct SafetyReport {
-    safe: bool,
-    critical_issues: Vec<SafetyIssue>,
-    warnings: Vec<SafetyIssue>,
-}
-
-#[derive(Serialize)]
-struct SafetyIssue {
-    severity: String,
-    param: String,
-    message: String,
-    recommendation: String,
+    /// Current config version, also sent as the `ETag` header
+    version: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -136,6 +400,47 @@ struct WorkerInfo {
     is_banned: bool,
     tags: Vec<String>,
     status: WorkerStatus,
+    share_stats: WorkerShareStats,
+    /// Stale and duplicate shares as a fraction of all submitted shares
+    reject_rate: f64,
+}
+
+#[derive(Serialize)]
+struct MinerWorkerStats {
+    name: String,
+    hashrate_ths: f64,
+    shares: u64,
+    last_share_time: u64,
+}
+
+#[derive(Serialize)]
+struct MinerPayment {
+    batch_id: String,
+    created_at: chrono::DateTime<Utc>,
+    amount_sats: u64,
+    status: PayoutStatus,
+    txid: Option<String>,
+}
+
+/// Public per-address stats
+#[derive(Serialize)]
+struct MinerStats {
+    address: String,
+    /// Chosen by the miner in their settings
+    display_name: Option<String>,
+    hashrate_ths: f64,
+    hashrate_window_secs: u64,
+    workers: Vec<MinerWorkerStats>,
+    /// The address's shares and difficulty in the PPLNS window
+    window_shares: u64,
+    window_difficulty: u64,
+    /// Fraction of the PPLNS window difficulty, in percent
+    window_share_percent: f64,
+    /// Projected payout if the pool found a block now
+    estimated_next_payout_sats: u64,
+    /// Credited but not yet batched
+    pending_balance_sats: u64,
+    recent_payments: Vec<MinerPayment>,
 }
 
 #[derive(Serialize)]
@@ -147,7 +452,8 @@ enum WorkerStatus {
 }
 
 /// Pagination request
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct PaginationRequest {
     page: Option<usize>,
     page_size: Option<usize>,
@@ -169,26 +475,220 @@ struct PaginatedResponse<T> {
 
 // ===== Request Types =====
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct ConfigUpdate {
     start_difficulty: Option<u32>,
     minimum_difficulty: Option<u32>,
     pool_signature: Option<String>,
+    /// Version the change was made against; `If-Match` may be sent instead
+    expected_version: Option<String>,
+    /// Apply even if the change introduces critical safety issues (admin only)
+    #[serde(default)]
+    force: bool,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct VersionDiffQuery {
+    /// Version to compare against; defaults to the current version
+    against: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct RollbackRequest {
+    /// Must be true to apply; otherwise only the diff is returned
+    #[serde(default)]
+    confirm: bool,
+    reason: Option<String>,
+    /// Apply even if the rollback introduces critical safety issues (admin only)
+    #[serde(default)]
+    force: bool,
+}
+
+#[derive(Deserialize, ToSchema, Default)]
+struct ReloadConfigRequest {
+    /// Version the file was edited against; `If-Match` may be sent instead
+    expected_version: Option<String>,
+    /// Apply even if the reload introduces critical safety issues (admin only)
+    #[serde(default)]
+    force: bool,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct BanRequest {
     reason: Option<String>,
+    /// Ban length in seconds; permanent if omitted
+    duration_secs: Option<u64>,
 }
 
-/// Main entry point
+#[derive(Deserialize, ToSchema)]
+struct CreateBanRequest {
+    /// BTC address, IP address or CIDR range
+    target: String,
+    reason: Option<String>,
+    /// Ban length in seconds; permanent if omitted
+    duration_secs: Option<u64>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct HealthHistoryQuery {
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ExportQuery {
+    /// `csv` (default) or `json`
+    format: Option<String>,
+    /// Unix seconds; defaults to 24 hours before `end`
+    start: Option<u64>,
+    /// Unix seconds; defaults to now
+    end: Option<u64>,
+    /// Only export this address
+    address: Option<String>,
+    /// Write the export to a file as a job instead of streaming it
+    background: Option<bool>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct FeeReportQuery {
+    /// `day`, `week`, `month` (default), `year` or `all`
+    range: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct HashrateQuery {
+    /// e.g. `24h`; defaults to 24h
+    range: Option<String>,
+    /// e.g. `5m`; defaults to 5m
+    step: Option<String>,
+    /// One worker of the address instead of the address total
+    worker: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DashboardHistoryQuery {
+    /// `hashrate`, `workers`, `share_rate` or `reject_rate`; defaults to hashrate
+    metric: Option<String>,
+    /// e.g. `7d`; defaults to 24h
+    range: Option<String>,
+    /// e.g. `1h`; defaults to 5m
+    step: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DifficultyDistributionQuery {
+    /// e.g. `6h`, at most 24h; defaults to 1h
+    range: Option<String>,
+    /// Only this address's shares and workers
+    address: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct RateLimitRuleRequest {
+    route_prefix: String,
+    user: Option<String>,
+    role: Option<String>,
+    limit: u32,
+    window_secs: u64,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct PplnsPreviewQuery {
+    /// Hypothetical block reward; defaults to the last found block's reward
+    reward_sats: Option<u64>,
+    /// Only return the payout of this address
+    address: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct PplnsReplayQuery {
+    /// Unix seconds; defaults to 30 days before `end`
+    start: Option<u64>,
+    /// Unix seconds; defaults to now
+    end: Option<u64>,
+    /// PPLNS window to replay with; defaults to the configured `pplns_ttl_days`
+    ttl_days: Option<u64>,
+    /// Pool fee to replay with (basis points); defaults to the configured fee
+    fee_bps: Option<u16>,
+    /// Donation to replay with (basis points); defaults to the configured donation
+    donation_bps: Option<u16>,
+    /// Only return the delta of this address
+    address: Option<String>,
+    /// Replay as a job instead of answering when done
+    background: Option<bool>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct AlertHistoryQuery {
+    rule_id: Option<String>,
+    limit: Option<usize>,
+}
+
+/// Live feed auth; browsers cannot set headers on WebSocket or `EventSource` requests
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct LiveFeedQuery {
+    token: Option<String>,
+}
+
+/// Main entry point; `--check` validates the setup and exits without serving
 #[tokio::main]
-async fn main() -> Result<()> {
-    tracing_subscriber::fmt()
-        .with_max_level(Level::INFO)
-        .init();
+async fn main() -> ExitCode {
+    let log_buffer_size: usize = std::env::var("LOG_BUFFER_SIZE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(5000);
+    let log_buffer = LogBuffer::new(log_buffer_size);
+    let log_format = match LogFormat::from_env() {
+        Ok(format) => format,
+        Err(e) => {
+            eprintln!("Invalid log settings: {:#}", e);
+            return ExitCode::from(78);
+        }
+    };
+    logging::init(log_format, Some(log_buffer.clone()));
+
+    if std::env::args().skip(1).any(|arg| arg == "--check") {
+        let check = bootstrap::check(&StartupSettings::from_env()).await;
+        print!("{}", check);
+        println!("{}", if check.is_ok() { "Startup check passed" } else { "Startup check failed" });
+        return ExitCode::from(check.exit_code());
+    }
+
+    match run(log_buffer).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => match e.downcast_ref::<StartupError>() {
+            Some(startup) => {
+                error!("Startup failed: {}", startup);
+                error!("{}", startup.hint());
+                ExitCode::from(startup.exit_code())
+            }
+            None => {
+                error!("Admin server failed: {:#}", e);
+                ExitCode::FAILURE
+            }
+        },
+    }
+}
 
-    let config_path = std::env::var("CONFIG_PATH").unwrap_or_else(|_| "config.toml".to_string());
+/// Set up and serve the admin API until shutdown
+async fn run(log_buffer: LogBuffer) -> Result<()> {
+    if let Ok(pool_log) = std::env::var("POOL_LOG_FILE") {
+        info!("Following pool log {}", pool_log);
+        tokio::spawn(log_buffer.clone().tail_file(pool_log.into()));
+    }
+
+    let settings = StartupSettings::from_env();
+    let config_path = settings.config_path.clone();
     let port: u16 = std::env::var("ADMIN_PORT")
         .unwrap_or_else(|_| "8080".to_string())
         .parse()
@@ -198,36 +698,27 @@ async fn main() -> Result<()> {
     let admin_username = std::env::var("ADMIN_USERNAME").unwrap_or_else(|_| "admin".to_string());
     let admin_password = std::env::var("ADMIN_PASSWORD").unwrap_or_else(|_| "admin123".to_string());
 
-    // Get JWT secret - MUST be set in production
-    let is_production = std::env::var("DMP_ENV").unwrap_or_else(|_| "development".to_string()) == "production";
-    let jwt_secret = std::env::var("JWT_SECRET").unwrap_or_else(|_| {
-        if is_production {
-            error!("JWT_SECRET environment variable MUST be set in production!");
-            error!("Generate a secure secret with: openssl rand -base64 32");
-            std::process::exit(1);
-        } else {
-            // For development, generate a random secret each time
-            use rand::Rng;
-            let secret: String = rand::thread_rng()
-                .sample_iter(&rand::distributions::Alphanumeric)
-                .take(32)
-                .map(char::from)
-                .collect();
-            warn!("Using generated JWT secret for development. Set JWT_SECRET for persistence!");
-            secret
-        }
-    });
+    // Secrets are looked up in the providers of SECRET_PROVIDERS, env vars by default
+    let secrets = bootstrap::secret_store()?;
+    info!("Secret providers: {}", secrets.provider_names().join(", "));
+
+    // Get JWT signing keys - MUST be set in production
+    let is_production = settings.production;
+    let jwt_keys = bootstrap::load_jwt_keys(&secrets, is_production).await?;
+
+    // Containers stop the server with SIGTERM
+    let shutdown = Shutdown::on_signal();
+    let shutdown_settings = ShutdownSettings::from_env();
 
-    // Validate JWT secret length
-    if jwt_secret.len() < 32 {
-        error!("JWT_SECRET must be at least 32 characters long! Current length: {}", jwt_secret.len());
-        std::process::exit(1);
+    // JWTs and passwords travel over this connection
+    let tls = bootstrap::setting("ADMIN_TLS", TlsSettings::from_env("ADMIN"))?;
+    if is_production && !tls.is_enabled() {
+        warn!("Admin API is served over plain HTTP; set ADMIN_TLS_CERT and ADMIN_TLS_KEY or terminate TLS in front of it");
     }
 
     // Load config
-    let config = Config::load(&config_path)?;
-    let store = Arc::new(Store::new(config.store.path.clone(), true)
-        .map_err(|e| anyhow::anyhow!("Failed to open store: {}", e))?);
+    let config = bootstrap::load_config(&config_path)?;
+    let store = Arc::new(bootstrap::open_store(&config.store.path)?);
     let genesis = ShareBlock::build_genesis_for_network(config.stratum.network);
     let chain_store = Arc::new(ChainStore::new(
         store.clone(),
@@ -235,62 +726,509 @@ async fn main() -> Result<()> {
         config.stratum.network,
     ));
 
+    // Initialize 2FA manager
+    let data_dir = settings.data_dir.clone();
+    bootstrap::check_data_dir(&data_dir)?;
+    let two_factor_policy = bootstrap::setting("2FA policy", TwoFactorPolicy::from_env())?;
+    if !two_factor_policy.required_roles.is_empty() {
+        info!("2FA required for role(s) {} ({:?} until enrolled)",
+            two_factor_policy.required_roles.join(", "), two_factor_policy.enforcement);
+    }
+    let mut two_factor = TwoFactorManager::new(
+        data_dir.join("2fa"),
+        "DMPool Admin".to_string(),
+    ).with_policy(two_factor_policy);
+    if let Some(rp) = bootstrap::setting("WebAuthn", RelyingParty::from_env())? {
+        info!("Security keys enabled for {} ({})", rp.id, rp.origins.join(", "));
+        two_factor = two_factor.with_webauthn(rp);
+    }
+    let two_factor = Arc::new(two_factor);
+    two_factor.initialize().await?;
+
     // Initialize auth manager
-    let auth_manager = Arc::new(AuthManager::new(jwt_secret));
+    let lockout_defaults = LockoutPolicy::default();
+    let lockout_policy = LockoutPolicy {
+        max_user_failures: std::env::var("LOGIN_LOCKOUT_USER_FAILURES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(lockout_defaults.max_user_failures),
+        max_ip_failures: std::env::var("LOGIN_LOCKOUT_IP_FAILURES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(lockout_defaults.max_ip_failures),
+        lockout_secs: std::env::var("LOGIN_LOCKOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(lockout_defaults.lockout_secs),
+    };
+    let auth_manager = Arc::new(
+        AuthManager::from_jwt_keys(jwt_keys)
+            .with_lockout_policy(lockout_policy)
+            .with_password_policy(bootstrap::setting("password policy", PasswordPolicy::from_env())?)
+            .with_two_factor(two_factor.clone()),
+    );
     auth_manager.load().await?;  // Load existing users from disk
     auth_manager.init_default_admin(&admin_username, &admin_password).await?;
     info!("Initialized admin user: {}", admin_username);
 
     // Initialize rate limiter
-    let rate_limit_config = RateLimitConfig::default();
+    let rate_limit_config = bootstrap::setting("rate limit", RateLimitConfig::from_env())?;
     let api_rpm = rate_limit_config.api_rpm.get();
     let login_rpm = rate_limit_config.login_rpm.get();
-    let rate_limiter = Arc::new(RateLimiterState::new(rate_limit_config));
-    info!("Initialized rate limiter: {} req/min (API), {} req/min (login)",
-        api_rpm, login_rpm);
+    let rate_limiter = RateLimiterState::new(rate_limit_config)
+        .with_rules_path(data_dir.join("rate_limit_rules.json"));
+    let rule_count = rate_limiter.load_rules().await?;
+    // Replicas behind a load balancer share counters through Redis
+    #[cfg(feature = "redis")]
+    let rate_limiter = match std::env::var("RATE_LIMIT_REDIS_URL") {
+        Ok(url) => {
+            let store = RedisRateLimitStore::connect(&url).await
+                .map_err(|e| anyhow::anyhow!("Failed to connect to rate limit Redis: {}", e))?;
+            info!("Using Redis for rate limit counters");
+            rate_limiter.with_store(Arc::new(store))
+        }
+        Err(_) => rate_limiter,
+    };
+    #[cfg(not(feature = "redis"))]
+    if std::env::var("RATE_LIMIT_REDIS_URL").is_ok() {
+        warn!("RATE_LIMIT_REDIS_URL is set but this build lacks the redis feature; using in-memory rate limits");
+    }
+    let rate_limiter = Arc::new(rate_limiter);
+    info!("Initialized rate limiter: {} req/min (API), {} req/min (login), {} route rule(s)",
+        api_rpm, login_rpm, rule_count);
 
-    // Initialize audit logger
-    let audit_logger = Arc::new(AuditLogger::default());
-    info!("Initialized audit logger (max 10000 entries in memory)");
+    let cors_policy = Arc::new(bootstrap::setting("CORS", CorsPolicy::from_env())?);
+    if cors_policy.is_enabled() {
+        info!("CORS allowed origins: {}", cors_policy.allowed_origins.join(", "));
+    }
+
+    // Initialize audit logger, persisted as rotated JSONL under the data dir
+    let audit_retention_days: i64 = std::env::var("AUDIT_RETENTION_DAYS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(90);
+    let audit_max_file_mb: u64 = std::env::var("AUDIT_MAX_FILE_MB")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(50);
+    let audit_anchor_interval: u64 = std::env::var("AUDIT_ANCHOR_INTERVAL")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(audit::DEFAULT_ANCHOR_INTERVAL);
+    let audit_policy = RotationPolicy {
+        max_file_bytes: Some(audit_max_file_mb * 1024 * 1024),
+        max_file_entries: std::env::var("AUDIT_MAX_FILE_ENTRIES").ok().and_then(|v| v.parse().ok()),
+        max_file_age_hours: std::env::var("AUDIT_MAX_FILE_AGE_HOURS").ok().and_then(|v| v.parse().ok()),
+        schedule: match std::env::var("AUDIT_ROTATION_SCHEDULE") {
+            Ok(cron) => Some(cron.parse::<CronExpr>()?),
+            Err(_) => None,
+        },
+        compress: std::env::var("AUDIT_COMPRESS_ARCHIVES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(true),
+        retention_days: Some(audit_retention_days),
+        max_archive_bytes: std::env::var("AUDIT_MAX_ARCHIVE_MB")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .map(|mb| mb * 1024 * 1024),
+    };
+    let geoip = Arc::new(GeoIp::from_env()?);
+    let audit_memory_entries: usize = std::env::var("AUDIT_MEMORY_ENTRIES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(10000);
+    let audit_logger = AuditLogger::with_persistence_async(audit_memory_entries, data_dir.join("audit"))
+        .await?
+        .with_rotation_policy(audit_policy)
+        .with_anchor_interval(audit_anchor_interval)
+        .with_geoip(geoip.clone());
+    let loaded = audit_logger.load_from_file().await?;
+    match audit_logger.verify().await {
+        Ok(report) if !report.valid => warn!("Audit log integrity check failed: {:?}", report.broken),
+        Ok(_) => {}
+        Err(e) => warn!("Failed to verify audit log: {}", e),
+    }
+    let audit_logger = Arc::new(audit_logger);
+    info!("Initialized audit logger ({} entries loaded, {} day retention)", loaded, audit_retention_days);
 
     // Initialize config confirmation
     let config_confirmation = Arc::new(ConfigConfirmation::new());
+
+    // Every applied change is snapshotted as a config version
+    let config_manager = Arc::new(ConfigManager::new(data_dir.join("config_versions")));
+    config_manager.initialize().await?;
+    if let Err(e) = config_manager.ensure_baseline(&config).await {
+        warn!("Running config failed schema validation, no baseline version recorded: {}", e);
+    }
     info!("Initialized config confirmation system");
 
     // Initialize backup manager
-    let backup_config = BackupConfig {
-        db_path: config.store.path.clone().into(),
-        backup_dir: std::path::PathBuf::from("./backups"),
-        retention_count: 7,
-        compress: true,
-        interval_hours: 24,
-    };
-    let backup_manager = Arc::new(BackupManager::new(backup_config));
+    let backup_config = BackupConfig::from_env(&config.store.path)?;
+    // The admin server only holds a read-only store handle, which cannot produce
+    // RocksDB checkpoints; checkpoints open the store by path, which fails
+    // while the pool is running rather than copying its live files
+    let backup_key = BackupKey::from_secrets(&secrets).await?;
+    let mut backup_manager = BackupManager::new(backup_config.clone())
+        .with_checkpoint_source(Arc::new(StorePathCheckpoint::new(&config.store.path)));
+    match backup_key.clone() {
+        Some(key) => {
+            info!("Backups are encrypted with key {}", key.fingerprint());
+            backup_manager = backup_manager.with_encryption_key(key);
+        }
+        None => warn!("No backup encryption key set; backups are stored unencrypted"),
+    }
+    let backup_manager = Arc::new(backup_manager);
     info!("Initialized backup manager");
 
-    let state = AdminState {
-        config_path,
+    // Compacting by path needs the pool stopped, so windows are off by default
+    let compaction_schedule = match std::env::var("STORE_COMPACTION_SCHEDULE") {
+        Ok(cron) => Some(cron.parse::<CronExpr>()?),
+        Err(_) => None,
+    };
+    let mut storage = StoreMaintenance::new(&config.store.path);
+    if let Some(schedule) = &compaction_schedule {
+        storage = storage.with_schedule(schedule.clone());
+    }
+    let storage = Arc::new(storage);
+
+    // Alert rules come from alerts.json in the data dir, or a recommended set
+    let alerts_path = data_dir.join("alerts.json");
+    let alert_config = if alerts_path.exists() {
+        let content = std::fs::read_to_string(&alerts_path)?;
+        serde_json::from_str(&content)?
+    } else {
+        AlertConfig::recommended()
+    };
+    let alert_manager = Arc::new(AlertManager::new(alert_config));
+    let live_feed = Arc::new(LiveFeed::default());
+    let ingest = Arc::new(StratumIngest::new(std::env::var("INGEST_TOKEN").ok()));
+
+    let defaults = PayoutConfig::default();
+    let payout_config = PayoutConfig {
+        maturity_confirmations: std::env::var("PAYOUT_MATURITY_CONFIRMATIONS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(defaults.maturity_confirmations),
+        min_payout_sats: std::env::var("PAYOUT_MIN_SATS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(defaults.min_payout_sats),
+        batch_interval_secs: std::env::var("PAYOUT_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(defaults.batch_interval_secs),
+        max_fee_bps: std::env::var("PAYOUT_MAX_FEE_BPS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(defaults.max_fee_bps),
+    };
+    // Pool blocks are recognised by the pool signature in their coinbase
+    let pool_signature = config.stratum.pool_signature.clone().unwrap_or_default();
+    let block_tracker = Arc::new(
+        BlockTracker::new(data_dir.join("blocks.json"), &pool_signature)
+            .with_maturity_confirmations(payout_config.maturity_confirmations),
+    );
+    let loaded = block_tracker.load().await?;
+    info!("Loaded {} found block(s)", loaded);
+    let payout_engine = Arc::new(PayoutEngine::new(data_dir.join("payouts.json"), payout_config));
+    payout_engine.load().await?;
+    let fee_ledger = Arc::new(FeeLedger::new(data_dir.join("fees.json")));
+    let loaded = fee_ledger.load().await?;
+    info!("Loaded fee records of {} block(s)", loaded);
+    let miner_webhooks = Arc::new(MinerWebhooks::new(data_dir.join("miner_webhooks.json"))?);
+    let loaded = miner_webhooks.load().await?;
+    info!("Loaded {} miner webhook(s)", loaded);
+    let miner_settings = Arc::new(MinerSettingsStore::new(data_dir.join("miner_settings.json")));
+    let loaded = miner_settings.load().await?;
+    info!("Loaded settings of {} miner(s)", loaded);
+    let farms = Arc::new(FarmStore::new(data_dir.join("farms.json")));
+    let loaded = farms.load().await?;
+    info!("Loaded {} farm(s)", loaded);
+    let report_subscriptions = Arc::new(ReportSubscriptions::new(data_dir.join("report_subscriptions.json")));
+    let loaded = report_subscriptions.load().await?;
+    info!("Loaded {} report subscription(s)", loaded);
+    let report_settings = Arc::new(ReportSettings::from_env()?);
+    let worker_watch = Arc::new(WorkerWatch::new(data_dir.join("worker_watch.json"), WatchDefaults::from_env()));
+    let loaded = worker_watch.load().await?;
+    info!("Loaded down detection settings of {} worker(s)", loaded);
+    let jobs = Arc::new(JobRegistry::new(data_dir.join("jobs"), JobSettings::from_env()));
+    let loaded = jobs.load().await?;
+    info!("Loaded {} job record(s), {} worker(s)", loaded, jobs.settings().workers);
+    let retry_defaults = RetryPolicy::default();
+    let outbox = Arc::new(Outbox::with_retry_policy(data_dir.join("outbox.json"), RetryPolicy {
+        max_attempts: std::env::var("WEBHOOK_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(retry_defaults.max_attempts),
+        initial_backoff_secs: std::env::var("WEBHOOK_RETRY_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(retry_defaults.initial_backoff_secs),
+        ..retry_defaults
+    })?);
+    let loaded = outbox.load().await?;
+    info!("Loaded {} event webhook(s)", loaded);
+    let restart = Arc::new(RestartCoordinator::from_env()?);
+
+    let wallet_mode = match std::env::var("PAYOUT_WALLET_MODE") {
+        Ok(mode) => mode.parse::<WalletMode>()?,
+        Err(_) => WalletMode::default(),
+    };
+    let payout_confirmations: i64 = std::env::var("PAYOUT_CONFIRMATIONS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(6);
+    let payout_wallet = Arc::new(
+        PayoutWallet::new(wallet_mode).with_required_confirmations(payout_confirmations),
+    );
+    info!("Payout wallet mode: {:?}", wallet_mode);
+
+    let hashrate_sample_secs: u64 = std::env::var("HASHRATE_SAMPLE_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(300);
+    let hashrate_history = Arc::new(TimeSeriesStore::new(
+        data_dir.join("timeseries"),
+        timeseries::default_tiers(hashrate_sample_secs),
+    ));
+    hashrate_history.load().await?;
+
+    let ban_manager = Arc::new(BanManager::new(data_dir.join("bans.json")));
+    let loaded = ban_manager.load().await?;
+    info!("Loaded {} active ban(s)", loaded);
+
+    // A running hashblock subscription tells whether ZMQ actually delivers blocks
+    let zmq_monitor = Arc::new(ZmqMonitor::new(config.stratum.zmqpubhashblock.clone()));
+    let new_tips = zmq_monitor.subscribe_blocks();
+    tokio::spawn(zmq_monitor.clone().run());
+    let zmq_stale_secs: u64 = std::env::var("ZMQ_STALE_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(3600);
+    let zmq_stale_after = std::time::Duration::from_secs(zmq_stale_secs);
+    let store_doctor = Arc::new(StoreDoctor::new(&config.store.path, data_dir.join("store_doctor")));
+    let health_checker = HealthChecker::new(config.clone())
+        .with_store(store.clone())
+        .with_zmq_monitor(zmq_monitor.clone())
+        .with_zmq_stale_after(zmq_stale_after)
+        // The pool runs in another process; count its stratum sockets instead
+        .with_connection_counter(Arc::new(SocketTableCounter::new(config.stratum.port)))
+        .with_backup_manager(backup_manager.clone())
+        .with_stratum_probe(health::stratum_probe_address(&config))
+        .with_store_doctor(store_doctor.clone());
+
+    // The pool at CONFIG_PATH is the primary instance; POOL_INSTANCES adds more
+    let primary_name = std::env::var("POOL_NAME").unwrap_or_else(|_| "default".to_string());
+    instances::validate_name(&primary_name)?;
+    let mut instance_registry = InstanceRegistry::new(PoolInstance {
+        name: primary_name,
+        config_path: config_path.clone(),
         config: Arc::new(RwLock::new(config.clone())),
         store: store.clone(),
         chain_store,
-        health_checker: Arc::new(HealthChecker::new(config).with_store(store.clone())),
+        health_checker: Arc::new(health_checker),
+        backup_manager: backup_manager.clone(),
+        storage: storage.clone(),
+        store_doctor: store_doctor.clone(),
+        block_tracker: block_tracker.clone(),
+        zmq_monitor,
+    });
+    if let Ok(list) = std::env::var("POOL_INSTANCES") {
+        for spec in instances::parse_instances(&list)? {
+            let instance = PoolInstance::open(
+                &spec,
+                &data_dir,
+                &backup_config,
+                backup_key.clone(),
+                zmq_stale_after,
+                compaction_schedule.as_ref(),
+            ).await?;
+            tokio::spawn(instance.zmq_monitor.clone().run());
+            info!("Managing pool instance {} ({})", instance.name, instance.config_path);
+            instance_registry.add(instance)?;
+        }
+    }
+    let instance_registry = Arc::new(instance_registry);
+    let primary = instance_registry.primary().clone();
+    let query_cache_settings = CacheSettings::from_env();
+
+    let state = AdminState {
+        config_path,
+        config: primary.config.clone(),
+        store: store.clone(),
+        chain_store: primary.chain_store.clone(),
+        health_checker: primary.health_checker.clone(),
         auth_manager: auth_manager.clone(),
+        two_factor: two_factor.clone(),
+        secrets: secrets.clone(),
         rate_limiter: rate_limiter.clone(),
         audit_logger: audit_logger.clone(),
         config_confirmation: config_confirmation.clone(),
+        config_manager: config_manager.clone(),
         backup_manager: backup_manager.clone(),
+        jobs,
+        storage,
+        store_doctor,
+        alert_manager: alert_manager.clone(),
+        block_tracker: block_tracker.clone(),
+        payout_engine,
+        payout_wakeup: Arc::new(Notify::new()),
+        fee_ledger,
+        fee_monitor: Arc::new(FeeMonitor::default()),
+        export_max_range_secs: std::env::var("EXPORT_MAX_RANGE_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_EXPORT_MAX_RANGE_SECS),
+        miner_challenges: Arc::new(ChallengeStore::new()),
+        miner_webhooks,
+        miner_settings,
+        farms,
+        report_subscriptions,
+        report_settings,
+        worker_watch,
+        outbox,
+        restart,
+        geoip,
+        payout_wallet,
+        hashrate_history,
+        hashrate_anomaly: Arc::new(AnomalyDetector::new(
+            std::env::var("HASHRATE_EWMA_ALPHA")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(anomaly::DEFAULT_ALPHA),
+            anomaly::DEFAULT_WARMUP_SAMPLES,
+        )),
+        live_feed: live_feed.clone(),
+        dashboard_cache: Arc::new(QueryCache::new(query_cache_settings)),
+        share_cache: Arc::new(QueryCache::new(query_cache_settings)),
+        preview_cache: Arc::new(QueryCache::new(query_cache_settings)),
+        network_cache: Arc::new(RwLock::new(None)),
         start_time: std::time::Instant::now(),
-        banned_workers: Arc::new(RwLock::new(HashSet::new())),
+        ban_manager,
         worker_tags: Arc::new(RwLock::new(HashMap::new())),
+        share_stats: Arc::new(ShareStatsTracker::new()),
+        orphans: Arc::new(OrphanTracker::new()),
+        ingest: ingest.clone(),
+        maintenance: MaintenanceMode::new(),
+        log_buffer,
+        instances: instance_registry.clone(),
     };
 
+    tokio::spawn(run_live_feed(state.clone()));
+    tokio::spawn(run_worker_watch(state.clone()));
+    if let Ok(path) = std::env::var("INGEST_SOCKET") {
+        tokio::spawn(run_ingest_socket(state.clone(), path));
+    }
+    info!("Started live dashboard feed ({}s interval)", LIVE_FEED_INTERVAL_SECS);
+    tokio::spawn(run_hashrate_sampler(state.clone(), hashrate_sample_secs));
+    info!("Started hashrate sampler ({}s interval)", hashrate_sample_secs);
+    tokio::spawn(run_backup_scheduler(state.clone()));
+    tokio::spawn(run_compaction_scheduler(state.clone()));
+    if let Some(schedule) = &state.audit_logger.policy().schedule {
+        info!("Rotating the audit log on schedule {}", schedule);
+        tokio::spawn(run_audit_rotation(state.clone()));
+    }
+    tokio::spawn(run_store_doctor(state.clone()));
+    tokio::spawn(run_job_cleanup(state.jobs.clone()));
+    tokio::spawn(run_outbox(state.clone(), config_manager.subscribe()));
+    tokio::spawn(run_report_scheduler(state.clone()));
+    let fee_poll_secs: u64 = std::env::var("FEE_POLL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(300);
+    tokio::spawn(run_fee_monitor(state.clone(), fee_poll_secs));
+    info!("Started fee monitor ({}s interval)", fee_poll_secs);
+    info!("Started webhook delivery ({}s interval)", OUTBOX_INTERVAL_SECS);
+    // The primary instance's data is mirrored into SQL for ad-hoc reporting
+    #[cfg(feature = "analytics")]
+    if let Ok(url) = std::env::var("ANALYTICS_DATABASE_URL") {
+        let sync_secs: u64 = std::env::var("ANALYTICS_SYNC_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|secs| *secs > 0)
+            .unwrap_or(60);
+        match AnalyticsMirror::connect(&url).await {
+            Ok(mirror) => {
+                let mirror = mirror
+                    .with_store(state.store.clone())
+                    .with_blocks(state.block_tracker.clone())
+                    .with_payouts(state.payout_engine.clone())
+                    .with_audit(state.audit_logger.clone());
+                tokio::spawn(run_analytics_mirror(Arc::new(mirror), sync_secs));
+                info!("Started analytics mirror ({}s interval)", sync_secs);
+            }
+            Err(e) => warn!("Analytics mirror disabled: {:#}", e),
+        }
+    }
+    #[cfg(not(feature = "analytics"))]
+    if std::env::var("ANALYTICS_DATABASE_URL").is_ok() {
+        warn!("ANALYTICS_DATABASE_URL is set but this build lacks the analytics feature; not mirroring");
+    }
+    // Other instances get their own backups, compaction and found block tracking; payouts,
+    // alert rules and the live feed follow the primary instance only
+    for instance in instance_registry.iter().skip(1) {
+        let instance_state = state.for_instance(instance);
+        tokio::spawn(run_backup_scheduler(instance_state.clone()));
+        tokio::spawn(run_compaction_scheduler(instance_state.clone()));
+        tokio::spawn(run_store_doctor(instance_state.clone()));
+        tokio::spawn(run_block_scanner(instance_state.clone(), instance.zmq_monitor.subscribe_blocks()));
+        tokio::spawn(run_block_maturity_tracker(instance_state));
+    }
+
+    if pool_signature.is_empty() {
+        warn!("No pool_signature configured; found blocks will not be tracked");
+    } else {
+        tokio::spawn(run_block_scanner(state.clone(), new_tips));
+        info!("Started found block scanner ({}s interval)", BLOCK_SCAN_INTERVAL_SECS);
+        tokio::spawn(run_block_maturity_tracker(state.clone()));
+        info!("Started block maturity tracker ({}s interval)", BLOCK_MATURITY_INTERVAL_SECS);
+        tokio::spawn(run_payout_scheduler(state.clone()));
+        info!("Started payout scheduler ({}s interval)", PAYOUT_CHECK_INTERVAL_SECS);
+    }
+
+    match ConfigWatcher::new(std::path::Path::new(&state.config_path)) {
+        Ok(watcher) => {
+            tokio::spawn(run_config_watcher(state.clone(), watcher));
+            info!("Watching {} for changes", state.config_path);
+        }
+        Err(e) => warn!("Config hot reload disabled: {:#}", e),
+    }
+
+    let alert_state = state.clone();
+    tokio::spawn(alert_manager.clone().run_evaluation_loop(
+        std::time::Duration::from_secs(ALERT_EVAL_INTERVAL_SECS),
+        move || {
+            let state = alert_state.clone();
+            async move { collect_alert_inputs(&state).await }
+        },
+    ));
+    info!("Started alert rule evaluation ({}s interval)", ALERT_EVAL_INTERVAL_SECS);
+
     // Create public router (no auth required, but rate limited)
+    // Routes are relative to the API version root; see `versioned_router`
     let public_routes = Router::new()
-        .route("/", get(index))
-        .route("/api/health", get(health))
-        .route("/api/services/status", get(services_status))
+        .route("/health", get(health))
+        .route("/health/live", get(health_live))
+        .route("/health/ready", get(health_ready))
+        .route("/services/status", get(services_status))
         // Login has stricter rate limiting
-        .route("/api/auth/login", post(login))
+        .route("/auth/login", post(login))
+        .route("/auth/login/2fa", post(login_2fa))
+        .route("/auth/login/webauthn", post(login_webauthn))
+        .route("/auth/refresh", post(refresh_token))
+        .route("/estimate", get(earnings_estimate))
+        .route("/miner/challenge", post(miner_challenge))
+        .route("/miner/token", post(miner_token))
+        .route("/miner/settings/signed", post(set_signed_miner_settings))
+        .route("/openapi.json", get(openapi_json))
+        .route("/docs", get(swagger_ui))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            audit_middleware,
+        ))
         .route_layer(middleware::from_fn_with_state(
             rate_limiter.clone(),
             rate_limit_middleware,
@@ -301,36 +1239,205 @@ async fn main() -> Result<()> {
             login_rate_limit_middleware,
         ));
 
+    // Read-only miner stats, served on their own port so the admin port can stay firewalled
+    if let Ok(public_port) = std::env::var("PUBLIC_API_PORT") {
+        let public_v1 = Router::new()
+            .route("/miners/:address", get(public_miner_stats))
+            .route("/blocks", get(public_blocks))
+            .route("/estimate", get(earnings_estimate))
+            .route("/openapi.json", get(public_openapi_json))
+            .route_layer(middleware::from_fn_with_state(
+                rate_limiter.clone(),
+                rate_limit_middleware,
+            ));
+        let public_api = versioned_router(vec![(ApiVersion::V1, public_v1)])
+            .layer(middleware::from_fn_with_state(
+                state.clone(),
+                ban_middleware,
+            ))
+            .layer(middleware::from_fn_with_state(
+                rate_limiter.clone(),
+                client_ip_middleware,
+            ))
+            .layer(middleware::from_fn(request_id_middleware))
+            .with_state(state.clone())
+            .fallback(not_found);
+        let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", public_port)).await?;
+        info!("Public stats API listening on port {}", public_port);
+        let stopped = shutdown.clone();
+        tokio::spawn(async move {
+            let stopped = async move { stopped.wait().await };
+            let public_api = public_api.into_make_service_with_connect_info::<SocketAddr>();
+            if let Err(e) = axum::serve(listener, public_api).with_graceful_shutdown(stopped).await {
+                error!("Public stats API stopped: {}", e);
+            }
+        });
+    }
+
+    // Live feeds authenticate themselves, since browsers can't send the auth header
+    let live_routes = Router::new()
+        .route("/ws", get(live_ws))
+        .route("/logs/stream", get(logs_stream))
+        .route("/alerts/stream", get(alerts_stream))
+        .route_layer(middleware::from_fn_with_state(
+            rate_limiter.clone(),
+            rate_limit_middleware,
+        ));
+
     // Create protected router (auth required + rate limited)
     let protected_routes = Router::new()
-        .route("/api/dashboard", get(dashboard))
-        .route("/api/config", get(get_config).post(update_config))
-        .route("/api/config/reload", post(reload_config))
-        .route("/api/workers", get(workers_list))
-        .route("/api/workers/:address", get(worker_detail))
-        .route("/api/workers/:address/ban", post(ban_worker))
-        .route("/api/workers/:address/unban", post(unban_worker))
-        .route("/api/workers/:address/tags", post(add_worker_tag))
-        .route("/api/workers/:address/tags/:tag", post(remove_worker_tag))
-        .route("/api/blocks", get(blocks_list))
-        .route("/api/blocks/:height", get(block_detail))
-        .route("/api/logs", get(logs))
-        .route("/api/safety/check", get(safety_check))
-        .route("/api/audit/logs", get(audit_logs))
-        .route("/api/audit/stats", get(audit_stats))
-        .route("/api/audit/rotate", post(audit_rotate))
-        .route("/api/audit/export", post(audit_export))
-        .route("/api/config/confirmations", get(get_confirmations))
-        .route("/api/config/confirmations/:id", post(confirm_config))
-        .route("/api/config/confirmations/:id/apply", post(apply_config))
+        .route("/dashboard", get(dashboard))
+        .route("/dashboard/history", get(dashboard_history))
+        .route("/connections", get(list_connections))
+        .route("/config", get(get_config).post(update_config))
+        .route("/config/reload", post(reload_config))
+        .route("/config/schema", get(config_schema))
+        .route("/config/vardiff/simulate", get(simulate_vardiff))
+        .route("/config/versions", get(list_config_versions))
+        .route("/config/versions/:id/diff", get(config_version_diff))
+        .route("/config/versions/:id/rollback", post(rollback_config_version))
+        .route("/workers", get(workers_list))
+        .route("/workers/export", get(export_workers))
+        .route("/workers/by-address", get(workers_by_address))
+        .route("/workers/by-address/:address", get(address_workers))
+        .route("/workers/:address", get(worker_detail))
+        .route("/shares/export", get(export_shares))
+        .route("/workers/:address/hashrate", get(worker_hashrate))
+        .route("/farms", get(list_farms))
+        .route("/farms/:name", get(get_farm).post(set_farm))
+        .route("/farms/:name/delete", post(delete_farm))
+        .route("/farms/:name/hashrate", get(farm_hashrate))
+        .route("/hashrate", get(pool_hashrate))
+        .route("/workers/:address/ban", post(ban_worker))
+        .route("/workers/:address/unban", post(unban_worker))
+        .route("/workers/:address/tags", post(add_worker_tag))
+        .route("/workers/:address/tags/:tag", post(remove_worker_tag))
+        .route("/workers/:address/watch", get(worker_watch_settings).post(set_worker_watch))
+        .route("/bans", get(list_bans).post(create_ban))
+        .route("/bans/:id/delete", post(delete_ban))
+        .route("/ratelimit/rules", get(list_rate_limit_rules).post(create_rate_limit_rule))
+        .route("/ratelimit/rules/:id/delete", post(delete_rate_limit_rule))
+        .route("/blocks", get(blocks_list))
+        .route("/blocks/:height", get(block_detail))
+        .route("/blocks/:height/status", get(block_status))
+        .route("/pplns/preview", get(pplns_preview))
+        .route("/pplns/validate", get(pplns_validate))
+        .route("/pplns/replay", get(pplns_replay))
+        .route("/sharechain/orphans", get(sharechain_orphans))
+        .route("/stats/difficulty-distribution", get(difficulty_distribution))
+        .route("/payouts", get(payout_history))
+        .route("/payouts/pending", get(payout_pending))
+        .route("/payouts/blocks", get(payout_blocks))
+        .route("/payouts/schedule", post(schedule_payouts))
+        .route("/payouts/:id", get(payout_detail))
+        .route("/payouts/:id/paid", post(mark_payout_paid))
+        .route("/payouts/:id/cancel", post(cancel_payout))
+        .route("/payouts/:id/broadcast", post(broadcast_payout))
+        .route("/mempool", get(mempool_status))
+        .route("/fees/report", get(fee_report))
+        .route("/fees/blocks", get(fee_blocks))
+        .route("/logs", get(logs))
+        .route("/health/history", get(health_history))
+        .route("/safety/check", get(safety_check))
+        .route("/audit/logs", get(audit_logs))
+        .route("/audit/search", get(audit_search))
+        .route("/audit/stats", get(audit_stats))
+        .route("/audit/verify", get(audit_verify))
+        .route("/audit/rotate", post(audit_rotate))
+        .route("/audit/export", post(audit_export))
+        .route("/config/confirmations", get(get_confirmations).post(request_config_change))
+        .route("/config/confirmations/:id", post(confirm_config))
+        .route("/config/confirmations/:id/apply", post(apply_config))
         // Backup API routes
-        .route("/api/backup/create", post(create_backup))
-        .route("/api/backup/list", get(list_backups))
-        .route("/api/backup/stats", get(backup_stats))
-        .route("/api/backup/:id", get(get_backup))
-        .route("/api/backup/:id/delete", post(delete_backup))
-        .route("/api/backup/:id/restore", post(restore_backup))
-        .route("/api/backup/cleanup", post(cleanup_backups))
+        .route("/backup/create", post(create_backup))
+        .route("/backup/list", get(list_backups))
+        .route("/backup/stats", get(backup_stats))
+        .route("/backup/at", get(backup_at))
+        .route("/backup/:id", get(get_backup))
+        .route("/backup/:id/delete", post(delete_backup))
+        .route("/backup/:id/download", get(download_backup))
+        .route("/backup/:id/restore", post(restore_backup))
+        .route("/backup/:id/verify", get(verify_backup))
+        .route("/backup/cleanup", post(cleanup_backups))
+        .route("/backup/upload", post(upload_backup))
+        .route("/backup/catalog/repair", post(repair_backup_catalog))
+        .route("/jobs", get(list_jobs))
+        .route("/jobs/:id", get(get_job))
+        .route("/jobs/:id/cancel", post(cancel_job))
+        .route("/jobs/:id/download", get(download_job))
+        .route("/store/stats", get(store_stats))
+        .route("/store/compact", post(compact_store))
+        .route("/store/compactions", get(store_compactions))
+        .route("/store/doctor", get(store_doctor_status).post(run_store_diagnosis))
+        // Maintenance mode
+        .route("/maintenance", get(maintenance_status))
+        .route("/maintenance/enter", post(enter_maintenance))
+        .route("/maintenance/exit", post(exit_maintenance))
+        // Alert API routes
+        .route("/alerts", get(active_alerts))
+        .route("/alerts/history", get(alert_history))
+        .route("/alerts/:id/ack", post(acknowledge_alert))
+        // User management API routes
+        .route("/users", get(list_users).post(create_user))
+        .route("/users/:username/delete", post(delete_user))
+        .route("/users/:username/disable", post(disable_user))
+        .route("/users/:username/enable", post(enable_user))
+        .route("/users/:username/reset-password", post(reset_user_password))
+        .route("/users/:username/revoke-sessions", post(revoke_user_sessions))
+        .route("/users/:username/unlock", post(unlock_user))
+        .route("/lockouts", get(list_lockouts))
+        .route("/lockouts/:ip/unlock", post(unlock_ip))
+        .route(CHANGE_PASSWORD_PATH, get(password_status).post(change_password))
+        .route("/auth/password", post(change_password))
+        .route("/auth/logout", post(logout))
+        .route("/auth/sessions", get(list_sessions))
+        .route("/auth/sessions/:id/revoke", post(revoke_session))
+        .route("/auth/attempts", get(list_login_attempts))
+        .route("/auth/2fa", get(two_factor_status))
+        .route("/auth/2fa/setup", post(two_factor_setup))
+        .route("/auth/2fa/enable", post(two_factor_enable))
+        .route("/auth/2fa/disable", post(two_factor_disable))
+        .route("/auth/2fa/recovery-codes", post(two_factor_recovery_codes))
+        .route("/auth/webauthn/keys", get(list_security_keys))
+        .route("/auth/webauthn/keys/:id/delete", post(delete_security_key))
+        .route("/auth/webauthn/register/start", post(start_security_key_registration))
+        .route("/auth/webauthn/register/finish", post(finish_security_key_registration))
+        .route("/secrets", get(secrets_status))
+        .route("/secrets/jwt/reload", post(reload_jwt_keys))
+        .route("/instances", get(list_instances))
+        .route("/instances/overview", get(instances_overview))
+        .route("/miner-tokens", get(list_miner_tokens).post(create_miner_token))
+        .route("/miner-tokens/:id/revoke", post(revoke_miner_token))
+        // Miner token routes, acting on the token's address
+        .route("/miner/stats", get(own_miner_stats))
+        .route("/miner/payout-threshold", get(miner_payout_threshold).post(set_miner_payout_threshold))
+        .route("/miner/webhooks", get(list_miner_webhooks).post(register_miner_webhook))
+        .route("/miner/webhooks/:id/delete", post(delete_miner_webhook))
+        .route("/miner/worker-watch", get(own_worker_watch_settings).post(set_own_worker_watch))
+        .route("/miner/settings", get(own_miner_settings).post(set_own_miner_settings))
+        .route("/miner-settings", get(list_miner_settings))
+        .route("/miner-settings/:address", get(get_miner_settings).post(set_miner_settings))
+        .route("/miner-settings/:address/delete", post(delete_miner_settings))
+        .route("/reports/subscription", get(own_report_subscription).post(set_report_subscription))
+        .route("/reports/subscription/delete", post(delete_report_subscription))
+        .route("/reports/subscriptions", get(list_report_subscriptions))
+        .route("/reports/preview", get(preview_report))
+        .route("/reports/send", post(send_report_now))
+        .route("/webhooks", get(list_webhooks).post(create_webhook))
+        .route("/webhooks/:id/delete", post(delete_webhook))
+        .route("/webhooks/deliveries", get(list_webhook_deliveries))
+        .route("/webhooks/deliveries/:id/retry", post(retry_webhook_delivery))
+        .route("/system/restart", get(restart_status).post(restart_pool))
+        // Refuse writes while in maintenance mode
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            maintenance_middleware,
+        ))
+        // Record mutating requests once the caller is known
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            audit_middleware,
+        ))
         // Apply rate limiting first
         .route_layer(middleware::from_fn_with_state(
             rate_limiter.clone(),
@@ -342,28 +1449,136 @@ async fn main() -> Result<()> {
             auth_middleware,
         ));
 
-    // Combine all routes
-    let app = public_routes
-        .merge(protected_routes)
-        .with_state(state)
-        .fallback(not_found);
+    // Routes of a single pool instance, served under /instances/<name>
+    let instance_routes = Router::new()
+        .route("/dashboard", get(dashboard))
+        .route("/connections", get(list_connections))
+        .route("/config", get(get_config))
+        .route("/services/status", get(services_status))
+        .route("/health/history", get(health_history))
+        .route("/workers", get(workers_list))
+        .route("/workers/export", get(export_workers))
+        .route("/workers/:address", get(worker_detail))
+        .route("/shares/export", get(export_shares))
+        .route("/blocks", get(blocks_list))
+        .route("/blocks/:height", get(block_detail))
+        .route("/blocks/:height/status", get(block_status))
+        .route("/backup/create", post(create_backup))
+        .route("/backup/list", get(list_backups))
+        .route("/backup/stats", get(backup_stats))
+        .route("/backup/at", get(backup_at))
+        .route("/backup/:id", get(get_backup))
+        .route("/backup/:id/delete", post(delete_backup))
+        .route("/backup/:id/download", get(download_backup))
+        .route("/backup/:id/restore", post(restore_backup))
+        .route("/backup/:id/verify", get(verify_backup))
+        .route("/backup/cleanup", post(cleanup_backups))
+        .route("/backup/upload", post(upload_backup))
+        .route("/backup/catalog/repair", post(repair_backup_catalog))
+        .route("/store/stats", get(store_stats))
+        .route("/store/compact", post(compact_store))
+        .route("/store/compactions", get(store_compactions))
+        .route("/store/doctor", get(store_doctor_status).post(run_store_diagnosis))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            maintenance_middleware,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            audit_middleware,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            rate_limiter.clone(),
+            rate_limit_middleware,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            auth_manager.clone(),
+            auth_middleware,
+        ));
+
+    // Stratum events authenticate with INGEST_TOKEN and have their own rate
+    // limit, since a busy pool reports shares faster than the API limit
+    let ingest_routes = Router::new()
+        .route("/internal/events", post(ingest_events))
+        .route_layer(middleware::from_fn_with_state(
+            rate_limiter.clone(),
+            ingest_rate_limit_middleware,
+        ));
+
+    let mut api_v1 = public_routes
+        .merge(live_routes)
+        .merge(ingest_routes)
+        .merge(protected_routes);
+    for instance in instance_registry.iter() {
+        api_v1 = api_v1.nest(
+            &format!("/instances/{}", instance.name),
+            instance_routes.clone().with_state(state.for_instance(instance)),
+        );
+    }
+
+    // Combine all routes; a future version is added here next to v1
+    let app = versioned_router(vec![(ApiVersion::V1, api_v1)])
+        .route("/", get(index))
+        // Banned IPs are refused before anything else runs
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            ban_middleware,
+        ))
+        // Preflights are answered here, before authentication
+        .layer(middleware::from_fn_with_state(
+            cors_policy,
+            cors_middleware,
+        ))
+        // Resolves the client IP every other layer and handler reads
+        .layer(middleware::from_fn_with_state(
+            rate_limiter.clone(),
+            client_ip_middleware,
+        ))
+        // Outermost, so every log line of a request carries its ID
+        .layer(middleware::from_fn(request_id_middleware))
+        .with_state(state.clone())
+        .fallback(admin_ui);
 
     // Start server - bind to all interfaces
     // Firewall rules restrict access to trusted networks (LAN + Tailscale)
-    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port)).await?;
+    if let Some(redirect_port) = tls.redirect_port {
+        tls::spawn_https_redirect(redirect_port, port, &shutdown).await?;
+    }
+    let scheme = if tls.is_enabled() { "https" } else { "http" };
     info!("DMPool Admin Server listening on port {}", port);
-    info!("Access admin panel at http://localhost:{}", port);
+    info!("Access admin panel at {}://localhost:{}", scheme, port);
     info!("Default credentials: {} / {}", admin_username, "***");
 
-    axum::serve(listener, app).await?;
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    tls::serve(addr, app, &tls, &shutdown, shutdown_settings.drain_timeout).await?;
+    shut_down(&state, shutdown_settings).await;
 
     Ok(())
 }
 
+/// Let running jobs and writes finish, then flush the audit log
+///
+/// Called once the server has stopped accepting requests.
+async fn shut_down(state: &AdminState, settings: ShutdownSettings) {
+    info!("Admin server stopped, waiting for {} job(s)", state.jobs.active());
+    let unfinished = state.jobs.shutdown(settings.job_timeout).await;
+    if unfinished > 0 {
+        warn!("Exiting with {} job(s) unfinished", unfinished);
+    }
+    // Scheduled backups and compactions run outside of jobs
+    if !state.maintenance.wait_for_writes(settings.job_timeout).await {
+        warn!("Exiting with {} write(s) still running", state.maintenance.status().in_flight);
+    }
+    if let Err(e) = state.audit_logger.flush().await {
+        error!("Failed to flush audit log: {:#}", e);
+    }
+    info!("Shutdown complete");
+}
+
 /// Authentication middleware for protected routes
 async fn auth_middleware(
     State(auth): State<Arc<AuthManager>>,
-    req: Request,
+    mut req: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
     // Extract Authorization header from request
@@ -372,46 +1587,256 @@ async fn auth_middleware(
         .get("authorization")
         .and_then(|h| h.to_str().ok());
 
-    if let Some(auth_header) = auth_header {
-        if auth_header.starts_with("Bearer ") {
-            let token = &auth_header[7..];
-            match auth.verify_token(token) {
-                Ok(_claims) => {
-                    // Token valid, proceed
-                    return Ok(next.run(req).await);
-                }
-                Err(e) => {
-                    warn!("Invalid token: {}", e);
-                    return Err(StatusCode::UNAUTHORIZED);
+    // Where client certificates replace JWTs, the certificate's common name is the user
+    let certificate = ClientCertificate::of(&req).filter(|c| c.authenticates).cloned();
+
+    let claims = match auth_header.and_then(|h| h.strip_prefix("Bearer ")) {
+        Some(token) => match auth.verify_token(token).await {
+            Ok(claims) => Some(claims),
+            Err(e) => {
+                warn!("Invalid token: {}", e);
+                return Err(StatusCode::UNAUTHORIZED);
+            }
+        },
+        None => match certificate {
+            Some(certificate) => {
+                let username = certificate.common_name.as_deref().unwrap_or_default();
+                match auth.certificate_claims(username).await {
+                    Ok(claims) => Some(claims),
+                    Err(e) => {
+                        warn!("Client certificate {} rejected: {}", certificate.fingerprint, e);
+                        return Err(StatusCode::UNAUTHORIZED);
+                    }
                 }
             }
+            None => None,
+        },
+    };
+
+    // Public routes live on their own router, so every request here needs credentials
+    let Some(claims) = claims else {
+        warn!("Unauthorized access attempt to: {}", request_path(&req));
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+
+    // Users with a temporary password may only change it
+    if claims.must_change_password
+        && !PASSWORD_CHANGE_ALLOWED_PATHS.contains(&unversioned_path(request_path(&req)).as_ref())
+    {
+        warn!("User '{}' must change password before accessing {}", claims.name, request_path(&req));
+        return Err(StatusCode::FORBIDDEN);
+    }
+    // Users whose role requires 2FA may only enroll until they have
+    if claims.must_enroll_2fa {
+        let path = unversioned_path(request_path(&req));
+        if !TWO_FACTOR_ENROLL_ALLOWED_PATHS.contains(&path.as_ref())
+            && !PASSWORD_CHANGE_ALLOWED_PATHS.contains(&path.as_ref())
+        {
+            warn!("User '{}' must enroll in 2FA before accessing {}", claims.name, request_path(&req));
+            return Err(StatusCode::FORBIDDEN);
+        }
+    }
+    // Scoped tokens only reach the routes their scopes cover
+    if claims.is_scoped() {
+        let path = unversioned_path(request_path(&req));
+        let allowed = MINER_SCOPED_PATHS.iter().any(|(prefix, scope)| {
+            (path == *prefix || path.starts_with(&format!("{}/", prefix))) && claims.has_scope(scope)
+        });
+        if !allowed {
+            warn!("Token of '{}' has no scope for {}", claims.name, request_path(&req));
+            return Err(StatusCode::FORBIDDEN);
         }
     }
+    // Authenticated, expose claims to handlers and proceed
+    req.extensions_mut().insert(claims);
+    Ok(next.run(req).await)
+}
 
-    // Allow public routes without auth
-    let path = req.uri().path();
-    let public_routes = [
-        "/",
-        "/api/health",
-        "/api/services/status",
-        "/api/auth/login",
-    ];
+/// Reject requests from banned IP addresses and ranges
+async fn ban_middleware(
+    State(state): State<AdminState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if let Ok(ip) = extract_client_ip(req.headers(), state.rate_limiter.config()) {
+        if let Some(ban) = state.ban_manager.ip_ban(&ip).await {
+            debug!("Refusing request from banned IP {} ({})", ip, ban.target);
+            return (
+                StatusCode::FORBIDDEN,
+                Json(ApiResponse::<()>::error("Access denied: IP address is banned")),
+            ).into_response();
+        }
+    }
+    next.run(req).await
+}
 
-    if public_routes.iter().any(|r| path == *r || path.starts_with(r)) {
+/// Audit middleware recording every mutating request
+///
+/// Runs inside the auth middleware so the authenticated user is available.
+async fn audit_middleware(
+    State(state): State<AdminState>,
+    req: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let method = req.method().clone();
+    if !matches!(method, Method::POST | Method::PUT | Method::PATCH | Method::DELETE) {
         return Ok(next.run(req).await);
     }
 
-    warn!("Unauthorized access attempt to: {}", path);
-    Err(StatusCode::UNAUTHORIZED)
+    let path = request_path(&req).to_string();
+    let route = req.extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| path.clone());
+    let username = req.extensions()
+        .get::<Claims>()
+        .map(|c| c.name.clone())
+        .unwrap_or_else(|| "anonymous".to_string());
+    let ip_address = client_ip(&state, req.headers());
+
+    // Binary uploads, like backup archives, are streamed through and only their size is recorded
+    let binary = req.headers().get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/octet-stream"));
+    let (req, body_summary) = if binary {
+        let size = req.headers().get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        (req, serde_json::json!({ "bytes": size }))
+    } else {
+        // Buffer the body so it can be summarized and still reach the handler
+        let (parts, body) = req.into_parts();
+        let bytes = axum::body::to_bytes(body, MAX_AUDITED_BODY_BYTES)
+            .await
+            .map_err(|e| {
+                warn!("Rejected {} {}: body not readable: {}", method, path, e);
+                StatusCode::PAYLOAD_TOO_LARGE
+            })?;
+        let body_summary = summarize_body(&bytes);
+        (Request::from_parts(parts, axum::body::Body::from(bytes)), body_summary)
+    };
+
+    let response = next.run(req).await;
+    let status = response.status();
+    let (response, error) = response_error(response).await;
+
+    state.audit_logger.log(AuditLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: Utc::now(),
+        username,
+        action: format!("{} {}", method, route),
+        resource: path,
+        ip_address,
+        details: serde_json::json!({
+            "method": method.as_str(),
+            "status": status.as_u16(),
+            "body": body_summary,
+        }),
+        success: error.is_none(),
+        error,
+        request_id: None,
+        geo: None,
+        chain: None,
+    }).await;
+
+    Ok(response)
+}
+
+/// Error a response reports, if any
+///
+/// Handlers answer most failures with a 200 whose `ApiResponse` has status
+/// `error`, so small JSON bodies are read rather than trusting the status.
+async fn response_error(response: Response) -> (Response, Option<String>) {
+    let status = response.status();
+    if !status.is_success() {
+        return (response, Some(status.to_string()));
+    }
+    let json = response.headers().get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+    let small = axum::body::HttpBody::size_hint(response.body()).exact()
+        .is_some_and(|size| size <= MAX_AUDITED_BODY_BYTES as u64);
+    if !json || !small {
+        return (response, None);
+    }
+    let (parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, MAX_AUDITED_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!("Failed to read response body for the audit log: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR.into_response(), Some(e.to_string()));
+        }
+    };
+    let error = serde_json::from_slice::<serde_json::Value>(&bytes).ok()
+        .filter(|body| body["status"] == "error")
+        .map(|body| body["message"].as_str().unwrap_or("error").to_string());
+    (Response::from_parts(parts, axum::body::Body::from(bytes)), error)
+}
+
+/// Refuse mutating requests while in maintenance mode
+///
+/// Allowed requests count as in-flight writes until their response is ready,
+/// so entering maintenance waits for them.
+async fn maintenance_middleware(
+    State(state): State<AdminState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !matches!(*req.method(), Method::POST | Method::PUT | Method::PATCH | Method::DELETE) {
+        return next.run(req).await;
+    }
+    let route = req.extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str())
+        .unwrap_or_else(|| request_path(&req));
+    let route = unversioned_path(route);
+    if MAINTENANCE_ALLOWED_ROUTES.contains(&route.as_ref()) || route.starts_with("/api/auth/") {
+        return next.run(req).await;
+    }
+    match state.maintenance.begin_write() {
+        Ok(_write) => next.run(req).await,
+        Err(e) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ApiResponse::<()>::error(e.to_string())),
+        ).into_response(),
+    }
 }
 
 /// Serve admin panel index
-async fn index() -> impl IntoResponse {
-    let html = include_str!("../../static/admin/index.html");
-    Html(html)
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "system",
+    responses(
+        (status = 200, description = "Admin panel", content_type = "text/html"),
+        (status = 304, description = "Unchanged since the ETag in If-None-Match"),
+    ),
+    security(()),
+)]
+async fn index(headers: HeaderMap) -> Response {
+    match assets::serve("/", &headers) {
+        Some(response) => response,
+        None => not_found().await.into_response(),
+    }
+}
+
+/// Files of the admin panel, and its client-side routes; unknown API paths stay 404
+async fn admin_ui(method: Method, uri: Uri, headers: HeaderMap) -> Response {
+    if !matches!(method, Method::GET | Method::HEAD) || uri.path().starts_with("/api/") {
+        return not_found().await.into_response();
+    }
+    match assets::serve(uri.path(), &headers) {
+        Some(response) => response,
+        None => not_found().await.into_response(),
+    }
 }
 
 /// Health check
+#[utoipa::path(
+    get,
+    path = "/api/v1/health",
+    tag = "system",
+    responses((status = 200, description = "Service is running")),
+    security(()),
+)]
 async fn health() -> impl IntoResponse {
     Json(serde_json::json!({
         "status": "healthy",
@@ -419,534 +1844,6325 @@ async fn health() -> impl IntoResponse {
     }))
 }
 
+/// Liveness: the process is up and serving requests
+#[utoipa::path(
+    get,
+    path = "/api/v1/health/live",
+    tag = "system",
+    responses((status = 200, description = "The admin server is running")),
+    security(()),
+)]
+async fn health_live() -> impl IntoResponse {
+    Json(serde_json::json!({ "live": true }))
+}
+
+/// Readiness: the store opens, the running config is valid and users are loaded
+#[utoipa::path(
+    get,
+    path = "/api/v1/health/ready",
+    tag = "system",
+    responses(
+        (status = 200, description = "Ready to take traffic"),
+        (status = 503, description = "A check failed; `checks` says which"),
+    ),
+    security(()),
+)]
+async fn health_ready(State(state): State<AdminState>) -> Response {
+    // Opening the store by path catches one that has gone away since startup
+    let store = match state.storage.stats().await {
+        Ok(stats) => ReadinessCheck::pass("store", format!("Store open ({} column families)", stats.column_families.len())),
+        Err(e) => ReadinessCheck::fail("store", format!("Failed to open store: {:#}", e)),
+    };
+    let params = config_mgt::config_params(&*state.config.read().await);
+    let config = match state.config_manager.validate_config(&params).await {
+        config_mgt::ValidationStatus::Invalid { errors } => ReadinessCheck::fail("config", errors.join("; ")),
+        _ => ReadinessCheck::pass("config", "Config valid"),
+    };
+    let users = state.auth_manager.list_users().await;
+    let enabled = users.iter().filter(|u| !u.disabled).count();
+    let auth = if enabled > 0 {
+        ReadinessCheck::pass("auth", format!("{} enabled user(s)", enabled))
+    } else {
+        ReadinessCheck::fail("auth", "No enabled users")
+    };
+
+    let readiness = Readiness::new(vec![store, config, auth]);
+    if !readiness.ready {
+        warn!("Admin server not ready: {}", readiness.failed().join(", "));
+    }
+    let status = if readiness.ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(readiness)).into_response()
+}
+
 /// Get comprehensive services status
+#[utoipa::path(
+    get,
+    path = "/api/v1/services/status",
+    tag = "system",
+    responses((status = 200, description = "Standard response envelope", body = ApiEnvelope)),
+    security(()),
+)]
 async fn services_status(State(state): State<AdminState>) -> impl IntoResponse {
     let health_status = state.health_checker.check().await;
     Json(ApiResponse::ok(health_status))
 }
 
+/// Recent health check results, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/health/history",
+    tag = "system",
+    params(HealthHistoryQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn health_history(
+    State(state): State<AdminState>,
+    Query(query): Query<HealthHistoryQuery>,
+) -> impl IntoResponse {
+    Json(ApiResponse::ok(state.health_checker.history(query.limit.unwrap_or(100))))
+}
+
 /// Get dashboard metrics
+#[utoipa::path(
+    get,
+    path = "/api/v1/dashboard",
+    tag = "dashboard",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
 async fn dashboard(State(state): State<AdminState>) -> impl IntoResponse {
-    let height = state.chain_store.get_tip_height()
-        .ok()
-        .flatten()
-        .map(|h| h as u64)
-        .unwrap_or(0);
+    Json(ApiResponse::ok(build_dashboard_metrics(&state).await))
+}
 
-    let metrics = DashboardMetrics {
-        pool_hashrate_ths: 0.0,
-        active_workers: 0,
-        total_shares: 0,
-        blocks_found: height,
-        uptime_seconds: state.start_time.elapsed().as_secs(),
-        pplns_window_shares: 0,
-        current_difficulty: 1.0,
-    };
+/// Miners connected from one address
+#[derive(Serialize)]
+struct ConnectionInfo {
+    ip: IpAddr,
+    connections: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    geo: Option<GeoInfo>,
+}
 
-    Json(ApiResponse::ok(metrics))
+/// List the addresses connected to the stratum port, with country and network when GeoIP is enabled
+#[utoipa::path(
+    get,
+    path = "/api/v1/connections",
+    tag = "dashboard",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn list_connections(State(state): State<AdminState>) -> impl IntoResponse {
+    let mut counts: HashMap<IpAddr, u64> = HashMap::new();
+    for ip in state.health_checker.peer_addresses() {
+        *counts.entry(ip).or_insert(0) += 1;
+    }
+    let mut connections: Vec<ConnectionInfo> = counts.into_iter()
+        .map(|(ip, connections)| ConnectionInfo { ip, connections, geo: state.geoip.lookup(ip) })
+        .collect();
+    connections.sort_by(|a, b| b.connections.cmp(&a.connections).then(a.ip.cmp(&b.ip)));
+    Json(ApiResponse::ok(connections))
 }
 
-/// Get current configuration
-async fn get_config(State(state): State<AdminState>) -> impl IntoResponse {
-    let config = state.config.read().await;
+#[derive(Serialize)]
+struct InstanceInfo {
+    name: String,
+    /// Served by the unscoped API as well
+    primary: bool,
+    config_path: String,
+    network: String,
+    stratum_port: u16,
+}
 
-    let view = ConfigView {
-        stratum_port: config.stratum.port,
-        stratum_hostname: config.stratum.hostname.clone(),
-        start_difficulty: config.stratum.start_difficulty,
-        minimum_difficulty: config.stratum.minimum_difficulty,
-        pplns_ttl_days: config.store.pplns_ttl_days,
-        difficulty_multiplier: 1.0,
-        network: config.stratum.network.to_string(),
-        pool_signature: config.stratum.pool_signature.clone(),
-        ignore_difficulty: config.stratum.ignore_difficulty.unwrap_or(false),
-        donation: config.stratum.donation,
-        fee: None,
-    };
+/// Managed pool instances, primary first
+#[utoipa::path(
+    get,
+    path = "/api/v1/instances",
+    tag = "instances",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn list_instances(State(state): State<AdminState>) -> impl IntoResponse {
+    let mut instances = Vec::with_capacity(state.instances.len());
+    for (index, instance) in state.instances.iter().enumerate() {
+        let config = instance.config.read().await;
+        instances.push(InstanceInfo {
+            name: instance.name.clone(),
+            primary: index == 0,
+            config_path: instance.config_path.clone(),
+            network: config.stratum.network.to_string(),
+            stratum_port: config.stratum.port,
+        });
+    }
+    Json(ApiResponse::ok(instances))
+}
 
-    Json(ApiResponse::ok(view))
+#[derive(Serialize)]
+struct InstanceOverview {
+    name: String,
+    status: String,
+    metrics: DashboardMetrics,
 }
 
-/// Update configuration (runtime only)
-async fn update_config(
-    State(state): State<AdminState>,
-    Json(update): Json<ConfigUpdate>,
-) -> impl IntoResponse {
-    let mut config = state.config.write().await;
-    let mut changes = Vec::new();
+#[derive(Serialize, Default)]
+struct OverviewTotals {
+    pool_hashrate_ths: f64,
+    active_workers: u64,
+    connected_miners: u32,
+    blocks_found: u64,
+    /// Instances whose health check is not healthy
+    unhealthy_instances: u64,
+}
 
-    // Update start_difficulty
-    if let Some(diff) = update.start_difficulty {
-        if diff >= 8 && diff <= 512 {
-            let old = config.stratum.start_difficulty;
-            config.stratum.start_difficulty = diff as u64;
-            changes.push(format!("start_difficulty: {} → {}", old, diff));
-            info!("Updated start_difficulty to {}", diff);
+/// Dashboard metrics and health of every instance, with combined totals
+#[utoipa::path(
+    get,
+    path = "/api/v1/instances/overview",
+    tag = "instances",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn instances_overview(State(state): State<AdminState>) -> impl IntoResponse {
+    let mut totals = OverviewTotals::default();
+    let mut instances = Vec::with_capacity(state.instances.len());
+    for instance in state.instances.iter() {
+        let metrics = compute_dashboard_metrics(&state.for_instance(instance)).await;
+        let health = instance.health_checker.check().await;
+        totals.pool_hashrate_ths += metrics.pool_hashrate_ths;
+        totals.active_workers += metrics.active_workers;
+        totals.connected_miners += metrics.connected_miners;
+        totals.blocks_found += metrics.blocks_found;
+        if health.is_unhealthy() {
+            totals.unhealthy_instances += 1;
         }
+        instances.push(InstanceOverview {
+            name: instance.name.clone(),
+            status: health.status,
+            metrics,
+        });
     }
+    Json(ApiResponse::ok(serde_json::json!({
+        "totals": totals,
+        "instances": instances,
+    })))
+}
 
-    // Update minimum_difficulty
-    if let Some(diff) = update.minimum_difficulty {
-        if diff >= 8 && diff <= 256 {
-            let old = config.stratum.minimum_difficulty;
-            config.stratum.minimum_difficulty = diff as u64;
-            changes.push(format!("minimum_difficulty: {} → {}", old, diff));
-            info!("Updated minimum_difficulty to {}", diff);
-        }
-    }
+/// Current unix time in seconds
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
 
-    // Update pool_signature
-    if let Some(signature) = update.pool_signature {
-        if signature.len() <= 16 {
-            let old = config.stratum.pool_signature.clone();
-            config.stratum.pool_signature = Some(signature.clone());
-            changes.push(format!("pool_signature: {:?} → {}", old, signature));
-            info!("Updated pool_signature to {}", signature);
-        }
-    }
+/// Share outcomes per worker key over the last `window_secs`
+///
+/// Duplicates are found in `shares`, which should cover the same window;
+/// workers the stratum layer reported on use its counts instead, which also
+/// include stale and invalid shares.
+fn worker_share_stats(state: &AdminState, shares: &[SimplePplnsShare], window_secs: u64) -> HashMap<String, WorkerShareStats> {
+    let mut stats = share_stats::derive_from_shares(shares);
+    stats.extend(state.share_stats.since(unix_now().saturating_sub(window_secs)));
+    stats
+}
 
-    if changes.is_empty() {
-        return Json(ApiResponse::<serde_json::Value>::error("No valid changes to apply".to_string()));
+/// Hashrate and worker count derived from shares submitted over `window_secs`
+struct ShareActivity {
+    hashrate_ths: f64,
+    workers: u64,
+    mean_difficulty: Option<f64>,
+}
+
+impl ShareActivity {
+    fn from_shares(shares: &[&SimplePplnsShare], window_secs: u64) -> Self {
+        let workers: HashSet<(Option<&str>, Option<&str>, u64)> = shares
+            .iter()
+            .map(|share| (share.btcaddress.as_deref(), share.workername.as_deref(), share.user_id))
+            .collect();
+        let total_difficulty: f64 = shares.iter().map(|share| share.difficulty as f64).sum();
+        Self {
+            hashrate_ths: hashrate_ths(total_difficulty, window_secs),
+            workers: workers.len() as u64,
+            mean_difficulty: (!shares.is_empty()).then(|| total_difficulty / shares.len() as f64),
+        }
     }
+}
 
-    let response = serde_json::json!({
-        "message": format!("Applied {} change(s)", changes.len()),
-        "changes": changes,
-    });
+/// Dashboard metrics, served from the query cache
+async fn build_dashboard_metrics(state: &AdminState) -> DashboardMetrics {
+    let source = state.clone();
+    let cached = state.dashboard_cache
+        .get_or_compute((), move || async move { compute_dashboard_metrics(&source).await })
+        .await;
+    let mut metrics = DashboardMetrics::clone(&cached);
+    metrics.uptime_seconds = state.start_time.elapsed().as_secs();
+    metrics
+}
 
-    Json(ApiResponse::ok(response))
+/// Shares of the last `window_secs`, at most `limit` of them, served from the
+/// query cache
+async fn cached_shares(state: &AdminState, limit: Option<usize>, window_secs: u64) -> Arc<Vec<SimplePplnsShare>> {
+    let store = state.store.clone();
+    state.share_cache
+        .get_or_compute((limit, window_secs), move || async move {
+            let now = unix_now();
+            store.get_pplns_shares_filtered(limit, Some(now.saturating_sub(window_secs)), Some(now))
+        })
+        .await
 }
 
-/// Reload configuration from file
-async fn reload_config(State(state): State<AdminState>) -> impl IntoResponse {
-    match Config::load(&state.config_path) {
-        Ok(new_config) => {
-            *state.config.write().await = new_config;
-            info!("Configuration reloaded from file");
-            let response = serde_json::json!({
-                "message": "Configuration reloaded successfully"
-            });
-            Json(ApiResponse::ok(response))
-        }
-        Err(e) => {
-            error!("Failed to reload config: {}", e);
-            Json(ApiResponse::<serde_json::Value>::error(format!("Failed to reload: {}", e)))
-        }
-    }
+/// Mark cached share aggregations stale after new shares arrived
+fn invalidate_share_caches(state: &AdminState) {
+    state.share_cache.invalidate();
+    state.preview_cache.invalidate();
+    state.dashboard_cache.invalidate();
 }
 
-/// Get workers list from PPLNS shares (with pagination)
-async fn workers_list(
-    State(state): State<AdminState>,
-    Query(params): Query<PaginationRequest>,
-) -> impl IntoResponse {
-    let banned = state.banned_workers.read().await;
-    let worker_tags = state.worker_tags.read().await;
+/// Aggregate dashboard metrics from the share store
+async fn compute_dashboard_metrics(state: &AdminState) -> DashboardMetrics {
+    let (pplns_ttl_days, start_difficulty) = {
+        let config = state.config.read().await;
+        (config.store.pplns_ttl_days, config.stratum.start_difficulty)
+    };
+    let share_chain_height = state.chain_store.get_tip_height()
+        .ok()
+        .flatten()
+        .map(|h| h as u64)
+        .unwrap_or(0);
 
-    // Get pagination parameters
-    let page = params.page.unwrap_or(1).max(1);
-    let page_size = params.page_size.unwrap_or(20).min(100);
-    let search = params.search.unwrap_or_default().to_lowercase();
-    let status_filter = params.status.unwrap_or_default().to_lowercase();
+    let activity_start = unix_now().saturating_sub(LIVE_WORKER_WINDOW_SECS);
 
-    // Get recent PPLNS shares (last 1000, last 24 hours)
-    let end_time = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
-    let start_time = end_time - (24 * 3600); // Last 24 hours
+    // Only the PPLNS window is read; the pool prunes shares older than it
+    let shares = cached_shares(state, None, pplns_ttl_days * 24 * 3600).await;
+    let recent: Vec<&SimplePplnsShare> = shares
+        .iter()
+        .filter(|share| share.n_time >= activity_start)
+        .collect();
+    let activity = ShareActivity::from_shares(&recent, LIVE_WORKER_WINDOW_SECS);
+
+    DashboardMetrics {
+        pool_hashrate_ths: activity.hashrate_ths,
+        active_workers: activity.workers,
+        connected_miners: state.health_checker.active_connections(),
+        total_shares: shares.len() as u64,
+        blocks_found: state.block_tracker.count().await,
+        share_chain_height,
+        uptime_seconds: state.start_time.elapsed().as_secs(),
+        pplns_window_shares: shares.len() as u64,
+        current_difficulty: activity.mean_difficulty.unwrap_or(start_difficulty as f64),
+        geography: state.geoip.is_enabled()
+            .then(|| state.geoip.summarize(&state.health_checker.peer_addresses())),
+        fees: state.fee_monitor.latest(),
+    }
+}
 
-    let shares = state.store.get_pplns_shares_filtered(
-        Some(1000),
-        Some(start_time),
-        Some(end_time),
+/// Gather health, share activity and backup status for alert rule evaluation
+async fn collect_alert_inputs(state: &AdminState) -> AlertInputs {
+    let now = unix_now();
+    let recent = state.store.get_pplns_shares_filtered(
+        None,
+        Some(now.saturating_sub(LIVE_WORKER_WINDOW_SECS)),
+        Some(now),
     );
+    let activity = ShareActivity::from_shares(&recent.iter().collect::<Vec<_>>(), LIVE_WORKER_WINDOW_SECS);
+
+    AlertInputs {
+        worker_share_stats: Some(worker_share_stats(state, &recent, LIVE_WORKER_WINDOW_SECS)),
+        hashrate_ths: Some(activity.hashrate_ths),
+        worker_count: Some(activity.workers),
+        last_block_at: state.block_tracker.last_found_at().await,
+        health: Some(state.health_checker.check().await),
+        backup_failure: state.backup_manager.last_failure(),
+        backup_space: state.backup_manager.check_space()
+            .inspect_err(|e| warn!("Failed to check backup volume space: {:#}", e))
+            .ok(),
+        api_error: None,
+        connection_countries: state.geoip.is_enabled().then(|| {
+            let mut countries = HashMap::new();
+            for country in state.health_checker.peer_addresses().into_iter()
+                .filter_map(|ip| state.geoip.lookup(ip).and_then(|geo| geo.country))
+            {
+                *countries.entry(country).or_insert(0) += 1;
+            }
+            countries
+        }),
+        orphaned_shares: Some({
+            let since = now.saturating_sub(ORPHAN_ALERT_WINDOW_SECS);
+            let credited = state.store.get_pplns_shares_filtered(None, Some(since), Some(now)).len() as u64;
+            let orphaned = state.orphans.since(since).iter()
+                .filter(|s| s.kind == share_stats::OrphanKind::Orphan)
+                .count() as u64;
+            (orphaned, credited + orphaned)
+        }),
+        stratum_auth_failures: Some(
+            state.ingest.auth_failures_since(now.saturating_sub(AUTH_FAILURE_ALERT_WINDOW_SECS)).len() as u64,
+        ),
+        hashrate_anomaly: state.hashrate_anomaly.latest(),
+        farms: Some({
+            let by_address = farms::group_by_address(&recent, LIVE_WORKER_WINDOW_SECS);
+            state.farms.list().await.iter()
+                .map(|farm| {
+                    let stats = farm.stats(&by_address);
+                    (farm.name.clone(), (stats.hashrate_ths, stats.workers))
+                })
+                .collect()
+        }),
+        fees: state.fee_monitor.latest(),
+        min_payout_sats: Some(state.payout_engine.config().min_payout_sats),
+        store_doctor: state.store_doctor.status().await
+            .inspect_err(|e| warn!("Failed to read store doctor status: {:#}", e))
+            .ok(),
+    }
+}
 
-    // Group shares by miner address
-    let mut workers_map: HashMap<String, WorkerInfo> = HashMap::new();
+/// Hot-reload the config file when it is edited
+///
+/// Safe parameters are applied directly; risky ones become pending change
+/// requests that must be confirmed in the UI, and settings that need a restart
+/// are only reported. Writes made by the admin server itself produce no diff.
+async fn run_config_watcher(state: AdminState, mut watcher: ConfigWatcher) {
+    while watcher.changed().await.is_some() {
+        let new_config = match Config::load(&state.config_path) {
+            Ok(config) => config,
+            Err(e) => {
+                warn!("Ignoring config file change, failed to load {}: {}", state.config_path, e);
+                continue;
+            }
+        };
 
-    for share in shares {
-        let address = share.btcaddress.clone().unwrap_or_else(|| format!("user_{}", share.user_id));
+        let status = state.config_manager.validate_config(&config_mgt::config_params(&new_config)).await;
+        if let config_mgt::ValidationStatus::Invalid { errors } = status {
+            warn!("Ignoring config file change, validation failed: {}", errors.join("; "));
+            continue;
+        }
 
-        let entry = workers_map.entry(address.clone()).or_insert_with(|| {
-            let now = chrono::Utc::now();
-            let is_banned = banned.contains(&address);
-            let tags = worker_tags.get(&address).cloned().unwrap_or_default();
-            WorkerInfo {
-                address: address.clone(),
-                worker_name: share.workername.clone().unwrap_or_else(|| "worker".to_string()),
-                hashrate_ths: 0.0,
-                shares_count: 0,
-                difficulty: share.difficulty,
-                last_seen: now.to_rfc3339(),
-                first_seen: now.to_rfc3339(),
-                is_banned,
-                tags,
-                status: if is_banned {
-                    WorkerStatus::Banned
-                } else {
-                    WorkerStatus::Active
-                },
+        let (changes, restart_required) = {
+            let current = state.config.read().await;
+            (
+                config_mgt::changed_parameters(&current, &new_config),
+                config_mgt::restart_required_changes(&current, &new_config),
+            )
+        };
+        state.restart.record(restart_required, CONFIG_FILE_USER).await;
+
+        // One request per parameter: an edit supersedes the file's earlier
+        // unconfirmed requests, and reverting it drops them
+        let file_requests: Vec<_> = state.config_confirmation.get_pending().await
+            .into_iter()
+            .filter(|r| r.username == CONFIG_FILE_USER)
+            .collect();
+        for request in file_requests.iter().filter(|r| !r.confirmed) {
+            if !changes.iter().any(|(p, _, v)| *p == request.parameter && *v == request.new_value) {
+                let _ = state.config_confirmation.cancel_change(&request.id).await;
             }
-        });
+        }
 
-        entry.shares_count += 1;
-        entry.difficulty = share.difficulty;
-        entry.last_seen = chrono::Utc::now().to_rfc3339();
+        for (parameter, old_value, new_value) in changes {
+            if let Err(e) = state.config_confirmation.validate_value(&parameter, &new_value) {
+                warn!("Config file sets unsafe {} = {}: {}", parameter, new_value, e);
+                continue;
+            }
+            if state.config_confirmation.requires_confirmation(&parameter) {
+                if file_requests.iter().any(|r| r.parameter == parameter && r.new_value == new_value) {
+                    continue;
+                }
+                match state.config_confirmation
+                    .create_change_request(
+                        parameter.clone(),
+                        old_value,
+                        new_value.clone(),
+                        CONFIG_FILE_USER.to_string(),
+                        "local".to_string(),
+                        state.config_manager.current_version().await.map(|v| v.id),
+                    )
+                    .await
+                {
+                    Ok(request) => warn!(
+                        "Config file changes {} to {}; confirm request {} to apply it",
+                        parameter, new_value, request.id
+                    ),
+                    Err(e) => error!("Failed to create change request for {}: {}", parameter, e),
+                }
+                continue;
+            }
+
+            let result = state.config_manager
+                .apply_change(&state.config, &parameter, &new_value, CONFIG_FILE_USER, None, false)
+                .await;
+            state.audit_logger.log(AuditLog {
+                id: uuid::Uuid::new_v4().to_string(),
+                timestamp: Utc::now(),
+                username: CONFIG_FILE_USER.to_string(),
+                action: "config_hot_reload".to_string(),
+                resource: format!("config:{}", parameter),
+                ip_address: "local".to_string(),
+                details: serde_json::json!({
+                    "parameter": parameter,
+                    "old_value": old_value,
+                    "new_value": new_value,
+                    "version": result.as_ref().ok().map(|v| v.id.clone()),
+                }),
+                success: result.is_ok(),
+                error: result.as_ref().err().map(|e| e.to_string()),
+                request_id: None,
+                geo: None,
+                chain: None,
+            }).await;
+            if let Err(e) = result {
+                warn!("Failed to hot-reload {}: {}", parameter, e);
+            }
+        }
     }
+    warn!("Config file watcher stopped");
+}
 
-    // Convert to vector and apply filters
-    let mut workers: Vec<WorkerInfo> = workers_map.into_values().collect();
+/// RPC client for the Bitcoin node in the current config
+async fn bitcoin_rpc(state: &AdminState) -> Result<bitcoincore_rpc::Client> {
+    let config = state.config.read().await;
+    bitcoincore_rpc::Client::new(
+        &config.bitcoinrpc.url,
+        bitcoincore_rpc::Auth::UserPass(
+            config.bitcoinrpc.username.clone(),
+            config.bitcoinrpc.password.clone(),
+        ),
+    )
+    .map_err(|e| anyhow::anyhow!("Failed to create RPC client: {}", e))
+}
 
-    // Apply search filter
-    if !search.is_empty() {
-        workers.retain(|w| {
-            w.address.to_lowercase().contains(&search)
-                || w.worker_name.to_lowercase().contains(&search)
-        });
+/// Scan the Bitcoin node for blocks found by the pool on every new chain tip
+/// announced over ZMQ, and periodically in case notifications are missed
+async fn run_block_scanner(state: AdminState, mut new_tips: broadcast::Receiver<String>) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(BLOCK_SCAN_INTERVAL_SECS));
+    let mut tips_open = true;
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            tip = new_tips.recv(), if tips_open => match tip {
+                Ok(hash) => debug!("New chain tip {}, scanning for pool blocks", hash),
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => tips_open = false,
+            },
+        }
+        let Ok(_write) = state.maintenance.begin_write() else {
+            debug!("In maintenance mode, skipping found block scan");
+            continue;
+        };
+        let result = match bitcoin_rpc(&state).await {
+            Ok(rpc) => state.block_tracker.scan(Arc::new(rpc)).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(found) => {
+                for block in found {
+                    // Recorded now, as the shares may be pruned before the block matures
+                    let shares = pplns_window_shares(&state, &block).await;
+                    if let Err(e) = state.payout_engine.record_window(&block, &shares).await {
+                        error!("Failed to record the PPLNS window of block {}: {:#}", block.hash, e);
+                    }
+                    let winner = winning_share_address(&state, &block);
+                    state.alert_manager.notify_block_found(&block, winner.as_deref()).await;
+                    state.outbox.publish(OutboxEvent::BlockFound, serde_json::json!({
+                        "instance": instance_name(&state),
+                        "block": block,
+                        "winner": winner,
+                    })).await;
+                }
+            }
+            Err(e) => warn!("Found block scan failed: {:#}", e),
+        }
     }
+}
 
-    // Apply status filter
-    if !status_filter.is_empty() {
-        workers.retain(|w| match status_filter.as_str() {
-            "active" => matches!(w.status, WorkerStatus::Active),
-            "banned" => matches!(w.status, WorkerStatus::Banned),
-            "inactive" => matches!(w.status, WorkerStatus::Inactive),
-            _ => true,
-        });
+/// Follow the confirmations of found blocks until they mature or are orphaned,
+/// alerting on each and starting a payout run when one matures
+async fn run_block_maturity_tracker(state: AdminState) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(BLOCK_MATURITY_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+        let Ok(_write) = state.maintenance.begin_write() else {
+            debug!("In maintenance mode, skipping block maturity check");
+            continue;
+        };
+        let result = match bitcoin_rpc(&state).await {
+            Ok(rpc) => state.block_tracker.refresh(Arc::new(rpc)).await,
+            Err(e) => Err(e),
+        };
+        let changed = match result {
+            Ok(changed) => changed,
+            Err(e) => {
+                warn!("Block maturity check failed: {:#}", e);
+                continue;
+            }
+        };
+        for (block, _) in &changed {
+            state.alert_manager.notify_block_state(block).await;
+        }
+        if changed.iter().any(|(block, _)| block.state == BlockState::Mature) {
+            state.payout_wakeup.notify_one();
+        }
     }
+}
 
-    // Apply sorting
-    let sort_by = params.sort_by.unwrap_or_else(|| "last_seen".to_string());
-    let sort_desc = params.sort_order.unwrap_or_else(|| "desc".to_string()) == "desc";
+/// Address of the last share submitted before `block` was found
+///
+/// Shares don't record their hash, so the latest share up to the block's
+/// timestamp stands in for the one that met the network target.
+fn winning_share_address(state: &AdminState, block: &FoundBlock) -> Option<String> {
+    let block_time = block.timestamp.timestamp().max(0) as u64;
+    state.store.get_pplns_shares_filtered(
+        None,
+        Some(block_time.saturating_sub(LIVE_WORKER_WINDOW_SECS)),
+        Some(block_time),
+    )
+    .into_iter()
+    .max_by_key(|share| share.n_time)
+    .and_then(|share| share.btcaddress)
+}
 
-    match sort_by.as_str() {
-        "address" => workers.sort_by(|a, b| {
-            if sort_desc {
-                b.address.cmp(&a.address)
-            } else {
-                a.address.cmp(&b.address)
+/// Shares of the PPLNS window that ended when `block` was found
+async fn pplns_window_shares(state: &AdminState, block: &FoundBlock) -> Vec<SimplePplnsShare> {
+    let ttl_days = state.config.read().await.store.pplns_ttl_days;
+    let block_time = block.timestamp.timestamp().max(0) as u64;
+    state.store.get_pplns_shares_filtered(
+        None,
+        Some(block_time.saturating_sub(ttl_days * 24 * 3600)),
+        Some(block_time),
+    )
+}
+
+/// Credit matured pool blocks, create payout batches when due and pay them
+async fn run_payout_scheduler(state: AdminState) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(PAYOUT_CHECK_INTERVAL_SECS));
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = state.payout_wakeup.notified() => debug!("Block matured, running payouts"),
+        }
+        let Ok(_write) = state.maintenance.begin_write() else {
+            debug!("In maintenance mode, skipping payout run");
+            continue;
+        };
+        let (fee_bps, donation_bps) = {
+            let config = state.config.read().await;
+            (config.stratum.fee.unwrap_or(0), config.stratum.donation.unwrap_or(0))
+        };
+        for block in state.block_tracker.blocks().await.iter().rev() {
+            if block.orphaned {
+                if let Err(e) = state.payout_engine.discard_window(block).await {
+                    error!("Failed to discard the PPLNS window of block {}: {:#}", block.hash, e);
+                }
+                continue;
             }
-        }),
-        "hashrate" => workers.sort_by(|a, b| {
-            if sort_desc {
-                b.hashrate_ths.partial_cmp(&a.hashrate_ths).unwrap_or(std::cmp::Ordering::Equal)
-            } else {
-                a.hashrate_ths.partial_cmp(&b.hashrate_ths).unwrap_or(std::cmp::Ordering::Equal)
+            if !state.payout_engine.needs_credit(block).await {
+                // Blocks found before windows were recorded get theirs while shares remain
+                let immature = block.confirmations < state.payout_engine.config().maturity_confirmations;
+                if immature && !state.payout_engine.has_window(block).await {
+                    let shares = pplns_window_shares(&state, block).await;
+                    if let Err(e) = state.payout_engine.record_window(block, &shares).await {
+                        error!("Failed to record the PPLNS window of block {}: {:#}", block.hash, e);
+                    }
+                }
+                continue;
             }
-        }),
-        "shares" => workers.sort_by(|a, b| {
-            if sort_desc {
-                b.shares_count.cmp(&a.shares_count)
+            // Only used when no window was recorded
+            let shares = if state.payout_engine.has_window(block).await {
+                Vec::new()
             } else {
-                a.shares_count.cmp(&b.shares_count)
+                pplns_window_shares(&state, block).await
+            };
+            match state.payout_engine.credit_block(block, &shares, fee_bps.saturating_add(donation_bps)).await {
+                Ok(Some(credit)) => {
+                    let notifications = credit.credits.iter()
+                        .map(|(address, amount)| (address.clone(), MinerEvent::BlockCredited, serde_json::json!({
+                            "height": credit.height,
+                            "hash": credit.hash,
+                            "amount_sats": amount,
+                        })))
+                        .collect();
+                    tokio::spawn(notify_miners(state.miner_webhooks.clone(), state.miner_settings.clone(), notifications));
+                }
+                Ok(None) => {}
+                Err(e) => error!("Failed to credit block {}: {:#}", block.hash, e),
             }
-        }),
-        _ => { // default: last_seen
-            workers.sort_by(|a, b| {
-                if sort_desc {
-                    b.last_seen.cmp(&a.last_seen)
-                } else {
-                    a.last_seen.cmp(&b.last_seen)
+        }
+        // Includes blocks credited before fee accounting, which get the current rates
+        for credit in state.payout_engine.credited_blocks().await {
+            if state.fee_ledger.contains(&credit.hash).await {
+                continue;
+            }
+            let found_at = match state.block_tracker.block_at(credit.height).await {
+                Some(block) => block.timestamp,
+                None => credit.credited_at,
+            };
+            if let Err(e) = state.fee_ledger.record(&credit, found_at, fee_bps, donation_bps).await {
+                error!("Failed to record fees of block {}: {:#}", credit.hash, e);
+            }
+        }
+        if state.payout_engine.batch_due(Utc::now()).await {
+            let payment_fee = state.fee_monitor.latest().and_then(|f| f.payment_fee_sats());
+            if let Err(e) = state.payout_engine.schedule_batch(payment_fee).await {
+                error!("Failed to schedule payout batch: {:#}", e);
+            }
+        }
+        let broadcast: HashSet<String> = state.payout_engine.batches().await
+            .into_iter()
+            .filter(|b| b.txid.is_some())
+            .map(|b| b.id)
+            .collect();
+        let result = match bitcoin_rpc(&state).await {
+            Ok(rpc) => state.payout_wallet.process(&state.payout_engine, Arc::new(rpc)).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            warn!("Payout wallet processing failed: {:#}", e);
+        }
+        let notifications = state.payout_engine.batches().await
+            .into_iter()
+            .filter(|b| b.txid.is_some() && !broadcast.contains(&b.id))
+            .flat_map(|batch| batch.payments.iter()
+                .map(|payment| (payment.address.clone(), MinerEvent::PayoutSent, serde_json::json!({
+                    "batch_id": batch.id,
+                    "txid": batch.txid,
+                    "amount_sats": payment.amount_sats,
+                })))
+                .collect::<Vec<_>>())
+            .collect();
+        tokio::spawn(notify_miners(state.miner_webhooks.clone(), state.miner_settings.clone(), notifications));
+    }
+}
+
+/// Check share activity for workers that stopped mining, alerting operators
+/// and notifying the workers' miners
+async fn run_worker_watch(state: AdminState) {
+    let defaults = state.worker_watch.defaults();
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(WORKER_WATCH_INTERVAL_SECS));
+    // The first pass picks up workers that were already mining before the server started
+    let mut since = unix_now().saturating_sub((defaults.min_active_minutes + defaults.idle_minutes) * 60);
+
+    loop {
+        interval.tick().await;
+        let now = unix_now();
+        let mut shares = state.store.get_pplns_shares_filtered(None, Some(since), Some(now));
+        shares.sort_by_key(|share| share.n_time);
+        // Shares can reach the store after later ones; already seen times are skipped
+        since = now.saturating_sub(2 * WORKER_WATCH_INTERVAL_SECS);
+
+        let mut events = Vec::new();
+        for share in &shares {
+            let Some(address) = share.btcaddress.as_deref() else {
+                continue;
+            };
+            let worker = share.workername.as_deref().unwrap_or("worker");
+            events.extend(state.worker_watch.observe(address, worker, share.n_time).await);
+        }
+        events.extend(state.worker_watch.check(now).await);
+
+        let mut notifications = Vec::new();
+        for event in events {
+            match event {
+                WorkerWatchEvent::Down { address, worker, active_since, last_share_at } => {
+                    warn!("Worker {}.{} stopped submitting shares", address, worker);
+                    let at = |secs: u64| DateTime::from_timestamp(secs as i64, 0).unwrap_or_default();
+                    state.alert_manager
+                        .notify_worker_down(&address, &worker, at(active_since), at(last_share_at))
+                        .await;
+                    notifications.push((address, MinerEvent::WorkerDown, serde_json::json!({
+                        "worker": worker,
+                        "active_since": active_since,
+                        "last_share_at": last_share_at,
+                    })));
                 }
-            });
+                WorkerWatchEvent::Recovered { address, worker, down_secs } => {
+                    info!("Worker {}.{} is submitting shares again after {}s", address, worker, down_secs);
+                    notifications.push((address, MinerEvent::WorkerRecovered, serde_json::json!({
+                        "worker": worker,
+                        "down_secs": down_secs,
+                    })));
+                }
+            }
+        }
+        if !notifications.is_empty() {
+            tokio::spawn(notify_miners(state.miner_webhooks.clone(), state.miner_settings.clone(), notifications));
         }
     }
+}
 
-    let total = workers.len();
-    let total_pages = (total + page_size - 1) / page_size;
+/// Deliver miner notifications, one address at a time
+async fn notify_miners(
+    webhooks: Arc<MinerWebhooks>,
+    settings: Arc<MinerSettingsStore>,
+    notifications: Vec<(String, MinerEvent, serde_json::Value)>,
+) {
+    for (address, event, data) in notifications {
+        let settings_url = settings.get(&address).await.and_then(|r| r.settings.notify_webhook);
+        webhooks.notify(&address, event, data, settings_url.as_deref()).await;
+    }
+}
 
-    // Apply pagination
-    let start_idx = (page - 1) * page_size;
-    let end_idx = start_idx + page_size;
-    let paginated_workers: Vec<WorkerInfo> = workers
-        .into_iter()
-        .skip(start_idx)
-        .take(page_size)
-        .collect();
+/// Take scheduled backups, then apply the retention policy
+async fn run_backup_scheduler(state: AdminState) {
+    loop {
+        let Some((next_run, kind)) = state.backup_manager.next_scheduled(Utc::now()) else {
+            info!("No backup schedules configured");
+            return;
+        };
+        info!("Next scheduled {:?} backup at {}", kind, next_run);
+        let wait = (next_run - Utc::now()).to_std().unwrap_or_default();
+        tokio::time::sleep(wait).await;
+
+        let Ok(_write) = state.maintenance.begin_write() else {
+            warn!("In maintenance mode, skipping scheduled {:?} backup", kind);
+            continue;
+        };
+        match state.backup_manager.create_backup_of_kind(kind).await {
+            Ok(metadata) => {
+                info!("Scheduled {:?} backup created: {}", metadata.kind, metadata.id);
+                if let Err(e) = state.backup_manager.cleanup_old_backups().await {
+                    warn!("Failed to clean up old backups: {:#}", e);
+                }
+            }
+            Err(e) => {
+                error!("Scheduled backup failed: {:#}", e);
+                publish_backup_failed(&state, kind, &e, true).await;
+            }
+        }
+    }
+}
 
-    let response = PaginatedResponse {
-        data: paginated_workers,
-        total,
-        page,
-        page_size,
-        total_pages,
-    };
+/// Rotate the audit log on its schedule
+async fn run_audit_rotation(state: AdminState) {
+    loop {
+        let Some(next_run) = state.audit_logger.next_scheduled_rotation(Utc::now()) else {
+            return;
+        };
+        let wait = (next_run - Utc::now()).to_std().unwrap_or_default();
+        tokio::time::sleep(wait).await;
+
+        match state.audit_logger.rotate_scheduled().await {
+            Ok(Some(archive)) => info!("Scheduled audit log rotation wrote {}", archive.display()),
+            Ok(None) => {}
+            Err(e) => warn!("Scheduled audit log rotation failed: {:#}", e),
+        }
+    }
+}
 
-    Json(ApiResponse::ok(response))
+/// Compact the store in its scheduled windows
+async fn run_compaction_scheduler(state: AdminState) {
+    loop {
+        let Some(next_run) = state.storage.next_scheduled(Utc::now()) else {
+            return;
+        };
+        info!("Next store compaction of {} at {}", state.storage.path().display(), next_run);
+        let wait = (next_run - Utc::now()).to_std().unwrap_or_default();
+        tokio::time::sleep(wait).await;
+
+        let Ok(_write) = state.maintenance.begin_write() else {
+            warn!("In maintenance mode, skipping scheduled store compaction");
+            continue;
+        };
+        if let Err(e) = state.storage.compact(CompactionTrigger::Scheduled, None).await {
+            warn!("Skipping scheduled store compaction: {:#}", e);
+        }
+    }
 }
 
-/// Get worker detail
-async fn worker_detail(
+/// Diagnose the store once for every corruption a health check detects
+async fn run_store_doctor(state: AdminState) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(STORE_DOCTOR_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+        if state.store_doctor.is_running() {
+            continue;
+        }
+        match state.store_doctor.needs_diagnosis().await {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(e) => {
+                warn!("Failed to check whether the store needs a diagnosis: {:#}", e);
+                continue;
+            }
+        }
+        if let Err(e) = state.store_doctor.diagnose(Some(&state.backup_manager), DoctorTrigger::HealthCheck, None).await {
+            warn!("Store diagnosis of {} failed: {:#}", state.store_doctor.path().display(), e);
+        }
+    }
+}
+
+/// Email daily and weekly summaries to the operators subscribed to them
+async fn run_report_scheduler(state: AdminState) {
+    loop {
+        let Some((next_run, periods)) = state.report_settings.next_run(Utc::now()) else {
+            info!("No report schedules configured");
+            return;
+        };
+        let wait = (next_run - Utc::now()).to_std().unwrap_or_default();
+        tokio::time::sleep(wait).await;
+
+        for period in periods {
+            let mut recipients = Vec::new();
+            for subscription in state.report_subscriptions.subscribers(period).await {
+                // Accounts disabled since subscribing get nothing
+                match state.auth_manager.get_user(&subscription.username).await {
+                    Some(user) if !user.disabled => recipients.push(subscription.email),
+                    _ => debug!("Skipping {:?} report of inactive user '{}'", period, subscription.username),
+                }
+            }
+            if recipients.is_empty() {
+                continue;
+            }
+            let report = build_report(&state, period, next_run).await;
+            let mut sent = 0;
+            for recipient in &recipients {
+                match send_report(&state, &report, recipient).await {
+                    Ok(()) => sent += 1,
+                    Err(e) => warn!("Failed to send {:?} report to {}: {:#}", period, recipient, e),
+                }
+            }
+            info!("Sent {:?} report to {} of {} subscriber(s)", period, sent, recipients.len());
+        }
+    }
+}
+
+/// Summary of the pool over the `period` ending at `to`
+async fn build_report(state: &AdminState, period: ReportPeriod, to: DateTime<Utc>) -> PoolReport {
+    let range = period.duration().num_seconds() as u64;
+    let end = to.timestamp().max(0) as u64;
+    let shares = state.store.get_pplns_shares_filtered(None, Some(end.saturating_sub(range)), Some(end));
+    let mut by_worker: HashMap<(&str, &str), Vec<&SimplePplnsShare>> = HashMap::new();
+    for share in &shares {
+        let Some(address) = share.btcaddress.as_deref() else {
+            continue;
+        };
+        let worker = share.workername.as_deref().unwrap_or("worker");
+        by_worker.entry((address, worker)).or_default().push(share);
+    }
+    let workers = by_worker.into_iter()
+        .map(|((address, worker), shares)| WorkerLine {
+            address: address.to_string(),
+            worker: worker.to_string(),
+            hashrate_ths: ShareActivity::from_shares(&shares, range).hashrate_ths,
+            shares: shares.len() as u64,
+        })
+        .collect();
+    let inputs = ReportInputs {
+        hashrate: state.hashrate_history.query(POOL_SERIES, range, range / REPORT_HASHRATE_POINTS, end).await,
+        blocks: state.block_tracker.blocks().await,
+        workers,
+        batches: state.payout_engine.batches().await,
+        alerts: state.alert_manager.get_history(None).await,
+    };
+    PoolReport::build(period, to, inputs)
+}
+
+/// Email `report` to one recipient, as text with an HTML alternative
+async fn send_report(state: &AdminState, report: &PoolReport, recipient: &str) -> Result<()> {
+    state.alert_manager.send_email(
+        state.report_settings.channel.as_deref(),
+        &[recipient.to_string()],
+        &report.subject(),
+        report.render_text(),
+        Some(report.render_html()),
+    )
+    .await
+}
+
+/// Forget finished jobs past their retention, with the files they produced
+/// Poll the node's fee estimates and mempool size
+async fn run_fee_monitor(state: AdminState, poll_secs: u64) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(poll_secs));
+    loop {
+        interval.tick().await;
+        let result = match bitcoin_rpc(&state).await {
+            Ok(rpc) => state.fee_monitor.poll(&rpc),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(fees) => debug!(
+                "Fee estimate {:?} sat/vB, {} transaction(s) in the mempool",
+                fees.normal_sat_vb, fees.mempool_txs
+            ),
+            Err(e) => warn!("Fee monitor poll failed: {:#}", e),
+        }
+    }
+}
+
+async fn run_job_cleanup(jobs: Arc<JobRegistry>) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(JOB_CLEANUP_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+        jobs.cleanup(Utc::now()).await;
+    }
+}
+
+/// Copy new shares, blocks, payouts and audit events into the analytics database
+#[cfg(feature = "analytics")]
+async fn run_analytics_mirror(mirror: Arc<AnalyticsMirror>, sync_secs: u64) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(sync_secs));
+    loop {
+        interval.tick().await;
+        if let Err(e) = mirror.sync().await {
+            warn!("Analytics mirror sync failed: {:#}", e);
+        }
+    }
+}
+
+/// Queue config changes for webhooks and attempt due deliveries
+async fn run_outbox(state: AdminState, mut versions: broadcast::Receiver<ConfigVersion>) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(OUTBOX_INTERVAL_SECS));
+    let mut versions_open = true;
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                state.outbox.deliver_due().await;
+            }
+            version = versions.recv(), if versions_open => match version {
+                Ok(version) => {
+                    state.outbox.publish(OutboxEvent::ConfigChanged, serde_json::json!({
+                        "version": version.id,
+                        "parent": version.parent_id,
+                        "description": version.description,
+                        "changed_by": version.created_by,
+                    })).await;
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!("{} config version(s) were not sent to webhooks", missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => versions_open = false,
+            },
+        }
+    }
+}
+
+/// Name of the pool instance `state` serves
+fn instance_name(state: &AdminState) -> &str {
+    &state.instances.iter()
+        .find(|i| i.config_path == state.config_path)
+        .unwrap_or(state.instances.primary())
+        .name
+}
+
+/// Tell webhooks that a backup could not be taken
+async fn publish_backup_failed(state: &AdminState, kind: BackupKind, error: &anyhow::Error, scheduled: bool) {
+    state.outbox.publish(OutboxEvent::BackupFailed, serde_json::json!({
+        "instance": instance_name(state),
+        "kind": kind,
+        "scheduled": scheduled,
+        "error": format!("{:#}", error),
+    })).await;
+}
+
+// ===== Hashrate History =====
+
+/// Sample pool, address and worker hashrate from the shares of each interval
+async fn run_hashrate_sampler(state: AdminState, sample_secs: u64) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(sample_secs));
+    // The first tick fires immediately, before a full interval of shares exists
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let Ok(_write) = state.maintenance.begin_write() else {
+            debug!("In maintenance mode, skipping hashrate sample");
+            continue;
+        };
+        let now = unix_now();
+        let shares = state.store.get_pplns_shares_filtered(
+            None,
+            Some(now.saturating_sub(sample_secs)),
+            Some(now),
+        );
+
+        let mut by_series: HashMap<String, Vec<&SimplePplnsShare>> = HashMap::new();
+        for share in &shares {
+            let Some(address) = share.btcaddress.as_deref() else {
+                continue;
+            };
+            let worker = share.workername.as_deref().unwrap_or("worker");
+            by_series.entry(address.to_string()).or_default().push(share);
+            by_series.entry(timeseries::worker_series(address, worker)).or_default().push(share);
+        }
+        let pool: Vec<&SimplePplnsShare> = shares.iter().collect();
+        let activity = ShareActivity::from_shares(&pool, sample_secs);
+        state.hashrate_anomaly.observe(activity.hashrate_ths, state.health_checker.active_connections() as u64);
+
+        let mut outcomes = reported_share_stats(&state, now.saturating_sub(sample_secs));
+        if outcomes.total() == 0 {
+            // Without stratum reports, duplicates are the only rejects the store shows
+            for stats in share_stats::derive_from_shares(&shares).values() {
+                outcomes.merge(stats);
+            }
+        }
+
+        let mut samples = vec![
+            (POOL_SERIES.to_string(), activity.hashrate_ths),
+            (WORKERS_SERIES.to_string(), activity.workers as f64),
+            (SHARE_RATE_SERIES.to_string(), shares.len() as f64 / sample_secs as f64),
+            (REJECT_RATE_SERIES.to_string(), outcomes.reject_ratio()),
+        ];
+        for farm in state.farms.list().await {
+            let farm_shares: Vec<&SimplePplnsShare> = farm.addresses.iter()
+                .filter_map(|address| by_series.get(address))
+                .flatten()
+                .copied()
+                .collect();
+            let hashrate = ShareActivity::from_shares(&farm_shares, sample_secs).hashrate_ths;
+            samples.push((timeseries::farm_series(&farm.name), hashrate));
+        }
+        samples.extend(by_series.into_iter().map(|(series, shares)| {
+            (series, ShareActivity::from_shares(&shares, sample_secs).hashrate_ths)
+        }));
+
+        if let Err(e) = state.hashrate_history.record(now, &samples).await {
+            error!("Failed to record hashrate sample: {:#}", e);
+        }
+    }
+}
+
+/// Pool-wide share outcomes reported by the stratum layer since unix time `start`
+fn reported_share_stats(state: &AdminState, start: u64) -> WorkerShareStats {
+    let mut total = WorkerShareStats::default();
+    for stats in state.share_stats.since(start).values() {
+        total.merge(stats);
+    }
+    total
+}
+
+/// Parse range and step of a history query, limiting the number of points
+fn history_window(range: Option<&str>, step: Option<&str>) -> Result<(u64, u64)> {
+    let range = timeseries::parse_duration(range.unwrap_or("24h"))?;
+    let step = timeseries::parse_duration(step.unwrap_or("5m"))?;
+    if range / step > MAX_HASHRATE_POINTS {
+        return Err(anyhow::anyhow!("Too many points; use a step of at least {}s", range.div_ceil(MAX_HASHRATE_POINTS)));
+    }
+    Ok((range, step))
+}
+
+/// Hashrate history of a series, for charting
+async fn hashrate_series(state: &AdminState, series: String, query: &HashrateQuery) -> Response {
+    let (range, step) = match history_window(query.range.as_deref(), query.step.as_deref()) {
+        Ok(window) => window,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    };
+    let points = state.hashrate_history.query(&series, range, step, unix_now()).await;
+    Json(ApiResponse::ok(serde_json::json!({
+        "series": series,
+        "range_secs": range,
+        "step_secs": step,
+        "points": points,
+    })))
+    .into_response()
+}
+
+/// Hashrate history of an address, or of one of its workers
+#[utoipa::path(
+    get,
+    path = "/api/v1/workers/{address}/hashrate",
+    tag = "workers",
+    params(("address" = String, Path, description = "Miner BTC address"), HashrateQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn worker_hashrate(
     State(state): State<AdminState>,
     Path(address): Path<String>,
-) -> impl IntoResponse {
-    // Get shares for the specific address
-    let end_time = std::time::SystemTime::now()
+    Query(query): Query<HashrateQuery>,
+) -> Response {
+    let series = match &query.worker {
+        Some(worker) => timeseries::worker_series(&address, worker),
+        None => address,
+    };
+    hashrate_series(&state, series, &query).await
+}
+
+/// Hashrate history of the whole pool
+#[utoipa::path(
+    get,
+    path = "/api/v1/hashrate",
+    tag = "workers",
+    params(HashrateQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn pool_hashrate(
+    State(state): State<AdminState>,
+    Query(query): Query<HashrateQuery>,
+) -> Response {
+    hashrate_series(&state, POOL_SERIES.to_string(), &query).await
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct AddressWorkersQuery {
+    /// Minutes of shares to aggregate; defaults to 10, at most 1440
+    window_minutes: Option<u64>,
+}
+
+impl AddressWorkersQuery {
+    fn window_secs(&self) -> u64 {
+        self.window_minutes
+            .map(|minutes| minutes.clamp(1, 24 * 60) * 60)
+            .unwrap_or(LIVE_WORKER_WINDOW_SECS)
+    }
+}
+
+/// Recent shares grouped by payout address and worker name
+async fn group_recent_shares(state: &AdminState, window_secs: u64) -> Vec<AddressWorkers> {
+    let shares = cached_shares(state, None, window_secs).await;
+    farms::group_by_address(&shares, window_secs)
+}
+
+/// Worker stats grouped by payout address, with a breakdown by worker name
+#[utoipa::path(
+    get,
+    path = "/api/v1/workers/by-address",
+    tag = "workers",
+    params(AddressWorkersQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn workers_by_address(
+    State(state): State<AdminState>,
+    Query(query): Query<AddressWorkersQuery>,
+) -> Response {
+    let window_secs = query.window_secs();
+    Json(ApiResponse::ok(serde_json::json!({
+        "window_secs": window_secs,
+        "addresses": group_recent_shares(&state, window_secs).await,
+    })))
+    .into_response()
+}
+
+/// Worker names mining to one address
+#[utoipa::path(
+    get,
+    path = "/api/v1/workers/by-address/{address}",
+    tag = "workers",
+    params(("address" = String, Path, description = "Miner BTC address"), AddressWorkersQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn address_workers(
+    State(state): State<AdminState>,
+    Path(address): Path<String>,
+    Query(query): Query<AddressWorkersQuery>,
+) -> Response {
+    let window_secs = query.window_secs();
+    let workers = group_recent_shares(&state, window_secs).await
+        .into_iter()
+        .find(|a| a.address == address)
+        .unwrap_or_else(|| AddressWorkers::idle(&address));
+    Json(ApiResponse::ok(serde_json::json!({
+        "window_secs": window_secs,
+        "address": workers,
+    })))
+    .into_response()
+}
+
+/// A farm with the combined activity of its addresses
+fn farm_view(farm: &Farm, by_address: &[AddressWorkers], window_secs: u64) -> serde_json::Value {
+    serde_json::json!({
+        "farm": farm,
+        "window_secs": window_secs,
+        "stats": farm.stats(by_address),
+    })
+}
+
+/// Every farm with its combined hashrate
+#[utoipa::path(
+    get,
+    path = "/api/v1/farms",
+    tag = "workers",
+    params(AddressWorkersQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn list_farms(
+    State(state): State<AdminState>,
+    Query(query): Query<AddressWorkersQuery>,
+) -> Response {
+    let window_secs = query.window_secs();
+    let by_address = group_recent_shares(&state, window_secs).await;
+    let farms: Vec<serde_json::Value> = state.farms.list().await.iter()
+        .map(|farm| farm_view(farm, &by_address, window_secs))
+        .collect();
+    Json(ApiResponse::ok(farms)).into_response()
+}
+
+/// A farm with a per-address and per-worker breakdown
+#[utoipa::path(
+    get,
+    path = "/api/v1/farms/{name}",
+    tag = "workers",
+    params(("name" = String, Path, description = "Farm name"), AddressWorkersQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "No such farm"),
+    ),
+)]
+async fn get_farm(
+    State(state): State<AdminState>,
+    Path(name): Path<String>,
+    Query(query): Query<AddressWorkersQuery>,
+) -> Response {
+    let Some(farm) = state.farms.get(&name).await else {
+        return (StatusCode::NOT_FOUND, Json(ApiResponse::<()>::error(format!("Farm {} not found", name)))).into_response();
+    };
+    let window_secs = query.window_secs();
+    let by_address = group_recent_shares(&state, window_secs).await;
+    Json(ApiResponse::ok(farm_view(&farm, &by_address, window_secs))).into_response()
+}
+
+#[derive(Deserialize, ToSchema)]
+struct FarmRequest {
+    #[serde(default)]
+    description: String,
+    /// Payout addresses grouped by the farm; replaces the current list
+    addresses: Vec<String>,
+}
+
+/// Create or replace a farm
+#[utoipa::path(
+    post,
+    path = "/api/v1/farms/{name}",
+    tag = "workers",
+    params(("name" = String, Path, description = "Letters, digits, '-' and '_'")),
+    request_body = FarmRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 400, description = "Invalid name or address"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+    ),
+)]
+async fn set_farm(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(name): Path<String>,
+    Json(req): Json<FarmRequest>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let network = state.config.read().await.stratum.network;
+    for address in &req.addresses {
+        if let Err(e) = miner_access::parse_address(address.trim(), network) {
+            return (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response();
+        }
+    }
+    match state.farms.set(&name, &req.description, req.addresses, &claims.name).await {
+        Ok(farm) => Json(ApiResponse::ok(farm)).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    }
+}
+
+/// Delete a farm; alert rules naming it stop evaluating
+#[utoipa::path(
+    post,
+    path = "/api/v1/farms/{name}/delete",
+    tag = "workers",
+    params(("name" = String, Path, description = "Farm name")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "No such farm"),
+    ),
+)]
+async fn delete_farm(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(name): Path<String>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    match state.farms.remove(&name).await {
+        Ok(true) => Json(ApiResponse::ok(serde_json::json!({ "deleted": name }))).into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, Json(ApiResponse::<()>::error(format!("Farm {} not found", name)))).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    }
+}
+
+/// Combined hashrate history of a farm, recorded from when it was created
+#[utoipa::path(
+    get,
+    path = "/api/v1/farms/{name}/hashrate",
+    tag = "workers",
+    params(("name" = String, Path, description = "Farm name"), HashrateQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "No such farm"),
+    ),
+)]
+async fn farm_hashrate(
+    State(state): State<AdminState>,
+    Path(name): Path<String>,
+    Query(query): Query<HashrateQuery>,
+) -> Response {
+    if state.farms.get(&name).await.is_none() {
+        return (StatusCode::NOT_FOUND, Json(ApiResponse::<()>::error(format!("Farm {} not found", name)))).into_response();
+    }
+    hashrate_series(&state, timeseries::farm_series(&name), &query).await
+}
+
+/// Downsampled history of a pool metric, with minimum and maximum per bucket
+/// for candlestick charts
+#[utoipa::path(
+    get,
+    path = "/api/v1/dashboard/history",
+    tag = "dashboard",
+    params(DashboardHistoryQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 400, description = "Unknown metric or invalid range or step"),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn dashboard_history(
+    State(state): State<AdminState>,
+    Query(query): Query<DashboardHistoryQuery>,
+) -> Response {
+    let metric = query.metric.as_deref().unwrap_or("hashrate");
+    let Some((series, unit)) = timeseries::metric_series(metric) else {
+        let error = format!("Unknown metric {}; use hashrate, workers, share_rate or reject_rate", metric);
+        return (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(error))).into_response();
+    };
+    let (range, step) = match history_window(query.range.as_deref(), query.step.as_deref()) {
+        Ok(window) => window,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    };
+    let buckets = state.hashrate_history.buckets(series, range, step, unix_now()).await;
+    Json(ApiResponse::ok(serde_json::json!({
+        "metric": metric,
+        "unit": unit,
+        "range_secs": range,
+        "step_secs": step,
+        "buckets": buckets,
+    })))
+    .into_response()
+}
+
+// ===== Live Feed =====
+
+/// Poll the store and alert manager, publishing changes to live feed subscribers
+async fn run_live_feed(state: AdminState) {
+    let mut alerts = state.alert_manager.subscribe();
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(LIVE_FEED_INTERVAL_SECS));
+    let mut presence = WorkerPresence::new();
+    let mut alerts_open = true;
+    let mut last_share_time = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap_or_default()
         .as_secs();
-    let start_time = end_time - (24 * 3600);
 
-    let all_shares = state.store.get_pplns_shares_filtered(
-        Some(1000),
-        Some(start_time),
-        Some(end_time),
-    );
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            alert = alerts.recv(), if alerts_open => {
+                match alert {
+                    Ok(alert) => {
+                        state.live_feed.publish(LiveEvent::Alert { alert });
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Live feed skipped {} alert(s)", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        warn!("Alert channel closed, live feed will no longer forward alerts");
+                        alerts_open = false;
+                    }
+                }
+                continue;
+            }
+        }
 
-    // Filter shares for the specific address
-    let shares: Vec<_> = all_shares
-        .into_iter()
-        .filter(|s| s.btcaddress.as_ref().map_or(false, |addr| addr == &address))
-        .collect();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        // Worker presence is tracked even without subscribers so that a new
+        // client doesn't receive a connect event for every known worker
+        let recent = state.store.get_pplns_shares_filtered(
+            Some(1000),
+            Some(now.saturating_sub(LIVE_WORKER_WINDOW_SECS)),
+            Some(now),
+        );
+        let seen = recent
+            .iter()
+            .map(|share| {
+                (
+                    share.btcaddress.clone().unwrap_or_else(|| format!("user_{}", share.user_id)),
+                    share.workername.clone().unwrap_or_else(|| "worker".to_string()),
+                )
+            })
+            .collect();
+        let presence_events = presence.update(seen);
+
+        // Shares the stratum server didn't report still make cached aggregations stale
+        if recent.iter().any(|share| share.n_time > last_share_time) {
+            invalidate_share_caches(&state);
+        }
 
-    if shares.is_empty() {
-        return Json(ApiResponse::<serde_json::Value>::error(format!("No shares found for address {} in last 24 hours", address)));
+        if state.live_feed.subscriber_count() == 0 {
+            last_share_time = now;
+            continue;
+        }
+
+        let mut new_shares: Vec<_> = recent
+            .iter()
+            .filter(|share| share.n_time > last_share_time)
+            .collect();
+        new_shares.sort_by_key(|share| share.n_time);
+        for share in new_shares {
+            state.live_feed.publish(LiveEvent::Share {
+                address: share.btcaddress.clone().unwrap_or_else(|| format!("user_{}", share.user_id)),
+                worker: share.workername.clone().unwrap_or_else(|| "worker".to_string()),
+                difficulty: share.difficulty,
+                timestamp: share.n_time,
+            });
+            last_share_time = last_share_time.max(share.n_time);
+        }
+
+        // Reported connects and disconnects are published as they arrive
+        if !state.ingest.presence_reported() {
+            for event in presence_events {
+                state.live_feed.publish(event);
+            }
+        }
+
+        if let Ok(metrics) = serde_json::to_value(build_dashboard_metrics(&state).await) {
+            state.live_feed.publish(LiveEvent::Dashboard { metrics });
+        }
     }
+}
 
-    // Group by worker name
-    let mut worker_stats: HashMap<String, u64> = HashMap::new();
-    let mut total_shares = 0u64;
+// ===== Stratum Event Ingestion =====
 
-    for share in shares {
-        let worker = share.workername.clone().unwrap_or_else(|| "worker".to_string());
-        *worker_stats.entry(worker).or_insert(0) += 1;
-        total_shares += 1;
+/// Feed an event reported by the stratum server into share statistics, the
+/// live feed, the audit log and alert inputs
+async fn apply_stratum_event(state: &AdminState, event: StratumEvent) {
+    match event {
+        StratumEvent::WorkerConnected { address, worker } => {
+            state.ingest.mark_presence_reported();
+            state.live_feed.publish(LiveEvent::WorkerConnected { address, worker });
+        }
+        StratumEvent::WorkerDisconnected { address, worker } => {
+            state.ingest.mark_presence_reported();
+            state.live_feed.publish(LiveEvent::WorkerDisconnected { address, worker });
+        }
+        StratumEvent::ShareAccepted { address, worker, .. } => {
+            state.share_stats.record(&address, &worker, ShareOutcome::Accepted);
+            invalidate_share_caches(state);
+        }
+        StratumEvent::ShareRejected { address, worker, reason } => {
+            state.share_stats.record(&address, &worker, reason.outcome());
+        }
+        StratumEvent::AuthorizationFailed { username, ip, reason } => {
+            state.ingest.record_auth_failure(AuthFailure {
+                username: username.clone(),
+                ip: ip.clone(),
+                timestamp: unix_now(),
+            });
+            state.audit_logger.log(AuditLog {
+                id: uuid::Uuid::new_v4().to_string(),
+                timestamp: Utc::now(),
+                username: username.clone(),
+                action: "stratum_auth_failed".to_string(),
+                resource: format!("worker:{}", username),
+                ip_address: ip,
+                details: serde_json::json!({ "reason": reason }),
+                success: false,
+                error: reason,
+                request_id: None,
+                geo: None,
+                chain: None,
+            }).await;
+        }
     }
+}
 
-    let response = serde_json::json!({
-        "address": address,
-        "total_shares": total_shares,
-        "worker_stats": worker_stats,
+/// Apply a batch of events pushed by the stratum server
+#[utoipa::path(
+    post,
+    path = "/api/v1/internal/events",
+    tag = "internal",
+    request_body = Vec<StratumEvent>,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 400, description = "Malformed events"),
+        (status = 401, description = "Missing or wrong INGEST_TOKEN"),
+        (status = 404, description = "INGEST_TOKEN is not set"),
+        (status = 429, description = "Too many event batches"),
+    ),
+    security(()),
+)]
+async fn ingest_events(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> Response {
+    if !state.ingest.http_enabled() {
+        return (StatusCode::NOT_FOUND, Json(ApiResponse::<()>::error("Event ingestion is disabled"))).into_response();
+    }
+    let token = headers
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    if !state.ingest.authorize(token) {
+        return (StatusCode::UNAUTHORIZED, Json(ApiResponse::<()>::error("Invalid ingest token"))).into_response();
+    }
+    // Parsed only once the caller is known to be the stratum server
+    let events: Vec<StratumEvent> = match serde_json::from_slice(&body) {
+        Ok(events) => events,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(format!("Malformed events: {}", e)))).into_response(),
+    };
+
+    let applied = events.len();
+    for event in events {
+        apply_stratum_event(&state, event).await;
+    }
+    Json(ApiResponse::ok(serde_json::json!({ "applied": applied }))).into_response()
+}
+
+/// Accept newline-delimited JSON events from the stratum server on a unix socket
+async fn run_ingest_socket(state: AdminState, path: String) {
+    use tokio::io::AsyncBufReadExt;
+
+    let listener = match bind_private_socket(std::path::Path::new(&path)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Failed to bind ingest socket {}: {}", path, e);
+            return;
+        }
+    };
+    info!("Accepting stratum events on {}", path);
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                warn!("Ingest socket accept failed: {}", e);
+                continue;
+            }
+        };
+        let state = state.clone();
+        tokio::spawn(async move {
+            let mut lines = tokio::io::BufReader::new(stream).lines();
+            loop {
+                match lines.next_line().await {
+                    Ok(Some(line)) => match ingest::parse_line(&line) {
+                        Ok(Some(event)) => apply_stratum_event(&state, event).await,
+                        Ok(None) => {}
+                        Err(e) => warn!("Ignoring malformed stratum event: {}", e),
+                    },
+                    Ok(None) => break,
+                    Err(e) => {
+                        warn!("Ingest socket read failed: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Bind a unix socket at `path` that only the owner and group can connect to
+///
+/// Anyone who can connect can report events, so the socket is bound in a
+/// fresh 0700 directory and restricted before it is moved into place; it is
+/// never reachable with looser permissions.
+fn bind_private_socket(path: &std::path::Path) -> std::io::Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    let name = path.file_name()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "socket path has no file name"))?;
+    let staging = path.with_file_name(format!(".{}.{}", name.to_string_lossy(), uuid::Uuid::new_v4().simple()));
+    std::fs::DirBuilder::new().mode(0o700).create(&staging)?;
+    let staged = staging.join("socket");
+    let bound = tokio::net::UnixListener::bind(&staged).and_then(|listener| {
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o660))?;
+        // Replaces a socket left by a previous run
+        std::fs::rename(&staged, path)?;
+        Ok(listener)
+    });
+    let _ = std::fs::remove_file(&staged);
+    let _ = std::fs::remove_dir(&staging);
+    bound
+}
+
+/// Upgrade to a WebSocket streaming live dashboard events
+#[utoipa::path(
+    get,
+    path = "/api/v1/ws",
+    tag = "dashboard",
+    params(LiveFeedQuery),
+    responses(
+        (status = 101, description = "Switched to the live event WebSocket"),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+    security(()),
+)]
+async fn live_ws(
+    ws: WebSocketUpgrade,
+    State(state): State<AdminState>,
+    Query(query): Query<LiveFeedQuery>,
+    headers: HeaderMap,
+    certificate: Option<Extension<Option<ClientCertificate>>>,
+) -> Response {
+    let claims = match live_claims(&state, &headers, query.token, certificate).await {
+        Ok(claims) => claims,
+        Err(status) => return status.into_response(),
+    };
+
+    ws.on_upgrade(move |socket| live_socket(socket, state, claims.name))
+}
+
+/// Authenticate a live connection by its bearer header or `token` query
+/// parameter, or like `auth_middleware` by a client certificate
+///
+/// Browsers can't set headers on WebSocket upgrades or `EventSource` requests.
+async fn live_claims(
+    state: &AdminState,
+    headers: &HeaderMap,
+    query_token: Option<String>,
+    certificate: Option<Extension<Option<ClientCertificate>>>,
+) -> Result<Claims, StatusCode> {
+    let token = headers
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(|t| t.to_string())
+        .or(query_token);
+    let certificate = certificate.and_then(|Extension(c)| c).filter(|c| c.authenticates);
+
+    let result = match (token, certificate) {
+        (Some(t), _) => Some(state.auth_manager.verify_token(&t).await),
+        (None, Some(certificate)) => {
+            let username = certificate.common_name.as_deref().unwrap_or_default();
+            match state.auth_manager.certificate_claims(username).await {
+                Ok(claims) => Some(Ok(claims)),
+                Err(e) => {
+                    warn!("Client certificate {} rejected for live feed: {}", certificate.fingerprint, e);
+                    return Err(StatusCode::UNAUTHORIZED);
+                }
+            }
+        }
+        (None, None) => None,
+    };
+    match result {
+        Some(Ok(claims)) if claims.must_change_password => {
+            warn!("User '{}' must change password before using the live feed", claims.name);
+            Err(StatusCode::FORBIDDEN)
+        }
+        Some(Ok(claims)) if claims.must_enroll_2fa => {
+            warn!("User '{}' must enroll in 2FA before using the live feed", claims.name);
+            Err(StatusCode::FORBIDDEN)
+        }
+        Some(Ok(claims)) if claims.is_scoped() => {
+            warn!("Token of '{}' has no scope for the live feed", claims.name);
+            Err(StatusCode::FORBIDDEN)
+        }
+        Some(Ok(claims)) => Ok(claims),
+        Some(Err(e)) => {
+            warn!("Invalid token for live feed: {}", e);
+            Err(StatusCode::UNAUTHORIZED)
+        }
+        None => {
+            warn!("Unauthorized live feed connection attempt");
+            Err(StatusCode::UNAUTHORIZED)
+        }
+    }
+}
+
+/// Forward live events to a connected WebSocket client until it disconnects
+async fn live_socket(mut socket: WebSocket, state: AdminState, username: String) {
+    info!("Live feed client connected: {}", username);
+    let mut events = state.live_feed.subscribe();
+
+    // Send a snapshot right away so the UI doesn't wait for the first tick
+    if let Ok(metrics) = serde_json::to_value(build_dashboard_metrics(&state).await) {
+        let snapshot = LiveEvent::Dashboard { metrics };
+        if let Ok(text) = serde_json::to_string(&snapshot) {
+            if socket.send(Message::Text(text)).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    loop {
+        tokio::select! {
+            event = events.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        debug!("Live feed client {} lagged by {} event(s)", username, skipped);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let text = match serde_json::to_string(&event) {
+                    Ok(text) => text,
+                    Err(e) => {
+                        error!("Failed to serialize live event: {}", e);
+                        continue;
+                    }
+                };
+                if socket.send(Message::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+            message = socket.recv() => {
+                match message {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    info!("Live feed client disconnected: {}", username);
+}
+
+/// ID of the last event an `EventSource` saw before reconnecting
+fn last_event_id(headers: &HeaderMap) -> Option<u64> {
+    headers.get("last-event-id")?.to_str().ok()?.trim().parse().ok()
+}
+
+/// Stream `missed` and then every item from `live` as server-sent events
+///
+/// `to_event` gives an item's ID and its event, or `None` for items the
+/// client filtered out. Items at or below the last ID sent are skipped.
+fn event_stream<T, F>(missed: Vec<T>, mut live: broadcast::Receiver<T>, to_event: F) -> Response
+where
+    T: Clone + Send + 'static,
+    F: Fn(&T) -> (u64, Option<Event>) + Send + 'static,
+{
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<Event, Infallible>>(64);
+    tokio::spawn(async move {
+        let mut last_id = 0;
+        for item in missed {
+            let (id, event) = to_event(&item);
+            last_id = id;
+            if let Some(event) = event {
+                if tx.send(Ok(event)).await.is_err() {
+                    return;
+                }
+            }
+        }
+        loop {
+            let item = tokio::select! {
+                item = live.recv() => item,
+                // The client went away
+                _ = tx.closed() => return,
+            };
+            let item = match item {
+                Ok(item) => item,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    debug!("Event stream client lagged by {} event(s)", skipped);
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            let (id, event) = to_event(&item);
+            if id <= last_id {
+                continue;
+            }
+            last_id = id;
+            if let Some(event) = event {
+                if tx.send(Ok(event)).await.is_err() {
+                    return;
+                }
+            }
+        }
     });
+    Sse::new(tokio_stream::wrappers::ReceiverStream::new(rx))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// Server-sent `log` events with each new log line
+///
+/// Takes the `/logs` filters. On reconnect, lines after `Last-Event-ID` (or
+/// `after_id`) still in the buffer are sent first.
+#[utoipa::path(
+    get,
+    path = "/api/v1/logs/stream",
+    tag = "system",
+    params(LogQuery, LiveFeedQuery),
+    responses(
+        (status = 200, description = "`text/event-stream` of `log` events carrying a log entry"),
+        (status = 400, description = "Unknown log level"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Operator role required"),
+    ),
+    security(()),
+)]
+async fn logs_stream(
+    State(state): State<AdminState>,
+    Query(auth): Query<LiveFeedQuery>,
+    Query(mut query): Query<LogQuery>,
+    headers: HeaderMap,
+    certificate: Option<Extension<Option<ClientCertificate>>>,
+) -> Response {
+    let claims = match live_claims(&state, &headers, auth.token, certificate).await {
+        Ok(claims) => claims,
+        Err(status) => return status.into_response(),
+    };
+    if let Some(denied) = require_operator(&claims) {
+        return denied;
+    }
+    query.after_id = last_event_id(&headers).or(query.after_id);
+    // Only a resuming client is sent earlier lines, and all it missed
+    query.limit = Some(if query.after_id.is_some() { usize::MAX } else { 0 });
+    let min_level = match query.min_level() {
+        Ok(min_level) => min_level,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    };
+    let (missed, live) = match state.log_buffer.follow(&query) {
+        Ok(followed) => followed,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    };
+    event_stream(missed, live, move |entry: &LogEntry| {
+        let event = if query.matches(entry, min_level) {
+            Event::default().id(entry.id.to_string()).event("log").json_data(entry).ok()
+        } else {
+            None
+        };
+        (entry.id, event)
+    })
+}
+
+/// Server-sent `alert` events as alerts fire, escalate, resolve or are acknowledged
+///
+/// On reconnect, changes after `Last-Event-ID` that are still kept are sent first.
+#[utoipa::path(
+    get,
+    path = "/api/v1/alerts/stream",
+    tag = "alerts",
+    params(LiveFeedQuery),
+    responses(
+        (status = 200, description = "`text/event-stream` of `alert` events carrying an alert change"),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+    security(()),
+)]
+async fn alerts_stream(
+    State(state): State<AdminState>,
+    Query(auth): Query<LiveFeedQuery>,
+    headers: HeaderMap,
+    certificate: Option<Extension<Option<ClientCertificate>>>,
+) -> Response {
+    if let Err(status) = live_claims(&state, &headers, auth.token, certificate).await {
+        return status.into_response();
+    }
+    let (missed, live) = match last_event_id(&headers) {
+        Some(after_id) => state.alert_manager.follow_changes(after_id).await,
+        None => {
+            let (_, live) = state.alert_manager.follow_changes(u64::MAX).await;
+            (Vec::new(), live)
+        }
+    };
+    event_stream(missed, live, |change: &AlertChange| {
+        let event = Event::default().id(change.id.to_string()).event("alert").json_data(change).ok();
+        (change.id, event)
+    })
+}
+
+/// Get current configuration
+///
+/// The `ETag` header carries the config version, to be sent back as
+/// `If-Match` or `expected_version` with a change.
+#[utoipa::path(
+    get,
+    path = "/api/v1/config",
+    tag = "config",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn get_config(State(state): State<AdminState>) -> Response {
+    let config = state.config.read().await;
+    let version = state.config_manager.current_version().await.map(|v| v.id);
+
+    let view = ConfigView {
+        stratum_port: config.stratum.port,
+        stratum_hostname: config.stratum.hostname.clone(),
+        start_difficulty: config.stratum.start_difficulty,
+        minimum_difficulty: config.stratum.minimum_difficulty,
+        pplns_ttl_days: config.store.pplns_ttl_days,
+        difficulty_multiplier: 1.0,
+        network: config.stratum.network.to_string(),
+        pool_signature: config.stratum.pool_signature.clone(),
+        ignore_difficulty: config.stratum.ignore_difficulty.unwrap_or(false),
+        donation: config.stratum.donation,
+        fee: None,
+        version: version.clone(),
+    };
+
+    let mut response = Json(ApiResponse::ok(view)).into_response();
+    if let Some(etag) = version.and_then(|v| header::HeaderValue::from_str(&format!("\"{}\"", v)).ok()) {
+        response.headers_mut().insert(header::ETAG, etag);
+    }
+    response
+}
+
+/// Version a config change was made against, from the body or `If-Match`
+fn expected_version(headers: &HeaderMap, body: Option<String>) -> Option<String> {
+    body.or_else(|| {
+        let value = headers.get(header::IF_MATCH)?.to_str().ok()?.trim();
+        let value = value.strip_prefix("W/").unwrap_or(value);
+        Some(value.trim_matches('"').to_string())
+    })
+    .filter(|v| !v.is_empty())
+}
+
+/// Changes must name the version they were made against
+fn version_required() -> Response {
+    (
+        StatusCode::PRECONDITION_REQUIRED,
+        Json(ApiResponse::<()>::error("Send the config version from GET /api/v1/config as If-Match or expected_version")),
+    ).into_response()
+}
+
+/// 409 with the version that won, so the client can reload and retry
+fn version_conflict(conflict: VersionConflict) -> Response {
+    let mut body = ApiResponse::error(conflict.to_string());
+    body.data = Some(conflict);
+    (StatusCode::CONFLICT, Json(body)).into_response()
+}
+
+/// Update configuration and persist it to the config file
+#[utoipa::path(
+    post,
+    path = "/api/v1/config",
+    tag = "config",
+    request_body = ConfigUpdate,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+        (status = 409, description = "Config changed since the expected version"),
+        (status = 422, description = "The change introduces critical safety issues"),
+        (status = 428, description = "No expected version given"),
+    ),
+)]
+async fn update_config(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Json(update): Json<ConfigUpdate>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let Some(expected) = expected_version(&headers, update.expected_version) else {
+        return version_required();
+    };
+    let mut requested = Vec::new();
+    if let Some(diff) = update.start_difficulty {
+        requested.push(("start_difficulty", serde_json::json!(diff)));
+    }
+    if let Some(diff) = update.minimum_difficulty {
+        requested.push(("minimum_difficulty", serde_json::json!(diff)));
+    }
+    if let Some(signature) = update.pool_signature {
+        requested.push(("pool_signature", serde_json::json!(signature)));
+    }
+
+    // Ranges and rules come from the config schema
+    let mut errors = Vec::new();
+    for (parameter, value) in &requested {
+        if let Err(e) = state.config_manager.validate_parameter(parameter, value).await {
+            errors.push(e.to_string());
+        }
+    }
+    if !errors.is_empty() {
+        return Json(ApiResponse::<serde_json::Value>::error(errors.join("; "))).into_response();
+    }
+
+    let mut config = state.config.write().await;
+    if let Err(conflict) = state.config_manager.check_version(&expected).await {
+        return version_conflict(conflict);
+    }
+    let mut candidate = config.clone();
+    let mut changes = Vec::new();
+    for (parameter, value) in requested {
+        let old = config_mgt::parameter_value(&candidate, parameter).unwrap_or_default();
+        if let Err(e) = config_mgt::set_parameter(&mut candidate, parameter, &value) {
+            return Json(ApiResponse::<serde_json::Value>::error(e.to_string())).into_response();
+        }
+        changes.push(format!("{}: {} → {}", parameter, old, value));
+    }
+
+    if changes.is_empty() {
+        return Json(ApiResponse::<serde_json::Value>::error("No valid changes to apply".to_string())).into_response();
+    }
+    let safety = match SafetyAnalyzer::new().check_change(&config, &candidate, update.force) {
+        Ok(report) => report,
+        Err(unsafe_change) => return unsafe_config_change(unsafe_change),
+    };
+    let version = match state.config_manager.record(&candidate, changes.join(", "), &claims.name).await {
+        Ok(version) => version,
+        Err(e) => {
+            return Json(ApiResponse::<serde_json::Value>::error(format!("Failed to record config version: {}", e)))
+                .into_response();
+        }
+    };
+    *config = candidate;
+    drop(config);
+    info!("Applied config changes {} (version {})", changes.join(", "), version.id);
+
+    let persisted = persist_running_config(&state).await;
+    let response = serde_json::json!({
+        "message": format!("Applied {} change(s)", changes.len()),
+        "changes": changes,
+        "version": version.id,
+        "persisted": persisted,
+        "warnings": safety.warnings,
+        "forced": !safety.safe,
+    });
+
+    Json(ApiResponse::ok(response)).into_response()
+}
+
+/// Type, range, risk level and restart flag of each managed parameter, for rendering forms
+#[utoipa::path(
+    get,
+    path = "/api/v1/config/schema",
+    tag = "config",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn config_schema(State(state): State<AdminState>) -> impl IntoResponse {
+    let mut schema: Vec<(String, ConfigSchema)> = state.config_manager.get_schema().await.into_iter().collect();
+    schema.sort_by(|a, b| a.0.cmp(&b.0));
+    let parameters: Vec<serde_json::Value> = schema.into_iter()
+        .map(|(key, schema)| serde_json::json!({
+            "key": key,
+            "parameter": config_mgt::runtime_parameter(&key),
+            "schema": schema,
+        }))
+        .collect();
+    Json(ApiResponse::ok(parameters))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct VardiffQuery {
+    /// Hashrate of the miner, in TH/s
+    hashrate_ths: f64,
+    /// Defaults to the configured start difficulty
+    start_difficulty: Option<u64>,
+    /// Defaults to the configured minimum difficulty
+    minimum_difficulty: Option<u64>,
+    /// Seconds per share vardiff aims for; defaults to 3.33
+    multiplier: Option<f64>,
+}
+
+/// Project how vardiff settles for a miner's hashrate under the current or proposed settings
+#[utoipa::path(
+    get,
+    path = "/api/v1/config/vardiff/simulate",
+    tag = "config",
+    params(VardiffQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 400, description = "Invalid hashrate or setting"),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn simulate_vardiff(
+    State(state): State<AdminState>,
+    Query(query): Query<VardiffQuery>,
+) -> Response {
+    let params = {
+        let config = state.config.read().await;
+        VardiffParams {
+            start_difficulty: query.start_difficulty.unwrap_or(config.stratum.start_difficulty),
+            minimum_difficulty: query.minimum_difficulty.unwrap_or(config.stratum.minimum_difficulty),
+            multiplier: query.multiplier.unwrap_or(vardiff::DEFAULT_MULTIPLIER),
+        }
+    };
+    match vardiff::simulate(query.hashrate_ths, params) {
+        Ok(simulation) => Json(ApiResponse::ok(simulation)).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    }
+}
+
+/// Reload configuration from file
+///
+/// The reloaded file goes through the same version check and safety gate as
+/// any other change. Parameters that need confirmation are not applied but
+/// turned into change requests.
+#[utoipa::path(
+    post,
+    path = "/api/v1/config/reload",
+    tag = "config",
+    request_body(content = ReloadConfigRequest, description = "Optional; `If-Match` may carry the version instead"),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+        (status = 409, description = "Config changed since the expected version"),
+        (status = 422, description = "The reload introduces critical safety issues"),
+        (status = 428, description = "No expected version given"),
+    ),
+)]
+async fn reload_config(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    body: Option<Json<ReloadConfigRequest>>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let req = body.map(|Json(req)| req).unwrap_or_default();
+    let Some(expected) = expected_version(&headers, req.expected_version) else {
+        return version_required();
+    };
+    let mut new_config = match Config::load(&state.config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Failed to reload config: {}", e);
+            return Json(ApiResponse::<serde_json::Value>::error(format!("Failed to reload: {}", e))).into_response();
+        }
+    };
+    let status = state.config_manager.validate_config(&config_mgt::config_params(&new_config)).await;
+    if let config_mgt::ValidationStatus::Invalid { errors } = status {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::<()>::error(format!("Invalid configuration: {}", errors.join("; ")))),
+        ).into_response();
+    }
+
+    let (changes, restart_required) = {
+        let live = state.config.read().await;
+        (
+            config_mgt::changed_parameters(&live, &new_config),
+            config_mgt::restart_required_changes(&live, &new_config),
+        )
+    };
+
+    // Keep the live value of anything that needs confirmation; those are
+    // requested below once the rest of the file has been applied
+    let mut confirm = Vec::new();
+    for (parameter, old_value, new_value) in changes {
+        if let Err(e) = state.config_confirmation.validate_value(&parameter, &new_value) {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<()>::error(format!("Invalid {}: {}", parameter, e))),
+            ).into_response();
+        }
+        if state.config_confirmation.requires_confirmation(&parameter) {
+            if let Err(e) = config_mgt::set_parameter(&mut new_config, &parameter, &old_value) {
+                return Json(ApiResponse::<serde_json::Value>::error(e.to_string())).into_response();
+            }
+            confirm.push((parameter, old_value, new_value));
+        }
+    }
+
+    let version = match state.config_manager
+        .replace_config(
+            &state.config,
+            new_config,
+            "Reloaded from file".to_string(),
+            &claims.name,
+            Some(&expected),
+            req.force,
+        )
+        .await
+    {
+        Ok(version) => version,
+        Err(e) => return config_change_error(e),
+    };
+    state.restart.record(restart_required, &claims.name).await;
+
+    let mut pending = Vec::new();
+    for (parameter, old_value, new_value) in confirm {
+        match state.config_confirmation
+            .create_change_request(
+                parameter.clone(),
+                old_value,
+                new_value,
+                claims.name.clone(),
+                client_ip(&state, &headers),
+                Some(version.id.clone()),
+            )
+            .await
+        {
+            Ok(request) => pending.push(request.id),
+            Err(e) => error!("Failed to create change request for {}: {}", parameter, e),
+        }
+    }
+    info!("Configuration reloaded from file as version {}", version.id);
+    let response = serde_json::json!({
+        "message": "Configuration reloaded successfully",
+        "version": version.id,
+        "pending_confirmation": pending,
+    });
+    Json(ApiResponse::ok(response)).into_response()
+}
+
+/// Get workers list from PPLNS shares (with pagination)
+#[utoipa::path(
+    get,
+    path = "/api/v1/workers",
+    tag = "workers",
+    params(PaginationRequest),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn workers_list(
+    State(state): State<AdminState>,
+    Query(params): Query<PaginationRequest>,
+) -> impl IntoResponse {
+    let banned = state.ban_manager.banned_addresses().await;
+    let worker_tags = state.worker_tags.read().await;
+
+    // Get pagination parameters
+    let page = params.page.unwrap_or(1).max(1);
+    let page_size = params.page_size.unwrap_or(20).min(100);
+    let search = params.search.unwrap_or_default().to_lowercase();
+    let status_filter = params.status.unwrap_or_default().to_lowercase();
+
+    // Recent PPLNS shares (last 1000, last 24 hours)
+    let shares = cached_shares(&state, Some(1000), 24 * 3600).await;
+
+    let outcomes = worker_share_stats(&state, &shares, 24 * 3600);
+
+    // Group shares by miner address
+    let mut workers_map: HashMap<String, WorkerInfo> = HashMap::new();
+
+    for share in shares.iter() {
+        let address = share.btcaddress.clone().unwrap_or_else(|| format!("user_{}", share.user_id));
+
+        let entry = workers_map.entry(address.clone()).or_insert_with(|| {
+            let now = chrono::Utc::now();
+            let is_banned = banned.contains(&address);
+            let tags = worker_tags.get(&address).cloned().unwrap_or_default();
+            WorkerInfo {
+                address: address.clone(),
+                worker_name: share.workername.clone().unwrap_or_else(|| "worker".to_string()),
+                hashrate_ths: 0.0,
+                shares_count: 0,
+                difficulty: share.difficulty,
+                last_seen: now.to_rfc3339(),
+                first_seen: now.to_rfc3339(),
+                is_banned,
+                tags,
+                status: if is_banned {
+                    WorkerStatus::Banned
+                } else {
+                    WorkerStatus::Active
+                },
+                share_stats: WorkerShareStats::default(),
+                reject_rate: 0.0,
+            }
+        });
+
+        entry.shares_count += 1;
+        entry.difficulty = share.difficulty;
+        entry.last_seen = chrono::Utc::now().to_rfc3339();
+    }
+
+    // Convert to vector and apply filters
+    let mut workers: Vec<WorkerInfo> = workers_map.into_values().collect();
+    for worker in &mut workers {
+        let prefix = format!("{}.", worker.address);
+        for (_, stats) in outcomes.iter().filter(|(key, _)| key.starts_with(&prefix)) {
+            worker.share_stats.merge(stats);
+        }
+        worker.reject_rate = worker.share_stats.reject_ratio();
+    }
+
+    // Apply search filter
+    if !search.is_empty() {
+        workers.retain(|w| {
+            w.address.to_lowercase().contains(&search)
+                || w.worker_name.to_lowercase().contains(&search)
+        });
+    }
+
+    // Apply status filter
+    if !status_filter.is_empty() {
+        workers.retain(|w| match status_filter.as_str() {
+            "active" => matches!(w.status, WorkerStatus::Active),
+            "banned" => matches!(w.status, WorkerStatus::Banned),
+            "inactive" => matches!(w.status, WorkerStatus::Inactive),
+            _ => true,
+        });
+    }
+
+    // Apply sorting
+    let sort_by = params.sort_by.unwrap_or_else(|| "last_seen".to_string());
+    let sort_desc = params.sort_order.unwrap_or_else(|| "desc".to_string()) == "desc";
+
+    match sort_by.as_str() {
+        "address" => workers.sort_by(|a, b| {
+            if sort_desc {
+                b.address.cmp(&a.address)
+            } else {
+                a.address.cmp(&b.address)
+            }
+        }),
+        "hashrate" => workers.sort_by(|a, b| {
+            if sort_desc {
+                b.hashrate_ths.partial_cmp(&a.hashrate_ths).unwrap_or(std::cmp::Ordering::Equal)
+            } else {
+                a.hashrate_ths.partial_cmp(&b.hashrate_ths).unwrap_or(std::cmp::Ordering::Equal)
+            }
+        }),
+        "reject_rate" => workers.sort_by(|a, b| {
+            if sort_desc {
+                b.reject_rate.total_cmp(&a.reject_rate)
+            } else {
+                a.reject_rate.total_cmp(&b.reject_rate)
+            }
+        }),
+        "shares" => workers.sort_by(|a, b| {
+            if sort_desc {
+                b.shares_count.cmp(&a.shares_count)
+            } else {
+                a.shares_count.cmp(&b.shares_count)
+            }
+        }),
+        _ => { // default: last_seen
+            workers.sort_by(|a, b| {
+                if sort_desc {
+                    b.last_seen.cmp(&a.last_seen)
+                } else {
+                    a.last_seen.cmp(&b.last_seen)
+                }
+            });
+        }
+    }
+
+    let total = workers.len();
+    let total_pages = (total + page_size - 1) / page_size;
+
+    // Apply pagination
+    let start_idx = (page - 1) * page_size;
+    let end_idx = start_idx + page_size;
+    let paginated_workers: Vec<WorkerInfo> = workers
+        .into_iter()
+        .skip(start_idx)
+        .take(page_size)
+        .collect();
+
+    let response = PaginatedResponse {
+        data: paginated_workers,
+        total,
+        page,
+        page_size,
+        total_pages,
+    };
+
+    Json(ApiResponse::ok(response))
+}
+
+/// Get worker detail
+#[utoipa::path(
+    get,
+    path = "/api/v1/workers/{address}",
+    tag = "workers",
+    params(("address" = String, Path, description = "Miner BTC address")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn worker_detail(
+    State(state): State<AdminState>,
+    Path(address): Path<String>,
+) -> impl IntoResponse {
+    // Get shares for the specific address
+    let end_time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let start_time = end_time - (24 * 3600);
+
+    let all_shares = state.store.get_pplns_shares_filtered(
+        Some(1000),
+        Some(start_time),
+        Some(end_time),
+    );
+
+    // Filter shares for the specific address
+    let shares: Vec<_> = all_shares
+        .into_iter()
+        .filter(|s| s.btcaddress.as_ref().map_or(false, |addr| addr == &address))
+        .collect();
+
+    if shares.is_empty() {
+        return Json(ApiResponse::<serde_json::Value>::error(format!("No shares found for address {} in last 24 hours", address)));
+    }
+
+    // Group by worker name
+    let mut worker_stats: HashMap<String, u64> = HashMap::new();
+    let mut total_shares = 0u64;
+
+    for share in shares {
+        let worker = share.workername.clone().unwrap_or_else(|| "worker".to_string());
+        *worker_stats.entry(worker).or_insert(0) += 1;
+        total_shares += 1;
+    }
+
+    let response = serde_json::json!({
+        "address": address,
+        "total_shares": total_shares,
+        "worker_stats": worker_stats,
+    });
+
+    Json(ApiResponse::ok(response))
+}
+
+// ===== Export =====
+
+/// Format and time range of an export request, refusing ranges longer than
+/// `max_range_secs`
+fn export_params(query: &ExportQuery, max_range_secs: u64) -> Result<(ExportFormat, u64, u64)> {
+    let format = match &query.format {
+        Some(format) => format.parse()?,
+        None => ExportFormat::default(),
+    };
+    let end = query.end.unwrap_or_else(unix_now);
+    let start = query.start.unwrap_or(end.saturating_sub(24 * 3600));
+    if start > end {
+        return Err(anyhow::anyhow!("start must not be after end"));
+    }
+    if end - start > max_range_secs {
+        return Err(anyhow::anyhow!(
+            "Export range is limited to {} seconds; split it into smaller ranges",
+            max_range_secs
+        ));
+    }
+    Ok((format, start, end))
+}
+
+/// Start of each chunk of `[start, end]` read by `share_chunk`
+fn share_chunk_starts(start: u64, end: u64) -> impl Iterator<Item = u64> {
+    (start..=end).step_by(EXPORT_CHUNK_SECS as usize)
+}
+
+/// Shares of the chunk starting at `from`, up to `end`, in time order
+///
+/// Read on the blocking thread pool, as store reads block.
+async fn share_chunk(store: &Arc<Store>, from: u64, end: u64) -> Result<Vec<SimplePplnsShare>, std::io::Error> {
+    let store = store.clone();
+    let to = from.saturating_add(EXPORT_CHUNK_SECS - 1).min(end);
+    tokio::task::spawn_blocking(move || {
+        let mut shares = store.get_pplns_shares_filtered(None, Some(from), Some(to));
+        shares.sort_by_key(|share| share.n_time);
+        shares
+    })
+    .await
+    .map_err(std::io::Error::other)
+}
+
+/// Number of chunks `share_chunks` reads
+fn share_chunk_count(start: u64, end: u64) -> u64 {
+    (end - start) / EXPORT_CHUNK_SECS + 1
+}
+
+type ExportSender = tokio::sync::mpsc::Sender<Result<String, std::io::Error>>;
+
+/// A download whose body is streamed from `pieces`
+fn export_response(
+    format: ExportFormat,
+    name: &str,
+    start: u64,
+    end: u64,
+    pieces: tokio::sync::mpsc::Receiver<Result<String, std::io::Error>>,
+) -> Response {
+    let filename = format!("{}-{}-{}.{}", name, start, end, format.extension());
+    (
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        Body::from_stream(tokio_stream::wrappers::ReceiverStream::new(pieces)),
+    )
+        .into_response()
+}
+
+/// Stream an export to the client, or with `background` write it to a file as a job
+fn run_export<F, Fut>(
+    state: &AdminState,
+    claims: &Claims,
+    name: &str,
+    (format, start, end): (ExportFormat, u64, u64),
+    background: bool,
+    produce: F,
+) -> Response
+where
+    F: FnOnce(ExportSender, Arc<Progress>) -> Fut + Send + 'static,
+    Fut: std::future::Future<Output = ()> + Send + 'static,
+{
+    if !background {
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        tokio::spawn(produce(tx, Arc::new(Progress::default())));
+        return export_response(format, name, start, end, rx);
+    }
+
+    let filename = format!("{}-{}-{}.{}", name, start, end, format.extension());
+    let description = format!("Export of {} from {} to {}", name, start, end);
+    let job = state.jobs.spawn(JobKind::Export, description, &claims.name, move |job| async move {
+        use tokio::io::AsyncWriteExt;
+        let path = job.artifact_path(format.extension());
+        let mut file = tokio::fs::File::create(&path).await.context("Failed to create export file")?;
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        job.progress.set_phase("exporting");
+        tokio::spawn(produce(tx, job.progress.clone()));
+        while let Some(piece) = rx.recv().await {
+            let piece = piece?;
+            file.write_all(piece.as_bytes()).await.context("Failed to write export file")?;
+            job.progress.add_bytes(piece.len() as u64);
+        }
+        file.flush().await.context("Failed to write export file")?;
+        job.progress.check_cancelled()?;
+        Ok(serde_json::json!({
+            "filename": filename,
+            "content_type": format.content_type(),
+            "bytes": tokio::fs::metadata(&path).await?.len(),
+            "download": format!("/api/v1/jobs/{}/download", job.id),
+        }))
+    });
+    let response = serde_json::json!({
+        "message": "Export started",
+        "job_id": job.id,
+        "job": job,
+    });
+    (StatusCode::ACCEPTED, Json(ApiResponse::ok(response))).into_response()
+}
+
+/// Every share in a time range, streamed as CSV or JSON
+#[utoipa::path(
+    get,
+    path = "/api/v1/shares/export",
+    tag = "workers",
+    params(ExportQuery),
+    responses(
+        (status = 200, description = "Streamed CSV or JSON export", content_type = "text/csv"),
+        (status = 202, description = "Standard response envelope with the started job", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn export_shares(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<ExportQuery>,
+) -> Response {
+    let (format, start, end) = match export_params(&query, state.export_max_range_secs) {
+        Ok(params) => params,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    };
+    let store = state.store.clone();
+    let address = query.address;
+    run_export(&state, &claims, "shares", (format, start, end), query.background.unwrap_or(false), move |tx, progress| async move {
+        let mut encoder = ExportEncoder::new(format, SHARE_EXPORT_COLUMNS);
+        if tx.send(Ok(encoder.header())).await.is_err() {
+            return;
+        }
+        progress.set_steps(share_chunk_count(start, end));
+        for from in share_chunk_starts(start, end) {
+            // A cancelled job stops early, and its partial file is removed
+            if progress.is_cancelled() {
+                return;
+            }
+            let shares = match share_chunk(&store, from, end).await {
+                Ok(shares) => shares,
+                Err(e) => {
+                    let _ = tx.send(Err(e)).await;
+                    return;
+                }
+            };
+            let mut chunk = String::new();
+            for share in shares {
+                if address.as_ref().is_some_and(|a| share.btcaddress.as_ref() != Some(a)) {
+                    continue;
+                }
+                let time = chrono::DateTime::from_timestamp(share.n_time as i64, 0).map(|t| t.to_rfc3339());
+                chunk.push_str(&encoder.row(&[
+                    share.n_time.into(),
+                    time.into(),
+                    share.btcaddress.into(),
+                    share.workername.into(),
+                    share.difficulty.into(),
+                    share.user_id.into(),
+                    share.job_id.into(),
+                    share.extranonce2.into(),
+                    share.nonce.into(),
+                ]));
+            }
+            progress.step();
+            // A closed channel means the client went away
+            if !chunk.is_empty() && tx.send(Ok(chunk)).await.is_err() {
+                return;
+            }
+        }
+        let _ = tx.send(Ok(encoder.footer())).await;
+    })
+}
+
+/// Per-worker totals over a time range, streamed as CSV or JSON
+#[utoipa::path(
+    get,
+    path = "/api/v1/workers/export",
+    tag = "workers",
+    params(ExportQuery),
+    responses(
+        (status = 200, description = "Streamed CSV or JSON export", content_type = "text/csv"),
+        (status = 202, description = "Standard response envelope with the started job", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn export_workers(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<ExportQuery>,
+) -> Response {
+    let (format, start, end) = match export_params(&query, state.export_max_range_secs) {
+        Ok(params) => params,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    };
+    let store = state.store.clone();
+    let banned = state.ban_manager.banned_addresses().await;
+    let address = query.address;
+    run_export(&state, &claims, "workers", (format, start, end), query.background.unwrap_or(false), move |tx, progress| async move {
+        // (shares, total difficulty, first share, last share) per address and worker
+        let mut totals: std::collections::BTreeMap<(String, String), (u64, u64, u64, u64)> = Default::default();
+        progress.set_steps(share_chunk_count(start, end));
+        for from in share_chunk_starts(start, end) {
+            if progress.is_cancelled() {
+                return;
+            }
+            let shares = match share_chunk(&store, from, end).await {
+                Ok(shares) => shares,
+                Err(e) => {
+                    let _ = tx.send(Err(e)).await;
+                    return;
+                }
+            };
+            for share in shares {
+                let Some(share_address) = share.btcaddress else {
+                    continue;
+                };
+                if address.as_ref().is_some_and(|a| a != &share_address) {
+                    continue;
+                }
+                let worker = share.workername.unwrap_or_else(|| "worker".to_string());
+                let entry = totals.entry((share_address, worker)).or_insert((0, 0, share.n_time, share.n_time));
+                entry.0 += 1;
+                entry.1 += share.difficulty;
+                entry.2 = entry.2.min(share.n_time);
+                entry.3 = entry.3.max(share.n_time);
+            }
+            progress.step();
+        }
+
+        let mut encoder = ExportEncoder::new(format, WORKER_EXPORT_COLUMNS);
+        if tx.send(Ok(encoder.header())).await.is_err() {
+            return;
+        }
+        for ((address, worker), (shares, difficulty, first, last)) in totals {
+            let is_banned = banned.contains(&address);
+            let row = encoder.row(&[
+                address.into(),
+                worker.into(),
+                shares.into(),
+                difficulty.into(),
+                hashrate_ths(difficulty as f64, end - start).into(),
+                first.into(),
+                last.into(),
+                is_banned.into(),
+            ]);
+            if tx.send(Ok(row)).await.is_err() {
+                return;
+            }
+        }
+        let _ = tx.send(Ok(encoder.footer())).await;
+    })
+}
+
+/// Ban worker
+#[utoipa::path(
+    post,
+    path = "/api/v1/workers/{address}/ban",
+    tag = "bans",
+    params(("address" = String, Path, description = "Miner BTC address")),
+    request_body = BanRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Operator role required"),
+    ),
+)]
+async fn ban_worker(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(address): Path<String>,
+    Json(req): Json<BanRequest>,
+) -> Response {
+    if let Some(denied) = require_operator(&claims) {
+        return denied;
+    }
+    let duration = req.duration_secs.map(std::time::Duration::from_secs);
+    let ban = match state.ban_manager
+        .ban(BanTarget::Address(address.clone()), req.reason.clone(), duration, &claims.name)
+        .await
+    {
+        Ok(ban) => ban,
+        Err(e) => return Json(ApiResponse::<serde_json::Value>::error(e.to_string())).into_response(),
+    };
+    info!("Banned worker: {} - reason: {:?}", address, req.reason);
+    state.outbox.publish(OutboxEvent::WorkerBanned, serde_json::json!(ban)).await;
+
+    let response = serde_json::json!({
+        "address": address,
+        "banned": true,
+        "expires_at": ban.expires_at,
+        "message": "Worker banned successfully"
+    });
+
+    Json(ApiResponse::ok(response)).into_response()
+}
+
+/// Unban worker
+#[utoipa::path(
+    post,
+    path = "/api/v1/workers/{address}/unban",
+    tag = "bans",
+    params(("address" = String, Path, description = "Miner BTC address")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Operator role required"),
+    ),
+)]
+async fn unban_worker(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(address): Path<String>,
+) -> Response {
+    if let Some(denied) = require_operator(&claims) {
+        return denied;
+    }
+    if let Err(e) = state.ban_manager.unban_target(&BanTarget::Address(address.clone())).await {
+        return Json(ApiResponse::<serde_json::Value>::error(e.to_string())).into_response();
+    }
+    info!("Unbanned worker: {}", address);
+
+    let response = serde_json::json!({
+        "address": address,
+        "banned": false,
+        "message": "Worker unbanned successfully"
+    });
+
+    Json(ApiResponse::ok(response)).into_response()
+}
+
+/// List active bans
+#[utoipa::path(
+    get,
+    path = "/api/v1/bans",
+    tag = "bans",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn list_bans(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(ApiResponse::ok(state.ban_manager.active().await))
+}
+
+/// Ban a BTC address, IP address or CIDR range
+#[utoipa::path(
+    post,
+    path = "/api/v1/bans",
+    tag = "bans",
+    request_body = CreateBanRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Operator role required"),
+    ),
+)]
+async fn create_ban(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<CreateBanRequest>,
+) -> Response {
+    if let Some(denied) = require_operator(&claims) {
+        return denied;
+    }
+    let target: BanTarget = match req.target.parse() {
+        Ok(target) => target,
+        Err(e) => return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::<()>::error(e.to_string())),
+        ).into_response(),
+    };
+    let duration = req.duration_secs.map(std::time::Duration::from_secs);
+    match state.ban_manager.ban(target, req.reason, duration, &claims.name).await {
+        Ok(ban) => {
+            state.outbox.publish(OutboxEvent::WorkerBanned, serde_json::json!(ban)).await;
+            Json(ApiResponse::ok(ban)).into_response()
+        }
+        Err(e) => Json(ApiResponse::<()>::error(e.to_string())).into_response(),
+    }
+}
+
+/// Lift a ban
+#[utoipa::path(
+    post,
+    path = "/api/v1/bans/{id}/delete",
+    tag = "bans",
+    params(("id" = String, Path, description = "Ban ID")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Operator role required"),
+    ),
+)]
+async fn delete_ban(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Response {
+    if let Some(denied) = require_operator(&claims) {
+        return denied;
+    }
+    match state.ban_manager.unban(&id).await {
+        Ok(Some(ban)) => Json(ApiResponse::ok(ban)).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<()>::error(format!("Ban not found: {}", id))),
+        ).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(e.to_string())).into_response(),
+    }
+}
+
+/// Add tag to worker
+#[derive(Deserialize, ToSchema)]
+struct AddTagRequest {
+    tag: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/workers/{address}/tags",
+    tag = "workers",
+    params(("address" = String, Path, description = "Miner BTC address")),
+    request_body = AddTagRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Operator role required"),
+    ),
+)]
+async fn add_worker_tag(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(address): Path<String>,
+    Json(req): Json<AddTagRequest>,
+) -> Response {
+    if let Some(denied) = require_operator(&claims) {
+        return denied;
+    }
+    let mut worker_tags = state.worker_tags.write().await;
+    let tags = worker_tags.entry(address.clone()).or_insert_with(Vec::new);
+
+    if !tags.contains(&req.tag) {
+        tags.push(req.tag.clone());
+        info!("Added tag '{}' to worker: {}", req.tag, address);
+    }
+
+    let response = serde_json::json!({
+        "address": address,
+        "tag": req.tag,
+        "tags": tags.clone(),
+        "message": "Tag added successfully"
+    });
+
+    Json(ApiResponse::ok(response)).into_response()
+}
+
+/// Remove tag from worker
+#[utoipa::path(
+    post,
+    path = "/api/v1/workers/{address}/tags/{tag}",
+    tag = "workers",
+    params(("address" = String, Path, description = "Miner BTC address"), ("tag" = String, Path, description = "Tag to remove")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Operator role required"),
+    ),
+)]
+async fn remove_worker_tag(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path((address, tag)): Path<(String, String)>,
+) -> Response {
+    if let Some(denied) = require_operator(&claims) {
+        return denied;
+    }
+    let mut worker_tags = state.worker_tags.write().await;
+
+    if let Some(tags) = worker_tags.get_mut(&address) {
+        let original_len = tags.len();
+        tags.retain(|t| t != &tag);
+        if tags.len() < original_len {
+            info!("Removed tag '{}' from worker: {}", tag, address);
+        }
+    }
+
+    let current_tags = worker_tags.get(&address).cloned().unwrap_or_default();
+
+    let response = serde_json::json!({
+        "address": address,
+        "tag": tag,
+        "tags": current_tags,
+        "message": "Tag removed successfully"
+    });
+
+    Json(ApiResponse::ok(response)).into_response()
+}
+
+#[derive(Deserialize, ToSchema)]
+struct WorkerWatchRequest {
+    worker: String,
+    /// `false` opts the worker out of down alerts and notifications
+    #[serde(default = "default_true")]
+    enabled: bool,
+    /// Minutes of mining before the worker is watched; the default if unset
+    min_active_minutes: Option<u64>,
+    /// Minutes without shares before the worker is down; the default if unset
+    idle_minutes: Option<u64>,
+}
+
+/// Down detection defaults and the address's per-worker settings
+async fn worker_watch_response(state: &AdminState, address: &str) -> Response {
+    Json(ApiResponse::ok(serde_json::json!({
+        "defaults": state.worker_watch.defaults(),
+        "workers": state.worker_watch.settings(address).await,
+    }))).into_response()
+}
+
+async fn update_worker_watch(state: &AdminState, address: String, req: WorkerWatchRequest) -> Response {
+    let settings = WorkerWatchSettings {
+        address,
+        worker: req.worker,
+        enabled: req.enabled,
+        min_active_minutes: req.min_active_minutes,
+        idle_minutes: req.idle_minutes,
+    };
+    match state.worker_watch.set(settings.clone()).await {
+        Ok(()) => Json(ApiResponse::ok(settings)).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    }
+}
+
+/// Down detection settings of an address's workers
+#[utoipa::path(
+    get,
+    path = "/api/v1/workers/{address}/watch",
+    tag = "workers",
+    params(("address" = String, Path, description = "Miner BTC address")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn worker_watch_settings(
+    State(state): State<AdminState>,
+    Path(address): Path<String>,
+) -> Response {
+    worker_watch_response(&state, &address).await
+}
+
+/// Opt a worker out of down detection or change its thresholds
+#[utoipa::path(
+    post,
+    path = "/api/v1/workers/{address}/watch",
+    tag = "workers",
+    params(("address" = String, Path, description = "Miner BTC address")),
+    request_body = WorkerWatchRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 400, description = "Invalid threshold"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Operator role required"),
+    ),
+)]
+async fn set_worker_watch(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(address): Path<String>,
+    Json(req): Json<WorkerWatchRequest>,
+) -> Response {
+    if let Some(denied) = require_operator(&claims) {
+        return denied;
+    }
+    update_worker_watch(&state, address, req).await
+}
+
+/// Get blocks found by the pool, newest first (with pagination)
+#[utoipa::path(
+    get,
+    path = "/api/v1/blocks",
+    tag = "blocks",
+    params(PaginationRequest),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn blocks_list(
+    State(state): State<AdminState>,
+    Query(params): Query<PaginationRequest>,
+) -> impl IntoResponse {
+    let page = params.page.unwrap_or(1).max(1);
+    let page_size = params.page_size.unwrap_or(20).clamp(1, 100);
+
+    let blocks = state.block_tracker.blocks().await;
+    let total = blocks.len();
+    let data: Vec<FoundBlock> = blocks
+        .into_iter()
+        .skip((page - 1) * page_size)
+        .take(page_size)
+        .collect();
+
+    Json(ApiResponse::ok(PaginatedResponse {
+        data,
+        total,
+        page,
+        page_size,
+        total_pages: total.div_ceil(page_size),
+    }))
+}
+
+/// Unpaid miner balances
+#[utoipa::path(
+    get,
+    path = "/api/v1/payouts/pending",
+    tag = "payouts",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn payout_pending(State(state): State<AdminState>) -> impl IntoResponse {
+    let balances = state.payout_engine.pending_balances().await;
+    Json(ApiResponse::ok(serde_json::json!({
+        "min_payout_sats": state.payout_engine.config().min_payout_sats,
+        "total_sats": balances.iter().map(|b| b.balance_sats).sum::<u64>(),
+        "balances": balances,
+    })))
+}
+
+/// Payout batches, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/payouts",
+    tag = "payouts",
+    params(PaginationRequest),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn payout_history(
+    State(state): State<AdminState>,
+    Query(params): Query<PaginationRequest>,
+) -> impl IntoResponse {
+    let page = params.page.unwrap_or(1).max(1);
+    let page_size = params.page_size.unwrap_or(20).clamp(1, 100);
+
+    let batches = state.payout_engine.batches().await;
+    let total = batches.len();
+    let data: Vec<PayoutBatch> = batches
+        .into_iter()
+        .skip((page - 1) * page_size)
+        .take(page_size)
+        .collect();
+
+    Json(ApiResponse::ok(PaginatedResponse {
+        data,
+        total,
+        page,
+        page_size,
+        total_pages: total.div_ceil(page_size),
+    }))
+}
+
+/// Get a payout batch
+#[utoipa::path(
+    get,
+    path = "/api/v1/payouts/{id}",
+    tag = "payouts",
+    params(("id" = String, Path, description = "Payout batch ID")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn payout_detail(
+    State(state): State<AdminState>,
+    Path(id): Path<String>,
+) -> Response {
+    match state.payout_engine.batch(&id).await {
+        Some(batch) => Json(ApiResponse::ok(batch)).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<()>::error(format!("Payout batch not found: {}", id))),
+        ).into_response(),
+    }
+}
+
+/// Blocks whose rewards were credited to miners
+#[utoipa::path(
+    get,
+    path = "/api/v1/payouts/blocks",
+    tag = "payouts",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn payout_blocks(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(ApiResponse::ok(state.payout_engine.credited_blocks().await))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct MempoolQuery {
+    /// Snapshots to return, newest first; defaults to 48
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct MempoolView {
+    latest: Option<FeeSnapshot>,
+    /// Latest payout fee rate against the median of the history
+    spike_ratio: Option<f64>,
+    /// Fee of adding one payment to a payout batch at the latest rate
+    payment_fee_sats: Option<u64>,
+    /// Smaller balances are deferred until fees drop; unset without PAYOUT_MAX_FEE_BPS
+    min_economical_payout_sats: Option<u64>,
+    history: Vec<FeeSnapshot>,
+}
+
+/// Node fee estimates and mempool size, and what they mean for payouts
+#[utoipa::path(
+    get,
+    path = "/api/v1/mempool",
+    tag = "payouts",
+    params(MempoolQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn mempool_status(
+    State(state): State<AdminState>,
+    Query(query): Query<MempoolQuery>,
+) -> impl IntoResponse {
+    let latest = state.fee_monitor.latest();
+    let payment_fee_sats = latest.as_ref().and_then(FeeSnapshot::payment_fee_sats);
+    Json(ApiResponse::ok(MempoolView {
+        spike_ratio: state.fee_monitor.spike_ratio(),
+        payment_fee_sats,
+        min_economical_payout_sats: payment_fee_sats.and_then(|fee| state.payout_engine.min_economical_payment(fee)),
+        history: state.fee_monitor.history(query.limit.unwrap_or(FEE_HISTORY_DEFAULT_LIMIT)),
+        latest,
+    }))
+}
+
+/// Pool fee and donation revenue over a range, with the payouts sent in it
+#[utoipa::path(
+    get,
+    path = "/api/v1/fees/report",
+    tag = "payouts",
+    params(FeeReportQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 400, description = "Unknown range"),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn fee_report(
+    State(state): State<AdminState>,
+    Query(query): Query<FeeReportQuery>,
+) -> impl IntoResponse {
+    let range = match query.range.as_deref().unwrap_or("month").parse::<FeeRange>() {
+        Ok(range) => range,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    };
+    let batches = state.payout_engine.batches().await;
+    Json(ApiResponse::ok(state.fee_ledger.report(range, Utc::now(), &batches).await)).into_response()
+}
+
+/// Fee and donation withheld from each credited block, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/fees/blocks",
+    tag = "payouts",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn fee_blocks(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(ApiResponse::ok(state.fee_ledger.records().await))
+}
+
+/// Create a payout batch now instead of waiting for the schedule (admin only)
+#[utoipa::path(
+    post,
+    path = "/api/v1/payouts/schedule",
+    tag = "payouts",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn schedule_payouts(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let payment_fee = state.fee_monitor.latest().and_then(|f| f.payment_fee_sats());
+    match state.payout_engine.schedule_batch(payment_fee).await {
+        Ok(batch) => Json(ApiResponse::ok(batch)).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(e.to_string())).into_response(),
+    }
+}
+
+/// Record a batch as paid outside the admin server (admin only)
+#[utoipa::path(
+    post,
+    path = "/api/v1/payouts/{id}/paid",
+    tag = "payouts",
+    params(("id" = String, Path, description = "Payout batch ID")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn mark_payout_paid(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    match state.payout_engine.mark_paid(&id).await {
+        Ok(batch) => Json(ApiResponse::ok(batch)).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    }
+}
+
+/// Cancel an unsent or stuck batch, returning its amounts to miner balances (admin only)
+#[utoipa::path(
+    post,
+    path = "/api/v1/payouts/{id}/cancel",
+    tag = "payouts",
+    params(("id" = String, Path, description = "Payout batch ID")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn cancel_payout(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    match state.payout_engine.cancel_batch(&id).await {
+        Ok(batch) => Json(ApiResponse::ok(batch)).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct BroadcastPayoutRequest {
+    /// Signed PSBT, base64
+    psbt: String,
+}
+
+/// Broadcast the externally signed PSBT of a batch (admin only)
+#[utoipa::path(
+    post,
+    path = "/api/v1/payouts/{id}/broadcast",
+    tag = "payouts",
+    params(("id" = String, Path, description = "Payout batch ID")),
+    request_body = BroadcastPayoutRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn broadcast_payout(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Json(req): Json<BroadcastPayoutRequest>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let result = match bitcoin_rpc(&state).await {
+        Ok(rpc) => state.payout_wallet.broadcast_signed(&state.payout_engine, Arc::new(rpc), &id, req.psbt.trim()).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(batch) => Json(ApiResponse::ok(batch)).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    }
+}
+
+// ===== Public Stats =====
+
+/// Stats for one miner address: hashrate, window shares, expected payout and payments
+#[utoipa::path(
+    get,
+    path = "/api/v1/miners/{address}",
+    tag = "public",
+    params(("address" = String, Path, description = "Miner BTC address")),
+    responses((status = 200, description = "Standard response envelope", body = ApiEnvelope)),
+    security(()),
+)]
+async fn public_miner_stats(
+    State(state): State<AdminState>,
+    Path(address): Path<String>,
+) -> Response {
+    if address.is_empty() || address.len() > 100 || !address.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error("Invalid address".to_string()))).into_response();
+    }
+    Json(ApiResponse::ok(miner_stats(&state, address).await)).into_response()
+}
+
+/// Hashrate, window shares, expected payout and payments of an address
+async fn miner_stats(state: &AdminState, address: String) -> MinerStats {
+    let reward_sats = state.block_tracker.blocks().await
+        .into_iter()
+        .find(|b| !b.orphaned)
+        .map(|b| b.reward_sats)
+        .unwrap_or(DEFAULT_PREVIEW_REWARD_SATS);
+    let (shares, preview) = cached_preview(state, reward_sats).await;
+    let estimate = preview.payouts.iter().find(|p| p.address == address);
+
+    // Hashrate per worker over the recent part of the window
+    let since = unix_now().saturating_sub(MINER_HASHRATE_WINDOW_SECS);
+    let mut by_worker: HashMap<String, Vec<&SimplePplnsShare>> = HashMap::new();
+    for share in shares.iter().filter(|s| s.n_time >= since && s.btcaddress.as_deref() == Some(address.as_str())) {
+        let worker = share.workername.clone().unwrap_or_else(|| "worker".to_string());
+        by_worker.entry(worker).or_default().push(share);
+    }
+    let mut workers: Vec<MinerWorkerStats> = by_worker
+        .into_iter()
+        .map(|(name, shares)| MinerWorkerStats {
+            hashrate_ths: ShareActivity::from_shares(&shares, MINER_HASHRATE_WINDOW_SECS).hashrate_ths,
+            shares: shares.len() as u64,
+            last_share_time: shares.iter().map(|s| s.n_time).max().unwrap_or(0),
+            name,
+        })
+        .collect();
+    workers.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let window_difficulty = estimate.map_or(0, |p| p.total_difficulty);
+    let pending_balance_sats = state.payout_engine.pending_balances().await
+        .into_iter()
+        .find(|b| b.address == address)
+        .map_or(0, |b| b.balance_sats);
+    let recent_payments = state.payout_engine.batches().await
+        .into_iter()
+        .filter(|batch| batch.status != PayoutStatus::Cancelled)
+        .filter_map(|batch| {
+            let payment = batch.payments.iter().find(|p| p.address == address)?;
+            Some(MinerPayment {
+                amount_sats: payment.amount_sats,
+                batch_id: batch.id,
+                created_at: batch.created_at,
+                status: batch.status,
+                txid: batch.txid,
+            })
+        })
+        .take(PUBLIC_RECENT_ITEMS)
+        .collect();
+    let display_name = state.miner_settings.get(&address).await.and_then(|r| r.settings.display_name);
+
+    MinerStats {
+        display_name,
+        hashrate_ths: workers.iter().map(|w| w.hashrate_ths).sum(),
+        hashrate_window_secs: MINER_HASHRATE_WINDOW_SECS,
+        workers,
+        window_shares: estimate.map_or(0, |p| p.share_count),
+        window_difficulty,
+        window_share_percent: if preview.window_difficulty == 0 {
+            0.0
+        } else {
+            window_difficulty as f64 * 100.0 / preview.window_difficulty as f64
+        },
+        estimated_next_payout_sats: estimate.map_or(0, |p| p.final_payout_satoshis),
+        pending_balance_sats,
+        recent_payments,
+        address,
+    }
+}
+
+// ===== Miner Self-Service =====
+
+/// Request a message to sign for a miner token
+#[derive(Deserialize, ToSchema)]
+struct MinerChallengeRequest {
+    address: String,
+}
+
+/// Signed challenge exchanged for a miner token
+#[derive(Deserialize, ToSchema)]
+struct MinerTokenRequest {
+    address: String,
+    /// Base64 `signmessage` signature of the challenge message
+    signature: String,
+    /// Defaults to every miner scope
+    scopes: Option<Vec<String>>,
+    ttl_days: Option<i64>,
+}
+
+/// Admin-issued miner token
+#[derive(Deserialize, ToSchema)]
+struct CreateMinerTokenRequest {
+    address: String,
+    scopes: Option<Vec<String>>,
+    ttl_days: Option<i64>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct MinerTokenQuery {
+    /// Only tokens of this address
+    address: Option<String>,
+}
+
+/// Payout threshold change; `null` returns to the pool's minimum payout
+#[derive(Deserialize, ToSchema)]
+struct PayoutThresholdRequest {
+    threshold_sats: Option<u64>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct CreateWebhookRequest {
+    /// http or https URL receiving a signed JSON POST per event
+    url: String,
+    events: Vec<OutboxEvent>,
+    /// Signing key of at least 16 characters; generated when omitted
+    secret: Option<String>,
+}
+
+/// Final confirmation of a pool restart
+#[derive(Deserialize, ToSchema)]
+struct RestartRequest {
+    /// Must be true to restart; otherwise the restart plan is returned
+    #[serde(default)]
+    confirm: bool,
+    /// Back up the pool data before restarting; defaults to true
+    #[serde(default = "default_true")]
+    backup: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DeliveryQuery {
+    /// Only deliveries to this webhook
+    webhook_id: Option<String>,
+    status: Option<DeliveryStatus>,
+    /// Defaults to 100
+    limit: Option<usize>,
+}
+
+/// Every setting of an address; omitted ones are cleared
+#[derive(Deserialize, ToSchema)]
+struct MinerSettingsRequest {
+    /// At most 32 characters, shown instead of the address on public pages
+    display_name: Option<String>,
+    notify_email: Option<String>,
+    /// https URL notified of every miner event
+    notify_webhook: Option<String>,
+    /// `null` returns to the pool's minimum payout
+    payout_threshold_sats: Option<u64>,
+}
+
+impl MinerSettingsRequest {
+    fn settings(&self) -> MinerSettings {
+        MinerSettings {
+            display_name: self.display_name.clone(),
+            notify_email: self.notify_email.clone(),
+            notify_webhook: self.notify_webhook.clone(),
+        }
+    }
+}
+
+/// Settings change signed for with a challenge instead of a miner token
+#[derive(Deserialize, ToSchema)]
+struct SignedMinerSettingsRequest {
+    address: String,
+    /// Base64 `signmessage` signature of the challenge message
+    signature: String,
+    #[serde(flatten)]
+    settings: MinerSettingsRequest,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct MinerWebhookRequest {
+    /// https URL receiving a JSON POST per event
+    url: String,
+    events: Vec<MinerEvent>,
+}
+
+/// Address of a miner token, or a 403 for other tokens
+fn miner_address(claims: &Claims) -> Result<String, Response> {
+    if claims.role == auth::MINER_ROLE {
+        return Ok(claims.name.clone());
+    }
+    Err((StatusCode::FORBIDDEN, Json(ApiResponse::<()>::error("Miner token required"))).into_response())
+}
+
+/// Validate a miner token request's address, scopes and lifetime
+async fn miner_token_params(
+    state: &AdminState,
+    address: &str,
+    scopes: Option<Vec<String>>,
+    ttl_days: Option<i64>,
+) -> Result<(bitcoin::Address, Vec<String>, i64), Response> {
+    let network = state.config.read().await.stratum.network;
+    let address = miner_access::parse_address(address, network)
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response())?;
+    let scopes = scopes.unwrap_or_else(|| auth::MINER_SCOPES.iter().map(|s| s.to_string()).collect());
+    let ttl_secs = ttl_days.map_or(auth::MINER_TOKEN_TTL_SECS, |days| days.saturating_mul(86400));
+    Ok((address, scopes, ttl_secs))
+}
+
+fn miner_token_response(result: Result<(String, auth::MinerTokenInfo)>) -> Response {
+    match result {
+        Ok((token, info)) => Json(ApiResponse::ok(serde_json::json!({
+            "token": token,
+            "token_info": info,
+        }))).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    }
+}
+
+/// Message a miner signs with their address's key to get a miner token
+#[utoipa::path(
+    post,
+    path = "/api/v1/miner/challenge",
+    tag = "miner",
+    request_body = MinerChallengeRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 400, description = "Invalid address"),
+        (status = 429, description = "Too many outstanding challenges"),
+    ),
+    security(()),
+)]
+async fn miner_challenge(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+    Json(req): Json<MinerChallengeRequest>,
+) -> Response {
+    let network = state.config.read().await.stratum.network;
+    if let Err(e) = miner_access::parse_address(&req.address, network) {
+        return (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response();
+    }
+    match state.miner_challenges.issue(&req.address, &client_ip(&state, &headers)).await {
+        Ok(challenge) => Json(ApiResponse::ok(challenge)).into_response(),
+        Err(e) => (StatusCode::TOO_MANY_REQUESTS, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    }
+}
+
+/// Exchange a signed challenge for a miner token
+#[utoipa::path(
+    post,
+    path = "/api/v1/miner/token",
+    tag = "miner",
+    request_body = MinerTokenRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 400, description = "Invalid address, scopes or lifetime"),
+        (status = 401, description = "Missing challenge or invalid signature"),
+    ),
+    security(()),
+)]
+async fn miner_token(
+    State(state): State<AdminState>,
+    Json(req): Json<MinerTokenRequest>,
+) -> Response {
+    let (address, scopes, ttl_secs) = match miner_token_params(&state, &req.address, req.scopes, req.ttl_days).await {
+        Ok(params) => params,
+        Err(response) => return response,
+    };
+    if let Err(e) = state.miner_challenges.verify(&address, &req.signature).await {
+        warn!("Miner token refused for {}: {:#}", req.address, e);
+        return (StatusCode::UNAUTHORIZED, Json(ApiResponse::<()>::error(e.to_string()))).into_response();
+    }
+    miner_token_response(state.auth_manager.issue_miner_token(&req.address, &scopes, ttl_secs, None).await)
+}
+
+/// Unexpired miner tokens
+#[utoipa::path(
+    get,
+    path = "/api/v1/miner-tokens",
+    tag = "miner",
+    params(MinerTokenQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn list_miner_tokens(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<MinerTokenQuery>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    Json(ApiResponse::ok(state.auth_manager.miner_tokens(query.address.as_deref()).await)).into_response()
+}
+
+/// Issue a miner token without a signed message
+#[utoipa::path(
+    post,
+    path = "/api/v1/miner-tokens",
+    tag = "miner",
+    request_body = CreateMinerTokenRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 400, description = "Invalid address, scopes or lifetime"),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn create_miner_token(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<CreateMinerTokenRequest>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let (_, scopes, ttl_secs) = match miner_token_params(&state, &req.address, req.scopes, req.ttl_days).await {
+        Ok(params) => params,
+        Err(response) => return response,
+    };
+    miner_token_response(
+        state.auth_manager.issue_miner_token(&req.address, &scopes, ttl_secs, Some(&claims.name)).await,
+    )
+}
+
+/// Revoke a miner token
+#[utoipa::path(
+    post,
+    path = "/api/v1/miner-tokens/{id}/revoke",
+    tag = "miner",
+    params(("id" = String, Path, description = "Token ID")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "Unknown token"),
+    ),
+)]
+async fn revoke_miner_token(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    if !state.auth_manager.revoke_miner_token(&id).await {
+        return (StatusCode::NOT_FOUND, Json(ApiResponse::<()>::error("Miner token not found"))).into_response();
+    }
+    Json(ApiResponse::ok(serde_json::json!({ "id": id }))).into_response()
+}
+
+/// Stats of the token's address
+#[utoipa::path(
+    get,
+    path = "/api/v1/miner/stats",
+    tag = "miner",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Not a miner token, or no miner:read scope"),
+    ),
+)]
+async fn own_miner_stats(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    match miner_address(&claims) {
+        Ok(address) => Json(ApiResponse::ok(miner_stats(&state, address).await)).into_response(),
+        Err(denied) => denied,
+    }
+}
+
+/// Payout threshold of the token's address
+#[utoipa::path(
+    get,
+    path = "/api/v1/miner/payout-threshold",
+    tag = "miner",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Not a miner token, or no miner:payout scope"),
+    ),
+)]
+async fn miner_payout_threshold(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    let address = match miner_address(&claims) {
+        Ok(address) => address,
+        Err(denied) => return denied,
+    };
+    Json(ApiResponse::ok(serde_json::json!({
+        "threshold_sats": state.payout_engine.payout_threshold(&address).await,
+        "min_payout_sats": state.payout_engine.config().min_payout_sats,
+        "max_threshold_sats": dmpool::payout::MAX_PAYOUT_THRESHOLD_SATS,
+        "address": address,
+    }))).into_response()
+}
+
+/// Set the payout threshold of the token's address
+#[utoipa::path(
+    post,
+    path = "/api/v1/miner/payout-threshold",
+    tag = "miner",
+    request_body = PayoutThresholdRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 400, description = "Threshold out of range"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Not a miner token, or no miner:payout scope"),
+    ),
+)]
+async fn set_miner_payout_threshold(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<PayoutThresholdRequest>,
+) -> Response {
+    let address = match miner_address(&claims) {
+        Ok(address) => address,
+        Err(denied) => return denied,
+    };
+    match state.payout_engine.set_payout_threshold(&address, req.threshold_sats).await {
+        Ok(threshold) => Json(ApiResponse::ok(serde_json::json!({
+            "threshold_sats": threshold,
+            "address": address,
+        }))).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    }
+}
+
+/// Webhooks of the token's address
+#[utoipa::path(
+    get,
+    path = "/api/v1/miner/webhooks",
+    tag = "miner",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Not a miner token, or no miner:webhooks scope"),
+    ),
+)]
+async fn list_miner_webhooks(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    match miner_address(&claims) {
+        Ok(address) => Json(ApiResponse::ok(state.miner_webhooks.list(&address).await)).into_response(),
+        Err(denied) => denied,
+    }
+}
+
+/// Register a webhook notified of the address's credits and payouts
+#[utoipa::path(
+    post,
+    path = "/api/v1/miner/webhooks",
+    tag = "miner",
+    request_body = MinerWebhookRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 400, description = "Invalid URL or events, or too many webhooks"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Not a miner token, or no miner:webhooks scope"),
+    ),
+)]
+async fn register_miner_webhook(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<MinerWebhookRequest>,
+) -> Response {
+    let address = match miner_address(&claims) {
+        Ok(address) => address,
+        Err(denied) => return denied,
+    };
+    match state.miner_webhooks.register(&address, &req.url, &req.events).await {
+        Ok(hook) => Json(ApiResponse::ok(hook)).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    }
+}
+
+/// Delete one of the address's webhooks
+#[utoipa::path(
+    post,
+    path = "/api/v1/miner/webhooks/{id}/delete",
+    tag = "miner",
+    params(("id" = String, Path, description = "Webhook ID")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Not a miner token, or no miner:webhooks scope"),
+        (status = 404, description = "Unknown webhook"),
+    ),
+)]
+async fn delete_miner_webhook(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Response {
+    let address = match miner_address(&claims) {
+        Ok(address) => address,
+        Err(denied) => return denied,
+    };
+    match state.miner_webhooks.remove(&address, &id).await {
+        Ok(true) => Json(ApiResponse::ok(serde_json::json!({ "id": id }))).into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, Json(ApiResponse::<()>::error("Webhook not found"))).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    }
+}
+
+/// Down detection settings of the token's workers
+#[utoipa::path(
+    get,
+    path = "/api/v1/miner/worker-watch",
+    tag = "miner",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Not a miner token, or no miner:webhooks scope"),
+    ),
+)]
+async fn own_worker_watch_settings(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    match miner_address(&claims) {
+        Ok(address) => worker_watch_response(&state, &address).await,
+        Err(denied) => denied,
+    }
+}
+
+/// Opt one of the token's workers out of down notifications or change its thresholds
+#[utoipa::path(
+    post,
+    path = "/api/v1/miner/worker-watch",
+    tag = "miner",
+    request_body = WorkerWatchRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 400, description = "Invalid threshold"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Not a miner token, or no miner:webhooks scope"),
+    ),
+)]
+async fn set_own_worker_watch(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<WorkerWatchRequest>,
+) -> Response {
+    match miner_address(&claims) {
+        Ok(address) => update_worker_watch(&state, address, req).await,
+        Err(denied) => denied,
+    }
+}
+
+/// Settings of an address, with the payout threshold kept by the payout engine
+async fn miner_settings_view(state: &AdminState, address: &str) -> serde_json::Value {
+    let record = state.miner_settings.get(address).await;
+    let settings = record.as_ref().map(|r| r.settings.clone()).unwrap_or_default();
+    serde_json::json!({
+        "address": address,
+        "display_name": settings.display_name,
+        "notify_email": settings.notify_email,
+        "notify_webhook": settings.notify_webhook,
+        "payout_threshold_sats": state.payout_engine.payout_threshold(address).await,
+        "min_payout_sats": state.payout_engine.config().min_payout_sats,
+        "updated_at": record.as_ref().map(|r| r.updated_at),
+        "updated_by": record.map(|r| r.updated_by),
+    })
+}
+
+/// Replace every setting of an address; nothing changes if one is invalid
+async fn apply_miner_settings(state: &AdminState, address: &str, req: &MinerSettingsRequest, updated_by: &str) -> Response {
+    let settings = req.settings();
+    if let Err(e) = settings.validate() {
+        return (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response();
+    }
+    if let Err(e) = state.payout_engine.set_payout_threshold(address, req.payout_threshold_sats).await {
+        return (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response();
+    }
+    if let Err(e) = state.miner_settings.set(address, settings, updated_by).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::<()>::error(e.to_string()))).into_response();
+    }
+    Json(ApiResponse::ok(miner_settings_view(state, address).await)).into_response()
+}
+
+/// Settings of the token's address
+#[utoipa::path(
+    get,
+    path = "/api/v1/miner/settings",
+    tag = "miner",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Not a miner token, or no miner:settings scope"),
+    ),
+)]
+async fn own_miner_settings(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    match miner_address(&claims) {
+        Ok(address) => Json(ApiResponse::ok(miner_settings_view(&state, &address).await)).into_response(),
+        Err(denied) => denied,
+    }
+}
+
+/// Replace the settings of the token's address
+///
+/// Changing the payout threshold also needs the `miner:payout` scope.
+#[utoipa::path(
+    post,
+    path = "/api/v1/miner/settings",
+    tag = "miner",
+    request_body = MinerSettingsRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 400, description = "Invalid setting"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Not a miner token, or missing scope"),
+    ),
+)]
+async fn set_own_miner_settings(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<MinerSettingsRequest>,
+) -> Response {
+    let address = match miner_address(&claims) {
+        Ok(address) => address,
+        Err(denied) => return denied,
+    };
+    let threshold = req.payout_threshold_sats.unwrap_or(state.payout_engine.config().min_payout_sats);
+    if threshold != state.payout_engine.payout_threshold(&address).await && !claims.has_scope(auth::SCOPE_MINER_PAYOUT) {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(ApiResponse::<()>::error("Changing the payout threshold needs the miner:payout scope")),
+        ).into_response();
+    }
+    apply_miner_settings(&state, &address, &req, &address).await
+}
+
+/// Replace an address's settings with a signed challenge, without a miner token
+///
+/// The challenge comes from `/api/v1/miner/challenge` and is used up.
+#[utoipa::path(
+    post,
+    path = "/api/v1/miner/settings/signed",
+    tag = "miner",
+    request_body = SignedMinerSettingsRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 400, description = "Invalid address or setting"),
+        (status = 401, description = "Missing challenge or invalid signature"),
+    ),
+    security(()),
+)]
+async fn set_signed_miner_settings(
+    State(state): State<AdminState>,
+    Json(req): Json<SignedMinerSettingsRequest>,
+) -> Response {
+    let network = state.config.read().await.stratum.network;
+    let address = match miner_access::parse_address(&req.address, network) {
+        Ok(address) => address,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    };
+    if let Err(e) = state.miner_challenges.verify(&address, &req.signature).await {
+        warn!("Settings change refused for {}: {:#}", req.address, e);
+        return (StatusCode::UNAUTHORIZED, Json(ApiResponse::<()>::error(e.to_string()))).into_response();
+    }
+    apply_miner_settings(&state, &req.address, &req.settings, &req.address).await
+}
+
+/// Every address with settings
+#[utoipa::path(
+    get,
+    path = "/api/v1/miner-settings",
+    tag = "miner",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+    ),
+)]
+async fn list_miner_settings(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    Json(ApiResponse::ok(state.miner_settings.list().await)).into_response()
+}
+
+/// Settings of any address
+#[utoipa::path(
+    get,
+    path = "/api/v1/miner-settings/{address}",
+    tag = "miner",
+    params(("address" = String, Path, description = "BTC address")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+    ),
+)]
+async fn get_miner_settings(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(address): Path<String>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    Json(ApiResponse::ok(miner_settings_view(&state, &address).await)).into_response()
+}
+
+/// Replace the settings of any address
+#[utoipa::path(
+    post,
+    path = "/api/v1/miner-settings/{address}",
+    tag = "miner",
+    params(("address" = String, Path, description = "BTC address")),
+    request_body = MinerSettingsRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 400, description = "Invalid address or setting"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+    ),
+)]
+async fn set_miner_settings(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(address): Path<String>,
+    Json(req): Json<MinerSettingsRequest>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let network = state.config.read().await.stratum.network;
+    if let Err(e) = miner_access::parse_address(&address, network) {
+        return (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response();
+    }
+    apply_miner_settings(&state, &address, &req, &claims.name).await
+}
+
+/// Clear an address's settings and reset its payout threshold
+#[utoipa::path(
+    post,
+    path = "/api/v1/miner-settings/{address}/delete",
+    tag = "miner",
+    params(("address" = String, Path, description = "BTC address")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+    ),
+)]
+async fn delete_miner_settings(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(address): Path<String>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let result = match state.miner_settings.remove(&address).await {
+        Ok(_) => state.payout_engine.set_payout_threshold(&address, None).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(_) => Json(ApiResponse::ok(miner_settings_view(&state, &address).await)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ReportSubscriptionRequest {
+    email: String,
+    /// Reports to receive: `daily`, `weekly` or both
+    periods: Vec<ReportPeriod>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct SendReportRequest {
+    period: ReportPeriod,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ReportPreviewQuery {
+    /// `daily` (default) or `weekly`
+    period: Option<ReportPeriod>,
+    /// `json` (default), `text` or `html`
+    format: Option<String>,
+}
+
+/// The caller's report subscription, `null` if they have none
+#[utoipa::path(
+    get,
+    path = "/api/v1/reports/subscription",
+    tag = "reports",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn own_report_subscription(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    Json(ApiResponse::ok(state.report_subscriptions.get(&claims.name).await))
+}
+
+/// Subscribe the caller to daily and/or weekly summary emails
+#[utoipa::path(
+    post,
+    path = "/api/v1/reports/subscription",
+    tag = "reports",
+    request_body = ReportSubscriptionRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 400, description = "Invalid email address or no reports chosen"),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn set_report_subscription(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<ReportSubscriptionRequest>,
+) -> Response {
+    match state.report_subscriptions.set(&claims.name, &req.email, req.periods).await {
+        Ok(subscription) => Json(ApiResponse::ok(subscription)).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    }
+}
+
+/// Stop the caller's summary emails
+#[utoipa::path(
+    post,
+    path = "/api/v1/reports/subscription/delete",
+    tag = "reports",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn delete_report_subscription(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    match state.report_subscriptions.remove(&claims.name).await {
+        Ok(removed) => Json(ApiResponse::ok(serde_json::json!({ "removed": removed }))).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    }
+}
+
+/// Every operator's report subscription
+#[utoipa::path(
+    get,
+    path = "/api/v1/reports/subscriptions",
+    tag = "reports",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+    ),
+)]
+async fn list_report_subscriptions(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    Json(ApiResponse::ok(state.report_subscriptions.list().await)).into_response()
+}
+
+/// The report of the period ending now, as data or rendered like the email
+#[utoipa::path(
+    get,
+    path = "/api/v1/reports/preview",
+    tag = "reports",
+    params(ReportPreviewQuery),
+    responses(
+        (status = 200, description = "Standard response envelope, or the rendered text or HTML", body = ApiEnvelope),
+        (status = 400, description = "Unknown format"),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn preview_report(
+    State(state): State<AdminState>,
+    Query(query): Query<ReportPreviewQuery>,
+) -> Response {
+    let period = query.period.unwrap_or(ReportPeriod::Daily);
+    let report = build_report(&state, period, Utc::now()).await;
+    match query.format.as_deref().unwrap_or("json") {
+        "json" => Json(ApiResponse::ok(serde_json::json!({
+            "subject": report.subject(),
+            "report": report,
+        })))
+        .into_response(),
+        "text" => ([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], report.render_text()).into_response(),
+        "html" => Html(report.render_html()).into_response(),
+        other => (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(format!("Unknown format: {}", other)))).into_response(),
+    }
+}
+
+/// Email the report of the period ending now to the caller's subscription address
+#[utoipa::path(
+    post,
+    path = "/api/v1/reports/send",
+    tag = "reports",
+    request_body = SendReportRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 400, description = "Caller has no report subscription"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 502, description = "Sending the email failed"),
+    ),
+)]
+async fn send_report_now(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<SendReportRequest>,
+) -> Response {
+    let Some(subscription) = state.report_subscriptions.get(&claims.name).await else {
+        return (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error("Subscribe to reports first"))).into_response();
+    };
+    let report = build_report(&state, req.period, Utc::now()).await;
+    match send_report(&state, &report, &subscription.email).await {
+        Ok(()) => Json(ApiResponse::ok(serde_json::json!({
+            "sent_to": subscription.email,
+            "subject": report.subject(),
+        })))
+        .into_response(),
+        Err(e) => {
+            warn!("Failed to send {:?} report to {}: {:#}", req.period, subscription.email, e);
+            (StatusCode::BAD_GATEWAY, Json(ApiResponse::<()>::error(format!("Failed to send report: {:#}", e)))).into_response()
+        }
+    }
+}
+
+/// Webhooks notified of pool events, without their secrets
+#[utoipa::path(
+    get,
+    path = "/api/v1/webhooks",
+    tag = "webhooks",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn list_webhooks(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    Json(ApiResponse::ok(state.outbox.webhooks().await)).into_response()
+}
+
+/// Add a webhook; the response is the only time its secret is shown
+#[utoipa::path(
+    post,
+    path = "/api/v1/webhooks",
+    tag = "webhooks",
+    request_body = CreateWebhookRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 400, description = "Invalid URL, events or secret"),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn create_webhook(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<CreateWebhookRequest>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    match state.outbox.add_webhook(&req.url, &req.events, req.secret, Some(claims.name.clone())).await {
+        Ok(hook) => Json(ApiResponse::ok(hook)).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    }
+}
+
+/// Delete a webhook and drop its pending deliveries
+#[utoipa::path(
+    post,
+    path = "/api/v1/webhooks/{id}/delete",
+    tag = "webhooks",
+    params(("id" = String, Path, description = "Webhook ID")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "Unknown webhook"),
+    ),
+)]
+async fn delete_webhook(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    match state.outbox.remove_webhook(&id).await {
+        Ok(true) => Json(ApiResponse::ok(serde_json::json!({ "id": id }))).into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, Json(ApiResponse::<()>::error("Webhook not found"))).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    }
+}
+
+/// Webhook deliveries, newest first, with their status, attempts and last error
+#[utoipa::path(
+    get,
+    path = "/api/v1/webhooks/deliveries",
+    tag = "webhooks",
+    params(DeliveryQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn list_webhook_deliveries(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<DeliveryQuery>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let deliveries = state.outbox
+        .deliveries(query.webhook_id.as_deref(), query.status, query.limit.unwrap_or(100))
+        .await;
+    Json(ApiResponse::ok(deliveries)).into_response()
+}
+
+/// Queue a failed delivery again
+#[utoipa::path(
+    post,
+    path = "/api/v1/webhooks/deliveries/{id}/retry",
+    tag = "webhooks",
+    params(("id" = String, Path, description = "Delivery ID")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "No failed delivery with this ID"),
+    ),
+)]
+async fn retry_webhook_delivery(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    match state.outbox.retry(&id).await {
+        Ok(true) => Json(ApiResponse::ok(serde_json::json!({ "id": id }))).into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, Json(ApiResponse::<()>::error("Failed delivery not found"))).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    }
+}
+
+/// Settings waiting for a restart, the configured restart method and the last restart
+#[utoipa::path(
+    get,
+    path = "/api/v1/system/restart",
+    tag = "system",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn restart_status(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    Json(ApiResponse::ok(serde_json::json!({
+        "pending": state.restart.pending().await,
+        "method": state.restart.method(),
+        "last_restart": state.restart.last_restart().await,
+    })))
+    .into_response()
+}
+
+/// Restart the pool to apply pending settings
+///
+/// Without `confirm` nothing happens and the response lists what the restart
+/// would apply. With it, a backup is taken first and the pool is only restarted
+/// if it succeeds; the config is then reloaded from file.
+#[utoipa::path(
+    post,
+    path = "/api/v1/system/restart",
+    tag = "system",
+    request_body = RestartRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 409, description = "No restart method configured"),
+        (status = 500, description = "Backup or restart failed, or a restart is already running"),
+    ),
+)]
+async fn restart_pool(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<RestartRequest>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let Some(method) = state.restart.method() else {
+        return (StatusCode::CONFLICT, Json(ApiResponse::<()>::error("No restart method configured (set POOL_RESTART)"))).into_response();
+    };
+    if !req.confirm {
+        return Json(ApiResponse::ok(serde_json::json!({
+            "confirmed": false,
+            "method": method,
+            "backup": req.backup,
+            "pending": state.restart.pending().await,
+            "message": "Send confirm: true to restart the pool",
+        })))
+        .into_response();
+    }
+
+    let result = state.restart.restart(&claims.name, || async {
+        if !req.backup {
+            return Ok(None);
+        }
+        match state.backup_manager.create_backup_of_kind(BackupKind::default()).await {
+            Ok(metadata) => Ok(Some(metadata.id)),
+            Err(e) => {
+                publish_backup_failed(&state, BackupKind::default(), &e, false).await;
+                Err(e)
+            }
+        }
+    }).await;
+    match result {
+        Ok(record) => {
+            match Config::load(&state.config_path) {
+                Ok(config) => *state.config.write().await = config,
+                Err(e) => warn!("Pool restarted but reloading {} failed: {}", state.config_path, e),
+            }
+            Json(ApiResponse::ok(record)).into_response()
+        }
+        Err(e) => {
+            error!("Pool restart failed: {:#}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::<()>::error(format!("{:#}", e)))).into_response()
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct EstimateQuery {
+    hashrate_ths: f64,
+    /// Transaction fees expected per block; defaults to the average of the
+    /// pool's recent blocks
+    tx_fees_sats: Option<u64>,
+}
+
+/// Chain tip and difficulty from the node, cached briefly
+async fn network_stats(state: &AdminState) -> Result<NetworkStats> {
+    if let Some((fetched_at, stats)) = state.network_cache.read().await.as_ref() {
+        if fetched_at.elapsed().as_secs() < NETWORK_STATS_CACHE_SECS {
+            return Ok(stats.clone());
+        }
+    }
+    let rpc = bitcoin_rpc(state).await?;
+    let info: serde_json::Value = tokio::task::spawn_blocking(move || {
+        use bitcoincore_rpc::RpcApi;
+        rpc.call("getblockchaininfo", &[]).map_err(|e| anyhow::anyhow!("RPC call failed: {}", e))
+    })
+    .await??;
+    let stats = NetworkStats::from_blockchain_info(&info)?;
+    *state.network_cache.write().await = Some((std::time::Instant::now(), stats.clone()));
+    Ok(stats)
+}
+
+/// Average transaction fees of the pool's recent blocks, 0 before it found any
+async fn recent_tx_fees_sats(state: &AdminState) -> u64 {
+    let fees: Vec<u64> = state.block_tracker.blocks().await
+        .iter()
+        .filter(|b| !b.orphaned)
+        .take(ESTIMATE_FEE_BLOCKS)
+        .map(|b| b.reward_sats.saturating_sub(estimate::block_subsidy_sats(b.height)))
+        .collect();
+    if fees.is_empty() { 0 } else { fees.iter().sum::<u64>() / fees.len() as u64 }
+}
+
+/// Expected daily earnings of a hashrate after pool fees, at the current network difficulty
+#[utoipa::path(
+    get,
+    path = "/api/v1/estimate",
+    tag = "public",
+    params(EstimateQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 400, description = "Invalid hashrate"),
+        (status = 503, description = "Bitcoin node unavailable"),
+    ),
+    security(()),
+)]
+async fn earnings_estimate(
+    State(state): State<AdminState>,
+    Query(query): Query<EstimateQuery>,
+) -> Response {
+    let network = match network_stats(&state).await {
+        Ok(network) => network,
+        Err(e) => {
+            warn!("Failed to get network difficulty for an estimate: {:#}", e);
+            return (StatusCode::SERVICE_UNAVAILABLE, Json(ApiResponse::<()>::error("Bitcoin node unavailable"))).into_response();
+        }
+    };
+    let tx_fees_sats = match query.tx_fees_sats {
+        Some(fees) => fees,
+        None => recent_tx_fees_sats(&state).await,
+    };
+    let params = {
+        let config = state.config.read().await;
+        EstimateParams {
+            fee_bps: config.stratum.fee.unwrap_or(0).saturating_add(config.stratum.donation.unwrap_or(0)),
+            pplns_window_days: config.store.pplns_ttl_days,
+            tx_fees_sats,
+        }
+    };
+    match estimate::estimate(query.hashrate_ths, &network, &params) {
+        Ok(estimate) => Json(ApiResponse::ok(estimate)).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    }
+}
+
+/// Recent blocks found by the pool
+///
+/// Served on `PUBLIC_API_PORT`, where it takes the place of the admin block list.
+#[utoipa::path(
+    get,
+    path = "/api/v1/blocks",
+    tag = "public",
+    responses((status = 200, description = "Standard response envelope", body = ApiEnvelope)),
+    security(()),
+)]
+async fn public_blocks(State(state): State<AdminState>) -> impl IntoResponse {
+    let blocks: Vec<FoundBlock> = state.block_tracker.blocks().await
+        .into_iter()
+        .take(PUBLIC_RECENT_ITEMS)
+        .collect();
+    Json(ApiResponse::ok(blocks))
+}
+
+/// Get block detail
+#[utoipa::path(
+    get,
+    path = "/api/v1/blocks/{height}",
+    tag = "blocks",
+    params(("height" = String, Path, description = "Block height")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn block_detail(
+    State(state): State<AdminState>,
+    Path(height): Path<String>,
+) -> impl IntoResponse {
+    let height: u64 = match height.parse() {
+        Ok(h) => h,
+        Err(_) => return Json(ApiResponse::<FoundBlock>::error("Invalid block height".to_string())),
+    };
+    match state.block_tracker.block_at(height).await {
+        Some(block) => Json(ApiResponse::ok(block)),
+        None => Json(ApiResponse::<FoundBlock>::error(format!("No pool block at height {}", height))),
+    }
+}
+
+/// Maturity state of a pool block, with its state changes
+#[utoipa::path(
+    get,
+    path = "/api/v1/blocks/{height}/status",
+    tag = "blocks",
+    params(("height" = u64, Path, description = "Block height")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "No pool block at this height"),
+    ),
+)]
+async fn block_status(
+    State(state): State<AdminState>,
+    Path(height): Path<u64>,
+) -> Response {
+    let Some(block) = state.block_tracker.block_at(height).await else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<()>::error(format!("No pool block at height {}", height))),
+        ).into_response();
+    };
+    let maturity = state.block_tracker.maturity_confirmations();
+    let credited = state.payout_engine.credited_blocks().await.iter().any(|c| c.hash == block.hash);
+    Json(ApiResponse::ok(serde_json::json!({
+        "height": block.height,
+        "hash": block.hash,
+        "state": block.state,
+        "confirmations": block.confirmations,
+        "maturity_confirmations": maturity,
+        "confirmations_to_maturity": match block.state {
+            BlockState::Pending => Some((maturity - block.confirmations).max(0)),
+            _ => None,
+        },
+        "credited": credited,
+        "state_changes": block.state_changes,
+    }))).into_response()
+}
+
+/// Shares in the current PPLNS window and the simulator for its payouts
+async fn pplns_window(state: &AdminState, reward_sats: u64) -> (Arc<Vec<SimplePplnsShare>>, PplnsSimulator) {
+    let (ttl_days, fee_bps) = {
+        let config = state.config.read().await;
+        let fee_bps = config.stratum.fee.unwrap_or(0).saturating_add(config.stratum.donation.unwrap_or(0));
+        (config.store.pplns_ttl_days, fee_bps)
+    };
+    let shares = cached_shares(state, None, ttl_days * 24 * 3600).await;
+    (shares, PplnsSimulator::new(reward_sats, fee_bps, ttl_days))
+}
+
+/// Shares of the PPLNS window and their payout preview for `reward_sats`,
+/// both served from cache so public requests don't rescan the window
+async fn cached_preview(state: &AdminState, reward_sats: u64) -> (Arc<Vec<SimplePplnsShare>>, Arc<PayoutPreview>) {
+    let (shares, simulator) = pplns_window(state, reward_sats).await;
+    let window = shares.clone();
+    let preview = state.preview_cache
+        .get_or_compute(reward_sats, move || async move { simulator.preview(&window) })
+        .await;
+    (shares, preview)
+}
+
+/// Project per-address payouts if the pool found a block now
+#[utoipa::path(
+    get,
+    path = "/api/v1/pplns/preview",
+    tag = "pplns",
+    params(PplnsPreviewQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn pplns_preview(
+    State(state): State<AdminState>,
+    Query(query): Query<PplnsPreviewQuery>,
+) -> impl IntoResponse {
+    let reward_sats = match query.reward_sats {
+        Some(reward) => reward,
+        None => state.block_tracker.blocks().await
+            .into_iter()
+            .find(|b| !b.orphaned)
+            .map(|b| b.reward_sats)
+            .unwrap_or(DEFAULT_PREVIEW_REWARD_SATS),
+    };
+    let (shares, simulator) = pplns_window(&state, reward_sats).await;
+    let mut preview = simulator.preview(&shares);
+    if let Some(address) = query.address {
+        preview.payouts.retain(|p| p.address == address);
+    }
+    Json(ApiResponse::ok(preview))
+}
+
+/// Orphaned and uncle share blocks over the PPLNS window, per miner with the
+/// payout they cost
+#[utoipa::path(
+    get,
+    path = "/api/v1/sharechain/orphans",
+    tag = "pplns",
+    params(PplnsPreviewQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn sharechain_orphans(
+    State(state): State<AdminState>,
+    Query(query): Query<PplnsPreviewQuery>,
+) -> impl IntoResponse {
+    let reward_sats = match query.reward_sats {
+        Some(reward) => reward,
+        None => state.block_tracker.blocks().await
+            .into_iter()
+            .find(|b| !b.orphaned)
+            .map(|b| b.reward_sats)
+            .unwrap_or(DEFAULT_PREVIEW_REWARD_SATS),
+    };
+    let ttl_days = state.config.read().await.store.pplns_ttl_days;
+    let (shares, simulator) = pplns_window(&state, reward_sats).await;
+    // Miners share what is left after fees
+    let distributed = simulator.preview(&shares).total_payout_satoshis;
+    let orphaned = state.orphans.since(unix_now().saturating_sub(ttl_days * 24 * 3600));
+    let mut report = share_stats::orphan_report(&shares, &orphaned, distributed);
+    if let Some(address) = query.address {
+        report.miners.retain(|m| m.address == address);
+    }
+    Json(ApiResponse::ok(report))
+}
+
+/// Recent shares by difficulty, and each worker's vardiff level
+#[utoipa::path(
+    get,
+    path = "/api/v1/stats/difficulty-distribution",
+    tag = "workers",
+    params(DifficultyDistributionQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 400, description = "Invalid range"),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn difficulty_distribution(
+    State(state): State<AdminState>,
+    Query(query): Query<DifficultyDistributionQuery>,
+) -> Response {
+    let window_secs = match timeseries::parse_duration(query.range.as_deref().unwrap_or("1h")) {
+        Ok(secs) if secs <= MAX_DIFFICULTY_WINDOW_SECS => secs,
+        Ok(_) => {
+            return (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error("range must be at most 24h"))).into_response();
+        }
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    };
+    let now = unix_now();
+    let mut shares = state.store.get_pplns_shares_filtered(None, Some(now.saturating_sub(window_secs)), Some(now));
+    if let Some(address) = &query.address {
+        shares.retain(|s| s.btcaddress.as_deref() == Some(address.as_str()));
+    }
+    let minimum_difficulty = state.config.read().await.stratum.minimum_difficulty;
+    Json(ApiResponse::ok(share_stats::difficulty_distribution(&shares, minimum_difficulty, window_secs))).into_response()
+}
+
+/// Recompute blocks credited between two times with other PPLNS parameters
+///
+/// Reports how much each address would have received compared with what it
+/// was actually credited.
+#[utoipa::path(
+    get,
+    path = "/api/v1/pplns/replay",
+    tag = "pplns",
+    params(PplnsReplayQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 202, description = "Standard response envelope with the started job", body = ApiEnvelope),
+        (status = 400, description = "Invalid range or parameters"),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn pplns_replay(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<PplnsReplayQuery>,
+) -> impl IntoResponse {
+    let end = query.end.unwrap_or_else(unix_now);
+    let start = query.start.unwrap_or_else(|| end.saturating_sub(PPLNS_REPLAY_DEFAULT_SECS));
+    let params = {
+        let config = state.config.read().await;
+        ReplayParams {
+            pplns_ttl_days: query.ttl_days.unwrap_or(config.store.pplns_ttl_days),
+            fee_bps: query.fee_bps.unwrap_or(config.stratum.fee.unwrap_or(0)),
+            donation_bps: query.donation_bps.unwrap_or(config.stratum.donation.unwrap_or(0)),
+        }
+    };
+    let error = if start > end {
+        Some("start must not be after end".to_string())
+    } else if params.pplns_ttl_days == 0 || params.pplns_ttl_days > MAX_REPLAY_TTL_DAYS {
+        Some(format!("ttl_days must be between 1 and {}", MAX_REPLAY_TTL_DAYS))
+    } else if params.fee_bps as u32 + params.donation_bps as u32 > 10_000 {
+        Some("fee_bps and donation_bps must not exceed 10000 together".to_string())
+    } else {
+        None
+    };
+    if let Some(error) = error {
+        return (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(error))).into_response();
+    }
+
+    let mut blocks = Vec::new();
+    for credit in state.payout_engine.credited_blocks().await.into_iter().rev() {
+        let found_at = match state.block_tracker.block_at(credit.height).await {
+            Some(block) => block.timestamp,
+            None => credit.credited_at,
+        };
+        let found_at = found_at.timestamp().max(0) as u64;
+        if found_at < start || found_at > end {
+            continue;
+        }
+        blocks.push(ReplayBlock {
+            height: credit.height,
+            found_at,
+            reward_sats: credit.reward_sats,
+            actual_credits: credit.credits,
+        });
+    }
+
+    if !query.background.unwrap_or(false) {
+        let mut report = PplnsSimulator::replay(&blocks, &params, |from, to| {
+            state.store.get_pplns_shares_filtered(None, Some(from), Some(to))
+        });
+        if let Some(address) = query.address {
+            report.deltas.retain(|d| d.address == address);
+        }
+        return Json(ApiResponse::ok(report)).into_response();
+    }
+
+    let store = state.store.clone();
+    let address = query.address;
+    let description = format!("PPLNS replay of {} blocks from {} to {}", blocks.len(), start, end);
+    let job = state.jobs.spawn(JobKind::Replay, description, &claims.name, move |job| async move {
+        let progress = job.progress;
+        progress.set_phase("replaying");
+        progress.set_steps(blocks.len() as u64);
+        let replay_progress = progress.clone();
+        let mut report = tokio::task::spawn_blocking(move || {
+            PplnsSimulator::replay(&blocks, &params, |from, to| {
+                replay_progress.step();
+                // Later blocks replay against no shares; the report is discarded
+                if replay_progress.is_cancelled() {
+                    return Vec::new();
+                }
+                store.get_pplns_shares_filtered(None, Some(from), Some(to))
+            })
+        })
+        .await?;
+        progress.check_cancelled()?;
+        if let Some(address) = address {
+            report.deltas.retain(|d| d.address == address);
+        }
+        Ok(serde_json::to_value(report)?)
+    });
+    let response = serde_json::json!({
+        "message": "Replay started",
+        "job_id": job.id,
+        "job": job,
+    });
+    (StatusCode::ACCEPTED, Json(ApiResponse::ok(response))).into_response()
+}
+
+/// Validate the stored PPLNS share window
+#[utoipa::path(
+    get,
+    path = "/api/v1/pplns/validate",
+    tag = "pplns",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn pplns_validate(State(state): State<AdminState>) -> impl IntoResponse {
+    let (shares, simulator) = pplns_window(&state, DEFAULT_PREVIEW_REWARD_SATS).await;
+    let report = simulator.validate_window(&shares, unix_now());
+    if !report.valid {
+        warn!("PPLNS validation failed: {}", report.failures.join(", "));
+    }
+    Json(ApiResponse::ok(report))
+}
+
+/// Recent log lines, oldest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/logs",
+    tag = "system",
+    params(LogQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 400, description = "Unknown log level"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Operator role required"),
+    ),
+)]
+async fn logs(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<LogQuery>,
+) -> Response {
+    if let Some(denied) = require_operator(&claims) {
+        return denied;
+    }
+    match state.log_buffer.query(&query) {
+        Ok(entries) => Json(ApiResponse::ok(entries)).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    }
+}
+
+/// Safety check endpoint
+#[utoipa::path(
+    get,
+    path = "/api/v1/safety/check",
+    tag = "system",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn safety_check(State(state): State<AdminState>) -> impl IntoResponse {
+    let config = state.config.read().await;
+    Json(SafetyAnalyzer::new().analyze(&config))
+}
+
+/// Start a session and build the login response
+async fn issue_session(state: &AdminState, headers: &HeaderMap, user: User) -> Result<Json<LoginResponse>, StatusCode> {
+    check_login_country(state, headers, &user.username).await;
+    let ip = extract_client_ip(headers, state.rate_limiter.config()).ok();
+    let tokens = state.auth_manager.create_session_from(&user, ip, user_agent(headers)).await
+        .map_err(|e| {
+            error!("Failed to generate token: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    info!("User '{}' logged in successfully", user.username);
+    Ok(Json(login_response(state, user, tokens).await))
+}
+
+/// Login response, telling the client whether the user must enroll in 2FA first
+async fn login_response(state: &AdminState, user: User, tokens: auth::TokenPair) -> LoginResponse {
+    let must_enroll_2fa = state.two_factor.enrollment_required(&user.username, &user.role).await;
+    LoginResponse {
+        must_enroll_2fa,
+        ..LoginResponse::new(user, tokens)
+    }
+}
+
+fn user_agent(headers: &HeaderMap) -> Option<&str> {
+    headers.get(header::USER_AGENT).and_then(|value| value.to_str().ok())
+}
+
+/// Add a login attempt, with where it came from, to the login activity
+async fn record_login_attempt(state: &AdminState, headers: &HeaderMap, username: &str, result: LoginResult) {
+    let ip = extract_client_ip(headers, state.rate_limiter.config()).ok();
+    let attempt = LoginAttempt::new(username, ip, user_agent(headers), result)
+        .with_geo(ip.and_then(|ip| state.geoip.lookup(ip)));
+    state.auth_manager.record_login_attempt(attempt).await;
+}
+
+/// Remember the country a user logs in from and alert when it is a new one
+async fn check_login_country(state: &AdminState, headers: &HeaderMap, username: &str) {
+    let ip = client_ip(state, headers);
+    let Some(country) = state.geoip.lookup_str(&ip).and_then(|geo| geo.country) else {
+        return;
+    };
+    match state.auth_manager.record_login_country(username, &country).await {
+        Ok(Some(known)) => {
+            warn!("User '{}' logged in from new country {} ({})", username, country, ip);
+            state.alert_manager.notify_login_from_new_country(username, &ip, &country, &known).await;
+        }
+        Ok(None) => {}
+        Err(e) => warn!("Failed to record login country of '{}': {}", username, e),
+    }
+}
+
+/// Login endpoint; users with 2FA enabled get a challenge instead of a token
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/login",
+    tag = "auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Session tokens, or a 2FA challenge", body = LoginResponse),
+        (status = 401, description = "Invalid credentials"),
+    ),
+    security(()),
+)]
+async fn login(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+    Json(req): Json<LoginRequest>,
+) -> Result<Response, StatusCode> {
+    info!("Login request received for user: {}", req.username);
+    let ip = extract_client_ip(&headers, state.rate_limiter.config()).ok();
+    match state.auth_manager.login(&req.username, &req.password, ip).await {
+        Ok(LoginOutcome::Success(user)) => {
+            if state.two_factor.get_status(&user.username).await.enabled {
+                info!("Password accepted for user '{}', awaiting 2FA code", user.username);
+                record_login_attempt(&state, &headers, &user.username, LoginResult::TwoFactorRequired).await;
+                let challenge = state.two_factor.create_challenge(&user.username).await;
+                return Ok(Json(challenge).into_response());
+            }
+            if state.two_factor.policy().enforcement == TwoFactorEnforcement::Block
+                && state.two_factor.enrollment_required(&user.username, &user.role).await
+            {
+                warn!("Refusing login of '{}': role {} requires 2FA", user.username, user.role);
+                record_login_attempt(&state, &headers, &user.username, LoginResult::TwoFactorEnrollmentRequired).await;
+                return Ok((
+                    StatusCode::FORBIDDEN,
+                    Json(ApiResponse::<()>::error("Your role requires 2FA; ask an administrator for help enrolling")),
+                ).into_response());
+            }
+
+            info!("Authentication successful for user: {}, generating token", req.username);
+            record_login_attempt(&state, &headers, &user.username, LoginResult::Success).await;
+            Ok(issue_session(&state, &headers, user).await?.into_response())
+        }
+        Ok(LoginOutcome::Failed { locked }) => {
+            warn!("Failed login attempt for user '{}'", req.username);
+            record_login_attempt(&state, &headers, &req.username, LoginResult::Failed).await;
+            for lockout in locked {
+                record_lockout(&state, &headers, &lockout).await;
+            }
+            Err(StatusCode::UNAUTHORIZED)
+        }
+        Ok(LoginOutcome::Locked(lockout)) => {
+            record_login_attempt(&state, &headers, &req.username, LoginResult::Locked).await;
+            let retry_after = (lockout.locked_until - Utc::now()).num_seconds().max(1);
+            Ok((
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                Json(ApiResponse::<()>::error("Too many failed logins, try again later")),
+            ).into_response())
+        }
+        Err(e) => {
+            error!("Authentication error: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Audit and alert a lockout started by a failed login
+async fn record_lockout(state: &AdminState, headers: &HeaderMap, lockout: &auth::Lockout) {
+    let target = lockout.target.to_string();
+    state.audit_logger.log(AuditLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: Utc::now(),
+        username: "anonymous".to_string(),
+        action: "login_lockout".to_string(),
+        resource: target.clone(),
+        ip_address: client_ip(state, headers),
+        details: serde_json::json!({
+            "failures": lockout.failures,
+            "locked_until": lockout.locked_until,
+        }),
+        success: true,
+        error: None,
+        request_id: None,
+        geo: None,
+        chain: None,
+    }).await;
+    state.alert_manager.notify_login_lockout(&target, lockout.failures, lockout.locked_until).await;
+}
+
+/// Second login step: exchange a challenge token and TOTP or recovery code for a session
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/login/2fa",
+    tag = "auth",
+    request_body = TwoFactorLogin,
+    responses(
+        (status = 200, description = "Session tokens", body = LoginResponse),
+        (status = 401, description = "Invalid challenge or code"),
+    ),
+    security(()),
+)]
+async fn login_2fa(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+    Json(req): Json<TwoFactorLogin>,
+) -> Result<Json<LoginResponse>, StatusCode> {
+    let challenged = state.two_factor.challenge_username(&req.challenge_token).await;
+    let result = state.two_factor
+        .complete_challenge(&req.challenge_token, req.totp_code.as_deref(), req.backup_code.as_deref())
+        .await;
+    finish_two_factor_login(&state, &headers, challenged, result).await
+}
+
+/// Second login step with a security key: exchange a challenge token and the
+/// signed assertion for a session
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/login/webauthn",
+    tag = "auth",
+    request_body = SecurityKeyLogin,
+    responses(
+        (status = 200, description = "Session tokens", body = LoginResponse),
+        (status = 401, description = "Invalid challenge or assertion"),
+    ),
+    security(()),
+)]
+async fn login_webauthn(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+    Json(req): Json<SecurityKeyLogin>,
+) -> Result<Json<LoginResponse>, StatusCode> {
+    let challenged = state.two_factor.challenge_username(&req.challenge_token).await;
+    let result = state.two_factor.complete_key_challenge(&req.challenge_token, &req.credential).await;
+    finish_two_factor_login(&state, &headers, challenged, result).await
+}
+
+/// Issue a session once a login challenge was completed
+async fn finish_two_factor_login(
+    state: &AdminState,
+    headers: &HeaderMap,
+    challenged: Option<String>,
+    result: Result<Option<String>>,
+) -> Result<Json<LoginResponse>, StatusCode> {
+    let username = match result {
+        Ok(Some(username)) => username,
+        Ok(None) => {
+            warn!("Invalid 2FA code for login challenge");
+            if let Some(username) = challenged {
+                record_login_attempt(state, headers, &username, LoginResult::TwoFactorFailed).await;
+            }
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        Err(e) => {
+            warn!("2FA login rejected: {}", e);
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+    };
+
+    // The account may have been disabled while the challenge was pending
+    match state.auth_manager.get_user(&username).await {
+        Some(user) if !user.disabled => {
+            record_login_attempt(state, headers, &username, LoginResult::Success).await;
+            issue_session(state, headers, user).await
+        }
+        _ => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+/// Exchange a refresh token for a new access token
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/refresh",
+    tag = "auth",
+    request_body = RefreshRequest,
+    responses(
+        (status = 200, description = "New session tokens", body = LoginResponse),
+        (status = 401, description = "Invalid or expired refresh token"),
+    ),
+    security(()),
+)]
+async fn refresh_token(
+    State(state): State<AdminState>,
+    Json(req): Json<RefreshRequest>,
+) -> Result<Json<LoginResponse>, StatusCode> {
+    match state.auth_manager.refresh_session(&req.refresh_token).await {
+        Ok((user, tokens)) => Ok(Json(login_response(&state, user, tokens).await)),
+        Err(e) => {
+            warn!("Token refresh rejected: {}", e);
+            Err(StatusCode::UNAUTHORIZED)
+        }
+    }
+}
+
+/// End the caller's session
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/logout",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn logout(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    state.auth_manager.revoke_session(&claims.sid).await;
+    info!("User '{}' logged out", claims.name);
+    Json(ApiResponse::ok(serde_json::json!({ "message": "Logged out" })))
+}
+
+/// A login session, marked if it is the caller's own
+#[derive(Serialize)]
+struct SessionView {
+    #[serde(flatten)]
+    session: SessionInfo,
+    /// The session of the calling token
+    current: bool,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SessionsQuery {
+    /// Sessions of one user (admin only)
+    username: Option<String>,
+}
+
+/// Active login sessions: every user's for admins, otherwise the caller's own
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/sessions",
+    tag = "auth",
+    params(SessionsQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required for other users' sessions"),
+    ),
+)]
+async fn list_sessions(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<SessionsQuery>,
+) -> Response {
+    let username = match query.username {
+        Some(username) if username != claims.name => {
+            if let Some(denied) = require_admin(&claims) {
+                return denied;
+            }
+            Some(username)
+        }
+        Some(username) => Some(username),
+        None if claims.role == "admin" => None,
+        None => Some(claims.name.clone()),
+    };
+    let sessions: Vec<SessionView> = state.auth_manager.sessions(username.as_deref()).await
+        .into_iter()
+        .map(|session| SessionView { current: session.id == claims.sid, session })
+        .collect();
+    Json(ApiResponse::ok(sessions)).into_response()
+}
+
+/// End one login session; admins can end anyone's
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/sessions/{id}/revoke",
+    tag = "auth",
+    params(("id" = String, Path, description = "Session ID")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "No such session"),
+    ),
+)]
+async fn revoke_session(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Response {
+    // Other users' sessions are reported missing to non-admins
+    let session = state.auth_manager.session(&id).await
+        .filter(|session| claims.role == "admin" || session.username == claims.name);
+    let Some(session) = session else {
+        return (StatusCode::NOT_FOUND, Json(ApiResponse::<()>::error("Session not found"))).into_response();
+    };
+    state.auth_manager.revoke_session(&id).await;
+    audit_user_action(&state, &claims, &headers, "session_revoke", &session.username, &Ok(())).await;
+    info!("Session {} of '{}' revoked by '{}'", id, session.username, claims.name);
+    Json(ApiResponse::ok(serde_json::json!({
+        "id": id,
+        "username": session.username,
+        "message": "Session revoked"
+    }))).into_response()
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct LoginAttemptsQuery {
+    username: Option<String>,
+    result: Option<LoginResult>,
+    /// At most 1000; default 100
+    limit: Option<usize>,
+}
+
+/// Recent logins, successful and failed, with where they came from
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/attempts",
+    tag = "auth",
+    params(LoginAttemptsQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+    ),
+)]
+async fn list_login_attempts(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<LoginAttemptsQuery>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let limit = query.limit.unwrap_or(100).min(1000);
+    let attempts = state.auth_manager.login_attempts(query.username.as_deref(), query.result, limit).await;
+    Json(ApiResponse::ok(attempts)).into_response()
+}
+
+/// 2FA code confirming a sensitive 2FA change
+#[derive(Deserialize, ToSchema)]
+struct TwoFactorCodeRequest {
+    code: Option<String>,
+    backup_code: Option<String>,
+}
+
+/// 2FA status for the caller
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/2fa",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn two_factor_status(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    Json(ApiResponse::ok(state.two_factor.status_for(&claims.name, &claims.role).await))
+}
+
+/// Generate a new TOTP secret and recovery codes; 2FA stays off until enabled
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/2fa/setup",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn two_factor_setup(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+) -> Response {
+    if state.two_factor.get_status(&claims.name).await.totp_enabled {
+        return Json(ApiResponse::<()>::error("2FA is already enabled; disable it first")).into_response();
+    }
+    match state.two_factor.generate_secret(&claims.name).await {
+        Ok(setup) => {
+            audit_user_action(&state, &claims, &headers, "2fa_setup", &claims.name, &Ok(())).await;
+            Json(ApiResponse::ok(setup)).into_response()
+        }
+        Err(e) => {
+            error!("2FA setup failed for user '{}': {}", claims.name, e);
+            Json(ApiResponse::<()>::error(e.to_string())).into_response()
+        }
+    }
+}
+
+/// Enable 2FA after confirming a code from the authenticator app
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/2fa/enable",
+    tag = "auth",
+    request_body = TwoFactorCodeRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn two_factor_enable(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Json(req): Json<TwoFactorCodeRequest>,
+) -> Response {
+    let code = req.code.unwrap_or_default();
+    let result = match state.two_factor.enable_2fa(&claims.name, &code).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(anyhow::anyhow!("Invalid 2FA code")),
+        Err(e) => Err(e),
+    };
+    audit_user_action(&state, &claims, &headers, "2fa_enable", &claims.name, &result).await;
+    user_action_response(result, &claims.name, "2FA enabled")
+}
+
+/// Check the caller's current TOTP or recovery code
+async fn verify_two_factor_code(state: &AdminState, username: &str, req: &TwoFactorCodeRequest) -> Result<()> {
+    if !state.two_factor.get_status(username).await.enabled {
+        return Err(anyhow::anyhow!("2FA is not enabled"));
+    }
+    if state.two_factor.verify_login(username, req.code.as_deref(), req.backup_code.as_deref()).await? {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("Invalid 2FA code"))
+    }
+}
+
+/// Disable 2FA; requires a current TOTP or recovery code
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/2fa/disable",
+    tag = "auth",
+    request_body = TwoFactorCodeRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn two_factor_disable(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Json(req): Json<TwoFactorCodeRequest>,
+) -> Response {
+    let result = if state.two_factor.policy().requires(&claims.role)
+        && state.two_factor.get_status(&claims.name).await.security_keys == 0
+    {
+        Err(anyhow::anyhow!("2FA is required for the {} role", claims.role))
+    } else {
+        match verify_two_factor_code(&state, &claims.name, &req).await {
+            Ok(()) => state.two_factor.disable_2fa(&claims.name).await,
+            Err(e) => Err(e),
+        }
+    };
+    audit_user_action(&state, &claims, &headers, "2fa_disable", &claims.name, &result).await;
+    user_action_response(result, &claims.name, "2FA disabled")
+}
+
+/// Replace the caller's recovery codes; requires a current TOTP or recovery code
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/2fa/recovery-codes",
+    tag = "auth",
+    request_body = TwoFactorCodeRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn two_factor_recovery_codes(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Json(req): Json<TwoFactorCodeRequest>,
+) -> Response {
+    let result = match verify_two_factor_code(&state, &claims.name, &req).await {
+        Ok(()) => state.two_factor.regenerate_backup_codes(&claims.name).await,
+        Err(e) => Err(e),
+    };
+    let audit_result = result.as_ref().map(|_| ()).map_err(|e| anyhow::anyhow!("{}", e));
+    audit_user_action(&state, &claims, &headers, "2fa_recovery_codes", &claims.name, &audit_result).await;
+    match result {
+        Ok(codes) => Json(ApiResponse::ok(serde_json::json!({ "recovery_codes": codes }))).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(e.to_string())).into_response(),
+    }
+}
+
+/// JWT key IDs in use, without their secrets
+fn jwt_key_ids(keys: &JwtKeySet) -> serde_json::Value {
+    serde_json::json!({
+        "current_kid": keys.current.kid(),
+        "previous_kids": keys.previous.iter().map(JwtKey::kid).collect::<Vec<_>>(),
+    })
+}
+
+/// Secret providers and the JWT signing key IDs (admin only)
+#[utoipa::path(
+    get,
+    path = "/api/v1/secrets",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+    ),
+)]
+async fn secrets_status(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    Json(ApiResponse::ok(serde_json::json!({
+        "providers": state.secrets.provider_names(),
+        "jwt": jwt_key_ids(&state.auth_manager.jwt_keys()),
+    }))).into_response()
+}
 
-    Json(ApiResponse::ok(response))
+/// Re-read the JWT secrets and sign new tokens with `JWT_SECRET` (admin only)
+///
+/// Keys in use before the reload keep validating tokens until the next
+/// restart; list them in `JWT_PREVIOUS_SECRETS` to keep them longer.
+#[utoipa::path(
+    post,
+    path = "/api/v1/secrets/jwt/reload",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+    ),
+)]
+async fn reload_jwt_keys(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let result = match JwtKeySet::from_secrets(&state.secrets).await {
+        Ok(Some(keys)) => Ok(state.auth_manager.jwt_keys().rotate_to(keys)),
+        Ok(None) => Err(anyhow::anyhow!("JWT_SECRET is not set in any secret provider")),
+        Err(e) => Err(e),
+    };
+    let audit_result = result.as_ref().map(|_| ()).map_err(|e| anyhow::anyhow!("{:#}", e));
+    audit_user_action(&state, &claims, &headers, "jwt_keys_reload", "jwt", &audit_result).await;
+    match result {
+        Ok(keys) => {
+            info!("JWT signing key is now {} ({} previous)", keys.current.kid(), keys.previous.len());
+            let ids = jwt_key_ids(&keys);
+            state.auth_manager.set_jwt_keys(keys);
+            Json(ApiResponse::ok(ids)).into_response()
+        }
+        Err(e) => {
+            error!("Failed to reload JWT secrets: {:#}", e);
+            Json(ApiResponse::<()>::error(format!("{:#}", e))).into_response()
+        }
+    }
 }
 
-/// Ban worker
-async fn ban_worker(
+/// Security keys of the caller
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/webauthn/keys",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn list_security_keys(
     State(state): State<AdminState>,
-    Path(address): Path<String>,
-    Json(req): Json<BanRequest>,
+    Extension(claims): Extension<Claims>,
 ) -> impl IntoResponse {
-    state.banned_workers.write().await.insert(address.clone());
-    info!("Banned worker: {} - reason: {:?}", address, req.reason);
+    Json(ApiResponse::ok(state.two_factor.security_keys(&claims.name).await))
+}
 
-    let response = serde_json::json!({
-        "address": address,
-        "banned": true,
-        "message": "Worker banned successfully"
-    });
+/// Start registering a security key; pass the options to `navigator.credentials.create()`
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/webauthn/register/start",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn start_security_key_registration(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    match state.two_factor.start_key_registration(&claims.name).await {
+        Ok(options) => Json(ApiResponse::ok(serde_json::json!({ "public_key": options }))).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(e.to_string())).into_response(),
+    }
+}
 
-    Json(ApiResponse::ok(response))
+/// Browser response finishing a security key registration
+#[derive(Deserialize, ToSchema)]
+struct RegisterSecurityKeyRequest {
+    /// Label shown in the key list, e.g. "YubiKey 5C"
+    name: Option<String>,
+    credential: RegistrationCredential,
 }
 
-/// Unban worker
-async fn unban_worker(
+/// Finish registering a security key; recovery codes are returned if the caller had none
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/webauthn/register/finish",
+    tag = "auth",
+    request_body = RegisterSecurityKeyRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn finish_security_key_registration(
     State(state): State<AdminState>,
-    Path(address): Path<String>,
-) -> impl IntoResponse {
-    state.banned_workers.write().await.remove(&address);
-    info!("Unbanned worker: {}", address);
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Json(req): Json<RegisterSecurityKeyRequest>,
+) -> Response {
+    let name = req.name.filter(|n| !n.trim().is_empty()).unwrap_or_else(|| "Security key".to_string());
+    let result = state.two_factor.finish_key_registration(&claims.name, name.trim(), &req.credential).await;
+    let audit_result = result.as_ref().map(|_| ()).map_err(|e| anyhow::anyhow!("{}", e));
+    audit_user_action(&state, &claims, &headers, "webauthn_register", &claims.name, &audit_result).await;
+    match result {
+        Ok((key, recovery_codes)) => Json(ApiResponse::ok(serde_json::json!({
+            "key": key,
+            "recovery_codes": recovery_codes,
+        }))).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(e.to_string())).into_response(),
+    }
+}
 
-    let response = serde_json::json!({
-        "address": address,
-        "banned": false,
-        "message": "Worker unbanned successfully"
-    });
+/// Remove one of the caller's security keys
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/webauthn/keys/{id}/delete",
+    tag = "auth",
+    params(("id" = String, Path, description = "Credential ID")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn delete_security_key(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Response {
+    let status = state.two_factor.get_status(&claims.name).await;
+    let result = if state.two_factor.policy().requires(&claims.role)
+        && !status.totp_enabled
+        && status.security_keys == 1
+    {
+        Err(anyhow::anyhow!("2FA is required for the {} role; add another factor first", claims.role))
+    } else {
+        state.two_factor.remove_security_key(&claims.name, &id).await
+    };
+    audit_user_action(&state, &claims, &headers, "webauthn_remove", &claims.name, &result).await;
+    user_action_response(result, &claims.name, "Security key removed")
+}
 
-    Json(ApiResponse::ok(response))
+/// Create user request
+#[derive(Deserialize, ToSchema)]
+struct CreateUserRequest {
+    username: String,
+    password: String,
+    role: String,
 }
 
-/// Add tag to worker
-#[derive(Deserialize)]
-struct AddTagRequest {
-    tag: String,
+/// Reset password request (admin sets a temporary password)
+#[derive(Deserialize, ToSchema)]
+struct ResetPasswordRequest {
+    password: String,
 }
 
-async fn add_worker_tag(
-    State(state): State<AdminState>,
-    Path(address): Path<String>,
-    Json(req): Json<AddTagRequest>,
-) -> impl IntoResponse {
-    let mut worker_tags = state.worker_tags.write().await;
-    let tags = worker_tags.entry(address.clone()).or_insert_with(Vec::new);
+/// Change own password request
+#[derive(Deserialize, ToSchema)]
+struct ChangePasswordRequest {
+    current_password: String,
+    new_password: String,
+}
 
-    if !tags.contains(&req.tag) {
-        tags.push(req.tag.clone());
-        info!("Added tag '{}' to worker: {}", req.tag, address);
+/// Resolve the client IP for audit entries
+fn client_ip(state: &AdminState, headers: &HeaderMap) -> String {
+    extract_client_ip(headers, state.rate_limiter.config())
+        .map(|ip| ip.to_string())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Record a user management action in the audit log
+async fn audit_user_action(
+    state: &AdminState,
+    claims: &Claims,
+    headers: &HeaderMap,
+    action: &str,
+    target: &str,
+    result: &Result<()>,
+) {
+    state.audit_logger.log(AuditLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: Utc::now(),
+        username: claims.name.clone(),
+        action: action.to_string(),
+        resource: format!("user:{}", target),
+        ip_address: client_ip(state, headers),
+        details: serde_json::json!({ "target": target }),
+        success: result.is_ok(),
+        error: result.as_ref().err().map(|e| e.to_string()),
+        request_id: None,
+        geo: None,
+        chain: None,
+    }).await;
+}
+
+/// Reject callers below the admin role
+fn require_admin(claims: &Claims) -> Option<Response> {
+    if claims.role == "admin" {
+        return None;
     }
+    warn!("User '{}' with role '{}' denied: admin role required", claims.name, claims.role);
+    Some((StatusCode::FORBIDDEN, Json(ApiResponse::<()>::error("Admin role required"))).into_response())
+}
 
-    let response = serde_json::json!({
-        "address": address,
-        "tag": req.tag,
-        "tags": tags.clone(),
-        "message": "Tag added successfully"
-    });
+/// Reject callers below the operator role, such as viewers
+fn require_operator(claims: &Claims) -> Option<Response> {
+    if claims.role == "admin" || claims.role == "operator" {
+        return None;
+    }
+    warn!("User '{}' with role '{}' denied: operator role required", claims.name, claims.role);
+    Some((StatusCode::FORBIDDEN, Json(ApiResponse::<()>::error("Operator role required"))).into_response())
+}
 
-    Json(ApiResponse::ok(response))
+/// Turn a user management result into an API response
+fn user_action_response(result: Result<()>, username: &str, message: &str) -> Response {
+    match result {
+        Ok(()) => Json(ApiResponse::ok(serde_json::json!({
+            "username": username,
+            "message": message
+        }))).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(e.to_string())).into_response(),
+    }
 }
 
-/// Remove tag from worker
-async fn remove_worker_tag(
+/// List users
+#[utoipa::path(
+    get,
+    path = "/api/v1/users",
+    tag = "users",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn list_users(
     State(state): State<AdminState>,
-    Path((address, tag)): Path<(String, String)>,
-) -> impl IntoResponse {
-    let mut worker_tags = state.worker_tags.write().await;
-
-    if let Some(tags) = worker_tags.get_mut(&address) {
-        let original_len = tags.len();
-        tags.retain(|t| t != &tag);
-        if tags.len() < original_len {
-            info!("Removed tag '{}' from worker: {}", tag, address);
-        }
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
     }
+    Json(ApiResponse::ok(state.auth_manager.list_users().await)).into_response()
+}
 
-    let current_tags = worker_tags.get(&address).cloned().unwrap_or_default();
+/// Create user; the new user must change their password on first login
+#[utoipa::path(
+    post,
+    path = "/api/v1/users",
+    tag = "users",
+    request_body = CreateUserRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn create_user(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Json(req): Json<CreateUserRequest>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let result = state.auth_manager.create_user(&req.username, &req.password, &req.role).await;
+    audit_user_action(&state, &claims, &headers, "user_create", &req.username, &result).await;
+    user_action_response(result, &req.username, "User created")
+}
 
-    let response = serde_json::json!({
-        "address": address,
-        "tag": tag,
-        "tags": current_tags,
-        "message": "Tag removed successfully"
-    });
+/// Delete user
+#[utoipa::path(
+    post,
+    path = "/api/v1/users/{username}/delete",
+    tag = "users",
+    params(("username" = String, Path, description = "Username")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn delete_user(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Path(username): Path<String>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let result = if username == claims.name {
+        Err(anyhow::anyhow!("Cannot delete your own account"))
+    } else {
+        state.auth_manager.delete_user(&username).await
+    };
+    if result.is_ok() {
+        // A future account with the same name must not inherit the old 2FA secret
+        if let Err(e) = state.two_factor.disable_2fa(&username).await {
+            warn!("Failed to clear 2FA for deleted user '{}': {}", username, e);
+        }
+        if let Err(e) = state.two_factor.remove_security_keys(&username).await {
+            warn!("Failed to remove security keys of deleted user '{}': {}", username, e);
+        }
+        if let Err(e) = state.report_subscriptions.remove(&username).await {
+            warn!("Failed to remove report subscription of deleted user '{}': {}", username, e);
+        }
+    }
+    audit_user_action(&state, &claims, &headers, "user_delete", &username, &result).await;
+    user_action_response(result, &username, "User deleted")
+}
 
-    Json(ApiResponse::ok(response))
+/// Disable user
+#[utoipa::path(
+    post,
+    path = "/api/v1/users/{username}/disable",
+    tag = "users",
+    params(("username" = String, Path, description = "Username")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn disable_user(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Path(username): Path<String>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let result = if username == claims.name {
+        Err(anyhow::anyhow!("Cannot disable your own account"))
+    } else {
+        state.auth_manager.set_disabled(&username, true).await
+    };
+    audit_user_action(&state, &claims, &headers, "user_disable", &username, &result).await;
+    user_action_response(result, &username, "User disabled")
 }
 
-/// Get blocks list
-async fn blocks_list(State(state): State<AdminState>) -> impl IntoResponse {
-    let _height = state.chain_store.get_tip_height()
-        .ok()
-        .flatten()
-        .map(|h| h as u64)
-        .unwrap_or(0);
-    // Return basic info - TODO: Get actual blocks from database
-    let blocks: Vec<()> = vec![];
-    Json(ApiResponse::ok(blocks))
+/// Enable user
+#[utoipa::path(
+    post,
+    path = "/api/v1/users/{username}/enable",
+    tag = "users",
+    params(("username" = String, Path, description = "Username")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn enable_user(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Path(username): Path<String>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let result = state.auth_manager.set_disabled(&username, false).await;
+    audit_user_action(&state, &claims, &headers, "user_enable", &username, &result).await;
+    user_action_response(result, &username, "User enabled")
 }
 
-/// Get block detail
-async fn block_detail(
-    State(_state): State<AdminState>,
-    Path(height): Path<String>,
-) -> impl IntoResponse {
-    let _height: u64 = match height.parse() {
-        Ok(h) => h,
-        Err(_) => return Json(ApiResponse::<serde_json::Value>::error("Invalid block height".to_string())),
-    };
-    // TODO: Get actual block detail
-    Json(ApiResponse::<serde_json::Value>::error("Block detail not yet implemented".to_string()))
+/// Reset a user's password to a temporary one
+#[utoipa::path(
+    post,
+    path = "/api/v1/users/{username}/reset-password",
+    tag = "users",
+    params(("username" = String, Path, description = "Username")),
+    request_body = ResetPasswordRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn reset_user_password(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Path(username): Path<String>,
+    Json(req): Json<ResetPasswordRequest>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let result = state.auth_manager.reset_password(&username, &req.password).await;
+    audit_user_action(&state, &claims, &headers, "user_reset_password", &username, &result).await;
+    user_action_response(result, &username, "Password reset; user must change it on next login")
 }
 
-/// Get logs
-async fn logs(State(_state): State<AdminState>) -> impl IntoResponse {
-    // TODO: Return actual log entries
-    let logs = vec![
-        "2026-02-03 10:00:00 [INFO] DMPool started".to_string(),
-        "2026-02-03 10:00:05 [INFO] Connected to Bitcoin RPC".to_string(),
-    ];
-    Json(ApiResponse::ok(logs))
+/// Revoke all sessions of a user, forcing them to log in again
+#[utoipa::path(
+    post,
+    path = "/api/v1/users/{username}/revoke-sessions",
+    tag = "users",
+    params(("username" = String, Path, description = "Username")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn revoke_user_sessions(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Path(username): Path<String>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let revoked = state.auth_manager.revoke_user_sessions(&username).await;
+    audit_user_action(&state, &claims, &headers, "user_revoke_sessions", &username, &Ok(())).await;
+    Json(ApiResponse::ok(serde_json::json!({
+        "username": username,
+        "revoked": revoked,
+        "message": "Sessions revoked"
+    }))).into_response()
 }
 
-/// Safety check endpoint
-async fn safety_check(State(state): State<AdminState>) -> impl IntoResponse {
-    let config = state.config.read().await;
-    let mut critical = vec![];
-    let mut warnings = vec![];
-
-    // Check ignore_difficulty
-    if config.stratum.ignore_difficulty.unwrap_or(false) {
-        critical.push(SafetyIssue {
-            severity: "critical".to_string(),
-            param: "ignore_difficulty".to_string(),
-            message: "已禁用难度验证，可能导致不公平的PPLNS收益分配".to_string(),
-            recommendation: "设置为 false".to_string(),
-        });
+/// Accounts and client addresses locked out by failed logins
+#[utoipa::path(
+    get,
+    path = "/api/v1/lockouts",
+    tag = "users",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn list_lockouts(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
     }
+    Json(ApiResponse::ok(state.auth_manager.lockouts().await)).into_response()
+}
 
-    // Check pplns_ttl_days
-    if config.store.pplns_ttl_days < 7 {
-        critical.push(SafetyIssue {
-            severity: "critical".to_string(),
-            param: "pplns_ttl_days".to_string(),
-            message: format!(
-                "TTL={}天过短，标准为7天，矿工可能损失约{}%的收益",
-                config.store.pplns_ttl_days,
-                ((7 - config.store.pplns_ttl_days) * 100 / 7)
-            ),
-            recommendation: "设置为 7".to_string(),
-        });
+/// Lift a user's lockout and clear their failed logins
+#[utoipa::path(
+    post,
+    path = "/api/v1/users/{username}/unlock",
+    tag = "users",
+    params(("username" = String, Path, description = "Username")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn unlock_user(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Path(username): Path<String>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
     }
+    let unlocked = state.auth_manager.unlock(&LockoutTarget::User(username.clone())).await;
+    audit_user_action(&state, &claims, &headers, "user_unlock", &username, &Ok(())).await;
+    Json(ApiResponse::ok(serde_json::json!({
+        "username": username,
+        "unlocked": unlocked,
+        "message": if unlocked { "User unlocked" } else { "User had no failed logins" }
+    }))).into_response()
+}
 
-    // Check donation
-    if let Some(donation) = config.stratum.donation {
-        if donation >= 10000 {
-            critical.push(SafetyIssue {
-                severity: "critical".to_string(),
-                param: "donation".to_string(),
-                message: "donation=10000意味着100%捐赠，矿工收益为0！".to_string(),
-                recommendation: "设置为0或注释掉donation".to_string(),
-            });
-        } else if donation > 500 {
-            warnings.push(SafetyIssue {
-                severity: "warning".to_string(),
-                param: "donation".to_string(),
-                message: format!("捐赠比例较高: {}%", donation / 100),
-                recommendation: "考虑设置为0-500(0-5%)".to_string(),
-            });
-        }
+/// Lift a client address's lockout and clear its failed logins
+#[utoipa::path(
+    post,
+    path = "/api/v1/lockouts/{ip}/unlock",
+    tag = "users",
+    params(("ip" = String, Path, description = "Client IP address")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn unlock_ip(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(ip): Path<IpAddr>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
     }
+    let unlocked = state.auth_manager.unlock(&LockoutTarget::Ip(ip)).await;
+    Json(ApiResponse::ok(serde_json::json!({
+        "ip": ip,
+        "unlocked": unlocked,
+        "message": if unlocked { "Address unlocked" } else { "Address had no failed logins" }
+    }))).into_response()
+}
 
-    let safe = critical.is_empty();
+/// The caller's password age and the policy a new password must follow
+#[derive(Serialize)]
+struct PasswordStatus {
+    changed_at: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at: Option<i64>,
+    must_change: bool,
+    policy: PasswordPolicy,
+}
 
-    Json(SafetyReport {
-        safe,
-        critical_issues: critical,
-        warnings,
-    })
+/// Show when the caller's password expires and the password policy
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/me/password",
+    tag = "users",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn password_status(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    let Some(user) = state.auth_manager.get_user(&claims.name).await else {
+        return (StatusCode::NOT_FOUND, Json(ApiResponse::<()>::error("User not found"))).into_response();
+    };
+    let policy = state.auth_manager.password_policy().clone();
+    Json(ApiResponse::ok(PasswordStatus {
+        changed_at: user.password_set_at(),
+        expires_at: policy.expires_at(user.password_set_at()),
+        must_change: user.must_change_password,
+        policy,
+    })).into_response()
 }
 
-/// Login endpoint using AdminState
-async fn login(
+/// Change the caller's own password; the new one must follow the password policy
+#[utoipa::path(
+    post,
+    path = "/api/v1/users/me/password",
+    tag = "users",
+    request_body = ChangePasswordRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn change_password(
     State(state): State<AdminState>,
-    Json(req): Json<LoginRequest>,
-) -> Result<Json<LoginResponse>, StatusCode> {
-    info!("Login request received for user: {}", req.username);
-    match state.auth_manager.authenticate(&req.username, &req.password).await {
-        Ok(Some(user)) => {
-            info!("Authentication successful for user: {}, generating token", req.username);
-            let token = state.auth_manager.generate_token(&user)
-                .map_err(|e| {
-                    error!("Failed to generate token: {}", e);
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })?;
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Json(req): Json<ChangePasswordRequest>,
+) -> Response {
+    let result = state.auth_manager
+        .change_password(&claims.name, &req.current_password, &req.new_password)
+        .await;
+    audit_user_action(&state, &claims, &headers, "user_change_password", &claims.name, &result).await;
+    user_action_response(result, &claims.name, "Password changed; please log in again")
+}
 
-            let expires_in = 24 * 3600; // 24 hours
+/// Currently firing alerts
+#[utoipa::path(
+    get,
+    path = "/api/v1/alerts",
+    tag = "alerts",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn active_alerts(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(ApiResponse::ok(state.alert_manager.active_alerts().await))
+}
 
-            info!("User '{}' logged in successfully", req.username);
+/// Resolved alerts, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/alerts/history",
+    tag = "alerts",
+    params(AlertHistoryQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn alert_history(
+    State(state): State<AdminState>,
+    Query(query): Query<AlertHistoryQuery>,
+) -> impl IntoResponse {
+    let limit = query.limit.unwrap_or(100).min(1000);
+    let alerts = state.alert_manager
+        .resolved_history(query.rule_id.as_deref(), Some(limit))
+        .await;
+    Json(ApiResponse::ok(alerts))
+}
 
-            Ok(Json(LoginResponse {
-                token,
-                user_info: UserInfo {
-                    username: user.username,
-                    role: user.role,
-                },
-                expires_in,
-            }))
-        }
-        Ok(None) => {
-            warn!("Failed login attempt for user '{}'", req.username);
-            Err(StatusCode::UNAUTHORIZED)
-        }
-        Err(e) => {
-            error!("Authentication error: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+/// Acknowledge an alert, stopping further escalation of its rule
+#[utoipa::path(
+    post,
+    path = "/api/v1/alerts/{id}/ack",
+    tag = "alerts",
+    params(("id" = String, Path, description = "Alert ID")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn acknowledge_alert(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Response {
+    match state.alert_manager.acknowledge_alert(&id, &claims.name).await {
+        Ok(true) => Json(ApiResponse::ok(serde_json::json!({
+            "id": id,
+            "message": "Alert acknowledged"
+        }))).into_response(),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<()>::error(format!("Alert not found: {}", id))),
+        ).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(e.to_string())).into_response(),
+    }
+}
+
+/// List per-route rate limit rules
+#[utoipa::path(
+    get,
+    path = "/api/v1/ratelimit/rules",
+    tag = "ratelimit",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn list_rate_limit_rules(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(ApiResponse::ok(state.rate_limiter.rules().await))
+}
+
+/// Add a per-route rate limit rule (admin only)
+#[utoipa::path(
+    post,
+    path = "/api/v1/ratelimit/rules",
+    tag = "ratelimit",
+    request_body = RateLimitRuleRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn create_rate_limit_rule(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<RateLimitRuleRequest>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let mut rule = RateLimitRule::new(req.route_prefix, req.limit, req.window_secs);
+    rule.user = req.user;
+    rule.role = req.role;
+    match state.rate_limiter.add_rule(rule.clone()).await {
+        Ok(()) => Json(ApiResponse::ok(rule)).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::<()>::error(e.to_string())),
+        ).into_response(),
+    }
+}
+
+/// Remove a per-route rate limit rule (admin only)
+#[utoipa::path(
+    post,
+    path = "/api/v1/ratelimit/rules/{id}/delete",
+    tag = "ratelimit",
+    params(("id" = String, Path, description = "Rule ID")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn delete_rate_limit_rule(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    match state.rate_limiter.remove_rule(&id).await {
+        Ok(true) => Json(ApiResponse::ok(serde_json::json!({
+            "id": id,
+            "message": "Rule removed"
+        }))).into_response(),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<()>::error(format!("Rule not found: {}", id))),
+        ).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(e.to_string())).into_response(),
     }
 }
 
 /// Get audit logs
+#[utoipa::path(
+    get,
+    path = "/api/v1/audit/logs",
+    tag = "audit",
+    params(AuditFilter),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
 async fn audit_logs(
     State(state): State<AdminState>,
     Query(filter): Query<AuditFilterWrapper>,
@@ -955,31 +8171,120 @@ async fn audit_logs(
     Json(ApiResponse::ok(logs))
 }
 
+/// Search audit logs by words and fields, with the total match count for paging
+#[utoipa::path(
+    get,
+    path = "/api/v1/audit/search",
+    tag = "audit",
+    params(AuditFilter),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+    ),
+)]
+async fn audit_search(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Query(filter): Query<AuditFilterWrapper>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let page = state.audit_logger.search(filter.0).await;
+    Json(ApiResponse::ok(page)).into_response()
+}
+
 /// Get audit statistics
+#[utoipa::path(
+    get,
+    path = "/api/v1/audit/stats",
+    tag = "audit",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
 async fn audit_stats(State(state): State<AdminState>) -> impl IntoResponse {
     let stats = state.audit_logger.stats().await;
     Json(ApiResponse::ok(stats))
 }
 
+/// Check the audit log's hash chain and anchors, reporting the first broken link
+#[utoipa::path(
+    get,
+    path = "/api/v1/audit/verify",
+    tag = "audit",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+    ),
+)]
+async fn audit_verify(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    match state.audit_logger.verify().await {
+        Ok(report) => Json(ApiResponse::ok(report)).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(format!("Failed to verify audit log: {}", e))).into_response(),
+    }
+}
+
 /// Rotate audit logs
-async fn audit_rotate(State(state): State<AdminState>) -> impl IntoResponse {
+#[utoipa::path(
+    post,
+    path = "/api/v1/audit/rotate",
+    tag = "audit",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+    ),
+)]
+async fn audit_rotate(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
     match state.audit_logger.rotate_logs().await {
         Ok(archive_path) => {
             let response = serde_json::json!({
                 "message": "Audit logs rotated successfully",
                 "archive_file": archive_path
             });
-            Json(ApiResponse::ok(response))
+            Json(ApiResponse::ok(response)).into_response()
         }
         Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!(
             "Failed to rotate logs: {}",
             e
-        ))),
+        ))).into_response(),
     }
 }
 
 /// Export audit logs
-async fn audit_export(State(state): State<AdminState>) -> impl IntoResponse {
+#[utoipa::path(
+    post,
+    path = "/api/v1/audit/export",
+    tag = "audit",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+    ),
+)]
+async fn audit_export(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
     let output_path = std::path::PathBuf::from(format!(
         "./audit_export_{}.jsonl",
         Utc::now().format("%Y%m%d_%H%M%S")
@@ -991,36 +8296,316 @@ async fn audit_export(State(state): State<AdminState>) -> impl IntoResponse {
                 "message": format!("Exported {} audit log entries", count),
                 "file": output_path
             });
-            Json(ApiResponse::ok(response))
+            Json(ApiResponse::ok(response)).into_response()
         }
         Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!(
             "Failed to export logs: {}",
             e
-        ))),
+        ))).into_response(),
+    }
+}
+
+/// Wrapper for Query<AuditFilter> to implement FromRequest
+#[derive(Debug, Deserialize)]
+struct AuditFilterWrapper(AuditFilter);
+
+impl Default for AuditFilterWrapper {
+    fn default() -> Self {
+        Self(AuditFilter::default())
+    }
+}
+
+/// Get pending configuration change confirmations
+#[utoipa::path(
+    get,
+    path = "/api/v1/config/confirmations",
+    tag = "config",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn get_confirmations(State(state): State<AdminState>) -> impl IntoResponse {
+    let pending = state.config_confirmation.get_pending().await;
+    Json(ApiResponse::ok(pending))
+}
+
+/// List recorded config versions, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/config/versions",
+    tag = "config",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn list_config_versions(
+    State(state): State<AdminState>,
+    Extension(_claims): Extension<Claims>,
+) -> impl IntoResponse {
+    let current = state.config_manager.current_version().await.map(|v| v.id);
+    let versions = state.config_manager.list_versions().await;
+    Json(ApiResponse::ok(serde_json::json!({
+        "current": current,
+        "versions": versions,
+    })))
+}
+
+/// Diff between a version and the current (or another) version
+async fn version_diff(state: &AdminState, id: &str, against: Option<String>) -> Result<config_mgt::ConfigDiff> {
+    let base = match against {
+        Some(base) => base,
+        None => state.config_manager.current_version().await
+            .map(|v| v.id)
+            .ok_or_else(|| anyhow::anyhow!("No current config version"))?,
+    };
+    state.config_manager.diff_versions(&base, id).await
+}
+
+/// Show what changes between the current config and a version
+#[utoipa::path(
+    get,
+    path = "/api/v1/config/versions/{id}/diff",
+    tag = "config",
+    params(("id" = String, Path, description = "Config version ID"), VersionDiffQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn config_version_diff(
+    State(state): State<AdminState>,
+    Extension(_claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Query(query): Query<VersionDiffQuery>,
+) -> impl IntoResponse {
+    match version_diff(&state, &id, query.against).await {
+        Ok(diff) => Json(ApiResponse::ok(serde_json::json!({
+            "lines": diff.describe(),
+            "diff": diff,
+        }))),
+        Err(e) => Json(ApiResponse::<serde_json::Value>::error(e.to_string())),
+    }
+}
+
+/// Roll the running config back to a version; requires `confirm: true`
+#[utoipa::path(
+    post,
+    path = "/api/v1/config/versions/{id}/rollback",
+    tag = "config",
+    params(("id" = String, Path, description = "Config version ID")),
+    request_body = RollbackRequest,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+        (status = 422, description = "The rollback introduces critical safety issues"),
+    ),
+)]
+async fn rollback_config_version(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(req): Json<RollbackRequest>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let diff = match version_diff(&state, &id, None).await {
+        Ok(diff) => diff,
+        Err(e) => return Json(ApiResponse::<serde_json::Value>::error(e.to_string())).into_response(),
+    };
+    if !req.confirm {
+        return Json(ApiResponse::ok(serde_json::json!({
+            "message": "Rollback not applied; resend with confirm=true to apply these changes",
+            "applied": false,
+            "lines": diff.describe(),
+            "critical_changes": diff.summary.critical_changes,
+        }))).into_response();
+    }
+
+    let result = state.config_manager.rollback_config(&state.config, &id, &claims.name, req.force).await;
+    state.audit_logger.log(AuditLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: Utc::now(),
+        username: claims.name.clone(),
+        action: "config_rollback".to_string(),
+        resource: format!("config_version:{}", id),
+        ip_address: client_ip(&state, &headers),
+        details: serde_json::json!({
+            "target_version": id,
+            "reason": req.reason,
+            "changes": diff.describe(),
+            "version": result.as_ref().ok().map(|v| v.id.clone()),
+            "force": req.force,
+        }),
+        success: result.is_ok(),
+        error: result.as_ref().err().map(|e| e.to_string()),
+        request_id: None,
+        geo: None,
+        chain: None,
+    }).await;
+
+    match result {
+        Ok(version) => {
+            let persisted = persist_running_config(&state).await;
+            Json(ApiResponse::ok(serde_json::json!({
+                "message": format!("Rolled back to {}", id),
+                "applied": true,
+                "version": version.id,
+                "lines": diff.describe(),
+                "persisted": persisted,
+            }))).into_response()
+        }
+        Err(e) => match e.downcast::<UnsafeChange>() {
+            Ok(unsafe_change) => unsafe_config_change(unsafe_change),
+            Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!("Rollback failed: {}", e))).into_response(),
+        },
+    }
+}
+
+/// Write the running config back to the config file, keeping a backup
+///
+/// File edits still waiting for confirmation are written as edited rather
+/// than reverted; those the running config has since moved past are dropped.
+async fn persist_running_config(state: &AdminState) -> bool {
+    let mut config = state.config.read().await.clone();
+    for request in state.config_confirmation.get_pending().await {
+        if request.username != CONFIG_FILE_USER {
+            continue;
+        }
+        if config_mgt::parameter_value(&config, &request.parameter).as_ref() != Some(&request.old_value) {
+            let _ = state.config_confirmation.cancel_change(&request.id).await;
+            continue;
+        }
+        if let Err(e) = config_mgt::set_parameter(&mut config, &request.parameter, &request.new_value) {
+            warn!("Not keeping pending config file edit of {}: {}", request.parameter, e);
+        }
+    }
+    let path = std::path::PathBuf::from(&state.config_path);
+    match tokio::task::spawn_blocking(move || config_mgt::persist_config(&path, &config)).await {
+        Ok(Ok(_)) => true,
+        Ok(Err(e)) => {
+            error!("Failed to persist config to {}: {:#}", state.config_path, e);
+            false
+        }
+        Err(e) => {
+            error!("Config persist task failed: {}", e);
+            false
+        }
+    }
+}
+
+/// Apply a validated change through the config manager and audit the outcome
+async fn apply_config_change(
+    state: &AdminState,
+    claims: &Claims,
+    headers: &HeaderMap,
+    parameter: &str,
+    new_value: &serde_json::Value,
+    expected_version: &str,
+    force: bool,
+) -> Result<ConfigVersion> {
+    let old_value = config_mgt::parameter_value(&*state.config.read().await, parameter);
+    let result = state.config_manager
+        .apply_change(&state.config, parameter, new_value, &claims.name, Some(expected_version), force)
+        .await;
+
+    state.audit_logger.log(AuditLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: Utc::now(),
+        username: claims.name.clone(),
+        action: "config_apply".to_string(),
+        resource: format!("config:{}", parameter),
+        ip_address: client_ip(state, headers),
+        details: serde_json::json!({
+            "parameter": parameter,
+            "old_value": old_value,
+            "new_value": new_value,
+            "version": result.as_ref().ok().map(|v| v.id.clone()),
+            "force": force,
+        }),
+        success: result.is_ok(),
+        error: result.as_ref().err().map(|e| e.to_string()),
+        request_id: None,
+        geo: None,
+        chain: None,
+    }).await;
+
+    if result.is_ok() {
+        persist_running_config(state).await;
+    }
+    result
+}
+
+/// Response for a failed `apply_config_change`; version conflicts become
+/// 409s and unsafe changes 422s
+fn config_change_error(e: anyhow::Error) -> Response {
+    let e = match e.downcast::<VersionConflict>() {
+        Ok(conflict) => return version_conflict(conflict),
+        Err(e) => e,
+    };
+    match e.downcast::<UnsafeChange>() {
+        Ok(unsafe_change) => unsafe_config_change(unsafe_change),
+        Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!("Failed to apply change: {}", e))).into_response(),
     }
 }
 
-/// Wrapper for Query<AuditFilter> to implement FromRequest
-#[derive(Debug, Deserialize)]
-struct AuditFilterWrapper(AuditFilter);
-
-impl Default for AuditFilterWrapper {
-    fn default() -> Self {
-        Self(AuditFilter::default())
-    }
+/// 422 with the critical issues the change would introduce
+fn unsafe_config_change(unsafe_change: UnsafeChange) -> Response {
+    let mut body = ApiResponse::error(unsafe_change.to_string());
+    body.data = Some(unsafe_change);
+    (StatusCode::UNPROCESSABLE_ENTITY, Json(body)).into_response()
 }
 
-/// Get pending configuration change confirmations
-async fn get_confirmations(State(state): State<AdminState>) -> impl IntoResponse {
-    let pending = state.config_confirmation.get_pending().await;
-    Json(ApiResponse::ok(pending))
+/// Only admins may force a change past the safety gate
+fn require_force_allowed(claims: &Claims, force: bool) -> Option<Response> {
+    if !force || claims.role == "admin" {
+        return None;
+    }
+    warn!("User '{}' with role '{}' denied forcing an unsafe config change", claims.name, claims.role);
+    Some((StatusCode::FORBIDDEN, Json(ApiResponse::<()>::error("Admin role required to force a config change"))).into_response())
 }
 
 /// Request a configuration change (creates confirmation request)
+#[utoipa::path(
+    post,
+    path = "/api/v1/config/confirmations",
+    tag = "config",
+    request_body = ConfigChangeRequestData,
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "force requires the admin role"),
+        (status = 409, description = "Config changed since the expected version"),
+        (status = 422, description = "The change introduces critical safety issues"),
+        (status = 428, description = "No expected version given"),
+    ),
+)]
 async fn request_config_change(
     State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
     Json(req): Json<ConfigChangeRequestData>,
-) -> impl IntoResponse {
+) -> Response {
+    let Some(expected) = expected_version(&headers, req.expected_version.clone()) else {
+        return version_required();
+    };
+    if let Some(denied) = require_force_allowed(&claims, req.force) {
+        return denied;
+    }
+    let old_value = match config_mgt::parameter_value(&*state.config.read().await, &req.parameter) {
+        Some(value) => value,
+        None => {
+            return Json(ApiResponse::<serde_json::Value>::error(format!(
+                "{} cannot be changed at runtime",
+                req.parameter
+            ))).into_response();
+        }
+    };
+
     // Validate the new value
     if let Err(e) = state
         .config_confirmation
@@ -1029,7 +8614,7 @@ async fn request_config_change(
         return Json(ApiResponse::<serde_json::Value>::error(format!(
             "Invalid value for {}: {}",
             req.parameter, e
-        )));
+        ))).into_response();
     }
 
     // Check if confirmation is required
@@ -1038,26 +8623,44 @@ async fn request_config_change(
         .requires_confirmation(&req.parameter)
     {
         // Apply immediately if no confirmation needed
-        let response = serde_json::json!({
-            "message": format!("{} updated (no confirmation required)", req.parameter),
-            "parameter": req.parameter,
-            "old_value": req.old_value,
-            "new_value": req.new_value,
-            "confirmed": true,
-            "applied": true,
-        });
-        return Json(ApiResponse::ok(response));
+        return match apply_config_change(&state, &claims, &headers, &req.parameter, &req.new_value, &expected, req.force).await {
+            Ok(version) => Json(ApiResponse::ok(serde_json::json!({
+                "message": format!("{} updated (no confirmation required)", req.parameter),
+                "parameter": req.parameter,
+                "old_value": old_value,
+                "new_value": req.new_value,
+                "version": version.id,
+                "confirmed": true,
+                "applied": true,
+            }))).into_response(),
+            Err(e) => config_change_error(e),
+        };
+    }
+
+    // A request against a stale version would only fail at apply time
+    if let Err(conflict) = state.config_manager.check_version(&expected).await {
+        return version_conflict(conflict);
     }
+    // Shown to the confirmer; the gate itself runs when the change is applied
+    let safety = {
+        let live = state.config.read().await;
+        let mut candidate = live.clone();
+        match config_mgt::set_parameter(&mut candidate, &req.parameter, &req.new_value) {
+            Ok(()) => SafetyAnalyzer::new().analyze_change(&live, &candidate),
+            Err(e) => return Json(ApiResponse::<serde_json::Value>::error(e.to_string())).into_response(),
+        }
+    };
 
     // Create confirmation request
     match state
         .config_confirmation
         .create_change_request(
             req.parameter.clone(),
-            req.old_value,
+            old_value,
             req.new_value.clone(),
-            req.username.clone(),
-            req.ip_address.clone(),
+            claims.name.clone(),
+            client_ip(&state, &headers),
+            Some(expected),
         )
         .await
     {
@@ -1072,84 +8675,188 @@ async fn request_config_change(
                 "request": request,
                 "risk_level": risk_level,
                 "meta": state.config_confirmation.get_config_meta(&req.parameter),
+                "safety": safety,
             });
-            Json(ApiResponse::ok(response))
+            Json(ApiResponse::ok(response)).into_response()
         }
         Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!(
             "Failed to create confirmation request: {}",
             e
-        ))),
+        ))).into_response(),
     }
 }
 
 /// Confirm a pending configuration change
+#[utoipa::path(
+    post,
+    path = "/api/v1/config/confirmations/{id}",
+    tag = "config",
+    params(("id" = String, Path, description = "Change request ID")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+    ),
+)]
 async fn confirm_config(
     State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
     Path(id): Path<String>,
-) -> impl IntoResponse {
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
     match state.config_confirmation.confirm_change(&id).await {
         Ok(true) => {
             let response = serde_json::json!({
                 "message": "Change confirmed. Use /apply to apply the change.",
                 "id": id
             });
-            Json(ApiResponse::ok(response))
+            Json(ApiResponse::ok(response)).into_response()
         }
         Ok(false) => {
             Json(ApiResponse::<serde_json::Value>::error(
                 "Change request not found or expired".to_string(),
-            ))
+            )).into_response()
         }
         Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!(
             "Failed to confirm change: {}",
             e
-        ))),
+        ))).into_response(),
     }
 }
 
 /// Apply a confirmed configuration change
+///
+/// The request stays pending if validation fails, so it can be cancelled or
+/// retried; the running config is only replaced once the change validates.
+/// The change must still apply to the version it was requested against, or
+/// to the version given in `If-Match`.
+#[utoipa::path(
+    post,
+    path = "/api/v1/config/confirmations/{id}/apply",
+    tag = "config",
+    params(("id" = String, Path, description = "Change request ID"), ApplyConfigQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+        (status = 409, description = "Config changed since the expected version"),
+        (status = 422, description = "The change introduces critical safety issues"),
+        (status = 428, description = "No expected version given"),
+    ),
+)]
 async fn apply_config(
     State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
     Path(id): Path<String>,
-) -> impl IntoResponse {
+    Query(query): Query<ApplyConfigQuery>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let request = match state.config_confirmation.get_request(&id).await {
+        Some(request) if !request.confirmed => {
+            return Json(ApiResponse::<serde_json::Value>::error("Change not confirmed".to_string())).into_response();
+        }
+        Some(request) if Utc::now() > request.expires_at => {
+            return Json(ApiResponse::<serde_json::Value>::error("Change request expired".to_string())).into_response();
+        }
+        Some(request) => request,
+        None => {
+            return Json(ApiResponse::<serde_json::Value>::error(
+                "Change request not found or expired".to_string(),
+            )).into_response();
+        }
+    };
+    let Some(expected) = expected_version(&headers, None).or_else(|| request.base_version.clone()) else {
+        return version_required();
+    };
+
+    let version = match apply_config_change(&state, &claims, &headers, &request.parameter, &request.new_value, &expected, query.force).await {
+        Ok(version) => version,
+        Err(e) => return config_change_error(e),
+    };
+
     match state.config_confirmation.apply_change(&id).await {
         Ok(request) => {
-            // TODO: Actually apply the config change to the running config
-            // For now, just log it
-
             let response = serde_json::json!({
-                "message": format!("Config change applied: {} = {:?}", request.parameter, request.new_value),
-                "request": request
+                "message": format!("Config change applied: {} = {}", request.parameter, request.new_value),
+                "request": request,
+                "version": version.id,
             });
-            Json(ApiResponse::ok(response))
+            Json(ApiResponse::ok(response)).into_response()
         }
         Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!(
             "Failed to apply change: {}",
             e
-        ))),
+        ))).into_response(),
     }
 }
 
 // ===== Backup API Handlers =====
 
-/// Create a new backup
-async fn create_backup(State(state): State<AdminState>) -> impl IntoResponse {
-    match state.backup_manager.create_backup().await {
-        Ok(metadata) => {
-            let response = serde_json::json!({
-                "message": "Backup created successfully",
-                "backup": metadata
-            });
-            Json(ApiResponse::ok(response))
-        }
-        Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!(
-            "Failed to create backup: {}",
-            e
-        ))),
+#[derive(Debug, Default, Deserialize, ToSchema)]
+struct CreateBackupRequest {
+    #[serde(default)]
+    kind: BackupKind,
+}
+
+/// Start a backup as a job
+///
+/// Progress and the resulting backup are reported by `/api/v1/jobs/{id}`.
+#[utoipa::path(
+    post,
+    path = "/api/v1/backup/create",
+    tag = "backup",
+    request_body(content = CreateBackupRequest, description = "Optional"),
+    responses(
+        (status = 202, description = "Standard response envelope with the started job", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+    ),
+)]
+async fn create_backup(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    body: Option<Json<CreateBackupRequest>>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
     }
+    let kind = body.map(|Json(req)| req.kind).unwrap_or_default();
+    let description = format!("{:?} backup of {}", kind, instance_name(&state));
+    let job_state = state.clone();
+    let job = state.jobs.spawn(JobKind::Backup, description, &claims.name, move |job| async move {
+        match job_state.backup_manager.create_backup_with_progress(kind, &job.progress).await {
+            Ok(metadata) => Ok(serde_json::json!({ "backup": metadata })),
+            Err(e) => {
+                if !job.progress.is_cancelled() {
+                    publish_backup_failed(&job_state, kind, &e, false).await;
+                }
+                Err(e)
+            }
+        }
+    });
+    let response = serde_json::json!({
+        "message": "Backup started",
+        "job_id": job.id,
+        "job": job,
+    });
+    (StatusCode::ACCEPTED, Json(ApiResponse::ok(response))).into_response()
 }
 
 /// List all backups
+#[utoipa::path(
+    get,
+    path = "/api/v1/backup/list",
+    tag = "backup",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
 async fn list_backups(State(state): State<AdminState>) -> impl IntoResponse {
     match state.backup_manager.list_backups() {
         Ok(backups) => {
@@ -1167,6 +8874,15 @@ async fn list_backups(State(state): State<AdminState>) -> impl IntoResponse {
 }
 
 /// Get backup statistics
+#[utoipa::path(
+    get,
+    path = "/api/v1/backup/stats",
+    tag = "backup",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
 async fn backup_stats(State(state): State<AdminState>) -> impl IntoResponse {
     match state.backup_manager.get_stats() {
         Ok(stats) => {
@@ -1183,6 +8899,16 @@ async fn backup_stats(State(state): State<AdminState>) -> impl IntoResponse {
 }
 
 /// Get a specific backup by ID
+#[utoipa::path(
+    get,
+    path = "/api/v1/backup/{id}",
+    tag = "backup",
+    params(("id" = String, Path, description = "Backup ID")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
 async fn get_backup(
     State(state): State<AdminState>,
     Path(id): Path<String>,
@@ -1190,6 +8916,7 @@ async fn get_backup(
     match state.backup_manager.load_metadata(&id) {
         Ok(metadata) => {
             let response = serde_json::json!({
+                "compatibility": state.backup_manager.compatibility(&metadata),
                 "backup": metadata
             });
             Json(ApiResponse::ok(response))
@@ -1202,72 +8929,1328 @@ async fn get_backup(
 }
 
 /// Delete a backup
+#[utoipa::path(
+    post,
+    path = "/api/v1/backup/{id}/delete",
+    tag = "backup",
+    params(("id" = String, Path, description = "Backup ID")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+    ),
+)]
 async fn delete_backup(
     State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
     Path(id): Path<String>,
-) -> impl IntoResponse {
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
     match state.backup_manager.delete_backup(&id).await {
         Ok(_) => {
             let response = serde_json::json!({
                 "message": format!("Backup {} deleted successfully", id)
             });
-            Json(ApiResponse::ok(response))
+            Json(ApiResponse::ok(response)).into_response()
         }
         Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!(
             "Failed to delete backup: {}",
             e
-        ))),
+        ))).into_response(),
+    }
+}
+
+/// First and last byte of a `Range` header's single range within `size` bytes
+///
+/// `None` asks for the whole file: no range, several ranges, or a unit other
+/// than bytes. `Some(Err(()))` is a range that can't be satisfied.
+fn byte_range(range: &str, size: u64) -> Option<Result<(u64, u64), ()>> {
+    let spec = range.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+    let (first, last) = spec.split_once('-')?;
+    let (first, last) = (first.trim(), last.trim());
+    let range = if first.is_empty() {
+        // The final `last` bytes
+        match last.parse::<u64>() {
+            Ok(suffix) if suffix > 0 && size > 0 => Ok((size.saturating_sub(suffix), size - 1)),
+            _ => Err(()),
+        }
+    } else {
+        match (first.parse::<u64>(), last.parse::<u64>()) {
+            (Ok(first), _) if first >= size => Err(()),
+            (Ok(first), Ok(last)) if first <= last => Ok((first, last.min(size - 1))),
+            (Ok(first), Err(_)) if last.is_empty() => Ok((first, size - 1)),
+            _ => Err(()),
+        }
+    };
+    Some(range)
+}
+
+/// Download a backup archive, with range requests for resuming
+///
+/// The archive is sent as stored: compressed, and encrypted if backups are.
+/// Its SHA-256 checksum is the `ETag`, so `If-Range` resumes only the same
+/// archive.
+#[utoipa::path(
+    get,
+    path = "/api/v1/backup/{id}/download",
+    tag = "backup",
+    params(("id" = String, Path, description = "Backup ID")),
+    responses(
+        (status = 200, description = "The backup archive"),
+        (status = 206, description = "The requested range of the backup archive"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "No such backup"),
+        (status = 416, description = "Range not satisfiable"),
+    ),
+)]
+async fn download_backup(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    use tokio::io::AsyncSeekExt;
+    let not_found = || (StatusCode::NOT_FOUND, Json(ApiResponse::<()>::error(format!("Backup not found: {}", id)))).into_response();
+    let Ok(metadata) = state.backup_manager.load_metadata(&id) else {
+        return not_found();
+    };
+    let Ok(mut file) = tokio::fs::File::open(&metadata.file_path).await else {
+        return not_found();
+    };
+    let size = match file.metadata().await {
+        Ok(info) => info.len(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::<()>::error(format!("Failed to read backup: {}", e)))).into_response(),
+    };
+
+    let etag = format!("\"{}\"", metadata.checksum);
+    let filename = metadata.file_path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let content_type = match filename.rsplit('.').next() {
+        Some("gz") => "application/gzip",
+        Some("tar") => "application/x-tar",
+        _ => "application/octet-stream",
+    };
+    let same_archive = headers.get(header::IF_RANGE)
+        .is_none_or(|value| value.to_str().is_ok_and(|value| value == etag));
+    let range = headers.get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .filter(|_| same_archive)
+        .and_then(|value| byte_range(value, size));
+
+    let (status, first, last) = match range {
+        None => (StatusCode::OK, 0, size.saturating_sub(1)),
+        Some(Ok((first, last))) => (StatusCode::PARTIAL_CONTENT, first, last),
+        Some(Err(())) => {
+            return (
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [(header::CONTENT_RANGE, format!("bytes */{}", size))],
+            )
+                .into_response();
+        }
+    };
+    let len = if size == 0 { 0 } else { last - first + 1 };
+    if first > 0 {
+        if let Err(e) = file.seek(std::io::SeekFrom::Start(first)).await {
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::<()>::error(format!("Failed to read backup: {}", e)))).into_response();
+        }
+    }
+    info!("User '{}' downloading backup {} (bytes {}-{} of {})", claims.name, id, first, last, size);
+
+    let mut response = (
+        status,
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+            (header::CONTENT_LENGTH, len.to_string()),
+            (header::ACCEPT_RANGES, "bytes".to_string()),
+            (header::ETAG, etag),
+        ],
+        file_body(file, len),
+    )
+        .into_response();
+    if status == StatusCode::PARTIAL_CONTENT {
+        if let Ok(value) = format!("bytes {}-{}/{}", first, last, size).parse() {
+            response.headers_mut().insert(header::CONTENT_RANGE, value);
+        }
+    }
+    response
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct BackupUploadQuery {
+    /// Expected SHA-256 of the archive, such as the `ETag` it was downloaded with
+    checksum: Option<String>,
+    /// When the backup was taken (RFC 3339); the time of the upload if omitted
+    taken_at: Option<DateTime<Utc>>,
+    /// Restore the uploaded backup over the live database; a dry run unless `confirm` is set
+    #[serde(default)]
+    restore: bool,
+    #[serde(default)]
+    confirm: bool,
+}
+
+/// Upload a backup archive taken elsewhere, optionally restoring it
+///
+/// The body is the archive as downloaded from `/api/v1/backup/{id}/download`.
+/// It is checked, checksummed and registered as a full backup. With
+/// `restore=true` a dry run restore of it is started, or with `confirm=true`
+/// too, a restore over the live database.
+#[utoipa::path(
+    post,
+    path = "/api/v1/backup/upload",
+    tag = "backup",
+    params(BackupUploadQuery),
+    request_body(content = Vec<u8>, content_type = "application/octet-stream", description = "The backup archive"),
+    responses(
+        (status = 200, description = "Standard response envelope with the imported backup", body = ApiEnvelope),
+        (status = 202, description = "Standard response envelope with the imported backup and the started restore job", body = ApiEnvelope),
+        (status = 400, description = "Invalid archive"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+        (status = 413, description = "Archive larger than BACKUP_UPLOAD_MAX_MB"),
+    ),
+)]
+async fn upload_backup(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Query(query): Query<BackupUploadQuery>,
+    body: Body,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    use tokio::io::AsyncWriteExt;
+    use tokio_stream::StreamExt;
+    let error = |status: StatusCode, message: String| (status, Json(ApiResponse::<()>::error(message))).into_response();
+    let max_bytes = state.backup_manager.config().max_upload_bytes;
+    let declared = headers.get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if declared.is_some_and(|len| len > max_bytes) {
+        return error(StatusCode::PAYLOAD_TOO_LARGE, format!("Archive larger than {} bytes", max_bytes));
+    }
+
+    let upload = match state.backup_manager.upload_file() {
+        Ok(upload) => upload,
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to receive upload: {:#}", e)),
+    };
+    let mut file = match upload.reopen() {
+        Ok(file) => tokio::fs::File::from_std(file),
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to receive upload: {}", e)),
+    };
+    let mut received = 0u64;
+    let mut chunks = body.into_data_stream();
+    while let Some(chunk) = chunks.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => return error(StatusCode::BAD_REQUEST, format!("Upload interrupted: {}", e)),
+        };
+        received += chunk.len() as u64;
+        if received > max_bytes {
+            return error(StatusCode::PAYLOAD_TOO_LARGE, format!("Archive larger than {} bytes", max_bytes));
+        }
+        if let Err(e) = file.write_all(&chunk).await {
+            return error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to store upload: {}", e));
+        }
     }
+    if let Err(e) = file.sync_all().await {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to store upload: {}", e));
+    }
+    drop(file);
+
+    let import = BackupImport { checksum: query.checksum, taken_at: query.taken_at };
+    let metadata = match state.backup_manager.import_backup(upload, &import).await {
+        Ok(metadata) => metadata,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("Failed to import backup: {:#}", e)),
+    };
+    info!("User '{}' uploaded backup {} ({} bytes)", claims.name, metadata.id, received);
+    let compatibility = state.backup_manager.compatibility(&metadata);
+    if !query.restore {
+        return Json(ApiResponse::ok(serde_json::json!({
+            "message": "Backup uploaded",
+            "backup": metadata,
+            "compatibility": compatibility,
+        }))).into_response();
+    }
+
+    let options = RestoreOptions { dry_run: !query.confirm, ..RestoreOptions::default() };
+    let job = start_restore(&state, &claims, &headers, metadata.id.clone(), options);
+    let message = if query.confirm {
+        "Backup uploaded, restore started"
+    } else {
+        "Backup uploaded, dry run restore started; upload with confirm=true to restore it"
+    };
+    (StatusCode::ACCEPTED, Json(ApiResponse::ok(serde_json::json!({
+        "message": message,
+        "backup": metadata,
+        "compatibility": compatibility,
+        "job_id": job.id,
+        "job": job,
+    })))).into_response()
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+struct RestoreBackupRequest {
+    #[serde(default)]
+    dry_run: bool,
+    /// Restore into this directory under the backup dir instead of over the live database
+    target_dir: Option<String>,
+    /// Restore only these column families, leaving the others untouched
+    #[serde(default)]
+    column_families: Vec<String>,
+    /// Restore even if the backup was taken by an incompatible version
+    #[serde(default)]
+    force: bool,
 }
 
-/// Restore from a backup
+/// Start a restore from a backup as a job
+///
+/// Unless the pool is already in maintenance mode, it is entered for a
+/// restore over the live database and left again afterwards. Progress and
+/// the restore plan are reported by `/api/v1/jobs/{id}`.
+#[utoipa::path(
+    post,
+    path = "/api/v1/backup/{id}/restore",
+    tag = "backup",
+    params(("id" = String, Path, description = "Backup ID")),
+    request_body(content = RestoreBackupRequest, description = "Optional"),
+    responses(
+        (status = 202, description = "Standard response envelope with the started job", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+    ),
+)]
 async fn restore_backup(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    body: Option<Json<RestoreBackupRequest>>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let req = body.map(|Json(req)| req).unwrap_or_default();
+    let target_dir = match req.target_dir.as_deref().map(|name| state.backup_manager.inspection_dir(name)) {
+        Some(Ok(dir)) => Some(dir),
+        Some(Err(e)) => return Json(ApiResponse::<serde_json::Value>::error(e.to_string())).into_response(),
+        None => None,
+    };
+    let metadata = match state.backup_manager.load_metadata(&id) {
+        Ok(metadata) => metadata,
+        Err(e) => return Json(ApiResponse::<serde_json::Value>::error(format!("Failed to restore backup: {}", e))).into_response(),
+    };
+    let compatibility = state.backup_manager.compatibility(&metadata);
+    if !compatibility.compatible() && !req.dry_run && !req.force {
+        return Json(ApiResponse::<serde_json::Value>::error(format!(
+            "Backup {} is not compatible with this version: {}; set force to restore anyway",
+            id,
+            compatibility.problems.join("; ")
+        ))).into_response();
+    }
+    let options = RestoreOptions {
+        dry_run: req.dry_run,
+        target_dir,
+        column_families: req.column_families,
+        force: req.force,
+    };
+    let job = start_restore(&state, &claims, &headers, id, options);
+    let response = serde_json::json!({
+        "message": "Restore started",
+        "job_id": job.id,
+        "job": job,
+    });
+    (StatusCode::ACCEPTED, Json(ApiResponse::ok(response))).into_response()
+}
+
+/// Start a restore as a job, entering maintenance mode for a restore over the live database
+fn start_restore(state: &AdminState, claims: &Claims, headers: &HeaderMap, id: String, options: RestoreOptions) -> Job {
+    let live = !options.dry_run && options.target_dir.is_none();
+    let ip_address = client_ip(state, headers);
+    let description = match (options.dry_run, live) {
+        (true, _) => format!("Dry run restore of backup {}", id),
+        (false, true) => format!("Restore of backup {} over {}", id, instance_name(state)),
+        (false, false) => format!("Restore of backup {} for inspection", id),
+    };
+    let job_state = state.clone();
+    let username = claims.name.clone();
+    state.jobs.spawn(JobKind::Restore, description, &claims.name, move |job| async move {
+        let state = job_state;
+        let progress = job.progress;
+        // Maintenance entered here is left even if the job is dropped mid-restore
+        let mut entered = None;
+        if live && !state.maintenance.is_active() {
+            progress.set_phase("draining");
+            let result = state.maintenance
+                .enter(&format!("restore {}", id), &username, std::time::Duration::from_secs(MAINTENANCE_DRAIN_SECS))
+                .await;
+            audit_maintenance(&state, &username, &ip_address, "maintenance_enter", &result).await;
+            result?;
+            entered = Some(state.maintenance.exit_on_drop());
+        }
+
+        let result = state.backup_manager.restore_backup_with_progress(&id, &options, &progress).await;
+
+        if let Some(guard) = entered {
+            let exited = guard.exit();
+            audit_maintenance(&state, &username, &ip_address, "maintenance_exit", &exited).await;
+        }
+        let plan = result?;
+        let families: Vec<&str> = plan.column_families.iter().map(|cf| cf.name.as_str()).collect();
+        let message = match (plan.dry_run, families.is_empty()) {
+            (true, true) => format!("Restoring backup {} would change {} file(s)", id, plan.changes.len()),
+            (true, false) => format!("Restoring column families {} of backup {} would write {} key(s)",
+                families.join(", "), id, plan.column_families.iter().map(|cf| cf.keys_written).sum::<u64>()),
+            (false, true) => format!("Backup {} restored successfully to {}", id, plan.target.display()),
+            (false, false) => format!("Column families {} of backup {} restored to {}",
+                families.join(", "), id, plan.target.display()),
+        };
+        let note = match (plan.dry_run, live) {
+            (true, _) => None,
+            (false, true) => Some("Database service restart may be required"),
+            (false, false) => Some("Live database left unchanged"),
+        };
+        Ok(serde_json::json!({
+            "message": message,
+            "note": note,
+            "plan": plan,
+        }))
+    })
+}
+
+// ===== Job API Handlers =====
+
+/// Jobs started since the admin server came up, newest first
+///
+/// Admins see every job, other users only their own.
+#[utoipa::path(
+    get,
+    path = "/api/v1/jobs",
+    tag = "jobs",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn list_jobs(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    let is_admin = claims.role == "admin";
+    let jobs: Vec<Job> = state.jobs.list()
+        .into_iter()
+        .filter(|job| job.visible_to(&claims.name, is_admin))
+        .collect();
+    Json(ApiResponse::ok(jobs))
+}
+
+/// A job the caller may see: one they started, or any job for an admin
+///
+/// Other users' jobs are reported as missing, like unknown ids.
+fn visible_job(state: &AdminState, claims: &Claims, id: &str) -> Option<Job> {
+    state.jobs.get(id).filter(|job| job.visible_to(&claims.name, claims.role == "admin"))
+}
+
+/// State, progress and outcome of a job
+#[utoipa::path(
+    get,
+    path = "/api/v1/jobs/{id}",
+    tag = "jobs",
+    params(("id" = String, Path, description = "Job ID")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "No such job, or another user's job"),
+    ),
+)]
+async fn get_job(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Response {
+    match visible_job(&state, &claims, &id) {
+        Some(job) => Json(ApiResponse::ok(job)).into_response(),
+        None => (StatusCode::NOT_FOUND, Json(ApiResponse::<()>::error(format!("Job not found: {}", id)))).into_response(),
+    }
+}
+
+/// The file a completed job produced, such as a background export
+#[utoipa::path(
+    get,
+    path = "/api/v1/jobs/{id}/download",
+    tag = "jobs",
+    params(("id" = String, Path, description = "Job ID")),
+    responses(
+        (status = 200, description = "The job's file"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "No such job, or it produced no file"),
+    ),
+)]
+async fn download_job(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Response {
+    let not_found = || (StatusCode::NOT_FOUND, Json(ApiResponse::<()>::error(format!("No download for job: {}", id)))).into_response();
+    let (Some(job), Some(path)) = (visible_job(&state, &claims, &id), state.jobs.artifact(&id)) else {
+        return not_found();
+    };
+    let Ok(file) = tokio::fs::File::open(&path).await else {
+        return not_found();
+    };
+    let result = job.result.unwrap_or_default();
+    let filename = result["filename"].as_str().map(str::to_string)
+        .unwrap_or_else(|| path.file_name().unwrap_or_default().to_string_lossy().into_owned());
+    let content_type = result["content_type"].as_str().unwrap_or("application/octet-stream").to_string();
+
+    (
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        file_body(file, u64::MAX),
+    )
+        .into_response()
+}
+
+/// A body streaming at most `len` bytes of `file` from its current position
+fn file_body(file: tokio::fs::File, len: u64) -> Body {
+    use tokio::io::AsyncReadExt;
+    let mut reader = file.take(len);
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<Vec<u8>, std::io::Error>>(4);
+    tokio::spawn(async move {
+        loop {
+            let mut chunk = vec![0u8; 64 * 1024];
+            let piece = match reader.read(&mut chunk).await {
+                Ok(0) => return,
+                Ok(n) => {
+                    chunk.truncate(n);
+                    Ok(chunk)
+                }
+                Err(e) => Err(e),
+            };
+            let failed = piece.is_err();
+            if tx.send(piece).await.is_err() || failed {
+                return;
+            }
+        }
+    });
+    Body::from_stream(tokio_stream::wrappers::ReceiverStream::new(rx))
+}
+
+/// Ask a queued or running job to stop
+///
+/// The job stops at its next safe point and then reports `cancelled`; a
+/// restore past the point of replacing files runs to completion.
+#[utoipa::path(
+    post,
+    path = "/api/v1/jobs/{id}/cancel",
+    tag = "jobs",
+    params(("id" = String, Path, description = "Job ID")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Operator role required"),
+        (status = 409, description = "Job not found or already finished"),
+    ),
+)]
+async fn cancel_job(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Response {
+    if let Some(denied) = require_operator(&claims) {
+        return denied;
+    }
+    let result = match visible_job(&state, &claims, &id) {
+        Some(_) => state.jobs.cancel(&id),
+        None => Err(anyhow::anyhow!("Job not found: {}", id)),
+    };
+    state.audit_logger.log(AuditLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: Utc::now(),
+        username: claims.name.clone(),
+        action: "job_cancel".to_string(),
+        resource: format!("job:{}", id),
+        ip_address: client_ip(&state, &headers),
+        details: serde_json::json!({
+            "kind": result.as_ref().ok().map(|job| job.kind),
+            "description": result.as_ref().ok().map(|job| job.description.clone()),
+        }),
+        success: result.is_ok(),
+        error: result.as_ref().err().map(|e| e.to_string()),
+        request_id: None,
+        geo: None,
+        chain: None,
+    }).await;
+    match result {
+        Ok(job) => Json(ApiResponse::ok(job)).into_response(),
+        Err(e) => (StatusCode::CONFLICT, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct BackupAtQuery {
+    /// RFC 3339 timestamp
+    time: DateTime<Utc>,
+}
+
+/// Newest backup taken at or before a point in time
+#[utoipa::path(
+    get,
+    path = "/api/v1/backup/at",
+    tag = "backup",
+    params(BackupAtQuery),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn backup_at(
+    State(state): State<AdminState>,
+    Query(query): Query<BackupAtQuery>,
+) -> impl IntoResponse {
+    match state.backup_manager.backup_at(query.time) {
+        Ok(Some(metadata)) => Json(ApiResponse::ok(serde_json::json!({ "backup": metadata }))),
+        Ok(None) => Json(ApiResponse::<serde_json::Value>::error(format!(
+            "No backup taken at or before {}",
+            query.time.to_rfc3339()
+        ))),
+        Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!(
+            "Failed to list backups: {}",
+            e
+        ))),
+    }
+}
+
+/// Verify every file in a backup against its recorded checksums
+#[utoipa::path(
+    get,
+    path = "/api/v1/backup/{id}/verify",
+    tag = "backup",
+    params(("id" = String, Path, description = "Backup ID")),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn verify_backup(
     State(state): State<AdminState>,
     Path(id): Path<String>,
 ) -> impl IntoResponse {
-    match state.backup_manager.restore_backup(&id, None).await {
-        Ok(_) => {
+    match state.backup_manager.verify_backup(&id).await {
+        Ok(report) => {
             let response = serde_json::json!({
-                "message": format!("Backup {} restored successfully", id),
-                "note": "Database service restart may be required"
+                "message": if report.valid {
+                    format!("Backup {} verified successfully", id)
+                } else {
+                    format!("Backup {} failed verification", id)
+                },
+                "verification": report
             });
             Json(ApiResponse::ok(response))
         }
         Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!(
-            "Failed to restore backup: {}",
+            "Failed to verify backup: {}",
             e
         ))),
     }
 }
 
 /// Cleanup old backups based on retention policy
-async fn cleanup_backups(State(state): State<AdminState>) -> impl IntoResponse {
+#[utoipa::path(
+    post,
+    path = "/api/v1/backup/cleanup",
+    tag = "backup",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+    ),
+)]
+async fn cleanup_backups(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
     match state.backup_manager.cleanup_old_backups().await {
         Ok(count) => {
             let response = serde_json::json!({
                 "message": format!("Cleaned up {} old backup(s)", count),
                 "deleted_count": count
             });
-            Json(ApiResponse::ok(response))
+            Json(ApiResponse::ok(response)).into_response()
         }
         Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!(
             "Failed to cleanup backups: {}",
             e
-        ))),
+        ))).into_response(),
+    }
+}
+
+/// Rebuild the backup catalog from the metadata files on disk
+#[utoipa::path(
+    post,
+    path = "/api/v1/backup/catalog/repair",
+    tag = "backup",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Admin role required"),
+    ),
+)]
+async fn repair_backup_catalog(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    match state.backup_manager.repair_catalog() {
+        Ok(repair) => Json(ApiResponse::ok(repair)).into_response(),
+        Err(e) => Json(ApiResponse::<CatalogRepair>::error(format!(
+            "Failed to repair backup catalog: {}",
+            e
+        ))).into_response(),
+    }
+}
+
+// ===== Store =====
+
+/// Size, live data and estimated keys of each column family of the store
+#[utoipa::path(
+    get,
+    path = "/api/v1/store/stats",
+    tag = "store",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn store_stats(State(state): State<AdminState>) -> impl IntoResponse {
+    match state.storage.stats().await {
+        Ok(stats) => Json(ApiResponse::ok(stats)),
+        Err(e) => Json(ApiResponse::error(format!("Failed to read store stats: {:#}", e))),
+    }
+}
+
+/// Compact every column family of the store to reclaim space, as a job
+///
+/// The job's result is the run, with its `error` set if the store couldn't be
+/// compacted, e.g. because the pool holds it open. A compaction can't be
+/// cancelled once it has started.
+#[utoipa::path(
+    post,
+    path = "/api/v1/store/compact",
+    tag = "store",
+    responses(
+        (status = 202, description = "Standard response envelope with the started job", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 409, description = "A compaction is already running"),
+    ),
+)]
+async fn compact_store(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    if state.storage.is_compacting() {
+        return (StatusCode::CONFLICT, Json(ApiResponse::<()>::error("A compaction is already in progress"))).into_response();
+    }
+    let storage = state.storage.clone();
+    let requested_by = claims.name.clone();
+    let description = format!("Compaction of {}", storage.path().display());
+    let job = state.jobs.spawn(JobKind::Compaction, description, &claims.name, move |job| async move {
+        job.progress.set_phase("compacting");
+        let run = storage.compact(CompactionTrigger::Manual, Some(requested_by)).await?;
+        Ok(serde_json::json!({
+            "run": run,
+            "reclaimed_bytes": run.reclaimed_bytes(),
+        }))
+    });
+    let response = serde_json::json!({
+        "message": "Compaction started",
+        "job_id": job.id,
+        "job": job,
+    });
+    (StatusCode::ACCEPTED, Json(ApiResponse::ok(response))).into_response()
+}
+
+/// Recent compactions, newest first, and the next scheduled one
+#[utoipa::path(
+    get,
+    path = "/api/v1/store/compactions",
+    tag = "store",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn store_compactions(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(ApiResponse::ok(serde_json::json!({
+        "schedule": state.storage.schedule().map(|cron| cron.to_string()),
+        "next_run": state.storage.next_scheduled(Utc::now()),
+        "runs": state.storage.history().await,
+    })))
+}
+
+/// Detected corruption, the latest diagnosis and earlier ones, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/store/doctor",
+    tag = "store",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn store_doctor_status(State(state): State<AdminState>) -> impl IntoResponse {
+    let status = match state.store_doctor.status().await {
+        Ok(status) => status,
+        Err(e) => return Json(ApiResponse::<serde_json::Value>::error(format!("{:#}", e))),
+    };
+    Json(ApiResponse::ok(serde_json::json!({
+        "status": status,
+        "latest": state.store_doctor.latest().await,
+        "reports": state.store_doctor.history().await,
+    })))
+}
+
+/// Read the whole store and, if it is corrupted, repair a copy and compare it
+/// against the last good backup, as a job
+///
+/// The job's result is the report. The live store is only read; a repaired
+/// copy is left in the staging directory for an operator to restore.
+#[utoipa::path(
+    post,
+    path = "/api/v1/store/doctor",
+    tag = "store",
+    responses(
+        (status = 202, description = "Standard response envelope with the started job", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 409, description = "A diagnosis is already running"),
+    ),
+)]
+async fn run_store_diagnosis(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    if state.store_doctor.is_running() {
+        return (StatusCode::CONFLICT, Json(ApiResponse::<()>::error("A store diagnosis is already in progress"))).into_response();
+    }
+    let (doctor, backups) = (state.store_doctor.clone(), state.backup_manager.clone());
+    let requested_by = claims.name.clone();
+    let description = format!("Diagnosis of {}", doctor.path().display());
+    let job = state.jobs.spawn(JobKind::StoreDoctor, description, &claims.name, move |job| async move {
+        job.progress.set_phase("diagnosing");
+        let report = doctor.diagnose(Some(&backups), DoctorTrigger::Manual, Some(requested_by)).await?;
+        Ok(serde_json::to_value(report)?)
+    });
+    let response = serde_json::json!({
+        "message": "Store diagnosis started",
+        "job_id": job.id,
+        "job": job,
+    });
+    (StatusCode::ACCEPTED, Json(ApiResponse::ok(response))).into_response()
+}
+
+// ===== Maintenance Mode =====
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+struct EnterMaintenanceRequest {
+    reason: Option<String>,
+}
+
+/// Record entering or leaving maintenance mode in the audit log
+async fn audit_maintenance(
+    state: &AdminState,
+    username: &str,
+    ip_address: &str,
+    action: &str,
+    result: &Result<MaintenanceInfo>,
+) {
+    state.audit_logger.log(AuditLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: Utc::now(),
+        username: username.to_string(),
+        action: action.to_string(),
+        resource: "maintenance".to_string(),
+        ip_address: ip_address.to_string(),
+        details: serde_json::json!({ "maintenance": result.as_ref().ok() }),
+        success: result.is_ok(),
+        error: result.as_ref().err().map(|e| e.to_string()),
+        request_id: None,
+        geo: None,
+        chain: None,
+    }).await;
+}
+
+/// Whether the pool is in maintenance mode, and why
+#[utoipa::path(
+    get,
+    path = "/api/v1/maintenance",
+    tag = "maintenance",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn maintenance_status(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(ApiResponse::ok(state.maintenance.status()))
+}
+
+/// Stop accepting writes and wait for running ones to finish (admin only)
+#[utoipa::path(
+    post,
+    path = "/api/v1/maintenance/enter",
+    tag = "maintenance",
+    request_body(content = EnterMaintenanceRequest, description = "Optional"),
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn enter_maintenance(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    body: Option<Json<EnterMaintenanceRequest>>,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let reason = body
+        .and_then(|Json(req)| req.reason)
+        .unwrap_or_else(|| "manual".to_string());
+    let result = state.maintenance
+        .enter(&reason, &claims.name, std::time::Duration::from_secs(MAINTENANCE_DRAIN_SECS))
+        .await;
+    audit_maintenance(&state, &claims.name, &client_ip(&state, &headers), "maintenance_enter", &result).await;
+    match result {
+        Ok(info) => Json(ApiResponse::ok(info)).into_response(),
+        Err(e) => (StatusCode::CONFLICT, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
+    }
+}
+
+/// Resume accepting writes (admin only)
+#[utoipa::path(
+    post,
+    path = "/api/v1/maintenance/exit",
+    tag = "maintenance",
+    responses(
+        (status = 200, description = "Standard response envelope", body = ApiEnvelope),
+        (status = 401, description = "Missing or invalid token"),
+    ),
+)]
+async fn exit_maintenance(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+) -> Response {
+    if let Some(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let result = state.maintenance.exit();
+    audit_maintenance(&state, &claims.name, &client_ip(&state, &headers), "maintenance_exit", &result).await;
+    match result {
+        Ok(info) => Json(ApiResponse::ok(info)).into_response(),
+        Err(e) => (StatusCode::CONFLICT, Json(ApiResponse::<()>::error(e.to_string()))).into_response(),
     }
 }
 
 /// Data for creating a config change request
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct ConfigChangeRequestData {
     pub parameter: String,
-    pub old_value: serde_json::Value,
     pub new_value: serde_json::Value,
-    pub username: String,
-    pub ip_address: String,
+    /// Version the change was made against; `If-Match` may be sent instead
+    pub expected_version: Option<String>,
+    /// Apply even if the change introduces critical safety issues (admin
+    /// only); only used by changes that need no confirmation
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ApplyConfigQuery {
+    /// Apply even if the change introduces critical safety issues (admin only)
+    #[serde(default)]
+    force: bool,
+}
+
+// ===== API Docs =====
+
+/// OpenAPI description of the admin API, generated from the handler annotations
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "DMPool Admin API", description = "Administration API of the DMPool mining pool"),
+    paths(
+        index,
+        health,
+        health_live,
+        health_ready,
+        services_status,
+        login,
+        login_2fa,
+        login_webauthn,
+        refresh_token,
+        public_miner_stats,
+        live_ws,
+        logs_stream,
+        alerts_stream,
+        dashboard,
+        dashboard_history,
+        list_connections,
+        get_config,
+        update_config,
+        config_schema,
+        simulate_vardiff,
+        reload_config,
+        list_config_versions,
+        config_version_diff,
+        rollback_config_version,
+        workers_list,
+        export_workers,
+        worker_detail,
+        export_shares,
+        worker_hashrate,
+        pool_hashrate,
+        workers_by_address,
+        address_workers,
+        list_farms,
+        get_farm,
+        set_farm,
+        delete_farm,
+        farm_hashrate,
+        ban_worker,
+        unban_worker,
+        add_worker_tag,
+        remove_worker_tag,
+        worker_watch_settings,
+        set_worker_watch,
+        list_bans,
+        create_ban,
+        delete_ban,
+        list_rate_limit_rules,
+        create_rate_limit_rule,
+        delete_rate_limit_rule,
+        blocks_list,
+        block_detail,
+        block_status,
+        pplns_preview,
+        pplns_validate,
+        pplns_replay,
+        sharechain_orphans,
+        difficulty_distribution,
+        payout_history,
+        payout_pending,
+        payout_blocks,
+        mempool_status,
+        schedule_payouts,
+        payout_detail,
+        mark_payout_paid,
+        cancel_payout,
+        broadcast_payout,
+        fee_report,
+        fee_blocks,
+        logs,
+        health_history,
+        safety_check,
+        audit_logs,
+        audit_search,
+        audit_stats,
+        audit_verify,
+        audit_rotate,
+        audit_export,
+        get_confirmations,
+        request_config_change,
+        confirm_config,
+        apply_config,
+        create_backup,
+        list_backups,
+        backup_stats,
+        backup_at,
+        get_backup,
+        delete_backup,
+        download_backup,
+        upload_backup,
+        repair_backup_catalog,
+        restore_backup,
+        verify_backup,
+        cleanup_backups,
+        list_jobs,
+        get_job,
+        cancel_job,
+        download_job,
+        store_stats,
+        compact_store,
+        store_compactions,
+        store_doctor_status,
+        run_store_diagnosis,
+        maintenance_status,
+        enter_maintenance,
+        exit_maintenance,
+        active_alerts,
+        alert_history,
+        acknowledge_alert,
+        list_users,
+        create_user,
+        delete_user,
+        disable_user,
+        enable_user,
+        reset_user_password,
+        revoke_user_sessions,
+        unlock_user,
+        list_lockouts,
+        unlock_ip,
+        password_status,
+        change_password,
+        logout,
+        list_sessions,
+        revoke_session,
+        list_login_attempts,
+        two_factor_status,
+        two_factor_setup,
+        two_factor_enable,
+        two_factor_disable,
+        two_factor_recovery_codes,
+        list_security_keys,
+        start_security_key_registration,
+        finish_security_key_registration,
+        delete_security_key,
+        secrets_status,
+        reload_jwt_keys,
+        list_instances,
+        instances_overview,
+        miner_challenge,
+        earnings_estimate,
+        ingest_events,
+        miner_token,
+        list_miner_tokens,
+        create_miner_token,
+        revoke_miner_token,
+        own_miner_stats,
+        miner_payout_threshold,
+        set_miner_payout_threshold,
+        list_miner_webhooks,
+        register_miner_webhook,
+        delete_miner_webhook,
+        own_worker_watch_settings,
+        set_own_worker_watch,
+        own_miner_settings,
+        set_own_miner_settings,
+        set_signed_miner_settings,
+        list_miner_settings,
+        get_miner_settings,
+        set_miner_settings,
+        delete_miner_settings,
+        own_report_subscription,
+        set_report_subscription,
+        delete_report_subscription,
+        list_report_subscriptions,
+        preview_report,
+        send_report_now,
+        list_webhooks,
+        create_webhook,
+        delete_webhook,
+        list_webhook_deliveries,
+        retry_webhook_delivery,
+        restart_status,
+        restart_pool
+    ),
+    components(schemas(
+        ApiEnvelope,
+        LoginRequest,
+        LoginResponse,
+        dmpool::auth::UserInfo,
+        LoginResult,
+        RefreshRequest,
+        TwoFactorLogin,
+        SecurityKeyLogin,
+        AssertionCredential,
+        AssertionResponse,
+        RegisterSecurityKeyRequest,
+        RegistrationCredential,
+        AttestationResponse,
+        BackupKind,
+        ConfigUpdate,
+        RollbackRequest,
+        ReloadConfigRequest,
+        BanRequest,
+        AddTagRequest,
+        CreateBanRequest,
+        RateLimitRuleRequest,
+        BroadcastPayoutRequest,
+        ConfigChangeRequestData,
+        CreateBackupRequest,
+        RestoreBackupRequest,
+        EnterMaintenanceRequest,
+        CreateUserRequest,
+        ResetPasswordRequest,
+        ChangePasswordRequest,
+        TwoFactorCodeRequest,
+        MinerChallengeRequest,
+        MinerTokenRequest,
+        CreateMinerTokenRequest,
+        PayoutThresholdRequest,
+        MinerWebhookRequest,
+        MinerSettingsRequest,
+        FarmRequest,
+        SignedMinerSettingsRequest,
+        ReportSubscriptionRequest,
+        SendReportRequest,
+        dmpool::reports::ReportPeriod,
+        WorkerWatchRequest,
+        MinerEvent,
+        CreateWebhookRequest,
+        OutboxEvent,
+        DeliveryStatus,
+        RestartRequest,
+        StratumEvent,
+        dmpool::ingest::RejectReason,
+    )),
+    modifiers(&BearerAuth),
+    security(("bearer_auth" = [])),
+)]
+struct ApiDoc;
+
+/// OpenAPI description of the public stats API on `PUBLIC_API_PORT`
+///
+/// Kept apart from [`ApiDoc`], as `/api/v1/blocks` is a different operation there.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "DMPool Public API", description = "Read-only stats of the DMPool mining pool"),
+    paths(public_miner_stats, public_blocks, earnings_estimate),
+    components(schemas(ApiEnvelope)),
+)]
+struct PublicApiDoc;
+
+/// Registers the JWT bearer scheme protected routes require
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).bearer_format("JWT").build()),
+        );
+    }
+}
+
+/// OpenAPI specification of this API
+async fn openapi_json() -> impl IntoResponse {
+    Json(ApiDoc::openapi())
+}
+
+/// OpenAPI specification of the public stats API
+async fn public_openapi_json() -> impl IntoResponse {
+    Json(PublicApiDoc::openapi())
+}
+
+/// Swagger UI for the OpenAPI specification
+async fn swagger_ui() -> impl IntoResponse {
+    Html(SWAGGER_UI_HTML)
 }
 
 /// 404 handler
 async fn not_found() -> impl IntoResponse {
     (StatusCode::NOT_FOUND, "Not Found")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(role: &str) -> Claims {
+        Claims {
+            sub: role.to_string(),
+            name: role.to_string(),
+            role: role.to_string(),
+            iat: 0,
+            exp: i64::MAX,
+            must_change_password: false,
+            sid: String::new(),
+            scopes: Vec::new(),
+            must_enroll_2fa: false,
+        }
+    }
+
+    #[test]
+    fn test_viewers_are_refused_privileged_routes() {
+        type RoleCheck = fn(&Claims) -> Option<Response>;
+        let routes: &[(&str, RoleCheck)] = &[
+            ("POST /backup/create", require_admin),
+            ("POST /backup/:id/delete", require_admin),
+            ("POST /backup/cleanup", require_admin),
+            ("POST /config/reload", require_admin),
+            ("POST /config/confirmations/:id", require_admin),
+            ("POST /audit/rotate", require_admin),
+            ("POST /audit/export", require_admin),
+            ("POST /workers/:address/ban", require_operator),
+            ("POST /workers/:address/unban", require_operator),
+            ("POST /workers/:address/tags", require_operator),
+            ("POST /workers/:address/tags/:tag", require_operator),
+            ("POST /workers/:address/watch", require_operator),
+            ("POST /jobs/:id/cancel", require_operator),
+            ("GET /logs", require_operator),
+            ("GET /logs/stream", require_operator),
+        ];
+        for (route, check) in routes {
+            let denied = check(&claims("viewer")).unwrap_or_else(|| panic!("viewer allowed on {}", route));
+            assert_eq!(denied.status(), StatusCode::FORBIDDEN, "{}", route);
+            assert!(check(&claims("admin")).is_none(), "{}", route);
+        }
+        assert!(require_operator(&claims("operator")).is_none());
+        assert_eq!(require_admin(&claims("operator")).unwrap().status(), StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn test_docs_are_self_contained_and_document_each_port() {
+        for asset in ["/swagger-ui/5.17.14/swagger-ui.css", "/swagger-ui/5.17.14/swagger-ui-bundle.js"] {
+            assert!(SWAGGER_UI_HTML.contains(asset));
+            assert!(assets::files().contains(&asset[1..].to_string()), "{} not embedded", asset);
+        }
+        assert!(!SWAGGER_UI_HTML.contains("https://"));
+
+        let operation = |doc: utoipa::openapi::OpenApi| {
+            doc.paths.paths["/api/v1/blocks"].operations[&utoipa::openapi::PathItemType::Get].operation_id.clone()
+        };
+        assert_eq!(operation(ApiDoc::openapi()).as_deref(), Some("blocks_list"));
+        assert_eq!(operation(PublicApiDoc::openapi()).as_deref(), Some("public_blocks"));
+    }
+
+    #[tokio::test]
+    async fn test_ingest_socket_is_private_from_the_start() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ingest.sock");
+        let first = bind_private_socket(&path).unwrap();
+        drop(first);
+        // A socket left by a previous run is replaced
+        let _listener = bind_private_socket(&path).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o660);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+        tokio::net::UnixStream::connect(&path).await.unwrap();
+    }
+}
@@ -14,9 +14,474 @@
 // You should have received a copy of the GNU General Public License along with
 // Hydra-Pool. If not, see <https://www.gnu.org/licenses/>.
 
+//! Pool management from the command line
+//!
+//! Works directly on the store, data directory and config file, reading the
+//! same environment variables as the admin server, so it can be scripted and
+//! used while the admin server is down. Other subcommands, like `gen-auth`,
+//! are handled by the p2poolv2 CLI.
+
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+use dmpool::auth::{AuthManager, PasswordPolicy};
+use dmpool::backup::{BackupConfig, BackupKey, BackupKind, BackupManager, RestoreOptions, StorePathCheckpoint};
+use dmpool::config_mgt::{self, ConfigManager};
+use dmpool::health::{self, HealthChecker};
+use dmpool::pplns_validator::PplnsSimulator;
+use dmpool::safety::SafetyAnalyzer;
 use p2poolv2_cli::commands;
-use std::error::Error;
+use p2poolv2_lib::config::Config;
+use p2poolv2_lib::store::Store;
+use serde::Serialize;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::process::exit;
+use std::sync::Arc;
+
+/// Reward the PPLNS window is validated against, 3.125 BTC
+const VALIDATE_REWARD_SATS: u64 = 312_500_000;
+
+/// Audit entries loaded for an analytics backfill
+#[cfg(feature = "analytics")]
+const AUDIT_BACKFILL_ENTRIES: usize = 1_000_000;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about = "DMPool management tool", long_about = None)]
+struct Cli {
+    /// Pool config file
+    #[arg(short, long, env = "CONFIG_PATH", default_value = "config.toml")]
+    config: PathBuf,
+    /// Admin data directory with users and config versions
+    #[arg(long, env = "DMP_DATA_DIR", default_value = "./data")]
+    data_dir: PathBuf,
+    /// Print results as JSON
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Create, list and restore store backups
+    #[command(subcommand)]
+    Backup(BackupCommand),
+    /// Manage admin users
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Read and change runtime parameters in the config file
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Check the PPLNS share window
+    #[command(subcommand)]
+    Pplns(PplnsCommand),
+    /// Check the store, bitcoin node and stratum port; exits 1 when unhealthy
+    Health,
+    /// Mirror pool data into the SQL analytics database
+    #[cfg(feature = "analytics")]
+    Analytics {
+        /// `sqlite:` or `postgres:` URL
+        #[arg(long, env = "ANALYTICS_DATABASE_URL")]
+        database_url: String,
+        #[command(subcommand)]
+        command: AnalyticsCommand,
+    },
+    /// p2poolv2 CLI commands
+    #[command(external_subcommand)]
+    Pool(#[allow(dead_code)] Vec<String>),
+}
+
+#[derive(Subcommand, Debug)]
+enum BackupCommand {
+    /// Back up the store
+    Create {
+        #[arg(long, default_value = "full")]
+        kind: BackupKind,
+    },
+    /// List backups, newest first
+    List,
+    /// Restore a backup over the store; stop the pool first
+    Restore {
+        id: String,
+        /// Only show what would change
+        #[arg(long)]
+        dry_run: bool,
+        /// Restore into this directory instead of over the store
+        #[arg(long)]
+        target_dir: Option<PathBuf>,
+        /// Restore only this column family; repeat for more
+        #[arg(long = "column-family")]
+        column_families: Vec<String>,
+        /// Restore even if the backup was taken by an incompatible version
+        #[arg(long)]
+        force: bool,
+    },
+    /// Rebuild the backup catalog from the metadata files
+    RepairCatalog,
+}
+
+#[derive(Subcommand, Debug)]
+enum UserCommand {
+    /// Add a user; the password is read from stdin unless given
+    Add {
+        username: String,
+        #[arg(long, default_value = "viewer")]
+        role: String,
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Reset a user's password; the password is read from stdin unless given
+    Passwd {
+        username: String,
+        #[arg(long)]
+        password: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Print one parameter, or all of them
+    Get { parameter: Option<String> },
+    /// Set a parameter; the value is parsed as JSON, or taken as a string
+    Set {
+        parameter: String,
+        value: String,
+        /// Apply even if the change introduces critical safety issues
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum PplnsCommand {
+    /// Validate the shares in the current window; exits 1 when invalid
+    Validate,
+}
+
+#[cfg(feature = "analytics")]
+#[derive(Subcommand, Debug)]
+enum AnalyticsCommand {
+    /// Create the tables and indexes
+    Init,
+    /// Copy what is new since the last sync
+    Sync,
+    /// Copy shares and audit events of the last days, and all blocks and payouts
+    Backfill {
+        /// Defaults to the PPLNS window
+        #[arg(long)]
+        days: Option<u64>,
+    },
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Command::Pool(_) = cli.command {
+        if let Err(e) = commands::run() {
+            eprintln!("Error: {}", e);
+            exit(2);
+        }
+        return;
+    }
+
+    let result = tokio::runtime::Runtime::new()
+        .context("Failed to start runtime")
+        .and_then(|runtime| runtime.block_on(run(cli)));
+    if let Err(e) = result {
+        eprintln!("Error: {:#}", e);
+        exit(2);
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    let config = Config::load(&cli.config.to_string_lossy())
+        .map_err(|e| anyhow::anyhow!("Failed to load {}: {}", cli.config.display(), e))?;
+
+    match cli.command {
+        Command::Backup(command) => backup(command, &config, cli.json).await,
+        Command::User(command) => user(command, &cli.data_dir).await,
+        Command::Config(command) => config_command(command, &cli.config, config, &cli.data_dir, cli.json).await,
+        Command::Pplns(PplnsCommand::Validate) => pplns_validate(&config, cli.json),
+        Command::Health => health(config, cli.json).await,
+        #[cfg(feature = "analytics")]
+        Command::Analytics { database_url, command } => {
+            analytics(command, &database_url, &config, &cli.data_dir, cli.json).await
+        }
+        Command::Pool(_) => unreachable!("handled by the p2poolv2 CLI"),
+    }
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+async fn backup(command: BackupCommand, config: &Config, json: bool) -> Result<()> {
+    let mut manager = BackupManager::new(BackupConfig::from_env(&config.store.path)?)
+        .with_checkpoint_source(Arc::new(StorePathCheckpoint::new(&config.store.path)));
+    if let Some(key) = BackupKey::from_env()? {
+        manager = manager.with_encryption_key(key);
+    }
+
+    match command {
+        BackupCommand::Create { kind } => {
+            let metadata = manager.create_backup_of_kind(kind).await?;
+            if json {
+                return print_json(&metadata);
+            }
+            println!("Created {:?} backup {} ({} bytes) at {}",
+                metadata.kind, metadata.id, metadata.backup_size, metadata.file_path.display());
+        }
+        BackupCommand::List => {
+            let backups = manager.list_backups()?;
+            if json {
+                return print_json(&backups);
+            }
+            for backup in backups {
+                println!("{}  {}  {:<11}  {:>12} bytes{}",
+                    backup.id,
+                    backup.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                    format!("{:?}", backup.kind).to_lowercase(),
+                    backup.backup_size,
+                    if backup.validated { "" } else { "  (unvalidated)" });
+            }
+        }
+        BackupCommand::Restore { id, dry_run, target_dir, column_families, force } => {
+            let options = RestoreOptions { dry_run, target_dir, column_families, force };
+            let plan = manager.restore_backup(&id, &options).await?;
+            if json {
+                return print_json(&plan);
+            }
+            if !plan.column_families.is_empty() {
+                for cf in &plan.column_families {
+                    println!("{} {}: {} key(s) removed, {} written",
+                        if plan.dry_run { "Would restore" } else { "Restored" },
+                        cf.name, cf.keys_deleted, cf.keys_written);
+                }
+                return Ok(());
+            }
+            for change in &plan.changes {
+                println!("{:?}: {}", change.change, change.path);
+            }
+            for problem in &plan.compatibility.problems {
+                println!("Incompatible: {}", problem);
+            }
+            for migration in &plan.compatibility.migrations {
+                println!("Migration: {}", migration);
+            }
+            println!("{} {} into {}: {} changed, {} unchanged file(s)",
+                if plan.dry_run { "Would restore" } else { "Restored" },
+                plan.backup_id,
+                plan.target.display(),
+                plan.changes.len(),
+                plan.unchanged_files);
+        }
+        BackupCommand::RepairCatalog => {
+            let repair = manager.repair_catalog()?;
+            if json {
+                return print_json(&repair);
+            }
+            for id in &repair.added {
+                println!("added:   {}", id);
+            }
+            for id in &repair.removed {
+                println!("removed: {}", id);
+            }
+            for id in &repair.updated {
+                println!("updated: {}", id);
+            }
+            println!("Catalog lists {} backup(s){}", repair.total_backups,
+                if repair.drifted() { "" } else { ", no drift found" });
+        }
+    }
+    Ok(())
+}
+
+/// `given`, or a line read from stdin
+fn read_password(given: Option<String>) -> Result<String> {
+    if let Some(password) = given {
+        return Ok(password);
+    }
+    if std::io::stdin().is_terminal() {
+        eprint!("Password: ");
+    }
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line).context("Failed to read password")?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+async fn user(command: UserCommand, data_dir: &std::path::Path) -> Result<()> {
+    // No tokens are issued here, so the JWT secret is not needed
+    let auth = AuthManager::new(String::new())
+        .with_users_file(data_dir.join("users.json"))
+        .with_password_policy(PasswordPolicy::from_env()?);
+    auth.load().await?;
+
+    match command {
+        UserCommand::Add { username, role, password } => {
+            auth.create_user(&username, &read_password(password)?, &role).await?;
+            println!("Added {} user {}; they must change the password on first login", role, username);
+        }
+        UserCommand::Passwd { username, password } => {
+            auth.reset_password(&username, &read_password(password)?).await?;
+            println!("Reset password of {}; they must change it on next login", username);
+        }
+    }
+    Ok(())
+}
+
+async fn config_command(
+    command: ConfigCommand,
+    config_path: &std::path::Path,
+    mut config: Config,
+    data_dir: &std::path::Path,
+    json: bool,
+) -> Result<()> {
+    match command {
+        ConfigCommand::Get { parameter: None } => {
+            let params = config_mgt::config_params(&config);
+            if json {
+                return print_json(&params);
+            }
+            for (parameter, value) in params.as_object().into_iter().flatten() {
+                println!("{} = {}", parameter, value);
+            }
+        }
+        ConfigCommand::Get { parameter: Some(parameter) } => {
+            let value = config_mgt::parameter_value(&config, &parameter)
+                .ok_or_else(|| anyhow::anyhow!("Unknown parameter {}", parameter))?;
+            println!("{}", value);
+        }
+        ConfigCommand::Set { parameter, value, force } => {
+            let value = serde_json::from_str(&value).unwrap_or(serde_json::Value::String(value));
+            let versions = ConfigManager::new(data_dir.join("config_versions"));
+            versions.initialize().await?;
+            versions.validate_parameter(&parameter, &value).await?;
+
+            let old = config_mgt::parameter_value(&config, &parameter).unwrap_or_default();
+            let live = config.clone();
+            config_mgt::set_parameter(&mut config, &parameter, &value)?;
+            let safety = SafetyAnalyzer::new().check_change(&live, &config, force)?;
+            for warning in &safety.warnings {
+                eprintln!("warning: {}: {}", warning.param, warning.message);
+            }
+            let backup = config_mgt::persist_config(config_path, &config)?;
+            versions.record(&config, format!("{}: {} → {}", parameter, old, value), "cli").await?;
+            println!("Set {} = {} (previous config saved as {})", parameter, value, backup.display());
+        }
+    }
+    Ok(())
+}
+
+fn pplns_validate(config: &Config, json: bool) -> Result<()> {
+    let store = Store::new(config.store.path.clone(), true)
+        .map_err(|e| anyhow::anyhow!("Failed to open store: {}", e))?;
+    let fee_bps = config.stratum.fee.unwrap_or(0).saturating_add(config.stratum.donation.unwrap_or(0));
+    let ttl_days = config.store.pplns_ttl_days;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let shares = store.get_pplns_shares_filtered(None, Some(now.saturating_sub(ttl_days * 24 * 3600)), Some(now));
+    let report = PplnsSimulator::new(VALIDATE_REWARD_SATS, fee_bps, ttl_days).validate_window(&shares, now);
+
+    if json {
+        print_json(&report)?;
+    } else {
+        for check in &report.checks {
+            println!("[{}] {}: {}",
+                if check.passed { "ok" } else { check.severity.as_str() },
+                check.name,
+                check.message);
+        }
+        println!("{} shares, {}", report.window_shares, if report.valid { "valid" } else { "INVALID" });
+    }
+    if !report.valid {
+        exit(1);
+    }
+    Ok(())
+}
+
+async fn health(config: Config, json: bool) -> Result<()> {
+    let backups = BackupManager::new(BackupConfig::from_env(&config.store.path)?);
+    let mut checker = HealthChecker::new(config.clone())
+        .with_backup_manager(Arc::new(backups))
+        .with_stratum_probe(health::stratum_probe_address(&config));
+    // A read-only handle works while the pool holds the store open
+    match Store::new(config.store.path.clone(), true) {
+        Ok(store) => checker = checker.with_store(Arc::new(store)),
+        Err(e) => eprintln!("Store not available, checking database with a temporary store: {}", e),
+    }
+    let status = checker.check().await;
+
+    if json {
+        print_json(&status)?;
+    } else {
+        println!("status:       {}", status.status);
+        println!("database:     {}", status.database.status);
+        println!("bitcoin node: {}", status.bitcoin_node.status);
+        println!("stratum:      {}", status.stratum.status);
+        println!("backups:      {}", status.backups.status);
+        println!("endpoint:     {}", status.stratum_endpoint.status);
+        for reason in &status.degraded_reasons {
+            println!("degraded:     {}", reason);
+        }
+    }
+    if status.is_unhealthy() {
+        exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "analytics")]
+async fn analytics(
+    command: AnalyticsCommand,
+    database_url: &str,
+    config: &Config,
+    data_dir: &std::path::Path,
+    json: bool,
+) -> Result<()> {
+    use dmpool::analytics::AnalyticsMirror;
+    use dmpool::audit::AuditLogger;
+    use dmpool::blocks::BlockTracker;
+    use dmpool::payout::{PayoutConfig, PayoutEngine};
+
+    let mirror = AnalyticsMirror::connect(database_url).await?;
+    if let AnalyticsCommand::Init = command {
+        mirror.apply_schema().await?;
+        println!("Analytics schema is up to date");
+        return Ok(());
+    }
+
+    let store = Store::new(config.store.path.clone(), true)
+        .map_err(|e| anyhow::anyhow!("Failed to open store: {}", e))?;
+    let blocks = BlockTracker::new(
+        data_dir.join("blocks.json"),
+        config.stratum.pool_signature.as_deref().unwrap_or_default(),
+    );
+    blocks.load().await?;
+    let payouts = PayoutEngine::new(data_dir.join("payouts.json"), PayoutConfig::default());
+    payouts.load().await?;
+    let audit = AuditLogger::with_persistence_async(AUDIT_BACKFILL_ENTRIES, data_dir.join("audit")).await?;
+    audit.load_from_file().await?;
+    let mirror = mirror
+        .with_store(Arc::new(store))
+        .with_blocks(Arc::new(blocks))
+        .with_payouts(Arc::new(payouts))
+        .with_audit(Arc::new(audit));
 
-fn main() -> Result<(), Box<dyn Error>> {
-    commands::run()
+    let report = match command {
+        AnalyticsCommand::Backfill { days } => {
+            let days = days.unwrap_or(config.store.pplns_ttl_days);
+            let since = (chrono::Utc::now().timestamp() as u64).saturating_sub(days * 24 * 3600);
+            mirror.backfill(since).await?
+        }
+        _ => mirror.sync().await?,
+    };
+    if json {
+        return print_json(&report);
+    }
+    println!("Mirrored {} shares, {} blocks, {} block credits, {} payout batches and {} audit events in {} ms",
+        report.shares, report.blocks, report.block_credits, report.payout_batches, report.audit_events,
+        report.duration_ms);
+    Ok(())
 }
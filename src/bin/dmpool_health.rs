@@ -1,76 +1,105 @@
 use anyhow::Result;
-use dmpool::health::{HealthChecker, HealthStatus, ComponentStatus, BitcoinNodeStatus, StratumStatus, BlockchainInfo, NetworkInfo};
+use dmpool::backup::{BackupConfig, BackupManager};
+use dmpool::connections::SocketTableCounter;
+use dmpool::health::{self, HealthChecker, HealthStatus};
+use dmpool::shutdown::{Shutdown, ShutdownSettings};
+use dmpool::tls::{self, TlsSettings};
+use dmpool::zmq_monitor::ZmqMonitor;
 use p2poolv2_lib::config::Config;
+use p2poolv2_lib::store::Store;
 use std::env;
-use axum::{Json, Router, routing::get};
-use tokio::net::TcpListener;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json},
+    Router,
+    routing::get,
+};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     println!("DMPool Health Check Service starting...");
 
+    let shutdown = Shutdown::on_signal();
     let config_path = env::var("CONFIG_PATH").unwrap_or_else(|_| "config.toml".to_string());
     let config = Config::load(&config_path)
         .map_err(|e| anyhow::anyhow!("Failed to load config: {}", e))?;
 
-    let health_checker = HealthChecker::new(config.clone());
+    let zmq_monitor = Arc::new(ZmqMonitor::new(config.stratum.zmqpubhashblock.clone()));
+    tokio::spawn(zmq_monitor.clone().run());
+    let zmq_stale_secs: u64 = env::var("ZMQ_STALE_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(3600);
 
-    let port = env::var("HEALTH_PORT").unwrap_or_else(|_| "8081".to_string());
-    let addr = format!("0.0.0.0:{}", port);
+    let mut health_checker = HealthChecker::new(config.clone())
+        .with_zmq_monitor(zmq_monitor)
+        .with_zmq_stale_after(Duration::from_secs(zmq_stale_secs))
+        .with_connection_counter(Arc::new(SocketTableCounter::new(config.stratum.port)))
+        .with_backup_manager(Arc::new(BackupManager::new(BackupConfig::from_env(&config.store.path)?)))
+        .with_stratum_probe(health::stratum_probe_address(&config));
+    // The pool holds the database open; a read-only handle is enough to check it
+    match Store::new(config.store.path.clone(), true) {
+        Ok(store) => health_checker = health_checker.with_store(Arc::new(store)),
+        Err(e) => println!("Store not available, checking database with a temporary store: {}", e),
+    }
+    let health_checker = Arc::new(health_checker);
+
+    let port: u16 = env::var("HEALTH_PORT")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(8081);
+    let tls = TlsSettings::from_env("HEALTH")?;
 
     let app = Router::new()
         .route("/health", get(health_handler))
-        .route("/ready", get(ready_handler));
+        .route("/health/live", get(live_handler))
+        .route("/health/ready", get(ready_handler))
+        .route("/ready", get(ready_handler))
+        .with_state(health_checker);
 
-    let listener = TcpListener::bind(&addr).await?;
-    println!("Health check service listening on {}", addr);
+    if let Some(redirect_port) = tls.redirect_port {
+        tls::spawn_https_redirect(redirect_port, port, &shutdown).await?;
+    }
+    println!("Health check service listening on port {}", port);
 
-    axum::serve(listener, app).await?;
+    let drain_timeout = ShutdownSettings::from_env().drain_timeout;
+    tls::serve(SocketAddr::from(([0, 0, 0, 0], port)), app, &tls, &shutdown, drain_timeout).await?;
+    println!("Health check service stopped");
 
     Ok(())
 }
 
-async fn health_handler() -> Json<HealthStatus> {
-    Json(HealthStatus {
-        status: "healthy".to_string(),
-        database: ComponentStatus::healthy(),
-        bitcoin_node: BitcoinNodeStatus {
-            status: "unknown".to_string(),
-            rpc_latency_ms: None,
-            blockchain: BlockchainInfo {
-                blocks: 0,
-                headers: 0,
-                initial_block_download: false,
-                verification_progress: 0.0,
-                block_time_seconds: None,
-                best_block_hash: "".to_string(),
-            },
-            network: NetworkInfo {
-                connections: 0,
-                network_active: false,
-                peer_count: 0,
-            },
-            sync_progress: 0.0,
-            message: "Not initialized".to_string(),
-        },
-        stratum: StratumStatus {
-            status: "unknown".to_string(),
-            listening: false,
-            active_connections: 0,
-            shares_per_second: 0.0,
-            current_difficulty: 0.0,
-            message: "Not initialized".to_string(),
-        },
-        zmq: ComponentStatus {
-            status: "unknown".to_string(),
-            message: "Not initialized".to_string(),
-            latency_ms: None,
-        },
-        uptime_seconds: 0,
-        memory_mb: None,
-    })
+/// 503 when unhealthy, so probes and load balancers take the pool out of rotation
+fn status_code(status: &HealthStatus) -> StatusCode {
+    if status.is_unhealthy() {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    }
+}
+
+/// Full health report
+async fn health_handler(State(checker): State<Arc<HealthChecker>>) -> impl IntoResponse {
+    let status = checker.check().await;
+    (status_code(&status), Json(status))
 }
 
-async fn ready_handler() -> &'static str {
+/// Liveness: the process is up and serving requests
+async fn live_handler() -> &'static str {
     "OK"
 }
+
+/// Readiness: dependencies are reachable and the pool can serve miners
+async fn ready_handler(State(checker): State<Arc<HealthChecker>>) -> impl IntoResponse {
+    let status = checker.check().await;
+    let body = serde_json::json!({
+        "ready": !status.is_unhealthy(),
+        "status": status.status,
+        "degraded_reasons": status.degraded_reasons,
+    });
+    (status_code(&status), Json(body))
+}
@@ -0,0 +1,452 @@
+// Found Blocks module for DMPool
+// Tracks Bitcoin blocks mined by the pool, identified by the pool signature in the coinbase,
+// until they mature or are orphaned
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// Blocks scanned on first run, when no scan height has been stored yet
+const DEFAULT_INITIAL_SCAN_DEPTH: u64 = 144;
+
+/// Most blocks scanned in one pass, so a long outage doesn't stall the caller
+const MAX_BLOCKS_PER_SCAN: u64 = 500;
+
+/// Confirmations before a coinbase can be spent
+pub const COINBASE_MATURITY: i64 = 100;
+
+/// Where a found block is in its life
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockState {
+    /// In the main chain, not yet mature
+    #[default]
+    Pending,
+    /// Enough confirmations for its reward to be paid out
+    Mature,
+    /// Left the main chain before maturing
+    Orphaned,
+}
+
+impl BlockState {
+    fn of(confirmations: i64, maturity: i64) -> Self {
+        if confirmations < 0 {
+            Self::Orphaned
+        } else if confirmations >= maturity {
+            Self::Mature
+        } else {
+            Self::Pending
+        }
+    }
+}
+
+/// A change of a found block's state
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StateChange {
+    pub from: BlockState,
+    pub to: BlockState,
+    /// Confirmations when the change was seen
+    pub confirmations: i64,
+    pub at: DateTime<Utc>,
+}
+
+/// A Bitcoin block found by the pool
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FoundBlock {
+    pub height: u64,
+    pub hash: String,
+    pub timestamp: DateTime<Utc>,
+    /// Address paid by the first coinbase output
+    pub finder_address: Option<String>,
+    /// Total coinbase output value (subsidy plus fees)
+    pub reward_sats: u64,
+    /// Confirmations at the last check; -1 once the block left the main chain
+    pub confirmations: i64,
+    pub orphaned: bool,
+    #[serde(default)]
+    pub state: BlockState,
+    /// State changes since the block was found, oldest first
+    #[serde(default)]
+    pub state_changes: Vec<StateChange>,
+}
+
+/// On-disk state of the tracker
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BlockIndex {
+    last_scanned_height: Option<u64>,
+    blocks: Vec<FoundBlock>,
+}
+
+/// Bitcoin node queries needed to find pool blocks
+pub trait BlockSource: Send + Sync {
+    /// Height of the current main chain tip
+    fn tip_height(&self) -> Result<u64>;
+    /// Main chain block at `height` with decoded transactions (`getblock` verbosity 2)
+    fn block_at_height(&self, height: u64) -> Result<Value>;
+    /// Block header summary for `hash` (`getblock` verbosity 1)
+    fn block(&self, hash: &str) -> Result<Value>;
+}
+
+impl BlockSource for bitcoincore_rpc::Client {
+    fn tip_height(&self) -> Result<u64> {
+        use bitcoincore_rpc::RpcApi;
+        let height: u64 = self.call("getblockcount", &[])
+            .map_err(|e| anyhow::anyhow!("RPC call failed: {}", e))?;
+        Ok(height)
+    }
+
+    fn block_at_height(&self, height: u64) -> Result<Value> {
+        use bitcoincore_rpc::RpcApi;
+        let hash: String = self.call("getblockhash", &[height.into()])
+            .map_err(|e| anyhow::anyhow!("RPC call failed: {}", e))?;
+        self.call("getblock", &[hash.into(), 2.into()])
+            .map_err(|e| anyhow::anyhow!("RPC call failed: {}", e))
+    }
+
+    fn block(&self, hash: &str) -> Result<Value> {
+        use bitcoincore_rpc::RpcApi;
+        self.call("getblock", &[hash.into(), 1.into()])
+            .map_err(|e| anyhow::anyhow!("RPC call failed: {}", e))
+    }
+}
+
+/// Run a node query on the blocking thread pool, as RPC clients block
+async fn blocking<T: Send + 'static>(
+    source: &Arc<dyn BlockSource>,
+    call: impl FnOnce(&dyn BlockSource) -> Result<T> + Send + 'static,
+) -> Result<T> {
+    let source = source.clone();
+    tokio::task::spawn_blocking(move || call(source.as_ref())).await
+        .context("Block source call panicked")?
+}
+
+/// Index of pool-found blocks, persisted as JSON
+pub struct BlockTracker {
+    path: PathBuf,
+    /// Hex-encoded pool signature searched for in coinbase scripts
+    signature_hex: String,
+    initial_scan_depth: u64,
+    maturity_confirmations: i64,
+    index: Arc<RwLock<BlockIndex>>,
+}
+
+impl BlockTracker {
+    /// Create a tracker looking for `pool_signature` in coinbase scripts
+    pub fn new(path: PathBuf, pool_signature: &str) -> Self {
+        Self {
+            path,
+            signature_hex: pool_signature.bytes().map(|b| format!("{:02x}", b)).collect(),
+            initial_scan_depth: DEFAULT_INITIAL_SCAN_DEPTH,
+            maturity_confirmations: COINBASE_MATURITY,
+            index: Arc::new(RwLock::new(BlockIndex::default())),
+        }
+    }
+
+    /// Confirmations after which a block counts as mature
+    pub fn with_maturity_confirmations(mut self, confirmations: i64) -> Self {
+        self.maturity_confirmations = confirmations;
+        self
+    }
+
+    pub fn maturity_confirmations(&self) -> i64 {
+        self.maturity_confirmations
+    }
+
+    /// Number of blocks below the tip to scan when starting without history
+    pub fn with_initial_scan_depth(mut self, depth: u64) -> Self {
+        self.initial_scan_depth = depth;
+        self
+    }
+
+    /// Load the index from disk, if present
+    pub async fn load(&self) -> Result<usize> {
+        if !self.path.exists() {
+            return Ok(0);
+        }
+        let content = tokio::fs::read_to_string(&self.path).await
+            .context("Failed to read block index")?;
+        let mut index: BlockIndex = serde_json::from_str(&content)
+            .context("Failed to parse block index")?;
+        // Blocks recorded before states were tracked
+        for block in index.blocks.iter_mut().filter(|b| b.state == BlockState::Pending) {
+            block.state = BlockState::of(block.confirmations, self.maturity_confirmations);
+        }
+        let count = index.blocks.len();
+        *self.index.write().await = index;
+        Ok(count)
+    }
+
+    async fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let content = serde_json::to_string_pretty(&*self.index.read().await)?;
+        tokio::fs::write(&self.path, content).await
+            .context("Failed to write block index")?;
+        Ok(())
+    }
+
+    /// Scan new main chain blocks for pool blocks
+    ///
+    /// Returns the blocks newly found in this pass.
+    pub async fn scan(&self, source: Arc<dyn BlockSource>) -> Result<Vec<FoundBlock>> {
+        let tip = blocking(&source, |source| source.tip_height()).await?;
+        let start = match self.index.read().await.last_scanned_height {
+            Some(height) => height + 1,
+            None => tip.saturating_sub(self.initial_scan_depth.saturating_sub(1)),
+        };
+        let end = tip.min(start.saturating_add(MAX_BLOCKS_PER_SCAN - 1));
+
+        let mut found = Vec::new();
+        let mut scanned = None;
+        for height in start..=end {
+            let block = blocking(&source, move |source| source.block_at_height(height)).await?;
+            if let Some(found_block) = self.parse_pool_block(&block, tip) {
+                info!("Found pool block {} at height {}", found_block.hash, found_block.height);
+                found.push(found_block);
+            }
+            scanned = Some(height);
+        }
+
+        let mut index = self.index.write().await;
+        // A reorg may have replaced a block we already recorded at that height
+        index.blocks.retain(|b| !found.iter().any(|f| f.hash == b.hash));
+        index.blocks.extend(found.iter().cloned());
+        index.blocks.sort_by_key(|b| b.height);
+        if scanned.is_some() {
+            index.last_scanned_height = scanned;
+        }
+        drop(index);
+
+        self.save().await?;
+        Ok(found)
+    }
+
+    /// Refresh the confirmations of blocks that can still change state
+    ///
+    /// Pending blocks are checked until they mature or leave the main chain;
+    /// orphaned ones while a reorg could still bring them back. Returns the
+    /// blocks whose state changed, each with its latest change.
+    pub async fn refresh(&self, source: Arc<dyn BlockSource>) -> Result<Vec<(FoundBlock, StateChange)>> {
+        let tip = blocking(&source, |source| source.tip_height()).await?;
+        let maturity = self.maturity_confirmations;
+        let hashes: Vec<String> = self.index.read().await.blocks.iter()
+            .filter(|block| match block.state {
+                BlockState::Pending => true,
+                BlockState::Orphaned => block.height.saturating_add(maturity.max(0) as u64) > tip,
+                BlockState::Mature => false,
+            })
+            .map(|block| block.hash.clone())
+            .collect();
+        if hashes.is_empty() {
+            return Ok(Vec::new());
+        }
+        // Queried without holding the index, which readers need meanwhile
+        let confirmations: Vec<(String, Option<i64>)> = blocking(&source, move |source| {
+            Ok(hashes.into_iter().map(|hash| match source.block(&hash) {
+                Ok(info) => (hash, info["confirmations"].as_i64()),
+                Err(e) => {
+                    warn!("Failed to refresh block {}: {}", hash, e);
+                    (hash, None)
+                }
+            }).collect())
+        }).await?;
+
+        let mut changed = Vec::new();
+        let mut index = self.index.write().await;
+        for (hash, confirmations) in confirmations {
+            let Some(block) = index.blocks.iter_mut().find(|b| b.hash == hash) else {
+                continue;
+            };
+            let confirmations = confirmations.unwrap_or(block.confirmations);
+            block.confirmations = confirmations;
+            block.orphaned = confirmations < 0;
+            let state = BlockState::of(confirmations, maturity);
+            if state == block.state {
+                continue;
+            }
+            match state {
+                BlockState::Orphaned => warn!("Pool block {} at height {} was orphaned", block.hash, block.height),
+                _ => info!("Pool block {} at height {} is {:?} with {} confirmations", block.hash, block.height, state, confirmations),
+            }
+            let change = StateChange {
+                from: block.state,
+                to: state,
+                confirmations,
+                at: Utc::now(),
+            };
+            block.state = state;
+            block.state_changes.push(change.clone());
+            changed.push((block.clone(), change));
+        }
+        drop(index);
+
+        if !changed.is_empty() {
+            self.save().await?;
+        }
+        Ok(changed)
+    }
+
+    /// Build a FoundBlock if the block's coinbase carries the pool signature
+    fn parse_pool_block(&self, block: &Value, tip: u64) -> Option<FoundBlock> {
+        let coinbase = block["tx"].get(0)?;
+        let script = coinbase["vin"].get(0)?["coinbase"].as_str()?;
+        if self.signature_hex.is_empty() || !script.contains(&self.signature_hex) {
+            return None;
+        }
+
+        let outputs = coinbase["vout"].as_array()?;
+        let reward_sats = outputs.iter()
+            .filter_map(|out| out["value"].as_f64())
+            .map(|btc| (btc * 100_000_000.0).round() as u64)
+            .sum();
+        let finder_address = outputs.iter()
+            .find_map(|out| out["scriptPubKey"]["address"].as_str())
+            .map(|a| a.to_string());
+        let height = block["height"].as_u64()?;
+        let confirmations = (tip.saturating_sub(height) + 1) as i64;
+
+        Some(FoundBlock {
+            height,
+            hash: block["hash"].as_str()?.to_string(),
+            timestamp: Utc.timestamp_opt(block["time"].as_i64()?, 0).single()?,
+            finder_address,
+            reward_sats,
+            confirmations,
+            orphaned: false,
+            state: BlockState::of(confirmations, self.maturity_confirmations),
+            state_changes: Vec::new(),
+        })
+    }
+
+    /// Found blocks, newest first
+    pub async fn blocks(&self) -> Vec<FoundBlock> {
+        self.index.read().await.blocks.iter().rev().cloned().collect()
+    }
+
+    /// Found block at `height`
+    pub async fn block_at(&self, height: u64) -> Option<FoundBlock> {
+        self.index.read().await.blocks.iter().rev().find(|b| b.height == height).cloned()
+    }
+
+    /// Number of found blocks still in the main chain
+    pub async fn count(&self) -> u64 {
+        self.index.read().await.blocks.iter().filter(|b| !b.orphaned).count() as u64
+    }
+
+    /// Time of the most recent found block still in the main chain
+    pub async fn last_found_at(&self) -> Option<DateTime<Utc>> {
+        self.index.read().await.blocks.iter().rev().find(|b| !b.orphaned).map(|b| b.timestamp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// In-memory chain of (hash, coinbase script) keyed by height
+    struct MockChain {
+        blocks: Mutex<Vec<(String, String)>>,
+        orphaned: Mutex<HashMap<String, bool>>,
+    }
+
+    impl MockChain {
+        fn new(coinbases: &[&str]) -> Self {
+            let blocks = coinbases.iter().enumerate()
+                .map(|(h, script)| (format!("hash{}", h), script.to_string()))
+                .collect();
+            Self { blocks: Mutex::new(blocks), orphaned: Mutex::new(HashMap::new()) }
+        }
+    }
+
+    impl BlockSource for MockChain {
+        fn tip_height(&self) -> Result<u64> {
+            Ok(self.blocks.lock().unwrap().len() as u64 - 1)
+        }
+
+        fn block_at_height(&self, height: u64) -> Result<Value> {
+            let (hash, script) = self.blocks.lock().unwrap()[height as usize].clone();
+            Ok(json!({
+                "hash": hash,
+                "height": height,
+                "time": 1_700_000_000 + height * 600,
+                "tx": [{
+                    "vin": [{ "coinbase": script }],
+                    "vout": [
+                        { "value": 3.0, "scriptPubKey": { "address": "bc1qfinder" } },
+                        { "value": 0.125, "scriptPubKey": { "address": "bc1qother" } },
+                        { "value": 0.0, "scriptPubKey": { "type": "nulldata" } }
+                    ]
+                }]
+            }))
+        }
+
+        fn block(&self, hash: &str) -> Result<Value> {
+            let tip = self.tip_height()? as i64;
+            let orphaned = self.orphaned.lock().unwrap().get(hash).copied().unwrap_or(false);
+            let height = self.blocks.lock().unwrap().iter().position(|(h, _)| h == hash);
+            let confirmations = match (orphaned, height) {
+                (false, Some(height)) => tip - height as i64 + 1,
+                _ => -1,
+            };
+            Ok(json!({ "hash": hash, "confirmations": confirmations }))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scan_finds_pool_blocks_and_detects_orphans() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("blocks.json");
+        // "dmp" hex-encoded is 646d70
+        let chain = Arc::new(MockChain::new(&["03aa", "03bb646d7000", "03cc", "03dd"]));
+
+        let tracker = BlockTracker::new(path.clone(), "dmp")
+            .with_initial_scan_depth(10)
+            .with_maturity_confirmations(5);
+        let found = tracker.scan(chain.clone()).await.unwrap();
+        assert_eq!(found.len(), 1);
+        let block = &found[0];
+        assert_eq!(block.height, 1);
+        assert_eq!(block.reward_sats, 312_500_000);
+        assert_eq!(block.finder_address.as_deref(), Some("bc1qfinder"));
+        assert_eq!(block.confirmations, 3);
+        assert_eq!(block.state, BlockState::Pending);
+
+        // New blocks are scanned incrementally and confirmations refreshed
+        chain.blocks.lock().unwrap().push(("hash4".to_string(), "03ee646d70".to_string()));
+        tracker.scan(chain.clone()).await.unwrap();
+        assert_eq!(tracker.count().await, 2);
+        assert!(tracker.refresh(chain.clone()).await.unwrap().is_empty());
+        assert_eq!(tracker.block_at(1).await.unwrap().confirmations, 4);
+
+        // Block 1 matures at 5 confirmations while block 4 is orphaned
+        chain.blocks.lock().unwrap().push(("hash5".to_string(), "03ff".to_string()));
+        chain.orphaned.lock().unwrap().insert("hash4".to_string(), true);
+        let changed = tracker.refresh(chain.clone()).await.unwrap();
+        let states: Vec<_> = changed.iter().map(|(b, c)| (b.height, c.from, c.to)).collect();
+        assert_eq!(states, [(1, BlockState::Pending, BlockState::Mature), (4, BlockState::Pending, BlockState::Orphaned)]);
+        assert!(tracker.block_at(4).await.unwrap().orphaned);
+        assert_eq!(tracker.count().await, 1);
+
+        // A reorg back into the main chain is picked up while it is recent
+        chain.orphaned.lock().unwrap().remove("hash4");
+        let changed = tracker.refresh(chain.clone()).await.unwrap();
+        assert_eq!(changed[0].1.to, BlockState::Pending);
+        assert_eq!(tracker.block_at(4).await.unwrap().state_changes.len(), 2);
+
+        // Index survives a restart
+        let reloaded = BlockTracker::new(path, "dmp");
+        assert_eq!(reloaded.load().await.unwrap(), 2);
+        assert_eq!(reloaded.blocks().await[0].height, 4);
+        assert_eq!(reloaded.block_at(1).await.unwrap().state, BlockState::Mature);
+        assert!(reloaded.scan(chain.clone()).await.unwrap().is_empty());
+    }
+}
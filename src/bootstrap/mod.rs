@@ -0,0 +1,250 @@
+// Startup Bootstrap for DMPool Admin
+// Loads what the admin server needs before serving, failing with errors that say how to fix them
+
+use crate::auth::PasswordPolicy;
+use crate::backup::{BackupConfig, BackupKey};
+use crate::cors::CorsPolicy;
+use crate::rate_limit::RateLimitConfig;
+use crate::secrets::{JwtKey, JwtKeySet, SecretStore};
+use crate::tls::TlsSettings;
+use crate::two_factor::{RelyingParty, TwoFactorPolicy};
+use p2poolv2_lib::config::Config;
+use p2poolv2_lib::store::Store;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Why the admin server can't start
+#[derive(Debug, thiserror::Error)]
+pub enum StartupError {
+    #[error("JWT_SECRET is not set")]
+    MissingJwtSecret,
+    #[error("invalid JWT secrets: {0:#}")]
+    InvalidJwtSecrets(anyhow::Error),
+    #[error("secret providers are misconfigured: {0:#}")]
+    SecretProviders(anyhow::Error),
+    #[error("config file {} not found", path.display())]
+    ConfigNotFound { path: PathBuf },
+    #[error("config file {} is invalid: {reason}", path.display())]
+    InvalidConfig { path: PathBuf, reason: String },
+    #[error("store {path} is locked by another process: {reason}")]
+    StoreLocked { path: String, reason: String },
+    #[error("store {path} can't be opened: {reason}")]
+    StoreUnavailable { path: String, reason: String },
+    #[error("data directory {} is not writable: {source}", path.display())]
+    DataDirNotWritable { path: PathBuf, source: std::io::Error },
+    #[error("invalid {setting} settings: {error:#}")]
+    InvalidSetting { setting: &'static str, error: anyhow::Error },
+}
+
+impl StartupError {
+    /// What the operator can do about it
+    pub fn hint(&self) -> &'static str {
+        match self {
+            Self::MissingJwtSecret => "Set JWT_SECRET (at least 32 characters, e.g. from `openssl rand -base64 32`) \
+                in one of the SECRET_PROVIDERS, or unset DMP_ENV=production to use a throwaway secret",
+            Self::InvalidJwtSecrets(_) => "JWT_SECRET and every JWT_PREVIOUS_SECRETS entry need at least 32 characters",
+            Self::SecretProviders(_) => "Check SECRET_PROVIDERS and the settings of each provider \
+                (SECRETS_DIR for file, VAULT_ADDR and VAULT_TOKEN for vault)",
+            Self::ConfigNotFound { .. } => "Point CONFIG_PATH at the pool's config.toml",
+            Self::InvalidConfig { .. } => "Fix the config file; the pool refuses the same file",
+            Self::StoreLocked { .. } => "Another process has the store open for writing; \
+                stop it, or wait for a running backup, restore or repair to finish",
+            Self::StoreUnavailable { .. } => "Check store.path in the config, that the pool has created the store \
+                and that this user can read it",
+            Self::DataDirNotWritable { .. } => "Set DMP_DATA_DIR to a directory this user can write",
+            Self::InvalidSetting { .. } => "Fix the environment variables named in the error",
+        }
+    }
+
+    /// Process exit code, following sysexits.h so supervisors can tell
+    /// configuration errors from temporary ones
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::StoreLocked { .. } => 75,        // EX_TEMPFAIL
+            Self::DataDirNotWritable { .. } => 73, // EX_CANTCREAT
+            Self::StoreUnavailable { .. } => 74,   // EX_IOERR
+            _ => 78,                               // EX_CONFIG
+        }
+    }
+}
+
+/// Settings every startup step needs
+#[derive(Clone, Debug)]
+pub struct StartupSettings {
+    pub config_path: String,
+    pub data_dir: PathBuf,
+    /// `DMP_ENV=production`: missing secrets are fatal
+    pub production: bool,
+}
+
+impl StartupSettings {
+    /// Read `CONFIG_PATH`, `DMP_DATA_DIR` and `DMP_ENV`
+    pub fn from_env() -> Self {
+        Self {
+            config_path: std::env::var("CONFIG_PATH").unwrap_or_else(|_| "config.toml".to_string()),
+            data_dir: PathBuf::from(std::env::var("DMP_DATA_DIR").unwrap_or_else(|_| "./data".to_string())),
+            production: std::env::var("DMP_ENV").unwrap_or_else(|_| "development".to_string()) == "production",
+        }
+    }
+}
+
+/// Name a failed settings check, so the error says which variables to fix
+pub fn setting<T>(setting: &'static str, result: anyhow::Result<T>) -> Result<T, StartupError> {
+    result.map_err(|error| StartupError::InvalidSetting { setting, error })
+}
+
+pub fn secret_store() -> Result<SecretStore, StartupError> {
+    SecretStore::from_env().map_err(StartupError::SecretProviders)
+}
+
+/// JWT signing keys; outside production a missing secret is replaced by a
+/// random one, which logs everyone out on restart
+pub async fn load_jwt_keys(secrets: &SecretStore, production: bool) -> Result<JwtKeySet, StartupError> {
+    match JwtKeySet::from_secrets(secrets).await {
+        Ok(Some(keys)) => Ok(keys),
+        Ok(None) if production => Err(StartupError::MissingJwtSecret),
+        Ok(None) => {
+            use rand::Rng;
+            let secret: String = rand::thread_rng()
+                .sample_iter(&rand::distributions::Alphanumeric)
+                .take(32)
+                .map(char::from)
+                .collect();
+            warn!("Using generated JWT secret for development. Set JWT_SECRET for persistence!");
+            Ok(JwtKeySet::new(JwtKey::new(secret)))
+        }
+        Err(e) => Err(StartupError::InvalidJwtSecrets(e)),
+    }
+}
+
+pub fn load_config(path: &str) -> Result<Config, StartupError> {
+    if !Path::new(path).is_file() {
+        return Err(StartupError::ConfigNotFound { path: path.into() });
+    }
+    Config::load(path).map_err(|e| StartupError::InvalidConfig { path: path.into(), reason: e.to_string() })
+}
+
+/// Classify a failure to open the store at `path`
+fn store_error(path: &str, reason: String) -> StartupError {
+    // RocksDB reports a held lock as "IO error: While lock file: .../LOCK: Resource temporarily unavailable"
+    if reason.contains("lock file") || reason.contains("LOCK") {
+        StartupError::StoreLocked { path: path.to_string(), reason }
+    } else {
+        StartupError::StoreUnavailable { path: path.to_string(), reason }
+    }
+}
+
+/// Open the store read-only, as the admin server does
+pub fn open_store(path: &str) -> Result<Store, StartupError> {
+    Store::new(path.to_string(), true).map_err(|e| store_error(path, e.to_string()))
+}
+
+/// Create the data directory if needed and make sure files can be written to it
+pub fn check_data_dir(path: &Path) -> Result<(), StartupError> {
+    let not_writable = |source| StartupError::DataDirNotWritable { path: path.to_path_buf(), source };
+    std::fs::create_dir_all(path).map_err(not_writable)?;
+    let probe = path.join(format!(".write-check-{}", std::process::id()));
+    std::fs::write(&probe, b"ok").map_err(not_writable)?;
+    std::fs::remove_file(&probe).map_err(not_writable)
+}
+
+/// Outcome of `--check`: every step is run, so all problems show at once
+#[derive(Debug, Default)]
+pub struct StartupCheck {
+    pub passed: Vec<&'static str>,
+    pub failed: Vec<(&'static str, StartupError)>,
+}
+
+impl StartupCheck {
+    /// Record the outcome of step `name`, returning its value if it passed
+    pub fn record<T>(&mut self, name: &'static str, result: Result<T, StartupError>) -> Option<T> {
+        match result {
+            Ok(value) => {
+                self.passed.push(name);
+                Some(value)
+            }
+            Err(e) => {
+                self.failed.push((name, e));
+                None
+            }
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Exit code of the first failure, 0 if all passed
+    pub fn exit_code(&self) -> u8 {
+        self.failed.first().map(|(_, e)| e.exit_code()).unwrap_or(0)
+    }
+}
+
+impl fmt::Display for StartupCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for name in &self.passed {
+            writeln!(f, "ok    {}", name)?;
+        }
+        for (name, error) in &self.failed {
+            writeln!(f, "FAIL  {}: {}", name, error)?;
+            writeln!(f, "      {}", error.hint())?;
+        }
+        Ok(())
+    }
+}
+
+/// Validate everything the admin server loads at startup, without binding
+/// ports or starting background tasks
+pub async fn check(settings: &StartupSettings) -> StartupCheck {
+    let mut check = StartupCheck::default();
+    if let Some(secrets) = check.record("secret providers", secret_store()) {
+        check.record("JWT secrets", load_jwt_keys(&secrets, settings.production).await);
+        let backup_key = BackupKey::from_secrets(&secrets).await;
+        check.record("backup encryption key", setting("backup encryption key", backup_key));
+    }
+    check.record("data directory", check_data_dir(&settings.data_dir));
+    if let Some(config) = check.record("config", load_config(&settings.config_path)) {
+        check.record("store", open_store(&config.store.path));
+        check.record("backups", setting("backup", BackupConfig::from_env(&config.store.path)));
+    }
+    check.record("TLS", setting("ADMIN_TLS", TlsSettings::from_env("ADMIN")));
+    check.record("password policy", setting("password policy", PasswordPolicy::from_env()));
+    check.record("2FA policy", setting("2FA policy", TwoFactorPolicy::from_env()));
+    check.record("security keys", setting("WebAuthn", RelyingParty::from_env()));
+    check.record("rate limits", setting("rate limit", RateLimitConfig::from_env()));
+    check.record("CORS", setting("CORS", CorsPolicy::from_env()));
+    check
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_startup_errors_are_classified() {
+        let locked = store_error("/data/store", "IO error: While lock file: /data/store/LOCK: Resource temporarily unavailable".into());
+        assert!(matches!(locked, StartupError::StoreLocked { .. }));
+        assert_eq!(locked.exit_code(), 75);
+        let missing = store_error("/data/store", "IO error: No such file or directory".into());
+        assert!(matches!(missing, StartupError::StoreUnavailable { .. }));
+
+        assert!(matches!(load_config("/nonexistent/config.toml"), Err(StartupError::ConfigNotFound { .. })));
+        assert_eq!(StartupError::MissingJwtSecret.exit_code(), 78);
+
+        let dir = tempfile::tempdir().unwrap();
+        assert!(check_data_dir(&dir.path().join("nested")).is_ok());
+        let file = dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        assert!(matches!(check_data_dir(&file), Err(StartupError::DataDirNotWritable { .. })));
+
+        let mut check = StartupCheck::default();
+        assert_eq!(check.record("config", Ok::<_, StartupError>(1)), Some(1));
+        assert!(check.record("JWT secrets", Err::<(), _>(StartupError::MissingJwtSecret)).is_none());
+        assert!(!check.is_ok());
+        assert_eq!(check.exit_code(), 78);
+        let report = check.to_string();
+        assert!(report.contains("ok    config"));
+        assert!(report.contains("FAIL  JWT secrets: JWT_SECRET is not set"));
+    }
+}
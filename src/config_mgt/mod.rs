@@ -1,14 +1,17 @@
 // Smart Configuration Management for DMPool
 // Provides versioning, rollback, validation, and diff capabilities
 
+use crate::confirmation::RiskLevel;
+use crate::safety::SafetyAnalyzer;
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
+use p2poolv2_lib::config::Config;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::fs;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
 use tracing::{info, warn};
 
 /// Configuration version with metadata
@@ -50,6 +53,19 @@ pub struct ConfigDiff {
     pub summary: ConfigDiffSummary,
 }
 
+impl ConfigDiff {
+    /// One line per change, e.g. `pplns_ttl_days: 7 → 14`
+    pub fn describe(&self) -> Vec<String> {
+        self.changes.iter()
+            .map(|c| match c.change_type {
+                ChangeType::Added => format!("{}: added {}", c.path, c.new_value),
+                ChangeType::Removed => format!("{}: removed (was {})", c.path, c.old_value),
+                _ => format!("{}: {} → {}", c.path, c.old_value, c.new_value),
+            })
+            .collect()
+    }
+}
+
 /// Individual configuration change
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ConfigChange {
@@ -119,6 +135,12 @@ pub struct ConfigSchema {
     pub default_value: Option<serde_json::Value>,
     pub validation_rules: Vec<ValidationRule>,
     pub description: String,
+    /// How much confirmation a change needs
+    #[serde(default)]
+    pub risk_level: RiskLevel,
+    /// Only takes effect after the pool restarts
+    #[serde(default)]
+    pub restart_required: bool,
 }
 
 /// Configuration parameter types
@@ -139,6 +161,355 @@ pub struct ValidationRule {
     pub error_message: String,
 }
 
+/// Parameters that can be changed at runtime, with their schema keys
+const RUNTIME_PARAMETERS: &[(&str, &str)] = &[
+    ("start_difficulty", "stratum.start_difficulty"),
+    ("minimum_difficulty", "stratum.minimum_difficulty"),
+    ("pool_signature", "stratum.pool_signature"),
+    ("ignore_difficulty", "ignore_difficulty"),
+    ("pplns_ttl_days", "pplns_ttl_days"),
+    ("donation", "donation"),
+    ("fee", "fee"),
+];
+
+/// Schema key of a runtime-adjustable parameter
+pub fn schema_key(parameter: &str) -> Option<&'static str> {
+    RUNTIME_PARAMETERS.iter()
+        .find(|(name, _)| *name == parameter)
+        .map(|(_, key)| *key)
+}
+
+/// Runtime-adjustable parameter of a schema key; `None` for settings that need a restart
+pub fn runtime_parameter(key: &str) -> Option<&'static str> {
+    RUNTIME_PARAMETERS.iter()
+        .find(|(_, k)| *k == key)
+        .map(|(name, _)| *name)
+}
+
+/// Flatten the managed parts of a config into schema keys
+pub fn config_params(config: &Config) -> serde_json::Value {
+    serde_json::json!({
+        "stratum.port": config.stratum.port,
+        "stratum.start_difficulty": config.stratum.start_difficulty,
+        "stratum.minimum_difficulty": config.stratum.minimum_difficulty,
+        "stratum.pool_signature": config.stratum.pool_signature.clone().unwrap_or_default(),
+        "ignore_difficulty": config.stratum.ignore_difficulty.unwrap_or(false),
+        "pplns_ttl_days": config.store.pplns_ttl_days,
+        "donation": config.stratum.donation.unwrap_or(0),
+        "fee": config.stratum.fee.unwrap_or(0),
+    })
+}
+
+/// Current value of a runtime-adjustable parameter
+pub fn parameter_value(config: &Config, parameter: &str) -> Option<serde_json::Value> {
+    config_params(config).get(schema_key(parameter)?).cloned()
+}
+
+/// Runtime-adjustable parameters whose value differs, as (parameter, old, new)
+pub fn changed_parameters(old: &Config, new: &Config) -> Vec<(String, serde_json::Value, serde_json::Value)> {
+    let (old_params, new_params) = (config_params(old), config_params(new));
+    RUNTIME_PARAMETERS.iter()
+        .filter_map(|(parameter, key)| {
+            let (before, after) = (old_params.get(*key)?, new_params.get(*key)?);
+            (before != after).then(|| (parameter.to_string(), before.clone(), after.clone()))
+        })
+        .collect()
+}
+
+/// A setting whose value differs between two configs
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettingChange {
+    pub setting: String,
+    pub old: String,
+    pub new: String,
+}
+
+impl std::fmt::Display for SettingChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} → {}", self.setting, self.old, self.new)
+    }
+}
+
+/// The config changed since the version a change was based on
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct VersionConflict {
+    pub expected_version: String,
+    pub current_version: Option<String>,
+    /// Author, time and description of the current version
+    pub changed_by: Option<String>,
+    pub changed_at: Option<DateTime<Utc>>,
+    pub description: Option<String>,
+}
+
+impl std::fmt::Display for VersionConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Config changed since version {} (now {})",
+            self.expected_version,
+            self.current_version.as_deref().unwrap_or("none"),
+        )?;
+        if let Some(changed_by) = &self.changed_by {
+            write!(f, " by {}", changed_by)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for VersionConflict {}
+
+/// Settings that differ between two configs but only take effect after a restart
+pub fn restart_required_changes(old: &Config, new: &Config) -> Vec<SettingChange> {
+    let mut changes = Vec::new();
+    let mut check = |setting: &str, old: String, new: String| {
+        if old != new {
+            changes.push(SettingChange { setting: setting.to_string(), old, new });
+        }
+    };
+    check("stratum.hostname", old.stratum.hostname.clone(), new.stratum.hostname.clone());
+    check("stratum.port", old.stratum.port.to_string(), new.stratum.port.to_string());
+    check("stratum.network", old.stratum.network.to_string(), new.stratum.network.to_string());
+    check("store.path", old.store.path.clone(), new.store.path.clone());
+    check("bitcoinrpc.url", old.bitcoinrpc.url.clone(), new.bitcoinrpc.url.clone());
+    check("api.port", old.api.port.to_string(), new.api.port.to_string());
+    changes
+}
+
+/// Set a runtime-adjustable parameter; the value type is checked, ranges are not
+pub fn set_parameter(config: &mut Config, parameter: &str, value: &serde_json::Value) -> Result<()> {
+    let as_u64 = || value.as_u64()
+        .ok_or_else(|| anyhow::anyhow!("{} must be a non-negative integer", parameter));
+    let as_u16 = || as_u64().and_then(|n| u16::try_from(n)
+        .map_err(|_| anyhow::anyhow!("{} must be at most {}", parameter, u16::MAX)));
+
+    match parameter {
+        "start_difficulty" => config.stratum.start_difficulty = as_u64()?,
+        "minimum_difficulty" => config.stratum.minimum_difficulty = as_u64()?,
+        "pool_signature" => {
+            let signature = value.as_str()
+                .ok_or_else(|| anyhow::anyhow!("pool_signature must be a string"))?;
+            config.stratum.pool_signature = Some(signature.to_string());
+        }
+        "ignore_difficulty" => {
+            let ignore = value.as_bool()
+                .ok_or_else(|| anyhow::anyhow!("ignore_difficulty must be a boolean"))?;
+            config.stratum.ignore_difficulty = Some(ignore);
+        }
+        "pplns_ttl_days" => config.store.pplns_ttl_days = as_u64()?,
+        "donation" => config.stratum.donation = Some(as_u16()?),
+        "fee" => config.stratum.fee = Some(as_u16()?),
+        _ => return Err(anyhow::anyhow!("{} cannot be changed at runtime", parameter)),
+    }
+    Ok(())
+}
+
+/// Type, range and rule errors of `value` for the parameter at schema key `path`
+pub fn check_value(path: &str, schema: &ConfigSchema, value: &serde_json::Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    match &schema.parameter_type {
+        ConfigType::String => {
+            if !value.is_string() {
+                errors.push(format!("{} must be a string", path));
+            }
+        }
+        ConfigType::Integer { min, max } => match value.as_i64() {
+            Some(n) if n < *min || n > *max => {
+                errors.push(format!("{} must be between {} and {}", path, min, max));
+            }
+            Some(_) => {}
+            None => errors.push(format!("{} must be an integer", path)),
+        },
+        ConfigType::Float { min, max } => match value.as_f64() {
+            Some(f) if f < *min || f > *max => {
+                errors.push(format!("{} must be between {} and {}", path, min, max));
+            }
+            Some(_) => {}
+            None => errors.push(format!("{} must be a number", path)),
+        },
+        ConfigType::Boolean => {
+            if !value.is_boolean() {
+                errors.push(format!("{} must be a boolean", path));
+            }
+        }
+        ConfigType::Enum { options } => match value.as_str() {
+            Some(s) if !options.iter().any(|o| o == s) => {
+                errors.push(format!("{} must be one of: {:?}", path, options));
+            }
+            Some(_) => {}
+            None => errors.push(format!("{} must be a string", path)),
+        },
+    }
+    for rule in &schema.validation_rules {
+        if !rule_passes(value, rule) {
+            errors.push(rule.error_message.clone());
+        }
+    }
+    errors
+}
+
+/// Messages of the `range_warning` rules `value` falls outside of; they don't reject it
+pub fn value_warnings(schema: &ConfigSchema, value: &serde_json::Value) -> Vec<String> {
+    let Some(n) = value.as_f64() else {
+        return Vec::new();
+    };
+    schema.validation_rules.iter()
+        .filter(|rule| rule.rule_type == "range_warning")
+        .filter(|rule| {
+            rule.params["min"].as_f64().is_some_and(|min| n < min)
+                || rule.params["max"].as_f64().is_some_and(|max| n > max)
+        })
+        .map(|rule| rule.error_message.clone())
+        .collect()
+}
+
+/// Whether `value` satisfies a rule; warnings always pass
+fn rule_passes(value: &serde_json::Value, rule: &ValidationRule) -> bool {
+    match rule.rule_type.as_str() {
+        "critical" => rule.params.get("forbidden").is_none_or(|forbidden| value != forbidden),
+        "max_length" => {
+            let max = rule.params["max"].as_u64().unwrap_or(u64::MAX);
+            value.as_str().is_none_or(|s| s.len() as u64 <= max)
+        }
+        _ => true,
+    }
+}
+
+/// Config file backups kept next to the config file
+const MAX_CONFIG_BACKUPS: usize = 10;
+
+/// TOML section and key of each managed schema key
+const TOML_LOCATIONS: &[(&str, &str, &str)] = &[
+    ("stratum.start_difficulty", "stratum", "start_difficulty"),
+    ("stratum.minimum_difficulty", "stratum", "minimum_difficulty"),
+    ("stratum.pool_signature", "stratum", "pool_signature"),
+    ("ignore_difficulty", "stratum", "ignore_difficulty"),
+    ("pplns_ttl_days", "store", "pplns_ttl_days"),
+    ("donation", "stratum", "donation"),
+    ("fee", "stratum", "fee"),
+];
+
+/// Write managed parameters into a TOML document, keeping comments and
+/// everything else as is
+///
+/// Keys missing from the file are only added when they differ from the
+/// default (zero, false or empty).
+pub fn update_toml(content: &str, params: &serde_json::Value) -> Result<String> {
+    let mut doc: toml_edit::DocumentMut = content.parse()
+        .context("Failed to parse config file")?;
+
+    for (schema_key, section, key) in TOML_LOCATIONS {
+        let value = match params.get(*schema_key) {
+            Some(value) => value,
+            None => continue,
+        };
+        let item = match value {
+            serde_json::Value::Bool(b) => toml_edit::value(*b),
+            serde_json::Value::String(s) => toml_edit::value(s.as_str()),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(n) => toml_edit::value(n),
+                None => continue,
+            },
+            _ => continue,
+        };
+
+        let table = doc.entry(section).or_insert(toml_edit::table())
+            .as_table_like_mut()
+            .ok_or_else(|| anyhow::anyhow!("[{}] is not a table", section))?;
+        let is_default = matches!(value, serde_json::Value::Bool(false))
+            || value.as_str() == Some("")
+            || value.as_i64() == Some(0);
+        match table.get_mut(key) {
+            Some(existing) => {
+                // Keep the inline comment and spacing of the existing entry
+                let decor = existing.as_value().map(|v| v.decor().clone());
+                *existing = item;
+                if let (Some(decor), Some(v)) = (decor, existing.as_value_mut()) {
+                    *v.decor_mut() = decor;
+                }
+            }
+            None if !is_default => {
+                table.insert(key, item);
+            }
+            None => {}
+        }
+    }
+
+    Ok(doc.to_string())
+}
+
+/// Persist the managed parameters of `config` to its TOML file
+///
+/// The previous file is copied to `<file>.<timestamp>.bak` first and the new
+/// content is written via a temporary file, so a crash never leaves a
+/// half-written config. Returns the backup path.
+pub fn persist_config(path: &Path, config: &Config) -> Result<PathBuf> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {:?}", path))?;
+    let updated = update_toml(&content, &config_params(config))?;
+
+    let file_name = path.file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| anyhow::anyhow!("Invalid config path {:?}", path))?;
+    let backup = path.with_file_name(format!(
+        "{}.{}.bak",
+        file_name,
+        Utc::now().format("%Y%m%d_%H%M%S_%3f")
+    ));
+    std::fs::copy(path, &backup).context("Failed to back up config file")?;
+
+    replace_file(path, &format!("{}.tmp", file_name), updated.as_bytes())?;
+
+    prune_config_backups(path, file_name);
+    info!("Persisted configuration to {:?} (backup {:?})", path, backup);
+    Ok(backup)
+}
+
+/// Replace `path` with `content` via the temporary file `tmp_name` beside it
+///
+/// The temporary file gets the permissions of `path` before anything is
+/// written to it, so a config holding credentials never becomes readable to
+/// others, even briefly.
+fn replace_file(path: &Path, tmp_name: &str, content: &[u8]) -> Result<()> {
+    use std::io::Write;
+    let permissions = std::fs::metadata(path)
+        .with_context(|| format!("Failed to read permissions of {:?}", path))?
+        .permissions();
+    let tmp = path.with_file_name(tmp_name);
+    let mut file = std::fs::File::create(&tmp).context("Failed to write config file")?;
+    file.set_permissions(permissions).context("Failed to set config file permissions")?;
+    file.write_all(content).context("Failed to write config file")?;
+    file.sync_all().context("Failed to write config file")?;
+    std::fs::rename(&tmp, path).context("Failed to replace config file")?;
+    Ok(())
+}
+
+/// Delete all but the newest config file backups
+fn prune_config_backups(path: &Path, file_name: &str) {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let prefix = format!("{}.", file_name);
+    let mut backups: Vec<PathBuf> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.file_name().and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with(&prefix) && n.ends_with(".bak"))
+            })
+            .collect(),
+        Err(_) => return,
+    };
+    // Timestamps sort lexicographically
+    backups.sort();
+    let excess = backups.len().saturating_sub(MAX_CONFIG_BACKUPS);
+    for old in backups.into_iter().take(excess) {
+        if let Err(e) = std::fs::remove_file(&old) {
+            warn!("Failed to remove old config backup {:?}: {}", old, e);
+        }
+    }
+}
+
 /// Smart configuration manager
 pub struct ConfigManager {
     /// Current active version
@@ -151,6 +522,8 @@ pub struct ConfigManager {
     schema: Arc<RwLock<HashMap<String, ConfigSchema>>>,
     /// Scheduled changes
     scheduled_changes: Arc<RwLock<Vec<ScheduledChange>>>,
+    /// Every version created from now on
+    versions_created: broadcast::Sender<ConfigVersion>,
 }
 
 impl ConfigManager {
@@ -160,13 +533,19 @@ impl ConfigManager {
             current_version: Arc::new(RwLock::new(None)),
             versions: Arc::new(RwLock::new(HashMap::new())),
             storage_dir,
-            schema: Arc::new(RwLock::new(Self::build_default_schema())),
+            schema: Arc::new(RwLock::new(Self::default_schema())),
             scheduled_changes: Arc::new(RwLock::new(Vec::new())),
+            versions_created: broadcast::channel(64).0,
         }
     }
 
-    /// Initialize with default schema
-    fn build_default_schema() -> HashMap<String, ConfigSchema> {
+    /// Receive each configuration version as it is created
+    pub fn subscribe(&self) -> broadcast::Receiver<ConfigVersion> {
+        self.versions_created.subscribe()
+    }
+
+    /// Type, range, risk level and restart flag of each managed parameter, by schema key
+    pub fn default_schema() -> HashMap<String, ConfigSchema> {
         let mut schema = HashMap::new();
 
         // Stratum settings
@@ -177,6 +556,70 @@ impl ConfigManager {
             default_value: Some(serde_json::json!(3333)),
             validation_rules: vec![],
             description: "Stratum server port".to_string(),
+            risk_level: RiskLevel::High,
+            restart_required: true,
+        });
+
+        schema.insert("stratum.minimum_difficulty".to_string(), ConfigSchema {
+            parameter_name: "stratum.minimum_difficulty".to_string(),
+            parameter_type: ConfigType::Integer { min: 8, max: 512 },
+            required: false,
+            default_value: Some(serde_json::json!(16)),
+            validation_rules: vec![],
+            description: "Lowest difficulty vardiff may assign".to_string(),
+            risk_level: RiskLevel::Medium,
+            restart_required: false,
+        });
+
+        schema.insert("stratum.pool_signature".to_string(), ConfigSchema {
+            parameter_name: "stratum.pool_signature".to_string(),
+            parameter_type: ConfigType::String,
+            required: false,
+            default_value: None,
+            validation_rules: vec![
+                ValidationRule {
+                    rule_type: "max_length".to_string(),
+                    params: serde_json::json!({"max": 16}),
+                    error_message: "Pool signature must be at most 16 bytes".to_string(),
+                }
+            ],
+            description: "Signature written into coinbase scripts".to_string(),
+            risk_level: RiskLevel::Low,
+            restart_required: false,
+        });
+
+        schema.insert("ignore_difficulty".to_string(), ConfigSchema {
+            parameter_name: "ignore_difficulty".to_string(),
+            parameter_type: ConfigType::Boolean,
+            required: false,
+            default_value: Some(serde_json::json!(false)),
+            validation_rules: vec![
+                ValidationRule {
+                    rule_type: "critical".to_string(),
+                    params: serde_json::json!({"forbidden": true}),
+                    error_message: "Ignoring share difficulty breaks PPLNS fairness".to_string(),
+                }
+            ],
+            description: "Accept shares regardless of difficulty".to_string(),
+            risk_level: RiskLevel::Critical,
+            restart_required: false,
+        });
+
+        schema.insert("fee".to_string(), ConfigSchema {
+            parameter_name: "fee".to_string(),
+            parameter_type: ConfigType::Integer { min: 0, max: 10000 },
+            required: false,
+            default_value: Some(serde_json::json!(0)),
+            validation_rules: vec![
+                ValidationRule {
+                    rule_type: "critical".to_string(),
+                    params: serde_json::json!({"forbidden": 10000}),
+                    error_message: "A fee of 100% (10000 basis points) prevents payouts!".to_string(),
+                }
+            ],
+            description: "Pool operator fee in basis points (0-10000)".to_string(),
+            risk_level: RiskLevel::Critical,
+            restart_required: false,
         });
 
         schema.insert("stratum.start_difficulty".to_string(), ConfigSchema {
@@ -186,6 +629,8 @@ impl ConfigManager {
             default_value: Some(serde_json::json!(32)),
             validation_rules: vec![],
             description: "Initial difficulty for new connections".to_string(),
+            risk_level: RiskLevel::Medium,
+            restart_required: false,
         });
 
         // PPLNS settings
@@ -202,6 +647,8 @@ impl ConfigManager {
                 }
             ],
             description: "PPLNS time-to-live in days".to_string(),
+            risk_level: RiskLevel::Critical,
+            restart_required: false,
         });
 
         schema.insert("donation".to_string(), ConfigSchema {
@@ -214,9 +661,16 @@ impl ConfigManager {
                     rule_type: "critical".to_string(),
                     params: serde_json::json!({"forbidden": 10000}),
                     error_message: "Donation of 100% (10000 basis points) prevents payouts!".to_string(),
+                },
+                ValidationRule {
+                    rule_type: "range_warning".to_string(),
+                    params: serde_json::json!({"max": 500}),
+                    error_message: "Donation above 5% (500 basis points) noticeably cuts miner payouts".to_string(),
                 }
             ],
             description: "Pool donation in basis points (0-10000)".to_string(),
+            risk_level: RiskLevel::Critical,
+            restart_required: false,
         });
 
         schema
@@ -290,8 +744,17 @@ impl ConfigManager {
             ));
         }
 
-        // Generate version ID
-        let version_id = format!("v{}", Utc::now().format("%Y%m%d%H%M%S"));
+        // Generate version ID, unique even for several changes within a second
+        let base_id = format!("v{}", Utc::now().format("%Y%m%d%H%M%S"));
+        let mut version_id = base_id.clone();
+        {
+            let versions = self.versions.read().await;
+            let mut n = 1;
+            while versions.contains_key(&version_id) {
+                version_id = format!("{}_{}", base_id, n);
+                n += 1;
+            }
+        }
 
         // Get parent version
         let parent_id = self.current_version.read().await.clone();
@@ -318,6 +781,8 @@ impl ConfigManager {
         versions.insert(version_id.clone(), version.clone());
 
         info!("Created configuration version {}: {}", version_id, description);
+        // No receivers is not an error
+        let _ = self.versions_created.send(version.clone());
 
         Ok(version)
     }
@@ -350,6 +815,24 @@ impl ConfigManager {
         versions.get(&current_id).cloned()
     }
 
+    /// Fail unless `expected` is still the current version
+    ///
+    /// Callers hold the shared config's write lock while checking, so no other
+    /// change can slip in between the check and their own.
+    pub async fn check_version(&self, expected: &str) -> std::result::Result<(), VersionConflict> {
+        let current = self.current_version().await;
+        if current.as_ref().is_some_and(|v| v.id == expected) {
+            return Ok(());
+        }
+        Err(VersionConflict {
+            expected_version: expected.to_string(),
+            current_version: current.as_ref().map(|v| v.id.clone()),
+            changed_by: current.as_ref().map(|v| v.created_by.clone()),
+            changed_at: current.as_ref().map(|v| v.created_at),
+            description: current.map(|v| v.description),
+        })
+    }
+
     /// Get a specific version by ID
     pub async fn get_version(&self, version_id: &str) -> Option<ConfigVersion> {
         let versions = self.versions.read().await;
@@ -364,6 +847,21 @@ impl ConfigManager {
         list
     }
 
+    /// Check a new value of a runtime-adjustable parameter against its schema
+    pub async fn validate_parameter(&self, parameter: &str, value: &serde_json::Value) -> Result<()> {
+        let key = schema_key(parameter)
+            .ok_or_else(|| anyhow::anyhow!("{} cannot be changed at runtime", parameter))?;
+        let errors = match self.schema.read().await.get(key) {
+            Some(param_schema) => check_value(key, param_schema, value),
+            None => Vec::new(),
+        };
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(errors.join("; ")))
+        }
+    }
+
     /// Validate configuration against schema
     pub async fn validate_config(&self, config: &serde_json::Value) -> ValidationStatus {
         let schema = self.schema.read().await;
@@ -380,59 +878,7 @@ impl ConfigManager {
             }
 
             if let Some(val) = value {
-                // Type validation
-                match &param_schema.parameter_type {
-                    ConfigType::String => {
-                        if !val.is_string() {
-                            errors.push(format!("{} must be a string", path));
-                        }
-                    }
-                    ConfigType::Integer { min, max } => {
-                        if let Some(n) = val.as_i64() {
-                            if *min > 0 && n < *min {
-                                errors.push(format!("{} must be >= {}", path, min));
-                            }
-                            if *max > 0 && n > *max {
-                                errors.push(format!("{} must be <= {}", path, max));
-                            }
-                        } else {
-                            errors.push(format!("{} must be an integer", path));
-                        }
-                    }
-                    ConfigType::Float { min, max } => {
-                        if let Some(f) = val.as_f64() {
-                            if *min > 0.0 && f < *min {
-                                errors.push(format!("{} must be >= {}", path, min));
-                            }
-                            if *max > 0.0 && f > *max {
-                                errors.push(format!("{} must be <= {}", path, max));
-                            }
-                        } else {
-                            errors.push(format!("{} must be a number", path));
-                        }
-                    }
-                    ConfigType::Boolean => {
-                        if !val.is_boolean() {
-                            errors.push(format!("{} must be a boolean", path));
-                        }
-                    }
-                    ConfigType::Enum { options } => {
-                        if let Some(s) = val.as_str() {
-                            if !options.contains(&s.to_string()) {
-                                errors.push(format!("{} must be one of: {:?}", path, options));
-                            }
-                        } else {
-                            errors.push(format!("{} must be a string", path));
-                        }
-                    }
-                }
-
-                // Run custom validation rules
-                for rule in &param_schema.validation_rules {
-                    if !self.run_validation_rule(val, rule) {
-                        errors.push(rule.error_message.clone());
-                    }
-                }
+                errors.extend(check_value(path, param_schema, val));
             }
         }
 
@@ -443,27 +889,6 @@ impl ConfigManager {
         }
     }
 
-    /// Run a validation rule on a value
-    fn run_validation_rule(&self, value: &serde_json::Value, rule: &ValidationRule) -> bool {
-        match rule.rule_type.as_str() {
-            "range_warning" => {
-                // This is a warning, not a hard failure
-                return true;
-            }
-            "critical" => {
-                if let Some(params) = rule.params.as_object() {
-                    if let Some(forbidden) = params.get("forbidden") {
-                        if let Some(n) = value.as_i64() {
-                            return n != *forbidden;
-                        }
-                    }
-                }
-                true
-            }
-            _ => true
-        }
-    }
-
     /// Compare two configuration versions
     pub async fn diff_versions(&self, version_a_id: &str, version_b_id: &str) -> Result<ConfigDiff> {
         let versions = self.versions.read().await;
@@ -533,6 +958,8 @@ impl ConfigManager {
             }
         }
 
+        changes.sort_by(|a, b| a.path.cmp(&b.path));
+
         // Identify critical changes
         let critical_params = ["pplns_ttl_days", "donation", "ignore_difficulty"]
             .map(|s| s.to_string());
@@ -558,6 +985,126 @@ impl ConfigManager {
         })
     }
 
+    /// Record the running config as the first version if none exists yet
+    pub async fn ensure_baseline(&self, config: &Config) -> Result<Option<ConfigVersion>> {
+        if self.current_version.read().await.is_some() {
+            return Ok(None);
+        }
+        let version = self.create_version(
+            config_params(config),
+            "Initial configuration".to_string(),
+            "system".to_string(),
+        ).await?;
+        Ok(Some(version))
+    }
+
+    /// Apply a single parameter change to the shared runtime config
+    ///
+    /// The change is made on a copy, validated against the schema and
+    /// snapshotted as a new version before it replaces the live config, so a
+    /// failure at any step leaves the running config untouched. With an
+    /// `expected_version` the change fails with a [`VersionConflict`] if the
+    /// config has moved on since, and without `force` it fails with an
+    /// [`UnsafeChange`](crate::safety::UnsafeChange) if it introduces
+    /// critical safety issues.
+    pub async fn apply_change(
+        &self,
+        config: &RwLock<Config>,
+        parameter: &str,
+        value: &serde_json::Value,
+        applied_by: &str,
+        expected_version: Option<&str>,
+        force: bool,
+    ) -> Result<ConfigVersion> {
+        let mut live = config.write().await;
+        if let Some(expected) = expected_version {
+            self.check_version(expected).await?;
+        }
+        let mut candidate = live.clone();
+        set_parameter(&mut candidate, parameter, value)?;
+        SafetyAnalyzer::new().check_change(&live, &candidate, force)?;
+
+        let old_value = parameter_value(&live, parameter).unwrap_or_default();
+        let version = self.create_version(
+            config_params(&candidate),
+            format!("{}: {} -> {}", parameter, old_value, value),
+            applied_by.to_string(),
+        ).await?;
+
+        *live = candidate;
+        info!("Applied config change {} = {} (version {})", parameter, value, version.id);
+        Ok(version)
+    }
+
+    /// Snapshot the running config after a change made outside `apply_change`
+    pub async fn record(&self, config: &Config, description: String, created_by: &str) -> Result<ConfigVersion> {
+        self.create_version(config_params(config), description, created_by.to_string()).await
+    }
+
+    /// Replace the whole shared config, e.g. after reloading it from disk
+    ///
+    /// Goes through the same version check, safety gate and snapshot as
+    /// `apply_change`, so an unsafe reload is refused unless `force` is set.
+    pub async fn replace_config(
+        &self,
+        config: &RwLock<Config>,
+        candidate: Config,
+        description: String,
+        applied_by: &str,
+        expected_version: Option<&str>,
+        force: bool,
+    ) -> Result<ConfigVersion> {
+        let mut live = config.write().await;
+        if let Some(expected) = expected_version {
+            self.check_version(expected).await?;
+        }
+        SafetyAnalyzer::new().check_change(&live, &candidate, force)?;
+
+        let version = self.create_version(
+            config_params(&candidate),
+            description,
+            applied_by.to_string(),
+        ).await?;
+
+        *live = candidate;
+        info!("Replaced configuration as version {}", version.id);
+        Ok(version)
+    }
+
+    /// Restore the runtime parameters of a stored version into the shared config
+    ///
+    /// Like `apply_change`, the live config is only replaced once the result
+    /// has validated, passed the safety gate and been recorded as a new version.
+    pub async fn rollback_config(
+        &self,
+        config: &RwLock<Config>,
+        version_id: &str,
+        performed_by: &str,
+        force: bool,
+    ) -> Result<ConfigVersion> {
+        let target = self.get_version(version_id).await
+            .ok_or_else(|| anyhow::anyhow!("Version not found: {}", version_id))?;
+
+        let mut live = config.write().await;
+        let mut candidate = live.clone();
+        for (parameter, key) in RUNTIME_PARAMETERS {
+            if let Some(value) = target.config_data.get(*key) {
+                set_parameter(&mut candidate, parameter, value)?;
+            }
+        }
+        SafetyAnalyzer::new().check_change(&live, &candidate, force)?;
+
+        let version = self.create_version(
+            config_params(&candidate),
+            format!("Rollback to {}", version_id),
+            performed_by.to_string(),
+        ).await?;
+
+        *live = candidate;
+        info!("Rolled back configuration to {} as version {}", version_id, version.id);
+        Ok(version)
+    }
+
     /// Rollback to a previous version
     pub async fn rollback(&self, version_id: &str, reason: String, performed_by: String) -> Result<()> {
         let version = self.get_version(version_id).await
@@ -716,6 +1263,134 @@ mod tests {
         assert!(manager.list_versions().await.len() > 0);
     }
 
+    #[tokio::test]
+    async fn test_versions_are_unique_and_schema_rejects_forbidden_values() {
+        let storage_dir = tempfile::tempdir().unwrap();
+        let manager = ConfigManager::new(storage_dir.path().to_path_buf());
+        manager.initialize().await.unwrap();
+
+        let mut config = json!({
+            "stratum.port": 3333,
+            "stratum.start_difficulty": 32,
+            "donation": 0,
+            "pplns_ttl_days": 7,
+            "stratum.pool_signature": "dmpool",
+            "ignore_difficulty": false
+        });
+        let first = manager.create_version(config.clone(), "a".to_string(), "test".to_string()).await.unwrap();
+        let second = manager.create_version(config.clone(), "b".to_string(), "test".to_string()).await.unwrap();
+        assert_ne!(first.id, second.id);
+        assert_eq!(manager.current_version().await.unwrap().id, second.id);
+
+        config["ignore_difficulty"] = json!(true);
+        assert!(matches!(manager.validate_config(&config).await, ValidationStatus::Invalid { .. }));
+        config["ignore_difficulty"] = json!(false);
+        config["stratum.pool_signature"] = json!("a signature that is too long");
+        assert!(manager.create_version(config, "c".to_string(), "test".to_string()).await.is_err());
+        assert_eq!(manager.list_versions().await.len(), 2);
+
+        assert_eq!(schema_key("start_difficulty"), Some("stratum.start_difficulty"));
+        assert_eq!(schema_key("stratum.port"), None);
+    }
+
+    #[tokio::test]
+    async fn test_stale_version_conflicts() {
+        let storage_dir = tempfile::tempdir().unwrap();
+        let manager = ConfigManager::new(storage_dir.path().to_path_buf());
+        manager.initialize().await.unwrap();
+
+        let config = json!({
+            "stratum.port": 3333,
+            "stratum.start_difficulty": 32,
+            "donation": 0,
+            "pplns_ttl_days": 7
+        });
+        let base = manager.create_version(config.clone(), "a".to_string(), "alice".to_string()).await.unwrap();
+        assert!(manager.check_version(&base.id).await.is_ok());
+
+        // A second admin still editing the first version is turned away
+        let newer = manager.create_version(config, "b".to_string(), "bob".to_string()).await.unwrap();
+        let conflict = manager.check_version(&base.id).await.unwrap_err();
+        assert_eq!(conflict.current_version.as_deref(), Some(newer.id.as_str()));
+        assert_eq!(conflict.changed_by.as_deref(), Some("bob"));
+        assert_eq!(conflict.description.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn test_update_toml_preserves_comments() {
+        let original = r#"# Pool config
+[stratum]
+# Initial vardiff
+start_difficulty = 32 # per connection
+minimum_difficulty = 16
+# donation = 0
+
+[store]
+path = "./store.db"
+pplns_ttl_days = 7
+"#;
+        let params = json!({
+            "stratum.start_difficulty": 64,
+            "stratum.minimum_difficulty": 16,
+            "stratum.pool_signature": "dmpool",
+            "pplns_ttl_days": 14,
+            "donation": 0,
+            "ignore_difficulty": false
+        });
+        let updated = update_toml(original, &params).unwrap();
+        assert!(updated.contains("# Initial vardiff\nstart_difficulty = 64 # per connection"));
+        assert!(updated.contains("pplns_ttl_days = 14"));
+        assert!(updated.contains("pool_signature = \"dmpool\""));
+        assert!(updated.contains("# donation = 0"));
+        assert!(!updated.contains("ignore_difficulty"));
+        assert!(updated.contains("path = \"./store.db\""));
+
+        // Applying the same values again is a no-op
+        assert_eq!(update_toml(&updated, &params).unwrap(), updated);
+
+        // The rewritten file keeps the original's permissions
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("config.toml");
+            std::fs::write(&path, original).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+            replace_file(&path, "config.toml.tmp", updated.as_bytes()).unwrap();
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), updated);
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_diff_is_sorted_and_readable() {
+        let storage_dir = tempfile::tempdir().unwrap();
+        let manager = ConfigManager::new(storage_dir.path().to_path_buf());
+        manager.initialize().await.unwrap();
+
+        let base = json!({
+            "stratum.port": 3333,
+            "stratum.start_difficulty": 32,
+            "donation": 0,
+            "pplns_ttl_days": 7
+        });
+        let mut changed = base.clone();
+        changed["pplns_ttl_days"] = json!(14);
+        changed["stratum.start_difficulty"] = json!(64);
+        changed["fee"] = json!(100);
+
+        let a = manager.create_version(base, "a".to_string(), "test".to_string()).await.unwrap();
+        let b = manager.create_version(changed, "b".to_string(), "test".to_string()).await.unwrap();
+        let diff = manager.diff_versions(&a.id, &b.id).await.unwrap();
+
+        assert_eq!(diff.describe(), vec![
+            "fee: added 100",
+            "pplns_ttl_days: 7 → 14",
+            "stratum.start_difficulty: 32 → 64",
+        ]);
+        assert_eq!(diff.summary.critical_changes, vec!["pplns_ttl_days"]);
+    }
+
     #[tokio::test]
     async fn test_config_validation() {
         let temp_dir = std::env::temp_dir();
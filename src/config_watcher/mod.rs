@@ -0,0 +1,104 @@
+// Config File Watcher for DMPool
+// Notifies when the config file is edited so safe parameters can be hot-reloaded
+
+use anyhow::{Context, Result};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+/// Editors often write a file in several steps; events closer together than
+/// this are reported as one change
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Watches a single file for modifications
+///
+/// The parent directory is watched rather than the file itself, because many
+/// editors save by writing a new file and renaming it over the old one.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    /// Dropping the watcher stops notifications
+    _watcher: RecommendedWatcher,
+    events: mpsc::UnboundedReceiver<()>,
+}
+
+impl ConfigWatcher {
+    /// Start watching `path`
+    pub fn new(path: &Path) -> Result<Self> {
+        let file_name: OsString = path.file_name()
+            .ok_or_else(|| anyhow::anyhow!("Invalid config path {:?}", path))?
+            .to_os_string();
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let (tx, events) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+            match res {
+                Ok(event) => {
+                    let relevant = !event.kind.is_access()
+                        && event.paths.iter().any(|p| p.file_name() == Some(file_name.as_os_str()));
+                    if relevant {
+                        let _ = tx.send(());
+                    }
+                }
+                Err(e) => warn!("Config watcher error: {}", e),
+            }
+        }).context("Failed to create config file watcher")?;
+        watcher.watch(&dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("Failed to watch {:?}", dir))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    /// Path of the watched file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Wait for the next change to the file, coalescing bursts of events
+    ///
+    /// Returns `None` if the watcher stopped.
+    pub async fn changed(&mut self) -> Option<()> {
+        self.events.recv().await?;
+        loop {
+            match tokio::time::timeout(DEBOUNCE, self.events.recv()).await {
+                Ok(Some(())) => continue,
+                Ok(None) => return None,
+                Err(_) => return Some(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reports_edits_to_the_watched_file_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "a = 1\n").unwrap();
+
+        let mut watcher = ConfigWatcher::new(&path).unwrap();
+
+        std::fs::write(dir.path().join("other.toml"), "b = 1\n").unwrap();
+        let other = tokio::time::timeout(Duration::from_secs(1), watcher.changed()).await;
+        assert!(other.is_err(), "edits to other files must be ignored");
+
+        // Replace via rename, as editors do
+        let tmp = dir.path().join("config.toml.tmp");
+        std::fs::write(&tmp, "a = 2\n").unwrap();
+        std::fs::rename(&tmp, &path).unwrap();
+        let changed = tokio::time::timeout(Duration::from_secs(5), watcher.changed()).await;
+        assert_eq!(changed.unwrap(), Some(()));
+    }
+}
@@ -1,6 +1,7 @@
 // Configuration Confirmation Module for DMPool Admin
 // Ensures dangerous config changes require explicit confirmation
 
+use crate::config_mgt::{self, ConfigManager, ConfigSchema};
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
@@ -32,16 +33,21 @@ pub struct ConfigChangeRequest {
     pub confirmed: bool,
     /// Whether this change has been applied
     pub applied: bool,
+    /// Config version the change was requested against; applying it fails
+    /// if the config has changed since
+    #[serde(default)]
+    pub base_version: Option<String>,
 }
 
 /// Risk level for configuration changes
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum RiskLevel {
     /// Safe - no confirmation needed
     Safe,
     /// Low - minimal risk
     Low,
     /// Medium - single confirmation required
+    #[default]
     Medium,
     /// High - double confirmation required
     High,
@@ -52,8 +58,6 @@ pub enum RiskLevel {
 /// Configuration change metadata
 #[derive(Clone, Serialize)]
 pub struct ConfigMeta {
-    /// Risk level
-    pub risk_level: RiskLevel,
     /// Description of the risk
     pub risk_description: String,
     /// Recommended value (if applicable)
@@ -66,6 +70,8 @@ pub struct ConfigConfirmation {
     pending: Arc<RwLock<HashMap<String, ConfigChangeRequest>>>,
     /// Configuration metadata for each parameter
     config_meta: HashMap<String, ConfigMeta>,
+    /// Type, range and risk level of each parameter, by schema key
+    schema: HashMap<String, ConfigSchema>,
     /// Confirmation timeout in seconds
     confirmation_timeout: i64,
 }
@@ -75,39 +81,33 @@ impl ConfigConfirmation {
     pub fn new() -> Self {
         let mut config_meta = HashMap::new();
 
-        // Define risk levels for each configuration parameter
+        // Explain the risk of each parameter; risk levels come from the schema
         config_meta.insert("pplns_ttl_days".to_string(), ConfigMeta {
-            risk_level: RiskLevel::Critical,
             risk_description: "TTL < 7天会导致矿工损失收益，TTL = 0会导致矿池无法支付".to_string(),
             recommended_value: Some("7".to_string()),
         });
 
         config_meta.insert("donation".to_string(), ConfigMeta {
-            risk_level: RiskLevel::Critical,
             risk_description: "donation = 10000 会导致矿工收益为0（100%捐赠）".to_string(),
             recommended_value: Some("0".to_string()),
         });
 
         config_meta.insert("ignore_difficulty".to_string(), ConfigMeta {
-            risk_level: RiskLevel::Critical,
             risk_description: "禁用难度验证会导致不公平的PPLNS分配，可能被攻击".to_string(),
             recommended_value: Some("false".to_string()),
         });
 
         config_meta.insert("start_difficulty".to_string(), ConfigMeta {
-            risk_level: RiskLevel::Medium,
             risk_description: "过高会导致矿工连接困难，过低会增加服务器负载".to_string(),
             recommended_value: Some("32".to_string()),
         });
 
         config_meta.insert("minimum_difficulty".to_string(), ConfigMeta {
-            risk_level: RiskLevel::Medium,
             risk_description: "过低会导致低算力矿工占便宜，过高会排除小矿工".to_string(),
             recommended_value: Some("16".to_string()),
         });
 
         config_meta.insert("pool_signature".to_string(), ConfigMeta {
-            risk_level: RiskLevel::Low,
             risk_description: "更改pool签名会影响支付识别".to_string(),
             recommended_value: None,
         });
@@ -115,24 +115,29 @@ impl ConfigConfirmation {
         Self {
             pending: Arc::new(RwLock::new(HashMap::new())),
             config_meta,
+            schema: ConfigManager::default_schema(),
             confirmation_timeout: 600, // 10 minutes
         }
     }
 
+    /// Schema of a runtime-adjustable parameter
+    fn schema_of(&self, parameter: &str) -> Option<&ConfigSchema> {
+        self.schema.get(config_mgt::schema_key(parameter)?)
+    }
+
     /// Check if a config change requires confirmation
     pub fn requires_confirmation(&self, parameter: &str) -> bool {
-        match self.config_meta.get(parameter) {
-            Some(meta) => meta.risk_level != RiskLevel::Safe && meta.risk_level != RiskLevel::Low,
+        match self.schema_of(parameter) {
+            Some(schema) => schema.risk_level != RiskLevel::Safe && schema.risk_level != RiskLevel::Low,
             None => true, // Unknown parameters require confirmation
         }
     }
 
     /// Get the risk level for a parameter
     pub fn get_risk_level(&self, parameter: &str) -> RiskLevel {
-        self.config_meta
-            .get(parameter)
-            .map(|m| m.risk_level)
-            .unwrap_or(RiskLevel::Medium)
+        self.schema_of(parameter)
+            .map(|s| s.risk_level)
+            .unwrap_or_default()
     }
 
     /// Create a change request for a configuration parameter
@@ -143,6 +148,7 @@ impl ConfigConfirmation {
         new_value: serde_json::Value,
         username: String,
         ip_address: String,
+        base_version: Option<String>,
     ) -> Result<ConfigChangeRequest> {
         let id = uuid::Uuid::new_v4().to_string();
         let created_at = Utc::now();
@@ -160,6 +166,7 @@ impl ConfigConfirmation {
             expires_at,
             confirmed: false,
             applied: false,
+            base_version,
         };
 
         // Store the pending request
@@ -271,49 +278,22 @@ impl ConfigConfirmation {
         self.config_meta.get(parameter)
     }
 
-    /// Validate a new configuration value
+    /// Validate a new configuration value against the parameter's schema
+    ///
+    /// Values outside a recommended range are accepted with a warning.
     pub fn validate_value(&self, parameter: &str, value: &serde_json::Value) -> Result<(), String> {
-        match parameter {
-            "pplns_ttl_days" => {
-                if let Some(days) = value.as_i64() {
-                    if days < 1 {
-                        return Err("TTL不能小于1天".to_string());
-                    }
-                    if days < 7 {
-                        warn!("TTL={}天低于标准的7天，矿工可能损失收益", days);
-                    }
-                } else {
-                    return Err("TTL必须是整数".to_string());
-                }
-            }
-            "donation" => {
-                if let Some(donation) = value.as_i64() {
-                    if donation < 0 || donation > 10000 {
-                        return Err("donation必须在0-10000之间".to_string());
-                    }
-                    if donation == 10000 {
-                        return Err("donation=10000意味着100%捐赠，矿工收益为0！".to_string());
-                    }
-                    if donation > 500 {
-                        warn!("donation={}较高，相当于{}%捐赠", donation, donation / 100);
-                    }
-                }
-            }
-            "ignore_difficulty" => {
-                if let Some(ignore) = value.as_bool() {
-                    if ignore {
-                        return Err("禁用难度验证非常危险！可能导致不公平的PPLNS分配".to_string());
-                    }
-                }
-            }
-            "start_difficulty" | "minimum_difficulty" => {
-                if let Some(diff) = value.as_i64() {
-                    if diff < 8 || diff > 512 {
-                        return Err("难度必须在8-512之间".to_string());
-                    }
-                }
-            }
-            _ => {}
+        let Some(key) = config_mgt::schema_key(parameter) else {
+            return Ok(());
+        };
+        let Some(schema) = self.schema.get(key) else {
+            return Ok(());
+        };
+        let errors = config_mgt::check_value(key, schema, value);
+        if !errors.is_empty() {
+            return Err(errors.join("; "));
+        }
+        for warning in config_mgt::value_warnings(schema, value) {
+            warn!("{} = {}: {}", parameter, value, warning);
         }
         Ok(())
     }
@@ -366,6 +346,13 @@ mod tests {
         assert!(conf
             .validate_value("ignore_difficulty", &json!(false))
             .is_ok());
+
+        // Ranges come from the config schema
+        assert!(conf.validate_value("start_difficulty", &json!(4)).is_err());
+        assert!(conf.validate_value("minimum_difficulty", &json!("16")).is_err());
+        assert!(conf.validate_value("pool_signature", &json!("a much too long signature")).is_err());
+        assert!(!conf.requires_confirmation("pool_signature"));
+        assert!(conf.requires_confirmation("fee"));
     }
 
     #[tokio::test]
@@ -380,6 +367,7 @@ mod tests {
                 json!(14),
                 "admin".to_string(),
                 "127.0.0.1".to_string(),
+                Some("v20250102120000".to_string()),
             )
             .await
             .unwrap();
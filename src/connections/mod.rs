@@ -0,0 +1,175 @@
+// Connection tracking for DMPool
+// Counts miners connected to the stratum server, in-process or from the OS socket table
+
+use anyhow::{Context, Result};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Source of the number of connected miners
+pub trait ConnectionCounter: Send + Sync {
+    fn active_connections(&self) -> Result<u32>;
+
+    /// Remote address of each connection, where the source knows them
+    fn peer_addresses(&self) -> Result<Vec<IpAddr>> {
+        Ok(Vec::new())
+    }
+}
+
+/// In-process registry updated by the stratum side as connections come and go
+#[derive(Default)]
+pub struct ConnectionRegistry {
+    count: Arc<AtomicU32>,
+}
+
+/// Keeps a connection registered until dropped
+pub struct ConnectionGuard {
+    count: Arc<AtomicU32>,
+}
+
+impl ConnectionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a new connection; hold the guard for the connection's lifetime
+    pub fn connect(&self) -> ConnectionGuard {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        ConnectionGuard { count: self.count.clone() }
+    }
+
+    /// Overwrite the count, for callers that only know the total
+    pub fn set(&self, count: u32) {
+        self.count.store(count, Ordering::Relaxed);
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let _ = self.count.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |c| Some(c.saturating_sub(1)));
+    }
+}
+
+impl ConnectionCounter for ConnectionRegistry {
+    fn active_connections(&self) -> Result<u32> {
+        Ok(self.count.load(Ordering::Relaxed))
+    }
+}
+
+/// Counts established TCP connections to a local port from `/proc/net/tcp{,6}`
+///
+/// Works from any process on the same host, so the admin server can report
+/// connections accepted by the pool.
+pub struct SocketTableCounter {
+    port: u16,
+}
+
+impl SocketTableCounter {
+    pub fn new(port: u16) -> Self {
+        Self { port }
+    }
+}
+
+/// TCP state code for ESTABLISHED in /proc/net/tcp
+const TCP_ESTABLISHED: &str = "01";
+
+/// Established sockets whose local port is `port` in a /proc/net/tcp table, as field lists
+fn established<'a>(table: &'a str, port: u16) -> impl Iterator<Item = Vec<&'a str>> + 'a {
+    table.lines()
+        .skip(1)
+        .map(|line| line.split_whitespace().collect::<Vec<&str>>())
+        .filter(move |fields| {
+            let local_port = fields.get(1)
+                .and_then(|local| local.rsplit(':').next())
+                .and_then(|hex| u16::from_str_radix(hex, 16).ok());
+            local_port == Some(port) && fields.get(3) == Some(&TCP_ESTABLISHED)
+        })
+}
+
+/// Count established sockets whose local port is `port` in a /proc/net/tcp table
+fn count_established(table: &str, port: u16) -> u32 {
+    established(table, port).count() as u32
+}
+
+/// Remote addresses of established sockets on `port`
+fn established_peers(table: &str, port: u16) -> Vec<IpAddr> {
+    established(table, port)
+        .filter_map(|fields| parse_socket_ip(fields.get(2)?.split(':').next()?))
+        .collect()
+}
+
+/// Parse an address from /proc/net/tcp{,6}, written as 32-bit words in host byte order
+fn parse_socket_ip(hex: &str) -> Option<IpAddr> {
+    let words: Vec<u32> = (0..hex.len() / 8)
+        .map(|i| u32::from_str_radix(hex.get(i * 8..i * 8 + 8)?, 16).ok())
+        .collect::<Option<_>>()?;
+    let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_ne_bytes()).collect();
+    match (hex.len(), bytes.len()) {
+        (8, 4) => Some(IpAddr::V4(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]))),
+        (32, 16) => {
+            let octets: [u8; 16] = bytes.try_into().ok()?;
+            let ip = Ipv6Addr::from(octets);
+            // IPv4 clients of a dual-stack socket
+            Some(ip.to_ipv4_mapped().map_or(IpAddr::V6(ip), IpAddr::V4))
+        }
+        _ => None,
+    }
+}
+
+impl ConnectionCounter for SocketTableCounter {
+    fn active_connections(&self) -> Result<u32> {
+        let ipv4 = std::fs::read_to_string("/proc/net/tcp")
+            .context("Failed to read /proc/net/tcp")?;
+        // IPv6 may be disabled
+        let ipv6 = std::fs::read_to_string("/proc/net/tcp6").unwrap_or_default();
+        Ok(count_established(&ipv4, self.port) + count_established(&ipv6, self.port))
+    }
+
+    fn peer_addresses(&self) -> Result<Vec<IpAddr>> {
+        let ipv4 = std::fs::read_to_string("/proc/net/tcp")
+            .context("Failed to read /proc/net/tcp")?;
+        let ipv6 = std::fs::read_to_string("/proc/net/tcp6").unwrap_or_default();
+        let mut peers = established_peers(&ipv4, self.port);
+        peers.extend(established_peers(&ipv6, self.port));
+        Ok(peers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_guards_track_connections() {
+        let registry = ConnectionRegistry::new();
+        let first = registry.connect();
+        let second = registry.connect();
+        assert_eq!(registry.active_connections().unwrap(), 2);
+        drop(first);
+        assert_eq!(registry.active_connections().unwrap(), 1);
+        registry.set(0);
+        drop(second);
+        assert_eq!(registry.active_connections().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_count_established_stratum_sockets() {
+        // Port 3333 is 0x0D05
+        let table = "\
+  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000:0D05 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 1 1
+   1: 0100007F:0D05 0100007F:C350 01 00000000:00000000 00:00000000 00000000     0        0 2 1
+   2: 0100007F:0D05 0100007F:C351 01 00000000:00000000 00:00000000 00000000     0        0 3 1
+   3: 0100007F:C350 0100007F:0D05 01 00000000:00000000 00:00000000 00000000     0        0 4 1
+   4: 0100007F:0D05 0100007F:C352 06 00000000:00000000 00:00000000 00000000     0        0 5 1
+";
+        assert_eq!(count_established(table, 3333), 2);
+        assert_eq!(count_established(table, 8080), 0);
+        assert_eq!(established_peers(table, 3333), vec![IpAddr::from([127, 0, 0, 1]); 2]);
+
+        // 2001:db8::1 and the IPv4-mapped ::ffff:203.0.113.7
+        assert_eq!(parse_socket_ip("B80D0120000000000000000001000000"), Some("2001:db8::1".parse().unwrap()));
+        assert_eq!(parse_socket_ip("0000000000000000FFFF0000077100CB"), Some("203.0.113.7".parse().unwrap()));
+        assert_eq!(parse_socket_ip("0100007"), None);
+    }
+}
@@ -0,0 +1,224 @@
+// CORS Policy for DMPool
+// Which browser origins may call the admin API, and preflight handling
+
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+
+/// Methods allowed when `CORS_ALLOWED_METHODS` is unset
+const DEFAULT_METHODS: [Method; 5] = [Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE];
+/// Request headers allowed when `CORS_ALLOWED_HEADERS` is unset
+const DEFAULT_HEADERS: [&str; 3] = ["authorization", "content-type", "x-request-id"];
+/// How long browsers may cache a preflight result by default
+const DEFAULT_MAX_AGE_SECS: u64 = 600;
+
+/// Cross-origin access to the admin API; no origin is allowed by default
+#[derive(Clone, Debug)]
+pub struct CorsPolicy {
+    /// Exact origins, e.g. `https://dashboard.example.com`, or `*` for any
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<Method>,
+    /// Lowercase request header names
+    pub allowed_headers: Vec<String>,
+    /// Let browsers send cookies and read responses of credentialed requests
+    pub allow_credentials: bool,
+    pub max_age_secs: u64,
+}
+
+impl Default for CorsPolicy {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            allowed_methods: DEFAULT_METHODS.to_vec(),
+            allowed_headers: DEFAULT_HEADERS.iter().map(|h| h.to_string()).collect(),
+            allow_credentials: false,
+            max_age_secs: DEFAULT_MAX_AGE_SECS,
+        }
+    }
+}
+
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').map(str::trim).filter(|item| !item.is_empty())
+}
+
+impl CorsPolicy {
+    /// Read `CORS_ALLOWED_ORIGINS`, `CORS_ALLOWED_METHODS`, `CORS_ALLOWED_HEADERS`,
+    /// `CORS_ALLOW_CREDENTIALS` and `CORS_MAX_AGE_SECS`
+    pub fn from_env() -> Result<Self> {
+        let mut policy = Self::default();
+        if let Ok(origins) = std::env::var("CORS_ALLOWED_ORIGINS") {
+            for origin in split_list(&origins) {
+                policy.add_origin(origin)?;
+            }
+        }
+        if let Ok(methods) = std::env::var("CORS_ALLOWED_METHODS") {
+            policy.allowed_methods = split_list(&methods)
+                .map(|m| Method::from_bytes(m.to_ascii_uppercase().as_bytes())
+                    .map_err(|_| anyhow!("Invalid CORS method: {}", m)))
+                .collect::<Result<_>>()?;
+        }
+        if let Ok(headers) = std::env::var("CORS_ALLOWED_HEADERS") {
+            policy.allowed_headers = split_list(&headers).map(str::to_ascii_lowercase).collect();
+        }
+        policy.allow_credentials = std::env::var("CORS_ALLOW_CREDENTIALS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(false);
+        policy.max_age_secs = std::env::var("CORS_MAX_AGE_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_AGE_SECS);
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    /// Allow an origin; scheme and host are required and a trailing slash is dropped
+    pub fn add_origin(&mut self, origin: &str) -> Result<()> {
+        let origin = origin.trim_end_matches('/');
+        if origin != "*" {
+            let uri: Uri = origin.parse().map_err(|_| anyhow!("Invalid CORS origin: {}", origin))?;
+            if uri.scheme().is_none() || uri.host().is_none() || !matches!(uri.path(), "" | "/") || uri.query().is_some() {
+                return Err(anyhow!("CORS origin must be scheme://host[:port]: {}", origin));
+            }
+        }
+        self.allowed_origins.push(origin.to_ascii_lowercase());
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.allow_credentials && self.allows_any_origin() {
+            return Err(anyhow!("CORS_ALLOW_CREDENTIALS can't be combined with a `*` origin"));
+        }
+        Ok(())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.allowed_origins.is_empty()
+    }
+
+    fn allows_any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|o| o == "*")
+    }
+
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        let origin = origin.to_ascii_lowercase();
+        self.allowed_origins.iter().any(|allowed| allowed == "*" || *allowed == origin)
+    }
+
+    /// Whether a preflight for `method` with the comma-separated `headers` may proceed
+    pub fn allows_request(&self, method: &str, headers: &str) -> bool {
+        self.allowed_methods.iter().any(|m| m.as_str().eq_ignore_ascii_case(method))
+            && split_list(headers).all(|h| self.allowed_headers.iter().any(|a| a.eq_ignore_ascii_case(h)))
+    }
+
+    /// Headers every response to an allowed origin carries
+    fn add_origin_headers(&self, origin: &HeaderValue, headers: &mut HeaderMap) {
+        let allow_origin = if self.allows_any_origin() && !self.allow_credentials {
+            HeaderValue::from_static("*")
+        } else {
+            origin.clone()
+        };
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+        if self.allow_credentials {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+        }
+        headers.append(header::VARY, HeaderValue::from_static("origin"));
+    }
+
+    fn preflight(&self, origin: &HeaderValue, request: &HeaderMap) -> Response {
+        let method = request.get(header::ACCESS_CONTROL_REQUEST_METHOD)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        let requested_headers = request.get(header::ACCESS_CONTROL_REQUEST_HEADERS)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        if !self.allows_request(method, requested_headers) {
+            return StatusCode::FORBIDDEN.into_response();
+        }
+
+        let mut response = StatusCode::NO_CONTENT.into_response();
+        let headers = response.headers_mut();
+        self.add_origin_headers(origin, headers);
+        let methods = self.allowed_methods.iter().map(Method::as_str).collect::<Vec<_>>().join(", ");
+        if let Ok(methods) = HeaderValue::from_str(&methods) {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, methods);
+        }
+        if let Ok(allowed) = HeaderValue::from_str(&self.allowed_headers.join(", ")) {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, allowed);
+        }
+        headers.insert(header::ACCESS_CONTROL_MAX_AGE, HeaderValue::from(self.max_age_secs));
+        response
+    }
+}
+
+/// Answer preflights and mark responses readable by allowed origins
+///
+/// Requests without an `Origin` header, or from origins that aren't allowed,
+/// pass through untouched: the browser then refuses to expose the response.
+/// Preflights from such origins are refused with 403.
+pub async fn cors_middleware(
+    State(policy): State<Arc<CorsPolicy>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(origin) = req.headers().get(header::ORIGIN).cloned() else {
+        return next.run(req).await;
+    };
+    let allowed = origin.to_str().map(|o| policy.allows_origin(o)).unwrap_or(false);
+    let is_preflight = req.method() == Method::OPTIONS
+        && req.headers().contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+
+    if is_preflight {
+        if !allowed {
+            return StatusCode::FORBIDDEN.into_response();
+        }
+        return policy.preflight(&origin, req.headers());
+    }
+
+    let mut response = next.run(req).await;
+    if allowed {
+        policy.add_origin_headers(&origin, response.headers_mut());
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_origins_and_preflight_requests() {
+        let mut policy = CorsPolicy::default();
+        assert!(!policy.is_enabled());
+        assert!(!policy.allows_origin("https://dashboard.example.com"));
+
+        policy.add_origin("https://Dashboard.example.com/").unwrap();
+        assert!(policy.allows_origin("https://dashboard.example.com"));
+        assert!(!policy.allows_origin("http://dashboard.example.com"));
+        assert!(!policy.allows_origin("https://dashboard.example.com.evil.net"));
+        assert!(policy.add_origin("https://example.com/admin").is_err());
+        assert!(policy.add_origin("dashboard.example.com").is_err());
+
+        assert!(policy.allows_request("POST", "Authorization, Content-Type"));
+        assert!(policy.allows_request("delete", ""));
+        assert!(!policy.allows_request("TRACE", ""));
+        assert!(!policy.allows_request("GET", "x-custom"));
+
+        let preflight = policy.preflight(
+            &HeaderValue::from_static("https://dashboard.example.com"),
+            &HeaderMap::from_iter([(header::ACCESS_CONTROL_REQUEST_METHOD, HeaderValue::from_static("PUT"))]),
+        );
+        assert_eq!(preflight.status(), StatusCode::NO_CONTENT);
+        assert_eq!(preflight.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://dashboard.example.com");
+        assert_eq!(preflight.headers()[header::ACCESS_CONTROL_MAX_AGE], "600");
+
+        policy.add_origin("*").unwrap();
+        policy.allow_credentials = true;
+        assert!(policy.validate().is_err());
+    }
+}
@@ -0,0 +1,191 @@
+// Cron Expressions for DMPool
+// Five-field cron schedules ("0 3 * * *") evaluated in UTC
+
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Duration, Timelike, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// Parsed cron expression: minute, hour, day of month, month, day of week
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CronExpr {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether day of month and day of week were both restricted, in which
+    /// case a day matching either runs (as in standard cron)
+    day_or_weekday: bool,
+}
+
+/// Parse one field into a bitmask of allowed values
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>()
+                .map_err(|_| anyhow::anyhow!("Invalid step in cron field: {}", part))?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(anyhow::anyhow!("Cron step must be positive: {}", part));
+        }
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (start.parse()?, end.parse()?)
+        } else {
+            let value: u32 = range.parse()
+                .map_err(|_| anyhow::anyhow!("Invalid cron field: {}", part))?;
+            // "5/15" means from 5 to the end in steps of 15
+            (value, if part.contains('/') { max } else { value })
+        };
+        if start < min || end > max || start > end {
+            return Err(anyhow::anyhow!("Cron field {} out of range {}-{}", part, min, max));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+impl FromStr for CronExpr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let source = s.trim();
+        let expanded = match source {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(anyhow::anyhow!("Cron expression needs 5 fields: {}", source));
+        }
+        let mut weekdays = parse_field(fields[4], 0, 7)?;
+        // Both 0 and 7 are Sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            source: source.to_string(),
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            weekdays,
+            day_or_weekday: fields[2] != "*" && fields[4] != "*",
+        })
+    }
+}
+
+impl CronExpr {
+    /// Every day at `hour:minute`, clamped to valid times
+    pub fn daily(hour: u32, minute: u32) -> Self {
+        let (hour, minute) = (hour.min(23), minute.min(59));
+        let every = |min: u32, max: u32| (min..=max).fold(0u64, |mask, value| mask | 1 << value);
+        Self {
+            source: format!("{} {} * * *", minute, hour),
+            minutes: 1 << minute,
+            hours: 1 << hour,
+            days: every(1, 31),
+            months: every(1, 12),
+            weekdays: every(0, 6),
+            day_or_weekday: false,
+        }
+    }
+
+    fn day_matches(&self, time: &DateTime<Utc>) -> bool {
+        let day = self.days & (1 << time.day()) != 0;
+        let weekday = self.weekdays & (1 << time.weekday().num_days_from_sunday()) != 0;
+        if self.day_or_weekday { day || weekday } else { day && weekday }
+    }
+
+    /// First matching minute strictly after `after`
+    ///
+    /// Returns `None` for expressions that never match, such as "0 0 31 2 *".
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut time = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        // Five years covers every valid day/month combination
+        let limit = after + Duration::days(5 * 366);
+        while time <= limit {
+            if self.months & (1 << time.month()) == 0 {
+                let (year, month) = if time.month() == 12 { (time.year() + 1, 1) } else { (time.year(), time.month() + 1) };
+                time = time.with_day(1)?.with_hour(0)?.with_minute(0)?.with_month(month)?.with_year(year)?;
+            } else if !self.day_matches(&time) {
+                time = time.with_hour(0)?.with_minute(0)? + Duration::days(1);
+            } else if self.hours & (1 << time.hour()) == 0 {
+                time = time.with_minute(0)? + Duration::hours(1);
+            } else if self.minutes & (1 << time.minute()) == 0 {
+                time += Duration::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+}
+
+impl fmt::Display for CronExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Serialize for CronExpr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+impl<'de> Deserialize<'de> for CronExpr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let source = String::deserialize(deserializer)?;
+        source.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+    }
+
+    #[test]
+    fn test_next_runs() {
+        let daily: CronExpr = "0 3 * * *".parse().unwrap();
+        assert_eq!(CronExpr::daily(3, 0), daily);
+        assert_eq!(daily.next_after(at(2026, 1, 1, 2, 59)), Some(at(2026, 1, 1, 3, 0)));
+        assert_eq!(daily.next_after(at(2026, 1, 1, 3, 0)), Some(at(2026, 1, 2, 3, 0)));
+
+        let hourly: CronExpr = "@hourly".parse().unwrap();
+        assert_eq!(hourly.next_after(at(2026, 12, 31, 23, 30)), Some(at(2027, 1, 1, 0, 0)));
+
+        // Every 15 minutes during weekday business hours; 2026-01-03 is a Saturday
+        let business: CronExpr = "*/15 9-17 * * 1-5".parse().unwrap();
+        assert_eq!(business.next_after(at(2026, 1, 2, 17, 50)), Some(at(2026, 1, 5, 9, 0)));
+
+        // Day of month or Sunday (7)
+        let either: CronExpr = "30 1 15 * 7".parse().unwrap();
+        assert_eq!(either.next_after(at(2026, 1, 5, 0, 0)), Some(at(2026, 1, 11, 1, 30)));
+        assert_eq!(either.next_after(at(2026, 1, 11, 2, 0)), Some(at(2026, 1, 15, 1, 30)));
+
+        let leap: CronExpr = "0 0 29 2 *".parse().unwrap();
+        assert_eq!(leap.next_after(at(2026, 3, 1, 0, 0)), Some(at(2028, 2, 29, 0, 0)));
+        assert_eq!("0 0 31 2 *".parse::<CronExpr>().unwrap().next_after(at(2026, 1, 1, 0, 0)), None);
+
+        assert!("0 3 * *".parse::<CronExpr>().is_err());
+        assert!("60 * * * *".parse::<CronExpr>().is_err());
+        assert!("*/0 * * * *".parse::<CronExpr>().is_err());
+    }
+}
@@ -0,0 +1,145 @@
+// Earnings Estimates for DMPool
+// Expected daily earnings of a hashrate at the current network difficulty, after pool fees
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Blocks between subsidy halvings
+const HALVING_INTERVAL: u64 = 210_000;
+/// Subsidy of the first blocks (50 BTC)
+const INITIAL_SUBSIDY_SATS: u64 = 5_000_000_000;
+/// Hashes needed on average per unit of difficulty
+pub const HASHES_PER_DIFFICULTY: f64 = 4_294_967_296.0;
+/// Target seconds between blocks
+const BLOCK_INTERVAL_SECS: f64 = 600.0;
+const SECS_PER_DAY: f64 = 86_400.0;
+
+/// Hashrate in TH/s of shares totalling `difficulty` over `window_secs`
+pub fn hashrate_ths(difficulty: f64, window_secs: u64) -> f64 {
+    difficulty * HASHES_PER_DIFFICULTY / window_secs.max(1) as f64 / 1e12
+}
+
+/// Block subsidy at `height`, without transaction fees
+pub fn block_subsidy_sats(height: u64) -> u64 {
+    let halvings = height / HALVING_INTERVAL;
+    if halvings >= 64 { 0 } else { INITIAL_SUBSIDY_SATS >> halvings }
+}
+
+/// Chain state the estimate is based on
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NetworkStats {
+    /// Height of the chain tip
+    pub height: u64,
+    pub difficulty: f64,
+}
+
+impl NetworkStats {
+    /// Read from a `getblockchaininfo` response
+    pub fn from_blockchain_info(info: &Value) -> Result<Self> {
+        let height = info["blocks"].as_u64()
+            .ok_or_else(|| anyhow::anyhow!("getblockchaininfo has no block height"))?;
+        let difficulty = info["difficulty"].as_f64()
+            .filter(|d| *d > 0.0)
+            .ok_or_else(|| anyhow::anyhow!("getblockchaininfo has no difficulty"))?;
+        Ok(Self { height, difficulty })
+    }
+
+    /// Network hashrate implied by the difficulty
+    pub fn hashrate_ths(&self) -> f64 {
+        self.difficulty * HASHES_PER_DIFFICULTY / BLOCK_INTERVAL_SECS / 1e12
+    }
+}
+
+/// Pool settings that decide what a miner keeps
+#[derive(Clone, Copy, Debug)]
+pub struct EstimateParams {
+    /// Pool fee plus donation, in basis points
+    pub fee_bps: u16,
+    pub pplns_window_days: u64,
+    /// Transaction fees expected per block on top of the subsidy
+    pub tx_fees_sats: u64,
+}
+
+/// Expected earnings of a hashrate; actual earnings vary with pool luck
+#[derive(Clone, Debug, Serialize)]
+pub struct EarningsEstimate {
+    pub hashrate_ths: f64,
+    pub network_height: u64,
+    pub network_difficulty: f64,
+    pub network_hashrate_ths: f64,
+    /// Reward of the next block: subsidy plus expected transaction fees
+    pub block_reward_sats: u64,
+    pub block_subsidy_sats: u64,
+    pub tx_fees_sats: u64,
+    pub fee_bps: u16,
+    /// Blocks the hashrate finds per day on average
+    pub blocks_per_day: f64,
+    pub gross_sats_per_day: f64,
+    pub fee_sats_per_day: f64,
+    pub net_sats_per_day: f64,
+    pub net_sats_per_week: f64,
+    /// Over 30 days
+    pub net_sats_per_month: f64,
+    /// Days until a new miner's shares fill the PPLNS window; earnings grow
+    /// towards the daily rate until then
+    pub ramp_up_days: u64,
+}
+
+/// Estimate the earnings of `hashrate_ths` mining with the pool
+pub fn estimate(hashrate_ths: f64, network: &NetworkStats, params: &EstimateParams) -> Result<EarningsEstimate> {
+    if !hashrate_ths.is_finite() || hashrate_ths <= 0.0 {
+        return Err(anyhow::anyhow!("Hashrate must be a positive number of TH/s"));
+    }
+    let subsidy = block_subsidy_sats(network.height + 1);
+    let reward = subsidy.saturating_add(params.tx_fees_sats);
+    let blocks_per_day = hashrate_ths * 1e12 * SECS_PER_DAY / (network.difficulty * HASHES_PER_DIFFICULTY);
+    let gross = blocks_per_day * reward as f64;
+    let fee = gross * params.fee_bps.min(10_000) as f64 / 10_000.0;
+    let net = gross - fee;
+    Ok(EarningsEstimate {
+        hashrate_ths,
+        network_height: network.height,
+        network_difficulty: network.difficulty,
+        network_hashrate_ths: network.hashrate_ths(),
+        block_reward_sats: reward,
+        block_subsidy_sats: subsidy,
+        tx_fees_sats: params.tx_fees_sats,
+        fee_bps: params.fee_bps,
+        blocks_per_day,
+        gross_sats_per_day: gross,
+        fee_sats_per_day: fee,
+        net_sats_per_day: net,
+        net_sats_per_week: net * 7.0,
+        net_sats_per_month: net * 30.0,
+        ramp_up_days: params.pplns_window_days,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_estimate() {
+        assert_eq!(block_subsidy_sats(0), 5_000_000_000);
+        assert_eq!(block_subsidy_sats(840_000), 312_500_000);
+        assert_eq!(block_subsidy_sats(64 * HALVING_INTERVAL), 0);
+
+        let network = NetworkStats::from_blockchain_info(&json!({ "blocks": 850_000, "difficulty": 1e14 })).unwrap();
+        assert!(NetworkStats::from_blockchain_info(&json!({ "blocks": 1 })).is_err());
+        let params = EstimateParams { fee_bps: 200, pplns_window_days: 7, tx_fees_sats: 20_000_000 };
+        let estimate = estimate(100.0, &network, &params).unwrap();
+        assert_eq!(estimate.block_reward_sats, 332_500_000);
+        // 100 TH/s finds 1e14 * 86400 / (1e14 * 2^32) blocks a day
+        let blocks = 86_400.0 / HASHES_PER_DIFFICULTY;
+        assert!((estimate.blocks_per_day - blocks).abs() < 1e-12);
+        assert!((estimate.net_sats_per_day - blocks * 332_500_000.0 * 0.98).abs() < 1e-6);
+        assert!((estimate.net_sats_per_week - estimate.net_sats_per_day * 7.0).abs() < 1e-6);
+        assert_eq!(estimate.ramp_up_days, 7);
+
+        assert!(super::estimate(0.0, &network, &params).is_err());
+        assert!(super::estimate(f64::NAN, &network, &params).is_err());
+    }
+}
@@ -0,0 +1,170 @@
+// Data Export for DMPool
+// Encodes exported rows as CSV or JSON, piece by piece, for streamed downloads
+
+use anyhow::Result;
+use serde_json::{Map, Value};
+use std::str::FromStr;
+
+/// Output format of an export
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            other => Err(anyhow::anyhow!("Unknown export format: {} (use csv or json)", other)),
+        }
+    }
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Json => "application/json",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+/// Quote a CSV field if it contains a separator, quote or line break
+///
+/// Text starting like a formula, e.g. a worker name chosen by a miner, is
+/// prefixed with `'` so spreadsheets show it instead of evaluating it.
+fn csv_field(value: &Value) -> String {
+    let text = match value {
+        Value::Null => return String::new(),
+        Value::String(s) if s.starts_with(['=', '+', '-', '@', '\t', '\r']) => format!("'{}", s),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+/// Writes a table as a CSV file or a JSON array of objects
+///
+/// `header`, each `row` and `footer` return the next piece of output, so a
+/// response can be streamed without holding the whole export in memory.
+pub struct ExportEncoder {
+    format: ExportFormat,
+    columns: Vec<&'static str>,
+    rows: usize,
+}
+
+impl ExportEncoder {
+    pub fn new(format: ExportFormat, columns: &[&'static str]) -> Self {
+        Self {
+            format,
+            columns: columns.to_vec(),
+            rows: 0,
+        }
+    }
+
+    pub fn header(&self) -> String {
+        match self.format {
+            ExportFormat::Csv => format!("{}\n", self.columns.join(",")),
+            ExportFormat::Json => "[".to_string(),
+        }
+    }
+
+    /// Encode a row; `values` are in column order
+    pub fn row(&mut self, values: &[Value]) -> String {
+        let separator = if self.rows == 0 { "\n" } else { ",\n" };
+        self.rows += 1;
+        match self.format {
+            ExportFormat::Csv => {
+                let fields: Vec<String> = values.iter().map(csv_field).collect();
+                format!("{}\n", fields.join(","))
+            }
+            ExportFormat::Json => {
+                let object: Map<String, Value> = self.columns.iter()
+                    .zip(values)
+                    .map(|(column, value)| (column.to_string(), value.clone()))
+                    .collect();
+                format!("{}{}", separator, Value::Object(object))
+            }
+        }
+    }
+
+    pub fn footer(&self) -> String {
+        match self.format {
+            ExportFormat::Csv => String::new(),
+            ExportFormat::Json if self.rows == 0 => "]\n".to_string(),
+            ExportFormat::Json => "\n]\n".to_string(),
+        }
+    }
+
+    /// Rows encoded so far
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_encodes_csv_and_json() {
+        let rows = [
+            vec![json!("bc1qminer"), json!("rig, \"one\""), json!(512)],
+            vec![json!("bc1qother"), Value::Null, json!(1.5)],
+        ];
+
+        let mut csv = ExportEncoder::new(ExportFormat::Csv, &["address", "worker", "difficulty"]);
+        let mut out = csv.header();
+        for row in &rows {
+            out.push_str(&csv.row(row));
+        }
+        out.push_str(&csv.footer());
+        assert_eq!(out, "address,worker,difficulty\nbc1qminer,\"rig, \"\"one\"\"\",512\nbc1qother,,1.5\n");
+
+        // Formulas in miner-chosen text are neutralized, numbers are kept
+        let mut csv = ExportEncoder::new(ExportFormat::Csv, &["worker", "difficulty"]);
+        for (worker, expected) in [
+            ("=HYPERLINK(\"http://x\")", "\"'=HYPERLINK(\"\"http://x\"\")\""),
+            ("+1", "'+1"),
+            ("-1+2", "'-1+2"),
+            ("@SUM(A1)", "'@SUM(A1)"),
+            ("\tcmd", "'\tcmd"),
+            ("\rcmd", "\"'\rcmd\""),
+            ("rig-1", "rig-1"),
+        ] {
+            assert_eq!(csv.row(&[json!(worker), json!(-1)]), format!("{},-1\n", expected));
+        }
+
+        let mut encoder = ExportEncoder::new(ExportFormat::Json, &["address", "worker", "difficulty"]);
+        let mut out = encoder.header();
+        for row in &rows {
+            out.push_str(&encoder.row(row));
+        }
+        out.push_str(&encoder.footer());
+        let parsed: Vec<Value> = serde_json::from_str(&out).unwrap();
+        assert_eq!(parsed[0]["worker"], "rig, \"one\"");
+        assert_eq!(parsed[1]["difficulty"], 1.5);
+
+        let empty = ExportEncoder::new(ExportFormat::Json, &["address"]);
+        let parsed: Vec<Value> = serde_json::from_str(&(empty.header() + &empty.footer())).unwrap();
+        assert!(parsed.is_empty());
+        assert!("xml".parse::<ExportFormat>().is_err());
+    }
+}
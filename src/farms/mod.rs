@@ -0,0 +1,297 @@
+// Worker Farms for DMPool
+// Per-address worker breakdowns and admin-defined farms grouping several addresses
+
+use crate::estimate::hashrate_ths;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use p2poolv2_lib::accounting::simple_pplns::SimplePplnsShare;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::PathBuf;
+use tokio::sync::RwLock;
+use tracing::info;
+
+/// Longest farm name, in characters
+pub const MAX_FARM_NAME_CHARS: usize = 64;
+/// Most addresses one farm can group
+pub const MAX_FARM_ADDRESSES: usize = 1000;
+/// Worker name reported for shares submitted without one
+const DEFAULT_WORKER_NAME: &str = "worker";
+
+/// Shares of one worker name under an address
+#[derive(Clone, Debug, Serialize)]
+pub struct WorkerBreakdown {
+    pub worker_name: String,
+    pub shares: u64,
+    /// Total difficulty of the shares
+    pub difficulty: u64,
+    pub hashrate_ths: f64,
+    pub last_share_at: Option<DateTime<Utc>>,
+}
+
+/// Shares of one payout address, with a breakdown by worker name
+#[derive(Clone, Debug, Serialize)]
+pub struct AddressWorkers {
+    pub address: String,
+    pub shares: u64,
+    pub difficulty: u64,
+    pub hashrate_ths: f64,
+    pub last_share_at: Option<DateTime<Utc>>,
+    /// Highest hashrate first
+    pub workers: Vec<WorkerBreakdown>,
+}
+
+impl AddressWorkers {
+    /// An address without shares in the window
+    pub fn idle(address: &str) -> Self {
+        Self {
+            address: address.to_string(),
+            shares: 0,
+            difficulty: 0,
+            hashrate_ths: 0.0,
+            last_share_at: None,
+            workers: Vec::new(),
+        }
+    }
+}
+
+/// Group shares submitted over `window_secs` by payout address and worker name,
+/// highest hashrate first
+///
+/// Shares without an address are grouped as `user_<id>`, like the workers list.
+pub fn group_by_address(shares: &[SimplePplnsShare], window_secs: u64) -> Vec<AddressWorkers> {
+    // address -> worker name -> (shares, difficulty, last n_time)
+    let mut grouped: HashMap<String, HashMap<&str, (u64, u64, u64)>> = HashMap::new();
+    for share in shares {
+        let address = share.btcaddress.clone().unwrap_or_else(|| format!("user_{}", share.user_id));
+        let worker = share.workername.as_deref().filter(|w| !w.is_empty()).unwrap_or(DEFAULT_WORKER_NAME);
+        let entry = grouped.entry(address).or_default().entry(worker).or_default();
+        entry.0 += 1;
+        entry.1 += share.difficulty;
+        entry.2 = entry.2.max(share.n_time);
+    }
+
+    let mut addresses: Vec<AddressWorkers> = grouped.into_iter().map(|(address, workers)| {
+        let mut workers: Vec<WorkerBreakdown> = workers.into_iter().map(|(name, (shares, difficulty, last))| {
+            WorkerBreakdown {
+                worker_name: name.to_string(),
+                shares,
+                difficulty,
+                hashrate_ths: hashrate_ths(difficulty as f64, window_secs),
+                last_share_at: DateTime::from_timestamp(last as i64, 0),
+            }
+        }).collect();
+        workers.sort_by(|a, b| b.difficulty.cmp(&a.difficulty).then_with(|| a.worker_name.cmp(&b.worker_name)));
+        let difficulty: u64 = workers.iter().map(|w| w.difficulty).sum();
+        AddressWorkers {
+            address,
+            shares: workers.iter().map(|w| w.shares).sum(),
+            difficulty,
+            hashrate_ths: hashrate_ths(difficulty as f64, window_secs),
+            last_share_at: workers.iter().filter_map(|w| w.last_share_at).max(),
+            workers,
+        }
+    }).collect();
+    addresses.sort_by(|a, b| b.difficulty.cmp(&a.difficulty).then_with(|| a.address.cmp(&b.address)));
+    addresses
+}
+
+/// An admin-defined group of payout addresses mined by one operation
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Farm {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Sorted, without duplicates
+    pub addresses: Vec<String>,
+    pub updated_at: DateTime<Utc>,
+    pub updated_by: String,
+}
+
+/// Combined activity of a farm's addresses
+#[derive(Clone, Debug, Serialize)]
+pub struct FarmStats {
+    pub name: String,
+    pub hashrate_ths: f64,
+    pub shares: u64,
+    /// Distinct worker names across the farm's addresses
+    pub workers: u64,
+    /// Every address of the farm, highest hashrate first
+    pub addresses: Vec<AddressWorkers>,
+}
+
+impl Farm {
+    /// Combine the activity of this farm's addresses from `group_by_address` output
+    pub fn stats(&self, by_address: &[AddressWorkers]) -> FarmStats {
+        let mut addresses: Vec<AddressWorkers> = self.addresses.iter()
+            .map(|address| by_address.iter()
+                .find(|a| a.address == *address)
+                .cloned()
+                .unwrap_or_else(|| AddressWorkers::idle(address)))
+            .collect();
+        addresses.sort_by(|a, b| b.difficulty.cmp(&a.difficulty).then_with(|| a.address.cmp(&b.address)));
+        FarmStats {
+            name: self.name.clone(),
+            hashrate_ths: addresses.iter().map(|a| a.hashrate_ths).sum(),
+            shares: addresses.iter().map(|a| a.shares).sum(),
+            workers: addresses.iter().map(|a| a.workers.len() as u64).sum(),
+            addresses,
+        }
+    }
+}
+
+/// Check a farm name, which is used in URLs and alert rules
+pub fn validate_farm_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.chars().count() > MAX_FARM_NAME_CHARS {
+        return Err(anyhow::anyhow!("Farm name must be 1 to {} characters", MAX_FARM_NAME_CHARS));
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(anyhow::anyhow!("Farm name may only contain letters, digits, '-' and '_'"));
+    }
+    Ok(())
+}
+
+/// Persistent farms, keyed by name
+pub struct FarmStore {
+    path: PathBuf,
+    farms: RwLock<BTreeMap<String, Farm>>,
+}
+
+impl FarmStore {
+    /// Create a store kept at `path`
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            farms: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Load farms from disk, if present
+    pub async fn load(&self) -> Result<usize> {
+        if !self.path.exists() {
+            return Ok(0);
+        }
+        let content = tokio::fs::read_to_string(&self.path).await
+            .context("Failed to read farms")?;
+        let farms: Vec<Farm> = serde_json::from_str(&content)
+            .context("Failed to parse farms")?;
+        let count = farms.len();
+        *self.farms.write().await = farms.into_iter().map(|f| (f.name.clone(), f)).collect();
+        Ok(count)
+    }
+
+    async fn save(&self, farms: &BTreeMap<String, Farm>) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let farms: Vec<&Farm> = farms.values().collect();
+        let tmp = self.path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, serde_json::to_string_pretty(&farms)?).await
+            .context("Failed to write farms")?;
+        tokio::fs::rename(&tmp, &self.path).await
+            .context("Failed to replace farms")?;
+        Ok(())
+    }
+
+    pub async fn get(&self, name: &str) -> Option<Farm> {
+        self.farms.read().await.get(name).cloned()
+    }
+
+    /// Every farm, sorted by name
+    pub async fn list(&self) -> Vec<Farm> {
+        self.farms.read().await.values().cloned().collect()
+    }
+
+    /// Create or replace the farm `name`
+    pub async fn set(&self, name: &str, description: &str, addresses: Vec<String>, updated_by: &str) -> Result<Farm> {
+        validate_farm_name(name)?;
+        let addresses: BTreeSet<String> = addresses.into_iter().map(|a| a.trim().to_string()).collect();
+        if addresses.is_empty() || addresses.iter().any(String::is_empty) {
+            return Err(anyhow::anyhow!("A farm needs at least one address"));
+        }
+        if addresses.len() > MAX_FARM_ADDRESSES {
+            return Err(anyhow::anyhow!("A farm can have at most {} addresses", MAX_FARM_ADDRESSES));
+        }
+        let farm = Farm {
+            name: name.to_string(),
+            description: description.trim().to_string(),
+            addresses: addresses.into_iter().collect(),
+            updated_at: Utc::now(),
+            updated_by: updated_by.to_string(),
+        };
+        let mut farms = self.farms.write().await;
+        farms.insert(name.to_string(), farm.clone());
+        self.save(&farms).await?;
+        info!("Farm {} with {} address(es) saved by {}", name, farm.addresses.len(), updated_by);
+        Ok(farm)
+    }
+
+    /// Remove the farm `name`; returns false if there is none
+    pub async fn remove(&self, name: &str) -> Result<bool> {
+        let mut farms = self.farms.write().await;
+        if farms.remove(name).is_none() {
+            return Ok(false);
+        }
+        self.save(&farms).await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn share(address: &str, worker: &str, difficulty: u64, n_time: u64) -> SimplePplnsShare {
+        SimplePplnsShare {
+            user_id: 1,
+            difficulty,
+            btcaddress: Some(address.to_string()),
+            workername: Some(worker.to_string()),
+            n_time,
+            job_id: String::new(),
+            extranonce2: String::new(),
+            nonce: n_time.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_farms_group_addresses_and_persist() {
+        let shares = vec![
+            share("bc1qa", "rig1", 100, 10),
+            share("bc1qa", "rig2", 300, 20),
+            share("bc1qa", "rig1", 100, 30),
+            share("bc1qb", "rig1", 1000, 15),
+            share("bc1qc", "", 50, 5),
+        ];
+        let grouped = group_by_address(&shares, 600);
+        assert_eq!(grouped.iter().map(|a| a.address.as_str()).collect::<Vec<_>>(), ["bc1qb", "bc1qa", "bc1qc"]);
+        let a = &grouped[1];
+        assert_eq!((a.shares, a.difficulty), (3, 500));
+        assert_eq!(a.workers.iter().map(|w| (w.worker_name.as_str(), w.shares)).collect::<Vec<_>>(), [("rig2", 1), ("rig1", 2)]);
+        assert_eq!(a.last_share_at, DateTime::from_timestamp(30, 0));
+        assert_eq!(grouped[2].workers[0].worker_name, DEFAULT_WORKER_NAME);
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("farms.json");
+        let store = FarmStore::new(path.clone());
+        assert!(store.set("bad name", "", vec!["bc1qa".to_string()], "admin").await.is_err());
+        assert!(store.set("east", "", Vec::new(), "admin").await.is_err());
+        let farm = store.set("east", " Rack A ", vec!["bc1qb".into(), "bc1qa".into(), "bc1qa".into(), "bc1qidle".into()], "admin")
+            .await.unwrap();
+        assert_eq!(farm.addresses, ["bc1qa", "bc1qb", "bc1qidle"]);
+        assert_eq!(farm.description, "Rack A");
+
+        let stats = farm.stats(&grouped);
+        assert_eq!((stats.shares, stats.workers), (4, 3));
+        assert!((stats.hashrate_ths - grouped[0].hashrate_ths - grouped[1].hashrate_ths).abs() < 1e-9);
+        assert_eq!(stats.addresses.last().unwrap().address, "bc1qidle");
+
+        let reloaded = FarmStore::new(path);
+        assert_eq!(reloaded.load().await.unwrap(), 1);
+        assert_eq!(reloaded.get("east").await.unwrap().addresses, farm.addresses);
+        assert!(reloaded.remove("east").await.unwrap());
+        assert!(!reloaded.remove("east").await.unwrap());
+        assert!(reloaded.list().await.is_empty());
+    }
+}
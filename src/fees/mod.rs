@@ -0,0 +1,306 @@
+// Fee Accounting for DMPool
+// Records the pool fee and donation withheld from each credited block and reports them over time
+
+use crate::payout::{BlockCredit, PayoutBatch, PayoutStatus};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use tokio::sync::RwLock;
+use tracing::info;
+
+/// Fee and donation withheld from one block's reward
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FeeRecord {
+    pub height: u64,
+    pub hash: String,
+    /// When the block was found; reports group records by this time
+    pub found_at: DateTime<Utc>,
+    pub reward_sats: u64,
+    pub fee_bps: u16,
+    pub donation_bps: u16,
+    /// Pool fee, including rounding remainders
+    pub fee_sats: u64,
+    pub donation_sats: u64,
+    /// Amount credited to miners
+    pub credited_sats: u64,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// Period covered by a fee report, ending now
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeRange {
+    Day,
+    Week,
+    Month,
+    Year,
+    All,
+}
+
+impl FromStr for FeeRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "day" => Ok(Self::Day),
+            "week" => Ok(Self::Week),
+            "month" => Ok(Self::Month),
+            "year" => Ok(Self::Year),
+            "all" => Ok(Self::All),
+            other => Err(anyhow::anyhow!("Unknown range: {} (expected day, week, month, year or all)", other)),
+        }
+    }
+}
+
+impl FeeRange {
+    /// Length of the range; `None` for all time
+    fn duration(&self) -> Option<Duration> {
+        match self {
+            FeeRange::Day => Some(Duration::days(1)),
+            FeeRange::Week => Some(Duration::days(7)),
+            FeeRange::Month => Some(Duration::days(30)),
+            FeeRange::Year => Some(Duration::days(365)),
+            FeeRange::All => None,
+        }
+    }
+
+    /// Period label of a time: its UTC day, or its month for long ranges
+    fn period_of(&self, time: DateTime<Utc>) -> String {
+        match self {
+            FeeRange::Year | FeeRange::All => time.format("%Y-%m").to_string(),
+            _ => time.format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+/// Amounts summed over a set of blocks
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct FeeTotals {
+    pub blocks: u64,
+    pub reward_sats: u64,
+    pub fee_sats: u64,
+    pub donation_sats: u64,
+    pub credited_sats: u64,
+}
+
+impl FeeTotals {
+    fn add(&mut self, record: &FeeRecord) {
+        self.blocks += 1;
+        self.reward_sats += record.reward_sats;
+        self.fee_sats += record.fee_sats;
+        self.donation_sats += record.donation_sats;
+        self.credited_sats += record.credited_sats;
+    }
+}
+
+/// Totals of one day or month
+#[derive(Clone, Debug, Serialize)]
+pub struct FeePeriod {
+    /// `YYYY-MM-DD`, or `YYYY-MM` for year and all-time reports
+    pub period: String,
+    #[serde(flatten)]
+    pub totals: FeeTotals,
+}
+
+/// Fee revenue over a range, next to what was paid out in it
+#[derive(Clone, Debug, Serialize)]
+pub struct FeeReport {
+    pub range: FeeRange,
+    /// Start of the range; `None` for all time
+    pub from: Option<DateTime<Utc>>,
+    pub to: DateTime<Utc>,
+    pub totals: FeeTotals,
+    /// Periods with blocks, oldest first
+    pub periods: Vec<FeePeriod>,
+    /// Payout batches broadcast or confirmed in the range
+    pub payout_batches: u64,
+    pub paid_out_sats: u64,
+}
+
+/// Split what a block withheld from miners into donation and pool fee
+///
+/// The donation is its share of the reward; the fee keeps the rest, so
+/// rounding remainders count as fee.
+fn split_withheld(reward_sats: u64, withheld_sats: u64, donation_bps: u16) -> (u64, u64) {
+    let donation = (reward_sats as u128 * donation_bps as u128 / 10_000) as u64;
+    let donation = donation.min(withheld_sats);
+    (withheld_sats - donation, donation)
+}
+
+/// Persistent per-block fee records
+pub struct FeeLedger {
+    path: PathBuf,
+    records: RwLock<Vec<FeeRecord>>,
+}
+
+impl FeeLedger {
+    /// Create a ledger stored at `path`
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            records: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Load records from disk, if present
+    pub async fn load(&self) -> Result<usize> {
+        if !self.path.exists() {
+            return Ok(0);
+        }
+        let content = tokio::fs::read_to_string(&self.path).await
+            .context("Failed to read fee ledger")?;
+        let records: Vec<FeeRecord> = serde_json::from_str(&content)
+            .context("Failed to parse fee ledger")?;
+        let count = records.len();
+        *self.records.write().await = records;
+        Ok(count)
+    }
+
+    async fn save(&self, records: &[FeeRecord]) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, serde_json::to_string_pretty(records)?).await
+            .context("Failed to write fee ledger")?;
+        tokio::fs::rename(&tmp, &self.path).await
+            .context("Failed to replace fee ledger")?;
+        Ok(())
+    }
+
+    /// Record the fee and donation of a credited block
+    ///
+    /// `fee_bps` and `donation_bps` are the rates the block was credited
+    /// with. Returns `None` if the block was already recorded.
+    pub async fn record(
+        &self,
+        credit: &BlockCredit,
+        found_at: DateTime<Utc>,
+        fee_bps: u16,
+        donation_bps: u16,
+    ) -> Result<Option<FeeRecord>> {
+        let mut records = self.records.write().await;
+        if records.iter().any(|r| r.hash == credit.hash) {
+            return Ok(None);
+        }
+        let (fee_sats, donation_sats) = split_withheld(credit.reward_sats, credit.pool_fee_sats, donation_bps);
+        let record = FeeRecord {
+            height: credit.height,
+            hash: credit.hash.clone(),
+            found_at,
+            reward_sats: credit.reward_sats,
+            fee_bps,
+            donation_bps,
+            fee_sats,
+            donation_sats,
+            credited_sats: credit.reward_sats.saturating_sub(credit.pool_fee_sats),
+            recorded_at: Utc::now(),
+        };
+        records.push(record.clone());
+        self.save(&records).await?;
+        info!(
+            "Recorded fees of block {}: {} sats fee, {} sats donation",
+            record.height, record.fee_sats, record.donation_sats
+        );
+        Ok(Some(record))
+    }
+
+    /// Whether a block's fees are recorded
+    pub async fn contains(&self, hash: &str) -> bool {
+        self.records.read().await.iter().any(|r| r.hash == hash)
+    }
+
+    /// Fee records, newest first
+    pub async fn records(&self) -> Vec<FeeRecord> {
+        let mut records = self.records.read().await.clone();
+        records.sort_by_key(|r| std::cmp::Reverse(r.found_at));
+        records
+    }
+
+    /// Fees of blocks found within `range` before `now`, with the payouts sent in it
+    pub async fn report(&self, range: FeeRange, now: DateTime<Utc>, batches: &[PayoutBatch]) -> FeeReport {
+        let from = range.duration().map(|duration| now - duration);
+        let in_range = |time: DateTime<Utc>| from.is_none_or(|from| time >= from) && time <= now;
+
+        let mut totals = FeeTotals::default();
+        let mut periods: BTreeMap<String, FeeTotals> = BTreeMap::new();
+        for record in self.records.read().await.iter().filter(|r| in_range(r.found_at)) {
+            totals.add(record);
+            periods.entry(range.period_of(record.found_at)).or_default().add(record);
+        }
+
+        let sent: Vec<&PayoutBatch> = batches.iter()
+            .filter(|b| matches!(b.status, PayoutStatus::Sent | PayoutStatus::Paid) && in_range(b.updated_at))
+            .collect();
+
+        FeeReport {
+            range,
+            from,
+            to: now,
+            totals,
+            periods: periods.into_iter().map(|(period, totals)| FeePeriod { period, totals }).collect(),
+            payout_batches: sent.len() as u64,
+            paid_out_sats: sent.iter().map(|b| b.total_sats).sum(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credit(height: u64, reward_sats: u64, pool_fee_sats: u64) -> BlockCredit {
+        BlockCredit {
+            height,
+            hash: format!("hash{}", height),
+            reward_sats,
+            pool_fee_sats,
+            window_shares: 10,
+            credits: BTreeMap::new(),
+            credited_at: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_records_and_reports_fees() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fees.json");
+        let ledger = FeeLedger::new(path.clone());
+        let now = Utc::now();
+
+        // 1% fee and 0.5% donation of 1,000,000 sats, plus 3 sats of rounding
+        let record = ledger.record(&credit(1, 1_000_000, 15_003), now - Duration::days(2), 100, 50).await.unwrap().unwrap();
+        assert_eq!((record.fee_sats, record.donation_sats, record.credited_sats), (10_003, 5_000, 984_997));
+        assert!(ledger.record(&credit(1, 1_000_000, 15_003), now, 100, 50).await.unwrap().is_none());
+        ledger.record(&credit(2, 2_000_000, 20_000), now - Duration::days(40), 100, 0).await.unwrap();
+
+        let batch = PayoutBatch {
+            id: "b1".to_string(),
+            created_at: now,
+            status: PayoutStatus::Paid,
+            payments: Vec::new(),
+            total_sats: 500_000,
+            updated_at: now,
+            txid: None,
+            psbt: None,
+            confirmations: 6,
+            note: None,
+        };
+        let month = ledger.report(FeeRange::Month, now, &[batch]).await;
+        assert_eq!(month.totals.blocks, 1);
+        assert_eq!(month.totals.fee_sats, 10_003);
+        assert_eq!(month.periods.len(), 1);
+        assert_eq!(month.paid_out_sats, 500_000);
+
+        let reloaded = FeeLedger::new(path);
+        assert_eq!(reloaded.load().await.unwrap(), 2);
+        let all = reloaded.report(FeeRange::All, now, &[]).await;
+        assert_eq!(all.totals.reward_sats, 3_000_000);
+        assert_eq!(all.totals.donation_sats, 5_000);
+        assert!(all.from.is_none());
+        assert!("fortnight".parse::<FeeRange>().is_err());
+    }
+}
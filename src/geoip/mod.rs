@@ -0,0 +1,200 @@
+// GeoIP for DMPool
+// Country and ASN of IP addresses from MaxMind databases, for connections, audit entries and alerts
+
+use anyhow::{Context, Result};
+use maxminddb::{geoip2, Reader};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::Path;
+use tracing::info;
+
+/// Where an IP address is
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GeoInfo {
+    /// ISO 3166-1 alpha-2 code
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asn: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub as_org: Option<String>,
+}
+
+/// Number of IP addresses per country or network
+#[derive(Clone, Debug, Serialize)]
+pub struct GeoCount {
+    pub key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub count: u64,
+}
+
+/// Where a set of IP addresses is, most common first
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct GeoSummary {
+    pub total: u64,
+    pub countries: Vec<GeoCount>,
+    pub networks: Vec<GeoCount>,
+    /// Private addresses and addresses missing from the databases
+    pub unknown: u64,
+}
+
+/// Lookups in a GeoLite2/GeoIP2 country (or city) database and an ASN database
+///
+/// Either database is optional; without both, every lookup returns `None`.
+#[derive(Default)]
+pub struct GeoIp {
+    country: Option<Reader<Vec<u8>>>,
+    asn: Option<Reader<Vec<u8>>>,
+}
+
+impl GeoIp {
+    /// Lookups that always return `None`
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Open the given `.mmdb` files
+    pub fn open(country_db: Option<&Path>, asn_db: Option<&Path>) -> Result<Self> {
+        let open = |path: &Path| {
+            Reader::open_readfile(path)
+                .with_context(|| format!("Failed to open GeoIP database {}", path.display()))
+        };
+        Ok(Self {
+            country: country_db.map(open).transpose()?,
+            asn: asn_db.map(open).transpose()?,
+        })
+    }
+
+    /// Databases from `GEOIP_COUNTRY_DB` and `GEOIP_ASN_DB`, disabled when neither is set
+    pub fn from_env() -> Result<Self> {
+        let country = std::env::var("GEOIP_COUNTRY_DB").ok();
+        let asn = std::env::var("GEOIP_ASN_DB").ok();
+        let geoip = Self::open(country.as_deref().map(Path::new), asn.as_deref().map(Path::new))?;
+        if geoip.is_enabled() {
+            info!("GeoIP lookups enabled (country: {}, ASN: {})",
+                country.as_deref().unwrap_or("none"), asn.as_deref().unwrap_or("none"));
+        }
+        Ok(geoip)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.country.is_some() || self.asn.is_some()
+    }
+
+    /// Country and network of a public address; `None` if neither is known
+    pub fn lookup(&self, ip: IpAddr) -> Option<GeoInfo> {
+        if !is_public(ip) {
+            return None;
+        }
+        let mut info = GeoInfo::default();
+        if let Some(country) = self.country.as_ref().and_then(|r| r.lookup::<geoip2::Country>(ip).ok()) {
+            info.country = country.country.and_then(|c| c.iso_code).map(str::to_string);
+        }
+        if let Some(asn) = self.asn.as_ref().and_then(|r| r.lookup::<geoip2::Asn>(ip).ok()) {
+            info.asn = asn.autonomous_system_number;
+            info.as_org = asn.autonomous_system_organization.map(str::to_string);
+        }
+        (info != GeoInfo::default()).then_some(info)
+    }
+
+    /// Lookup of an address given as text, e.g. from an audit entry
+    pub fn lookup_str(&self, ip: &str) -> Option<GeoInfo> {
+        self.lookup(ip.parse().ok()?)
+    }
+
+    /// Countries and networks of `ips`
+    pub fn summarize(&self, ips: &[IpAddr]) -> GeoSummary {
+        summarize(ips.iter().map(|ip| self.lookup(*ip)))
+    }
+}
+
+/// Count lookups by country and by network
+fn summarize(lookups: impl Iterator<Item = Option<GeoInfo>>) -> GeoSummary {
+    let mut summary = GeoSummary::default();
+    let mut countries: BTreeMap<String, u64> = BTreeMap::new();
+    let mut networks: BTreeMap<u32, (Option<String>, u64)> = BTreeMap::new();
+    for info in lookups {
+        summary.total += 1;
+        let Some(info) = info else {
+            summary.unknown += 1;
+            continue;
+        };
+        if let Some(country) = info.country {
+            *countries.entry(country).or_default() += 1;
+        }
+        if let Some(asn) = info.asn {
+            let entry = networks.entry(asn).or_insert((info.as_org, 0));
+            entry.1 += 1;
+        }
+    }
+    summary.countries = countries.into_iter()
+        .map(|(key, count)| GeoCount { key, name: None, count })
+        .collect();
+    summary.networks = networks.into_iter()
+        .map(|(asn, (name, count))| GeoCount { key: format!("AS{}", asn), name, count })
+        .collect();
+    summary.countries.sort_by_key(|c| std::cmp::Reverse(c.count));
+    summary.networks.sort_by_key(|c| std::cmp::Reverse(c.count));
+    summary
+}
+
+/// Addresses that can be located; private, loopback and similar ranges can't
+///
+/// Also decides which addresses miner webhooks may reach.
+pub(crate) fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => !(v4.is_private()
+            || v4.is_loopback()
+            || v4.is_link_local()
+            || v4.is_unspecified()
+            || v4.is_broadcast()
+            || v4.is_documentation()
+            || v4.is_multicast()
+            // "This network", 0.0.0.0/8
+            || v4.octets()[0] == 0
+            // Carrier-grade NAT, 100.64.0.0/10
+            || (v4.octets()[0] == 100 && v4.octets()[1] & 0xc0 == 64)),
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_public(IpAddr::V4(v4)),
+            None => !(v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                // Unique local fc00::/7 and link-local fe80::/10
+                || (v6.segments()[0] & 0xfe00) == 0xfc00
+                || (v6.segments()[0] & 0xffc0) == 0xfe80),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_and_private_addresses() {
+        assert!(!is_public("10.1.2.3".parse().unwrap()));
+        assert!(!is_public("100.64.0.1".parse().unwrap()));
+        assert!(!is_public("::ffff:192.168.1.1".parse().unwrap()));
+        assert!(!is_public("fd00::1".parse().unwrap()));
+        assert!(is_public("8.8.8.8".parse().unwrap()));
+        assert!(is_public("2001:4860::8888".parse().unwrap()));
+
+        let disabled = GeoIp::disabled();
+        assert!(!disabled.is_enabled());
+        assert_eq!(disabled.lookup("8.8.8.8".parse().unwrap()), None);
+        assert_eq!(disabled.lookup_str("not an ip"), None);
+
+        let located = |country: &str, asn: u32| Some(GeoInfo {
+            country: Some(country.to_string()),
+            asn: Some(asn),
+            as_org: Some(format!("Org {}", asn)),
+        });
+        let summary = summarize(vec![located("DE", 3320), located("US", 15169), located("DE", 3320), None].into_iter());
+        assert_eq!((summary.total, summary.unknown), (4, 1));
+        assert_eq!((summary.countries[0].key.as_str(), summary.countries[0].count), ("DE", 2));
+        assert_eq!(summary.networks[0].key, "AS3320");
+        assert_eq!(summary.networks[0].name.as_deref(), Some("Org 3320"));
+    }
+}
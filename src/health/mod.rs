@@ -1,26 +1,181 @@
 // Health check module for DMPool
-// Enhanced health monitoring with database/RPC/ZMQ/Bitcoin node integration
+// Enhanced health monitoring with database/RPC/ZMQ/Bitcoin node/backup integration
 
+use crate::backup::BackupManager;
+use crate::connections::ConnectionCounter;
+use crate::store_doctor::{self, StoreDoctor};
+use crate::zmq_monitor::{self, ZmqMonitor};
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use p2poolv2_lib::store::Store;
 use p2poolv2_lib::config::Config;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::sync::Arc;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
 use tokio::time::timeout;
 
+/// Check results kept by default; a day of checks at one per minute
+const DEFAULT_HISTORY_CAPACITY: usize = 1440;
+
+/// ZMQ is reported degraded after this long without a block notification
+const DEFAULT_ZMQ_STALE_AFTER: Duration = Duration::from_secs(3600);
+
+/// Longest wait for the stratum endpoint to answer `mining.subscribe`
+const STRATUM_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// A stratum handshake slower than this is reported degraded
+const STRATUM_PROBE_SLOW: Duration = Duration::from_secs(2);
+/// Lines read while waiting for the subscribe response, skipping notifications
+const STRATUM_PROBE_MAX_LINES: usize = 16;
+
 /// Comprehensive health check response structure
+///
+/// Built with [`HealthStatus::from_components`], which derives the overall
+/// status and degraded reasons, so every binary reports them the same way.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct HealthStatus {
     pub status: String,
     pub database: ComponentStatus,
     pub bitcoin_node: BitcoinNodeStatus,
     pub stratum: StratumStatus,
     pub zmq: ComponentStatus,
+    #[serde(default = "ComponentStatus::healthy")]
+    pub backups: ComponentStatus,
+    /// Whether the public stratum endpoint completes a `mining.subscribe` handshake
+    #[serde(default = "ComponentStatus::healthy")]
+    pub stratum_endpoint: ComponentStatus,
     pub uptime_seconds: u64,
     pub memory_mb: Option<u64>,
+    /// Why the overall status isn't healthy, one entry per affected component
+    #[serde(default)]
+    pub degraded_reasons: Vec<String>,
+}
+
+impl HealthStatus {
+    /// Report for the given component checks
+    pub fn from_components(
+        database: ComponentStatus,
+        bitcoin_node: BitcoinNodeStatus,
+        stratum: StratumStatus,
+        zmq: ComponentStatus,
+        backups: ComponentStatus,
+        stratum_endpoint: ComponentStatus,
+    ) -> Self {
+        let statuses = [
+            database.status.as_str(),
+            bitcoin_node.status.as_str(),
+            stratum.status.as_str(),
+            zmq.status.as_str(),
+            backups.status.as_str(),
+            stratum_endpoint.status.as_str(),
+        ];
+        let overall_status = if statuses.contains(&"unhealthy") {
+            "unhealthy"
+        } else if statuses.iter().all(|s| *s == "healthy") {
+            "healthy"
+        } else {
+            "degraded"
+        };
+        let mut status = Self {
+            status: overall_status.to_string(),
+            database,
+            bitcoin_node,
+            stratum,
+            zmq,
+            backups,
+            stratum_endpoint,
+            uptime_seconds: 0,
+            memory_mb: None,
+            degraded_reasons: Vec::new(),
+        };
+        status.degraded_reasons = degraded_reasons(&status);
+        status
+    }
+
+    pub fn with_uptime(mut self, uptime_seconds: u64) -> Self {
+        self.uptime_seconds = uptime_seconds;
+        self
+    }
+
+    pub fn with_memory_mb(mut self, memory_mb: Option<u64>) -> Self {
+        self.memory_mb = memory_mb;
+        self
+    }
+
+    pub fn is_unhealthy(&self) -> bool {
+        self.status == "unhealthy"
+    }
+}
+
+/// Summary of one health check, kept in the checker's history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthHistoryEntry {
+    pub timestamp: DateTime<Utc>,
+    pub status: String,
+    pub database: String,
+    pub bitcoin_node: String,
+    pub stratum: String,
+    pub zmq: String,
+    #[serde(default)]
+    pub backups: String,
+    #[serde(default)]
+    pub stratum_endpoint: String,
+    pub rpc_latency_ms: Option<u64>,
+    #[serde(default)]
+    pub stratum_probe_ms: Option<u64>,
+    pub degraded_reasons: Vec<String>,
+}
+
+impl HealthHistoryEntry {
+    fn from_status(status: &HealthStatus) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            status: status.status.clone(),
+            database: status.database.status.clone(),
+            bitcoin_node: status.bitcoin_node.status.clone(),
+            stratum: status.stratum.status.clone(),
+            zmq: status.zmq.status.clone(),
+            backups: status.backups.status.clone(),
+            stratum_endpoint: status.stratum_endpoint.status.clone(),
+            rpc_latency_ms: status.bitcoin_node.rpc_latency_ms,
+            stratum_probe_ms: status.stratum_endpoint.latency_ms,
+            degraded_reasons: status.degraded_reasons.clone(),
+        }
+    }
+}
+
+/// Ring buffer of the most recent health check results
+pub struct HealthHistory {
+    entries: Mutex<VecDeque<HealthHistoryEntry>>,
+    capacity: usize,
+}
+
+impl HealthHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(VecDeque::new()),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Add a result, dropping the oldest once full
+    pub fn record(&self, status: &HealthStatus) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(HealthHistoryEntry::from_status(status));
+    }
+
+    /// Recent results, newest first
+    pub fn recent(&self, limit: usize) -> Vec<HealthHistoryEntry> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.iter().rev().take(limit).cloned().collect()
+    }
 }
 
 /// Bitcoin node detailed status
@@ -70,6 +225,9 @@ pub struct ComponentStatus {
     pub status: String,
     pub message: String,
     pub latency_ms: Option<u64>,
+    /// Component specific figures
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
 }
 
 impl ComponentStatus {
@@ -78,6 +236,16 @@ impl ComponentStatus {
             status: "healthy".to_string(),
             message: "OK".to_string(),
             latency_ms: None,
+            details: None,
+        }
+    }
+
+    fn degraded(message: impl Into<String>) -> Self {
+        Self {
+            status: "degraded".to_string(),
+            message: message.into(),
+            latency_ms: None,
+            details: None,
         }
     }
 
@@ -86,6 +254,7 @@ impl ComponentStatus {
             status: "unhealthy".to_string(),
             message: message.into(),
             latency_ms: None,
+            details: None,
         }
     }
 
@@ -98,6 +267,11 @@ impl ComponentStatus {
         self.message = msg.into();
         self
     }
+
+    fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
 }
 
 /// Health checker with Store integration
@@ -109,6 +283,18 @@ pub struct HealthChecker {
     active_connections: std::sync::Arc<std::sync::atomic::AtomicU32>,
     shares_per_second: std::sync::Arc<std::sync::atomic::AtomicU64>,  // Store as fixed-point (3 decimal places)
     current_difficulty: std::sync::Arc<std::sync::atomic::AtomicU64>,  // Store as fixed-point (2 decimal places)
+    history: HealthHistory,
+    /// Long-lived hashblock subscription; without it each check does a handshake
+    zmq_monitor: Option<Arc<ZmqMonitor>>,
+    zmq_stale_after: Duration,
+    /// Source of the connected miner count; `update_connections` is used without one
+    connection_counter: Option<Arc<dyn ConnectionCounter>>,
+    /// Backups whose age and volume space are checked; not checked without one
+    backup_manager: Option<Arc<BackupManager>>,
+    /// `host:port` miners connect to, probed with `mining.subscribe`; not probed without one
+    stratum_probe_address: Option<String>,
+    /// Probes the store for corruption; not probed without one
+    store_doctor: Option<Arc<StoreDoctor>>,
 }
 
 impl HealthChecker {
@@ -121,6 +307,13 @@ impl HealthChecker {
             active_connections: std::sync::Arc::new(std::sync::atomic::AtomicU32::new(0)),
             shares_per_second: std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0)),
             current_difficulty: std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0)),
+            history: HealthHistory::new(DEFAULT_HISTORY_CAPACITY),
+            zmq_monitor: None,
+            zmq_stale_after: DEFAULT_ZMQ_STALE_AFTER,
+            connection_counter: None,
+            backup_manager: None,
+            stratum_probe_address: None,
+            store_doctor: None,
         }
     }
 
@@ -129,6 +322,76 @@ impl HealthChecker {
         self
     }
 
+    /// Judge ZMQ health by a running hashblock subscription
+    pub fn with_zmq_monitor(mut self, monitor: Arc<ZmqMonitor>) -> Self {
+        self.zmq_monitor = Some(monitor);
+        self
+    }
+
+    /// How long without a block notification before ZMQ is reported degraded
+    pub fn with_zmq_stale_after(mut self, stale_after: Duration) -> Self {
+        self.zmq_stale_after = stale_after;
+        self
+    }
+
+    /// Count connected miners with `counter`
+    pub fn with_connection_counter(mut self, counter: Arc<dyn ConnectionCounter>) -> Self {
+        self.connection_counter = Some(counter);
+        self
+    }
+
+    /// Report the age and volume space of `manager`'s backups
+    pub fn with_backup_manager(mut self, manager: Arc<BackupManager>) -> Self {
+        self.backup_manager = Some(manager);
+        self
+    }
+
+    /// Probe the stratum endpoint at `address` (`host:port`) as a miner would
+    pub fn with_stratum_probe(mut self, address: impl Into<String>) -> Self {
+        self.stratum_probe_address = Some(address.into());
+        self
+    }
+
+    /// Probe the store for corruption with every database check
+    pub fn with_store_doctor(mut self, doctor: Arc<StoreDoctor>) -> Self {
+        self.store_doctor = Some(doctor);
+        self
+    }
+
+    /// Number of miners connected to the stratum server
+    pub fn active_connections(&self) -> u32 {
+        let reported = self.active_connections.load(std::sync::atomic::Ordering::Relaxed);
+        match &self.connection_counter {
+            Some(counter) => counter.active_connections().unwrap_or_else(|e| {
+                tracing::warn!("Failed to count stratum connections: {}", e);
+                reported
+            }),
+            None => reported,
+        }
+    }
+
+    /// Remote addresses of connected miners, where the connection counter knows them
+    pub fn peer_addresses(&self) -> Vec<std::net::IpAddr> {
+        match &self.connection_counter {
+            Some(counter) => counter.peer_addresses().unwrap_or_else(|e| {
+                tracing::warn!("Failed to list stratum connections: {}", e);
+                Vec::new()
+            }),
+            None => Vec::new(),
+        }
+    }
+
+    /// Number of check results to keep in the history
+    pub fn with_history_capacity(mut self, capacity: usize) -> Self {
+        self.history = HealthHistory::new(capacity);
+        self
+    }
+
+    /// Recent check results, newest first
+    pub fn history(&self, limit: usize) -> Vec<HealthHistoryEntry> {
+        self.history.recent(limit)
+    }
+
     pub fn update_block_height(&self, height: u64) {
         self.last_block_height.store(height, std::sync::atomic::Ordering::Relaxed);
     }
@@ -161,35 +424,54 @@ impl HealthChecker {
         let bitcoin_status = self.check_bitcoin_node().await;
         let stratum_status = self.check_stratum().await;
         let zmq_status = self.check_zmq().await;
+        let backup_status = self.check_backups();
+        let endpoint_status = self.check_stratum_endpoint().await;
 
-        let overall_status = match (
-            db_status.status.as_str(),
-            bitcoin_status.status.as_str(),
-            stratum_status.status.as_str(),
-            zmq_status.status.as_str(),
-        ) {
-            ("healthy", "healthy", "healthy", "healthy") => "healthy",
-            ("unhealthy", _, _, _) | (_, "unhealthy", _, _) | (_, _, "unhealthy", _) | (_, _, _, "unhealthy") => "unhealthy",
-            _ => "degraded",
-        };
-
-        let memory_mb = self.get_memory_usage();
-
-        HealthStatus {
-            status: overall_status.to_string(),
-            database: db_status,
-            bitcoin_node: bitcoin_status,
-            stratum: stratum_status,
-            zmq: zmq_status,
-            uptime_seconds: self.start_time.elapsed().as_secs(),
-            memory_mb,
-        }
+        let status = HealthStatus::from_components(
+            db_status,
+            bitcoin_status,
+            stratum_status,
+            zmq_status,
+            backup_status,
+            endpoint_status,
+        )
+            .with_uptime(self.start_time.elapsed().as_secs())
+            .with_memory_mb(self.get_memory_usage());
+        self.history.record(&status);
+        status
     }
 
     /// Check database connectivity and status
     async fn check_database(&self) -> ComponentStatus {
         let start = Instant::now();
 
+        if let Some(doctor) = &self.store_doctor {
+            // Other probe errors, e.g. a missing store, are left to the checks below
+            if let Err(e) = doctor.probe().await {
+                let error = format!("{:#}", e);
+                if store_doctor::is_corruption(&error) {
+                    return ComponentStatus::unhealthy(format!("Database corruption: {}", error))
+                        .with_latency(start.elapsed().as_millis() as u64);
+                }
+            }
+            // Corruption found elsewhere in the store stays reported until a diagnosis clears it
+            match doctor.detection() {
+                Ok(Some(detection)) => {
+                    return ComponentStatus::unhealthy(format!(
+                        "Database corruption detected at {}: {}",
+                        detection.at.to_rfc3339(),
+                        detection.error
+                    ))
+                    .with_latency(start.elapsed().as_millis() as u64);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    return ComponentStatus::unhealthy(format!("{:#}", e))
+                        .with_latency(start.elapsed().as_millis() as u64);
+                }
+            }
+        }
+
         if let Some(store) = &self.store {
             // get_chain_tip returns BlockHash directly
             let _tip = store.get_chain_tip();
@@ -349,7 +631,7 @@ impl HealthChecker {
 
     /// Check Stratum service status
     async fn check_stratum(&self) -> StratumStatus {
-        let active_connections = self.active_connections.load(std::sync::atomic::Ordering::Relaxed);
+        let active_connections = self.active_connections();
         let shares_per_second = self.get_shares_per_second();
         let current_difficulty = self.get_difficulty();
 
@@ -387,25 +669,99 @@ impl HealthChecker {
         }
     }
 
-    /// Check ZMQ endpoint connectivity
+    /// Check the ZMQ hashblock subscription
     async fn check_zmq(&self) -> ComponentStatus {
-        let zmq_url = &self.config.stratum.zmqpubhashblock;
-        let parts: Vec<&str> = zmq_url.split("://").collect();
+        let Some(monitor) = &self.zmq_monitor else {
+            // No long-lived subscription: at least complete a SUB handshake
+            let zmq_url = &self.config.stratum.zmqpubhashblock;
+            return match zmq_monitor::probe(zmq_url).await {
+                Ok(latency) => ComponentStatus::healthy()
+                    .with_latency(latency.as_millis() as u64)
+                    .with_message(format!("ZMQ handshake OK on {}", zmq_url)),
+                Err(e) => ComponentStatus::unhealthy(format!("{:#}", e)),
+            };
+        };
 
-        if parts.len() != 2 || parts[0] != "tcp" {
-            return ComponentStatus::unhealthy("Invalid ZMQ URL format (expected tcp://host:port)");
+        let status = monitor.status().await;
+        if !status.connected {
+            let reason = status.last_error.unwrap_or_else(|| "connecting".to_string());
+            return ComponentStatus::unhealthy(format!("Not subscribed to {}: {}", monitor.endpoint(), reason));
         }
+        let Some(since) = status.last_notification_at.or(status.connected_at) else {
+            return ComponentStatus::healthy().with_message("Subscribed to hashblock");
+        };
+        let age = (Utc::now() - since).to_std().unwrap_or_default();
+        if age > self.zmq_stale_after {
+            return ComponentStatus::degraded(format!(
+                "No hashblock notification for {} min", age.as_secs() / 60
+            ));
+        }
+        match status.last_notification_at {
+            Some(_) => ComponentStatus::healthy()
+                .with_message(format!("Last hashblock notification {}s ago", age.as_secs())),
+            None => ComponentStatus::healthy()
+                .with_message("Subscribed to hashblock, waiting for the first block"),
+        }
+    }
 
-        let host_port = parts[1];
-
-        match timeout(Duration::from_secs(2), TcpStream::connect(host_port)).await {
-            Ok(Ok(_)) => ComponentStatus::healthy()
-                .with_message(format!("ZMQ listening on {}", host_port)),
-            Ok(Err(e)) => ComponentStatus::unhealthy(format!("ZMQ connection failed: {}", e)),
-            Err(_) => ComponentStatus::unhealthy("ZMQ connection timeout (2s)"),
+    /// Subscribe to the public stratum endpoint, as a miner connecting from outside would
+    async fn check_stratum_endpoint(&self) -> ComponentStatus {
+        let Some(address) = &self.stratum_probe_address else {
+            return ComponentStatus::healthy().with_message("Stratum endpoint not probed");
+        };
+        match probe_stratum(address, STRATUM_PROBE_TIMEOUT).await {
+            Ok(probe) => {
+                let status = if probe.latency > STRATUM_PROBE_SLOW {
+                    ComponentStatus::degraded(format!(
+                        "{} answered mining.subscribe in {} ms",
+                        address,
+                        probe.latency.as_millis()
+                    ))
+                } else {
+                    ComponentStatus::healthy().with_message(format!("{} accepting miners", address))
+                };
+                status
+                    .with_latency(probe.latency.as_millis() as u64)
+                    .with_details(serde_json::json!({
+                        "address": address,
+                        "connect_ms": probe.connect.as_millis() as u64,
+                        "extranonce1": probe.extranonce1,
+                        "extranonce2_size": probe.extranonce2_size,
+                    }))
+            }
+            Err(e) => ComponentStatus::unhealthy(format!("{} not accepting miners: {:#}", address, e))
+                .with_details(serde_json::json!({ "address": address })),
         }
     }
 
+    /// Check the age of the newest backup, the last failure and backup volume space
+    fn check_backups(&self) -> ComponentStatus {
+        let Some(manager) = &self.backup_manager else {
+            return ComponentStatus::healthy().with_message("Backups not monitored");
+        };
+        let newest = match manager.list_backups() {
+            Ok(backups) => backups.into_iter().next(),
+            Err(e) => return ComponentStatus::unhealthy(format!("{:#}", e)),
+        };
+        let now = Utc::now();
+        let age = newest.as_ref().map(|b| (now - b.timestamp).to_std().unwrap_or_default());
+        let interval = manager.backup_interval(now).and_then(|i| i.to_std().ok());
+        let last_failure = manager.last_failure();
+        let space = manager.check_space()
+            .inspect_err(|e| tracing::warn!("Failed to check backup volume space: {:#}", e))
+            .ok();
+
+        backup_status(age, interval, self.start_time.elapsed(), last_failure.as_deref())
+            .with_details(serde_json::json!({
+                "newest_backup_at": newest.map(|b| b.timestamp),
+                "newest_backup_age_seconds": age.map(|a| a.as_secs()),
+                "interval_seconds": interval.map(|i| i.as_secs()),
+                "last_failure": last_failure,
+                "available_bytes": space.as_ref().map(|s| s.available_bytes),
+                "space_sufficient": space.as_ref().map(|s| s.sufficient()),
+            }))
+    }
+
     /// Get current process memory usage in MB
     fn get_memory_usage(&self) -> Option<u64> {
         #[cfg(unix)]
@@ -434,10 +790,172 @@ impl HealthChecker {
     }
 }
 
+/// `host:port` probed as the public stratum endpoint: `STRATUM_PROBE_ADDRESS`,
+/// or the configured stratum hostname and port, with a wildcard host read as loopback
+pub fn stratum_probe_address(config: &Config) -> String {
+    std::env::var("STRATUM_PROBE_ADDRESS").ok().filter(|a| !a.trim().is_empty()).unwrap_or_else(|| {
+        let host = match config.stratum.hostname.as_str() {
+            "0.0.0.0" | "" => "127.0.0.1",
+            "::" | "[::]" => "[::1]",
+            host => host,
+        };
+        format!("{}:{}", host, config.stratum.port)
+    })
+}
+
+/// Result of a stratum handshake
+#[derive(Debug, Clone)]
+pub struct StratumProbe {
+    /// Time to open the TCP connection
+    pub connect: Duration,
+    /// Time until the `mining.subscribe` response, including the connection
+    pub latency: Duration,
+    pub extranonce1: String,
+    pub extranonce2_size: u64,
+}
+
+/// Connect to a stratum server and complete a `mining.subscribe` handshake
+///
+/// The probe never authorizes, so it submits no shares and isn't counted as a
+/// failed authorization.
+pub async fn probe_stratum(address: &str, limit: Duration) -> Result<StratumProbe> {
+    let start = Instant::now();
+    timeout(limit, async {
+        let stream = TcpStream::connect(address).await
+            .map_err(|e| anyhow::anyhow!("Connection failed: {}", e))?;
+        let connect = start.elapsed();
+        let (reader, mut writer) = stream.into_split();
+        let request = serde_json::json!({
+            "id": 1,
+            "method": "mining.subscribe",
+            "params": [format!("dmpool-health/{}", env!("CARGO_PKG_VERSION"))],
+        });
+        writer.write_all(format!("{}\n", request).as_bytes()).await?;
+
+        let mut lines = BufReader::new(reader).lines();
+        for _ in 0..STRATUM_PROBE_MAX_LINES {
+            let line = lines.next_line().await?
+                .ok_or_else(|| anyhow::anyhow!("Connection closed before the subscribe response"))?;
+            let message: Value = serde_json::from_str(&line)
+                .map_err(|_| anyhow::anyhow!("Not a stratum response: {}", line.chars().take(64).collect::<String>()))?;
+            // Servers may send notifications such as mining.set_difficulty first
+            if message["id"] != 1 {
+                continue;
+            }
+            if !message["error"].is_null() {
+                return Err(anyhow::anyhow!("mining.subscribe refused: {}", message["error"]));
+            }
+            let extranonce1 = message["result"][1].as_str()
+                .ok_or_else(|| anyhow::anyhow!("mining.subscribe response has no extranonce1"))?;
+            return Ok(StratumProbe {
+                connect,
+                latency: start.elapsed(),
+                extranonce1: extranonce1.to_string(),
+                extranonce2_size: message["result"][2].as_u64().unwrap_or(0),
+            });
+        }
+        Err(anyhow::anyhow!("No subscribe response in the first {} messages", STRATUM_PROBE_MAX_LINES))
+    })
+    .await
+    .map_err(|_| anyhow::anyhow!("Stratum handshake timeout ({}s)", limit.as_secs()))?
+}
+
+/// Backups are degraded once the newest successful one is more than twice the
+/// backup interval old, or none was taken in that long since the checker started
+fn backup_status(
+    age: Option<Duration>,
+    interval: Option<Duration>,
+    uptime: Duration,
+    last_failure: Option<&str>,
+) -> ComponentStatus {
+    let failure = last_failure.map(|e| format!("; last backup failed: {}", e)).unwrap_or_default();
+    let overdue = |elapsed: Duration| interval.is_some_and(|i| elapsed > i * 2);
+    match age {
+        Some(age) if overdue(age) => ComponentStatus::degraded(format!(
+            "Newest backup is {} min old, more than twice the {} min backup interval{}",
+            age.as_secs() / 60,
+            interval.unwrap_or_default().as_secs() / 60,
+            failure
+        )),
+        Some(age) => ComponentStatus::healthy()
+            .with_message(format!("Newest backup {} min ago{}", age.as_secs() / 60, failure)),
+        None if overdue(uptime) => ComponentStatus::degraded(format!("No successful backup yet{}", failure)),
+        None => ComponentStatus::healthy().with_message(format!("No backup yet{}", failure)),
+    }
+}
+
+/// One reason per component that isn't healthy
+pub fn degraded_reasons(status: &HealthStatus) -> Vec<String> {
+    [
+        ("database", &status.database.status, &status.database.message),
+        ("bitcoin_node", &status.bitcoin_node.status, &status.bitcoin_node.message),
+        ("stratum", &status.stratum.status, &status.stratum.message),
+        ("zmq", &status.zmq.status, &status.zmq.message),
+        ("backups", &status.backups.status, &status.backups.message),
+        ("stratum_endpoint", &status.stratum_endpoint.status, &status.stratum_endpoint.message),
+    ]
+    .into_iter()
+    .filter(|(_, state, _)| state.as_str() != "healthy")
+    .map(|(component, state, message)| format!("{} {}: {}", component, state, message))
+    .collect()
+}
+
+/// Whether one thing a service needs before taking traffic is in place
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReadinessCheck {
+    pub name: String,
+    pub ready: bool,
+    pub message: String,
+}
+
+impl ReadinessCheck {
+    pub fn pass(name: &str, message: impl Into<String>) -> Self {
+        Self { name: name.to_string(), ready: true, message: message.into() }
+    }
+
+    pub fn fail(name: &str, message: impl Into<String>) -> Self {
+        Self { name: name.to_string(), ready: false, message: message.into() }
+    }
+}
+
+/// Readiness of a service: ready only when every check passes
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub checks: Vec<ReadinessCheck>,
+}
+
+impl Readiness {
+    pub fn new(checks: Vec<ReadinessCheck>) -> Self {
+        Self { ready: checks.iter().all(|c| c.ready), checks }
+    }
+
+    /// Names of the checks that failed
+    pub fn failed(&self) -> Vec<&str> {
+        self.checks.iter().filter(|c| !c.ready).map(|c| c.name.as_str()).collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_readiness() {
+        let ready = Readiness::new(vec![
+            ReadinessCheck::pass("store", "Store open"),
+            ReadinessCheck::pass("auth", "2 users"),
+        ]);
+        assert!(ready.ready && ready.failed().is_empty());
+
+        let not_ready = Readiness::new(vec![
+            ReadinessCheck::pass("auth", "2 users"),
+            ReadinessCheck::fail("store", "Failed to open store"),
+        ]);
+        assert!(!not_ready.ready);
+        assert_eq!(not_ready.failed(), vec!["store"]);
+    }
+
     #[test]
     fn test_component_status_creation() {
         let status = ComponentStatus::healthy();
@@ -460,10 +978,9 @@ mod tests {
 
     #[test]
     fn test_health_status_serialization() {
-        let status = HealthStatus {
-            status: "healthy".to_string(),
-            database: ComponentStatus::healthy(),
-            bitcoin_node: BitcoinNodeStatus {
+        let status = HealthStatus::from_components(
+            ComponentStatus::healthy(),
+            BitcoinNodeStatus {
                 status: "healthy".to_string(),
                 rpc_latency_ms: Some(100),
                 blockchain: BlockchainInfo {
@@ -482,7 +999,7 @@ mod tests {
                 sync_progress: 1.0,
                 message: "OK".to_string(),
             },
-            stratum: StratumStatus {
+            StratumStatus {
                 status: "healthy".to_string(),
                 listening: true,
                 active_connections: 5,
@@ -490,13 +1007,110 @@ mod tests {
                 current_difficulty: 32.0,
                 message: "OK".to_string(),
             },
-            zmq: ComponentStatus::healthy(),
-            uptime_seconds: 3600,
-            memory_mb: Some(512),
-        };
+            ComponentStatus::healthy(),
+            ComponentStatus::healthy(),
+            ComponentStatus::healthy(),
+        )
+        .with_uptime(3600)
+        .with_memory_mb(Some(512));
+        assert_eq!(status.status, "healthy");
+        assert!(status.degraded_reasons.is_empty());
 
         let json = serde_json::to_string(&status).unwrap();
         assert!(json.contains("healthy"));
         assert!(json.contains("800000"));
     }
+
+    #[test]
+    fn test_degraded_reasons_and_history() {
+        let history = HealthHistory::new(2);
+
+        let mut status = HealthStatus::from_components(
+            ComponentStatus::healthy(),
+            BitcoinNodeStatus {
+                status: "syncing".to_string(),
+                rpc_latency_ms: Some(12),
+                blockchain: BlockchainInfo {
+                    blocks: 10,
+                    headers: 20,
+                    initial_block_download: true,
+                    verification_progress: 0.5,
+                    block_time_seconds: None,
+                    best_block_hash: String::new(),
+                },
+                network: NetworkInfo { connections: 1, network_active: true, peer_count: 1 },
+                sync_progress: 0.5,
+                message: "syncing".to_string(),
+            },
+            StratumStatus {
+                status: "healthy".to_string(),
+                listening: true,
+                active_connections: 0,
+                shares_per_second: 0.0,
+                current_difficulty: 0.0,
+                message: "OK".to_string(),
+            },
+            ComponentStatus::unhealthy("ZMQ connection timeout (2s)"),
+            ComponentStatus::healthy(),
+            ComponentStatus::healthy(),
+        );
+        assert!(status.is_unhealthy());
+        assert_eq!(status.degraded_reasons, vec![
+            "bitcoin_node syncing: syncing".to_string(),
+            "zmq unhealthy: ZMQ connection timeout (2s)".to_string(),
+        ]);
+
+        for _ in 0..3 {
+            history.record(&status);
+        }
+        status.status = "healthy".to_string();
+        history.record(&status);
+        let recent = history.recent(10);
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].status, "healthy");
+        assert_eq!(recent[1].bitcoin_node, "syncing");
+        assert_eq!(recent[1].rpc_latency_ms, Some(12));
+    }
+
+    #[tokio::test]
+    async fn test_stratum_probe_handshake() {
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            for reply in [
+                r#"{"id":1,"result":[[["mining.notify","ae6812eb4cd7735a302a8a9dd95cf71f"]],"08000002",4],"error":null}"#,
+                "HTTP/1.1 400 Bad Request",
+            ] {
+                let (stream, _) = listener.accept().await.unwrap();
+                let (reader, mut writer) = stream.into_split();
+                let request = BufReader::new(reader).lines().next_line().await.unwrap().unwrap();
+                assert!(request.contains("mining.subscribe"));
+                writer.write_all(b"{\"id\":null,\"method\":\"mining.set_difficulty\",\"params\":[32]}\n").await.unwrap();
+                writer.write_all(format!("{}\n", reply).as_bytes()).await.unwrap();
+            }
+        });
+
+        let probe = probe_stratum(&address, STRATUM_PROBE_TIMEOUT).await.unwrap();
+        assert_eq!(probe.extranonce1, "08000002");
+        assert_eq!(probe.extranonce2_size, 4);
+        assert!(probe.latency >= probe.connect);
+        assert!(probe_stratum(&address, STRATUM_PROBE_TIMEOUT).await.is_err());
+        assert!(probe_stratum("127.0.0.1:1", STRATUM_PROBE_TIMEOUT).await.is_err());
+    }
+
+    #[test]
+    fn test_backup_status_age() {
+        let hour = Duration::from_secs(3600);
+        let day = Some(hour * 24);
+        assert_eq!(backup_status(Some(hour), day, hour, None).status, "healthy");
+        let overdue = backup_status(Some(hour * 49), day, hour, Some("disk full"));
+        assert_eq!(overdue.status, "degraded");
+        assert!(overdue.message.ends_with("last backup failed: disk full"));
+        // Without any backup, only once the checker has run past twice the interval
+        assert_eq!(backup_status(None, day, hour, None).status, "healthy");
+        assert_eq!(backup_status(None, day, hour * 49, None).status, "degraded");
+        assert_eq!(backup_status(Some(hour * 1000), None, hour, None).status, "healthy");
+    }
 }
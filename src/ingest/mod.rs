@@ -0,0 +1,161 @@
+// Stratum Event Ingestion for DMPool
+// Structured events the stratum server pushes over HTTP or a unix socket
+
+use crate::share_stats::ShareOutcome;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use utoipa::ToSchema;
+
+/// Authorization failures kept in memory at most
+const MAX_TRACKED_AUTH_FAILURES: usize = 10_000;
+
+/// Why the stratum server rejected a share
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectReason {
+    Stale,
+    Duplicate,
+    LowDifficulty,
+    Invalid,
+}
+
+impl RejectReason {
+    pub fn outcome(self) -> ShareOutcome {
+        match self {
+            RejectReason::Stale => ShareOutcome::Stale,
+            RejectReason::Duplicate => ShareOutcome::Duplicate,
+            RejectReason::LowDifficulty | RejectReason::Invalid => ShareOutcome::Invalid,
+        }
+    }
+}
+
+/// An event reported by the stratum server
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StratumEvent {
+    /// A worker authorized on a stratum connection
+    WorkerConnected { address: String, worker: String },
+    /// A worker's stratum connection closed
+    WorkerDisconnected { address: String, worker: String },
+    ShareAccepted { address: String, worker: String, difficulty: u64 },
+    ShareRejected { address: String, worker: String, reason: RejectReason },
+    /// `mining.authorize` was refused
+    AuthorizationFailed {
+        username: String,
+        ip: String,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+}
+
+/// Parse one line of the socket protocol; blank lines carry no event
+pub fn parse_line(line: &str) -> Result<Option<StratumEvent>> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(line)?))
+}
+
+/// A refused `mining.authorize`
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AuthFailure {
+    pub username: String,
+    pub ip: String,
+    /// Unix time the failure was reported at
+    pub timestamp: u64,
+}
+
+/// Ingestion state shared by the HTTP endpoint and the socket listener
+#[derive(Default)]
+pub struct StratumIngest {
+    /// Bearer token the HTTP endpoint requires; the endpoint is off without one
+    token: Option<String>,
+    auth_failures: Mutex<VecDeque<AuthFailure>>,
+    presence_reported: AtomicBool,
+}
+
+impl StratumIngest {
+    pub fn new(token: Option<String>) -> Self {
+        Self {
+            token: token.filter(|t| !t.is_empty()),
+            ..Self::default()
+        }
+    }
+
+    pub fn http_enabled(&self) -> bool {
+        self.token.is_some()
+    }
+
+    /// Whether `given` is the configured token, compared in constant time
+    pub fn authorize(&self, given: &str) -> bool {
+        let Some(token) = &self.token else {
+            return false;
+        };
+        let (expected, given) = (Sha256::digest(token.as_bytes()), Sha256::digest(given.as_bytes()));
+        expected.iter().zip(given.iter()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+    }
+
+    pub fn record_auth_failure(&self, failure: AuthFailure) {
+        let mut failures = self.auth_failures.lock().unwrap_or_else(|e| e.into_inner());
+        if failures.len() >= MAX_TRACKED_AUTH_FAILURES {
+            failures.pop_front();
+        }
+        failures.push_back(failure);
+    }
+
+    /// Failures reported at or after `start`
+    pub fn auth_failures_since(&self, start: u64) -> Vec<AuthFailure> {
+        let failures = self.auth_failures.lock().unwrap_or_else(|e| e.into_inner());
+        failures.iter().filter(|f| f.timestamp >= start).cloned().collect()
+    }
+
+    /// Note that the stratum server reports connects and disconnects itself
+    pub fn mark_presence_reported(&self) {
+        self.presence_reported.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether worker presence should come from reported events rather than
+    /// be derived from stored shares
+    pub fn presence_reported(&self) -> bool {
+        self.presence_reported.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_and_auth_failures() {
+        let event = parse_line(r#"{"type":"share_rejected","address":"bc1qminer","worker":"rig1","reason":"low_difficulty"}"#)
+            .unwrap()
+            .unwrap();
+        assert_eq!(event, StratumEvent::ShareRejected {
+            address: "bc1qminer".to_string(),
+            worker: "rig1".to_string(),
+            reason: RejectReason::LowDifficulty,
+        });
+        assert_eq!(RejectReason::LowDifficulty.outcome(), ShareOutcome::Invalid);
+        assert!(parse_line("  ").unwrap().is_none());
+        assert!(parse_line(r#"{"type":"unknown"}"#).is_err());
+
+        let ingest = StratumIngest::new(Some("secret".to_string()));
+        assert!(ingest.authorize("secret"));
+        assert!(!ingest.authorize("secret2"));
+        assert!(!StratumIngest::new(Some(String::new())).http_enabled());
+
+        for timestamp in [100, 200, 300] {
+            ingest.record_auth_failure(AuthFailure {
+                username: "bc1qminer.rig1".to_string(),
+                ip: "192.0.2.1".to_string(),
+                timestamp,
+            });
+        }
+        assert_eq!(ingest.auth_failures_since(200).len(), 2);
+    }
+}
@@ -0,0 +1,222 @@
+// Pool Instances for DMPool
+// Several pools managed from one admin server, each with its own config, store, health checks and backups
+
+use crate::backup::{BackupConfig, BackupKey, BackupManager, StorePathCheckpoint};
+use crate::blocks::BlockTracker;
+use crate::connections::SocketTableCounter;
+use crate::cron::CronExpr;
+use crate::health::HealthChecker;
+use crate::storage::StoreMaintenance;
+use crate::store_doctor::StoreDoctor;
+use crate::zmq_monitor::ZmqMonitor;
+use anyhow::Result;
+use p2poolv2_lib::config::Config;
+use p2poolv2_lib::shares::chain::chain_store::ChainStore;
+use p2poolv2_lib::shares::share_block::ShareBlock;
+use p2poolv2_lib::store::Store;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+/// Names that can't be used for an instance because they are routes of their own
+const RESERVED_NAMES: &[&str] = &["overview"];
+
+/// A pool to manage, as `name=config_path`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InstanceSpec {
+    pub name: String,
+    pub config_path: String,
+}
+
+impl FromStr for InstanceSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, config_path) = s.split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Invalid pool instance {} (expected name=config_path)", s))?;
+        let (name, config_path) = (name.trim(), config_path.trim());
+        validate_name(name)?;
+        if config_path.is_empty() {
+            return Err(anyhow::anyhow!("Pool instance {} has no config path", name));
+        }
+        Ok(Self { name: name.to_string(), config_path: config_path.to_string() })
+    }
+}
+
+/// Instance names appear in URL paths, so they are kept to a safe alphabet
+pub fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 32
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if !valid {
+        return Err(anyhow::anyhow!(
+            "Invalid pool instance name {:?}: use up to 32 lowercase letters, digits, '-' or '_'",
+            name
+        ));
+    }
+    if RESERVED_NAMES.contains(&name) {
+        return Err(anyhow::anyhow!("Pool instance name {:?} is reserved", name));
+    }
+    Ok(())
+}
+
+/// Parse `name=config_path` pairs separated by ';'
+pub fn parse_instances(list: &str) -> Result<Vec<InstanceSpec>> {
+    list.split(';')
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| s.parse())
+        .collect()
+}
+
+/// One managed pool and the resources that belong to it
+pub struct PoolInstance {
+    pub name: String,
+    pub config_path: String,
+    pub config: Arc<RwLock<Config>>,
+    pub store: Arc<Store>,
+    pub chain_store: Arc<ChainStore>,
+    pub health_checker: Arc<HealthChecker>,
+    pub backup_manager: Arc<BackupManager>,
+    pub storage: Arc<StoreMaintenance>,
+    pub store_doctor: Arc<StoreDoctor>,
+    pub block_tracker: Arc<BlockTracker>,
+    /// Not yet running; the caller spawns it
+    pub zmq_monitor: Arc<ZmqMonitor>,
+}
+
+impl PoolInstance {
+    /// Open a pool from its config file
+    ///
+    /// The store is opened read-only and its stratum sockets are counted, as
+    /// the pool runs in another process. Backups follow `backups` but go to a
+    /// directory of the instance's own, the store is compacted on
+    /// `compaction`, and found blocks and repaired store copies are kept under
+    /// `data_dir/instances/<name>`.
+    pub async fn open(
+        spec: &InstanceSpec,
+        data_dir: &Path,
+        backups: &BackupConfig,
+        backup_key: Option<BackupKey>,
+        zmq_stale_after: Duration,
+        compaction: Option<&CronExpr>,
+    ) -> Result<Self> {
+        let config = Config::load(&spec.config_path)
+            .map_err(|e| anyhow::anyhow!("Failed to load config of pool instance {}: {}", spec.name, e))?;
+        let store = Arc::new(Store::new(config.store.path.clone(), true)
+            .map_err(|e| anyhow::anyhow!("Failed to open store of pool instance {}: {}", spec.name, e))?);
+        let genesis = ShareBlock::build_genesis_for_network(config.stratum.network);
+        let chain_store = Arc::new(ChainStore::new(store.clone(), genesis, config.stratum.network));
+
+        let zmq_monitor = Arc::new(ZmqMonitor::new(config.stratum.zmqpubhashblock.clone()));
+
+        let mut backup_manager = BackupManager::new(BackupConfig {
+            db_path: config.store.path.clone().into(),
+            backup_dir: backups.backup_dir.join(&spec.name),
+            ..backups.clone()
+        })
+        .with_checkpoint_source(Arc::new(StorePathCheckpoint::new(&config.store.path)));
+        if let Some(key) = backup_key {
+            backup_manager = backup_manager.with_encryption_key(key);
+        }
+        let backup_manager = Arc::new(backup_manager);
+
+        let instance_dir = data_dir.join("instances").join(&spec.name);
+        let store_doctor = Arc::new(StoreDoctor::new(&config.store.path, instance_dir.join("store_doctor")));
+        let health_checker = HealthChecker::new(config.clone())
+            .with_store(store.clone())
+            .with_zmq_monitor(zmq_monitor.clone())
+            .with_zmq_stale_after(zmq_stale_after)
+            .with_connection_counter(Arc::new(SocketTableCounter::new(config.stratum.port)))
+            .with_backup_manager(backup_manager.clone())
+            .with_store_doctor(store_doctor.clone());
+
+        let mut storage = StoreMaintenance::new(&config.store.path);
+        if let Some(schedule) = compaction {
+            storage = storage.with_schedule(schedule.clone());
+        }
+
+        let pool_signature = config.stratum.pool_signature.clone().unwrap_or_default();
+        let block_tracker = BlockTracker::new(
+            instance_dir.join("blocks.json"),
+            &pool_signature,
+        );
+        block_tracker.load().await?;
+
+        Ok(Self {
+            name: spec.name.clone(),
+            config_path: spec.config_path.clone(),
+            config: Arc::new(RwLock::new(config)),
+            store,
+            chain_store,
+            health_checker: Arc::new(health_checker),
+            backup_manager,
+            storage: Arc::new(storage),
+            store_doctor,
+            block_tracker: Arc::new(block_tracker),
+            zmq_monitor,
+        })
+    }
+}
+
+/// Managed pools by name, in the order they were added
+///
+/// The first is the primary instance, which the unscoped API serves.
+pub struct InstanceRegistry {
+    instances: Vec<Arc<PoolInstance>>,
+}
+
+impl InstanceRegistry {
+    pub fn new(primary: PoolInstance) -> Self {
+        Self { instances: vec![Arc::new(primary)] }
+    }
+
+    /// Add an instance; names must be unique
+    pub fn add(&mut self, instance: PoolInstance) -> Result<()> {
+        if self.get(&instance.name).is_some() {
+            return Err(anyhow::anyhow!("Duplicate pool instance name: {}", instance.name));
+        }
+        self.instances.push(Arc::new(instance));
+        Ok(())
+    }
+
+    pub fn primary(&self) -> &Arc<PoolInstance> {
+        &self.instances[0]
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<PoolInstance>> {
+        self.instances.iter().find(|i| i.name == name)
+    }
+
+    /// All instances, primary first
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<PoolInstance>> {
+        self.instances.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_instances() {
+        let specs = parse_instances("main=/etc/dmpool/config.toml; testnet = /etc/dmpool/testnet.toml;").unwrap();
+        assert_eq!(specs.len(), 2);
+        assert_eq!(specs[1], InstanceSpec { name: "testnet".into(), config_path: "/etc/dmpool/testnet.toml".into() });
+
+        assert!("main".parse::<InstanceSpec>().is_err());
+        assert!("Main=/etc/dmpool/config.toml".parse::<InstanceSpec>().is_err());
+        assert!("a/b=/etc/dmpool/config.toml".parse::<InstanceSpec>().is_err());
+        assert!("overview=/etc/dmpool/config.toml".parse::<InstanceSpec>().is_err());
+        assert!("main=".parse::<InstanceSpec>().is_err());
+    }
+}
@@ -0,0 +1,624 @@
+// Background Jobs for DMPool
+// Long-running admin operations on a bounded worker pool, with progress,
+// cancellation and persistent records
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tracing::{info, warn};
+
+/// Finished jobs kept at most, however recent
+const MAX_FINISHED_JOBS: usize = 500;
+/// File the job records are kept in, inside the registry's directory
+const JOBS_FILE: &str = "jobs.json";
+/// How often a shutdown checks whether running jobs have finished
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Progress of a running job, updated by the job and read by status requests
+#[derive(Default)]
+pub struct Progress {
+    phase: Mutex<String>,
+    bytes_done: AtomicU64,
+    bytes_total: AtomicU64,
+    files_done: AtomicU64,
+    files_total: AtomicU64,
+    steps_done: AtomicU64,
+    steps_total: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+impl Progress {
+    pub fn set_phase(&self, phase: &str) {
+        *self.phase.lock().unwrap_or_else(|e| e.into_inner()) = phase.to_string();
+    }
+
+    pub fn set_totals(&self, files: u64, bytes: u64) {
+        self.files_total.store(files, Ordering::Relaxed);
+        self.bytes_total.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Count a finished file of `bytes`
+    pub fn add_file(&self, bytes: u64) {
+        self.files_done.fetch_add(1, Ordering::Relaxed);
+        self.bytes_done.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Count bytes written that aren't files of their own, like export output
+    pub fn add_bytes(&self, bytes: u64) {
+        self.bytes_done.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Total of a job that works through a known number of steps, like blocks or chunks
+    pub fn set_steps(&self, total: u64) {
+        self.steps_total.store(total, Ordering::Relaxed);
+    }
+
+    pub fn step(&self) {
+        self.steps_done.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Ask the job to stop at its next safe point
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Fail if the job was cancelled; called where stopping leaves nothing half done
+    pub fn check_cancelled(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(anyhow::anyhow!("Cancelled"));
+        }
+        Ok(())
+    }
+
+    /// Current progress of a job running for `elapsed_secs`
+    ///
+    /// The ETA extrapolates the rate so far, by bytes when their total is
+    /// known, then by files, then by steps.
+    pub fn report(&self, elapsed_secs: f64) -> ProgressReport {
+        let bytes_done = self.bytes_done.load(Ordering::Relaxed);
+        let bytes_total = self.bytes_total.load(Ordering::Relaxed);
+        let files_done = self.files_done.load(Ordering::Relaxed);
+        let files_total = self.files_total.load(Ordering::Relaxed);
+        let steps_done = self.steps_done.load(Ordering::Relaxed);
+        let steps_total = self.steps_total.load(Ordering::Relaxed);
+        let (done, total) = if bytes_total > 0 {
+            (bytes_done, bytes_total)
+        } else if files_total > 0 {
+            (files_done, files_total)
+        } else {
+            (steps_done, steps_total)
+        };
+        let fraction = (total > 0).then(|| (done as f64 / total as f64).min(1.0));
+        let eta_secs = fraction
+            .filter(|f| *f > 0.0)
+            .map(|f| (elapsed_secs * (1.0 - f) / f).round() as u64);
+        ProgressReport {
+            phase: self.phase.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+            bytes_done,
+            bytes_total,
+            files_done,
+            files_total,
+            steps_done,
+            steps_total,
+            percent: fraction.map(|f| (f * 1000.0).round() / 10.0),
+            eta_secs,
+        }
+    }
+}
+
+/// Snapshot of a job's progress
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProgressReport {
+    pub phase: String,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    pub files_done: u64,
+    pub files_total: u64,
+    #[serde(default)]
+    pub steps_done: u64,
+    #[serde(default)]
+    pub steps_total: u64,
+    pub percent: Option<f64>,
+    /// Estimated seconds until done
+    pub eta_secs: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    Backup,
+    Restore,
+    /// Share or worker export written to a file for download
+    Export,
+    Compaction,
+    /// PPLNS replay of credited blocks
+    Replay,
+    /// Corruption check and repair attempt of the store
+    StoreDoctor,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    /// Waiting for a free worker
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobState {
+    pub fn is_finished(self) -> bool {
+        !matches!(self, JobState::Queued | JobState::Running)
+    }
+}
+
+/// A job and how it went
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub kind: JobKind,
+    pub description: String,
+    pub created_by: String,
+    pub state: JobState,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub progress: ProgressReport,
+    /// What the job returned when it completed
+    pub result: Option<serde_json::Value>,
+    pub error: Option<String>,
+    /// File the job produced for download
+    #[serde(default)]
+    pub artifact: Option<String>,
+}
+
+impl Job {
+    /// Whether `username` may see the job: the user who started it, or any admin
+    pub fn visible_to(&self, username: &str, is_admin: bool) -> bool {
+        is_admin || self.created_by == username
+    }
+}
+
+/// What a job's task gets to work with
+#[derive(Clone)]
+pub struct JobContext {
+    pub id: String,
+    pub progress: Arc<Progress>,
+    artifact_dir: PathBuf,
+    artifact: Arc<Mutex<Option<String>>>,
+}
+
+impl JobContext {
+    /// Path of the file the job produces for download, named after the job
+    ///
+    /// The file is removed if the job doesn't complete, and with the job's
+    /// record once that is cleaned up.
+    pub fn artifact_path(&self, extension: &str) -> PathBuf {
+        let name = format!("{}.{}", self.id, extension);
+        *self.artifact.lock().unwrap_or_else(|e| e.into_inner()) = Some(name.clone());
+        self.artifact_dir.join(name)
+    }
+}
+
+/// Worker pool size and retention of finished jobs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JobSettings {
+    /// Jobs running at once; others wait in the queue
+    pub workers: usize,
+    /// How long finished jobs and their files are kept
+    pub retention_hours: u64,
+}
+
+impl Default for JobSettings {
+    fn default() -> Self {
+        Self { workers: 2, retention_hours: 24 }
+    }
+}
+
+impl JobSettings {
+    /// Settings from `JOB_WORKERS` and `JOB_RETENTION_HOURS`
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            workers: std::env::var("JOB_WORKERS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|workers| *workers > 0)
+                .unwrap_or(defaults.workers),
+            retention_hours: std::env::var("JOB_RETENTION_HOURS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.retention_hours),
+        }
+    }
+}
+
+struct Entry {
+    job: Job,
+    context: JobContext,
+}
+
+impl Entry {
+    fn snapshot(&self) -> Job {
+        let mut job = self.job.clone();
+        if let (JobState::Running, Some(started_at)) = (job.state, job.started_at) {
+            let elapsed = (Utc::now() - started_at).num_milliseconds().max(0) as f64 / 1000.0;
+            job.progress = self.context.progress.report(elapsed);
+        }
+        job
+    }
+}
+
+/// Queued, running and recently finished jobs, kept in a directory with the
+/// files they produce
+pub struct JobRegistry {
+    dir: PathBuf,
+    settings: JobSettings,
+    workers: Arc<Semaphore>,
+    jobs: Mutex<HashMap<String, Entry>>,
+    /// Serializes writes of the job records
+    save_lock: tokio::sync::Mutex<()>,
+}
+
+impl JobRegistry {
+    pub fn new(dir: PathBuf, settings: JobSettings) -> Self {
+        Self {
+            dir,
+            settings,
+            workers: Arc::new(Semaphore::new(settings.workers.max(1))),
+            jobs: Mutex::new(HashMap::new()),
+            save_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn settings(&self) -> JobSettings {
+        self.settings
+    }
+
+    /// Load job records from disk, if present
+    ///
+    /// Jobs that were queued or running when the process stopped are marked
+    /// failed and their partial files removed.
+    pub async fn load(&self) -> Result<usize> {
+        let path = self.dir.join(JOBS_FILE);
+        if !path.exists() {
+            return Ok(0);
+        }
+        let content = tokio::fs::read_to_string(&path).await.context("Failed to read job records")?;
+        let records: Vec<Job> = serde_json::from_str(&content).context("Failed to parse job records")?;
+        let count = records.len();
+        let mut interrupted = Vec::new();
+        {
+            let mut jobs = self.lock();
+            for mut job in records {
+                if !job.state.is_finished() {
+                    job.state = JobState::Failed;
+                    job.error = Some("Interrupted by an admin server restart".to_string());
+                    job.finished_at = Some(Utc::now());
+                    interrupted.extend(job.artifact.take());
+                }
+                let context = self.context(&job.id);
+                jobs.insert(job.id.clone(), Entry { job, context });
+            }
+        }
+        for artifact in interrupted {
+            let _ = tokio::fs::remove_file(self.dir.join(artifact)).await;
+        }
+        Ok(count)
+    }
+
+    async fn save(&self) {
+        let _guard = self.save_lock.lock().await;
+        let records = self.list();
+        let result = async {
+            tokio::fs::create_dir_all(&self.dir).await?;
+            let path = self.dir.join(JOBS_FILE);
+            let tmp = path.with_extension("json.tmp");
+            tokio::fs::write(&tmp, serde_json::to_string_pretty(&records)?).await
+                .context("Failed to write job records")?;
+            tokio::fs::rename(&tmp, &path).await.context("Failed to replace job records")?;
+            anyhow::Ok(())
+        }.await;
+        if let Err(e) = result {
+            warn!("Failed to save job records: {:#}", e);
+        }
+    }
+
+    fn context(&self, id: &str) -> JobContext {
+        JobContext {
+            id: id.to_string(),
+            progress: Arc::new(Progress::default()),
+            artifact_dir: self.dir.clone(),
+            artifact: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Queue `task` to run in the background as a new job
+    ///
+    /// The task starts once one of the workers is free, and gets the job's
+    /// progress to report into and to check for cancellation. A task that
+    /// fails after being cancelled ends up `Cancelled` rather than `Failed`.
+    pub fn spawn<F, Fut>(self: &Arc<Self>, kind: JobKind, description: String, created_by: &str, task: F) -> Job
+    where
+        F: FnOnce(JobContext) -> Fut,
+        Fut: Future<Output = Result<serde_json::Value>> + Send + 'static,
+    {
+        let job = Job {
+            id: uuid::Uuid::new_v4().to_string(),
+            kind,
+            description,
+            created_by: created_by.to_string(),
+            state: JobState::Queued,
+            created_at: Utc::now(),
+            started_at: None,
+            finished_at: None,
+            progress: ProgressReport::default(),
+            result: None,
+            error: None,
+            artifact: None,
+        };
+        let context = self.context(&job.id);
+        info!("Queued {:?} job {}: {}", job.kind, job.id, job.description);
+        self.lock().insert(job.id.clone(), Entry { job: job.clone(), context: context.clone() });
+
+        let future = task(context.clone());
+        let registry = self.clone();
+        tokio::spawn(async move {
+            registry.save().await;
+            let Ok(_permit) = registry.workers.clone().acquire_owned().await else {
+                // The registry is shutting down
+                context.progress.cancel();
+                let stopped = anyhow::anyhow!("Admin server shut down before the job started");
+                registry.finish(&context, Err(stopped)).await;
+                return;
+            };
+            // Cancelled while still queued
+            if let Err(e) = context.progress.check_cancelled() {
+                registry.finish(&context, Err(e)).await;
+                return;
+            }
+            registry.update(&context.id, |job| {
+                job.state = JobState::Running;
+                job.started_at = Some(Utc::now());
+            });
+            registry.save().await;
+            let result = future.await;
+            registry.finish(&context, result).await;
+        });
+        job
+    }
+
+    fn update(&self, id: &str, apply: impl FnOnce(&mut Job)) {
+        if let Some(entry) = self.lock().get_mut(id) {
+            apply(&mut entry.job);
+        }
+    }
+
+    async fn finish(&self, context: &JobContext, result: Result<serde_json::Value>) {
+        let artifact = context.artifact.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let mut discard = None;
+        {
+            let mut jobs = self.lock();
+            let Some(entry) = jobs.get_mut(&context.id) else {
+                return;
+            };
+            let mut job = entry.snapshot();
+            job.finished_at = Some(Utc::now());
+            match result {
+                Ok(value) => {
+                    job.state = JobState::Completed;
+                    job.result = Some(value);
+                    job.artifact = artifact;
+                }
+                Err(e) => {
+                    if context.progress.is_cancelled() {
+                        job.state = JobState::Cancelled;
+                    } else {
+                        warn!("{:?} job {} failed: {:#}", job.kind, job.id, e);
+                        job.state = JobState::Failed;
+                    }
+                    job.error = Some(format!("{:#}", e));
+                    discard = artifact;
+                }
+            }
+            info!("{:?} job {} finished: {:?}", job.kind, job.id, job.state);
+            entry.job = job;
+        }
+        if let Some(name) = discard {
+            let _ = tokio::fs::remove_file(self.dir.join(name)).await;
+        }
+        self.save().await;
+    }
+
+    pub fn get(&self, id: &str) -> Option<Job> {
+        self.lock().get(id).map(Entry::snapshot)
+    }
+
+    /// All jobs, newest first
+    pub fn list(&self) -> Vec<Job> {
+        let mut jobs: Vec<Job> = self.lock().values().map(Entry::snapshot).collect();
+        jobs.sort_by_key(|job| std::cmp::Reverse(job.created_at));
+        jobs
+    }
+
+    /// Jobs queued or running
+    pub fn active(&self) -> usize {
+        self.lock().values().filter(|e| !e.job.state.is_finished()).count()
+    }
+
+    /// File a completed job produced
+    pub fn artifact(&self, id: &str) -> Option<PathBuf> {
+        let jobs = self.lock();
+        let job = &jobs.get(id)?.job;
+        (job.state == JobState::Completed).then(|| job.artifact.as_ref().map(|name| self.dir.join(name)))?
+    }
+
+    /// Ask a queued or running job to stop; it is `Cancelled` once it has
+    pub fn cancel(&self, id: &str) -> Result<Job> {
+        let jobs = self.lock();
+        let entry = jobs.get(id).ok_or_else(|| anyhow::anyhow!("Job not found: {}", id))?;
+        if entry.job.state.is_finished() {
+            return Err(anyhow::anyhow!("Job {} already finished", id));
+        }
+        entry.context.progress.cancel();
+        info!("Cancelling {:?} job {}", entry.job.kind, id);
+        Ok(entry.snapshot())
+    }
+
+    /// Stop starting jobs and wait up to `timeout` for running ones to finish
+    ///
+    /// Queued jobs, and any spawned from now on, are cancelled. Returns the
+    /// number of jobs still running after the timeout; their records are
+    /// marked failed when next loaded.
+    pub async fn shutdown(&self, timeout: Duration) -> usize {
+        self.workers.close();
+        let idle = async {
+            while self.active() > 0 {
+                tokio::time::sleep(SHUTDOWN_POLL_INTERVAL).await;
+            }
+        };
+        if tokio::time::timeout(timeout, idle).await.is_err() {
+            let running = self.active();
+            warn!("{} job(s) still running after {}s", running, timeout.as_secs());
+            return running;
+        }
+        0
+    }
+
+    /// Forget finished jobs past the retention period, or beyond the most
+    /// recent `MAX_FINISHED_JOBS`, and remove their files
+    pub async fn cleanup(&self, now: DateTime<Utc>) -> usize {
+        let cutoff = now - chrono::Duration::hours(self.settings.retention_hours as i64);
+        let removed: Vec<Job> = {
+            let mut jobs = self.lock();
+            let mut finished: Vec<(DateTime<Utc>, String)> = jobs.values()
+                .filter_map(|e| Some((e.job.finished_at?, e.job.id.clone())))
+                .collect();
+            finished.sort_by_key(|(finished_at, _)| std::cmp::Reverse(*finished_at));
+            finished.into_iter()
+                .enumerate()
+                .filter(|(i, (finished_at, _))| *i >= MAX_FINISHED_JOBS || *finished_at < cutoff)
+                .filter_map(|(_, (_, id))| jobs.remove(&id))
+                .map(|entry| entry.job)
+                .collect()
+        };
+        for artifact in removed.iter().filter_map(|job| job.artifact.as_ref()) {
+            let _ = tokio::fs::remove_file(self.dir.join(artifact)).await;
+        }
+        if !removed.is_empty() {
+            info!("Cleaned up {} finished job(s)", removed.len());
+            self.save().await;
+        }
+        removed.len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Entry>> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_report() {
+        let progress = Progress::default();
+        progress.set_phase("archiving");
+        progress.set_totals(4, 1000);
+        assert_eq!(progress.report(1.0).eta_secs, None);
+
+        progress.add_file(250);
+        let report = progress.report(10.0);
+        assert_eq!(report.percent, Some(25.0));
+        assert_eq!(report.eta_secs, Some(30));
+        assert_eq!(report.files_done, 1);
+        assert_eq!(report.phase, "archiving");
+
+        let steps = Progress::default();
+        steps.set_steps(8);
+        steps.step();
+        steps.step();
+        assert_eq!(steps.report(1.0).percent, Some(25.0));
+    }
+
+    async fn wait_until_idle(registry: &JobRegistry) {
+        for _ in 0..200 {
+            if registry.active() == 0 {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("jobs still running");
+    }
+
+    #[tokio::test]
+    async fn test_queue_cancel_persist_and_cleanup() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = Arc::new(JobRegistry::new(dir.path().to_path_buf(), JobSettings { workers: 1, retention_hours: 1 }));
+
+        // The single worker is held until the blocking job is cancelled
+        let blocking = registry.spawn(JobKind::Restore, "restore b1".to_string(), "admin", |job| async move {
+            while !job.progress.is_cancelled() {
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+            job.progress.check_cancelled()?;
+            Ok(serde_json::Value::Null)
+        });
+        let export = registry.spawn(JobKind::Export, "shares".to_string(), "admin", |job| async move {
+            tokio::fs::write(job.artifact_path("csv"), "n_time\n").await?;
+            Ok(serde_json::json!({ "rows": 0 }))
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(registry.get(&export.id).unwrap().state, JobState::Queued);
+        // Only the user who started a job and admins see it
+        let queued = registry.get(&export.id).unwrap();
+        assert!(queued.visible_to("admin", false));
+        assert!(!queued.visible_to("operator", false));
+        assert!(queued.visible_to("root", true));
+
+        registry.cancel(&blocking.id).unwrap();
+        wait_until_idle(&registry).await;
+        assert_eq!(registry.get(&blocking.id).unwrap().state, JobState::Cancelled);
+        let export = registry.get(&export.id).unwrap();
+        assert_eq!(export.state, JobState::Completed);
+        let artifact = registry.artifact(&export.id).unwrap();
+        assert_eq!(std::fs::read_to_string(&artifact).unwrap(), "n_time\n");
+        assert!(registry.cancel(&export.id).is_err());
+
+        let reloaded = JobRegistry::new(dir.path().to_path_buf(), JobSettings::default());
+        assert_eq!(reloaded.load().await.unwrap(), 2);
+        assert_eq!(reloaded.get(&export.id).unwrap().result, Some(serde_json::json!({ "rows": 0 })));
+
+        assert_eq!(registry.cleanup(Utc::now()).await, 0);
+        assert_eq!(registry.cleanup(Utc::now() + chrono::Duration::hours(2)).await, 2);
+        assert!(registry.list().is_empty());
+        assert!(!artifact.exists());
+
+        // Shutting down lets the running job finish and cancels the queued one
+        let running = registry.spawn(JobKind::Backup, "backup".to_string(), "admin", |_| async move {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            Ok(serde_json::Value::Null)
+        });
+        let queued = registry.spawn(JobKind::Compaction, "compaction".to_string(), "admin", |_| async move {
+            Ok(serde_json::Value::Null)
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert_eq!(registry.shutdown(Duration::from_secs(5)).await, 0);
+        assert_eq!(registry.get(&running.id).unwrap().state, JobState::Completed);
+        assert_eq!(registry.get(&queued.id).unwrap().state, JobState::Cancelled);
+    }
+}
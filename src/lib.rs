@@ -4,25 +4,109 @@
 // a derivative of Hydrapool by 256 Foundation.
 
 pub mod alert;
+pub mod anomaly;
+#[cfg(feature = "analytics")]
+pub mod analytics;
+pub mod assets;
 pub mod auth;
 pub mod audit;
 pub mod backup;
+pub mod bans;
+pub mod blocks;
+pub mod bootstrap;
 pub mod config;
 pub mod config_mgt;
+pub mod config_watcher;
+pub mod connections;
+pub mod cors;
+pub mod cron;
+pub mod estimate;
+pub mod export;
+pub mod farms;
+pub mod fees;
+pub mod geoip;
 pub mod confirmation;
 pub mod health;
+pub mod ingest;
+pub mod instances;
+pub mod jobs;
+pub mod live_feed;
+pub mod logging;
+pub mod maintenance;
+pub mod mempool;
+pub mod miner_access;
+pub mod miner_settings;
+pub mod outbox;
+pub mod payout;
 pub mod pplns_validator;
+pub mod query_cache;
 pub mod rate_limit;
+pub mod reports;
+pub mod restart;
+pub mod safety;
+pub mod secrets;
+pub mod share_stats;
+pub mod shutdown;
+pub mod storage;
+pub mod store_doctor;
+pub mod timeseries;
+pub mod tls;
 pub mod two_factor;
+pub mod vardiff;
+pub mod versioning;
+pub mod wallet;
+pub mod worker_watch;
+pub mod zmq_monitor;
 
-pub use alert::{AlertManager, AlertConfig, AlertRule, AlertChannel, AlertLevel, AlertCondition, Alert};
-pub use auth::{AuthManager, Claims, User, UserInfo, LoginRequest, LoginResponse, PasswordValidation, validate_password_strength};
-pub use audit::{AuditLogger, AuditLog, AuditFilter, AuditStats};
-pub use backup::{BackupManager, BackupConfig, BackupMetadata, BackupStats};
-pub use config_mgt::{ConfigManager, ConfigVersion, ConfigDiff, ScheduledChange, ConfigSchema};
+#[cfg(feature = "analytics")]
+pub use analytics::{AnalyticsMirror, SyncReport};
+pub use anomaly::{AnomalyDetector, AnomalyReading, DropCause, Ewma};
+pub use alert::{AlertManager, AlertConfig, AlertRule, AlertChannel, AlertLevel, AlertCondition, Alert, AlertChange, AlertChangeKind};
+pub use auth::{AuthManager, Claims, Lockout, LockoutPolicy, LockoutTarget, LoginAttempt, LoginOutcome, LoginResult, MinerTokenInfo, SessionInfo, User, UserInfo, LoginRequest, LoginResponse, PasswordPolicy, PasswordValidation, validate_password_strength};
+pub use audit::{AuditAnchor, AuditChain, AuditFileStats, AuditLogger, AuditLog, AuditFilter, AuditPage, AuditStats, ChainReport, RotationPolicy};
+pub use backup::{BackupManager, BackupConfig, BackupMetadata, BackupStats, BackupVerification, CatalogRepair, CheckpointSource, StorePathCheckpoint, RestoreOptions, RestorePlan, RetentionPolicy, SpaceCheck, FileChange, BackupKind, BackupSchedule, ScheduledBackup, BackupKey, BackupCompatibility, BackupImport, BackupMigration, StoreVersions, VersionComponent};
+pub use bans::{BanManager, Ban, BanTarget};
+pub use blocks::{BlockTracker, BlockSource, FoundBlock};
+pub use bootstrap::{StartupCheck, StartupError, StartupSettings};
+pub use config_mgt::{ConfigManager, ConfigVersion, ConfigDiff, ScheduledChange, ConfigSchema, SettingChange, VersionConflict};
+pub use config_watcher::ConfigWatcher;
+pub use connections::{ConnectionCounter, ConnectionRegistry, SocketTableCounter};
+pub use cors::CorsPolicy;
+pub use cron::CronExpr;
 pub use confirmation::{ConfigConfirmation, ConfigChangeRequest, RiskLevel, ConfigMeta};
-pub use health::{HealthChecker, HealthStatus, ComponentStatus};
-pub use pplns_validator::{PplnsSimulator, PayoutCalculation, PplnsValidationResult, ScenarioResult};
-pub use rate_limit::{RateLimiterState, RateLimitConfig, extract_client_ip};
-pub use two_factor::{TwoFactorManager, TwoFactorSetup, TwoFactorVerify, TwoFactorEnable, TwoFactorStatus, TwoFactorLogin};
-
+pub use estimate::{EarningsEstimate, EstimateParams, NetworkStats};
+pub use export::{ExportEncoder, ExportFormat};
+pub use farms::{AddressWorkers, Farm, FarmStats, FarmStore, WorkerBreakdown};
+pub use fees::{FeeLedger, FeeRecord, FeeRange, FeeReport};
+pub use geoip::{GeoInfo, GeoIp, GeoSummary};
+pub use health::{HealthChecker, HealthHistory, HealthHistoryEntry, HealthStatus, ComponentStatus, Readiness, ReadinessCheck};
+pub use ingest::{AuthFailure, RejectReason, StratumEvent, StratumIngest};
+pub use instances::{InstanceRegistry, InstanceSpec, PoolInstance};
+pub use jobs::{Job, JobContext, JobKind, JobRegistry, JobSettings, JobState, Progress, ProgressReport};
+pub use live_feed::{LiveFeed, LiveEvent, WorkerPresence};
+pub use mempool::{FeeMonitor, FeeSnapshot, FeeSource};
+pub use maintenance::{MaintenanceMode, MaintenanceInfo, MaintenanceStatus};
+pub use miner_access::{Challenge, ChallengeStore, MinerEvent, MinerWebhook, MinerWebhooks};
+pub use miner_settings::{MinerSettings, MinerSettingsRecord, MinerSettingsStore};
+pub use outbox::{Delivery, DeliveryStatus, Outbox, OutboxEvent, RetryPolicy, Webhook, WebhookInfo};
+pub use payout::{PayoutEngine, PayoutConfig, PayoutBatch, PayoutStatus, BlockCredit};
+pub use pplns_validator::{PplnsSimulator, PayoutCalculation, PayoutPreview, PplnsValidationReport, PplnsValidationResult, ScenarioResult, ReplayBlock, ReplayParams, ReplayReport};
+pub use query_cache::{CacheSettings, CacheStats, QueryCache};
+pub use reports::{PoolReport, ReportPeriod, ReportSettings, ReportSubscription, ReportSubscriptions};
+pub use restart::{PendingRestartChange, RestartCoordinator, RestartMethod, RestartRecord};
+pub use rate_limit::{RateLimiterState, RateLimitConfig, RateLimitRule, RateLimitStore, MemoryRateLimitStore, extract_client_ip};
+pub use logging::{LogBuffer, LogEntry, LogFormat, LogQuery, LogSource, current_request_id, request_id_middleware};
+pub use timeseries::{TimeSeriesStore, Point, Tier};
+pub use vardiff::{VardiffParams, VardiffSimulation, VardiffStep};
+pub use safety::{SafetyAnalyzer, SafetyIssue, SafetyReport, Severity, UnsafeChange};
+pub use secrets::{JwtKey, JwtKeySet, SecretProvider, SecretStore};
+pub use share_stats::{DifficultyBucket, DifficultyDistribution, MinerOrphanStats, OrphanKind, OrphanReport, OrphanTracker, OrphanedShare, ShareOutcome, ShareStatsTracker, WorkerDifficulty, WorkerShareStats};
+pub use shutdown::{Shutdown, ShutdownSettings};
+pub use storage::{ColumnFamilyCopy, ColumnFamilyStats, CompactionRun, CompactionTrigger, StoreCompactor, StoreMaintenance, StoreStats};
+pub use store_doctor::{DoctorReport, DoctorStatus, DoctorTrigger, DoctorVerdict, StoreDoctor};
+pub use tls::{ClientCertAuth, ClientCertificate, TlsSettings};
+pub use two_factor::{TwoFactorEnforcement, TwoFactorManager, TwoFactorPolicy, TwoFactorSetup, TwoFactorVerify, TwoFactorEnable, TwoFactorStatus, TwoFactorLogin};
+pub use versioning::{ApiVersion, versioned_router};
+pub use wallet::{PayoutWallet, WalletMode, WalletRpc};
+pub use worker_watch::{WatchDefaults, WorkerWatch, WorkerWatchEvent, WorkerWatchSettings};
+pub use zmq_monitor::{ZmqMonitor, ZmqMonitorStatus};
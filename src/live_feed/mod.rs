@@ -0,0 +1,161 @@
+// Live Feed module for DMPool Admin
+// Broadcasts dashboard metrics, shares, worker presence and alerts to WebSocket clients
+
+use crate::alert::Alert;
+use serde::Serialize;
+use std::collections::HashSet;
+use tokio::sync::broadcast;
+use tracing::debug;
+
+/// Default number of events buffered per subscriber before it starts lagging
+pub const DEFAULT_FEED_CAPACITY: usize = 1024;
+
+/// Event pushed to live dashboard subscribers
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveEvent {
+    /// Periodic dashboard metrics snapshot
+    Dashboard { metrics: serde_json::Value },
+    /// A share accepted into the PPLNS window
+    Share {
+        address: String,
+        worker: String,
+        difficulty: u64,
+        timestamp: u64,
+    },
+    /// A worker started submitting shares
+    WorkerConnected { address: String, worker: String },
+    /// A worker stopped submitting shares
+    WorkerDisconnected { address: String, worker: String },
+    /// An alert was triggered
+    Alert { alert: Alert },
+}
+
+/// Fan-out channel for live events
+pub struct LiveFeed {
+    sender: broadcast::Sender<LiveEvent>,
+}
+
+impl LiveFeed {
+    /// Create a feed buffering up to `capacity` events per subscriber
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    /// Publish an event, returning how many subscribers received it
+    pub fn publish(&self, event: LiveEvent) -> usize {
+        match self.sender.send(event) {
+            Ok(count) => count,
+            Err(_) => {
+                debug!("Live event dropped: no subscribers");
+                0
+            }
+        }
+    }
+
+    /// Subscribe to future events
+    pub fn subscribe(&self) -> broadcast::Receiver<LiveEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Number of connected subscribers
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+impl Default for LiveFeed {
+    fn default() -> Self {
+        Self::new(DEFAULT_FEED_CAPACITY)
+    }
+}
+
+/// Tracks which workers were active at the last poll to derive connect/disconnect events
+#[derive(Default)]
+pub struct WorkerPresence {
+    active: HashSet<(String, String)>,
+}
+
+impl WorkerPresence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the active set with `seen` and return the resulting presence changes
+    pub fn update(&mut self, seen: HashSet<(String, String)>) -> Vec<LiveEvent> {
+        let mut events = Vec::new();
+
+        for (address, worker) in seen.difference(&self.active) {
+            events.push(LiveEvent::WorkerConnected {
+                address: address.clone(),
+                worker: worker.clone(),
+            });
+        }
+        for (address, worker) in self.active.difference(&seen) {
+            events.push(LiveEvent::WorkerDisconnected {
+                address: address.clone(),
+                worker: worker.clone(),
+            });
+        }
+
+        self.active = seen;
+        events
+    }
+
+    /// Number of workers currently considered active
+    pub fn active_count(&self) -> usize {
+        self.active.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn worker(address: &str, name: &str) -> (String, String) {
+        (address.to_string(), name.to_string())
+    }
+
+    #[test]
+    fn test_worker_presence_changes() {
+        let mut presence = WorkerPresence::new();
+
+        let events = presence.update([worker("bc1qa", "rig1"), worker("bc1qb", "rig1")].into());
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|e| matches!(e, LiveEvent::WorkerConnected { .. })));
+
+        let events = presence.update([worker("bc1qa", "rig1")].into());
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            &events[0],
+            LiveEvent::WorkerDisconnected { address, .. } if address == "bc1qb"
+        ));
+
+        assert!(presence.update([worker("bc1qa", "rig1")].into()).is_empty());
+        assert_eq!(presence.active_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_live_feed_fan_out() {
+        let feed = LiveFeed::new(8);
+        assert_eq!(feed.publish(LiveEvent::Dashboard { metrics: serde_json::json!({}) }), 0);
+
+        let mut first = feed.subscribe();
+        let mut second = feed.subscribe();
+        assert_eq!(feed.subscriber_count(), 2);
+
+        let delivered = feed.publish(LiveEvent::WorkerConnected {
+            address: "bc1qa".to_string(),
+            worker: "rig1".to_string(),
+        });
+        assert_eq!(delivered, 2);
+
+        for rx in [&mut first, &mut second] {
+            let event = rx.recv().await.unwrap();
+            let json = serde_json::to_value(&event).unwrap();
+            assert_eq!(json["type"], "worker_connected");
+            assert_eq!(json["worker"], "rig1");
+        }
+    }
+}
@@ -0,0 +1,362 @@
+// Log Buffer for DMPool
+// Keeps recent log events in memory for the admin API, from this process and the pool's log file
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::broadcast;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use utoipa::IntoParams;
+
+/// How much of the pool log file is read on startup
+const POOL_LOG_BACKFILL_BYTES: u64 = 64 * 1024;
+/// How often the pool log file is checked for new lines
+const POOL_LOG_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Entries queued per follower before it starts lagging
+const FOLLOW_CAPACITY: usize = 1024;
+
+/// Where a log line came from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogSource {
+    /// This process
+    Admin,
+    /// The pool's log file
+    Pool,
+}
+
+/// One captured log line
+#[derive(Clone, Debug, Serialize)]
+pub struct LogEntry {
+    /// Increases with every entry
+    pub id: u64,
+    pub timestamp: DateTime<Utc>,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+    pub source: LogSource,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// Log query options
+#[derive(Clone, Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LogQuery {
+    /// Minimum level: `error`, `warn`, `info`, `debug` or `trace`
+    pub level: Option<String>,
+    /// Target prefix, e.g. `dmpool::backup`
+    pub target: Option<String>,
+    /// `admin` or `pool`
+    pub source: Option<LogSource>,
+    /// Case-insensitive text the message must contain
+    pub search: Option<String>,
+    /// Start time (Unix timestamp)
+    pub since: Option<i64>,
+    /// End time (Unix timestamp)
+    pub until: Option<i64>,
+    /// Only entries with a larger ID
+    pub after_id: Option<u64>,
+    /// Maximum results, newest kept (default 200)
+    pub limit: Option<usize>,
+}
+
+impl LogQuery {
+    /// The `level` filter, an error when it names no level
+    pub fn min_level(&self) -> anyhow::Result<Option<Level>> {
+        self.level.as_deref()
+            .map(|level| Level::from_str(level).map_err(|_| anyhow::anyhow!("Unknown log level: {}", level)))
+            .transpose()
+    }
+
+    /// Whether an entry passes every filter; `min_level` comes from [`LogQuery::min_level`]
+    pub fn matches(&self, entry: &LogEntry, min_level: Option<Level>) -> bool {
+        // Less verbose levels compare lower
+        if min_level.is_some_and(|min_level| !Level::from_str(&entry.level).is_ok_and(|level| level <= min_level)) {
+            return false;
+        }
+        if self.target.as_ref().is_some_and(|target| !entry.target.starts_with(target.as_str())) {
+            return false;
+        }
+        if self.source.is_some_and(|source| entry.source != source) {
+            return false;
+        }
+        if self.search.as_ref()
+            .is_some_and(|search| !entry.message.to_lowercase().contains(&search.to_lowercase()))
+        {
+            return false;
+        }
+        let timestamp = entry.timestamp.timestamp();
+        self.since.is_none_or(|since| timestamp >= since)
+            && self.until.is_none_or(|until| timestamp <= until)
+            && self.after_id.is_none_or(|after| entry.id > after)
+    }
+}
+
+struct Inner {
+    entries: Mutex<VecDeque<LogEntry>>,
+    capacity: usize,
+    next_id: AtomicU64,
+    events: broadcast::Sender<LogEntry>,
+}
+
+/// Bounded buffer of recent log lines; the oldest are dropped when full
+#[derive(Clone)]
+pub struct LogBuffer {
+    inner: Arc<Inner>,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                entries: Mutex::new(VecDeque::with_capacity(capacity)),
+                capacity: capacity.max(1),
+                next_id: AtomicU64::new(1),
+                events: broadcast::channel(FOLLOW_CAPACITY).0,
+            }),
+        }
+    }
+
+    pub fn push(&self, timestamp: DateTime<Utc>, level: &str, target: &str, message: String, source: LogSource, request_id: Option<String>) {
+        let entry = LogEntry {
+            id: self.inner.next_id.fetch_add(1, Ordering::Relaxed),
+            timestamp,
+            level: level.to_string(),
+            target: target.to_string(),
+            message,
+            source,
+            request_id,
+        };
+        let mut entries = self.entries();
+        if entries.len() >= self.inner.capacity {
+            entries.pop_front();
+        }
+        // Sent under the lock so followers see entries in ID order; no followers is not an error
+        let _ = self.inner.events.send(entry.clone());
+        entries.push_back(entry);
+    }
+
+    /// Matching entries, oldest first
+    pub fn query(&self, query: &LogQuery) -> anyhow::Result<Vec<LogEntry>> {
+        let min_level = query.min_level()?;
+        let entries = self.entries();
+        Ok(Self::matching(&entries, query, min_level))
+    }
+
+    /// Matching entries so far and a receiver for every entry added after them
+    ///
+    /// Nothing falls between the two, so a client resuming from an ID misses no lines.
+    pub fn follow(&self, query: &LogQuery) -> anyhow::Result<(Vec<LogEntry>, broadcast::Receiver<LogEntry>)> {
+        let min_level = query.min_level()?;
+        let entries = self.entries();
+        Ok((Self::matching(&entries, query, min_level), self.inner.events.subscribe()))
+    }
+
+    /// The buffer is fed from inside logging calls, which must not panic, so
+    /// a poisoned lock is used as is
+    fn entries(&self) -> MutexGuard<'_, VecDeque<LogEntry>> {
+        self.inner.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn matching(entries: &VecDeque<LogEntry>, query: &LogQuery, min_level: Option<Level>) -> Vec<LogEntry> {
+        let mut matching: Vec<LogEntry> = entries.iter()
+            .rev()
+            .filter(|entry| query.matches(entry, min_level))
+            .take(query.limit.unwrap_or(200))
+            .cloned()
+            .collect();
+        matching.reverse();
+        matching
+    }
+
+    /// Follow the pool's log file, adding new lines as they are written
+    ///
+    /// Starts with the last few KiB of the file and reopens it from the start
+    /// when it shrinks (rotation or truncation).
+    pub async fn tail_file(self, path: PathBuf) {
+        let mut position: Option<u64> = None;
+        let mut partial = String::new();
+        loop {
+            if let Err(e) = self.read_new_lines(&path, &mut position, &mut partial).await {
+                tracing::debug!("Pool log {:?} not readable: {}", path, e);
+            }
+            tokio::time::sleep(POOL_LOG_POLL_INTERVAL).await;
+        }
+    }
+
+    async fn read_new_lines(&self, path: &Path, position: &mut Option<u64>, partial: &mut String) -> anyhow::Result<()> {
+        let mut file = tokio::fs::File::open(path).await?;
+        let len = file.metadata().await?.len();
+        let start = match *position {
+            Some(pos) if pos <= len => pos,
+            // Shrunk: a new file
+            Some(_) => 0,
+            None => len.saturating_sub(POOL_LOG_BACKFILL_BYTES),
+        };
+        if position.is_none_or(|pos| pos > len) {
+            partial.clear();
+        }
+        file.seek(std::io::SeekFrom::Start(start)).await?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes).await?;
+        let mut text = String::from_utf8_lossy(&bytes).into_owned();
+        // The backfill most likely starts mid-line
+        if position.is_none() && start > 0 {
+            text = text.split_once('\n').map(|(_, rest)| rest.to_string()).unwrap_or_default();
+        }
+        *position = Some(start + bytes.len() as u64);
+
+        partial.push_str(&text);
+        let complete = match partial.rfind('\n') {
+            Some(end) => partial.drain(..=end).collect::<String>(),
+            None => return Ok(()),
+        };
+        for line in complete.lines().filter(|line| !line.trim().is_empty()) {
+            self.push(Utc::now(), pool_line_level(line), "pool", line.to_string(), LogSource::Pool, None);
+        }
+        Ok(())
+    }
+}
+
+/// Level named in a pool log line, `INFO` when none is found
+fn pool_line_level(line: &str) -> &'static str {
+    ["ERROR", "WARN", "INFO", "DEBUG", "TRACE"]
+        .into_iter()
+        .find(|level| line.split(|c: char| !c.is_ascii_alphabetic()).any(|word| word == *level))
+        .unwrap_or("INFO")
+}
+
+#[derive(Default)]
+struct EventVisitor {
+    message: String,
+    fields: Vec<String>,
+}
+
+impl Visit for EventVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields.push(format!("{}={}", field.name(), value));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.fields.push(format!("{}={:?}", field.name(), value));
+        }
+    }
+}
+
+/// Tracing layer copying every event into a [`LogBuffer`]
+pub struct LogBufferLayer {
+    buffer: LogBuffer,
+}
+
+impl LogBufferLayer {
+    pub fn new(buffer: LogBuffer) -> Self {
+        Self { buffer }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for LogBufferLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = EventVisitor::default();
+        event.record(&mut visitor);
+        let mut message = visitor.message;
+        for field in visitor.fields {
+            if !message.is_empty() {
+                message.push(' ');
+            }
+            message.push_str(&field);
+        }
+        let metadata = event.metadata();
+        self.buffer.push(
+            Utc::now(),
+            metadata.level().as_str(),
+            metadata.target(),
+            message,
+            LogSource::Admin,
+            super::current_request_id(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_capture_and_query() {
+        let buffer = LogBuffer::new(3);
+        let subscriber = tracing_subscriber::registry().with(LogBufferLayer::new(buffer.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "dmpool::backup", "Backup created");
+            tracing::warn!(target: "dmpool::alert", count = 2, "Alerts firing");
+            tracing::error!(target: "dmpool::backup", "Backup failed");
+            tracing::debug!(target: "dmpool::auth", "Token verified");
+        });
+
+        // The first entry was dropped to stay within capacity
+        let all = buffer.query(&LogQuery::default()).unwrap();
+        assert_eq!(all.iter().map(|e| e.id).collect::<Vec<_>>(), vec![2, 3, 4]);
+        assert_eq!(all[0].message, "Alerts firing count=2");
+
+        let warnings = buffer.query(&LogQuery { level: Some("warn".into()), ..Default::default() }).unwrap();
+        assert_eq!(warnings.iter().map(|e| e.level.as_str()).collect::<Vec<_>>(), vec!["WARN", "ERROR"]);
+        let backup = buffer.query(&LogQuery { target: Some("dmpool::backup".into()), ..Default::default() }).unwrap();
+        assert_eq!(backup.len(), 1);
+        let newest = buffer.query(&LogQuery { limit: Some(1), ..Default::default() }).unwrap();
+        assert_eq!(newest[0].id, 4);
+        assert_eq!(buffer.query(&LogQuery { after_id: Some(3), ..Default::default() }).unwrap().len(), 1);
+        assert!(buffer.query(&LogQuery { level: Some("loud".into()), ..Default::default() }).is_err());
+
+        // Followers get what was missed, then every new entry
+        let (missed, mut live) = buffer.follow(&LogQuery { after_id: Some(3), ..Default::default() }).unwrap();
+        assert_eq!(missed.iter().map(|e| e.id).collect::<Vec<_>>(), vec![4]);
+        buffer.push(Utc::now(), "INFO", "dmpool", "Later".into(), LogSource::Admin, None);
+        assert_eq!(live.try_recv().unwrap().id, 5);
+    }
+
+    #[tokio::test]
+    async fn test_tail_pool_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pool.log");
+        tokio::fs::write(&path, "2026-01-01 INFO started\n").await.unwrap();
+
+        let buffer = LogBuffer::new(100);
+        let (mut position, mut partial) = (None, String::new());
+        buffer.read_new_lines(&path, &mut position, &mut partial).await.unwrap();
+
+        // A line is only taken once it is complete
+        let mut file = tokio::fs::OpenOptions::new().append(true).open(&path).await.unwrap();
+        tokio::io::AsyncWriteExt::write_all(&mut file, b"2026-01-01 ERROR share rejec").await.unwrap();
+        buffer.read_new_lines(&path, &mut position, &mut partial).await.unwrap();
+        assert_eq!(buffer.query(&LogQuery::default()).unwrap().len(), 1);
+        tokio::io::AsyncWriteExt::write_all(&mut file, b"ted\n").await.unwrap();
+        buffer.read_new_lines(&path, &mut position, &mut partial).await.unwrap();
+
+        let entries = buffer.query(&LogQuery { source: Some(LogSource::Pool), ..Default::default() }).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].level, "ERROR");
+        assert_eq!(entries[1].message, "2026-01-01 ERROR share rejected");
+
+        // Truncated (rotated) files are read again from the start
+        tokio::fs::write(&path, "WARN fresh\n").await.unwrap();
+        buffer.read_new_lines(&path, &mut position, &mut partial).await.unwrap();
+        assert_eq!(buffer.query(&LogQuery::default()).unwrap().last().unwrap().message, "WARN fresh");
+    }
+}
@@ -0,0 +1,132 @@
+// Logging for DMPool
+// Text or JSON log output, and per-request correlation IDs tying log lines, responses and audit entries together
+
+mod buffer;
+
+pub use buffer::{LogBuffer, LogBufferLayer, LogEntry, LogQuery, LogSource};
+
+use anyhow::Result;
+use axum::{
+    extract::Request,
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+use std::str::FromStr;
+use tracing::Instrument;
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+/// Header carrying the request ID, accepted from clients and proxies and echoed in responses
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest client-supplied request ID that is kept
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Log output format
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, for Loki, ELK and similar
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            other => Err(anyhow::anyhow!("Unknown log format: {} (expected text or json)", other)),
+        }
+    }
+}
+
+impl LogFormat {
+    /// Format from `LOG_FORMAT`, text when unset
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("LOG_FORMAT") {
+            Ok(format) => format.parse(),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+}
+
+/// Install the global subscriber; `RUST_LOG` filters events, `info` by default
+///
+/// Events are also copied into `buffer` when given.
+pub fn init(format: LogFormat, buffer: Option<LogBuffer>) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(buffer.map(LogBufferLayer::new));
+    match format {
+        LogFormat::Text => registry.with(fmt::layer()).init(),
+        // The request span's fields (request_id, method, path) go on every line
+        LogFormat::Json => registry
+            .with(fmt::layer().json().flatten_event(true).with_current_span(true).with_span_list(false))
+            .init(),
+    }
+}
+
+/// ID of the request being handled by the current task, if any
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Client-supplied ID if it is short and made of safe characters
+fn accepted_request_id(value: Option<&HeaderValue>) -> Option<String> {
+    let id = value?.to_str().ok()?;
+    let safe = !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    safe.then(|| id.to_string())
+}
+
+/// Give every request an ID, log it within a span carrying that ID, and echo it back
+///
+/// An `X-Request-Id` from the client or a proxy is kept when well-formed;
+/// otherwise a UUID is generated.
+pub async fn request_id_middleware(req: Request, next: Next) -> Response {
+    let id = accepted_request_id(req.headers().get(REQUEST_ID_HEADER))
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let span = tracing::info_span!(
+        "request",
+        request_id = %id,
+        method = %req.method(),
+        path = %req.uri().path(),
+    );
+    let mut response = REQUEST_ID.scope(id.clone(), next.run(req).instrument(span)).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_request_ids() {
+        assert_eq!(
+            accepted_request_id(Some(&HeaderValue::from_static("abc-123_x.y"))).as_deref(),
+            Some("abc-123_x.y"),
+        );
+        assert_eq!(accepted_request_id(Some(&HeaderValue::from_static("has space"))), None);
+        assert_eq!(accepted_request_id(Some(&HeaderValue::from_str(&"a".repeat(129)).unwrap())), None);
+        assert_eq!(accepted_request_id(None), None);
+
+        assert_eq!(current_request_id(), None);
+        let id = REQUEST_ID.scope("req-1".to_string(), async { current_request_id() }).await;
+        assert_eq!(id.as_deref(), Some("req-1"));
+
+        assert_eq!("json".parse::<LogFormat>().unwrap(), LogFormat::Json);
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
+}
@@ -0,0 +1,241 @@
+// Maintenance Mode for DMPool
+// Pauses writes and drains in-flight operations so restores run against a quiet pool
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+/// Why and by whom maintenance was entered
+#[derive(Clone, Debug, Serialize)]
+pub struct MaintenanceInfo {
+    pub reason: String,
+    pub entered_by: String,
+    pub entered_at: DateTime<Utc>,
+}
+
+/// Current maintenance state
+#[derive(Clone, Debug, Serialize)]
+pub struct MaintenanceStatus {
+    pub active: bool,
+    pub info: Option<MaintenanceInfo>,
+    /// Writes that were running when checked
+    pub in_flight: usize,
+}
+
+#[derive(Default)]
+struct Inner {
+    active: AtomicBool,
+    in_flight: AtomicUsize,
+    drained: Notify,
+    info: Mutex<Option<MaintenanceInfo>>,
+}
+
+/// Shared switch that write paths check before modifying pool state
+#[derive(Clone, Default)]
+pub struct MaintenanceMode {
+    inner: Arc<Inner>,
+}
+
+/// Marks a write as in flight until dropped
+pub struct WriteGuard {
+    inner: Arc<Inner>,
+}
+
+impl Drop for WriteGuard {
+    fn drop(&mut self) {
+        if self.inner.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.inner.drained.notify_waiters();
+        }
+    }
+}
+
+/// Leaves maintenance when dropped, so a task that is cancelled or panics
+/// doesn't leave writes refused
+pub struct ExitGuard {
+    mode: Option<MaintenanceMode>,
+}
+
+impl ExitGuard {
+    /// Leave maintenance now
+    pub fn exit(mut self) -> Result<MaintenanceInfo> {
+        match self.mode.take() {
+            Some(mode) => mode.exit(),
+            None => Err(anyhow::anyhow!("Not in maintenance mode")),
+        }
+    }
+}
+
+impl Drop for ExitGuard {
+    fn drop(&mut self) {
+        if let Some(info) = self.mode.take().and_then(|mode| mode.clear()) {
+            warn!("Leaving maintenance mode entered by {}; its task ended early", info.entered_by);
+        }
+    }
+}
+
+impl MaintenanceMode {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.inner.active.load(Ordering::SeqCst)
+    }
+
+    /// Register a write; fails while in maintenance
+    pub fn begin_write(&self) -> Result<WriteGuard> {
+        // Count first, then check, so `enter` either sees this write or the
+        // write sees maintenance
+        self.inner.in_flight.fetch_add(1, Ordering::SeqCst);
+        let guard = WriteGuard { inner: self.inner.clone() };
+        if self.is_active() {
+            return Err(anyhow::anyhow!("Pool is in maintenance mode"));
+        }
+        Ok(guard)
+    }
+
+    /// Refuse new writes and wait up to `drain_timeout` for running ones to finish
+    ///
+    /// If writes are still running after the timeout, maintenance is left
+    /// again and an error returned.
+    pub async fn enter(&self, reason: &str, entered_by: &str, drain_timeout: Duration) -> Result<MaintenanceInfo> {
+        if self.inner.active.swap(true, Ordering::SeqCst) {
+            return Err(anyhow::anyhow!("Already in maintenance mode"));
+        }
+        let info = MaintenanceInfo {
+            reason: reason.to_string(),
+            entered_by: entered_by.to_string(),
+            entered_at: Utc::now(),
+        };
+        *self.info() = Some(info.clone());
+        info!("Entering maintenance mode: {} (by {})", reason, entered_by);
+
+        if tokio::time::timeout(drain_timeout, self.drain()).await.is_err() {
+            let in_flight = self.inner.in_flight.load(Ordering::SeqCst);
+            warn!("{} write(s) still running after {:?}, leaving maintenance mode", in_flight, drain_timeout);
+            self.clear();
+            return Err(anyhow::anyhow!(
+                "Timed out draining {} in-flight write(s) after {}s",
+                in_flight,
+                drain_timeout.as_secs()
+            ));
+        }
+        Ok(info)
+    }
+
+    async fn drain(&self) {
+        loop {
+            let drained = self.inner.drained.notified();
+            if self.inner.in_flight.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            drained.await;
+        }
+    }
+
+    /// Wait up to `timeout` for running writes to finish, without refusing new ones
+    ///
+    /// Returns whether they did.
+    pub async fn wait_for_writes(&self, timeout: Duration) -> bool {
+        tokio::time::timeout(timeout, self.drain()).await.is_ok()
+    }
+
+    /// Guard that leaves maintenance when dropped, for a task that entered it
+    pub fn exit_on_drop(&self) -> ExitGuard {
+        ExitGuard { mode: Some(self.clone()) }
+    }
+
+    /// Resume writes; returns the maintenance that ended
+    pub fn exit(&self) -> Result<MaintenanceInfo> {
+        if !self.is_active() {
+            return Err(anyhow::anyhow!("Not in maintenance mode"));
+        }
+        let info = self.clear()
+            .ok_or_else(|| anyhow::anyhow!("Not in maintenance mode"))?;
+        info!("Leaving maintenance mode entered by {}", info.entered_by);
+        Ok(info)
+    }
+
+    fn clear(&self) -> Option<MaintenanceInfo> {
+        let info = self.info().take();
+        self.inner.active.store(false, Ordering::SeqCst);
+        info
+    }
+
+    pub fn status(&self) -> MaintenanceStatus {
+        MaintenanceStatus {
+            active: self.is_active(),
+            info: self.info().clone(),
+            in_flight: self.inner.in_flight.load(Ordering::SeqCst),
+        }
+    }
+
+    /// The info stays usable even if a holder of the lock panicked
+    fn info(&self) -> std::sync::MutexGuard<'_, Option<MaintenanceInfo>> {
+        self.inner.info.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_enter_drains_writes_and_blocks_new_ones() {
+        let mode = MaintenanceMode::new();
+        let write = mode.begin_write().unwrap();
+
+        let entering = {
+            let mode = mode.clone();
+            tokio::spawn(async move { mode.enter("restore", "admin", Duration::from_secs(5)).await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(mode.is_active());
+        assert!(!entering.is_finished());
+        assert!(mode.begin_write().is_err());
+
+        drop(write);
+        let info = entering.await.unwrap().unwrap();
+        assert_eq!(info.reason, "restore");
+        assert_eq!(mode.status().in_flight, 0);
+
+        mode.exit().unwrap();
+        assert!(mode.begin_write().is_ok());
+        assert!(mode.exit().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_enter_times_out_on_stuck_writes() {
+        let mode = MaintenanceMode::new();
+        let _write = mode.begin_write().unwrap();
+        assert!(mode.enter("restore", "admin", Duration::from_millis(20)).await.is_err());
+        assert!(!mode.is_active());
+        assert!(mode.status().info.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_exit_guard_leaves_maintenance_when_dropped() {
+        let mode = MaintenanceMode::new();
+        mode.enter("restore", "admin", Duration::from_secs(1)).await.unwrap();
+        let restore = {
+            let guard = mode.exit_on_drop();
+            tokio::spawn(async move {
+                let _guard = guard;
+                std::future::pending::<()>().await
+            })
+        };
+        restore.abort();
+        assert!(restore.await.unwrap_err().is_cancelled());
+        assert!(!mode.is_active());
+        assert!(mode.begin_write().is_ok());
+
+        mode.enter("restore", "admin", Duration::from_secs(1)).await.unwrap();
+        assert_eq!(mode.exit_on_drop().exit().unwrap().reason, "restore");
+        assert!(!mode.is_active());
+    }
+}
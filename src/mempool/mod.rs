@@ -0,0 +1,190 @@
+// Mempool and Fee Monitor for DMPool
+// Fee estimates and mempool size polled from the node, and what they cost a payout
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::{Mutex, MutexGuard};
+
+/// Confirmation targets, in blocks, of the fast, normal and economy estimates
+pub const FAST_TARGET_BLOCKS: u16 = 2;
+pub const NORMAL_TARGET_BLOCKS: u16 = 6;
+pub const ECONOMY_TARGET_BLOCKS: u16 = 144;
+/// Virtual size one payment adds to a batch transaction: a P2TR output, the
+/// largest common kind; inputs and change are shared by the whole batch
+pub const PAYMENT_VBYTES: u64 = 43;
+/// Snapshots kept by default; a day of polls at one per 5 minutes
+const DEFAULT_HISTORY_CAPACITY: usize = 288;
+/// Snapshots needed before the latest is compared against the median
+const MIN_SPIKE_BASELINE: usize = 6;
+
+/// Node queries needed to follow fees and the mempool
+pub trait FeeSource: Send + Sync {
+    /// Fee rate in sat/vB to confirm within `target` blocks (`estimatesmartfee`);
+    /// `None` while the node has too little data
+    fn estimate_fee_rate(&self, target: u16) -> Result<Option<f64>>;
+    /// `getmempoolinfo`
+    fn mempool_info(&self) -> Result<Value>;
+}
+
+/// BTC/kvB, as the node reports fee rates, to sat/vB
+fn btc_per_kvb_to_sat_per_vb(rate: f64) -> f64 {
+    // 1e8 sat per BTC over 1000 vB per kvB
+    rate * 100_000.0
+}
+
+impl FeeSource for bitcoincore_rpc::Client {
+    fn estimate_fee_rate(&self, target: u16) -> Result<Option<f64>> {
+        use bitcoincore_rpc::RpcApi;
+        let estimate: Value = self.call("estimatesmartfee", &[target.into()])
+            .map_err(|e| anyhow::anyhow!("estimatesmartfee failed: {}", e))?;
+        Ok(estimate["feerate"].as_f64().map(btc_per_kvb_to_sat_per_vb))
+    }
+
+    fn mempool_info(&self) -> Result<Value> {
+        use bitcoincore_rpc::RpcApi;
+        self.call("getmempoolinfo", &[])
+            .map_err(|e| anyhow::anyhow!("getmempoolinfo failed: {}", e))
+    }
+}
+
+/// Fee estimates and mempool size at one point in time
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FeeSnapshot {
+    pub at: DateTime<Utc>,
+    /// sat/vB to confirm within 2 blocks
+    pub fast_sat_vb: Option<f64>,
+    /// sat/vB to confirm within 6 blocks
+    pub normal_sat_vb: Option<f64>,
+    /// sat/vB to confirm within a day
+    pub economy_sat_vb: Option<f64>,
+    pub mempool_txs: u64,
+    pub mempool_vbytes: u64,
+    /// Lowest fee rate the node's mempool accepts
+    pub mempool_min_fee_sat_vb: f64,
+}
+
+impl FeeSnapshot {
+    /// Rate payouts are expected to pay: the normal estimate, at least the mempool minimum
+    pub fn payout_fee_rate(&self) -> Option<f64> {
+        self.normal_sat_vb.map(|rate| rate.max(self.mempool_min_fee_sat_vb))
+    }
+
+    /// Fee of adding one payment to a payout batch
+    pub fn payment_fee_sats(&self) -> Option<u64> {
+        self.payout_fee_rate().map(|rate| (rate * PAYMENT_VBYTES as f64).ceil() as u64)
+    }
+}
+
+/// Recent fee snapshots, polled from the node
+pub struct FeeMonitor {
+    capacity: usize,
+    history: Mutex<VecDeque<FeeSnapshot>>,
+}
+
+impl Default for FeeMonitor {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_CAPACITY)
+    }
+}
+
+impl FeeMonitor {
+    /// Keep the last `capacity` snapshots
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            history: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Query the node and record a snapshot
+    pub fn poll(&self, source: &dyn FeeSource) -> Result<FeeSnapshot> {
+        let info = source.mempool_info()?;
+        let snapshot = FeeSnapshot {
+            at: Utc::now(),
+            fast_sat_vb: source.estimate_fee_rate(FAST_TARGET_BLOCKS)?,
+            normal_sat_vb: source.estimate_fee_rate(NORMAL_TARGET_BLOCKS)?,
+            economy_sat_vb: source.estimate_fee_rate(ECONOMY_TARGET_BLOCKS)?,
+            mempool_txs: info["size"].as_u64().unwrap_or(0),
+            mempool_vbytes: info["bytes"].as_u64().unwrap_or(0),
+            mempool_min_fee_sat_vb: info["mempoolminfee"].as_f64().map(btc_per_kvb_to_sat_per_vb).unwrap_or(0.0),
+        };
+        self.record(snapshot.clone());
+        Ok(snapshot)
+    }
+
+    pub fn record(&self, snapshot: FeeSnapshot) {
+        let mut history = self.snapshots();
+        if history.len() == self.capacity {
+            history.pop_front();
+        }
+        history.push_back(snapshot);
+    }
+
+    pub fn latest(&self) -> Option<FeeSnapshot> {
+        self.snapshots().back().cloned()
+    }
+
+    /// Recent snapshots, newest first
+    pub fn history(&self, limit: usize) -> Vec<FeeSnapshot> {
+        self.snapshots().iter().rev().take(limit).cloned().collect()
+    }
+
+    /// Latest payout fee rate divided by the median of the history; above 1 while fees spike
+    pub fn spike_ratio(&self) -> Option<f64> {
+        let history = self.snapshots();
+        let mut rates: Vec<f64> = history.iter().filter_map(FeeSnapshot::payout_fee_rate).collect();
+        if rates.len() < MIN_SPIKE_BASELINE {
+            return None;
+        }
+        let latest = history.back()?.payout_fee_rate()?;
+        rates.sort_by(f64::total_cmp);
+        let median = rates[rates.len() / 2];
+        (median > 0.0).then(|| latest / median)
+    }
+
+    /// Snapshots stay readable even if a holder of the lock panicked
+    fn snapshots(&self) -> MutexGuard<'_, VecDeque<FeeSnapshot>> {
+        self.history.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FakeNode {
+        normal: f64,
+    }
+
+    impl FeeSource for FakeNode {
+        fn estimate_fee_rate(&self, target: u16) -> Result<Option<f64>> {
+            // BTC/kvB from the node, converted by the source
+            Ok((target != ECONOMY_TARGET_BLOCKS).then(|| btc_per_kvb_to_sat_per_vb(self.normal / 100_000.0)))
+        }
+
+        fn mempool_info(&self) -> Result<Value> {
+            Ok(serde_json::json!({ "size": 1200, "bytes": 800_000, "mempoolminfee": 0.00001 }))
+        }
+    }
+
+    #[test]
+    fn test_polls_fees_and_detects_spikes() {
+        let monitor = FeeMonitor::new(10);
+        for _ in 0..MIN_SPIKE_BASELINE {
+            monitor.poll(&FakeNode { normal: 10.0 }).unwrap();
+        }
+        let calm = monitor.latest().unwrap();
+        assert_eq!(calm.normal_sat_vb, Some(10.0));
+        assert_eq!(calm.economy_sat_vb, None);
+        assert_eq!((calm.mempool_txs, calm.mempool_min_fee_sat_vb), (1200, 1.0));
+        assert_eq!(calm.payment_fee_sats(), Some(430));
+        assert_eq!(monitor.spike_ratio(), Some(1.0));
+
+        monitor.poll(&FakeNode { normal: 50.0 }).unwrap();
+        assert_eq!(monitor.spike_ratio(), Some(5.0));
+        assert_eq!(monitor.history(2)[0].normal_sat_vb, Some(50.0));
+    }
+}
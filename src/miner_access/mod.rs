@@ -0,0 +1,417 @@
+// Miner Self-Service for DMPool
+// Proves ownership of a BTC address with a signed message, and notifies miners of their credits, payouts and workers
+
+use anyhow::{Context, Result};
+use bitcoin::address::NetworkUnchecked;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::sign_message::{signed_msg_hash, MessageSignature};
+use bitcoin::{Address, Network};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+/// How long a challenge can be signed
+pub const CHALLENGE_TTL_SECS: i64 = 10 * 60;
+/// Outstanding challenges kept at most, so unauthenticated requests can't grow memory
+const MAX_PENDING_CHALLENGES: usize = 10_000;
+/// Outstanding challenges one client can hold, so no one can crowd out the rest
+const MAX_CHALLENGES_PER_CLIENT: usize = 5;
+/// Webhooks one address can register
+pub const MAX_WEBHOOKS_PER_ADDRESS: usize = 5;
+
+/// Parse an address and check it belongs to `network`
+pub fn parse_address(address: &str, network: Network) -> Result<Address> {
+    address.parse::<Address<NetworkUnchecked>>()
+        .map_err(|e| anyhow::anyhow!("Invalid address {}: {}", address, e))?
+        .require_network(network)
+        .map_err(|e| anyhow::anyhow!("Address {} is not for {}: {}", address, network, e))
+}
+
+/// Check a base64 `signmessage` signature of `message` against `address`
+///
+/// Accepts P2PKH, P2SH-P2WPKH and P2WPKH addresses, including the BIP 137
+/// segwit headers that Electrum and hardware wallets use.
+pub fn verify_message(address: &Address, message: &str, signature: &str) -> Result<()> {
+    use base64::Engine;
+
+    let mut bytes = base64::engine::general_purpose::STANDARD.decode(signature.trim())
+        .context("Signature is not base64")?;
+    // Headers 35-42 mark segwit keys, which are always compressed
+    if bytes.len() == 65 && (35..=42).contains(&bytes[0]) {
+        bytes[0] = 31 + ((bytes[0] - 27) & 0x03);
+    }
+    let signature = MessageSignature::from_slice(&bytes)
+        .map_err(|e| anyhow::anyhow!("Invalid signature: {}", e))?;
+    let pubkey = signature.recover_pubkey(&Secp256k1::verification_only(), signed_msg_hash(message))
+        .map_err(|e| anyhow::anyhow!("Invalid signature: {}", e))?;
+    if !address.is_related_to_pubkey(&pubkey) {
+        return Err(anyhow::anyhow!("Signature was not made by {}", address));
+    }
+    Ok(())
+}
+
+/// Check a miner-supplied notification URL: https, with a host that isn't a
+/// private, loopback or link-local address
+///
+/// Host names are checked again when they are resolved, see `PublicResolver`.
+pub fn validate_webhook_url(url: &str) -> Result<reqwest::Url> {
+    let parsed = reqwest::Url::parse(url).context("Invalid webhook URL")?;
+    let Some(host) = parsed.host_str().filter(|_| parsed.scheme() == "https") else {
+        return Err(anyhow::anyhow!("Webhook URL must be an https URL"));
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let local_name = host.eq_ignore_ascii_case("localhost") || host.to_ascii_lowercase().ends_with(".localhost");
+    let private_ip = host.parse::<IpAddr>().is_ok_and(|ip| !crate::geoip::is_public(ip));
+    if local_name || private_ip {
+        return Err(anyhow::anyhow!("Webhook URL must point to a public host"));
+    }
+    Ok(parsed)
+}
+
+/// Resolver of the miner webhook client that drops non-public addresses, so a
+/// host name can't lead requests into the pool's own network
+struct PublicResolver;
+
+impl PublicResolver {
+    async fn lookup(name: reqwest::dns::Name) -> Result<reqwest::dns::Addrs, Box<dyn std::error::Error + Send + Sync>> {
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0)).await?
+            .filter(|addr| crate::geoip::is_public(addr.ip()))
+            .collect();
+        if addrs.is_empty() {
+            return Err(format!("{} has no public address", name.as_str()).into());
+        }
+        Ok(Box::new(addrs.into_iter()))
+    }
+}
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        Box::pin(Self::lookup(name))
+    }
+}
+
+/// Message a miner signs to prove they own an address
+#[derive(Clone, Debug, Serialize)]
+pub struct Challenge {
+    pub address: String,
+    pub message: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A challenge and the client that asked for it
+struct PendingChallenge {
+    challenge: Challenge,
+    client: String,
+}
+
+/// Outstanding challenges, keyed by nonce; each can be answered once
+///
+/// An address can have several, so requesting challenges for someone else's
+/// address doesn't void theirs.
+#[derive(Default)]
+pub struct ChallengeStore {
+    pending: RwLock<HashMap<String, PendingChallenge>>,
+}
+
+impl ChallengeStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Issue a challenge for `address` to `client`, the requester's IP address
+    pub async fn issue(&self, address: &str, client: &str) -> Result<Challenge> {
+        let now = Utc::now();
+        let mut pending = self.pending.write().await;
+        pending.retain(|_, p| p.challenge.expires_at > now);
+        if pending.len() >= MAX_PENDING_CHALLENGES {
+            return Err(anyhow::anyhow!("Too many outstanding challenges, try again later"));
+        }
+        if pending.values().filter(|p| p.client == client).count() >= MAX_CHALLENGES_PER_CLIENT {
+            return Err(anyhow::anyhow!("Too many outstanding challenges from {}, try again later", client));
+        }
+        let nonce = uuid::Uuid::new_v4().to_string();
+        let expires_at = now + Duration::seconds(CHALLENGE_TTL_SECS);
+        let challenge = Challenge {
+            address: address.to_string(),
+            message: format!(
+                "DMPool: I own {}\nNonce: {}\nExpires: {}",
+                address,
+                nonce,
+                expires_at.to_rfc3339(),
+            ),
+            expires_at,
+        };
+        pending.insert(nonce, PendingChallenge {
+            challenge: challenge.clone(),
+            client: client.to_string(),
+        });
+        Ok(challenge)
+    }
+
+    /// Consume the challenge of `address` the miner signed
+    ///
+    /// Challenges are only used up by a valid signature, so wrong ones can't
+    /// void the challenges of others.
+    pub async fn verify(&self, address: &Address, signature: &str) -> Result<()> {
+        let now = Utc::now();
+        let address_str = address.to_string();
+        let mut pending = self.pending.write().await;
+        let mut last_error = None;
+        let mut signed = None;
+        for (nonce, p) in pending.iter() {
+            if p.challenge.address != address_str || p.challenge.expires_at <= now {
+                continue;
+            }
+            match verify_message(address, &p.challenge.message, signature) {
+                Ok(()) => {
+                    signed = Some(nonce.clone());
+                    break;
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        match (signed, last_error) {
+            (Some(nonce), _) => {
+                pending.remove(&nonce);
+                Ok(())
+            }
+            (None, Some(e)) => Err(e),
+            (None, None) => Err(anyhow::anyhow!("No pending challenge for {}", address)),
+        }
+    }
+}
+
+/// Events a miner can be notified of
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MinerEvent {
+    /// A matured block credited the address's balance
+    BlockCredited,
+    /// A payout batch paying the address was broadcast
+    PayoutSent,
+    /// One of the address's workers stopped submitting shares
+    WorkerDown,
+    /// A worker that was down submitted shares again
+    WorkerRecovered,
+}
+
+/// Notification endpoint registered by a miner
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MinerWebhook {
+    pub id: String,
+    pub address: String,
+    pub url: String,
+    pub events: Vec<MinerEvent>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Body posted to a miner's webhook
+#[derive(Clone, Debug, Serialize)]
+pub struct MinerNotification {
+    pub event: MinerEvent,
+    pub address: String,
+    pub data: serde_json::Value,
+    pub sent_at: DateTime<Utc>,
+}
+
+/// Persistent miner webhooks
+pub struct MinerWebhooks {
+    path: PathBuf,
+    hooks: RwLock<Vec<MinerWebhook>>,
+    http: reqwest::Client,
+}
+
+impl MinerWebhooks {
+    /// Create a registry stored at `path`
+    ///
+    /// Fails if the HTTP client can't be built, rather than falling back to
+    /// one without the redirect and address checks.
+    pub fn new(path: PathBuf) -> Result<Self> {
+        // Redirects are not followed, since they could lead past the URL checks
+        let http = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .redirect(reqwest::redirect::Policy::none())
+            .dns_resolver(Arc::new(PublicResolver))
+            .build()
+            .context("Failed to build miner webhook HTTP client")?;
+        Ok(Self {
+            path,
+            hooks: RwLock::new(Vec::new()),
+            http,
+        })
+    }
+
+    /// Load webhooks from disk, if present
+    pub async fn load(&self) -> Result<usize> {
+        if !self.path.exists() {
+            return Ok(0);
+        }
+        let content = tokio::fs::read_to_string(&self.path).await
+            .context("Failed to read miner webhooks")?;
+        let hooks: Vec<MinerWebhook> = serde_json::from_str(&content)
+            .context("Failed to parse miner webhooks")?;
+        let count = hooks.len();
+        *self.hooks.write().await = hooks;
+        Ok(count)
+    }
+
+    async fn save(&self, hooks: &[MinerWebhook]) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, serde_json::to_string_pretty(hooks)?).await
+            .context("Failed to write miner webhooks")?;
+        tokio::fs::rename(&tmp, &self.path).await
+            .context("Failed to replace miner webhooks")?;
+        Ok(())
+    }
+
+    /// Register a webhook for `address`; only https URLs of public hosts are accepted
+    pub async fn register(&self, address: &str, url: &str, events: &[MinerEvent]) -> Result<MinerWebhook> {
+        validate_webhook_url(url)?;
+        if events.is_empty() {
+            return Err(anyhow::anyhow!("A webhook needs at least one event"));
+        }
+        let mut hooks = self.hooks.write().await;
+        if hooks.iter().filter(|h| h.address == address).count() >= MAX_WEBHOOKS_PER_ADDRESS {
+            return Err(anyhow::anyhow!("At most {} webhooks per address", MAX_WEBHOOKS_PER_ADDRESS));
+        }
+        let mut unique = Vec::new();
+        for event in events {
+            if !unique.contains(event) {
+                unique.push(*event);
+            }
+        }
+        let hook = MinerWebhook {
+            id: uuid::Uuid::new_v4().to_string(),
+            address: address.to_string(),
+            url: url.to_string(),
+            events: unique,
+            created_at: Utc::now(),
+        };
+        hooks.push(hook.clone());
+        self.save(&hooks).await?;
+        info!("Registered webhook {} for {}", hook.id, address);
+        Ok(hook)
+    }
+
+    /// Webhooks of an address
+    pub async fn list(&self, address: &str) -> Vec<MinerWebhook> {
+        self.hooks.read().await.iter().filter(|h| h.address == address).cloned().collect()
+    }
+
+    /// Remove one of an address's webhooks; returns false if it has none with this id
+    pub async fn remove(&self, address: &str, id: &str) -> Result<bool> {
+        let mut hooks = self.hooks.write().await;
+        let before = hooks.len();
+        hooks.retain(|h| !(h.address == address && h.id == id));
+        if hooks.len() == before {
+            return Ok(false);
+        }
+        self.save(&hooks).await?;
+        Ok(true)
+    }
+
+    /// Post an event to the address's webhooks that subscribed to it, and to
+    /// `settings_url`, the webhook of the address's settings, which gets every event
+    ///
+    /// URLs are checked again before sending, as they may predate the checks.
+    /// Failures are logged; delivery is not retried.
+    pub async fn notify(&self, address: &str, event: MinerEvent, data: serde_json::Value, settings_url: Option<&str>) {
+        let mut targets: Vec<(String, String)> = self.hooks.read().await.iter()
+            .filter(|h| h.address == address && h.events.contains(&event))
+            .map(|h| (h.id.clone(), h.url.clone()))
+            .collect();
+        if let Some(url) = settings_url {
+            targets.push(("settings".to_string(), url.to_string()));
+        }
+        if targets.is_empty() {
+            return;
+        }
+        let notification = MinerNotification {
+            event,
+            address: address.to_string(),
+            data,
+            sent_at: Utc::now(),
+        };
+        for (id, url) in targets {
+            let url = match validate_webhook_url(&url) {
+                Ok(url) => url,
+                Err(e) => {
+                    warn!("Miner webhook {} of {} skipped: {}", id, address, e);
+                    continue;
+                }
+            };
+            let result = self.http.post(url).json(&notification).send().await
+                .and_then(|r| r.error_for_status());
+            if let Err(e) = result {
+                warn!("Miner webhook {} of {} failed: {}", id, address, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::Hash;
+    use bitcoin::secp256k1::{Message, SecretKey};
+    use bitcoin::{CompressedPublicKey, PrivateKey};
+
+    #[tokio::test]
+    async fn test_challenge_signatures() {
+        let secp = Secp256k1::new();
+        let secret = SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let key = CompressedPublicKey::from_private_key(&secp, &PrivateKey::new(secret, Network::Bitcoin)).unwrap();
+        let address = Address::p2wpkh(&key, Network::Bitcoin);
+        assert!(parse_address(&address.to_string(), Network::Testnet).is_err());
+        let address = parse_address(&address.to_string(), Network::Bitcoin).unwrap();
+
+        let sign = |message: &str, header_base: u8| {
+            let msg = Message::from_digest(signed_msg_hash(message).to_byte_array());
+            let (recid, compact) = secp.sign_ecdsa_recoverable(&msg, &secret).serialize_compact();
+            let mut bytes = vec![header_base + recid.to_i32() as u8];
+            bytes.extend_from_slice(&compact);
+            base64::Engine::encode(&base64::engine::general_purpose::STANDARD, bytes)
+        };
+
+        let challenges = ChallengeStore::new();
+        let challenge = challenges.issue(&address.to_string(), "192.0.2.1").await.unwrap();
+        // BIP 137 P2WPKH header
+        challenges.verify(&address, &sign(&challenge.message, 39)).await.unwrap();
+        // Challenges are single-use
+        assert!(challenges.verify(&address, &sign(&challenge.message, 39)).await.is_err());
+
+        let challenge = challenges.issue(&address.to_string(), "192.0.2.1").await.unwrap();
+        assert!(challenges.verify(&address, &sign("something else", 31)).await.is_err());
+        assert!(verify_message(&address, &challenge.message, &sign(&challenge.message, 31)).is_ok());
+        assert!(verify_message(&address, &challenge.message, "not base64!").is_err());
+
+        // Challenges others request for the address, or wrong signatures, don't void it
+        for _ in 0..MAX_CHALLENGES_PER_CLIENT {
+            challenges.issue(&address.to_string(), "198.51.100.7").await.unwrap();
+        }
+        assert!(challenges.issue(&address.to_string(), "198.51.100.7").await.is_err());
+        challenges.verify(&address, &sign(&challenge.message, 31)).await.unwrap();
+    }
+
+    #[test]
+    fn test_webhook_urls() {
+        assert!(validate_webhook_url("https://hooks.example.com/miner").is_ok());
+        assert!(validate_webhook_url("https://8.8.8.8/hook").is_ok());
+        assert!(validate_webhook_url("http://hooks.example.com/miner").is_err());
+        assert!(validate_webhook_url("https://localhost/hook").is_err());
+        assert!(validate_webhook_url("https://127.0.0.1/hook").is_err());
+        assert!(validate_webhook_url("https://10.0.0.5/hook").is_err());
+        assert!(validate_webhook_url("https://169.254.169.254/latest/meta-data").is_err());
+        assert!(validate_webhook_url("https://[::1]/hook").is_err());
+        assert!(validate_webhook_url("https://[::ffff:192.168.1.1]/hook").is_err());
+    }
+}
@@ -0,0 +1,200 @@
+// Miner Settings for DMPool
+// Display names and notification contacts miners set for their BTC addresses
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tokio::sync::RwLock;
+use tracing::info;
+
+/// Longest display name, in characters
+pub const MAX_DISPLAY_NAME_CHARS: usize = 32;
+/// Longest notification email address (RFC 5321)
+const MAX_EMAIL_LEN: usize = 254;
+
+/// Basic format check of an email address
+pub fn validate_email(email: &str) -> Result<()> {
+    let valid = email.len() <= MAX_EMAIL_LEN
+        && !email.chars().any(|c| c.is_whitespace() || c.is_control())
+        && email.split_once('@').is_some_and(|(local, domain)| {
+            !local.is_empty() && !domain.contains('@') && domain.contains('.')
+                && !domain.starts_with('.') && !domain.ends_with('.')
+        });
+    if !valid {
+        return Err(anyhow::anyhow!("Invalid email address {}", email));
+    }
+    Ok(())
+}
+
+/// Settings a miner chooses for their address
+///
+/// The payout threshold lives in the payout ledger, which batches payments by it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MinerSettings {
+    /// Shown instead of the address on public pages
+    pub display_name: Option<String>,
+    /// Where operators can reach the miner
+    pub notify_email: Option<String>,
+    /// https URL of a public host notified of every miner event
+    pub notify_webhook: Option<String>,
+}
+
+impl MinerSettings {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Check every setting, so an update is applied whole or not at all
+    pub fn validate(&self) -> Result<()> {
+        if let Some(name) = &self.display_name {
+            if name.trim().is_empty() || name.trim() != name {
+                return Err(anyhow::anyhow!("Display name can't be blank or start or end with spaces"));
+            }
+            if name.chars().count() > MAX_DISPLAY_NAME_CHARS {
+                return Err(anyhow::anyhow!("Display name is longer than {} characters", MAX_DISPLAY_NAME_CHARS));
+            }
+            if name.chars().any(char::is_control) {
+                return Err(anyhow::anyhow!("Display name contains control characters"));
+            }
+        }
+        if let Some(email) = &self.notify_email {
+            validate_email(email)?;
+        }
+        if let Some(url) = &self.notify_webhook {
+            crate::miner_access::validate_webhook_url(url)?;
+        }
+        Ok(())
+    }
+}
+
+/// Settings of one address and who last changed them
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MinerSettingsRecord {
+    pub address: String,
+    #[serde(flatten)]
+    pub settings: MinerSettings,
+    pub updated_at: DateTime<Utc>,
+    /// The address itself for self-service changes, otherwise the admin user
+    pub updated_by: String,
+}
+
+/// Persistent miner settings, keyed by address
+pub struct MinerSettingsStore {
+    path: PathBuf,
+    records: RwLock<BTreeMap<String, MinerSettingsRecord>>,
+}
+
+impl MinerSettingsStore {
+    /// Create a store kept at `path`
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            records: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Load settings from disk, if present
+    pub async fn load(&self) -> Result<usize> {
+        if !self.path.exists() {
+            return Ok(0);
+        }
+        let content = tokio::fs::read_to_string(&self.path).await
+            .context("Failed to read miner settings")?;
+        let records: Vec<MinerSettingsRecord> = serde_json::from_str(&content)
+            .context("Failed to parse miner settings")?;
+        let count = records.len();
+        *self.records.write().await = records.into_iter().map(|r| (r.address.clone(), r)).collect();
+        Ok(count)
+    }
+
+    async fn save(&self, records: &BTreeMap<String, MinerSettingsRecord>) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let records: Vec<&MinerSettingsRecord> = records.values().collect();
+        let tmp = self.path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, serde_json::to_string_pretty(&records)?).await
+            .context("Failed to write miner settings")?;
+        tokio::fs::rename(&tmp, &self.path).await
+            .context("Failed to replace miner settings")?;
+        Ok(())
+    }
+
+    pub async fn get(&self, address: &str) -> Option<MinerSettingsRecord> {
+        self.records.read().await.get(address).cloned()
+    }
+
+    /// Every address with settings, sorted by address
+    pub async fn list(&self) -> Vec<MinerSettingsRecord> {
+        self.records.read().await.values().cloned().collect()
+    }
+
+    /// Replace the settings of `address`; empty settings remove its record
+    pub async fn set(&self, address: &str, settings: MinerSettings, updated_by: &str) -> Result<Option<MinerSettingsRecord>> {
+        settings.validate()?;
+        let mut records = self.records.write().await;
+        let record = (!settings.is_empty()).then(|| MinerSettingsRecord {
+            address: address.to_string(),
+            settings,
+            updated_at: Utc::now(),
+            updated_by: updated_by.to_string(),
+        });
+        match &record {
+            Some(record) => records.insert(address.to_string(), record.clone()),
+            None => records.remove(address),
+        };
+        self.save(&records).await?;
+        info!("Settings of {} updated by {}", address, updated_by);
+        Ok(record)
+    }
+
+    /// Remove the settings of `address`; returns false if it had none
+    pub async fn remove(&self, address: &str) -> Result<bool> {
+        let mut records = self.records.write().await;
+        if records.remove(address).is_none() {
+            return Ok(false);
+        }
+        self.save(&records).await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_settings_are_validated_and_persisted() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("miner_settings.json");
+        let store = MinerSettingsStore::new(path.clone());
+        let settings = MinerSettings {
+            display_name: Some("Garage rig".to_string()),
+            notify_email: Some("miner@example.com".to_string()),
+            notify_webhook: Some("https://example.com/hook".to_string()),
+        };
+        let record = store.set("bc1qminer", settings.clone(), "bc1qminer").await.unwrap().unwrap();
+        assert_eq!(record.settings, settings);
+
+        for invalid in [
+            MinerSettings { display_name: Some(" padded".to_string()), ..settings.clone() },
+            MinerSettings { display_name: Some("x".repeat(MAX_DISPLAY_NAME_CHARS + 1)), ..settings.clone() },
+            MinerSettings { notify_email: Some("miner@localhost".to_string()), ..settings.clone() },
+            MinerSettings { notify_webhook: Some("http://example.com/hook".to_string()), ..settings.clone() },
+            MinerSettings { notify_webhook: Some("https://169.254.169.254/hook".to_string()), ..settings.clone() },
+        ] {
+            assert!(store.set("bc1qminer", invalid, "admin").await.is_err());
+        }
+
+        let reloaded = MinerSettingsStore::new(path);
+        assert_eq!(reloaded.load().await.unwrap(), 1);
+        assert_eq!(reloaded.get("bc1qminer").await.unwrap().settings, settings);
+        // Clearing every setting drops the record
+        assert!(reloaded.set("bc1qminer", MinerSettings::default(), "admin").await.unwrap().is_none());
+        assert!(reloaded.list().await.is_empty());
+        assert!(!reloaded.remove("bc1qminer").await.unwrap());
+    }
+}
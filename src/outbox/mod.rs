@@ -0,0 +1,518 @@
+// Event Outbox for DMPool
+// Pool events posted to external webhooks as signed JSON, retried with exponential backoff until delivered
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::path::PathBuf;
+use tokio::sync::{Mutex, RwLock};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+/// Delivered and failed deliveries kept for the status API; pending ones are never dropped
+const MAX_FINISHED_DELIVERIES: usize = 1000;
+/// Header with `sha256=<hex>`, the HMAC-SHA256 of `<timestamp>.<body>` keyed with the webhook secret
+pub const SIGNATURE_HEADER: &str = "X-DMPool-Signature";
+/// Header with the Unix time the request was signed at
+pub const TIMESTAMP_HEADER: &str = "X-DMPool-Timestamp";
+pub const EVENT_HEADER: &str = "X-DMPool-Event";
+pub const DELIVERY_HEADER: &str = "X-DMPool-Delivery";
+
+/// Events webhooks can subscribe to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxEvent {
+    BlockFound,
+    ConfigChanged,
+    BackupFailed,
+    WorkerBanned,
+}
+
+impl OutboxEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::BlockFound => "block_found",
+            Self::ConfigChanged => "config_changed",
+            Self::BackupFailed => "backup_failed",
+            Self::WorkerBanned => "worker_banned",
+        }
+    }
+}
+
+/// When failed deliveries are retried
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// Attempts per delivery, including the first
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled after every failure
+    pub initial_backoff_secs: i64,
+    /// Upper bound for the retry delay
+    pub max_backoff_secs: i64,
+    /// Timeout for a single attempt
+    pub timeout_secs: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 10,
+            initial_backoff_secs: 30,
+            max_backoff_secs: 6 * 3600,
+            timeout_secs: 10,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `attempt` (1-based)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1i64 << attempt.saturating_sub(1).min(16);
+        Duration::seconds(self.initial_backoff_secs.saturating_mul(factor).min(self.max_backoff_secs))
+    }
+}
+
+/// An endpoint events are posted to
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    pub events: Vec<OutboxEvent>,
+    /// Key of the payload signatures
+    pub secret: String,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub created_by: Option<String>,
+}
+
+/// A webhook, without its secret
+#[derive(Clone, Debug, Serialize)]
+pub struct WebhookInfo {
+    pub id: String,
+    pub url: String,
+    pub events: Vec<OutboxEvent>,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub created_by: Option<String>,
+}
+
+impl From<&Webhook> for WebhookInfo {
+    fn from(hook: &Webhook) -> Self {
+        Self {
+            id: hook.id.clone(),
+            url: hook.url.clone(),
+            events: hook.events.clone(),
+            enabled: hook.enabled,
+            created_at: hook.created_at,
+            created_by: hook.created_by.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// Waiting for its first attempt or a retry
+    Pending,
+    Delivered,
+    /// Out of attempts; can be retried by hand
+    Failed,
+}
+
+/// One event queued for one webhook
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Delivery {
+    pub id: String,
+    pub webhook_id: String,
+    pub event: OutboxEvent,
+    /// Body posted to the webhook
+    pub payload: serde_json::Value,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    pub created_at: DateTime<Utc>,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub last_status_code: Option<u16>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct OutboxState {
+    webhooks: Vec<Webhook>,
+    deliveries: Vec<Delivery>,
+}
+
+/// Hex HMAC-SHA256 of `<timestamp>.<body>`, as sent in the signature header
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+/// Persistent webhooks and their delivery queue
+pub struct Outbox {
+    path: PathBuf,
+    state: RwLock<OutboxState>,
+    policy: RetryPolicy,
+    http: reqwest::Client,
+    /// Held while delivering, so overlapping runs don't post a delivery twice
+    delivering: Mutex<()>,
+}
+
+impl Outbox {
+    /// Create an outbox stored at `path`
+    pub fn new(path: PathBuf) -> Result<Self> {
+        Self::with_retry_policy(path, RetryPolicy::default())
+    }
+
+    /// Fails if the HTTP client can't be built, rather than falling back to
+    /// one without the delivery timeout
+    pub fn with_retry_policy(path: PathBuf, policy: RetryPolicy) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(policy.timeout_secs))
+            .build()
+            .context("Failed to build webhook HTTP client")?;
+        Ok(Self {
+            path,
+            state: RwLock::new(OutboxState::default()),
+            policy,
+            http,
+            delivering: Mutex::new(()),
+        })
+    }
+
+    /// Load webhooks and queued deliveries from disk, if present; returns the number of webhooks
+    pub async fn load(&self) -> Result<usize> {
+        if !self.path.exists() {
+            return Ok(0);
+        }
+        let content = tokio::fs::read_to_string(&self.path).await
+            .context("Failed to read outbox")?;
+        let state: OutboxState = serde_json::from_str(&content)
+            .context("Failed to parse outbox")?;
+        let count = state.webhooks.len();
+        *self.state.write().await = state;
+        Ok(count)
+    }
+
+    async fn save(&self, state: &OutboxState) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, serde_json::to_string_pretty(state)?).await
+            .context("Failed to write outbox")?;
+        tokio::fs::rename(&tmp, &self.path).await
+            .context("Failed to replace outbox")?;
+        Ok(())
+    }
+
+    /// Add a webhook; a random secret is generated when none is given
+    pub async fn add_webhook(
+        &self,
+        url: &str,
+        events: &[OutboxEvent],
+        secret: Option<String>,
+        created_by: Option<String>,
+    ) -> Result<Webhook> {
+        let parsed = reqwest::Url::parse(url).context("Invalid webhook URL")?;
+        if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+            return Err(anyhow::anyhow!("Webhook URL must be an http or https URL"));
+        }
+        if events.is_empty() {
+            return Err(anyhow::anyhow!("A webhook needs at least one event"));
+        }
+        let secret = match secret {
+            Some(secret) if secret.len() < 16 => {
+                return Err(anyhow::anyhow!("Webhook secret must be at least 16 characters"));
+            }
+            Some(secret) => secret,
+            None => {
+                use rand::RngCore;
+                let mut bytes = [0u8; 32];
+                rand::thread_rng().fill_bytes(&mut bytes);
+                bytes.iter().map(|b| format!("{:02x}", b)).collect()
+            }
+        };
+        let mut unique = Vec::new();
+        for event in events {
+            if !unique.contains(event) {
+                unique.push(*event);
+            }
+        }
+        let hook = Webhook {
+            id: uuid::Uuid::new_v4().to_string(),
+            url: url.to_string(),
+            events: unique,
+            secret,
+            enabled: true,
+            created_at: Utc::now(),
+            created_by,
+        };
+        let mut state = self.state.write().await;
+        state.webhooks.push(hook.clone());
+        self.save(&state).await?;
+        info!("Added webhook {} for {}", hook.id, hook.url);
+        Ok(hook)
+    }
+
+    pub async fn webhooks(&self) -> Vec<WebhookInfo> {
+        self.state.read().await.webhooks.iter().map(WebhookInfo::from).collect()
+    }
+
+    /// Remove a webhook and its pending deliveries; returns false if there is none with this id
+    pub async fn remove_webhook(&self, id: &str) -> Result<bool> {
+        let mut state = self.state.write().await;
+        let before = state.webhooks.len();
+        state.webhooks.retain(|h| h.id != id);
+        if state.webhooks.len() == before {
+            return Ok(false);
+        }
+        state.deliveries.retain(|d| !(d.webhook_id == id && d.status == DeliveryStatus::Pending));
+        self.save(&state).await?;
+        Ok(true)
+    }
+
+    /// Queue an event for every enabled webhook subscribed to it; returns the number of deliveries
+    ///
+    /// Deliveries are made by [`Outbox::deliver_due`], so publishing never waits on a webhook.
+    pub async fn publish(&self, event: OutboxEvent, data: serde_json::Value) -> usize {
+        let mut state = self.state.write().await;
+        let now = Utc::now();
+        let event_id = uuid::Uuid::new_v4().to_string();
+        let queued: Vec<Delivery> = state.webhooks.iter()
+            .filter(|h| h.enabled && h.events.contains(&event))
+            .map(|h| Delivery {
+                id: uuid::Uuid::new_v4().to_string(),
+                webhook_id: h.id.clone(),
+                event,
+                payload: serde_json::json!({
+                    "id": event_id,
+                    "event": event,
+                    "created_at": now,
+                    "data": data,
+                }),
+                status: DeliveryStatus::Pending,
+                attempts: 0,
+                created_at: now,
+                next_attempt_at: Some(now),
+                delivered_at: None,
+                last_error: None,
+                last_status_code: None,
+            })
+            .collect();
+        let count = queued.len();
+        if count == 0 {
+            return 0;
+        }
+        state.deliveries.extend(queued);
+        if let Err(e) = self.save(&state).await {
+            warn!("Failed to save outbox after queuing {}: {}", event.as_str(), e);
+        }
+        count
+    }
+
+    /// Deliveries, newest first, optionally of one webhook or in one status
+    pub async fn deliveries(
+        &self,
+        webhook_id: Option<&str>,
+        status: Option<DeliveryStatus>,
+        limit: usize,
+    ) -> Vec<Delivery> {
+        self.state.read().await.deliveries.iter().rev()
+            .filter(|d| webhook_id.is_none_or(|id| d.webhook_id == id))
+            .filter(|d| status.is_none_or(|s| d.status == s))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Queue a failed delivery again with a fresh set of attempts; returns false if there is
+    /// no failed delivery with this id
+    pub async fn retry(&self, id: &str) -> Result<bool> {
+        let mut state = self.state.write().await;
+        let Some(delivery) = state.deliveries.iter_mut()
+            .find(|d| d.id == id && d.status == DeliveryStatus::Failed)
+        else {
+            return Ok(false);
+        };
+        delivery.status = DeliveryStatus::Pending;
+        delivery.attempts = 0;
+        delivery.next_attempt_at = Some(Utc::now());
+        self.save(&state).await?;
+        Ok(true)
+    }
+
+    /// Attempt every pending delivery that is due; returns the number delivered
+    pub async fn deliver_due(&self) -> usize {
+        let _delivering = self.delivering.lock().await;
+        let now = Utc::now();
+        let due: Vec<(Delivery, Webhook)> = {
+            let state = self.state.read().await;
+            state.deliveries.iter()
+                .filter(|d| d.status == DeliveryStatus::Pending && d.next_attempt_at.is_some_and(|t| t <= now))
+                .filter_map(|d| {
+                    let hook = state.webhooks.iter().find(|h| h.id == d.webhook_id)?;
+                    Some((d.clone(), hook.clone()))
+                })
+                .collect()
+        };
+        if due.is_empty() {
+            return 0;
+        }
+
+        let mut delivered = 0;
+        let mut results = Vec::with_capacity(due.len());
+        for (delivery, hook) in due {
+            let result = self.send(&delivery, &hook).await;
+            if result.is_ok() {
+                delivered += 1;
+            }
+            results.push((delivery.id, result));
+        }
+
+        let mut state = self.state.write().await;
+        let now = Utc::now();
+        for (id, result) in results {
+            let Some(delivery) = state.deliveries.iter_mut().find(|d| d.id == id) else {
+                continue;
+            };
+            delivery.attempts += 1;
+            match result {
+                Ok(code) => {
+                    delivery.status = DeliveryStatus::Delivered;
+                    delivery.delivered_at = Some(now);
+                    delivery.next_attempt_at = None;
+                    delivery.last_status_code = Some(code);
+                    delivery.last_error = None;
+                }
+                Err((code, error)) => {
+                    delivery.last_status_code = code;
+                    delivery.last_error = Some(error);
+                    if delivery.attempts >= self.policy.max_attempts {
+                        warn!("Giving up on delivery {} of {} to webhook {} after {} attempts",
+                            delivery.id, delivery.event.as_str(), delivery.webhook_id, delivery.attempts);
+                        delivery.status = DeliveryStatus::Failed;
+                        delivery.next_attempt_at = None;
+                    } else {
+                        delivery.next_attempt_at = Some(now + self.policy.backoff(delivery.attempts));
+                    }
+                }
+            }
+        }
+        prune_finished(&mut state.deliveries);
+        if let Err(e) = self.save(&state).await {
+            warn!("Failed to save outbox: {}", e);
+        }
+        delivered
+    }
+
+    /// Post one delivery; returns the response status, or the status and error of a failure
+    async fn send(&self, delivery: &Delivery, hook: &Webhook) -> std::result::Result<u16, (Option<u16>, String)> {
+        let body = serde_json::to_string(&delivery.payload).map_err(|e| (None, e.to_string()))?;
+        let timestamp = Utc::now().timestamp();
+        let response = self.http.post(&hook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, delivery.event.as_str())
+            .header(DELIVERY_HEADER, &delivery.id)
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(SIGNATURE_HEADER, format!("sha256={}", sign(&hook.secret, timestamp, &body)))
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| (None, e.to_string()))?;
+        let code = response.status().as_u16();
+        if response.status().is_success() {
+            Ok(code)
+        } else {
+            Err((Some(code), format!("Webhook returned {}", response.status())))
+        }
+    }
+}
+
+/// Drop the oldest delivered and failed deliveries beyond [`MAX_FINISHED_DELIVERIES`]
+fn prune_finished(deliveries: &mut Vec<Delivery>) {
+    let finished = deliveries.iter().filter(|d| d.status != DeliveryStatus::Pending).count();
+    let mut excess = finished.saturating_sub(MAX_FINISHED_DELIVERIES);
+    if excess == 0 {
+        return;
+    }
+    deliveries.retain(|d| {
+        if excess > 0 && d.status != DeliveryStatus::Pending {
+            excess -= 1;
+            false
+        } else {
+            true
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_signed_delivery_with_retries() {
+        use axum::{http::{HeaderMap, StatusCode}, routing::post, Router};
+        use std::sync::Arc;
+
+        // Webhook that fails the first request and records the rest
+        let received: Arc<std::sync::Mutex<Vec<(HeaderMap, String)>>> = Arc::default();
+        let requests = received.clone();
+        let app = Router::new().route("/hook", post(move |headers: HeaderMap, body: String| {
+            let requests = requests.clone();
+            async move {
+                let mut requests = requests.lock().unwrap();
+                requests.push((headers, body));
+                if requests.len() == 1 { StatusCode::SERVICE_UNAVAILABLE } else { StatusCode::OK }
+            }
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let dir = tempfile::tempdir().unwrap();
+        let policy = RetryPolicy { max_attempts: 2, initial_backoff_secs: 0, ..Default::default() };
+        let outbox = Outbox::with_retry_policy(dir.path().join("outbox.json"), policy).unwrap();
+        let secret = "0123456789abcdef".to_string();
+        let hook = outbox.add_webhook(&format!("http://{}/hook", addr), &[OutboxEvent::BlockFound], Some(secret.clone()), None)
+            .await.unwrap();
+        let unreachable = outbox.add_webhook("http://127.0.0.1:1/hook", &[OutboxEvent::BlockFound], None, None)
+            .await.unwrap();
+        assert!(outbox.add_webhook("ftp://example.com", &[OutboxEvent::BlockFound], None, None).await.is_err());
+
+        assert_eq!(outbox.publish(OutboxEvent::ConfigChanged, serde_json::json!({})).await, 0);
+        assert_eq!(outbox.publish(OutboxEvent::BlockFound, serde_json::json!({ "height": 840000 })).await, 2);
+
+        // First attempt: 503 and connection refused, both retried
+        assert_eq!(outbox.deliver_due().await, 0);
+        let pending = outbox.deliveries(None, Some(DeliveryStatus::Pending), 10).await;
+        assert_eq!(pending.len(), 2);
+        assert!(pending.iter().any(|d| d.last_status_code == Some(503)));
+
+        // Second attempt: delivered, and the unreachable one is out of attempts
+        assert_eq!(outbox.deliver_due().await, 1);
+        let delivered = outbox.deliveries(Some(&hook.id), None, 10).await;
+        assert_eq!((delivered[0].status, delivered[0].attempts), (DeliveryStatus::Delivered, 2));
+        let failed = outbox.deliveries(Some(&unreachable.id), None, 10).await;
+        assert_eq!(failed[0].status, DeliveryStatus::Failed);
+
+        let (headers, body) = received.lock().unwrap().last().cloned().unwrap();
+        let timestamp: i64 = headers[TIMESTAMP_HEADER].to_str().unwrap().parse().unwrap();
+        assert_eq!(headers[SIGNATURE_HEADER].to_str().unwrap(), format!("sha256={}", sign(&secret, timestamp, &body)));
+        assert_eq!(headers[EVENT_HEADER].to_str().unwrap(), "block_found");
+        let payload: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(payload["data"]["height"], 840000);
+
+        // Queue and webhooks survive a restart, and failed deliveries can be retried
+        let reloaded = Outbox::new(dir.path().join("outbox.json")).unwrap();
+        assert_eq!(reloaded.load().await.unwrap(), 2);
+        assert!(reloaded.retry(&failed[0].id).await.unwrap());
+        assert!(!reloaded.retry(&delivered[0].id).await.unwrap());
+        assert_eq!(reloaded.deliveries(None, Some(DeliveryStatus::Pending), 10).await.len(), 1);
+        assert!(reloaded.remove_webhook(&unreachable.id).await.unwrap());
+        assert!(reloaded.deliveries(None, Some(DeliveryStatus::Pending), 10).await.is_empty());
+    }
+}
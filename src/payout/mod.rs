@@ -0,0 +1,670 @@
+// Payout Engine for DMPool
+// Credits PPLNS rewards of matured pool blocks to miner balances and batches payments
+
+use crate::blocks::FoundBlock;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use p2poolv2_lib::accounting::simple_pplns::SimplePplnsShare;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tokio::sync::RwLock;
+use tracing::info;
+
+/// Highest payout threshold a miner can choose (1 BTC)
+pub const MAX_PAYOUT_THRESHOLD_SATS: u64 = 100_000_000;
+
+/// Payout settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PayoutConfig {
+    /// Confirmations before a block's reward is credited (coinbase maturity)
+    pub maturity_confirmations: i64,
+    /// Balances below this stay pending until they grow
+    pub min_payout_sats: u64,
+    /// Seconds between scheduled payment batches
+    pub batch_interval_secs: u64,
+    /// Balances whose payment fee would exceed this share of the amount, in
+    /// basis points, wait for cheaper fees; 0 turns the check off
+    #[serde(default)]
+    pub max_fee_bps: u16,
+}
+
+impl Default for PayoutConfig {
+    fn default() -> Self {
+        Self {
+            maturity_confirmations: 100,
+            min_payout_sats: 100_000,
+            batch_interval_secs: 24 * 3600,
+            max_fee_bps: 0,
+        }
+    }
+}
+
+/// Reward of a matured block split among miners
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlockCredit {
+    pub height: u64,
+    pub hash: String,
+    pub reward_sats: u64,
+    /// Fee kept by the pool, including rounding remainders
+    pub pool_fee_sats: u64,
+    pub window_shares: u64,
+    /// Amount credited per address
+    pub credits: BTreeMap<String, u64>,
+    pub credited_at: DateTime<Utc>,
+}
+
+/// PPLNS window of a found block, kept until the block is credited
+///
+/// Shares can be pruned by their TTL during the ~100 confirmations a block
+/// needs to mature, so the window is summarized when the block is found.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PplnsWindow {
+    /// Shares in the window
+    pub shares: u64,
+    /// Difficulty submitted per address
+    pub difficulty: BTreeMap<String, u64>,
+}
+
+impl PplnsWindow {
+    pub fn from_shares(shares: &[SimplePplnsShare]) -> Self {
+        let mut window = Self { shares: shares.len() as u64, difficulty: BTreeMap::new() };
+        for share in shares {
+            if let Some(address) = &share.btcaddress {
+                *window.difficulty.entry(address.clone()).or_default() += share.difficulty;
+            }
+        }
+        window
+    }
+
+    /// Split `reward_sats` by difficulty, deducting `fee_bps` from each part
+    fn split(&self, reward_sats: u64, fee_bps: u16) -> BTreeMap<String, u64> {
+        let total: u128 = self.difficulty.values().map(|d| *d as u128).sum();
+        if total == 0 {
+            return BTreeMap::new();
+        }
+        self.difficulty.iter()
+            .map(|(address, difficulty)| {
+                let payout = (reward_sats as u128) * (*difficulty as u128) / total;
+                let fee = payout * (fee_bps as u128) / 10_000;
+                (address.clone(), payout.saturating_sub(fee).min(u64::MAX as u128) as u64)
+            })
+            .filter(|(_, amount)| *amount > 0)
+            .collect()
+    }
+}
+
+/// State of a payment batch
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PayoutStatus {
+    /// Waiting to be sent
+    Pending,
+    /// PSBT created, waiting for an external signer
+    AwaitingSignature,
+    /// Handed to the wallet; if this state persists the outcome is unknown and
+    /// an operator marks it paid or cancels it after checking the wallet
+    Sending,
+    /// Broadcast, waiting for confirmations
+    Sent,
+    Paid,
+    /// Sending failed; needs to be cancelled or marked paid by an operator
+    Failed,
+    /// Given up on; amounts were returned to balances
+    Cancelled,
+}
+
+/// A single payment in a batch
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Payment {
+    pub address: String,
+    pub amount_sats: u64,
+}
+
+/// Payments made together, e.g. in one transaction
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PayoutBatch {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub status: PayoutStatus,
+    pub payments: Vec<Payment>,
+    pub total_sats: u64,
+    pub updated_at: DateTime<Utc>,
+    /// Payment transaction, once broadcast
+    #[serde(default)]
+    pub txid: Option<String>,
+    /// Unsigned PSBT awaiting a signature
+    #[serde(default)]
+    pub psbt: Option<String>,
+    #[serde(default)]
+    pub confirmations: i64,
+    /// Latest wallet message, e.g. a dry-run summary or an error
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+/// Unpaid balance of an address
+#[derive(Clone, Debug, Serialize)]
+pub struct PendingBalance {
+    pub address: String,
+    pub balance_sats: u64,
+    /// Whether the balance reaches the address's payout threshold
+    pub eligible: bool,
+}
+
+/// Persistent payout state
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct PayoutLedger {
+    credited: Vec<BlockCredit>,
+    balances: BTreeMap<String, u64>,
+    batches: Vec<PayoutBatch>,
+    last_batch_at: Option<DateTime<Utc>>,
+    /// Payout thresholds miners chose for their addresses
+    #[serde(default)]
+    thresholds: BTreeMap<String, u64>,
+    /// PPLNS windows of found blocks awaiting credit, by block hash
+    #[serde(default)]
+    windows: BTreeMap<String, PplnsWindow>,
+}
+
+impl PayoutLedger {
+    fn threshold(&self, address: &str, config: &PayoutConfig) -> u64 {
+        self.thresholds.get(address).copied().unwrap_or(config.min_payout_sats)
+    }
+}
+
+/// Computes payouts for matured blocks and schedules payment batches
+pub struct PayoutEngine {
+    path: PathBuf,
+    config: PayoutConfig,
+    ledger: RwLock<PayoutLedger>,
+}
+
+impl PayoutEngine {
+    /// Create an engine storing its ledger at `path`
+    pub fn new(path: PathBuf, config: PayoutConfig) -> Self {
+        Self {
+            path,
+            config,
+            ledger: RwLock::new(PayoutLedger::default()),
+        }
+    }
+
+    pub fn config(&self) -> &PayoutConfig {
+        &self.config
+    }
+
+    /// Load the ledger from disk, if present
+    pub async fn load(&self) -> Result<()> {
+        if !self.path.exists() {
+            return Ok(());
+        }
+        let content = tokio::fs::read_to_string(&self.path).await
+            .context("Failed to read payout ledger")?;
+        *self.ledger.write().await = serde_json::from_str(&content)
+            .context("Failed to parse payout ledger")?;
+        Ok(())
+    }
+
+    async fn save(&self, ledger: &PayoutLedger) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        // Write then rename, so a crash never leaves a truncated ledger
+        let tmp = self.path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, serde_json::to_string_pretty(ledger)?).await
+            .context("Failed to write payout ledger")?;
+        tokio::fs::rename(&tmp, &self.path).await
+            .context("Failed to replace payout ledger")?;
+        Ok(())
+    }
+
+    /// Whether `block` is mature and not yet credited
+    pub async fn needs_credit(&self, block: &FoundBlock) -> bool {
+        !block.orphaned
+            && block.confirmations >= self.config.maturity_confirmations
+            && !self.ledger.read().await.credited.iter().any(|c| c.hash == block.hash)
+    }
+
+    /// Whether the PPLNS window of `block` was recorded
+    pub async fn has_window(&self, block: &FoundBlock) -> bool {
+        self.ledger.read().await.windows.contains_key(&block.hash)
+    }
+
+    /// Record the PPLNS window of a found block, unless one is recorded or
+    /// the block was credited already
+    ///
+    /// `shares` is the window ending at the block. Empty windows aren't
+    /// recorded, so a later call can still find the shares.
+    pub async fn record_window(&self, block: &FoundBlock, shares: &[SimplePplnsShare]) -> Result<bool> {
+        let mut ledger = self.ledger.write().await;
+        if shares.is_empty()
+            || ledger.windows.contains_key(&block.hash)
+            || ledger.credited.iter().any(|c| c.hash == block.hash)
+        {
+            return Ok(false);
+        }
+        let mut updated = ledger.clone();
+        updated.windows.insert(block.hash.clone(), PplnsWindow::from_shares(shares));
+        self.save(&updated).await?;
+        *ledger = updated;
+        info!("Recorded PPLNS window of block {}: {} shares", block.hash, shares.len());
+        Ok(true)
+    }
+
+    /// Drop the recorded window of a block that won't be credited, e.g. an orphan
+    pub async fn discard_window(&self, block: &FoundBlock) -> Result<()> {
+        let mut ledger = self.ledger.write().await;
+        if ledger.windows.contains_key(&block.hash) {
+            let mut updated = ledger.clone();
+            updated.windows.remove(&block.hash);
+            self.save(&updated).await?;
+            *ledger = updated;
+        }
+        Ok(())
+    }
+
+    /// Split a matured block's reward over its PPLNS window and credit balances
+    ///
+    /// The window recorded when the block was found is used if there is one,
+    /// otherwise `shares`, the PPLNS window ending at the block; `fee_bps` is
+    /// deducted from every miner's share. Returns `None` if the block was
+    /// already credited or isn't mature. A window without shares, e.g. after
+    /// they were pruned, is an error and leaves the block uncredited, so its
+    /// reward isn't booked as pool fee.
+    pub async fn credit_block(
+        &self,
+        block: &FoundBlock,
+        shares: &[SimplePplnsShare],
+        fee_bps: u16,
+    ) -> Result<Option<BlockCredit>> {
+        if !self.needs_credit(block).await {
+            return Ok(None);
+        }
+        let window = self.ledger.read().await.windows.get(&block.hash).cloned()
+            .unwrap_or_else(|| PplnsWindow::from_shares(shares));
+        let credits = window.split(block.reward_sats, fee_bps);
+        if credits.is_empty() {
+            return Err(anyhow::anyhow!(
+                "No PPLNS shares found for block {} at height {}; leaving it uncredited",
+                block.hash, block.height
+            ));
+        }
+        let credited_total: u64 = credits.values().sum();
+        let credit = BlockCredit {
+            height: block.height,
+            hash: block.hash.clone(),
+            reward_sats: block.reward_sats,
+            pool_fee_sats: block.reward_sats.saturating_sub(credited_total),
+            window_shares: window.shares,
+            credits,
+            credited_at: Utc::now(),
+        };
+
+        // Changed on a copy, so a failed save leaves memory matching the file
+        let mut ledger = self.ledger.write().await;
+        let mut updated = ledger.clone();
+        for (address, amount) in &credit.credits {
+            *updated.balances.entry(address.clone()).or_default() += amount;
+        }
+        updated.credited.push(credit.clone());
+        updated.windows.remove(&credit.hash);
+        self.save(&updated).await?;
+        *ledger = updated;
+        info!(
+            "Credited block {} at height {}: {} sats to {} miner(s)",
+            credit.hash, credit.height, credited_total, credit.credits.len()
+        );
+        Ok(Some(credit))
+    }
+
+    /// Balance an address needs before it is paid
+    pub async fn payout_threshold(&self, address: &str) -> u64 {
+        self.ledger.read().await.threshold(address, &self.config)
+    }
+
+    /// Set an address's payout threshold, or go back to the minimum payout with `None`
+    ///
+    /// Thresholds can't be below the minimum payout or above
+    /// `MAX_PAYOUT_THRESHOLD_SATS`. Returns the threshold now in effect.
+    pub async fn set_payout_threshold(&self, address: &str, threshold_sats: Option<u64>) -> Result<u64> {
+        let range = self.config.min_payout_sats..=MAX_PAYOUT_THRESHOLD_SATS;
+        if threshold_sats.is_some_and(|threshold| !range.contains(&threshold)) {
+            return Err(anyhow::anyhow!(
+                "Payout threshold must be between {} and {} sats",
+                range.start(), range.end()
+            ));
+        }
+        let mut ledger = self.ledger.write().await;
+        let mut updated = ledger.clone();
+        match threshold_sats {
+            Some(threshold) => updated.thresholds.insert(address.to_string(), threshold),
+            None => updated.thresholds.remove(address),
+        };
+        self.save(&updated).await?;
+        *ledger = updated;
+        info!("Payout threshold of {} set to {:?}", address, threshold_sats);
+        Ok(ledger.threshold(address, &self.config))
+    }
+
+    /// Whether the batch interval has passed since the last batch
+    pub async fn batch_due(&self, now: DateTime<Utc>) -> bool {
+        self.ledger.read().await.last_batch_at
+            .is_none_or(|last| (now - last).num_seconds() >= self.config.batch_interval_secs as i64)
+    }
+
+    /// Smallest payment whose fee of `payment_fee_sats` stays within `max_fee_bps`
+    pub fn min_economical_payment(&self, payment_fee_sats: u64) -> Option<u64> {
+        (self.config.max_fee_bps > 0)
+            .then(|| payment_fee_sats.saturating_mul(10_000).div_ceil(self.config.max_fee_bps as u64))
+    }
+
+    /// Move every balance at or above its payout threshold into a new pending batch
+    ///
+    /// With the current fee of one payment in `payment_fee_sats`, balances too
+    /// small to pay it within `max_fee_bps` stay pending until fees drop.
+    /// Returns `None` if no balance is eligible.
+    pub async fn schedule_batch(&self, payment_fee_sats: Option<u64>) -> Result<Option<PayoutBatch>> {
+        let now = Utc::now();
+        let mut ledger = self.ledger.write().await;
+        let mut updated = ledger.clone();
+        updated.last_batch_at = Some(now);
+
+        let economical = payment_fee_sats.and_then(|fee| self.min_economical_payment(fee)).unwrap_or(0);
+        let (payments, deferred): (Vec<Payment>, Vec<Payment>) = updated.balances.iter()
+            .filter(|(address, balance)| **balance >= updated.threshold(address, &self.config) && **balance > 0)
+            .map(|(address, balance)| Payment { address: address.clone(), amount_sats: *balance })
+            .partition(|payment| payment.amount_sats >= economical);
+        if !deferred.is_empty() {
+            info!("Deferred {} payment(s) below {} sats until fees drop", deferred.len(), economical);
+        }
+        if payments.is_empty() {
+            self.save(&updated).await?;
+            *ledger = updated;
+            return Ok(None);
+        }
+        for payment in &payments {
+            updated.balances.remove(&payment.address);
+        }
+
+        let batch = PayoutBatch {
+            id: uuid::Uuid::new_v4().to_string(),
+            created_at: now,
+            status: PayoutStatus::Pending,
+            total_sats: payments.iter().map(|p| p.amount_sats).sum(),
+            payments,
+            updated_at: now,
+            txid: None,
+            psbt: None,
+            confirmations: 0,
+            note: None,
+        };
+        updated.batches.push(batch.clone());
+        self.save(&updated).await?;
+        *ledger = updated;
+        info!("Scheduled payout batch {}: {} sats to {} address(es)",
+            batch.id, batch.total_sats, batch.payments.len());
+        Ok(Some(batch))
+    }
+
+    /// Mark a batch as paid, e.g. after paying it outside the admin server
+    pub async fn mark_paid(&self, batch_id: &str) -> Result<PayoutBatch> {
+        use PayoutStatus::*;
+        self.update_batch(batch_id, &[Pending, AwaitingSignature, Sending, Sent, Failed], |_, batch| {
+            batch.status = Paid;
+        }).await
+    }
+
+    /// Cancel an unsent batch, returning its amounts to the balances
+    ///
+    /// A batch stuck in `Sending` can be cancelled once the wallet shows it
+    /// was never paid.
+    pub async fn cancel_batch(&self, batch_id: &str) -> Result<PayoutBatch> {
+        use PayoutStatus::*;
+        self.update_batch(batch_id, &[Pending, AwaitingSignature, Sending, Failed], |balances, batch| {
+            for payment in &batch.payments {
+                *balances.entry(payment.address.clone()).or_default() += payment.amount_sats;
+            }
+            batch.status = Cancelled;
+        }).await
+    }
+
+    /// Record that a pending batch is being handed to the wallet
+    ///
+    /// Persisted before the wallet call, so a crash mid-send can't lead to the
+    /// batch being sent twice.
+    pub async fn mark_sending(&self, batch_id: &str) -> Result<PayoutBatch> {
+        use PayoutStatus::*;
+        self.update_batch(batch_id, &[Pending, AwaitingSignature], |_, batch| {
+            batch.status = Sending;
+        }).await
+    }
+
+    /// Record the broadcast transaction of a batch
+    pub async fn mark_sent(&self, batch_id: &str, txid: &str) -> Result<PayoutBatch> {
+        use PayoutStatus::*;
+        self.update_batch(batch_id, &[Pending, AwaitingSignature, Sending, Failed], |_, batch| {
+            batch.status = Sent;
+            batch.txid = Some(txid.to_string());
+            batch.psbt = None;
+            batch.note = None;
+        }).await
+    }
+
+    /// Attach an unsigned PSBT to a pending batch
+    pub async fn attach_psbt(&self, batch_id: &str, psbt: &str) -> Result<PayoutBatch> {
+        use PayoutStatus::*;
+        self.update_batch(batch_id, &[Pending, Sending], |_, batch| {
+            batch.status = AwaitingSignature;
+            batch.psbt = Some(psbt.to_string());
+        }).await
+    }
+
+    /// Record a failed send
+    pub async fn mark_failed(&self, batch_id: &str, error: &str) -> Result<PayoutBatch> {
+        use PayoutStatus::*;
+        self.update_batch(batch_id, &[Pending, AwaitingSignature, Sending, Sent], |_, batch| {
+            batch.status = Failed;
+            batch.note = Some(error.to_string());
+        }).await
+    }
+
+    /// Set the wallet message of a batch without changing its status
+    pub async fn set_note(&self, batch_id: &str, note: &str) -> Result<PayoutBatch> {
+        use PayoutStatus::*;
+        self.update_batch(batch_id, &[Pending, AwaitingSignature, Sending, Sent, Failed], |_, batch| {
+            batch.note = Some(note.to_string());
+        }).await
+    }
+
+    /// Update the confirmations of a sent batch; paid once `required` is reached
+    pub async fn update_confirmations(&self, batch_id: &str, confirmations: i64, required: i64) -> Result<PayoutBatch> {
+        self.update_batch(batch_id, &[PayoutStatus::Sent], |_, batch| {
+            batch.confirmations = confirmations;
+            if confirmations >= required {
+                batch.status = PayoutStatus::Paid;
+            }
+        }).await
+    }
+
+    async fn update_batch(
+        &self,
+        batch_id: &str,
+        allowed: &[PayoutStatus],
+        update: impl FnOnce(&mut BTreeMap<String, u64>, &mut PayoutBatch),
+    ) -> Result<PayoutBatch> {
+        let mut ledger = self.ledger.write().await;
+        let mut updated = ledger.clone();
+        let batch = updated.batches.iter_mut()
+            .find(|b| b.id == batch_id)
+            .ok_or_else(|| anyhow::anyhow!("Payout batch not found: {}", batch_id))?;
+        if !allowed.contains(&batch.status) {
+            return Err(anyhow::anyhow!("Payout batch {} is {:?}", batch_id, batch.status));
+        }
+        update(&mut updated.balances, batch);
+        batch.updated_at = Utc::now();
+        let batch = batch.clone();
+        self.save(&updated).await?;
+        *ledger = updated;
+        Ok(batch)
+    }
+
+    /// Unpaid balances, largest first
+    pub async fn pending_balances(&self) -> Vec<PendingBalance> {
+        let ledger = self.ledger.read().await;
+        let mut balances: Vec<PendingBalance> = ledger.balances.iter()
+            .map(|(address, balance)| PendingBalance {
+                address: address.clone(),
+                balance_sats: *balance,
+                eligible: *balance >= ledger.threshold(address, &self.config),
+            })
+            .collect();
+        balances.sort_by(|a, b| b.balance_sats.cmp(&a.balance_sats).then_with(|| a.address.cmp(&b.address)));
+        balances
+    }
+
+    /// Payment batches, newest first
+    pub async fn batches(&self) -> Vec<PayoutBatch> {
+        self.ledger.read().await.batches.iter().rev().cloned().collect()
+    }
+
+    /// Payment batch by id
+    pub async fn batch(&self, batch_id: &str) -> Option<PayoutBatch> {
+        self.ledger.read().await.batches.iter().find(|b| b.id == batch_id).cloned()
+    }
+
+    /// Credited blocks, newest first
+    pub async fn credited_blocks(&self) -> Vec<BlockCredit> {
+        self.ledger.read().await.credited.iter().rev().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn share(address: &str, difficulty: u64) -> SimplePplnsShare {
+        SimplePplnsShare {
+            btcaddress: Some(address.to_string()),
+            workername: Some("rig".to_string()),
+            user_id: 1,
+            difficulty,
+            n_time: 1_700_000_000,
+            job_id: "job".to_string(),
+            extranonce2: "00000001".to_string(),
+            nonce: "00000001".to_string(),
+        }
+    }
+
+    fn block(height: u64, confirmations: i64) -> FoundBlock {
+        FoundBlock {
+            height,
+            hash: format!("hash{}", height),
+            timestamp: Utc::now(),
+            finder_address: None,
+            reward_sats: 1_000_000,
+            confirmations,
+            orphaned: false,
+            state: Default::default(),
+            state_changes: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_credits_matured_blocks_and_batches_above_threshold() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("payouts.json");
+        let config = PayoutConfig { min_payout_sats: 200_000, ..PayoutConfig::default() };
+        let engine = PayoutEngine::new(path.clone(), config.clone());
+        let shares = vec![share("bc1qbig", 900), share("bc1qsmall", 100)];
+
+        // Immature blocks are not credited
+        assert!(engine.credit_block(&block(1, 99), &shares, 100).await.unwrap().is_none());
+        // Nor are blocks whose window has no shares left
+        assert!(engine.credit_block(&block(1, 100), &[], 100).await.is_err());
+        assert!(engine.needs_credit(&block(1, 100)).await);
+
+        let credit = engine.credit_block(&block(1, 100), &shares, 100).await.unwrap().unwrap();
+        assert_eq!(credit.credits["bc1qbig"], 891_000);
+        assert_eq!(credit.credits["bc1qsmall"], 99_000);
+        assert_eq!(credit.pool_fee_sats, 10_000);
+        // Each block is credited once
+        assert!(engine.credit_block(&block(1, 120), &shares, 100).await.unwrap().is_none());
+        assert!(!engine.record_window(&block(1, 120), &shares).await.unwrap());
+
+        assert!(engine.batch_due(Utc::now()).await);
+        let batch = engine.schedule_batch(None).await.unwrap().unwrap();
+        assert_eq!(batch.payments.len(), 1);
+        assert_eq!(batch.total_sats, 891_000);
+        assert!(!engine.batch_due(Utc::now()).await);
+
+        let pending = engine.pending_balances().await;
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].balance_sats, 99_000);
+        assert!(!pending[0].eligible);
+
+        // Cancelling returns the amounts, also after a send that never completed;
+        // paid batches can't change again
+        engine.mark_sending(&batch.id).await.unwrap();
+        engine.cancel_batch(&batch.id).await.unwrap();
+        assert_eq!(engine.pending_balances().await[0].balance_sats, 891_000);
+        assert!(engine.mark_paid(&batch.id).await.is_err());
+
+        // Miners can raise their threshold, but not below the minimum payout
+        assert!(engine.set_payout_threshold("bc1qbig", Some(100_000)).await.is_err());
+        assert_eq!(engine.set_payout_threshold("bc1qbig", Some(1_000_000)).await.unwrap(), 1_000_000);
+        assert!(!engine.pending_balances().await[0].eligible);
+
+        let reloaded = PayoutEngine::new(path.clone(), config.clone());
+        reloaded.load().await.unwrap();
+        assert_eq!(reloaded.credited_blocks().await.len(), 1);
+        assert_eq!(reloaded.batches().await[0].status, PayoutStatus::Cancelled);
+        assert_eq!(reloaded.pending_balances().await.len(), 2);
+        assert_eq!(reloaded.payout_threshold("bc1qbig").await, 1_000_000);
+        assert_eq!(reloaded.set_payout_threshold("bc1qbig", None).await.unwrap(), 200_000);
+
+        // While fees would take more than 1% of a payment, it waits
+        let capped = PayoutEngine::new(path, PayoutConfig { max_fee_bps: 100, ..config });
+        capped.load().await.unwrap();
+        assert_eq!(capped.min_economical_payment(10_000), Some(1_000_000));
+        assert!(capped.schedule_batch(Some(10_000)).await.unwrap().is_none());
+        assert_eq!(capped.pending_balances().await.len(), 2);
+        let batch = capped.schedule_batch(Some(1_000)).await.unwrap().unwrap();
+        assert_eq!(batch.total_sats, 891_000);
+
+        // A window recorded when the block was found outlives pruned shares
+        let engine = PayoutEngine::new(dir.path().join("windows.json"), PayoutConfig::default());
+        assert!(engine.record_window(&block(2, 0), &shares).await.unwrap());
+        assert!(!engine.record_window(&block(2, 1), &shares[..1]).await.unwrap());
+        let credit = engine.credit_block(&block(2, 100), &[], 100).await.unwrap().unwrap();
+        assert_eq!(credit.credits["bc1qsmall"], 99_000);
+        assert_eq!(credit.window_shares, 2);
+        assert!(!engine.has_window(&block(2, 100)).await);
+    }
+
+    #[tokio::test]
+    async fn test_failed_save_leaves_ledger_unchanged() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("payouts.json");
+        let config = PayoutConfig { min_payout_sats: 200_000, ..PayoutConfig::default() };
+        let engine = PayoutEngine::new(path.clone(), config);
+        let shares = vec![share("bc1qbig", 900), share("bc1qsmall", 100)];
+        engine.credit_block(&block(1, 100), &shares, 100).await.unwrap().unwrap();
+        let batch = engine.schedule_batch(None).await.unwrap().unwrap();
+
+        // A directory in the way of the temporary file makes every save fail
+        std::fs::create_dir(path.with_extension("json.tmp")).unwrap();
+
+        assert!(engine.set_payout_threshold("bc1qsmall", Some(1_000_000)).await.is_err());
+        assert_eq!(engine.payout_threshold("bc1qsmall").await, 200_000);
+
+        assert!(engine.cancel_batch(&batch.id).await.is_err());
+        assert_eq!(engine.batch(&batch.id).await.unwrap().status, PayoutStatus::Pending);
+        assert_eq!(engine.pending_balances().await.len(), 1);
+
+        let last_batch_at = engine.ledger.read().await.last_batch_at;
+        assert!(engine.schedule_batch(None).await.is_err());
+        assert_eq!(engine.ledger.read().await.last_batch_at, last_batch_at);
+        assert_eq!(engine.batches().await.len(), 1);
+    }
+}
@@ -5,7 +5,7 @@ use anyhow::Result;
 use chrono::{DateTime, Utc};
 use p2poolv2_lib::accounting::simple_pplns::SimplePplnsShare;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 /// PPLNS payout calculation result
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -51,6 +51,52 @@ pub struct PplnsValidationResult {
     pub validated_at: DateTime<Utc>,
 }
 
+/// Projected payouts if a block were found now
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PayoutPreview {
+    /// Block reward the projection assumes (satoshi)
+    pub block_reward_satoshis: u64,
+    /// Fee and donation deducted from each payout (basis points)
+    pub pool_fee_bps: u16,
+    /// Shares in the PPLNS window
+    pub window_shares: u64,
+    /// Total difficulty of the PPLNS window
+    pub window_difficulty: u64,
+    /// Payouts per address, largest first
+    pub payouts: Vec<PayoutCalculation>,
+    /// Sum of final payouts (satoshi)
+    pub total_payout_satoshis: u64,
+}
+
+/// Outcome of a single validation check
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ValidationCheck {
+    pub name: String,
+    pub passed: bool,
+    /// "error" checks make the report invalid, "warning" checks do not
+    pub severity: String,
+    pub message: String,
+}
+
+/// Validation of a live PPLNS share window
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PplnsValidationReport {
+    /// False if any error-severity check failed
+    pub valid: bool,
+    pub window_shares: u64,
+    pub window_difficulty: u64,
+    /// Reward minus fees that payouts should add up to (satoshi)
+    pub expected_payout_satoshis: u64,
+    pub total_payout_satoshis: u64,
+    pub checks: Vec<ValidationCheck>,
+    /// Names of failed checks, errors first
+    pub failures: Vec<String>,
+    pub validated_at: DateTime<Utc>,
+}
+
+/// Shares timestamped this far ahead of the validator's clock are rejected
+const MAX_FUTURE_SHARE_SECS: u64 = 2 * 3600;
+
 /// PPLNS payment simulator for testing
 pub struct PplnsSimulator {
     /// Block reward in satoshis (for mainnet, this is variable)
@@ -204,6 +250,58 @@ impl PplnsSimulator {
         }
     }
 
+    /// Project the payout of every address in the window in a single pass
+    ///
+    /// Equivalent to `calculate_payout` for each address, without rescanning
+    /// the window per miner.
+    pub fn preview(&self, shares: &[SimplePplnsShare]) -> PayoutPreview {
+        let window_difficulty: u64 = shares.iter().map(|s| s.difficulty).sum();
+
+        let mut by_address: HashMap<&str, (u64, u64, Option<&str>)> = HashMap::new();
+        for share in shares {
+            if let Some(address) = share.btcaddress.as_deref() {
+                let entry = by_address.entry(address).or_insert((0, 0, share.workername.as_deref()));
+                entry.0 += 1;
+                entry.1 += share.difficulty;
+            }
+        }
+
+        let mut payouts: Vec<PayoutCalculation> = if window_difficulty == 0 {
+            Vec::new()
+        } else {
+            by_address.into_iter()
+                .map(|(address, (share_count, total_difficulty, worker))| {
+                    let payout = (self.block_reward_satoshis as u128) * (total_difficulty as u128)
+                        / (window_difficulty as u128);
+                    let fee = payout * (self.pool_fee_bps as u128) / 10000u128;
+                    PayoutCalculation {
+                        address: address.to_string(),
+                        worker: worker.unwrap_or("unknown").to_string(),
+                        share_count,
+                        total_difficulty,
+                        payout_satoshis: payout.min(u64::MAX as u128) as u64,
+                        pplns_window_size: shares.len() as u64,
+                        block_reward_satoshis: self.block_reward_satoshis,
+                        pool_fee_satoshis: fee.min(u64::MAX as u128) as u64,
+                        final_payout_satoshis: payout.saturating_sub(fee).min(u64::MAX as u128) as u64,
+                    }
+                })
+                .collect()
+        };
+        payouts.sort_by(|a, b| {
+            b.final_payout_satoshis.cmp(&a.final_payout_satoshis).then_with(|| a.address.cmp(&b.address))
+        });
+
+        PayoutPreview {
+            block_reward_satoshis: self.block_reward_satoshis,
+            pool_fee_bps: self.pool_fee_bps,
+            window_shares: shares.len() as u64,
+            window_difficulty,
+            total_payout_satoshis: payouts.iter().map(|p| p.final_payout_satoshis).sum(),
+            payouts,
+        }
+    }
+
     /// Validate share difficulty bounds
     pub fn validate_difficulty_bounds(&self, shares: &[SimplePplnsShare]) -> Result<(), String> {
         if shares.is_empty() {
@@ -254,6 +352,203 @@ impl PplnsSimulator {
     }
 }
 
+impl PplnsSimulator {
+    /// Check a share window against the PPLNS TTL, difficulty rules and payout totals
+    pub fn validate_window(&self, shares: &[SimplePplnsShare], now: u64) -> PplnsValidationReport {
+        let mut checks = Vec::new();
+        let mut check = |name: &str, severity: &str, result: Result<String, String>| {
+            let (passed, message) = match result {
+                Ok(message) => (true, message),
+                Err(message) => (false, message),
+            };
+            checks.push(ValidationCheck {
+                name: name.to_string(),
+                passed,
+                severity: severity.to_string(),
+                message,
+            });
+        };
+
+        check("window_span", "error", self.validate_window_size(shares, self.pplns_window_days)
+            .map(|_| format!("Window spans at most {} days", self.pplns_window_days * 2)));
+
+        let oldest_allowed = now.saturating_sub(self.pplns_window_days * 86400);
+        let expired = shares.iter().filter(|s| s.n_time < oldest_allowed).count();
+        let future = shares.iter().filter(|s| s.n_time > now + MAX_FUTURE_SHARE_SECS).count();
+        check("window_ttl", "error", if expired == 0 && future == 0 {
+            Ok(format!("All shares are within the {}-day TTL", self.pplns_window_days))
+        } else {
+            Err(format!("{} share(s) older than the TTL, {} share(s) in the future", expired, future))
+        });
+
+        let zero_difficulty = shares.iter().filter(|s| s.difficulty == 0).count();
+        check("share_difficulty", "error", if zero_difficulty == 0 {
+            Ok("No zero-difficulty shares".to_string())
+        } else {
+            Err(format!("{} share(s) with zero difficulty", zero_difficulty))
+        });
+
+        if !shares.is_empty() && zero_difficulty == 0 {
+            check("difficulty_range", "warning", self.validate_difficulty_bounds(shares)
+                .map(|_| "Difficulty range is within 1000x".to_string()));
+        }
+
+        let unattributed = shares.iter().filter(|s| s.btcaddress.is_none()).count();
+        check("share_addresses", "warning", if unattributed == 0 {
+            Ok("All shares have a payout address".to_string())
+        } else {
+            Err(format!("{} share(s) without a payout address are not paid", unattributed))
+        });
+
+        let preview = self.preview(shares);
+        let fee = (self.block_reward_satoshis as u128 * self.pool_fee_bps as u128 / 10000) as u64;
+        let expected = self.block_reward_satoshis.saturating_sub(fee);
+        let unattributed_difficulty: u64 = shares.iter()
+            .filter(|s| s.btcaddress.is_none())
+            .map(|s| s.difficulty)
+            .sum();
+        let unattributed_share = if preview.window_difficulty == 0 {
+            0
+        } else {
+            (expected as u128 * unattributed_difficulty as u128 / preview.window_difficulty as u128) as u64
+        };
+        // Payouts and fees are each rounded down per address, so the sum may
+        // be off by up to 1 sat per payout in either direction
+        let tolerance = preview.payouts.len() as u64 + 1;
+        let target = expected.saturating_sub(unattributed_share);
+        check("payout_sum", "error", if shares.is_empty() {
+            Ok("No shares to pay".to_string())
+        } else if preview.total_payout_satoshis > expected + tolerance {
+            Err(format!("Payouts ({}) exceed reward minus fees ({})", preview.total_payout_satoshis, expected))
+        } else if target.abs_diff(preview.total_payout_satoshis) > tolerance {
+            Err(format!("Payouts ({}) differ from reward minus fees ({}) by more than {} sat",
+                preview.total_payout_satoshis, target, tolerance))
+        } else {
+            Ok(format!("Payouts ({}) match reward minus fees ({})", preview.total_payout_satoshis, target))
+        });
+
+        let mut failed: Vec<&ValidationCheck> = checks.iter().filter(|c| !c.passed).collect();
+        failed.sort_by_key(|c| c.severity != "error");
+
+        PplnsValidationReport {
+            valid: !checks.iter().any(|c| !c.passed && c.severity == "error"),
+            window_shares: preview.window_shares,
+            window_difficulty: preview.window_difficulty,
+            expected_payout_satoshis: expected,
+            total_payout_satoshis: preview.total_payout_satoshis,
+            failures: failed.iter().map(|c| c.name.clone()).collect(),
+            checks,
+            validated_at: Utc::now(),
+        }
+    }
+}
+
+/// Payout parameters a replay recomputes blocks with
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReplayParams {
+    pub pplns_ttl_days: u64,
+    /// Pool fee (basis points)
+    pub fee_bps: u16,
+    /// Donation (basis points)
+    pub donation_bps: u16,
+}
+
+/// A credited block and what each address actually received for it
+#[derive(Clone, Debug)]
+pub struct ReplayBlock {
+    pub height: u64,
+    /// When the block was found (Unix seconds); its PPLNS window ends here
+    pub found_at: u64,
+    pub reward_sats: u64,
+    pub actual_credits: BTreeMap<String, u64>,
+}
+
+/// One replayed block
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlockReplay {
+    pub height: u64,
+    pub reward_sats: u64,
+    /// Shares in the replayed window
+    pub window_shares: u64,
+    pub actual_total_sats: u64,
+    pub replayed_total_sats: u64,
+}
+
+/// How an address's payouts change under the replayed parameters
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AddressDelta {
+    pub address: String,
+    pub actual_sats: u64,
+    pub replayed_sats: u64,
+    /// `replayed_sats - actual_sats`
+    pub delta_sats: i64,
+}
+
+/// Credited blocks recomputed with alternative parameters
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReplayReport {
+    pub params: ReplayParams,
+    pub blocks: Vec<BlockReplay>,
+    /// Addresses paid either way, largest change first
+    pub deltas: Vec<AddressDelta>,
+    pub actual_total_sats: u64,
+    pub replayed_total_sats: u64,
+}
+
+impl PplnsSimulator {
+    /// Recompute credited blocks with `params` and compare with what was paid
+    ///
+    /// `shares_between(start, end)` returns the stored shares in that time
+    /// range (Unix seconds), so each block's window is rebuilt with the
+    /// replayed TTL.
+    pub fn replay<F>(blocks: &[ReplayBlock], params: &ReplayParams, shares_between: F) -> ReplayReport
+    where
+        F: Fn(u64, u64) -> Vec<SimplePplnsShare>,
+    {
+        let fee_bps = params.fee_bps.saturating_add(params.donation_bps);
+        let mut totals: BTreeMap<String, (u64, u64)> = BTreeMap::new();
+        let mut replays = Vec::with_capacity(blocks.len());
+
+        for block in blocks {
+            let window_start = block.found_at.saturating_sub(params.pplns_ttl_days * 24 * 3600);
+            let shares = shares_between(window_start, block.found_at);
+            let preview = Self::new(block.reward_sats, fee_bps, params.pplns_ttl_days).preview(&shares);
+
+            for (address, amount) in &block.actual_credits {
+                totals.entry(address.clone()).or_default().0 += amount;
+            }
+            for payout in preview.payouts.iter().filter(|p| p.final_payout_satoshis > 0) {
+                totals.entry(payout.address.clone()).or_default().1 += payout.final_payout_satoshis;
+            }
+            replays.push(BlockReplay {
+                height: block.height,
+                reward_sats: block.reward_sats,
+                window_shares: preview.window_shares,
+                actual_total_sats: block.actual_credits.values().sum(),
+                replayed_total_sats: preview.total_payout_satoshis,
+            });
+        }
+
+        let mut deltas: Vec<AddressDelta> = totals.into_iter()
+            .map(|(address, (actual_sats, replayed_sats))| AddressDelta {
+                address,
+                actual_sats,
+                replayed_sats,
+                delta_sats: replayed_sats as i64 - actual_sats as i64,
+            })
+            .collect();
+        deltas.sort_by(|a, b| b.delta_sats.abs().cmp(&a.delta_sats.abs()).then_with(|| a.address.cmp(&b.address)));
+
+        ReplayReport {
+            params: params.clone(),
+            actual_total_sats: replays.iter().map(|b| b.actual_total_sats).sum(),
+            replayed_total_sats: replays.iter().map(|b| b.replayed_total_sats).sum(),
+            blocks: replays,
+            deltas,
+        }
+    }
+}
+
 /// PPLNS validation test scenarios
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ValidationScenario {
@@ -355,6 +650,51 @@ mod tests {
         assert_eq!(test1_payout.final_payout_satoshis, 59400000);
     }
 
+    #[test]
+    fn test_preview_matches_per_miner_calculation() {
+        let simulator = PplnsSimulator::new(312_500_000, 250, 7);
+        let mut shares = vec![
+            create_test_share("bc1qtest1", 1000, 1000),
+            create_test_share("bc1qtest2", 1500, 3000),
+            create_test_share("bc1qtest1", 2000, 2000),
+        ];
+        shares.push(SimplePplnsShare { btcaddress: None, ..create_test_share("", 500, 4000) });
+
+        let preview = simulator.preview(&shares);
+        assert_eq!(preview.window_shares, 4);
+        assert_eq!(preview.window_difficulty, 5000);
+        assert_eq!(preview.payouts.len(), 2);
+        assert_eq!(preview.payouts[0].address, "bc1qtest1");
+        for payout in &preview.payouts {
+            let expected = simulator.calculate_payout(&shares, &payout.address).unwrap();
+            assert_eq!(payout.final_payout_satoshis, expected.final_payout_satoshis);
+            assert_eq!(payout.pool_fee_satoshis, expected.pool_fee_satoshis);
+        }
+        assert!(simulator.preview(&[]).payouts.is_empty());
+    }
+
+    #[test]
+    fn test_validate_window_report() {
+        let simulator = PplnsSimulator::new(100_000_000, 100, 7);
+        let now = 100 * 86400;
+        let shares = vec![
+            create_test_share("bc1qtest1", 1000, now - 3600),
+            create_test_share("bc1qtest2", 3000, now - 60),
+            create_test_share("bc1qtest3", 333, now),
+        ];
+        let report = simulator.validate_window(&shares, now);
+        assert!(report.valid, "{:?}", report.checks);
+        assert!(report.failures.is_empty());
+        assert_eq!(report.expected_payout_satoshis, 99_000_000);
+
+        let mut bad = shares.clone();
+        bad.push(create_test_share("bc1qtest1", 0, now - 10 * 86400));
+        bad.push(SimplePplnsShare { btcaddress: None, ..create_test_share("", 10, now) });
+        let report = simulator.validate_window(&bad, now);
+        assert!(!report.valid);
+        assert_eq!(report.failures, vec!["window_ttl", "share_difficulty", "share_addresses"]);
+    }
+
     #[test]
     fn test_difficulty_validation() {
         let simulator = PplnsSimulator::default();
@@ -381,6 +721,32 @@ mod tests {
         assert!(simulator.validate_difficulty_bounds(&wide_range_shares).is_err());
     }
 
+    #[test]
+    fn test_replay_with_shorter_window() {
+        let found_at = 100 * 86400;
+        let shares = vec![
+            create_test_share("bc1qold", 100, found_at - 5 * 86400),
+            create_test_share("bc1qnew", 100, found_at - 86400),
+        ];
+        let block = ReplayBlock {
+            height: 1,
+            found_at,
+            reward_sats: 1_000_000,
+            actual_credits: [("bc1qold".to_string(), 495_000), ("bc1qnew".to_string(), 495_000)].into(),
+        };
+        let params = ReplayParams { pplns_ttl_days: 2, fee_bps: 100, donation_bps: 0 };
+
+        let report = PplnsSimulator::replay(&[block], &params, |start, end| {
+            shares.iter().filter(|s| s.n_time >= start && s.n_time <= end).cloned().collect()
+        });
+        assert_eq!(report.blocks[0].window_shares, 1);
+        assert_eq!(report.actual_total_sats, 990_000);
+        assert_eq!(report.replayed_total_sats, 990_000);
+        assert_eq!(report.deltas.len(), 2);
+        assert_eq!((report.deltas[0].address.as_str(), report.deltas[0].delta_sats), ("bc1qnew", 495_000));
+        assert_eq!((report.deltas[1].address.as_str(), report.deltas[1].delta_sats), ("bc1qold", -495_000));
+    }
+
     #[test]
     fn test_window_validation() {
         let simulator = PplnsSimulator::default();
@@ -0,0 +1,262 @@
+// Query Cache for DMPool
+// Stale-while-revalidate cache for expensive store reads shared between admin endpoints
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Seconds a cached result is served without recomputing it
+pub const DEFAULT_TTL_SECS: u64 = 10;
+/// Seconds past the TTL a result may still be served while it is recomputed
+pub const DEFAULT_MAX_STALE_SECS: u64 = 60;
+
+/// How long cached results stay fresh, and how long they may be served stale
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CacheSettings {
+    pub ttl: Duration,
+    pub max_stale: Duration,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(DEFAULT_TTL_SECS),
+            max_stale: Duration::from_secs(DEFAULT_MAX_STALE_SECS),
+        }
+    }
+}
+
+impl CacheSettings {
+    /// Read `QUERY_CACHE_TTL_SECS` and `QUERY_CACHE_MAX_STALE_SECS`; a TTL of
+    /// 0 disables caching
+    pub fn from_env() -> Self {
+        let secs = |name: &str, default: u64| {
+            Duration::from_secs(std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default))
+        };
+        Self {
+            ttl: secs("QUERY_CACHE_TTL_SECS", DEFAULT_TTL_SECS),
+            max_stale: secs("QUERY_CACHE_MAX_STALE_SECS", DEFAULT_MAX_STALE_SECS),
+        }
+    }
+}
+
+/// Lookups served by a cache since it was created
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CacheStats {
+    pub hits: u64,
+    /// Served stale while a background refresh ran
+    pub stale_hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+struct Entry<V> {
+    value: Arc<V>,
+    computed_at: Instant,
+    /// Cache generation when the computation started
+    generation: u64,
+}
+
+/// Results of expensive queries by key
+///
+/// A fresh result is served as is. Once its TTL has passed, or `invalidate`
+/// was called, it is still served for up to `max_stale` while one background
+/// task recomputes it; older results are recomputed before answering, and
+/// evicted when another result is stored.
+pub struct QueryCache<K, V> {
+    settings: CacheSettings,
+    entries: Mutex<HashMap<K, Entry<V>>>,
+    /// Keys being recomputed in the background
+    refreshing: Mutex<HashSet<K>>,
+    generation: AtomicU64,
+    hits: AtomicU64,
+    stale_hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<K, V> QueryCache<K, V>
+where
+    K: Eq + Hash + Clone + Send + 'static,
+    V: Send + Sync + 'static,
+{
+    pub fn new(settings: CacheSettings) -> Self {
+        Self {
+            settings,
+            entries: Mutex::new(HashMap::new()),
+            refreshing: Mutex::new(HashSet::new()),
+            generation: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            stale_hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn settings(&self) -> CacheSettings {
+        self.settings
+    }
+
+    /// Result for `key`, running `compute` if there is none fresh enough
+    pub async fn get_or_compute<F, Fut>(self: &Arc<Self>, key: K, compute: F) -> Arc<V>
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = V> + Send + 'static,
+    {
+        if self.settings.ttl.is_zero() {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return Arc::new(compute().await);
+        }
+
+        let generation = self.generation.load(Ordering::Acquire);
+        let cached = self.entries().get(&key).map(|entry| {
+            (entry.value.clone(), entry.computed_at.elapsed(), entry.generation)
+        });
+        if let Some((value, age, computed_generation)) = cached {
+            if age < self.settings.ttl && computed_generation == generation {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return value;
+            }
+            if age < self.settings.ttl + self.settings.max_stale {
+                self.stale_hits.fetch_add(1, Ordering::Relaxed);
+                self.refresh(key, compute);
+                return value;
+            }
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let value = Arc::new(compute().await);
+        self.insert(key, value.clone(), generation);
+        value
+    }
+
+    /// Mark every result stale, e.g. when new shares arrive; they are still
+    /// served while recomputed
+    pub fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Drop every result, so the next lookups recompute before answering
+    pub fn clear(&self) {
+        self.entries().clear();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            stale_hits: self.stale_hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries().len(),
+        }
+    }
+
+    /// Recompute `key` in the background unless a refresh is already running
+    fn refresh<F, Fut>(self: &Arc<Self>, key: K, compute: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = V> + Send + 'static,
+    {
+        if !self.refreshing().insert(key.clone()) {
+            return;
+        }
+        let guard = RefreshGuard { cache: self.clone(), key };
+        tokio::spawn(async move {
+            let generation = guard.cache.generation.load(Ordering::Acquire);
+            let value = Arc::new(compute().await);
+            guard.cache.insert(guard.key.clone(), value, generation);
+        });
+    }
+
+    fn insert(&self, key: K, value: Arc<V>, generation: u64) {
+        let mut entries = self.entries();
+        // Results too old to be served are dropped, so keys that aren't asked
+        // for again don't keep theirs
+        let expiry = self.settings.ttl + self.settings.max_stale;
+        entries.retain(|_, entry| entry.computed_at.elapsed() < expiry);
+        // A slower computation doesn't replace a result that started later
+        if entries.get(&key).is_some_and(|entry| entry.generation > generation) {
+            return;
+        }
+        entries.insert(key, Entry { value, computed_at: Instant::now(), generation });
+    }
+
+    // Poisoning is ignored: the maps are never left half-updated
+    fn entries(&self) -> MutexGuard<'_, HashMap<K, Entry<V>>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn refreshing(&self) -> MutexGuard<'_, HashSet<K>> {
+        self.refreshing.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Clears the refresh mark of a key when its task ends, also by panicking,
+/// so the key is refreshed again later
+struct RefreshGuard<K: Eq + Hash, V> {
+    cache: Arc<QueryCache<K, V>>,
+    key: K,
+}
+
+impl<K: Eq + Hash, V> Drop for RefreshGuard<K, V> {
+    fn drop(&mut self) {
+        self.cache.refreshing.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[tokio::test]
+    async fn test_stale_while_revalidate() {
+        let settings = CacheSettings { ttl: Duration::from_secs(10), max_stale: Duration::from_secs(30) };
+        let cache = Arc::new(QueryCache::<&'static str, usize>::new(settings));
+        let runs = Arc::new(AtomicUsize::new(0));
+        let lookup = |cache: &Arc<QueryCache<&'static str, usize>>| {
+            let runs = runs.clone();
+            let cache = cache.clone();
+            async move {
+                cache.get_or_compute("shares", move || async move { runs.fetch_add(1, Ordering::SeqCst) + 1 }).await
+            }
+        };
+
+        assert_eq!(*lookup(&cache).await, 1);
+        assert_eq!(*lookup(&cache).await, 1);
+
+        // Invalidated: the old result is served while one refresh runs
+        cache.invalidate();
+        assert_eq!(*lookup(&cache).await, 1);
+        assert_eq!(*lookup(&cache).await, 1);
+        tokio::task::yield_now().await;
+        assert_eq!(*lookup(&cache).await, 2);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+
+        assert_eq!(cache.stats(), CacheStats { hits: 2, stale_hits: 2, misses: 1, entries: 1 });
+
+        // Past the stale limit the result is recomputed before answering
+        let short = CacheSettings { ttl: Duration::from_millis(10), max_stale: Duration::from_millis(10) };
+        let expiring = Arc::new(QueryCache::<&'static str, usize>::new(short));
+        assert_eq!(*lookup(&expiring).await, 3);
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(*lookup(&expiring).await, 4);
+
+        // A refresh that panics doesn't block later ones
+        expiring.invalidate();
+        let panicking = expiring.get_or_compute("shares", || async { panic!("store read failed") }).await;
+        assert_eq!(*panicking, 4);
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        assert!(expiring.refreshing().is_empty());
+
+        // Results past the stale limit are evicted by later inserts
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        expiring.get_or_compute("workers", || async { 0 }).await;
+        assert_eq!(expiring.stats().entries, 1);
+
+        let uncached = Arc::new(QueryCache::<&'static str, usize>::new(CacheSettings { ttl: Duration::ZERO, ..settings }));
+        assert_eq!(*lookup(&uncached).await, 5);
+        assert_eq!(*lookup(&uncached).await, 6);
+    }
+}
@@ -2,17 +2,22 @@
 // Prevents brute force attacks and API abuse
 
 use anyhow::{anyhow, Result};
+use async_trait::async_trait;
 use axum::{
-    extract::{Request, State},
-    http::{StatusCode, HeaderMap},
+    extract::{ConnectInfo, Request, State},
+    http::{StatusCode, HeaderMap, HeaderValue},
     middleware::Next,
     response::{IntoResponse, Response},
 };
-use serde::Serialize;
-use std::collections::HashSet;
-use std::net::{IpAddr, Ipv4Addr};
+use crate::auth::Claims;
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::num::NonZeroU32;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 use tracing::{warn, debug, error, info};
 
@@ -23,11 +28,13 @@ pub struct RateLimitConfig {
     pub api_rpm: NonZeroU32,
     /// Requests per minute for login endpoint (stricter)
     pub login_rpm: NonZeroU32,
+    /// Event batches per minute the stratum server may push
+    pub ingest_rpm: NonZeroU32,
     /// Burst size
     pub burst: NonZeroU32,
-    /// Trusted proxy IPs that can set X-Forwarded-For
+    /// Networks of proxies whose forwarding headers are believed
     /// If empty, proxy headers are ignored (safer)
-    pub trusted_proxies: HashSet<IpAddr>,
+    pub trusted_proxies: Vec<IpNet>,
     /// Whether to require IP validation (fail if IP cannot be determined)
     pub require_valid_ip: bool,
 }
@@ -39,10 +46,12 @@ impl Default for RateLimitConfig {
             api_rpm: NonZeroU32::new(60).unwrap(),
             // 10 requests per minute for login (anti-brute-force)
             login_rpm: NonZeroU32::new(10).unwrap(),
+            // 600 event batches per minute, as a busy pool reports often
+            ingest_rpm: NonZeroU32::new(600).unwrap(),
             // Allow burst of 10 requests
             burst: NonZeroU32::new(10).unwrap(),
             // No trusted proxies by default (safer)
-            trusted_proxies: HashSet::new(),
+            trusted_proxies: Vec::new(),
             // Require valid IP in production
             require_valid_ip: std::env::var("DMP_ENV").unwrap_or("development".to_string()) == "production",
         }
@@ -50,35 +59,255 @@ impl Default for RateLimitConfig {
 }
 
 impl RateLimitConfig {
+    /// Defaults, with trusted proxies from `TRUSTED_PROXIES`: addresses and
+    /// CIDRs separated by commas, e.g. "127.0.0.1,10.0.0.0/8", and the event
+    /// ingestion limit from `INGEST_RATE_LIMIT_RPM`
+    pub fn from_env() -> Result<Self> {
+        let mut config = Self::default();
+        if let Ok(rpm) = std::env::var("INGEST_RATE_LIMIT_RPM") {
+            config.ingest_rpm = rpm.parse()
+                .map_err(|_| anyhow!("INGEST_RATE_LIMIT_RPM must be a positive number: {}", rpm))?;
+        }
+        if let Ok(proxies) = std::env::var("TRUSTED_PROXIES") {
+            for proxy in proxies.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+                config.add_trusted_proxy_cidr(proxy)?;
+            }
+        }
+        Ok(config)
+    }
+
     /// Add a trusted proxy IP
     pub fn add_trusted_proxy(&mut self, ip: IpAddr) {
-        self.trusted_proxies.insert(ip);
+        self.trusted_proxies.push(IpNet::from(ip));
     }
 
-    /// Add trusted proxy from CIDR (e.g., "10.0.0.0/8")
+    /// Add trusted proxy from CIDR (e.g., "10.0.0.0/8") or a single address
     pub fn add_trusted_proxy_cidr(&mut self, cidr: &str) -> Result<()> {
-        // For simplicity, just support single IP for now
-        // Full CIDR support would require additional dependencies
-        let ip = cidr.parse::<IpAddr>()
-            .map_err(|_| anyhow!("Invalid CIDR format: {}", cidr))?;
-        self.trusted_proxies.insert(ip);
+        let net = match cidr.parse::<IpNet>() {
+            Ok(net) => net.trunc(),
+            Err(_) => IpNet::from(cidr.parse::<IpAddr>()
+                .map_err(|_| anyhow!("Invalid CIDR format: {}", cidr))?),
+        };
+        self.trusted_proxies.push(net);
         Ok(())
     }
 
+    /// Whether forwarding headers from `ip` are believed
+    pub fn is_trusted_proxy(&self, ip: &IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.trusted_proxies.iter().any(|net| net.contains(&ip))
+    }
+
     /// Set whether to require valid IP
     pub fn set_require_valid_ip(&mut self, require: bool) {
         self.require_valid_ip = require;
     }
 }
 
+/// Window that the per-minute limits apply to
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Extra limit for matching routes, optionally only for a user or role
+///
+/// Rules are enforced in addition to the per-IP limits. Requests are counted
+/// per authenticated user, or per IP for anonymous requests.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RateLimitRule {
+    pub id: String,
+    /// Path prefix, matched by segment; `*` matches any single segment
+    /// (e.g. `/api/backup/*/restore`)
+    pub route_prefix: String,
+    /// Only apply to this username
+    #[serde(default)]
+    pub user: Option<String>,
+    /// Only apply to users with this role
+    #[serde(default)]
+    pub role: Option<String>,
+    /// Requests allowed per window
+    pub limit: u32,
+    pub window_secs: u64,
+}
+
+impl RateLimitRule {
+    /// Create a rule for every caller of routes under `route_prefix`
+    pub fn new(route_prefix: impl Into<String>, limit: u32, window_secs: u64) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            route_prefix: route_prefix.into(),
+            user: None,
+            role: None,
+            limit,
+            window_secs,
+        }
+    }
+
+    /// Only apply to users with `role`
+    pub fn with_role(mut self, role: impl Into<String>) -> Self {
+        self.role = Some(role.into());
+        self
+    }
+
+    /// Only apply to `user`
+    pub fn with_user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    /// Check the rule is usable
+    pub fn validate(&self) -> Result<()> {
+        if !self.route_prefix.starts_with('/') {
+            return Err(anyhow!("Route prefix must start with '/': {}", self.route_prefix));
+        }
+        if self.limit == 0 {
+            return Err(anyhow!("Limit must be at least 1"));
+        }
+        if self.window_secs == 0 {
+            return Err(anyhow!("Window must be at least 1 second"));
+        }
+        Ok(())
+    }
+
+    /// Whether the rule covers a request to `path` by `claims`
+    pub fn matches(&self, path: &str, claims: Option<&Claims>) -> bool {
+        if self.user.as_ref().is_some_and(|user| claims.is_none_or(|c| &c.name != user)) {
+            return false;
+        }
+        if self.role.as_ref().is_some_and(|role| claims.is_none_or(|c| &c.role != role)) {
+            return false;
+        }
+        let mut segments = path.trim_end_matches('/').split('/');
+        self.route_prefix.trim_end_matches('/').split('/')
+            .all(|pattern| segments.next().is_some_and(|s| pattern == "*" || pattern == s))
+    }
+
+    /// Rules applied when none have been configured
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            // Restores replace the live database
+            Self::new("/api/backup/*/restore", 2, 3600),
+        ]
+    }
+}
+
+/// Storage for request counters
+///
+/// The default in-memory store is per process; a shared store lets several
+/// admin replicas enforce the same limits.
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Count a request against `key` unless `limit` requests were already
+    /// counted within `window`; returns whether the request is allowed
+    async fn hit(&self, key: &str, limit: u32, window: Duration) -> Result<bool>;
+
+    /// Requests counted against `key` within `window`
+    async fn count(&self, key: &str, window: Duration) -> Result<u32>;
+}
+
+/// Sliding-window counters kept in process memory
+#[derive(Default)]
+pub struct MemoryRateLimitStore {
+    requests: RwLock<HashMap<String, Vec<Instant>>>,
+}
+
+impl MemoryRateLimitStore {
+    /// Clean up request timestamps older than the window
+    fn cleanup_old_requests(times: &mut Vec<Instant>, window: Duration) {
+        let now = Instant::now();
+        times.retain(|t| now.duration_since(*t) < window);
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryRateLimitStore {
+    async fn hit(&self, key: &str, limit: u32, window: Duration) -> Result<bool> {
+        let mut requests = self.requests.write().await;
+        let times = requests.entry(key.to_string()).or_default();
+        Self::cleanup_old_requests(times, window);
+        if times.len() >= limit as usize {
+            return Ok(false);
+        }
+        times.push(Instant::now());
+        Ok(true)
+    }
+
+    async fn count(&self, key: &str, window: Duration) -> Result<u32> {
+        let now = Instant::now();
+        Ok(self.requests.read().await.get(key)
+            .map_or(0, |times| times.iter().filter(|t| now.duration_since(**t) < window).count()) as u32)
+    }
+}
+
+/// Fixed-window counters in Redis, shared by every instance using the same server
+#[cfg(feature = "redis")]
+pub struct RedisRateLimitStore {
+    connection: redis::aio::ConnectionManager,
+    prefix: String,
+}
+
+#[cfg(feature = "redis")]
+impl RedisRateLimitStore {
+    /// Connect to the Redis server at `url` (e.g. `redis://127.0.0.1/`)
+    pub async fn connect(url: &str) -> Result<Self> {
+        let client = redis::Client::open(url)?;
+        let connection = redis::aio::ConnectionManager::new(client).await?;
+        Ok(Self {
+            connection,
+            prefix: "dmpool:ratelimit".to_string(),
+        })
+    }
+
+    /// Use a different key prefix, e.g. to separate deployments sharing a server
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Key of the counter for the window containing the current time
+    fn window_key(&self, key: &str, window: Duration) -> String {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        format!("{}:{}:{}", self.prefix, key, now / window.as_secs().max(1))
+    }
+}
+
+#[cfg(feature = "redis")]
+#[async_trait]
+impl RateLimitStore for RedisRateLimitStore {
+    async fn hit(&self, key: &str, limit: u32, window: Duration) -> Result<bool> {
+        let window_key = self.window_key(key, window);
+        let mut connection = self.connection.clone();
+        let (count,): (u32,) = redis::pipe()
+            .atomic()
+            .incr(&window_key, 1)
+            .expire(&window_key, window.as_secs().max(1) as i64).ignore()
+            .query_async(&mut connection)
+            .await?;
+        Ok(count <= limit)
+    }
+
+    async fn count(&self, key: &str, window: Duration) -> Result<u32> {
+        let mut connection = self.connection.clone();
+        let count: Option<u32> = redis::cmd("GET")
+            .arg(self.window_key(key, window))
+            .query_async(&mut connection)
+            .await?;
+        Ok(count.unwrap_or(0))
+    }
+}
+
 /// Rate limiter state - stores rate limit information per IP
 #[derive(Clone)]
 pub struct RateLimiterState {
     /// Rate limit configuration
     config: RateLimitConfig,
-    /// Store last request time per IP (simple in-memory tracking)
-    api_request_times: Arc<RwLock<std::collections::HashMap<String, Vec<std::time::Instant>>>>,
-    login_request_times: Arc<RwLock<std::collections::HashMap<String, Vec<std::time::Instant>>>>,
+    /// Request counters, in memory unless a shared store is configured
+    store: Arc<dyn RateLimitStore>,
+    /// Per-route and per-user rules
+    rules: Arc<RwLock<Vec<RateLimitRule>>>,
+    /// Where rules are persisted, if anywhere
+    rules_path: Option<PathBuf>,
 }
 
 impl RateLimiterState {
@@ -86,67 +315,150 @@ impl RateLimiterState {
     pub fn new(config: RateLimitConfig) -> Self {
         Self {
             config,
-            api_request_times: Arc::new(RwLock::new(std::collections::HashMap::new())),
-            login_request_times: Arc::new(RwLock::new(std::collections::HashMap::new())),
+            store: Arc::new(MemoryRateLimitStore::default()),
+            rules: Arc::new(RwLock::new(Vec::new())),
+            rules_path: None,
         }
     }
 
-    /// Clean up old request timestamps (older than 1 minute)
-    fn cleanup_old_requests(times: &mut Vec<std::time::Instant>, window: std::time::Duration) {
-        let now = std::time::Instant::now();
-        times.retain(|t| now.duration_since(*t) < window);
+    /// Persist rules as JSON at `path`
+    pub fn with_rules_path(mut self, path: PathBuf) -> Self {
+        self.rules_path = Some(path);
+        self
     }
 
-    /// Check if the given IP is rate limited for API requests
-    pub async fn check_api_rate_limit(&self, ip: IpAddr) -> Result<(), RateLimitError> {
-        let ip_str = ip.to_string();
-        let mut times = self.api_request_times.write().await;
-        let requests = times.entry(ip_str.clone()).or_insert_with(Vec::new);
+    /// Load rules from the rules file, falling back to `RateLimitRule::defaults`
+    pub async fn load_rules(&self) -> Result<usize> {
+        let rules = match &self.rules_path {
+            Some(path) if path.exists() => {
+                let content = tokio::fs::read_to_string(path).await?;
+                let rules: Vec<RateLimitRule> = serde_json::from_str(&content)?;
+                for rule in &rules {
+                    rule.validate()?;
+                }
+                rules
+            }
+            _ => RateLimitRule::defaults(),
+        };
+        let count = rules.len();
+        *self.rules.write().await = rules;
+        Ok(count)
+    }
 
-        // Clean up old requests
-        Self::cleanup_old_requests(requests, std::time::Duration::from_secs(60));
+    async fn save_rules(&self, rules: &[RateLimitRule]) -> Result<()> {
+        if let Some(path) = &self.rules_path {
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(path, serde_json::to_string_pretty(rules)?).await?;
+        }
+        Ok(())
+    }
 
-        // Check rate limit
-        if requests.len() >= self.config.api_rpm.get() as usize {
-            warn!("Rate limit exceeded for API: {}", ip_str);
-            return Err(RateLimitError::TooManyRequests);
+    /// Configured per-route rules
+    pub async fn rules(&self) -> Vec<RateLimitRule> {
+        self.rules.read().await.clone()
+    }
+
+    /// Add a rule, or replace the rule with the same id
+    pub async fn add_rule(&self, rule: RateLimitRule) -> Result<()> {
+        rule.validate()?;
+        let mut rules = self.rules.write().await;
+        rules.retain(|r| r.id != rule.id);
+        rules.push(rule);
+        self.save_rules(&rules).await
+    }
+
+    /// Remove a rule; returns whether it existed
+    pub async fn remove_rule(&self, id: &str) -> Result<bool> {
+        let mut rules = self.rules.write().await;
+        let before = rules.len();
+        rules.retain(|r| r.id != id);
+        if rules.len() == before {
+            return Ok(false);
         }
+        self.save_rules(&rules).await?;
+        Ok(true)
+    }
 
-        // Add current request timestamp
-        requests.push(std::time::Instant::now());
-        debug!("API request allowed for: {} (total: {})", ip_str, requests.len());
+    /// Enforce every rule matching a request to `path`
+    pub async fn check_rules(&self, path: &str, claims: Option<&Claims>, ip: IpAddr) -> Result<(), RateLimitError> {
+        let matching: Vec<RateLimitRule> = self.rules.read().await.iter()
+            .filter(|rule| rule.matches(path, claims))
+            .cloned()
+            .collect();
+        let caller = match claims {
+            Some(claims) => format!("user:{}", claims.name),
+            None => format!("ip:{}", ip),
+        };
+        for rule in matching {
+            let key = format!("rule:{}:{}", rule.id, caller);
+            match self.store.hit(&key, rule.limit, Duration::from_secs(rule.window_secs)).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    warn!("Rate limit rule {} ({}) exceeded by {}", rule.id, rule.route_prefix, caller);
+                    return Err(RateLimitError::TooManyRequests);
+                }
+                Err(e) => error!("Rate limit store unavailable, skipping rule {}: {}", rule.id, e),
+            }
+        }
         Ok(())
     }
 
-    /// Check if the given IP is rate limited for login attempts
-    pub async fn check_login_rate_limit(&self, ip: IpAddr) -> Result<(), RateLimitError> {
-        let ip_str = ip.to_string();
-        let mut times = self.login_request_times.write().await;
-        let requests = times.entry(ip_str.clone()).or_insert_with(Vec::new);
+    /// Keep request counters in `store` instead of process memory
+    pub fn with_store(mut self, store: Arc<dyn RateLimitStore>) -> Self {
+        self.store = store;
+        self
+    }
 
-        // Clean up old requests
-        Self::cleanup_old_requests(requests, std::time::Duration::from_secs(60));
+    /// Rate limit configuration, also used to resolve client IPs
+    pub fn config(&self) -> &RateLimitConfig {
+        &self.config
+    }
 
-        // Check rate limit (stricter for login)
-        if requests.len() >= self.config.login_rpm.get() as usize {
-            warn!("Rate limit exceeded for login: {}", ip_str);
-            return Err(RateLimitError::TooManyRequests);
+    /// Count a request against `key`
+    ///
+    /// If the store is unreachable the request is allowed, so an outage of a
+    /// shared store doesn't take the admin API down with it.
+    async fn check(&self, key: &str, limit: u32) -> Result<(), RateLimitError> {
+        match self.store.hit(key, limit, RATE_LIMIT_WINDOW).await {
+            Ok(true) => {
+                debug!("Request allowed for: {}", key);
+                Ok(())
+            }
+            Ok(false) => {
+                warn!("Rate limit exceeded for {}", key);
+                Err(RateLimitError::TooManyRequests)
+            }
+            Err(e) => {
+                error!("Rate limit store unavailable, allowing {}: {}", key, e);
+                Ok(())
+            }
         }
+    }
 
-        // Add current request timestamp
-        requests.push(std::time::Instant::now());
-        debug!("Login attempt allowed for: {} (total: {})", ip_str, requests.len());
-        Ok(())
+    /// Check if the given IP is rate limited for API requests
+    pub async fn check_api_rate_limit(&self, ip: IpAddr) -> Result<(), RateLimitError> {
+        self.check(&format!("api:{}", ip), self.config.api_rpm.get()).await
+    }
+
+    /// Check if the given IP is rate limited for login attempts
+    pub async fn check_login_rate_limit(&self, ip: IpAddr) -> Result<(), RateLimitError> {
+        self.check(&format!("login:{}", ip), self.config.login_rpm.get()).await
+    }
+
+    /// Check if the given IP is rate limited for pushing stratum events
+    pub async fn check_ingest_rate_limit(&self, ip: IpAddr) -> Result<(), RateLimitError> {
+        self.check(&format!("ingest:{}", ip), self.config.ingest_rpm.get()).await
     }
 
     /// Get current rate limit status for an IP
     pub async fn get_rate_limit_status(&self, ip: IpAddr) -> RateLimitStatus {
         let ip_str = ip.to_string();
-        let api_times = self.api_request_times.read().await;
-        let login_times = self.login_request_times.read().await;
-
-        let api_count = api_times.get(&ip_str).map_or(0, |v| v.len()) as u32;
-        let login_count = login_times.get(&ip_str).map_or(0, |v| v.len()) as u32;
+        let api_count = self.store.count(&format!("api:{}", ip_str), RATE_LIMIT_WINDOW).await
+            .unwrap_or(0);
+        let login_count = self.store.count(&format!("login:{}", ip_str), RATE_LIMIT_WINDOW).await
+            .unwrap_or(0);
 
         RateLimitStatus {
             ip: ip_str,
@@ -198,80 +510,105 @@ impl IntoResponse for RateLimitError {
     }
 }
 
-/// Extract client IP from request headers
-/// Only trusts X-Forwarded-For from configured trusted proxies
-/// Returns error if IP cannot be determined (unless in development mode)
-pub fn extract_client_ip(headers: &HeaderMap, config: &RateLimitConfig) -> Result<IpAddr, RateLimitError> {
-    // First, try to get the direct connection IP from CF-Connecting-IP header
-    // This header is set by Cloudflare and cannot be spoofed by the client
-    if let Some(cf_ip) = headers.get("cf-connecting-ip") {
-        if let Ok(ip_str) = cf_ip.to_str() {
-            if let Ok(ip) = ip_str.parse::<IpAddr>() {
-                debug!("Using CF-Connecting-IP: {}", ip);
-                return Ok(ip);
-            }
-        }
+/// Header `client_ip_middleware` records the resolved client address in;
+/// a copy sent by the client is replaced
+pub const CLIENT_IP_HEADER: &str = "x-dmpool-client-ip";
+
+/// Resolve the client address of a request that arrived from `peer`
+///
+/// Forwarding headers are only believed when `peer` is a trusted proxy. The
+/// `Forwarded` chain, or else the `X-Forwarded-For` one, is walked from the
+/// nearest hop, skipping trusted proxies; the first untrusted address is the
+/// client. Without a chain, `CF-Connecting-IP` or `X-Real-IP` set by the proxy
+/// is used.
+pub fn resolve_client_ip(peer: IpAddr, headers: &HeaderMap, config: &RateLimitConfig) -> IpAddr {
+    let peer = peer.to_canonical();
+    if !config.is_trusted_proxy(&peer) {
+        return peer;
     }
 
-    // Check X-Forwarded-For (only from trusted proxies)
-    if let Some(forwarded) = headers.get("x-forwarded-for") {
-        if let Ok(forwarded_str) = forwarded.to_str() {
-            // X-Forwarded-For format: "client, proxy1, proxy2"
-            let parts: Vec<&str> = forwarded_str.split(',').collect();
-
-            // If we have trusted proxies, validate the chain
-            if !config.trusted_proxies.is_empty() {
-                // The rightmost IP should be our direct connection
-                // Check if it's from a trusted proxy
-                if let Some(direct_ip_str) = parts.last() {
-                    if let Ok(direct_ip) = direct_ip_str.trim().parse::<IpAddr>() {
-                        if config.trusted_proxies.contains(&direct_ip) {
-                            // Proxy is trusted, use the client IP (leftmost)
-                            if let Some(client_ip_str) = parts.first() {
-                                if let Ok(client_ip) = client_ip_str.trim().parse::<IpAddr>() {
-                                    // Validate client IP is not a private/internal network
-                                    if is_valid_client_ip(&client_ip) {
-                                        debug!("Using X-Forwarded-For client IP: {} (via trusted proxy)", client_ip);
-                                        return Ok(client_ip);
-                                    }
-                                }
-                            }
-                        }
+    let chain = forwarded_chain(headers);
+    if !chain.is_empty() {
+        let mut client = peer;
+        for hop in chain.iter().rev() {
+            match hop {
+                Some(ip) => {
+                    client = *ip;
+                    if !config.is_trusted_proxy(ip) {
+                        debug!("Using forwarded client IP {} (via trusted proxy {})", client, peer);
+                        return client;
                     }
                 }
-                // If we reach here, X-Forwarded-For is from untrusted source or invalid
-                warn!("X-Forwarded-For from untrusted source, ignoring");
+                // An obfuscated or unknown hop hides everything before it
+                None => break,
             }
         }
+        return client;
     }
 
-    // Check X-Real-IP (only from trusted proxies)
-    if let Some(real_ip) = headers.get("x-real-ip") {
-        if let Ok(real_ip_str) = real_ip.to_str() {
-            if let Ok(ip) = real_ip_str.parse::<IpAddr>() {
-                // Only accept X-Real-IP if it's from localhost (direct connection)
-                // In a real setup, you'd check the direct connection IP
-                if is_localhost(&ip) {
-                    debug!("Using X-Real-IP from local connection: {}", ip);
-                    return Ok(ip);
-                }
-            }
+    for name in ["cf-connecting-ip", "x-real-ip"] {
+        if let Some(ip) = header_ip(headers, name) {
+            debug!("Using {} {} (via trusted proxy {})", name, ip, peer);
+            return ip;
         }
     }
+    peer
+}
 
-    // Check for CF-Pseudo-IPv4 (Cloudflare pseudo IPv4 for IPv6 clients)
-    if let Some(pseudo_ipv4) = headers.get("cf-pseudo-ipv4") {
-        if let Ok(ip_str) = pseudo_ipv4.to_str() {
-            if let Ok(ip) = ip_str.parse::<IpAddr>() {
-                debug!("Using CF-Pseudo-IPv4: {}", ip);
-                return Ok(ip);
-            }
-        }
+/// Hops of the `Forwarded` header, or else of `X-Forwarded-For`, client first;
+/// `None` for hops without a usable address
+fn forwarded_chain(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    let values = |name: &str| -> Vec<String> {
+        headers.get_all(name).iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|hop| hop.trim().to_string())
+            .filter(|hop| !hop.is_empty())
+            .collect()
+    };
+    let forwarded = values("forwarded");
+    if !forwarded.is_empty() {
+        return forwarded.iter()
+            .map(|element| {
+                element.split(';')
+                    .filter_map(|pair| pair.split_once('='))
+                    .find(|(key, _)| key.trim().eq_ignore_ascii_case("for"))
+                    .and_then(|(_, node)| parse_node(node))
+            })
+            .collect();
+    }
+    values("x-forwarded-for").iter().map(|hop| parse_node(hop)).collect()
+}
+
+/// Address of a forwarding hop: bare, quoted, bracketed IPv6 or with a port
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    let ip = if let Some(rest) = node.strip_prefix('[') {
+        rest.split(']').next()?.parse().ok()
+    } else {
+        node.parse().ok().or_else(|| {
+            let (host, _port) = node.rsplit_once(':')?;
+            host.parse::<Ipv4Addr>().ok().map(IpAddr::V4)
+        })
+    };
+    ip.map(|ip: IpAddr| ip.to_canonical())
+}
+
+fn header_ip(headers: &HeaderMap, name: &str) -> Option<IpAddr> {
+    headers.get(name)?.to_str().ok().and_then(parse_node)
+}
+
+/// Client IP of a request, as resolved by `client_ip_middleware`
+///
+/// Returns error if IP cannot be determined (unless in development mode)
+pub fn extract_client_ip(headers: &HeaderMap, config: &RateLimitConfig) -> Result<IpAddr, RateLimitError> {
+    if let Some(ip) = header_ip(headers, CLIENT_IP_HEADER) {
+        return Ok(ip);
     }
 
     // If we require valid IP and couldn't determine one, fail
     if config.require_valid_ip {
-        error!("Could not determine valid client IP from headers");
+        error!("Could not determine valid client IP");
         return Err(RateLimitError::InvalidIp("Could not determine valid client IP".to_string()));
     }
 
@@ -280,35 +617,28 @@ pub fn extract_client_ip(headers: &HeaderMap, config: &RateLimitConfig) -> Resul
     Ok(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)))
 }
 
-/// Check if an IP is a valid client IP (not a private/internal network)
-fn is_valid_client_ip(ip: &IpAddr) -> bool {
-    match ip {
-        IpAddr::V4(ipv4) => {
-            // Reject private IPs
-            if ipv4.is_loopback() || ipv4.is_private() || ipv4.is_link_local() {
-                return false;
-            }
-            // Accept public IPs
-            true
+/// Resolve the client IP of every request for `extract_client_ip`
+///
+/// Must wrap every route that reads the client IP, on a server that provides
+/// `ConnectInfo<SocketAddr>`; without it the client IP is unknown.
+pub async fn client_ip_middleware(
+    State(limiter): State<Arc<RateLimiterState>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let peer = req.extensions().get::<ConnectInfo<SocketAddr>>().map(|info| info.0.ip());
+    let resolved = peer
+        .map(|peer| resolve_client_ip(peer, req.headers(), &limiter.config))
+        .and_then(|ip| HeaderValue::from_str(&ip.to_string()).ok());
+    match resolved {
+        Some(value) => {
+            req.headers_mut().insert(CLIENT_IP_HEADER, value);
         }
-        IpAddr::V6(ipv6) => {
-            // Check if it's a loopback address
-            if ipv6.is_loopback() {
-                return false;
-            }
-            // For IPv6, we're more permissive in development
-            // In production, you'd want to check for unique local addresses etc.
-            true
+        None => {
+            req.headers_mut().remove(CLIENT_IP_HEADER);
         }
     }
-}
-
-/// Check if an IP is localhost
-fn is_localhost(ip: &IpAddr) -> bool {
-    match ip {
-        IpAddr::V4(ipv4) => ipv4.is_loopback(),
-        IpAddr::V6(ipv6) => ipv6.is_loopback(),
-    }
+    next.run(req).await
 }
 
 /// Extract client IP using default config
@@ -338,6 +668,10 @@ pub async fn rate_limit_middleware(
 
     // Check rate limit
     limiter.check_api_rate_limit(ip).await?;
+    // Then any per-route rules, keyed by user once authenticated; rules name
+    // unversioned paths so they cover every API version
+    let path = crate::versioning::unversioned_path(crate::versioning::request_path(&req)).into_owned();
+    limiter.check_rules(&path, req.extensions().get::<Claims>(), ip).await?;
     info!("MIDDLEWARE: Rate limit middleware: rate limit check passed");
 
     // Continue with request
@@ -364,6 +698,20 @@ pub async fn login_rate_limit_middleware(
     Ok(next.run(req).await)
 }
 
+/// Middleware for rate limiting stratum event ingestion
+///
+/// Has its own, higher limit than the API, and runs before the ingest token
+/// is checked, so guessing it is limited too.
+pub async fn ingest_rate_limit_middleware(
+    State(limiter): State<Arc<RateLimiterState>>,
+    req: Request,
+    next: Next,
+) -> Result<Response, RateLimitError> {
+    let ip = extract_client_ip(req.headers(), &limiter.config)?;
+    limiter.check_ingest_rate_limit(ip).await?;
+    Ok(next.run(req).await)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -373,6 +721,7 @@ mod tests {
         let config = RateLimitConfig::default();
         assert_eq!(config.api_rpm.get(), 60);
         assert_eq!(config.login_rpm.get(), 10);
+        assert_eq!(config.ingest_rpm.get(), 600);
         assert_eq!(config.burst.get(), 10);
     }
 
@@ -399,8 +748,9 @@ mod tests {
         let config = RateLimitConfig {
             api_rpm: NonZeroU32::new(5).unwrap(),
             login_rpm: NonZeroU32::new(2).unwrap(),
+            ingest_rpm: NonZeroU32::new(3).unwrap(),
             burst: NonZeroU32::new(2).unwrap(),
-            trusted_proxies: HashSet::new(),
+            trusted_proxies: Vec::new(),
             require_valid_ip: false, // Allow localhost in tests
         };
         let limiter = RateLimiterState::new(config);
@@ -419,5 +769,115 @@ mod tests {
         assert!(limiter.check_login_rate_limit(ip2).await.is_ok());
         assert!(limiter.check_login_rate_limit(ip2).await.is_ok());
         assert!(limiter.check_login_rate_limit(ip2).await.is_err());
+
+        // Ingestion is counted apart from the API
+        for _ in 0..3 {
+            assert!(limiter.check_ingest_rate_limit(ip).await.is_ok());
+        }
+        assert!(limiter.check_ingest_rate_limit(ip).await.is_err());
+
+        let status = limiter.get_rate_limit_status(ip).await;
+        assert_eq!(status.api_requests_remaining, 0);
+        assert_eq!(status.login_requests_remaining, 2);
+    }
+
+    /// Store that behaves like a shared backend: two limiters see the same counters
+    #[tokio::test]
+    async fn test_limiters_share_a_store() {
+        let config = RateLimitConfig {
+            api_rpm: NonZeroU32::new(3).unwrap(),
+            login_rpm: NonZeroU32::new(1).unwrap(),
+            ingest_rpm: NonZeroU32::new(1).unwrap(),
+            burst: NonZeroU32::new(1).unwrap(),
+            trusted_proxies: Vec::new(),
+            require_valid_ip: false,
+        };
+        let store: Arc<dyn RateLimitStore> = Arc::new(MemoryRateLimitStore::default());
+        let first = RateLimiterState::new(config.clone()).with_store(store.clone());
+        let second = RateLimiterState::new(config).with_store(store);
+        let ip = IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 3));
+
+        assert!(first.check_api_rate_limit(ip).await.is_ok());
+        assert!(second.check_api_rate_limit(ip).await.is_ok());
+        assert!(first.check_api_rate_limit(ip).await.is_ok());
+        assert!(second.check_api_rate_limit(ip).await.is_err());
+    }
+
+    fn claims(name: &str, role: &str) -> Claims {
+        Claims {
+            sub: name.to_string(),
+            name: name.to_string(),
+            role: role.to_string(),
+            iat: 0,
+            exp: 0,
+            must_change_password: false,
+            sid: String::new(),
+            scopes: Vec::new(),
+            must_enroll_2fa: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rules_match_routes_users_and_roles() {
+        let restore = RateLimitRule::new("/api/backup/*/restore", 2, 3600);
+        assert!(restore.matches("/api/backup/abc/restore", None));
+        assert!(!restore.matches("/api/backup/abc/verify", None));
+        assert!(!restore.matches("/api/backup", None));
+
+        let viewer = RateLimitRule::new("/api/", 1, 60).with_role("viewer");
+        let alice = claims("alice", "viewer");
+        let admin = claims("root", "admin");
+        assert!(viewer.matches("/api/workers", Some(&alice)));
+        assert!(!viewer.matches("/api/workers", Some(&admin)));
+        assert!(!viewer.matches("/api/workers", None));
+        assert!(RateLimitRule::new("api", 1, 60).validate().is_err());
+
+        let limiter = RateLimiterState::new(RateLimitConfig::default());
+        limiter.add_rule(viewer).await.unwrap();
+        let ip = IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 4));
+        assert!(limiter.check_rules("/api/workers", Some(&alice), ip).await.is_ok());
+        assert!(limiter.check_rules("/api/dashboard", Some(&alice), ip).await.is_err());
+        assert!(limiter.check_rules("/api/dashboard", Some(&admin), ip).await.is_ok());
+    }
+
+    #[test]
+    fn test_client_ip_only_trusts_forwarding_from_trusted_proxies() {
+        let mut config = RateLimitConfig::default();
+        config.add_trusted_proxy_cidr("10.0.0.0/8").unwrap();
+        config.add_trusted_proxy_cidr("127.0.0.1").unwrap();
+        assert!(config.add_trusted_proxy_cidr("not-a-network").is_err());
+        let headers = |pairs: &[(&'static str, &str)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.append(*name, value.parse().unwrap());
+            }
+            headers
+        };
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+        let proxy = ip("10.0.0.2");
+
+        // A client connecting directly can't claim another address
+        let spoofed = headers(&[("x-forwarded-for", "1.2.3.4"), ("cf-connecting-ip", "1.2.3.4")]);
+        assert_eq!(resolve_client_ip(ip("203.0.113.9"), &spoofed, &config), ip("203.0.113.9"));
+
+        // The chain is walked from the nearest hop past trusted proxies only,
+        // so addresses the client prepended are ignored
+        let chain = headers(&[("x-forwarded-for", "1.2.3.4, 198.51.100.7"), ("x-forwarded-for", "10.1.1.1")]);
+        assert_eq!(resolve_client_ip(proxy, &chain, &config), ip("198.51.100.7"));
+        let forwarded = headers(&[
+            ("forwarded", "for=1.2.3.4, for=\"[2001:db8::17]:4711\";proto=https"),
+            ("x-forwarded-for", "5.6.7.8"),
+        ]);
+        assert_eq!(resolve_client_ip(proxy, &forwarded, &config), ip("2001:db8::17"));
+        let hidden = headers(&[("forwarded", "for=1.2.3.4, for=_hidden, for=10.1.1.1")]);
+        assert_eq!(resolve_client_ip(proxy, &hidden, &config), ip("10.1.1.1"));
+
+        let cloudflare = headers(&[("cf-connecting-ip", "192.0.2.44")]);
+        assert_eq!(resolve_client_ip(proxy, &cloudflare, &config), ip("192.0.2.44"));
+        assert_eq!(resolve_client_ip(ip("::ffff:127.0.0.1"), &headers(&[("x-real-ip", "192.0.2.45")]), &config), ip("192.0.2.45"));
+        assert_eq!(resolve_client_ip(proxy, &HeaderMap::new(), &config), proxy);
+
+        let resolved = headers(&[(CLIENT_IP_HEADER, "192.0.2.46")]);
+        assert_eq!(extract_client_ip(&resolved, &config).unwrap(), ip("192.0.2.46"));
     }
 }
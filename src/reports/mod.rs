@@ -0,0 +1,545 @@
+// Summary Reports for DMPool
+// Daily and weekly pool summaries, rendered as text and HTML and emailed to subscribed operators
+
+use crate::alert::{Alert, AlertLevel};
+use crate::blocks::{BlockState, FoundBlock};
+use crate::cron::CronExpr;
+use crate::miner_settings::validate_email;
+use crate::payout::{PayoutBatch, PayoutStatus};
+use crate::timeseries::Point;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+use tokio::sync::RwLock;
+use tracing::info;
+use utoipa::ToSchema;
+
+/// Workers listed in a report
+const TOP_WORKERS: usize = 10;
+/// Critical and warning alerts listed in a report; the rest are only counted
+const LISTED_ALERTS: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportPeriod {
+    Daily,
+    Weekly,
+}
+
+impl ReportPeriod {
+    pub fn duration(&self) -> Duration {
+        match self {
+            Self::Daily => Duration::days(1),
+            Self::Weekly => Duration::days(7),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Daily => "daily",
+            Self::Weekly => "weekly",
+        }
+    }
+}
+
+/// When reports go out and over which alert channel
+#[derive(Clone, Debug)]
+pub struct ReportSettings {
+    pub daily: CronExpr,
+    pub weekly: CronExpr,
+    /// Email alert channel whose SMTP server sends reports; the first one by name if unset
+    pub channel: Option<String>,
+}
+
+impl ReportSettings {
+    /// Settings from `REPORT_DAILY_SCHEDULE` (default 08:00 UTC),
+    /// `REPORT_WEEKLY_SCHEDULE` (default Monday 08:00 UTC) and `REPORT_EMAIL_CHANNEL`
+    pub fn from_env() -> Result<Self> {
+        let schedule = |var: &str, default: &str| -> Result<CronExpr> {
+            std::env::var(var).unwrap_or_else(|_| default.to_string()).parse()
+                .with_context(|| format!("Invalid {}", var))
+        };
+        Ok(Self {
+            daily: schedule("REPORT_DAILY_SCHEDULE", "0 8 * * *")?,
+            weekly: schedule("REPORT_WEEKLY_SCHEDULE", "0 8 * * 1")?,
+            channel: std::env::var("REPORT_EMAIL_CHANNEL").ok(),
+        })
+    }
+
+    /// The next time a report is due after `after`, with every period due then
+    pub fn next_run(&self, after: DateTime<Utc>) -> Option<(DateTime<Utc>, Vec<ReportPeriod>)> {
+        let runs = [
+            (self.daily.next_after(after), ReportPeriod::Daily),
+            (self.weekly.next_after(after), ReportPeriod::Weekly),
+        ];
+        let next = runs.iter().filter_map(|(time, _)| *time).min()?;
+        let periods = runs.iter().filter(|(time, _)| *time == Some(next)).map(|(_, period)| *period).collect();
+        Some((next, periods))
+    }
+}
+
+/// Hashrate of one worker over the report period
+#[derive(Clone, Debug, Serialize)]
+pub struct WorkerLine {
+    pub address: String,
+    pub worker: String,
+    pub hashrate_ths: f64,
+    pub shares: u64,
+}
+
+/// Payout batches sent during the report period
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PayoutSummary {
+    pub batches: u64,
+    pub payments: u64,
+    pub total_sats: u64,
+}
+
+/// Alerts raised during the report period
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct AlertSummary {
+    pub critical: u64,
+    pub warning: u64,
+    pub info: u64,
+    /// Latest critical and warning alerts, newest first
+    pub recent: Vec<Alert>,
+}
+
+/// What a report is computed from; the report keeps what falls in its period
+#[derive(Default)]
+pub struct ReportInputs {
+    /// Pool hashrate samples
+    pub hashrate: Vec<Point>,
+    pub blocks: Vec<FoundBlock>,
+    pub workers: Vec<WorkerLine>,
+    pub batches: Vec<PayoutBatch>,
+    pub alerts: Vec<Alert>,
+}
+
+/// Summary of the pool over a report period
+#[derive(Clone, Debug, Serialize)]
+pub struct PoolReport {
+    pub period: ReportPeriod,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// None without hashrate samples in the period
+    pub avg_hashrate_ths: Option<f64>,
+    pub peak_hashrate_ths: Option<f64>,
+    /// Blocks found in the period, oldest first
+    pub blocks: Vec<FoundBlock>,
+    /// Highest hashrate first
+    pub top_workers: Vec<WorkerLine>,
+    pub payouts: PayoutSummary,
+    pub alerts: AlertSummary,
+}
+
+impl PoolReport {
+    /// The report of the `period` ending at `to`
+    pub fn build(period: ReportPeriod, to: DateTime<Utc>, inputs: ReportInputs) -> Self {
+        let from = to - period.duration();
+        let in_period = |time: DateTime<Utc>| time > from && time <= to;
+
+        let (start, end) = (from.timestamp().max(0) as u64, to.timestamp().max(0) as u64);
+        let hashrate: Vec<f64> = inputs.hashrate.iter()
+            .filter(|p| p.timestamp >= start && p.timestamp <= end)
+            .map(|p| p.hashrate_ths)
+            .collect();
+        let avg_hashrate_ths = (!hashrate.is_empty()).then(|| hashrate.iter().sum::<f64>() / hashrate.len() as f64);
+        let peak_hashrate_ths = hashrate.iter().copied().reduce(f64::max);
+
+        let mut blocks: Vec<FoundBlock> = inputs.blocks.into_iter().filter(|b| in_period(b.timestamp)).collect();
+        blocks.sort_by_key(|b| b.height);
+
+        let mut top_workers = inputs.workers;
+        top_workers.sort_by(|a, b| b.hashrate_ths.total_cmp(&a.hashrate_ths));
+        top_workers.truncate(TOP_WORKERS);
+
+        let mut payouts = PayoutSummary::default();
+        for batch in &inputs.batches {
+            let sent = matches!(batch.status, PayoutStatus::Sent | PayoutStatus::Paid);
+            if sent && in_period(batch.updated_at) {
+                payouts.batches += 1;
+                payouts.payments += batch.payments.len() as u64;
+                payouts.total_sats += batch.total_sats;
+            }
+        }
+
+        let mut alerts = AlertSummary::default();
+        let mut raised: Vec<Alert> = inputs.alerts.into_iter().filter(|a| in_period(a.triggered_at)).collect();
+        raised.sort_by_key(|a| std::cmp::Reverse(a.triggered_at));
+        for alert in raised {
+            match alert.level {
+                AlertLevel::Critical => alerts.critical += 1,
+                AlertLevel::Warning => alerts.warning += 1,
+                AlertLevel::Info => alerts.info += 1,
+            }
+            if alert.level != AlertLevel::Info && alerts.recent.len() < LISTED_ALERTS {
+                alerts.recent.push(alert);
+            }
+        }
+
+        Self {
+            period,
+            from,
+            to,
+            avg_hashrate_ths,
+            peak_hashrate_ths,
+            blocks,
+            top_workers,
+            payouts,
+            alerts,
+        }
+    }
+
+    pub fn subject(&self) -> String {
+        format!("DMPool {} report for {}", self.period.name(), self.to.format("%Y-%m-%d"))
+    }
+
+    /// Plain text body, also the fallback of the HTML one
+    pub fn render_text(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "{}", self.subject());
+        let _ = writeln!(out, "{} to {}\n", format_time(self.from), format_time(self.to));
+
+        let _ = writeln!(out, "Hashrate");
+        let _ = writeln!(out, "  Average: {}", format_hashrate(self.avg_hashrate_ths));
+        let _ = writeln!(out, "  Peak:    {}\n", format_hashrate(self.peak_hashrate_ths));
+
+        let _ = writeln!(out, "Blocks found: {}", self.blocks.len());
+        for block in &self.blocks {
+            let _ = writeln!(
+                out, "  #{} {} BTC, {} ({} confirmations)",
+                block.height, format_btc(block.reward_sats), state_label(block.state), block.confirmations.max(0)
+            );
+        }
+
+        let _ = writeln!(out, "\nTop workers");
+        if self.top_workers.is_empty() {
+            let _ = writeln!(out, "  No shares in this period");
+        }
+        for worker in &self.top_workers {
+            let _ = writeln!(
+                out, "  {}.{}  {:.2} TH/s, {} shares",
+                worker.address, worker.worker, worker.hashrate_ths, worker.shares
+            );
+        }
+
+        let _ = writeln!(out, "\nPayouts");
+        let _ = writeln!(
+            out, "  {} BTC in {} payments over {} batches",
+            format_btc(self.payouts.total_sats), self.payouts.payments, self.payouts.batches
+        );
+
+        let _ = writeln!(out, "\nAlerts");
+        let _ = writeln!(
+            out, "  {} critical, {} warning, {} info",
+            self.alerts.critical, self.alerts.warning, self.alerts.info
+        );
+        for alert in &self.alerts.recent {
+            let _ = writeln!(out, "  {} [{}] {}", format_time(alert.triggered_at), alert.level, alert.title);
+        }
+        out
+    }
+
+    pub fn render_html(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{0}</title></head>\n\
+             <body style=\"font-family: sans-serif; color: #222;\">\n<h1>{0}</h1>\n<p>{1} to {2}</p>\n",
+            escape_html(&self.subject()), format_time(self.from), format_time(self.to)
+        );
+
+        let _ = write!(
+            out,
+            "<h2>Hashrate</h2>\n<table>\n<tr><td>Average</td><td>{}</td></tr>\n<tr><td>Peak</td><td>{}</td></tr>\n</table>\n",
+            format_hashrate(self.avg_hashrate_ths), format_hashrate(self.peak_hashrate_ths)
+        );
+
+        let _ = writeln!(out, "<h2>Blocks found: {}</h2>", self.blocks.len());
+        if !self.blocks.is_empty() {
+            out.push_str("<table>\n<tr><th>Height</th><th>Reward (BTC)</th><th>State</th><th>Confirmations</th></tr>\n");
+            for block in &self.blocks {
+                let _ = writeln!(
+                    out, "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    block.height, format_btc(block.reward_sats), state_label(block.state), block.confirmations.max(0)
+                );
+            }
+            out.push_str("</table>\n");
+        }
+
+        out.push_str("<h2>Top workers</h2>\n");
+        if self.top_workers.is_empty() {
+            out.push_str("<p>No shares in this period</p>\n");
+        } else {
+            out.push_str("<table>\n<tr><th>Worker</th><th>Hashrate (TH/s)</th><th>Shares</th></tr>\n");
+            for worker in &self.top_workers {
+                let _ = writeln!(
+                    out, "<tr><td>{}.{}</td><td>{:.2}</td><td>{}</td></tr>",
+                    escape_html(&worker.address), escape_html(&worker.worker), worker.hashrate_ths, worker.shares
+                );
+            }
+            out.push_str("</table>\n");
+        }
+
+        let _ = writeln!(
+            out, "<h2>Payouts</h2>\n<p>{} BTC in {} payments over {} batches</p>",
+            format_btc(self.payouts.total_sats), self.payouts.payments, self.payouts.batches
+        );
+
+        let _ = writeln!(
+            out, "<h2>Alerts</h2>\n<p>{} critical, {} warning, {} info</p>",
+            self.alerts.critical, self.alerts.warning, self.alerts.info
+        );
+        if !self.alerts.recent.is_empty() {
+            out.push_str("<ul>\n");
+            for alert in &self.alerts.recent {
+                let _ = writeln!(
+                    out, "<li>{} <strong>{}</strong> {}</li>",
+                    format_time(alert.triggered_at), alert.level, escape_html(&alert.title)
+                );
+            }
+            out.push_str("</ul>\n");
+        }
+        out.push_str("</body></html>\n");
+        out
+    }
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%d %H:%M UTC").to_string()
+}
+
+fn format_hashrate(hashrate_ths: Option<f64>) -> String {
+    match hashrate_ths {
+        Some(hashrate) => format!("{:.2} TH/s", hashrate),
+        None => "no data".to_string(),
+    }
+}
+
+fn format_btc(sats: u64) -> String {
+    format!("{}.{:08}", sats / 100_000_000, sats % 100_000_000)
+}
+
+fn state_label(state: BlockState) -> &'static str {
+    match state {
+        BlockState::Pending => "pending",
+        BlockState::Mature => "mature",
+        BlockState::Orphaned => "orphaned",
+    }
+}
+
+/// Escape text for HTML element content and attribute values
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reports an operator receives and where
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReportSubscription {
+    pub username: String,
+    pub email: String,
+    pub periods: Vec<ReportPeriod>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Persistent report subscriptions, keyed by username
+pub struct ReportSubscriptions {
+    path: PathBuf,
+    subscriptions: RwLock<BTreeMap<String, ReportSubscription>>,
+}
+
+impl ReportSubscriptions {
+    /// Create a store kept at `path`
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            subscriptions: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Load subscriptions from disk, if present
+    pub async fn load(&self) -> Result<usize> {
+        if !self.path.exists() {
+            return Ok(0);
+        }
+        let content = tokio::fs::read_to_string(&self.path).await
+            .context("Failed to read report subscriptions")?;
+        let subscriptions: Vec<ReportSubscription> = serde_json::from_str(&content)
+            .context("Failed to parse report subscriptions")?;
+        let count = subscriptions.len();
+        *self.subscriptions.write().await = subscriptions.into_iter().map(|s| (s.username.clone(), s)).collect();
+        Ok(count)
+    }
+
+    async fn save(&self, subscriptions: &BTreeMap<String, ReportSubscription>) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let subscriptions: Vec<&ReportSubscription> = subscriptions.values().collect();
+        let tmp = self.path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, serde_json::to_string_pretty(&subscriptions)?).await
+            .context("Failed to write report subscriptions")?;
+        tokio::fs::rename(&tmp, &self.path).await
+            .context("Failed to replace report subscriptions")?;
+        Ok(())
+    }
+
+    pub async fn get(&self, username: &str) -> Option<ReportSubscription> {
+        self.subscriptions.read().await.get(username).cloned()
+    }
+
+    /// Every subscription, sorted by username
+    pub async fn list(&self) -> Vec<ReportSubscription> {
+        self.subscriptions.read().await.values().cloned().collect()
+    }
+
+    /// Subscribe `username` to the reports of `periods`, replacing any previous subscription
+    pub async fn set(&self, username: &str, email: &str, mut periods: Vec<ReportPeriod>) -> Result<ReportSubscription> {
+        validate_email(email)?;
+        periods.sort();
+        periods.dedup();
+        if periods.is_empty() {
+            return Err(anyhow::anyhow!("Choose daily reports, weekly reports or both"));
+        }
+        let subscription = ReportSubscription {
+            username: username.to_string(),
+            email: email.to_string(),
+            periods,
+            updated_at: Utc::now(),
+        };
+        let mut subscriptions = self.subscriptions.write().await;
+        subscriptions.insert(username.to_string(), subscription.clone());
+        self.save(&subscriptions).await?;
+        info!("Report subscription of {} updated", username);
+        Ok(subscription)
+    }
+
+    /// Unsubscribe `username`; returns false if they had no subscription
+    pub async fn remove(&self, username: &str) -> Result<bool> {
+        let mut subscriptions = self.subscriptions.write().await;
+        if subscriptions.remove(username).is_none() {
+            return Ok(false);
+        }
+        self.save(&subscriptions).await?;
+        Ok(true)
+    }
+
+    /// Subscriptions to the reports of `period`
+    pub async fn subscribers(&self, period: ReportPeriod) -> Vec<ReportSubscription> {
+        self.subscriptions.read().await.values()
+            .filter(|s| s.periods.contains(&period))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_report_covers_its_period() {
+        let to = Utc.with_ymd_and_hms(2026, 10, 12, 8, 0, 0).unwrap();
+        let block = |height: u64, hours_ago: i64| FoundBlock {
+            height,
+            hash: format!("{:064x}", height),
+            timestamp: to - Duration::hours(hours_ago),
+            finder_address: None,
+            reward_sats: 312_500_000,
+            confirmations: 3,
+            orphaned: false,
+            state: BlockState::Pending,
+            state_changes: Vec::new(),
+        };
+        let point = |hours_ago: i64, hashrate_ths: f64| Point {
+            timestamp: (to - Duration::hours(hours_ago)).timestamp() as u64,
+            hashrate_ths,
+        };
+        let mut alert = Alert {
+            id: "a1".to_string(),
+            rule_id: "pool_down".to_string(),
+            level: AlertLevel::Critical,
+            title: "Pool <down>".to_string(),
+            message: String::new(),
+            context: serde_json::Value::Null,
+            triggered_at: to - Duration::hours(2),
+            acknowledged: false,
+            acknowledged_by: None,
+            acknowledged_at: None,
+            channel: "email".to_string(),
+            resolved_at: None,
+        };
+        let old_alert = Alert { triggered_at: to - Duration::days(2), ..alert.clone() };
+        alert.id = "a2".to_string();
+        let inputs = ReportInputs {
+            hashrate: vec![point(30, 500.0), point(12, 100.0), point(1, 300.0)],
+            blocks: vec![block(900_001, 3), block(900_000, 30)],
+            workers: (0..12).map(|i| WorkerLine {
+                address: "bc1qminer".to_string(),
+                worker: format!("rig{}", i),
+                hashrate_ths: i as f64,
+                shares: 10,
+            }).collect(),
+            batches: Vec::new(),
+            alerts: vec![alert, old_alert],
+        };
+
+        let daily = PoolReport::build(ReportPeriod::Daily, to, inputs);
+        assert_eq!(daily.avg_hashrate_ths, Some(200.0));
+        assert_eq!(daily.peak_hashrate_ths, Some(300.0));
+        assert_eq!(daily.blocks.iter().map(|b| b.height).collect::<Vec<_>>(), vec![900_001]);
+        assert_eq!(daily.top_workers.len(), TOP_WORKERS);
+        assert_eq!(daily.top_workers[0].worker, "rig11");
+        assert_eq!(daily.alerts.critical, 1);
+
+        let text = daily.render_text();
+        assert!(text.contains("Blocks found: 1") && text.contains("#900001 3.12500000 BTC, pending"));
+        let html = daily.render_html();
+        assert!(html.contains("Pool &lt;down&gt;") && !html.contains("Pool <down>"));
+
+        let settings = ReportSettings {
+            daily: "0 8 * * *".parse().unwrap(),
+            weekly: "0 8 * * 1".parse().unwrap(),
+            channel: None,
+        };
+        // 2026-10-12 is a Monday, when both reports are due
+        let (next, periods) = settings.next_run(to - Duration::minutes(1)).unwrap();
+        assert_eq!((next, periods), (to, vec![ReportPeriod::Daily, ReportPeriod::Weekly]));
+        assert_eq!(settings.next_run(to).unwrap().1, vec![ReportPeriod::Daily]);
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions_persist() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("report_subscriptions.json");
+        let subscriptions = ReportSubscriptions::new(path.clone());
+        assert!(subscriptions.set("alice", "not-an-email", vec![ReportPeriod::Daily]).await.is_err());
+        assert!(subscriptions.set("alice", "alice@example.com", Vec::new()).await.is_err());
+        let periods = vec![ReportPeriod::Weekly, ReportPeriod::Daily, ReportPeriod::Weekly];
+        let subscription = subscriptions.set("alice", "alice@example.com", periods).await.unwrap();
+        assert_eq!(subscription.periods, vec![ReportPeriod::Daily, ReportPeriod::Weekly]);
+
+        let reloaded = ReportSubscriptions::new(path);
+        assert_eq!(reloaded.load().await.unwrap(), 1);
+        assert_eq!(reloaded.subscribers(ReportPeriod::Weekly).await.len(), 1);
+        assert!(reloaded.remove("alice").await.unwrap());
+        assert!(reloaded.subscribers(ReportPeriod::Daily).await.is_empty());
+    }
+}
@@ -0,0 +1,250 @@
+// Pool Restarts for DMPool
+// Settings changed on disk that only take effect after a restart, and a coordinated restart of the pool
+
+use crate::config_mgt::SettingChange;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::str::FromStr;
+use tokio::process::Command;
+use tokio::sync::{Mutex, RwLock};
+use tracing::{info, warn};
+
+/// How the pool process is restarted
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RestartMethod {
+    /// `systemctl restart <unit>`
+    Systemd { unit: String },
+    /// Signal the process in a pid file, for a supervisor that restarts it
+    Signal { pid_file: PathBuf, signal: String },
+    /// Run a shell command
+    Command { command: String },
+}
+
+impl FromStr for RestartMethod {
+    type Err = anyhow::Error;
+
+    /// `systemd:<unit>`, `signal:<pid file>[:<signal>]` or `command:<shell command>`
+    fn from_str(s: &str) -> Result<Self> {
+        let (kind, target) = s.split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("Invalid restart method {:?} (expected kind:target)", s))?;
+        let target = target.trim();
+        if target.is_empty() {
+            return Err(anyhow::anyhow!("Restart method {} has no target", kind));
+        }
+        match kind.trim() {
+            "systemd" => Ok(Self::Systemd { unit: target.to_string() }),
+            "signal" => {
+                let (pid_file, signal) = match target.rsplit_once(':') {
+                    Some((path, signal)) if signal.chars().all(|c| c.is_ascii_alphanumeric()) => (path, signal),
+                    _ => (target, "TERM"),
+                };
+                Ok(Self::Signal { pid_file: PathBuf::from(pid_file), signal: signal.to_uppercase() })
+            }
+            "command" => Ok(Self::Command { command: target.to_string() }),
+            other => Err(anyhow::anyhow!("Unknown restart method {:?}: use systemd, signal or command", other)),
+        }
+    }
+}
+
+impl RestartMethod {
+    async fn run(&self) -> Result<()> {
+        let mut command = match self {
+            Self::Systemd { unit } => {
+                let mut command = Command::new("systemctl");
+                command.arg("restart").arg(unit);
+                command
+            }
+            Self::Signal { pid_file, signal } => {
+                let pid = tokio::fs::read_to_string(pid_file).await
+                    .with_context(|| format!("Failed to read pid file {}", pid_file.display()))?;
+                let pid: u32 = pid.trim().parse()
+                    .with_context(|| format!("Invalid pid in {}", pid_file.display()))?;
+                let mut command = Command::new("kill");
+                command.arg("-s").arg(signal).arg(pid.to_string());
+                command
+            }
+            Self::Command { command: line } => {
+                let mut command = Command::new("sh");
+                command.arg("-c").arg(line);
+                command
+            }
+        };
+        let output = command.output().await.context("Failed to run restart command")?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "Restart command failed ({}): {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A setting the running pool still has its old value of
+#[derive(Clone, Debug, Serialize)]
+pub struct PendingRestartChange {
+    #[serde(flatten)]
+    pub change: SettingChange,
+    pub detected_at: DateTime<Utc>,
+    /// What noticed the change, e.g. the config file watcher or a reload
+    pub source: String,
+}
+
+/// A completed restart
+#[derive(Clone, Debug, Serialize)]
+pub struct RestartRecord {
+    pub restarted_at: DateTime<Utc>,
+    pub restarted_by: String,
+    pub method: RestartMethod,
+    pub changes: Vec<PendingRestartChange>,
+    /// Backup taken just before the restart
+    pub backup_id: Option<String>,
+}
+
+/// Accumulates restart-required changes and restarts the pool to apply them
+pub struct RestartCoordinator {
+    method: Option<RestartMethod>,
+    pending: RwLock<Vec<PendingRestartChange>>,
+    last_restart: RwLock<Option<RestartRecord>>,
+    /// Held while a restart runs, so two can't overlap
+    restarting: Mutex<()>,
+}
+
+impl RestartCoordinator {
+    /// A coordinator that restarts with `method`; without one, changes are only tracked
+    pub fn new(method: Option<RestartMethod>) -> Self {
+        Self {
+            method,
+            pending: RwLock::new(Vec::new()),
+            last_restart: RwLock::new(None),
+            restarting: Mutex::new(()),
+        }
+    }
+
+    /// Method from `POOL_RESTART`; unset means restarts are left to the operator
+    pub fn from_env() -> Result<Self> {
+        let method = match std::env::var("POOL_RESTART") {
+            Ok(method) => Some(method.parse()?),
+            Err(_) => None,
+        };
+        if let Some(method) = &method {
+            info!("Pool restarts enabled: {:?}", method);
+        }
+        Ok(Self::new(method))
+    }
+
+    pub fn method(&self) -> Option<&RestartMethod> {
+        self.method.as_ref()
+    }
+
+    /// Record settings that need a restart
+    ///
+    /// A setting changed again keeps the value the pool runs with as `old`, and
+    /// is dropped once it is changed back to it.
+    pub async fn record(&self, changes: Vec<SettingChange>, source: &str) {
+        let mut pending = self.pending.write().await;
+        for change in changes {
+            warn!("Config change requires a restart to take effect: {}", change);
+            match pending.iter().position(|p| p.change.setting == change.setting) {
+                Some(i) if pending[i].change.old == change.new => {
+                    pending.remove(i);
+                }
+                Some(i) => {
+                    pending[i].change.new = change.new;
+                    pending[i].detected_at = Utc::now();
+                    pending[i].source = source.to_string();
+                }
+                None => pending.push(PendingRestartChange {
+                    change,
+                    detected_at: Utc::now(),
+                    source: source.to_string(),
+                }),
+            }
+        }
+    }
+
+    pub async fn pending(&self) -> Vec<PendingRestartChange> {
+        self.pending.read().await.clone()
+    }
+
+    pub async fn last_restart(&self) -> Option<RestartRecord> {
+        self.last_restart.read().await.clone()
+    }
+
+    /// Restart the pool and clear the pending changes
+    ///
+    /// `prepare` runs first, e.g. to take a backup, and returns its ID; the
+    /// pool is not restarted if it fails.
+    pub async fn restart<F, Fut>(&self, restarted_by: &str, prepare: F) -> Result<RestartRecord>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<Option<String>>>,
+    {
+        let method = self.method.clone()
+            .ok_or_else(|| anyhow::anyhow!("No restart method configured (set POOL_RESTART)"))?;
+        let _restarting = self.restarting.try_lock()
+            .map_err(|_| anyhow::anyhow!("A restart is already in progress"))?;
+        let backup_id = prepare().await.context("Pre-restart step failed; pool not restarted")?;
+
+        info!("Restarting pool ({:?}) for {}", method, restarted_by);
+        method.run().await?;
+
+        let record = RestartRecord {
+            restarted_at: Utc::now(),
+            restarted_by: restarted_by.to_string(),
+            method,
+            changes: std::mem::take(&mut *self.pending.write().await),
+            backup_id,
+        };
+        *self.last_restart.write().await = Some(record.clone());
+        info!("Pool restarted, applying {} pending change(s)", record.changes.len());
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(setting: &str, old: &str, new: &str) -> SettingChange {
+        SettingChange { setting: setting.to_string(), old: old.to_string(), new: new.to_string() }
+    }
+
+    #[tokio::test]
+    async fn test_pending_changes_and_restart() {
+        assert_eq!("systemd:dmpool.service".parse::<RestartMethod>().unwrap(),
+            RestartMethod::Systemd { unit: "dmpool.service".to_string() });
+        assert_eq!("signal:/run/dmpool.pid:hup".parse::<RestartMethod>().unwrap(),
+            RestartMethod::Signal { pid_file: "/run/dmpool.pid".into(), signal: "HUP".to_string() });
+        assert_eq!("signal:/run/dmpool.pid".parse::<RestartMethod>().unwrap(),
+            RestartMethod::Signal { pid_file: "/run/dmpool.pid".into(), signal: "TERM".to_string() });
+        assert!("reboot:now".parse::<RestartMethod>().is_err());
+        assert!("systemd:".parse::<RestartMethod>().is_err());
+
+        let untracked = RestartCoordinator::new(None);
+        assert!(untracked.restart("admin", || async { Ok(None) }).await.is_err());
+
+        let coordinator = RestartCoordinator::new(Some("command:true".parse().unwrap()));
+        coordinator.record(vec![change("stratum.port", "3333", "3334"), change("api.port", "8080", "8081")], "config-file").await;
+        coordinator.record(vec![change("stratum.port", "3334", "3335"), change("api.port", "8081", "8080")], "reload").await;
+        let pending = coordinator.pending().await;
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].change, change("stratum.port", "3333", "3335"));
+
+        // A failed pre-restart step leaves everything pending
+        assert!(coordinator.restart("admin", || async { Err(anyhow::anyhow!("disk full")) }).await.is_err());
+        assert_eq!(coordinator.pending().await.len(), 1);
+
+        let record = coordinator.restart("admin", || async { Ok(Some("backup_1".to_string())) }).await.unwrap();
+        assert_eq!((record.changes.len(), record.backup_id.as_deref()), (1, Some("backup_1")));
+        assert!(coordinator.pending().await.is_empty());
+        assert!(coordinator.last_restart().await.is_some());
+
+        let failing = RestartCoordinator::new(Some("command:exit 3".parse().unwrap()));
+        assert!(failing.restart("admin", || async { Ok(None) }).await.is_err());
+    }
+}
@@ -0,0 +1,244 @@
+// Config Safety Analysis for DMPool
+// Flags settings that hurt miners and gates config changes that introduce them
+
+use p2poolv2_lib::config::Config;
+use serde::Serialize;
+use tracing::warn;
+
+/// PPLNS window miners are used to; shorter windows cost them rewards
+const STANDARD_PPLNS_TTL_DAYS: u64 = 7;
+/// Fees and donations above this (basis points) are worth a warning
+const HIGH_FEE_BPS: u16 = 500;
+/// All of the reward, in basis points
+const FULL_REWARD_BPS: u32 = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Refused by the apply gate unless forced
+    Critical,
+    Warning,
+}
+
+/// A setting that puts miners' rewards at risk
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SafetyIssue {
+    pub severity: Severity,
+    pub param: String,
+    pub message: String,
+    pub recommendation: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct SafetyReport {
+    /// No critical issues
+    pub safe: bool,
+    pub critical_issues: Vec<SafetyIssue>,
+    pub warnings: Vec<SafetyIssue>,
+}
+
+impl SafetyReport {
+    fn new(issues: Vec<SafetyIssue>) -> Self {
+        let (critical_issues, warnings): (Vec<_>, Vec<_>) =
+            issues.into_iter().partition(|issue| issue.severity == Severity::Critical);
+        Self {
+            safe: critical_issues.is_empty(),
+            critical_issues,
+            warnings,
+        }
+    }
+}
+
+/// A config change refused because it introduces critical issues
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct UnsafeChange {
+    pub critical_issues: Vec<SafetyIssue>,
+}
+
+impl std::fmt::Display for UnsafeChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let params: Vec<&str> = self.critical_issues.iter().map(|i| i.param.as_str()).collect();
+        write!(f, "Unsafe config change ({}); it only applies when forced", params.join(", "))
+    }
+}
+
+impl std::error::Error for UnsafeChange {}
+
+/// The settings the checks look at
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Settings {
+    ignore_difficulty: bool,
+    pplns_ttl_days: u64,
+    donation_bps: u16,
+    fee_bps: u16,
+    start_difficulty: u64,
+    minimum_difficulty: u64,
+}
+
+impl From<&Config> for Settings {
+    fn from(config: &Config) -> Self {
+        Self {
+            ignore_difficulty: config.stratum.ignore_difficulty.unwrap_or(false),
+            pplns_ttl_days: config.store.pplns_ttl_days,
+            donation_bps: config.stratum.donation.unwrap_or(0),
+            fee_bps: config.stratum.fee.unwrap_or(0),
+            start_difficulty: config.stratum.start_difficulty,
+            minimum_difficulty: config.stratum.minimum_difficulty,
+        }
+    }
+}
+
+/// Checks of every setting that decides how much miners are paid
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SafetyAnalyzer;
+
+impl SafetyAnalyzer {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Every issue of `config`
+    pub fn analyze(&self, config: &Config) -> SafetyReport {
+        SafetyReport::new(self.issues(&config.into()))
+    }
+
+    /// Issues of `new` that `old` doesn't have
+    ///
+    /// A setting that was already unsafe doesn't hold up unrelated changes,
+    /// but changing it to another unsafe value counts as introducing it.
+    pub fn analyze_change(&self, old: &Config, new: &Config) -> SafetyReport {
+        self.compare(&old.into(), &new.into())
+    }
+
+    fn compare(&self, old: &Settings, new: &Settings) -> SafetyReport {
+        let existing = self.issues(old);
+        SafetyReport::new(self.issues(new).into_iter().filter(|issue| !existing.contains(issue)).collect())
+    }
+
+    /// The gate of the config apply pipeline: refuse a change that introduces
+    /// critical issues unless it is forced
+    ///
+    /// Returns what the change introduces, warnings included, when it may go ahead.
+    pub fn check_change(&self, old: &Config, new: &Config, force: bool) -> Result<SafetyReport, UnsafeChange> {
+        let report = self.analyze_change(old, new);
+        if !report.safe {
+            if !force {
+                return Err(UnsafeChange { critical_issues: report.critical_issues });
+            }
+            let params: Vec<&str> = report.critical_issues.iter().map(|i| i.param.as_str()).collect();
+            warn!("Forcing unsafe config change of {}", params.join(", "));
+        }
+        Ok(report)
+    }
+
+    fn issues(&self, settings: &Settings) -> Vec<SafetyIssue> {
+        let mut issues = Vec::new();
+        let mut issue = |severity, param: &str, message: String, recommendation: &str| {
+            issues.push(SafetyIssue {
+                severity,
+                param: param.to_string(),
+                message,
+                recommendation: recommendation.to_string(),
+            });
+        };
+
+        if settings.ignore_difficulty {
+            issue(
+                Severity::Critical,
+                "ignore_difficulty",
+                "已禁用难度验证，可能导致不公平的PPLNS收益分配".to_string(),
+                "设置为 false",
+            );
+        }
+
+        let ttl = settings.pplns_ttl_days;
+        if ttl < STANDARD_PPLNS_TTL_DAYS {
+            issue(
+                Severity::Critical,
+                "pplns_ttl_days",
+                format!(
+                    "TTL={}天过短，标准为{}天，矿工可能损失约{}%的收益",
+                    ttl,
+                    STANDARD_PPLNS_TTL_DAYS,
+                    (STANDARD_PPLNS_TTL_DAYS - ttl) * 100 / STANDARD_PPLNS_TTL_DAYS
+                ),
+                "设置为 7",
+            );
+        }
+
+        let (donation, fee) = (settings.donation_bps, settings.fee_bps);
+        for (param, bps, name) in [("donation", donation, "捐赠"), ("fee", fee, "手续费")] {
+            if bps as u32 >= FULL_REWARD_BPS {
+                issue(
+                    Severity::Critical,
+                    param,
+                    format!("{}={}意味着100%{}，矿工收益为0！", param, bps, name),
+                    &format!("设置为0或注释掉{}", param),
+                );
+            } else if bps > HIGH_FEE_BPS {
+                issue(
+                    Severity::Warning,
+                    param,
+                    format!("{}比例较高: {}%", name, bps / 100),
+                    &format!("考虑设置为0-{}(0-{}%)", HIGH_FEE_BPS, HIGH_FEE_BPS / 100),
+                );
+            }
+        }
+        let total = fee as u32 + donation as u32;
+        if total >= FULL_REWARD_BPS && (fee as u32) < FULL_REWARD_BPS && (donation as u32) < FULL_REWARD_BPS {
+            issue(
+                Severity::Critical,
+                "fee",
+                format!("fee与donation合计{}%，矿工收益为0！", total / 100),
+                "降低fee或donation，使合计低于100%",
+            );
+        }
+
+        let (start, minimum) = (settings.start_difficulty, settings.minimum_difficulty);
+        if start < minimum {
+            issue(
+                Severity::Warning,
+                "start_difficulty",
+                format!("start_difficulty={}低于minimum_difficulty={}，新连接的初始难度将不一致", start, minimum),
+                "设置为不低于 minimum_difficulty",
+            );
+        }
+
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_issues_and_change_gate() {
+        let analyzer = SafetyAnalyzer::new();
+        let safe = Settings {
+            ignore_difficulty: false,
+            pplns_ttl_days: 7,
+            donation_bps: 0,
+            fee_bps: 100,
+            start_difficulty: 32,
+            minimum_difficulty: 16,
+        };
+        assert!(analyzer.issues(&safe).is_empty());
+
+        let unsafe_settings = Settings { pplns_ttl_days: 3, fee_bps: 6000, donation_bps: 5000, ..safe };
+        let report = SafetyReport::new(analyzer.issues(&unsafe_settings));
+        assert!(!report.safe);
+        let critical: Vec<&str> = report.critical_issues.iter().map(|i| i.param.as_str()).collect();
+        assert_eq!(critical, vec!["pplns_ttl_days", "fee"]);
+        assert_eq!(report.warnings.len(), 2);
+
+        // Only what a change introduces counts
+        let short_ttl = Settings { pplns_ttl_days: 3, ..safe };
+        assert!(analyzer.compare(&short_ttl, &Settings { fee_bps: 200, ..short_ttl }).safe);
+        assert!(!analyzer.compare(&short_ttl, &Settings { pplns_ttl_days: 2, ..short_ttl }).safe);
+        let raised_fee = analyzer.compare(&safe, &Settings { fee_bps: 800, ..safe });
+        assert!(raised_fee.safe);
+        assert_eq!(raised_fee.warnings[0].param, "fee");
+        assert_eq!(serde_json::to_value(&raised_fee.warnings[0]).unwrap()["severity"], "warning");
+    }
+}
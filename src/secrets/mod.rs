@@ -0,0 +1,331 @@
+// Secret Management for DMPool
+// Looks up secrets in env vars, files or an external store, and holds the JWT signing keys
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Directory `FileSecretProvider` reads by default, where Docker and
+/// Kubernetes mount secrets
+const DEFAULT_SECRETS_DIR: &str = "/run/secrets";
+/// Shortest JWT signing secret accepted
+pub const MIN_JWT_SECRET_LEN: usize = 32;
+
+/// Source of named secrets such as `JWT_SECRET`
+///
+/// Implement this to fetch secrets from a KMS or another store and add it
+/// with `SecretStore::with_provider`.
+#[async_trait]
+pub trait SecretProvider: Send + Sync {
+    /// Short name shown in logs and the secrets status
+    fn name(&self) -> &str;
+
+    /// Value of secret `name`, or `None` if this provider doesn't have it
+    async fn get(&self, name: &str) -> Result<Option<String>>;
+}
+
+/// Secrets from environment variables of the same name
+pub struct EnvSecretProvider;
+
+#[async_trait]
+impl SecretProvider for EnvSecretProvider {
+    fn name(&self) -> &str {
+        "env"
+    }
+
+    async fn get(&self, name: &str) -> Result<Option<String>> {
+        Ok(std::env::var(name).ok())
+    }
+}
+
+/// Secrets from files named after them in a directory, without trailing newlines
+pub struct FileSecretProvider {
+    dir: PathBuf,
+}
+
+impl FileSecretProvider {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+}
+
+#[async_trait]
+impl SecretProvider for FileSecretProvider {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    async fn get(&self, name: &str) -> Result<Option<String>> {
+        let path = self.dir.join(name);
+        match tokio::fs::read_to_string(&path).await {
+            Ok(value) => Ok(Some(value.trim_end_matches(['\r', '\n']).to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read secret file {:?}", path)),
+        }
+    }
+}
+
+/// Secrets from one HashiCorp Vault KV v2 secret, one key per secret name
+pub struct VaultSecretProvider {
+    http: reqwest::Client,
+    /// Full URL of the secret, e.g. `https://vault:8200/v1/secret/data/dmpool`
+    url: String,
+    token: String,
+}
+
+impl VaultSecretProvider {
+    pub fn new(addr: &str, mount: &str, path: &str, token: String) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap_or_else(|_| reqwest::Client::new());
+        Self {
+            http,
+            url: format!("{}/v1/{}/data/{}", addr.trim_end_matches('/'), mount, path.trim_matches('/')),
+            token,
+        }
+    }
+
+    /// Read `VAULT_ADDR`, `VAULT_TOKEN`, `VAULT_MOUNT` (default `secret`) and
+    /// `VAULT_SECRET_PATH` (default `dmpool`)
+    pub fn from_env() -> Result<Self> {
+        let addr = std::env::var("VAULT_ADDR").map_err(|_| anyhow!("VAULT_ADDR must be set for the vault secret provider"))?;
+        let token = std::env::var("VAULT_TOKEN").map_err(|_| anyhow!("VAULT_TOKEN must be set for the vault secret provider"))?;
+        let mount = std::env::var("VAULT_MOUNT").unwrap_or_else(|_| "secret".to_string());
+        let path = std::env::var("VAULT_SECRET_PATH").unwrap_or_else(|_| "dmpool".to_string());
+        Ok(Self::new(&addr, &mount, &path, token))
+    }
+}
+
+#[async_trait]
+impl SecretProvider for VaultSecretProvider {
+    fn name(&self) -> &str {
+        "vault"
+    }
+
+    async fn get(&self, name: &str) -> Result<Option<String>> {
+        let response = self.http.get(&self.url)
+            .header("X-Vault-Token", &self.token)
+            .send()
+            .await
+            .context("Failed to reach Vault")?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let body: serde_json::Value = response.error_for_status()
+            .context("Vault refused the secret request")?
+            .json()
+            .await
+            .context("Invalid Vault response")?;
+        Ok(body["data"]["data"][name].as_str().map(str::to_string))
+    }
+}
+
+/// Providers asked for a secret in order; the first that has it wins
+#[derive(Clone)]
+pub struct SecretStore {
+    providers: Vec<Arc<dyn SecretProvider>>,
+}
+
+impl Default for SecretStore {
+    fn default() -> Self {
+        Self::empty().with_provider(Arc::new(EnvSecretProvider))
+    }
+}
+
+impl SecretStore {
+    /// Store without providers
+    pub fn empty() -> Self {
+        Self { providers: Vec::new() }
+    }
+
+    /// Ask `provider` after the ones already added
+    pub fn with_provider(mut self, provider: Arc<dyn SecretProvider>) -> Self {
+        self.providers.push(provider);
+        self
+    }
+
+    /// Read `SECRET_PROVIDERS`, a comma-separated list of `env`, `file` and
+    /// `vault` (default `env`); the file provider reads `SECRETS_DIR`
+    pub fn from_env() -> Result<Self> {
+        let names = std::env::var("SECRET_PROVIDERS").unwrap_or_else(|_| "env".to_string());
+        let mut store = Self::empty();
+        for name in names.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            let provider: Arc<dyn SecretProvider> = match name {
+                "env" => Arc::new(EnvSecretProvider),
+                "file" => Arc::new(FileSecretProvider::new(PathBuf::from(
+                    std::env::var("SECRETS_DIR").unwrap_or_else(|_| DEFAULT_SECRETS_DIR.to_string()),
+                ))),
+                "vault" => Arc::new(VaultSecretProvider::from_env()?),
+                other => return Err(anyhow!("Unknown secret provider in SECRET_PROVIDERS: {}", other)),
+            };
+            store = store.with_provider(provider);
+        }
+        if store.providers.is_empty() {
+            return Err(anyhow!("SECRET_PROVIDERS lists no provider"));
+        }
+        Ok(store)
+    }
+
+    pub fn provider_names(&self) -> Vec<String> {
+        self.providers.iter().map(|p| p.name().to_string()).collect()
+    }
+
+    /// Value of secret `name` from the first provider that has it
+    pub async fn get(&self, name: &str) -> Result<Option<String>> {
+        for provider in &self.providers {
+            let value = provider.get(name).await
+                .with_context(|| format!("Secret provider {} failed to look up {}", provider.name(), name))?;
+            if value.is_some() {
+                return Ok(value);
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// HMAC key signing JWTs, named by its key ID in token headers
+#[derive(Clone)]
+pub struct JwtKey {
+    kid: String,
+    secret: String,
+}
+
+impl fmt::Debug for JwtKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "JwtKey({})", self.kid)
+    }
+}
+
+impl JwtKey {
+    /// Key whose ID is derived from the secret, so it is stable across restarts
+    pub fn new(secret: String) -> Self {
+        let digest = Sha256::digest(secret.as_bytes());
+        let kid = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+        Self { kid, secret }
+    }
+
+    pub fn kid(&self) -> &str {
+        &self.kid
+    }
+
+    pub fn secret(&self) -> &[u8] {
+        self.secret.as_bytes()
+    }
+}
+
+/// Key new tokens are signed with, and retired keys whose tokens still validate
+#[derive(Clone, Debug)]
+pub struct JwtKeySet {
+    pub current: JwtKey,
+    pub previous: Vec<JwtKey>,
+}
+
+impl JwtKeySet {
+    pub fn new(current: JwtKey) -> Self {
+        Self { current, previous: Vec::new() }
+    }
+
+    /// Keep validating tokens signed with `key`
+    pub fn with_previous(mut self, key: JwtKey) -> Self {
+        if key.kid != self.current.kid && self.find(&key.kid).is_none() {
+            self.previous.push(key);
+        }
+        self
+    }
+
+    /// Keys from the `JWT_SECRET` secret and the comma-separated
+    /// `JWT_PREVIOUS_SECRETS`; `None` if `JWT_SECRET` is missing
+    pub async fn from_secrets(secrets: &SecretStore) -> Result<Option<Self>> {
+        let Some(current) = secrets.get("JWT_SECRET").await? else {
+            return Ok(None);
+        };
+        let mut keys = Self::new(JwtKey::new(current));
+        if let Some(previous) = secrets.get("JWT_PREVIOUS_SECRETS").await? {
+            for secret in previous.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                keys = keys.with_previous(JwtKey::new(secret.to_string()));
+            }
+        }
+        keys.validate()?;
+        Ok(Some(keys))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for key in std::iter::once(&self.current).chain(&self.previous) {
+            if key.secret.len() < MIN_JWT_SECRET_LEN {
+                return Err(anyhow!(
+                    "JWT secret {} must be at least {} characters long, not {}",
+                    key.kid, MIN_JWT_SECRET_LEN, key.secret.len(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Key with ID `kid`
+    pub fn find(&self, kid: &str) -> Option<&JwtKey> {
+        std::iter::once(&self.current).chain(&self.previous).find(|key| key.kid == kid)
+    }
+
+    /// Every key, current first
+    pub fn keys(&self) -> impl Iterator<Item = &JwtKey> {
+        std::iter::once(&self.current).chain(&self.previous)
+    }
+
+    /// `next`, also keeping the keys of this set so tokens signed before a
+    /// rotation validate until the process restarts
+    pub fn rotate_to(&self, next: JwtKeySet) -> JwtKeySet {
+        self.keys().cloned().fold(next, JwtKeySet::with_previous)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fixed secrets for tests
+    struct StaticProvider(Vec<(&'static str, &'static str)>);
+
+    #[async_trait]
+    impl SecretProvider for StaticProvider {
+        fn name(&self) -> &str {
+            "static"
+        }
+
+        async fn get(&self, name: &str) -> Result<Option<String>> {
+            Ok(self.0.iter().find(|(n, _)| *n == name).map(|(_, v)| v.to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_provider_order_and_jwt_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("JWT_SECRET"), "file-secret-0123456789abcdefghijklmnop\n").unwrap();
+        let store = SecretStore::empty()
+            .with_provider(Arc::new(FileSecretProvider::new(dir.path().to_path_buf())))
+            .with_provider(Arc::new(StaticProvider(vec![
+                ("JWT_SECRET", "static-secret-0123456789abcdefghijklmn"),
+                ("JWT_PREVIOUS_SECRETS", "old-secret-0123456789abcdefghijklmnopq, short"),
+            ])));
+        assert_eq!(store.provider_names(), ["file", "static"]);
+        assert_eq!(store.get("JWT_SECRET").await.unwrap().as_deref(), Some("file-secret-0123456789abcdefghijklmnop"));
+        assert_eq!(store.get("MISSING").await.unwrap(), None);
+
+        // The short previous secret is refused
+        assert!(JwtKeySet::from_secrets(&store).await.is_err());
+
+        let old = JwtKeySet::new(JwtKey::new("old-secret-0123456789abcdefghijklmnopq".to_string()));
+        let new = JwtKeySet::new(JwtKey::new("new-secret-0123456789abcdefghijklmnopq".to_string()));
+        assert_eq!(old.current.kid().len(), 16);
+        assert_ne!(old.current.kid(), new.current.kid());
+        let rotated = old.rotate_to(new.clone());
+        assert_eq!(rotated.current.kid(), new.current.kid());
+        assert!(rotated.find(old.current.kid()).is_some());
+        // Rotating to the same key keeps a single entry
+        assert!(rotated.rotate_to(new).previous.len() == 1);
+    }
+}
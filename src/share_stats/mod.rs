@@ -0,0 +1,504 @@
+// Share Statistics for DMPool
+// Accepted, stale, duplicate and invalid share counts per worker, with reject ratios, orphaned share blocks
+// and share difficulty distribution
+
+use p2poolv2_lib::accounting::simple_pplns::SimplePplnsShare;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+
+/// Orphaned and uncle share blocks kept in memory at most
+const MAX_TRACKED_ORPHANS: usize = 100_000;
+/// Width of the buckets reported share outcomes are counted in
+const OUTCOME_BUCKET_SECS: u64 = 60;
+/// Reported share outcomes are kept this long
+pub const MAX_OUTCOME_WINDOW_SECS: u64 = 24 * 3600;
+
+/// Share rate above which a worker still at minimum difficulty should have
+/// been retargeted
+pub const STUCK_SHARES_PER_MINUTE: f64 = 6.0;
+
+/// How the pool handled a submitted share
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShareOutcome {
+    Accepted,
+    /// Submitted for a job that was no longer current
+    Stale,
+    /// Same work submitted again
+    Duplicate,
+    /// Below the share target or otherwise failing validation
+    Invalid,
+}
+
+/// Share counts of one worker
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct WorkerShareStats {
+    pub accepted: u64,
+    pub stale: u64,
+    pub duplicate: u64,
+    #[serde(default)]
+    pub invalid: u64,
+}
+
+impl WorkerShareStats {
+    pub fn record(&mut self, outcome: ShareOutcome) {
+        match outcome {
+            ShareOutcome::Accepted => self.accepted += 1,
+            ShareOutcome::Stale => self.stale += 1,
+            ShareOutcome::Duplicate => self.duplicate += 1,
+            ShareOutcome::Invalid => self.invalid += 1,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.accepted + self.rejected()
+    }
+
+    pub fn rejected(&self) -> u64 {
+        self.stale + self.duplicate + self.invalid
+    }
+
+    /// Fraction of submitted shares that were rejected, 0 when nothing was submitted
+    pub fn reject_ratio(&self) -> f64 {
+        match self.total() {
+            0 => 0.0,
+            total => self.rejected() as f64 / total as f64,
+        }
+    }
+
+    pub fn merge(&mut self, other: &WorkerShareStats) {
+        self.accepted += other.accepted;
+        self.stale += other.stale;
+        self.duplicate += other.duplicate;
+        self.invalid += other.invalid;
+    }
+}
+
+/// Key of a worker in share statistics
+pub fn worker_key(address: &str, worker: &str) -> String {
+    format!("{}.{}", address, worker)
+}
+
+/// Count outcomes of stored shares per worker
+///
+/// The store only keeps shares the pool accepted, so stale shares can't be
+/// seen here; a share whose job, extranonce2 and nonce repeat an earlier one
+/// from the same address counts as a duplicate.
+pub fn derive_from_shares(shares: &[SimplePplnsShare]) -> HashMap<String, WorkerShareStats> {
+    let mut seen: HashSet<(&str, &str, &str, &str)> = HashSet::new();
+    let mut stats: HashMap<String, WorkerShareStats> = HashMap::new();
+    for share in shares {
+        let Some(address) = share.btcaddress.as_deref() else {
+            continue;
+        };
+        let worker = share.workername.as_deref().unwrap_or("worker");
+        let work = (address, share.job_id.as_str(), share.extranonce2.as_str(), share.nonce.as_str());
+        let outcome = if seen.insert(work) {
+            ShareOutcome::Accepted
+        } else {
+            ShareOutcome::Duplicate
+        };
+        stats.entry(worker_key(address, worker)).or_default().record(outcome);
+    }
+    stats
+}
+
+/// Outcome counts reported by the stratum layer as shares are validated
+///
+/// Counted in one-minute buckets over the last day, so counts can be taken
+/// over the window a view or alert rule covers.
+#[derive(Default)]
+pub struct ShareStatsTracker {
+    /// Start of each bucket and its counts per worker key, oldest first
+    buckets: Mutex<VecDeque<(u64, HashMap<String, WorkerShareStats>)>>,
+}
+
+impl ShareStatsTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, address: &str, worker: &str, outcome: ShareOutcome) {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.record_at(address, worker, outcome, now);
+    }
+
+    /// Record an outcome reported at unix time `now`
+    pub fn record_at(&self, address: &str, worker: &str, outcome: ShareOutcome, now: u64) {
+        let start = now - now % OUTCOME_BUCKET_SECS;
+        let Ok(mut buckets) = self.buckets.lock() else {
+            return;
+        };
+        // Reports arrive in time order; a late one counts in the newest bucket
+        if buckets.back().is_none_or(|(newest, _)| *newest < start) {
+            buckets.push_back((start, HashMap::new()));
+        }
+        while buckets.front().is_some_and(|(oldest, _)| oldest + MAX_OUTCOME_WINDOW_SECS <= start) {
+            buckets.pop_front();
+        }
+        if let Some((_, stats)) = buckets.back_mut() {
+            stats.entry(worker_key(address, worker)).or_default().record(outcome);
+        }
+    }
+
+    /// Counts per worker key of the buckets ending after unix time `start`
+    pub fn since(&self, start: u64) -> HashMap<String, WorkerShareStats> {
+        let mut totals: HashMap<String, WorkerShareStats> = HashMap::new();
+        if let Ok(buckets) = self.buckets.lock() {
+            for (_, stats) in buckets.iter().filter(|(bucket, _)| bucket + OUTCOME_BUCKET_SECS > start) {
+                for (key, counts) in stats {
+                    totals.entry(key.clone()).or_default().merge(counts);
+                }
+            }
+        }
+        totals
+    }
+}
+
+/// How a share block missed the main sharechain
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrphanKind {
+    /// Referenced by a later share block, so it is still credited
+    Uncle,
+    /// Left out of the sharechain; its work earns nothing
+    Orphan,
+}
+
+/// A share block that did not end up in the main sharechain
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OrphanedShare {
+    pub hash: String,
+    pub address: String,
+    pub worker: String,
+    pub difficulty: u64,
+    pub kind: OrphanKind,
+    /// Unix time the share was found at
+    pub n_time: u64,
+}
+
+/// Orphaned and uncle share blocks reported by the sharechain layer, oldest first
+#[derive(Default)]
+pub struct OrphanTracker {
+    shares: Mutex<VecDeque<OrphanedShare>>,
+}
+
+impl OrphanTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, share: OrphanedShare) {
+        if let Ok(mut shares) = self.shares.lock() {
+            if shares.len() >= MAX_TRACKED_ORPHANS {
+                shares.pop_front();
+            }
+            shares.push_back(share);
+        }
+    }
+
+    /// Shares found at or after `start`
+    pub fn since(&self, start: u64) -> Vec<OrphanedShare> {
+        self.shares.lock()
+            .map(|shares| shares.iter().filter(|s| s.n_time >= start).cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Orphan counts of one miner, with what its orphans cost it
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct MinerOrphanStats {
+    pub address: String,
+    /// Credited shares, uncles included
+    pub shares: u64,
+    pub uncles: u64,
+    pub orphans: u64,
+    /// Orphans out of all shares the miner found
+    pub orphan_rate: f64,
+    pub uncle_rate: f64,
+    pub orphaned_difficulty: u64,
+    /// Payout per block compared to a sharechain without orphans; negative is a loss
+    pub payout_impact_sats: i64,
+}
+
+/// Orphans of the pool and of each miner over a PPLNS window
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct OrphanReport {
+    pub shares: u64,
+    pub uncles: u64,
+    pub orphans: u64,
+    pub orphan_rate: f64,
+    pub uncle_rate: f64,
+    /// Block reward the payout impact is estimated for
+    pub reward_sats: u64,
+    /// Miners with uncles or orphans, biggest loss first
+    pub miners: Vec<MinerOrphanStats>,
+}
+
+/// Compare what each miner is paid for `credited` with what it would be paid
+/// if its orphaned shares had been credited too
+///
+/// `credited` are the window's PPLNS shares, which include uncles but not orphans.
+pub fn orphan_report(credited: &[SimplePplnsShare], orphaned: &[OrphanedShare], reward_sats: u64) -> OrphanReport {
+    #[derive(Default)]
+    struct Totals {
+        shares: u64,
+        difficulty: u64,
+        uncles: u64,
+        orphans: u64,
+        orphaned_difficulty: u64,
+    }
+    let mut miners: BTreeMap<&str, Totals> = BTreeMap::new();
+    for share in credited {
+        if let Some(address) = share.btcaddress.as_deref() {
+            let totals = miners.entry(address).or_default();
+            totals.shares += 1;
+            totals.difficulty += share.difficulty;
+        }
+    }
+    for share in orphaned {
+        let totals = miners.entry(share.address.as_str()).or_default();
+        match share.kind {
+            OrphanKind::Uncle => totals.uncles += 1,
+            OrphanKind::Orphan => {
+                totals.orphans += 1;
+                totals.orphaned_difficulty += share.difficulty;
+            }
+        }
+    }
+
+    let credited_difficulty: u64 = miners.values().map(|t| t.difficulty).sum();
+    let all_difficulty = credited_difficulty + miners.values().map(|t| t.orphaned_difficulty).sum::<u64>();
+    let rate = |count: u64, found: u64| if found == 0 { 0.0 } else { count as f64 / found as f64 };
+    let payout = |difficulty: u64, total: u64| {
+        if total == 0 { 0.0 } else { reward_sats as f64 * difficulty as f64 / total as f64 }
+    };
+
+    let mut report = OrphanReport { reward_sats, ..Default::default() };
+    for (address, totals) in &miners {
+        report.shares += totals.shares;
+        report.uncles += totals.uncles;
+        report.orphans += totals.orphans;
+        if totals.uncles == 0 && totals.orphans == 0 {
+            continue;
+        }
+        let found = totals.shares + totals.orphans;
+        let actual = payout(totals.difficulty, credited_difficulty);
+        let without_orphans = payout(totals.difficulty + totals.orphaned_difficulty, all_difficulty);
+        report.miners.push(MinerOrphanStats {
+            address: address.to_string(),
+            shares: totals.shares,
+            uncles: totals.uncles,
+            orphans: totals.orphans,
+            orphan_rate: rate(totals.orphans, found),
+            uncle_rate: rate(totals.uncles, found),
+            orphaned_difficulty: totals.orphaned_difficulty,
+            payout_impact_sats: (actual - without_orphans).round() as i64,
+        });
+    }
+    let found = report.shares + report.orphans;
+    report.orphan_rate = rate(report.orphans, found);
+    report.uncle_rate = rate(report.uncles, found);
+    report.miners.sort_by_key(|m| m.payout_impact_sats);
+    report
+}
+
+/// Shares and workers within a power-of-two difficulty range
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct DifficultyBucket {
+    pub min_difficulty: u64,
+    /// Exclusive
+    pub max_difficulty: u64,
+    pub shares: u64,
+    /// Workers whose latest share falls in the range
+    pub workers: u64,
+}
+
+/// Vardiff level of one worker over the window
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct WorkerDifficulty {
+    pub address: String,
+    pub worker: String,
+    /// Difficulty of the worker's latest share
+    pub difficulty: u64,
+    pub min_difficulty: u64,
+    pub max_difficulty: u64,
+    pub shares: u64,
+    /// How often the difficulty changed between consecutive shares
+    pub retargets: u64,
+    /// Every share at the pool minimum while submitting faster than
+    /// `STUCK_SHARES_PER_MINUTE`
+    pub stuck_at_minimum: bool,
+}
+
+/// Shares and workers by difficulty
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct DifficultyDistribution {
+    pub window_secs: u64,
+    pub minimum_difficulty: u64,
+    pub shares: u64,
+    /// Non-empty buckets, lowest first
+    pub buckets: Vec<DifficultyBucket>,
+    /// Workers stuck at minimum first, then by share count
+    pub workers: Vec<WorkerDifficulty>,
+    pub stuck_workers: u64,
+}
+
+/// Bucket shares of the last `window_secs` by difficulty, and each worker by
+/// its vardiff level
+pub fn difficulty_distribution(
+    shares: &[SimplePplnsShare],
+    minimum_difficulty: u64,
+    window_secs: u64,
+) -> DifficultyDistribution {
+    let bucket_of = |difficulty: u64| difficulty.max(1).ilog2();
+    let mut buckets: BTreeMap<u32, DifficultyBucket> = BTreeMap::new();
+    let mut workers: BTreeMap<String, WorkerDifficulty> = BTreeMap::new();
+
+    let mut sorted: Vec<&SimplePplnsShare> = shares.iter().collect();
+    sorted.sort_by_key(|s| s.n_time);
+    for share in sorted {
+        buckets.entry(bucket_of(share.difficulty)).or_default().shares += 1;
+        let Some(address) = share.btcaddress.as_deref() else {
+            continue;
+        };
+        let worker = share.workername.as_deref().unwrap_or("worker");
+        let stats = workers.entry(worker_key(address, worker)).or_insert_with(|| WorkerDifficulty {
+            address: address.to_string(),
+            worker: worker.to_string(),
+            difficulty: share.difficulty,
+            min_difficulty: share.difficulty,
+            max_difficulty: share.difficulty,
+            ..Default::default()
+        });
+        if share.difficulty != stats.difficulty {
+            stats.retargets += 1;
+        }
+        stats.difficulty = share.difficulty;
+        stats.min_difficulty = stats.min_difficulty.min(share.difficulty);
+        stats.max_difficulty = stats.max_difficulty.max(share.difficulty);
+        stats.shares += 1;
+    }
+
+    let minutes = window_secs.max(1) as f64 / 60.0;
+    let mut workers: Vec<WorkerDifficulty> = workers.into_values().collect();
+    for worker in &mut workers {
+        buckets.entry(bucket_of(worker.difficulty)).or_default().workers += 1;
+        worker.stuck_at_minimum = worker.max_difficulty <= minimum_difficulty
+            && worker.shares as f64 / minutes > STUCK_SHARES_PER_MINUTE;
+    }
+    workers.sort_by(|a, b| b.stuck_at_minimum.cmp(&a.stuck_at_minimum).then(b.shares.cmp(&a.shares)));
+
+    DifficultyDistribution {
+        window_secs,
+        minimum_difficulty,
+        shares: shares.len() as u64,
+        buckets: buckets.into_iter()
+            .map(|(exponent, bucket)| DifficultyBucket {
+                min_difficulty: 1 << exponent,
+                max_difficulty: 1u64.checked_shl(exponent + 1).unwrap_or(u64::MAX),
+                ..bucket
+            })
+            .collect(),
+        stuck_workers: workers.iter().filter(|w| w.stuck_at_minimum).count() as u64,
+        workers,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn share(worker: &str, nonce: &str) -> SimplePplnsShare {
+        SimplePplnsShare {
+            btcaddress: Some("bc1qminer".to_string()),
+            workername: Some(worker.to_string()),
+            user_id: 1,
+            difficulty: 1,
+            n_time: 1_700_000_000,
+            job_id: "job".to_string(),
+            extranonce2: "00000001".to_string(),
+            nonce: nonce.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_duplicates_and_reject_ratio() {
+        let shares = vec![share("rig1", "01"), share("rig1", "02"), share("rig1", "01"), share("rig2", "01")];
+        let stats = derive_from_shares(&shares);
+        let rig1 = stats[&worker_key("bc1qminer", "rig1")];
+        assert_eq!(rig1, WorkerShareStats { accepted: 2, stale: 0, duplicate: 1, invalid: 0 });
+        assert!((rig1.reject_ratio() - 1.0 / 3.0).abs() < 1e-9);
+        // Another worker of the same address resubmitting the work is a duplicate too
+        assert_eq!(stats[&worker_key("bc1qminer", "rig2")].duplicate, 1);
+
+        // Reported outcomes only count within the window asked for
+        let tracker = ShareStatsTracker::new();
+        let now = 1_700_000_000;
+        tracker.record_at("bc1qminer", "rig1", ShareOutcome::Stale, now - 3600);
+        tracker.record_at("bc1qminer", "rig1", ShareOutcome::Stale, now - 60);
+        tracker.record_at("bc1qminer", "rig1", ShareOutcome::Accepted, now);
+        let key = worker_key("bc1qminer", "rig1");
+        assert_eq!(tracker.since(now - 600)[&key], WorkerShareStats { accepted: 1, stale: 1, duplicate: 0, invalid: 0 });
+        assert_eq!(tracker.since(now - 7200)[&key].stale, 2);
+        // After a day they are dropped
+        tracker.record_at("bc1qminer", "rig1", ShareOutcome::Accepted, now + MAX_OUTCOME_WINDOW_SECS - 1800);
+        assert_eq!(tracker.since(0)[&key], WorkerShareStats { accepted: 2, stale: 1, duplicate: 0, invalid: 0 });
+        assert_eq!(WorkerShareStats::default().reject_ratio(), 0.0);
+    }
+
+    #[test]
+    fn test_orphan_report() {
+        let mut credited = vec![share("rig1", "01"), share("rig1", "02"), share("rig1", "03")];
+        credited.push(SimplePplnsShare { btcaddress: Some("bc1qother".to_string()), ..share("rig", "04") });
+        let orphaned = |address: &str, kind: OrphanKind| OrphanedShare {
+            hash: "00".to_string(),
+            address: address.to_string(),
+            worker: "rig".to_string(),
+            difficulty: 1,
+            kind,
+            n_time: 1_700_000_000,
+        };
+
+        let tracker = OrphanTracker::new();
+        tracker.record(orphaned("bc1qother", OrphanKind::Orphan));
+        tracker.record(orphaned("bc1qminer", OrphanKind::Uncle));
+        assert_eq!(tracker.since(1_700_000_001), vec![]);
+        let report = orphan_report(&credited, &tracker.since(0), 1_000_000);
+        assert_eq!((report.shares, report.uncles, report.orphans), (4, 1, 1));
+        assert!((report.orphan_rate - 0.2).abs() < 1e-9);
+
+        // bc1qother is paid 1/4 of the reward instead of 2/5, and bc1qminer 3/4 instead of 3/5
+        assert_eq!(report.miners[0].address, "bc1qother");
+        assert!((report.miners[0].orphan_rate - 0.5).abs() < 1e-9);
+        assert_eq!(report.miners[0].payout_impact_sats, -150_000);
+        assert_eq!(report.miners[1].payout_impact_sats, 150_000);
+        assert_eq!(report.miners[1].uncles, 1);
+    }
+
+    #[test]
+    fn test_difficulty_distribution() {
+        let at = |worker: &str, difficulty: u64, n_time: u64| SimplePplnsShare {
+            difficulty,
+            n_time,
+            ..share(worker, &n_time.to_string())
+        };
+        // rig1 is retargeted from 1 to 1024; rig2 stays at the minimum with 10 shares a minute
+        let mut shares = vec![at("rig1", 1, 0), at("rig1", 1024, 60), at("rig1", 1500, 120)];
+        shares.extend((0..600).map(|t| at("rig2", 1, t)));
+        let dist = difficulty_distribution(&shares, 1, 600);
+
+        assert_eq!(dist.shares, 603);
+        assert_eq!(dist.buckets.len(), 2);
+        assert_eq!(dist.buckets[0], DifficultyBucket { min_difficulty: 1, max_difficulty: 2, shares: 601, workers: 1 });
+        assert_eq!(dist.buckets[1], DifficultyBucket { min_difficulty: 1024, max_difficulty: 2048, shares: 2, workers: 1 });
+        assert_eq!(dist.stuck_workers, 1);
+        assert_eq!(dist.workers[0].worker, "rig2");
+        let rig1 = &dist.workers[1];
+        assert_eq!((rig1.difficulty, rig1.min_difficulty, rig1.retargets, rig1.stuck_at_minimum), (1500, 1, 2, false));
+    }
+}
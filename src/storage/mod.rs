@@ -0,0 +1,495 @@
+// Store Maintenance for DMPool
+// Column family statistics and compaction of the pool's RocksDB store
+
+use crate::cron::CronExpr;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rocksdb::{ColumnFamily, IteratorMode, Options, WriteBatch, DB};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+use tracing::{info, warn};
+
+/// Compaction runs kept in the history
+const MAX_COMPACTION_HISTORY: usize = 50;
+
+/// Keys written per batch when copying a column family
+const COPY_BATCH_KEYS: usize = 10_000;
+
+/// Size and key count of one column family
+#[derive(Clone, Debug, Serialize)]
+pub struct ColumnFamilyStats {
+    pub name: String,
+    /// Size of its SST files on disk
+    pub sst_bytes: u64,
+    /// Estimated size of the live data; the rest of `sst_bytes` is reclaimable
+    pub live_data_bytes: u64,
+    pub estimated_keys: u64,
+    /// Data still in memory, not yet flushed to SST files
+    pub memtable_bytes: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct StoreStats {
+    pub path: PathBuf,
+    pub column_families: Vec<ColumnFamilyStats>,
+    pub sst_bytes: u64,
+    pub live_data_bytes: u64,
+    pub estimated_keys: u64,
+    /// Every file in the store directory, including logs and manifests
+    pub disk_bytes: u64,
+    pub collected_at: DateTime<Utc>,
+}
+
+/// Per column family statistics of the store at `path`
+///
+/// The store is opened read-only, so this works while the pool is running.
+pub fn store_stats(path: &Path) -> Result<StoreStats> {
+    let names = DB::list_cf(&Options::default(), path)
+        .with_context(|| format!("Failed to list column families of {}", path.display()))?;
+    let db = DB::open_cf_for_read_only(&Options::default(), path, &names, false)
+        .with_context(|| format!("Failed to open {} read-only", path.display()))?;
+
+    let mut column_families = Vec::with_capacity(names.len());
+    for name in &names {
+        let cf = db.cf_handle(name)
+            .ok_or_else(|| anyhow::anyhow!("Column family {} not opened", name))?;
+        let property = |property: &str| -> Result<u64> {
+            Ok(db.property_int_value_cf(cf, property)?.unwrap_or(0))
+        };
+        column_families.push(ColumnFamilyStats {
+            name: name.clone(),
+            sst_bytes: property("rocksdb.total-sst-files-size")?,
+            live_data_bytes: property("rocksdb.estimate-live-data-size")?,
+            estimated_keys: property("rocksdb.estimate-num-keys")?,
+            memtable_bytes: property("rocksdb.cur-size-all-mem-tables")?,
+        });
+    }
+
+    Ok(StoreStats {
+        path: path.to_path_buf(),
+        sst_bytes: column_families.iter().map(|cf| cf.sst_bytes).sum(),
+        live_data_bytes: column_families.iter().map(|cf| cf.live_data_bytes).sum(),
+        estimated_keys: column_families.iter().map(|cf| cf.estimated_keys).sum(),
+        column_families,
+        disk_bytes: directory_bytes(path)?,
+        collected_at: Utc::now(),
+    })
+}
+
+/// SST files of each column family of the store at `path`, relative to it
+///
+/// The store is opened read-only, so this works while the pool is running.
+pub fn column_family_files(path: &Path) -> Result<BTreeMap<String, Vec<String>>> {
+    let names = DB::list_cf(&Options::default(), path)
+        .with_context(|| format!("Failed to list column families of {}", path.display()))?;
+    let db = DB::open_cf_for_read_only(&Options::default(), path, &names, false)
+        .with_context(|| format!("Failed to open {} read-only", path.display()))?;
+
+    let mut files: BTreeMap<String, Vec<String>> = names.into_iter().map(|name| (name, Vec::new())).collect();
+    for file in db.live_files()? {
+        files.entry(file.column_family_name)
+            .or_default()
+            .push(file.name.trim_start_matches('/').to_string());
+    }
+    Ok(files)
+}
+
+/// What replacing one column family changed, or would change on a dry run
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ColumnFamilyCopy {
+    pub name: String,
+    /// Keys removed from the target
+    pub keys_deleted: u64,
+    /// Keys copied from the source
+    pub keys_written: u64,
+}
+
+fn column_family<'a>(db: &'a DB, name: &str) -> Result<&'a ColumnFamily> {
+    db.cf_handle(name).ok_or_else(|| anyhow::anyhow!("Column family {} not opened", name))
+}
+
+fn count_keys(db: &DB, name: &str) -> Result<u64> {
+    let cf = column_family(db, name)?;
+    let mut keys = 0;
+    for item in db.iterator_cf(cf, IteratorMode::Start) {
+        item?;
+        keys += 1;
+    }
+    Ok(keys)
+}
+
+/// Replace the column families `names` of the store at `target` with their
+/// contents in the store at `source`, leaving its other column families alone
+///
+/// `target` is opened read-write, which RocksDB refuses while the pool holds
+/// its lock; with `create` it is created if missing. Keys are written in
+/// batches, so an interrupted copy leaves a column family partly replaced
+/// until it is run again. With `dry_run` only the keys are counted.
+pub fn copy_column_families(
+    source: &Path,
+    target: &Path,
+    names: &[String],
+    create: bool,
+    dry_run: bool,
+) -> Result<Vec<ColumnFamilyCopy>> {
+    let source_names = DB::list_cf(&Options::default(), source)
+        .with_context(|| format!("Failed to list column families of {}", source.display()))?;
+    if let Some(missing) = names.iter().find(|name| !source_names.contains(name)) {
+        return Err(anyhow::anyhow!("Column family {} is not in the backup", missing));
+    }
+    let source_db = DB::open_cf_for_read_only(&Options::default(), source, &source_names, false)
+        .with_context(|| format!("Failed to open {} read-only", source.display()))?;
+    let mut target_names = if target.exists() {
+        DB::list_cf(&Options::default(), target)
+            .with_context(|| format!("Failed to list column families of {}", target.display()))?
+    } else if create {
+        Vec::new()
+    } else {
+        return Err(anyhow::anyhow!("No store at {}", target.display()));
+    };
+
+    if dry_run {
+        let target_db = if target_names.is_empty() {
+            None
+        } else {
+            Some(DB::open_cf_for_read_only(&Options::default(), target, &target_names, false)
+                .with_context(|| format!("Failed to open {} read-only", target.display()))?)
+        };
+        return names.iter()
+            .map(|name| Ok(ColumnFamilyCopy {
+                name: name.clone(),
+                keys_deleted: match &target_db {
+                    Some(db) if target_names.contains(name) => count_keys(db, name)?,
+                    _ => 0,
+                },
+                keys_written: count_keys(&source_db, name)?,
+            }))
+            .collect();
+    }
+
+    for name in names {
+        if !target_names.contains(name) {
+            target_names.push(name.clone());
+        }
+    }
+    let mut options = Options::default();
+    options.create_if_missing(create);
+    options.create_missing_column_families(true);
+    let target_db = DB::open_cf(&options, target, &target_names)
+        .with_context(|| format!("Failed to open {} for restoring; is the pool running?", target.display()))?;
+
+    let mut copies = Vec::with_capacity(names.len());
+    for name in names {
+        let (from, to) = (column_family(&source_db, name)?, column_family(&target_db, name)?);
+        let mut copy = ColumnFamilyCopy { name: name.clone(), keys_deleted: 0, keys_written: 0 };
+        let mut batch = WriteBatch::default();
+        for item in target_db.iterator_cf(to, IteratorMode::Start) {
+            let (key, _) = item?;
+            batch.delete_cf(to, key);
+            copy.keys_deleted += 1;
+            if batch.len() >= COPY_BATCH_KEYS {
+                target_db.write(std::mem::take(&mut batch))?;
+            }
+        }
+        for item in source_db.iterator_cf(from, IteratorMode::Start) {
+            let (key, value) = item?;
+            batch.put_cf(to, key, value);
+            copy.keys_written += 1;
+            if batch.len() >= COPY_BATCH_KEYS {
+                target_db.write(std::mem::take(&mut batch))?;
+            }
+        }
+        target_db.write(batch)?;
+        target_db.flush_cf(to)?;
+        info!("Restored column family {}: {} key(s) removed, {} written", name, copy.keys_deleted, copy.keys_written);
+        copies.push(copy);
+    }
+    Ok(copies)
+}
+
+/// Keys in each column family of the store at `path`
+///
+/// Every key is read, so every block's checksum is verified; this surfaces
+/// corruption that opening the store or reading a few keys doesn't. The store
+/// is opened read-only.
+pub fn key_counts(path: &Path) -> Result<BTreeMap<String, u64>> {
+    let names = DB::list_cf(&Options::default(), path)
+        .with_context(|| format!("Failed to list column families of {}", path.display()))?;
+    let db = DB::open_cf_for_read_only(&Options::default(), path, &names, false)
+        .with_context(|| format!("Failed to open {} read-only", path.display()))?;
+    names.iter()
+        .map(|name| {
+            let keys = count_keys(&db, name).with_context(|| format!("Failed to read column family {}", name))?;
+            Ok((name.clone(), keys))
+        })
+        .collect()
+}
+
+/// Open the store at `path` read-only and read the first and last key of
+/// every column family, a check cheap enough to run with every health check
+pub fn probe_store(path: &Path) -> Result<()> {
+    let names = DB::list_cf(&Options::default(), path)
+        .with_context(|| format!("Failed to list column families of {}", path.display()))?;
+    let db = DB::open_cf_for_read_only(&Options::default(), path, &names, false)
+        .with_context(|| format!("Failed to open {} read-only", path.display()))?;
+    for name in &names {
+        let cf = column_family(&db, name)?;
+        for mode in [IteratorMode::Start, IteratorMode::End] {
+            if let Some(item) = db.iterator_cf(cf, mode).next() {
+                item.with_context(|| format!("Failed to read column family {}", name))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn directory_bytes(path: &Path) -> Result<u64> {
+    let mut total = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        total += if metadata.is_dir() { directory_bytes(&entry.path())? } else { metadata.len() };
+    }
+    Ok(total)
+}
+
+/// Compacts every column family of a database
+///
+/// Compaction needs a read-write handle. Without one held in-process, the
+/// store is opened read-write by path, which RocksDB refuses while the pool
+/// holds its lock.
+pub trait StoreCompactor: Send + Sync {
+    fn compact(&self) -> Result<()>;
+}
+
+impl StoreCompactor for DB {
+    fn compact(&self) -> Result<()> {
+        for name in DB::list_cf(&Options::default(), self.path())? {
+            let cf = self.cf_handle(&name)
+                .ok_or_else(|| anyhow::anyhow!("Column family {} not opened", name))?;
+            self.compact_range_cf(cf, None::<&[u8]>, None::<&[u8]>);
+        }
+        Ok(())
+    }
+}
+
+fn compact_path(path: &Path) -> Result<()> {
+    let names = DB::list_cf(&Options::default(), path)
+        .with_context(|| format!("Failed to list column families of {}", path.display()))?;
+    let db = DB::open_cf(&Options::default(), path, &names)
+        .with_context(|| format!("Failed to open {} for compaction; is the pool running?", path.display()))?;
+    db.compact()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompactionTrigger {
+    Manual,
+    Scheduled,
+}
+
+/// One compaction of the store
+#[derive(Clone, Debug, Serialize)]
+pub struct CompactionRun {
+    pub trigger: CompactionTrigger,
+    pub requested_by: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    /// Store directory size before and after; `None` if it couldn't be read
+    pub disk_bytes_before: Option<u64>,
+    pub disk_bytes_after: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl CompactionRun {
+    /// Bytes freed on disk, negative if the store grew
+    pub fn reclaimed_bytes(&self) -> Option<i64> {
+        Some(self.disk_bytes_before? as i64 - self.disk_bytes_after? as i64)
+    }
+}
+
+/// Statistics, manual and scheduled compaction of one store
+pub struct StoreMaintenance {
+    path: PathBuf,
+    compactor: Option<Arc<dyn StoreCompactor>>,
+    schedule: Option<CronExpr>,
+    /// Held while a compaction runs, so two can't overlap
+    compacting: Mutex<()>,
+    history: RwLock<VecDeque<CompactionRun>>,
+}
+
+impl StoreMaintenance {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            compactor: None,
+            schedule: None,
+            compacting: Mutex::new(()),
+            history: RwLock::new(VecDeque::new()),
+        }
+    }
+
+    /// Compact through a read-write handle held in-process
+    pub fn with_compactor(mut self, compactor: Arc<dyn StoreCompactor>) -> Self {
+        self.compactor = Some(compactor);
+        self
+    }
+
+    /// Compact whenever `schedule` matches
+    pub fn with_schedule(mut self, schedule: CronExpr) -> Self {
+        self.schedule = Some(schedule);
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn schedule(&self) -> Option<&CronExpr> {
+        self.schedule.as_ref()
+    }
+
+    /// When the next scheduled compaction starts
+    pub fn next_scheduled(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.schedule.as_ref()?.next_after(after)
+    }
+
+    pub async fn stats(&self) -> Result<StoreStats> {
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || store_stats(&path)).await?
+    }
+
+    /// Compact every column family, recording the run in the history
+    ///
+    /// Fails without running if a compaction is already in progress; a failed
+    /// compaction is recorded and returned as `Ok` with its `error` set.
+    pub async fn compact(&self, trigger: CompactionTrigger, requested_by: Option<String>) -> Result<CompactionRun> {
+        let _compacting = self.compacting.try_lock()
+            .map_err(|_| anyhow::anyhow!("A compaction is already in progress"))?;
+
+        let started_at = Utc::now();
+        let path = self.path.clone();
+        let compactor = self.compactor.clone();
+        info!("Compacting store {} ({:?})", path.display(), trigger);
+        let (before, result, after) = tokio::task::spawn_blocking(move || {
+            let before = directory_bytes(&path).ok();
+            let result = match compactor {
+                Some(compactor) => compactor.compact(),
+                None => compact_path(&path),
+            };
+            (before, result, directory_bytes(&path).ok())
+        })
+        .await?;
+
+        let run = CompactionRun {
+            trigger,
+            requested_by,
+            started_at,
+            finished_at: Utc::now(),
+            disk_bytes_before: before,
+            disk_bytes_after: after,
+            error: result.err().map(|e| format!("{:#}", e)),
+        };
+        match &run.error {
+            Some(e) => warn!("Store compaction failed: {}", e),
+            None => info!("Store compaction finished, reclaimed {:?} bytes", run.reclaimed_bytes()),
+        }
+        let mut history = self.history.write().await;
+        history.push_front(run.clone());
+        history.truncate(MAX_COMPACTION_HISTORY);
+        Ok(run)
+    }
+
+    /// Whether a compaction is running
+    pub fn is_compacting(&self) -> bool {
+        self.compacting.try_lock().is_err()
+    }
+
+    /// Compaction runs, newest first
+    pub async fn history(&self) -> Vec<CompactionRun> {
+        self.history.read().await.iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_stats_and_compaction() {
+        let dir = TempDir::new().unwrap();
+        {
+            let mut opts = Options::default();
+            opts.create_if_missing(true);
+            opts.create_missing_column_families(true);
+            let db = DB::open_cf(&opts, dir.path(), ["shares"]).unwrap();
+            let shares = db.cf_handle("shares").unwrap();
+            for i in 0..1000u32 {
+                db.put_cf(shares, i.to_be_bytes(), [0u8; 64]).unwrap();
+            }
+            db.flush_cf(shares).unwrap();
+            for i in 0..900u32 {
+                db.delete_cf(shares, i.to_be_bytes()).unwrap();
+            }
+        }
+
+        let maintenance = StoreMaintenance::new(dir.path())
+            .with_schedule("0 4 * * 0".parse().unwrap());
+        let stats = maintenance.stats().await.unwrap();
+        let names: Vec<_> = stats.column_families.iter().map(|cf| cf.name.as_str()).collect();
+        assert_eq!(names, ["default", "shares"]);
+        assert!(stats.sst_bytes > 0 && stats.disk_bytes >= stats.sst_bytes);
+
+        let run = maintenance.compact(CompactionTrigger::Manual, Some("admin".to_string())).await.unwrap();
+        assert_eq!(run.error, None);
+        assert!(run.reclaimed_bytes().is_some());
+        let stats = maintenance.stats().await.unwrap();
+        let shares = stats.column_families.iter().find(|cf| cf.name == "shares").unwrap();
+        assert!(shares.estimated_keys <= 100);
+
+        // The store is locked while another process holds it read-write
+        let _held = DB::open_cf(&Options::default(), dir.path(), ["default", "shares"]).unwrap();
+        let run = maintenance.compact(CompactionTrigger::Scheduled, None).await.unwrap();
+        assert!(run.error.is_some());
+        assert_eq!(maintenance.history().await.len(), 2);
+        assert!(maintenance.next_scheduled(Utc::now()).is_some());
+    }
+
+    #[test]
+    fn test_copy_column_families() {
+        let source = TempDir::new().unwrap();
+        let target = TempDir::new().unwrap();
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        {
+            let db = DB::open_cf(&opts, source.path(), ["shares", "users"]).unwrap();
+            db.put_cf(db.cf_handle("shares").unwrap(), b"a", b"backed up").unwrap();
+            db.put_cf(db.cf_handle("users").unwrap(), b"u", b"backed up").unwrap();
+            db.flush_cf(db.cf_handle("shares").unwrap()).unwrap();
+        }
+        {
+            let db = DB::open_cf(&opts, target.path(), ["shares", "users"]).unwrap();
+            db.put_cf(db.cf_handle("shares").unwrap(), b"b", b"live").unwrap();
+            db.put_cf(db.cf_handle("users").unwrap(), b"u", b"live").unwrap();
+        }
+        assert!(column_family_files(source.path()).unwrap()["shares"].iter().all(|f| f.ends_with(".sst")));
+
+        let names = vec!["shares".to_string()];
+        let plan = copy_column_families(source.path(), target.path(), &names, false, true).unwrap();
+        assert_eq!((plan[0].keys_deleted, plan[0].keys_written), (1, 1));
+        copy_column_families(source.path(), target.path(), &names, false, false).unwrap();
+
+        let db = DB::open_cf(&Options::default(), target.path(), ["default", "shares", "users"]).unwrap();
+        let shares = db.cf_handle("shares").unwrap();
+        assert_eq!(db.get_cf(shares, b"a").unwrap(), Some(b"backed up".to_vec()));
+        assert_eq!(db.get_cf(shares, b"b").unwrap(), None);
+        // Column families not named are left alone
+        assert_eq!(db.get_cf(db.cf_handle("users").unwrap(), b"u").unwrap(), Some(b"live".to_vec()));
+        assert!(copy_column_families(source.path(), target.path(), &["blocks".to_string()], false, true).is_err());
+    }
+}
@@ -0,0 +1,397 @@
+// Store Doctor for DMPool
+// Detects RocksDB corruption, repairs a copy of the store and checks it against the last good backup
+
+use crate::backup::BackupManager;
+use crate::storage;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rocksdb::{Options, DB};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::path::{Path, PathBuf};
+use tokio::sync::{Mutex, RwLock};
+use tracing::{info, warn};
+
+/// Diagnoses kept in the history
+const MAX_REPORTS: usize = 20;
+/// Repaired copies kept in the staging directory; older ones are removed
+const MAX_STAGED_REPAIRS: usize = 3;
+/// File RocksDB holds locked while the store is open, left out of copies
+const LOCK_FILE: &str = "LOCK";
+
+/// Whether `error` is RocksDB reporting corrupted data, rather than e.g. a
+/// locked or missing store
+pub fn is_corruption(error: &str) -> bool {
+    error.contains("Corruption:") || error.to_ascii_lowercase().contains("checksum mismatch")
+}
+
+/// Corruption seen in the live store
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Detection {
+    pub at: DateTime<Utc>,
+    pub error: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DoctorTrigger {
+    Manual,
+    /// A health check found corruption
+    HealthCheck,
+}
+
+/// Outcome of a diagnosis
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DoctorVerdict {
+    /// Every record of the live store could be read
+    Healthy,
+    /// The repaired copy has at least the records of the last good backup in
+    /// every column family
+    Repaired,
+    /// The repaired copy has fewer records than the last good backup in some
+    /// column family
+    RecordsMissing,
+    /// The store was repaired, but there was no good backup to compare against
+    Unverified,
+    RepairFailed,
+}
+
+impl DoctorVerdict {
+    pub fn describe(self) -> &'static str {
+        match self {
+            DoctorVerdict::Healthy => "the store reads cleanly",
+            DoctorVerdict::Repaired => "a repaired copy matching the last good backup is staged",
+            DoctorVerdict::RecordsMissing => "the repaired copy has fewer records than the last good backup",
+            DoctorVerdict::Unverified => "a repaired copy is staged, but no good backup could be compared",
+            DoctorVerdict::RepairFailed => "repair failed",
+        }
+    }
+}
+
+/// Repair of a copy of the live store
+#[derive(Clone, Debug, Serialize)]
+pub struct RepairAttempt {
+    /// Where the repaired copy is; the live store is never changed
+    pub staging_dir: PathBuf,
+    /// Keys per column family of the repaired copy
+    pub records: BTreeMap<String, u64>,
+    pub error: Option<String>,
+}
+
+/// Keys per column family of the last good backup
+#[derive(Clone, Debug, Serialize)]
+pub struct BackupBaseline {
+    pub backup_id: String,
+    pub taken_at: DateTime<Utc>,
+    pub records: BTreeMap<String, u64>,
+}
+
+/// Records of one column family in the repaired copy against the backup
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RecordComparison {
+    pub column_family: String,
+    pub backup: Option<u64>,
+    pub repaired: Option<u64>,
+    /// Records the backup has beyond the repaired copy
+    pub missing: u64,
+}
+
+/// Compare keys per column family of a repaired copy against a backup
+///
+/// Counts can also shrink legitimately, e.g. through PPLNS pruning since the backup.
+pub fn compare_records(backup: &BTreeMap<String, u64>, repaired: &BTreeMap<String, u64>) -> Vec<RecordComparison> {
+    let names: BTreeSet<&String> = backup.keys().chain(repaired.keys()).collect();
+    names.into_iter()
+        .map(|name| {
+            let (backup, repaired) = (backup.get(name).copied(), repaired.get(name).copied());
+            RecordComparison {
+                column_family: name.clone(),
+                backup,
+                repaired,
+                missing: backup.unwrap_or(0).saturating_sub(repaired.unwrap_or(0)),
+            }
+        })
+        .collect()
+}
+
+/// One diagnosis of the store
+#[derive(Clone, Debug, Serialize)]
+pub struct DoctorReport {
+    pub trigger: DoctorTrigger,
+    pub requested_by: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    /// Corruption found reading the live store in full; `None` if it read cleanly
+    pub corruption: Option<String>,
+    /// Keys per column family of the live store, when it read cleanly
+    pub live_records: BTreeMap<String, u64>,
+    pub repair: Option<RepairAttempt>,
+    pub baseline: Option<BackupBaseline>,
+    /// Why the repaired copy couldn't be compared against a backup
+    pub baseline_error: Option<String>,
+    pub comparison: Vec<RecordComparison>,
+    pub verdict: DoctorVerdict,
+}
+
+/// Detected corruption and how the latest diagnosis ended
+#[derive(Clone, Debug, Serialize)]
+pub struct DoctorStatus {
+    /// Corruption not yet found fixed by a diagnosis
+    pub detection: Option<Detection>,
+    pub latest_verdict: Option<DoctorVerdict>,
+    pub running: bool,
+}
+
+/// Copy the files of the store at `source` to `staging`, repair the copy and count its keys
+fn repair_into(source: &Path, staging: &Path) -> Result<BTreeMap<String, u64>> {
+    std::fs::create_dir_all(staging)
+        .with_context(|| format!("Failed to create {}", staging.display()))?;
+    for entry in std::fs::read_dir(source).with_context(|| format!("Failed to read {}", source.display()))? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() && entry.file_name() != LOCK_FILE {
+            std::fs::copy(entry.path(), staging.join(entry.file_name()))
+                .with_context(|| format!("Failed to copy {}", entry.path().display()))?;
+        }
+    }
+    DB::repair(&Options::default(), staging)
+        .map_err(|e| anyhow::anyhow!("RocksDB repair of {} failed: {}", staging.display(), e))?;
+    storage::key_counts(staging)
+}
+
+/// Remove all but the newest `keep` repaired copies under `dir`
+fn prune_staged(dir: &Path, keep: usize) -> Result<()> {
+    let mut staged: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_dir())
+        .collect();
+    // Named by timestamp, so they sort oldest first
+    staged.sort();
+    for old in &staged[..staged.len().saturating_sub(keep)] {
+        std::fs::remove_dir_all(old)?;
+    }
+    Ok(())
+}
+
+/// Corruption detection and repair attempts for one store
+pub struct StoreDoctor {
+    path: PathBuf,
+    staging_dir: PathBuf,
+    detection: std::sync::Mutex<Option<Detection>>,
+    /// Held while a diagnosis runs, so two can't overlap
+    running: Mutex<()>,
+    reports: RwLock<VecDeque<DoctorReport>>,
+}
+
+impl StoreDoctor {
+    /// Look after the store at `path`, repairing copies under `staging_dir`
+    pub fn new(path: impl Into<PathBuf>, staging_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            staging_dir: staging_dir.into(),
+            detection: std::sync::Mutex::new(None),
+            running: Mutex::new(()),
+            reports: RwLock::new(VecDeque::new()),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read the first and last key of every column family, recording corruption it finds
+    pub async fn probe(&self) -> Result<()> {
+        let path = self.path.clone();
+        let result = tokio::task::spawn_blocking(move || storage::probe_store(&path)).await?;
+        if let Err(e) = &result {
+            let error = format!("{:#}", e);
+            if is_corruption(&error) {
+                self.record_detection(error)?;
+            }
+        }
+        result
+    }
+
+    /// Remember corruption seen in the live store until a diagnosis finds it readable
+    pub fn record_detection(&self, error: String) -> Result<()> {
+        let mut detection = self.detection_lock()?;
+        if detection.is_none() {
+            warn!("Corruption detected in store {}: {}", self.path.display(), error);
+            *detection = Some(Detection { at: Utc::now(), error });
+        }
+        Ok(())
+    }
+
+    pub fn detection(&self) -> Result<Option<Detection>> {
+        Ok(self.detection_lock()?.clone())
+    }
+
+    fn detection_lock(&self) -> Result<std::sync::MutexGuard<'_, Option<Detection>>> {
+        self.detection.lock()
+            .map_err(|_| anyhow::anyhow!("Store corruption state is poisoned"))
+    }
+
+    /// Whether corruption was detected since the latest diagnosis started
+    pub async fn needs_diagnosis(&self) -> Result<bool> {
+        let Some(detection) = self.detection()? else {
+            return Ok(false);
+        };
+        Ok(self.latest().await.is_none_or(|report| report.started_at < detection.at))
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.try_lock().is_err()
+    }
+
+    pub async fn status(&self) -> Result<DoctorStatus> {
+        Ok(DoctorStatus {
+            detection: self.detection()?,
+            latest_verdict: self.latest().await.map(|report| report.verdict),
+            running: self.is_running(),
+        })
+    }
+
+    pub async fn latest(&self) -> Option<DoctorReport> {
+        self.reports.read().await.front().cloned()
+    }
+
+    /// Diagnoses, newest first
+    pub async fn history(&self) -> Vec<DoctorReport> {
+        self.reports.read().await.iter().cloned().collect()
+    }
+
+    /// Read the whole store; if it is corrupted, repair a copy in the staging
+    /// directory and compare its records against the last good backup of `backups`
+    ///
+    /// The live store is only read. Fails without running if a diagnosis is
+    /// in progress, or if the store can't be read for reasons other than corruption.
+    pub async fn diagnose(
+        &self,
+        backups: Option<&BackupManager>,
+        trigger: DoctorTrigger,
+        requested_by: Option<String>,
+    ) -> Result<DoctorReport> {
+        let _running = self.running.try_lock()
+            .map_err(|_| anyhow::anyhow!("A store diagnosis is already in progress"))?;
+        let started_at = Utc::now();
+        info!("Diagnosing store {} ({:?})", self.path.display(), trigger);
+
+        let path = self.path.clone();
+        let (corruption, live_records) = match tokio::task::spawn_blocking(move || storage::key_counts(&path)).await? {
+            Ok(records) => (None, records),
+            Err(e) if is_corruption(&format!("{:#}", e)) => (Some(format!("{:#}", e)), BTreeMap::new()),
+            Err(e) => return Err(e.context("Failed to read store")),
+        };
+
+        let mut report = DoctorReport {
+            trigger,
+            requested_by,
+            started_at,
+            finished_at: started_at,
+            corruption: corruption.clone(),
+            live_records,
+            repair: None,
+            baseline: None,
+            baseline_error: None,
+            comparison: Vec::new(),
+            verdict: DoctorVerdict::Healthy,
+        };
+        match corruption {
+            None => *self.detection_lock()? = None,
+            Some(error) => {
+                self.record_detection(error)?;
+                let staging = self.staging_dir.join(started_at.format("%Y%m%d_%H%M%S").to_string());
+                let (source, target) = (self.path.clone(), staging.clone());
+                let repaired = tokio::task::spawn_blocking(move || repair_into(&source, &target)).await?;
+                if let Err(e) = prune_staged(&self.staging_dir, MAX_STAGED_REPAIRS) {
+                    warn!("Failed to remove old repaired copies: {:#}", e);
+                }
+                let repair = match repaired {
+                    Ok(records) => RepairAttempt { staging_dir: staging, records, error: None },
+                    Err(e) => RepairAttempt { staging_dir: staging, records: BTreeMap::new(), error: Some(format!("{:#}", e)) },
+                };
+
+                if repair.error.is_none() {
+                    match backups.map(Self::baseline) {
+                        Some(baseline) => match baseline.await {
+                            Ok(Some(baseline)) => {
+                                report.comparison = compare_records(&baseline.records, &repair.records);
+                                report.baseline = Some(baseline);
+                            }
+                            Ok(None) => report.baseline_error = Some("No validated backup".to_string()),
+                            Err(e) => report.baseline_error = Some(format!("{:#}", e)),
+                        },
+                        None => report.baseline_error = Some("Backups are not configured".to_string()),
+                    }
+                }
+                report.verdict = if repair.error.is_some() {
+                    DoctorVerdict::RepairFailed
+                } else if report.baseline.is_none() {
+                    DoctorVerdict::Unverified
+                } else if report.comparison.iter().any(|c| c.missing > 0) {
+                    DoctorVerdict::RecordsMissing
+                } else {
+                    DoctorVerdict::Repaired
+                };
+                report.repair = Some(repair);
+            }
+        }
+        report.finished_at = Utc::now();
+
+        match report.verdict {
+            DoctorVerdict::Healthy => info!("Store {} reads cleanly", self.path.display()),
+            verdict => warn!("Store {} is corrupted: {}", self.path.display(), verdict.describe()),
+        }
+        let mut reports = self.reports.write().await;
+        reports.push_front(report.clone());
+        reports.truncate(MAX_REPORTS);
+        Ok(report)
+    }
+
+    async fn baseline(backups: &BackupManager) -> Result<Option<BackupBaseline>> {
+        let Some(backup) = backups.last_good_backup()? else {
+            return Ok(None);
+        };
+        let records = backups.record_counts(&backup.id).await
+            .with_context(|| format!("Failed to count records of backup {}", backup.id))?;
+        Ok(Some(BackupBaseline { backup_id: backup.id, taken_at: backup.timestamp, records }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_detection_and_record_comparison() {
+        assert!(is_corruption("Failed to read column family shares: Corruption: block checksum mismatch: stored = 1"));
+        assert!(!is_corruption("IO error: While lock file: /data/store/LOCK: Resource temporarily unavailable"));
+
+        let backup = BTreeMap::from([("shares".to_string(), 100), ("users".to_string(), 5)]);
+        let repaired = BTreeMap::from([("shares".to_string(), 90), ("blocks".to_string(), 3), ("users".to_string(), 7)]);
+        let comparison = compare_records(&backup, &repaired);
+        assert_eq!(comparison.iter().map(|c| (c.column_family.as_str(), c.missing)).collect::<Vec<_>>(),
+            [("blocks", 0), ("shares", 10), ("users", 0)]);
+        assert_eq!(comparison[0].backup, None);
+
+        let dir = TempDir::new().unwrap();
+        for name in ["20260101_000000", "20260102_000000", "20260103_000000"] {
+            std::fs::create_dir(dir.path().join(name)).unwrap();
+        }
+        prune_staged(dir.path(), 2).unwrap();
+        assert!(!dir.path().join("20260101_000000").exists());
+        assert!(dir.path().join("20260103_000000").exists());
+
+        let doctor = StoreDoctor::new(dir.path().join("store"), dir.path());
+        assert!(!doctor.needs_diagnosis().await.unwrap());
+        doctor.record_detection("Corruption: bad block".to_string()).unwrap();
+        let first = doctor.detection().unwrap().unwrap();
+        // The first detection is kept until a diagnosis clears it
+        doctor.record_detection("Corruption: another block".to_string()).unwrap();
+        assert_eq!(doctor.detection().unwrap(), Some(first));
+        assert!(doctor.needs_diagnosis().await.unwrap());
+        let status = doctor.status().await.unwrap();
+        assert!(status.detection.is_some() && status.latest_verdict.is_none() && !status.running);
+    }
+}
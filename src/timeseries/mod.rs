@@ -0,0 +1,466 @@
+// Pool Time Series for DMPool
+// Stores pool, address and worker samples on disk, downsampled into coarser tiers
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::RwLock;
+
+/// Series holding the whole pool's hashrate
+pub const POOL_SERIES: &str = "pool";
+
+/// Series of pool-wide metrics other than hashrate
+pub const WORKERS_SERIES: &str = "pool:workers";
+pub const SHARE_RATE_SERIES: &str = "pool:share_rate";
+pub const REJECT_RATE_SERIES: &str = "pool:reject_rate";
+
+/// Series and unit of a dashboard history metric
+pub fn metric_series(metric: &str) -> Option<(&'static str, &'static str)> {
+    match metric {
+        "hashrate" => Some((POOL_SERIES, "TH/s")),
+        "workers" => Some((WORKERS_SERIES, "workers")),
+        "share_rate" => Some((SHARE_RATE_SERIES, "shares/s")),
+        "reject_rate" => Some((REJECT_RATE_SERIES, "ratio")),
+        _ => None,
+    }
+}
+
+/// Bytes per stored point: timestamp (u64), series id (u32), average, minimum
+/// and maximum (f32 each)
+const RECORD_SIZE: usize = 24;
+
+/// Records before minimum and maximum were stored, without them
+const LEGACY_RECORD_SIZE: usize = 16;
+
+/// Series name of a single worker of `address`
+pub fn worker_series(address: &str, worker: &str) -> String {
+    format!("{}.{}", address, worker)
+}
+
+/// Series holding the combined hashrate of a farm
+pub fn farm_series(name: &str) -> String {
+    format!("farm:{}", name)
+}
+
+/// Parse a duration such as `30s`, `5m`, `24h` or `7d` into seconds
+pub fn parse_duration(s: &str) -> Result<u64> {
+    let s = s.trim();
+    let (value, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let value: u64 = value.parse()
+        .map_err(|_| anyhow::anyhow!("Invalid duration: {}", s))?;
+    let unit_secs = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return Err(anyhow::anyhow!("Invalid duration unit in {} (use s, m, h or d)", s)),
+    };
+    value.checked_mul(unit_secs)
+        .filter(|secs| *secs > 0)
+        .ok_or_else(|| anyhow::anyhow!("Invalid duration: {}", s))
+}
+
+/// A hashrate sample, or the average over a bucket starting at `timestamp`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Point {
+    pub timestamp: u64,
+    pub hashrate_ths: f64,
+}
+
+/// Average, minimum and maximum of a series over a bucket starting at `timestamp`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Bucket {
+    pub timestamp: u64,
+    pub avg: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Stored points the bucket was computed from
+    pub points: u32,
+}
+
+/// A raw sample, or the average, minimum and maximum of a coarse tier's bucket
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Stored {
+    timestamp: u64,
+    avg: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Stored {
+    fn sample(timestamp: u64, value: f64) -> Self {
+        Self { timestamp, avg: value, min: value, max: value }
+    }
+}
+
+/// Resolution and retention of one storage tier
+#[derive(Clone, Copy, Debug)]
+pub struct Tier {
+    pub step_secs: u64,
+    pub retention_secs: u64,
+}
+
+/// Raw samples for two days, hourly averages for 90 days
+pub fn default_tiers(sample_secs: u64) -> Vec<Tier> {
+    vec![
+        Tier { step_secs: sample_secs, retention_secs: 2 * 86400 },
+        Tier { step_secs: 3600, retention_secs: 90 * 86400 },
+    ]
+}
+
+struct TierData {
+    tier: Tier,
+    path: PathBuf,
+    points: HashMap<u32, VecDeque<Stored>>,
+    /// Records in the file, including pruned ones not yet compacted away
+    records_on_disk: usize,
+    /// Start of the bucket still collecting raw samples (coarse tiers only)
+    open_bucket: Option<u64>,
+}
+
+impl TierData {
+    fn len(&self) -> usize {
+        self.points.values().map(VecDeque::len).sum()
+    }
+}
+
+#[derive(Default)]
+struct SeriesState {
+    names: Vec<String>,
+    ids: HashMap<String, u32>,
+    tiers: Vec<TierData>,
+}
+
+fn encode(records: &[(u32, Stored)]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(records.len() * RECORD_SIZE);
+    for (id, point) in records {
+        bytes.extend_from_slice(&point.timestamp.to_le_bytes());
+        bytes.extend_from_slice(&id.to_le_bytes());
+        for value in [point.avg, point.min, point.max] {
+            bytes.extend_from_slice(&(value as f32).to_le_bytes());
+        }
+    }
+    bytes
+}
+
+/// Records of `record_size` bytes; legacy records hold only the average
+fn decode(bytes: &[u8], record_size: usize) -> impl Iterator<Item = (u32, Stored)> + '_ {
+    let float = |bytes: &[u8]| f32::from_le_bytes(bytes.try_into().unwrap_or_default()) as f64;
+    bytes.chunks_exact(record_size).map(move |record| {
+        let timestamp = u64::from_le_bytes(record[0..8].try_into().unwrap_or_default());
+        let id = u32::from_le_bytes(record[8..12].try_into().unwrap_or_default());
+        let avg = float(&record[12..16]);
+        let point = if record_size == LEGACY_RECORD_SIZE {
+            Stored::sample(timestamp, avg)
+        } else {
+            Stored { timestamp, avg, min: float(&record[16..20]), max: float(&record[20..24]) }
+        };
+        (id, point)
+    })
+}
+
+async fn append(path: &Path, bytes: &[u8]) -> Result<()> {
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    file.write_all(bytes).await?;
+    Ok(())
+}
+
+/// Sample history in fixed-size binary records, one file per tier
+///
+/// The first tier receives every sample; coarser tiers store the average,
+/// minimum and maximum of it per step, so its retention must cover their steps.
+pub struct TimeSeriesStore {
+    dir: PathBuf,
+    state: RwLock<SeriesState>,
+}
+
+impl TimeSeriesStore {
+    /// Create a store in `dir` with `tiers`, finest first
+    pub fn new(dir: PathBuf, tiers: Vec<Tier>) -> Self {
+        let tiers = tiers.into_iter()
+            .map(|tier| TierData {
+                path: dir.join(format!("series_{}s.bin", tier.step_secs)),
+                tier,
+                points: HashMap::new(),
+                records_on_disk: 0,
+                open_bucket: None,
+            })
+            .collect();
+        Self {
+            dir,
+            state: RwLock::new(SeriesState { tiers, ..SeriesState::default() }),
+        }
+    }
+
+    fn names_path(&self) -> PathBuf {
+        self.dir.join("series.txt")
+    }
+
+    /// Load series and points from disk, if present
+    ///
+    /// Tier files written before minimums and maximums were stored are
+    /// converted to the current format.
+    pub async fn load(&self) -> Result<()> {
+        let mut state = self.state.write().await;
+        let state = &mut *state;
+        if let Ok(names) = tokio::fs::read_to_string(self.names_path()).await {
+            for name in names.lines() {
+                state.ids.insert(name.to_string(), state.names.len() as u32);
+                state.names.push(name.to_string());
+            }
+        }
+        for tier in &mut state.tiers {
+            if let Ok(bytes) = tokio::fs::read(&tier.path).await {
+                tier.records_on_disk = bytes.len() / RECORD_SIZE;
+                for (id, point) in decode(&bytes, RECORD_SIZE) {
+                    tier.points.entry(id).or_default().push_back(point);
+                }
+                continue;
+            }
+            let legacy = self.dir.join(format!("hashrate_{}s.bin", tier.tier.step_secs));
+            let Ok(bytes) = tokio::fs::read(&legacy).await else {
+                continue;
+            };
+            let records: Vec<(u32, Stored)> = decode(&bytes, LEGACY_RECORD_SIZE).collect();
+            tokio::fs::write(&tier.path, encode(&records)).await
+                .with_context(|| format!("Failed to convert {}", legacy.display()))?;
+            tokio::fs::remove_file(&legacy).await?;
+            tier.records_on_disk = records.len();
+            for (id, point) in records {
+                tier.points.entry(id).or_default().push_back(point);
+            }
+        }
+        let latest = state.tiers.first()
+            .and_then(|raw| raw.points.values().filter_map(|p| p.back()).map(|p| p.timestamp).max());
+        if let Some(latest) = latest {
+            for tier in state.tiers.iter_mut().skip(1) {
+                tier.open_bucket = Some(latest - latest % tier.tier.step_secs);
+            }
+        }
+        Ok(())
+    }
+
+    /// Store one sample per series taken at `timestamp`
+    pub async fn record(&self, timestamp: u64, samples: &[(String, f64)]) -> Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await
+            .context("Failed to create time series directory")?;
+        let mut state = self.state.write().await;
+        let state = &mut *state;
+
+        let mut new_names = String::new();
+        let mut raw = Vec::with_capacity(samples.len());
+        for (name, value) in samples {
+            let id = match state.ids.get(name) {
+                Some(id) => *id,
+                None => {
+                    let id = state.names.len() as u32;
+                    state.ids.insert(name.clone(), id);
+                    state.names.push(name.clone());
+                    new_names.push_str(name);
+                    new_names.push('\n');
+                    id
+                }
+            };
+            raw.push((id, Stored::sample(timestamp, *value)));
+        }
+        if !new_names.is_empty() {
+            append(&self.names_path(), new_names.as_bytes()).await?;
+        }
+
+        let Some((first, coarse)) = state.tiers.split_first_mut() else {
+            return Ok(());
+        };
+        // Close coarse buckets that this sample has moved past
+        let mut appended = vec![raw];
+        for tier in coarse.iter_mut() {
+            let step = tier.tier.step_secs;
+            let bucket = timestamp - timestamp % step;
+            let mut averages = Vec::new();
+            if let Some(open) = tier.open_bucket.filter(|open| *open < bucket) {
+                for (id, points) in &first.points {
+                    let in_bucket = points.iter()
+                        .filter(|p| p.timestamp >= open && p.timestamp < open + step);
+                    if let Some(bucket) = summarize(open, in_bucket) {
+                        averages.push((*id, Stored { timestamp: open, avg: bucket.avg, min: bucket.min, max: bucket.max }));
+                    }
+                }
+            }
+            tier.open_bucket = Some(bucket);
+            appended.push(averages);
+        }
+
+        for (tier, records) in state.tiers.iter_mut().zip(appended) {
+            for (id, point) in &records {
+                tier.points.entry(*id).or_default().push_back(*point);
+            }
+            let cutoff = timestamp.saturating_sub(tier.tier.retention_secs);
+            for points in tier.points.values_mut() {
+                while points.front().is_some_and(|p| p.timestamp < cutoff) {
+                    points.pop_front();
+                }
+            }
+            tier.points.retain(|_, points| !points.is_empty());
+
+            tier.records_on_disk += records.len();
+            let kept = tier.len();
+            if tier.records_on_disk > kept * 2 + 1024 {
+                // Rewrite without the pruned records
+                let mut all: Vec<(u32, Stored)> = tier.points.iter()
+                    .flat_map(|(id, points)| points.iter().map(move |p| (*id, *p)))
+                    .collect();
+                all.sort_by_key(|(_, p)| p.timestamp);
+                let tmp = tier.path.with_extension("bin.tmp");
+                tokio::fs::write(&tmp, encode(&all)).await
+                    .context("Failed to write time series")?;
+                tokio::fs::rename(&tmp, &tier.path).await
+                    .context("Failed to replace time series")?;
+                tier.records_on_disk = kept;
+            } else if !records.is_empty() {
+                append(&tier.path, &encode(&records)).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Average hashrate of `series` per `step_secs` bucket over the last `range_secs`
+    ///
+    /// Reads the finest tier that retains the whole range; buckets without
+    /// samples are omitted.
+    pub async fn query(&self, series: &str, range_secs: u64, step_secs: u64, now: u64) -> Vec<Point> {
+        self.buckets(series, range_secs, step_secs, now).await
+            .into_iter()
+            .map(|bucket| Point { timestamp: bucket.timestamp, hashrate_ths: bucket.avg })
+            .collect()
+    }
+
+    /// Average, minimum and maximum of `series` per `step_secs` bucket over the
+    /// last `range_secs`, read like `query`
+    pub async fn buckets(&self, series: &str, range_secs: u64, step_secs: u64, now: u64) -> Vec<Bucket> {
+        let state = self.state.read().await;
+        let Some(id) = state.ids.get(series) else {
+            return Vec::new();
+        };
+        let tier = state.tiers.iter()
+            .find(|t| t.tier.retention_secs >= range_secs && t.tier.step_secs <= step_secs)
+            .or_else(|| state.tiers.last());
+        let Some(points) = tier.and_then(|t| t.points.get(id)) else {
+            return Vec::new();
+        };
+
+        let step = step_secs.max(1);
+        let start = now.saturating_sub(range_secs);
+        let mut buckets = Vec::new();
+        let mut in_range = points.iter().filter(|p| p.timestamp >= start && p.timestamp <= now).peekable();
+        while let Some(first) = in_range.peek() {
+            let bucket = first.timestamp - first.timestamp % step;
+            let mut in_bucket = Vec::new();
+            while let Some(point) = in_range.next_if(|p| p.timestamp < bucket + step) {
+                in_bucket.push(point);
+            }
+            buckets.extend(summarize(bucket, in_bucket.into_iter()));
+        }
+        buckets
+    }
+}
+
+/// Average of the averages, and the extremes, of `points`
+fn summarize<'a>(timestamp: u64, points: impl Iterator<Item = &'a Stored>) -> Option<Bucket> {
+    let mut bucket = Bucket { timestamp, avg: 0.0, min: f64::INFINITY, max: f64::NEG_INFINITY, points: 0 };
+    for point in points {
+        bucket.avg += point.avg;
+        bucket.min = bucket.min.min(point.min);
+        bucket.max = bucket.max.max(point.max);
+        bucket.points += 1;
+    }
+    if bucket.points == 0 {
+        return None;
+    }
+    bucket.avg /= bucket.points as f64;
+    Some(bucket)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("5m").unwrap(), 300);
+        assert_eq!(parse_duration("24h").unwrap(), 86400);
+        assert_eq!(parse_duration("7d").unwrap(), 7 * 86400);
+        assert_eq!(parse_duration("90").unwrap(), 90);
+        assert!(parse_duration("0m").is_err());
+        assert!(parse_duration("5w").is_err());
+        assert!(parse_duration("h").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_samples_downsample_and_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let tiers = vec![
+            Tier { step_secs: 300, retention_secs: 3 * 3600 },
+            Tier { step_secs: 3600, retention_secs: 30 * 86400 },
+        ];
+        let store = TimeSeriesStore::new(dir.path().to_path_buf(), tiers.clone());
+        let worker = worker_series("bc1qminer", "rig1");
+
+        // Four hours of 5-minute samples: 10 TH/s for an hour, then 20 TH/s
+        let start = 1_700_000_000 - 1_700_000_000 % 3600;
+        for i in 0..48 {
+            let timestamp = start + i * 300;
+            let hashrate = if i < 12 { 10.0 } else { 20.0 };
+            let workers = (i % 3) as f64;
+            store.record(timestamp, &[
+                (POOL_SERIES.to_string(), hashrate),
+                (worker.clone(), hashrate),
+                (WORKERS_SERIES.to_string(), workers),
+            ]).await.unwrap();
+        }
+        let now = start + 47 * 300;
+
+        let recent = store.query(&worker, 3600, 300, now).await;
+        assert_eq!(recent.len(), 13);
+        assert!(recent.iter().all(|p| p.hashrate_ths == 20.0));
+
+        // Older than the raw retention, so served from the hourly tier
+        let hourly = store.query(POOL_SERIES, 86400, 3600, now).await;
+        assert_eq!(hourly.len(), 3);
+        assert_eq!(hourly[0], Point { timestamp: start, hashrate_ths: 10.0 });
+        assert_eq!(hourly[1].hashrate_ths, 20.0);
+
+        // Coarse buckets keep the extremes of the samples they average
+        let candles = store.buckets(WORKERS_SERIES, 86400, 3600, now).await;
+        assert_eq!(candles.len(), 3);
+        assert!(candles.iter().all(|c| (c.min, c.avg, c.max, c.points) == (0.0, 1.0, 2.0, 1)));
+        let raw = store.buckets(WORKERS_SERIES, 3600, 900, now).await;
+        assert!(raw[1..].iter().all(|c| (c.min, c.max, c.points) == (0.0, 2.0, 3)));
+
+        let reloaded = TimeSeriesStore::new(dir.path().to_path_buf(), tiers.clone());
+        reloaded.load().await.unwrap();
+        assert_eq!(reloaded.query(POOL_SERIES, 86400, 3600, now).await, hourly);
+        assert_eq!(reloaded.query(&worker, 3600, 300, now).await, recent);
+        assert!(reloaded.query("unknown", 3600, 300, now).await.is_empty());
+        assert_eq!(reloaded.buckets(WORKERS_SERIES, 86400, 3600, now).await, candles);
+
+        // Files from before extremes were stored are converted on load
+        let legacy = tempfile::tempdir().unwrap();
+        std::fs::write(legacy.path().join("series.txt"), "pool\n").unwrap();
+        let mut record = now.to_le_bytes().to_vec();
+        record.extend_from_slice(&0u32.to_le_bytes());
+        record.extend_from_slice(&12.5f32.to_le_bytes());
+        std::fs::write(legacy.path().join("hashrate_300s.bin"), record).unwrap();
+        let converted = TimeSeriesStore::new(legacy.path().to_path_buf(), tiers);
+        converted.load().await.unwrap();
+        let buckets = converted.buckets(POOL_SERIES, 3600, 300, now).await;
+        assert_eq!((buckets[0].min, buckets[0].avg, buckets[0].max), (12.5, 12.5, 12.5));
+        assert!(!legacy.path().join("hashrate_300s.bin").exists());
+        assert!(legacy.path().join("series_300s.bin").exists());
+    }
+}
@@ -0,0 +1,401 @@
+// TLS for DMPool HTTP Servers
+// Serves the admin and health APIs over HTTPS, with HSTS, an optional HTTP-to-HTTPS
+// redirect and optional client certificate (mutual TLS) authentication
+
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode, Uri},
+    middleware::{self, Next},
+    response::{IntoResponse, Redirect, Response},
+    Extension, Router,
+};
+use axum_server::accept::Accept;
+use axum_server::tls_rustls::{RustlsAcceptor, RustlsConfig};
+use crate::shutdown::Shutdown;
+use rustls::pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tower::Layer;
+use tracing::{error, info, warn};
+
+/// HSTS max-age used when TLS is on and none is configured (one year)
+const DEFAULT_HSTS_MAX_AGE: u64 = 365 * 24 * 3600;
+
+/// What a verified client certificate is used for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClientCertAuth {
+    /// Certificates only gate the connection; requests still need a JWT
+    #[default]
+    Augment,
+    /// The certificate's common name is the user; no JWT is needed
+    Replace,
+}
+
+impl FromStr for ClientCertAuth {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "augment" => Ok(Self::Augment),
+            "replace" => Ok(Self::Replace),
+            other => Err(anyhow::anyhow!("Unknown client certificate mode: {} (expected augment or replace)", other)),
+        }
+    }
+}
+
+/// Verified client certificate of a mutual TLS connection, added to each request's extensions
+#[derive(Clone, Debug)]
+pub struct ClientCertificate {
+    /// Subject common name
+    pub common_name: Option<String>,
+    /// SHA-256 of the certificate, hex
+    pub fingerprint: String,
+    /// Whether the listener accepts the certificate in place of a JWT
+    pub authenticates: bool,
+}
+
+impl ClientCertificate {
+    /// Certificate the client of `req` presented, if its listener asked for one
+    pub fn of(req: &Request) -> Option<&ClientCertificate> {
+        req.extensions().get::<Option<ClientCertificate>>().and_then(Option::as_ref)
+    }
+
+    fn from_der(der: &[u8], authenticates: bool) -> Self {
+        use sha2::{Digest, Sha256};
+        let common_name = x509_parser::parse_x509_certificate(der).ok().and_then(|(_, cert)| {
+            cert.subject()
+                .iter_common_name()
+                .next()
+                .and_then(|cn| cn.as_str().ok())
+                .map(str::to_string)
+        });
+        Self {
+            common_name,
+            fingerprint: format!("{:x}", Sha256::digest(der)),
+            authenticates,
+        }
+    }
+}
+
+/// TLS settings of one server
+#[derive(Clone, Debug, Default)]
+pub struct TlsSettings {
+    /// PEM certificate chain
+    pub cert_path: Option<PathBuf>,
+    /// PEM private key
+    pub key_path: Option<PathBuf>,
+    /// Generate a throwaway self-signed certificate when no paths are set (development only)
+    pub self_signed: bool,
+    /// `Strict-Transport-Security` max-age in seconds; `None` sends no header
+    pub hsts_max_age: Option<u64>,
+    /// Port of a plain HTTP listener that redirects to HTTPS
+    pub redirect_port: Option<u16>,
+    /// PEM CA certificates; when set, clients must present a certificate they issued
+    pub client_ca_path: Option<PathBuf>,
+    /// What verified client certificates are used for
+    pub client_cert_auth: ClientCertAuth,
+}
+
+fn env_var(prefix: &str, name: &str) -> Option<String> {
+    std::env::var(format!("{}_{}", prefix, name)).ok().filter(|v| !v.is_empty())
+}
+
+impl TlsSettings {
+    /// Settings from `<PREFIX>_TLS_CERT`, `<PREFIX>_TLS_KEY`, `<PREFIX>_TLS_SELF_SIGNED`,
+    /// `<PREFIX>_HSTS_MAX_AGE`, `<PREFIX>_HTTP_REDIRECT_PORT`, `<PREFIX>_TLS_CLIENT_CA`
+    /// and `<PREFIX>_CLIENT_CERT_AUTH`
+    pub fn from_env(prefix: &str) -> Result<Self> {
+        let mut settings = Self {
+            cert_path: env_var(prefix, "TLS_CERT").map(PathBuf::from),
+            key_path: env_var(prefix, "TLS_KEY").map(PathBuf::from),
+            self_signed: env_var(prefix, "TLS_SELF_SIGNED").is_some_and(|v| v == "true" || v == "1"),
+            hsts_max_age: None,
+            redirect_port: env_var(prefix, "HTTP_REDIRECT_PORT")
+                .map(|v| v.parse().with_context(|| format!("Invalid {}_HTTP_REDIRECT_PORT: {}", prefix, v)))
+                .transpose()?,
+            client_ca_path: env_var(prefix, "TLS_CLIENT_CA").map(PathBuf::from),
+            client_cert_auth: env_var(prefix, "CLIENT_CERT_AUTH")
+                .map(|v| v.parse())
+                .transpose()?
+                .unwrap_or_default(),
+        };
+        if settings.cert_path.is_some() != settings.key_path.is_some() {
+            return Err(anyhow::anyhow!("{0}_TLS_CERT and {0}_TLS_KEY must be set together", prefix));
+        }
+        if settings.redirect_port.is_some() && !settings.is_enabled() {
+            return Err(anyhow::anyhow!("{}_HTTP_REDIRECT_PORT needs TLS to be enabled", prefix));
+        }
+        if settings.client_ca_path.is_some() && !settings.is_enabled() {
+            return Err(anyhow::anyhow!("{}_TLS_CLIENT_CA needs TLS to be enabled", prefix));
+        }
+        if settings.is_enabled() {
+            let max_age = match env_var(prefix, "HSTS_MAX_AGE") {
+                Some(v) => v.parse().with_context(|| format!("Invalid {}_HSTS_MAX_AGE: {}", prefix, v))?,
+                None => DEFAULT_HSTS_MAX_AGE,
+            };
+            settings.hsts_max_age = (max_age > 0).then_some(max_age);
+        }
+        Ok(settings)
+    }
+
+    /// Whether the server speaks HTTPS
+    pub fn is_enabled(&self) -> bool {
+        self.cert_path.is_some() || self.self_signed
+    }
+
+    /// Rustls configuration, `None` when TLS is off
+    pub async fn rustls_config(&self) -> Result<Option<RustlsConfig>> {
+        // Several crates link rustls; pick the provider explicitly
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let (cert, key) = match (&self.cert_path, &self.key_path) {
+            (Some(cert), Some(key)) => (
+                tokio::fs::read(cert).await
+                    .with_context(|| format!("Failed to read TLS certificate {:?}", cert))?,
+                tokio::fs::read(key).await
+                    .with_context(|| format!("Failed to read TLS key {:?}", key))?,
+            ),
+            _ if self.self_signed => {
+                let (cert, key) = self_signed_cert(vec!["localhost".to_string(), "127.0.0.1".to_string()])?;
+                warn!("Using a generated self-signed TLS certificate; do not use this in production");
+                (cert.into_bytes(), key.into_bytes())
+            }
+            _ => return Ok(None),
+        };
+        let chain = CertificateDer::pem_slice_iter(&cert)
+            .collect::<Result<Vec<_>, _>>()
+            .context("Invalid TLS certificate")?;
+        let key = PrivateKeyDer::from_pem_slice(&key).context("Invalid TLS key")?;
+
+        let builder = ServerConfig::builder();
+        let builder = match &self.client_ca_path {
+            Some(path) => builder.with_client_cert_verifier(
+                WebPkiClientVerifier::builder(Arc::new(load_client_roots(path)?))
+                    .build()
+                    .context("Invalid client CA")?,
+            ),
+            None => builder.with_no_client_auth(),
+        };
+        let mut config = builder.with_single_cert(chain, key).context("Invalid TLS certificate or key")?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(Some(RustlsConfig::from_config(Arc::new(config))))
+    }
+}
+
+fn load_client_roots(path: &Path) -> Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for cert in CertificateDer::pem_file_iter(path)
+        .with_context(|| format!("Failed to read client CA {:?}", path))?
+    {
+        roots.add(cert.context("Invalid client CA certificate")?)
+            .context("Invalid client CA certificate")?;
+    }
+    if roots.is_empty() {
+        return Err(anyhow::anyhow!("No certificates in client CA {:?}", path));
+    }
+    Ok(roots)
+}
+
+/// Rustls acceptor that adds the peer's [`ClientCertificate`] to every request
+#[derive(Clone)]
+struct ClientCertAcceptor {
+    inner: RustlsAcceptor,
+    authenticates: bool,
+}
+
+impl<S> Accept<TcpStream, S> for ClientCertAcceptor
+where
+    S: Send + 'static,
+{
+    type Stream = <RustlsAcceptor as Accept<TcpStream, S>>::Stream;
+    type Service = <Extension<Option<ClientCertificate>> as Layer<S>>::Service;
+    type Future = Pin<Box<dyn Future<Output = io::Result<(Self::Stream, Self::Service)>> + Send>>;
+
+    fn accept(&self, stream: TcpStream, service: S) -> Self::Future {
+        let inner = self.inner.clone();
+        let authenticates = self.authenticates;
+        Box::pin(async move {
+            let (stream, service) = inner.accept(stream, service).await?;
+            let certificate = stream.get_ref().1
+                .peer_certificates()
+                .and_then(|chain| chain.first())
+                .map(|der| ClientCertificate::from_der(der, authenticates));
+            Ok((stream, Extension(certificate).layer(service)))
+        })
+    }
+}
+
+/// Self-signed certificate and private key, both PEM, for the given host names
+pub fn self_signed_cert(hostnames: Vec<String>) -> Result<(String, String)> {
+    let certified = rcgen::generate_simple_self_signed(hostnames)
+        .context("Failed to generate self-signed certificate")?;
+    Ok((certified.cert.pem(), certified.key_pair.serialize_pem()))
+}
+
+/// Add `Strict-Transport-Security` to every response
+async fn hsts_middleware(State(max_age): State<u64>, req: Request, next: Next) -> Response {
+    let mut response = next.run(req).await;
+    if let Ok(value) = HeaderValue::from_str(&format!("max-age={}; includeSubDomains", max_age)) {
+        response.headers_mut().insert(header::STRICT_TRANSPORT_SECURITY, value);
+    }
+    response
+}
+
+/// Serve `app` on `addr`, over HTTPS when `settings` enable TLS, until `shutdown`
+///
+/// Once shutdown is requested no new connections are accepted, and requests
+/// still in flight after `drain_timeout` are cut off.
+pub async fn serve(
+    addr: SocketAddr,
+    app: Router,
+    settings: &TlsSettings,
+    shutdown: &Shutdown,
+    drain_timeout: Duration,
+) -> Result<()> {
+    match settings.rustls_config().await? {
+        Some(config) => {
+            let app = match settings.hsts_max_age {
+                Some(max_age) => app.layer(middleware::from_fn_with_state(max_age, hsts_middleware)),
+                None => app,
+            };
+            let acceptor = ClientCertAcceptor {
+                inner: RustlsAcceptor::new(config),
+                authenticates: settings.client_cert_auth == ClientCertAuth::Replace,
+            };
+            if settings.client_ca_path.is_some() {
+                info!("Serving HTTPS on {} (client certificates required, {:?})", addr, settings.client_cert_auth);
+            } else {
+                info!("Serving HTTPS on {}", addr);
+            }
+            let handle = axum_server::Handle::new();
+            let stopping = handle.clone();
+            let shutdown = shutdown.clone();
+            tokio::spawn(async move {
+                shutdown.wait().await;
+                stopping.graceful_shutdown(Some(drain_timeout));
+            });
+            axum_server::bind(addr)
+                .handle(handle)
+                .acceptor(acceptor)
+                .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+                .context("HTTPS server failed")
+        }
+        None => {
+            let listener = TcpListener::bind(addr).await?;
+            info!("Serving HTTP on {}", addr);
+            serve_http(listener, app, shutdown, drain_timeout).await.context("HTTP server failed")
+        }
+    }
+}
+
+/// Serve plain HTTP until `shutdown`, then give open requests `drain_timeout` to finish
+async fn serve_http(listener: TcpListener, app: Router, shutdown: &Shutdown, drain_timeout: Duration) -> io::Result<()> {
+    let (stopped_tx, stopped_rx) = tokio::sync::oneshot::channel();
+    let signal = shutdown.clone();
+    let server = axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(async move {
+            signal.wait().await;
+            let _ = stopped_tx.send(());
+        })
+        .into_future();
+    tokio::pin!(server);
+    tokio::select! {
+        result = &mut server => return result,
+        _ = stopped_rx => {}
+    }
+    // Live feeds never finish on their own, so draining is bounded
+    match tokio::time::timeout(drain_timeout, server).await {
+        Ok(result) => result,
+        Err(_) => {
+            warn!("Requests still open after {}s, closing them", drain_timeout.as_secs());
+            Ok(())
+        }
+    }
+}
+
+/// HTTPS location of a plain HTTP request
+fn https_location(host: Option<&str>, uri: &Uri, https_port: u16) -> Option<String> {
+    let host = host?;
+    // Drop the port of the HTTP listener; bracketed IPv6 hosts contain colons themselves
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) && !name.ends_with(':') => name,
+        _ => host,
+    };
+    let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    Some(match https_port {
+        443 => format!("https://{}{}", host, path),
+        port => format!("https://{}:{}{}", host, port, path),
+    })
+}
+
+/// Listen for plain HTTP on `port` and redirect every request to HTTPS on
+/// `https_port`, until `shutdown`
+pub async fn spawn_https_redirect(port: u16, https_port: u16, shutdown: &Shutdown) -> Result<()> {
+    let app = Router::new().fallback(move |req: Request| async move {
+        let host = req.headers().get(header::HOST).and_then(|h| h.to_str().ok());
+        match https_location(host, req.uri(), https_port) {
+            Some(location) => Redirect::permanent(&location).into_response(),
+            None => (StatusCode::BAD_REQUEST, "Missing Host header").into_response(),
+        }
+    });
+    let listener = TcpListener::bind(format!("0.0.0.0:{}", port)).await?;
+    info!("Redirecting HTTP on port {} to HTTPS on port {}", port, https_port);
+    let shutdown = shutdown.clone();
+    tokio::spawn(async move {
+        let stopped = async move { shutdown.wait().await };
+        if let Err(e) = axum::serve(listener, app).with_graceful_shutdown(stopped).await {
+            error!("HTTPS redirect listener stopped: {}", e);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_self_signed_config_and_redirects() {
+        let settings = TlsSettings { self_signed: true, ..Default::default() };
+        assert!(settings.rustls_config().await.unwrap().is_some());
+        assert!(TlsSettings::default().rustls_config().await.unwrap().is_none());
+
+        let uri: Uri = "/api/v1/dashboard?x=1".parse().unwrap();
+        assert_eq!(
+            https_location(Some("pool.example:8080"), &uri, 8443).as_deref(),
+            Some("https://pool.example:8443/api/v1/dashboard?x=1"),
+        );
+        assert_eq!(
+            https_location(Some("[::1]:8080"), &uri, 443).as_deref(),
+            Some("https://[::1]/api/v1/dashboard?x=1"),
+        );
+        assert_eq!(https_location(None, &uri, 443), None);
+
+        // Mutual TLS against a CA file
+        let dir = tempfile::tempdir().unwrap();
+        let (ca, _) = self_signed_cert(vec!["clients.example".to_string()]).unwrap();
+        let ca_path = dir.path().join("ca.pem");
+        std::fs::write(&ca_path, &ca).unwrap();
+        let mtls = TlsSettings { self_signed: true, client_ca_path: Some(ca_path), ..Default::default() };
+        assert!(mtls.rustls_config().await.unwrap().is_some());
+        let empty_path = dir.path().join("empty.pem");
+        std::fs::write(&empty_path, "").unwrap();
+        let empty = TlsSettings { self_signed: true, client_ca_path: Some(empty_path), ..Default::default() };
+        assert!(empty.rustls_config().await.is_err());
+
+        let der = CertificateDer::from_pem_slice(ca.as_bytes()).unwrap();
+        let certificate = ClientCertificate::from_der(&der, true);
+        assert_eq!(certificate.common_name.as_deref(), Some("rcgen self signed cert"));
+        assert_eq!(certificate.fingerprint.len(), 64);
+    }
+}
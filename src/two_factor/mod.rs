@@ -1,6 +1,14 @@
 // Two-Factor Authentication (2FA) module for DMPool Admin
 // Implements TOTP-based 2FA with QR code setup and backup codes
 // TOTP secrets are encrypted at rest using AES-256-GCM
+// WebAuthn security keys can be used instead of, or next to, TOTP
+
+mod webauthn;
+
+pub use webauthn::{
+    AssertionCredential, AssertionResponse, AttestationResponse, CreationOptions, RegistrationCredential,
+    RelyingParty, RequestOptions, SecurityKey,
+};
 
 use anyhow::{Context, Result};
 use aes_gcm::{
@@ -12,6 +20,7 @@ use chrono::{DateTime, Utc};
 use qrcode::QrCode;
 use rand::distributions::Distribution;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -20,6 +29,9 @@ use tokio::sync::RwLock;
 use totp_rs::{Algorithm, TOTP};
 use tracing::{error, info, warn};
 
+/// How long a password-verified login may wait for its second factor
+pub const LOGIN_CHALLENGE_TTL_SECS: i64 = 300;
+
 /// Encrypted TOTP secret storage
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct EncryptedSecret {
@@ -152,19 +164,65 @@ pub struct TwoFactorEnable {
     pub code: String,
 }
 
-/// 2FA login request
-#[derive(Clone, Debug, Deserialize)]
+/// 2FA login request (second step, after the password was accepted)
+#[derive(Clone, Debug, Deserialize, ToSchema)]
 pub struct TwoFactorLogin {
-    pub username: String,
+    pub challenge_token: String,
     pub totp_code: Option<String>,
     pub backup_code: Option<String>,
 }
 
+/// Security key login request (second step, after the password was accepted)
+#[derive(Clone, Debug, Deserialize, ToSchema)]
+pub struct SecurityKeyLogin {
+    pub challenge_token: String,
+    pub credential: AssertionCredential,
+}
+
+/// Returned by the password step when the user has 2FA enabled
+#[derive(Clone, Debug, Serialize)]
+pub struct TwoFactorChallenge {
+    pub two_factor_required: bool,
+    pub challenge_token: String,
+    pub expires_in: u64,
+    /// Second factors the user can answer with: `totp`, `recovery_code`, `webauthn`
+    pub methods: Vec<String>,
+    /// Options for `navigator.credentials.get()` when `webauthn` is offered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webauthn: Option<RequestOptions>,
+}
+
+/// Pending login waiting for a second factor
+#[derive(Clone, Debug)]
+struct LoginChallenge {
+    username: String,
+    expires_at: DateTime<Utc>,
+    attempts: u32,
+    /// Challenge a security key must sign, if the user has any
+    webauthn_challenge: Option<Vec<u8>>,
+}
+
+/// Security key registration waiting for the browser's response
+#[derive(Clone, Debug)]
+struct PendingRegistration {
+    challenge: Vec<u8>,
+    expires_at: DateTime<Utc>,
+}
+
 /// 2FA status response
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TwoFactorStatus {
+    /// TOTP is enabled or a security key is registered
     pub enabled: bool,
+    #[serde(default)]
+    pub totp_enabled: bool,
+    /// Number of registered security keys
+    #[serde(default)]
+    pub security_keys: usize,
     pub has_backup_codes: bool,
+    /// The user's role requires 2FA; only set by `status_for`
+    #[serde(default)]
+    pub required: bool,
 }
 
 /// Rate limit tracker for 2FA attempts
@@ -174,6 +232,52 @@ pub struct TwoFactorRateLimit {
     pub locked_until: Option<DateTime<Utc>>,
 }
 
+/// What happens to a user whose role requires 2FA but who hasn't enabled it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TwoFactorEnforcement {
+    /// Log in, but only to enroll in 2FA (or change a temporary password)
+    #[default]
+    Enroll,
+    /// Refuse the login
+    Block,
+}
+
+/// Roles whose users must have 2FA enabled
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TwoFactorPolicy {
+    pub required_roles: Vec<String>,
+    pub enforcement: TwoFactorEnforcement,
+}
+
+impl TwoFactorPolicy {
+    /// Read `TWO_FACTOR_REQUIRED_ROLES` (comma-separated) and
+    /// `TWO_FACTOR_ENFORCEMENT` (`enroll` or `block`)
+    pub fn from_env() -> Result<Self> {
+        let mut policy = Self::default();
+        if let Ok(roles) = std::env::var("TWO_FACTOR_REQUIRED_ROLES") {
+            for role in roles.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+                if !crate::auth::VALID_ROLES.contains(&role) {
+                    return Err(anyhow::anyhow!("Unknown role in TWO_FACTOR_REQUIRED_ROLES: {}", role));
+                }
+                policy.required_roles.push(role.to_string());
+            }
+        }
+        if let Ok(value) = std::env::var("TWO_FACTOR_ENFORCEMENT") {
+            policy.enforcement = match value.as_str() {
+                "enroll" => TwoFactorEnforcement::Enroll,
+                "block" => TwoFactorEnforcement::Block,
+                other => return Err(anyhow::anyhow!("Unknown TWO_FACTOR_ENFORCEMENT: {}", other)),
+            };
+        }
+        Ok(policy)
+    }
+
+    pub fn requires(&self, role: &str) -> bool {
+        self.required_roles.iter().any(|r| r == role)
+    }
+}
+
 /// Two-Factor Authentication manager
 pub struct TwoFactorManager {
     /// TOTP secrets storage
@@ -196,6 +300,16 @@ pub struct TwoFactorManager {
     issuer: String,
     /// Encryption key for TOTP secrets
     encryption_key: Arc<EncryptionKey>,
+    /// Outstanding login challenges keyed by challenge token
+    challenges: Arc<RwLock<HashMap<String, LoginChallenge>>>,
+    /// Roles that must use 2FA
+    policy: TwoFactorPolicy,
+    /// Site security keys are registered for; `None` disables new ones
+    webauthn: Option<RelyingParty>,
+    /// Security keys by username
+    security_keys: Arc<RwLock<HashMap<String, Vec<SecurityKey>>>>,
+    /// Outstanding security key registrations by username
+    key_registrations: Arc<RwLock<HashMap<String, PendingRegistration>>>,
 }
 
 impl TwoFactorManager {
@@ -214,9 +328,47 @@ impl TwoFactorManager {
             lockout_duration: 300, // 5 minutes
             issuer,
             encryption_key,
+            challenges: Arc::new(RwLock::new(HashMap::new())),
+            policy: TwoFactorPolicy::default(),
+            webauthn: None,
+            security_keys: Arc::new(RwLock::new(HashMap::new())),
+            key_registrations: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Let users register security keys for the relying party `rp`
+    pub fn with_webauthn(mut self, rp: RelyingParty) -> Self {
+        self.webauthn = Some(rp);
+        self
+    }
+
+    pub fn webauthn_enabled(&self) -> bool {
+        self.webauthn.is_some()
+    }
+
+    /// Require 2FA for the roles of `policy`
+    pub fn with_policy(mut self, policy: TwoFactorPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn policy(&self) -> &TwoFactorPolicy {
+        &self.policy
+    }
+
+    /// 2FA status of a user with `role`, including whether the policy requires it
+    pub async fn status_for(&self, username: &str, role: &str) -> TwoFactorStatus {
+        TwoFactorStatus {
+            required: self.policy.requires(role),
+            ..self.get_status(username).await
         }
     }
 
+    /// Whether a user with `role` must enable 2FA before using the API
+    pub async fn enrollment_required(&self, username: &str, role: &str) -> bool {
+        self.policy.requires(role) && !self.get_status(username).await.enabled
+    }
+
     /// Initialize the 2FA manager
     pub async fn initialize(&self) -> Result<()> {
         // Create storage directory
@@ -277,6 +429,18 @@ impl TwoFactorManager {
             info!("Loaded backup codes for {} users", count);
         }
 
+        // Load security keys
+        let keys_file = self.storage_dir.join("security_keys.json");
+        if keys_file.exists() {
+            let json = fs::read_to_string(&keys_file).await
+                .context("Failed to read security keys file")?;
+            let keys: HashMap<String, Vec<SecurityKey>> = serde_json::from_str(&json)
+                .context("Failed to parse security keys")?;
+            let count = keys.values().map(Vec::len).sum::<usize>();
+            *self.security_keys.write().await = keys;
+            info!("Loaded {} security keys", count);
+        }
+
         Ok(())
     }
 
@@ -327,6 +491,17 @@ impl TwoFactorManager {
         Ok(())
     }
 
+    /// Save security keys to disk; they hold only public keys, so aren't encrypted
+    async fn save_security_keys(&self) -> Result<()> {
+        let keys_file = self.storage_dir.join("security_keys.json");
+        let keys = self.security_keys.read().await;
+        let json = serde_json::to_string_pretty(&*keys)
+            .context("Failed to serialize security keys")?;
+        fs::write(&keys_file, json).await
+            .context("Failed to write security keys file")?;
+        Ok(())
+    }
+
     /// Generate a new TOTP secret for a user
     pub async fn generate_secret(&self, username: &str) -> Result<TwoFactorSetup> {
         // Generate a random secret (20 bytes = 160 bits)
@@ -456,20 +631,14 @@ impl TwoFactorManager {
             secrets.get(username).cloned()
         };
 
-        let secret = match secret {
-            Some(s) if s.enabled => s,
-            Some(_) => {
-                // 2FA not enabled, skip verification
-                return Ok(true);
-            }
-            None => {
-                // No 2FA configured, skip verification
-                return Ok(true);
-            }
-        };
+        let secret = secret.filter(|s| s.enabled);
+        if secret.is_none() && !self.has_security_keys(username).await {
+            // No 2FA configured, skip verification
+            return Ok(true);
+        }
 
-        // Try TOTP code first
-        if let Some(code) = totp_code {
+        // Try TOTP code first; users with only security keys can't use one
+        if let (Some(code), Some(secret)) = (totp_code, &secret) {
             // Check rate limit
             if self.is_rate_limited(username).await {
                 warn!("User '{}' is rate limited for TOTP", username);
@@ -503,12 +672,234 @@ impl TwoFactorManager {
         Ok(false)
     }
 
+    /// Start the second login step for a user whose password was verified
+    pub async fn create_challenge(&self, username: &str) -> TwoFactorChallenge {
+        use rand::Rng;
+        let token: String = rand::thread_rng()
+            .sample_iter(&rand::distributions::Alphanumeric)
+            .take(43)
+            .map(char::from)
+            .collect();
+
+        let status = self.get_status(username).await;
+        let mut methods = Vec::new();
+        if status.totp_enabled {
+            methods.push("totp".to_string());
+        }
+        if status.has_backup_codes {
+            methods.push("recovery_code".to_string());
+        }
+        let keys = self.security_keys(username).await;
+        let webauthn = self.webauthn.as_ref()
+            .filter(|_| !keys.is_empty())
+            .map(|rp| {
+                let challenge = Self::generate_random_secret();
+                (rp.request_options(&challenge, &keys), challenge)
+            });
+        if webauthn.is_some() {
+            methods.push("webauthn".to_string());
+        }
+        let (webauthn, webauthn_challenge) = webauthn.unzip();
+
+        let now = Utc::now();
+        let mut challenges = self.challenges.write().await;
+        challenges.retain(|_, c| c.expires_at > now);
+        challenges.insert(token.clone(), LoginChallenge {
+            username: username.to_string(),
+            expires_at: now + chrono::Duration::seconds(LOGIN_CHALLENGE_TTL_SECS),
+            attempts: 0,
+            webauthn_challenge,
+        });
+
+        TwoFactorChallenge {
+            two_factor_required: true,
+            challenge_token: token,
+            expires_in: LOGIN_CHALLENGE_TTL_SECS as u64,
+            methods,
+            webauthn,
+        }
+    }
+
+    /// User a pending login challenge belongs to
+    pub async fn challenge_username(&self, token: &str) -> Option<String> {
+        self.challenges.read().await.get(token).map(|c| c.username.clone())
+    }
+
+    /// Complete a login challenge with a TOTP or backup code
+    ///
+    /// Returns the username on success and `None` for a wrong code. The
+    /// challenge is discarded on success, expiry or too many wrong codes.
+    pub async fn complete_challenge(
+        &self,
+        token: &str,
+        totp_code: Option<&str>,
+        backup_code: Option<&str>,
+    ) -> Result<Option<String>> {
+        let challenge = self.pending_challenge(token).await?;
+        let verified = self.verify_login(&challenge.username, totp_code, backup_code).await?;
+        Ok(self.finish_challenge(token, challenge, verified).await)
+    }
+
+    /// Complete a login challenge with a security key, like `complete_challenge`
+    pub async fn complete_key_challenge(
+        &self,
+        token: &str,
+        credential: &AssertionCredential,
+    ) -> Result<Option<String>> {
+        let challenge = self.pending_challenge(token).await?;
+        let (Some(rp), Some(expected)) = (&self.webauthn, &challenge.webauthn_challenge) else {
+            return Err(anyhow::anyhow!("Security keys can't be used for this login"));
+        };
+
+        let mut keys = self.security_keys.write().await;
+        let key = keys.get_mut(&challenge.username)
+            .and_then(|keys| keys.iter_mut().find(|k| k.id == credential.id));
+        let verified = match key {
+            Some(key) => match rp.verify_assertion(expected, key, credential) {
+                Ok(sign_count) => {
+                    key.sign_count = sign_count;
+                    key.last_used_at = Some(Utc::now());
+                    true
+                }
+                Err(e) => {
+                    warn!("Security key login of user '{}' failed: {}", challenge.username, e);
+                    false
+                }
+            },
+            None => false,
+        };
+        drop(keys);
+
+        if verified {
+            self.save_security_keys().await?;
+            info!("User '{}' authenticated via security key", challenge.username);
+        }
+        Ok(self.finish_challenge(token, challenge, verified).await)
+    }
+
+    /// Login challenge `token`, if it is still open
+    async fn pending_challenge(&self, token: &str) -> Result<LoginChallenge> {
+        let mut challenges = self.challenges.write().await;
+        match challenges.get(token) {
+            Some(c) if c.expires_at > Utc::now() => Ok(c.clone()),
+            Some(_) => {
+                challenges.remove(token);
+                Err(anyhow::anyhow!("Login challenge expired"))
+            }
+            None => Err(anyhow::anyhow!("Unknown login challenge")),
+        }
+    }
+
+    /// Close the challenge on success or after too many failures
+    async fn finish_challenge(&self, token: &str, challenge: LoginChallenge, verified: bool) -> Option<String> {
+        let mut challenges = self.challenges.write().await;
+        if verified {
+            challenges.remove(token);
+            return Some(challenge.username);
+        }
+        if let Some(c) = challenges.get_mut(token) {
+            c.attempts += 1;
+            if c.attempts >= self.max_attempts {
+                warn!("Login challenge for user '{}' discarded after {} failed codes", c.username, c.attempts);
+                challenges.remove(token);
+            }
+        }
+        None
+    }
+
+    async fn has_security_keys(&self, username: &str) -> bool {
+        self.security_keys.read().await.get(username).is_some_and(|keys| !keys.is_empty())
+    }
+
+    /// Security keys registered to a user
+    pub async fn security_keys(&self, username: &str) -> Vec<SecurityKey> {
+        self.security_keys.read().await.get(username).cloned().unwrap_or_default()
+    }
+
+    /// Start registering a security key, returning the options to pass to the browser
+    pub async fn start_key_registration(&self, username: &str) -> Result<CreationOptions> {
+        let rp = self.webauthn.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Security keys are not configured"))?;
+        let challenge = Self::generate_random_secret();
+        let options = rp.creation_options(&challenge, username, &self.security_keys(username).await);
+
+        let now = Utc::now();
+        let mut registrations = self.key_registrations.write().await;
+        registrations.retain(|_, r| r.expires_at > now);
+        registrations.insert(username.to_string(), PendingRegistration {
+            challenge,
+            expires_at: now + chrono::Duration::seconds(LOGIN_CHALLENGE_TTL_SECS),
+        });
+        Ok(options)
+    }
+
+    /// Finish registering a security key named `name`
+    ///
+    /// Also returns recovery codes when the user had none, so losing the key
+    /// doesn't lock them out.
+    pub async fn finish_key_registration(
+        &self,
+        username: &str,
+        name: &str,
+        credential: &RegistrationCredential,
+    ) -> Result<(SecurityKey, Option<Vec<String>>)> {
+        let rp = self.webauthn.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Security keys are not configured"))?;
+        let pending = self.key_registrations.write().await.remove(username)
+            .filter(|r| r.expires_at > Utc::now())
+            .ok_or_else(|| anyhow::anyhow!("No security key registration in progress"))?;
+        let key = rp.verify_registration(&pending.challenge, name, credential)?;
+
+        let mut keys = self.security_keys.write().await;
+        if keys.values().flatten().any(|k| k.id == key.id) {
+            return Err(anyhow::anyhow!("This security key is already registered"));
+        }
+        keys.entry(username.to_string()).or_default().push(key.clone());
+        drop(keys);
+        self.save_security_keys().await?;
+        info!("Registered security key '{}' for user '{}'", name, username);
+
+        let recovery_codes = if self.get_status(username).await.has_backup_codes {
+            None
+        } else {
+            Some(self.regenerate_backup_codes(username).await?)
+        };
+        Ok((key, recovery_codes))
+    }
+
+    /// Remove one of a user's security keys
+    pub async fn remove_security_key(&self, username: &str, id: &str) -> Result<()> {
+        let mut keys = self.security_keys.write().await;
+        let user_keys = keys.get_mut(username)
+            .ok_or_else(|| anyhow::anyhow!("Security key not found"))?;
+        let before = user_keys.len();
+        user_keys.retain(|k| k.id != id);
+        if user_keys.len() == before {
+            return Err(anyhow::anyhow!("Security key not found"));
+        }
+        if user_keys.is_empty() {
+            keys.remove(username);
+        }
+        drop(keys);
+        self.save_security_keys().await?;
+        info!("Removed security key {} of user '{}'", id, username);
+        Ok(())
+    }
+
+    /// Remove every security key of a user
+    pub async fn remove_security_keys(&self, username: &str) -> Result<()> {
+        if self.security_keys.write().await.remove(username).is_some() {
+            self.save_security_keys().await?;
+        }
+        Ok(())
+    }
+
     /// Get 2FA status for a user
     pub async fn get_status(&self, username: &str) -> TwoFactorStatus {
         let secrets = self.secrets.read().await;
         let codes = self.backup_codes.read().await;
 
-        let enabled = secrets.get(username)
+        let totp_enabled = secrets.get(username)
             .map(|s| s.enabled)
             .unwrap_or(false);
 
@@ -516,9 +907,17 @@ impl TwoFactorManager {
             .map(|c| !c.codes.is_empty())
             .unwrap_or(false);
 
+        let security_keys = self.security_keys.read().await
+            .get(username)
+            .map(Vec::len)
+            .unwrap_or(0);
+
         TwoFactorStatus {
-            enabled,
+            enabled: totp_enabled || security_keys > 0,
+            totp_enabled,
+            security_keys,
             has_backup_codes,
+            required: false,
         }
     }
 
@@ -686,6 +1085,8 @@ impl TwoFactorManager {
         if let Some(backup) = codes.get_mut(username) {
             backup.codes.retain(|c| c != &hashed);
         }
+        // save_backup_codes takes its own read lock
+        drop(codes);
 
         self.save_backup_codes().await?;
         Ok(())
@@ -782,6 +1183,44 @@ mod tests {
         assert!(!status.enabled); // Not enabled yet
     }
 
+    fn current_code(secret: &str) -> String {
+        let bytes = base32::decode(base32::Alphabet::Rfc4648 { padding: true }, secret).unwrap();
+        TOTP::new(Algorithm::SHA1, 6, 1, 30, bytes, None, String::new())
+            .unwrap()
+            .generate_current()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_login_challenge() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = TwoFactorManager::new(dir.path().to_path_buf(), "TestApp".to_string());
+        manager.initialize().await.unwrap();
+
+        let setup = manager.generate_secret("admin").await.unwrap();
+        assert!(manager.enable_2fa("admin", &current_code(&setup.secret)).await.unwrap());
+
+        // Wrong code keeps the challenge open, right code completes it once
+        let challenge = manager.create_challenge("admin").await;
+        assert_eq!(challenge.methods, ["totp", "recovery_code"]);
+        assert!(challenge.webauthn.is_none());
+        let token = &challenge.challenge_token;
+        assert_eq!(manager.complete_challenge(token, Some("000000"), None).await.unwrap(), None);
+        let code = current_code(&setup.secret);
+        assert_eq!(
+            manager.complete_challenge(token, Some(&code), None).await.unwrap().as_deref(),
+            Some("admin")
+        );
+        assert!(manager.complete_challenge(token, Some(&code), None).await.is_err());
+
+        // Recovery codes work once
+        let challenge = manager.create_challenge("admin").await;
+        let recovery = &setup.backup_codes[0];
+        assert!(manager.complete_challenge(&challenge.challenge_token, None, Some(recovery)).await.unwrap().is_some());
+        let challenge = manager.create_challenge("admin").await;
+        assert!(manager.complete_challenge(&challenge.challenge_token, None, Some(recovery)).await.unwrap().is_none());
+    }
+
     #[test]
     fn test_generate_backup_codes() {
         let codes = TwoFactorManager::generate_backup_codes();
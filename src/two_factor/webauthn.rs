@@ -0,0 +1,507 @@
+// WebAuthn Security Keys for DMPool
+// Registration and assertion checks for hardware keys used as a second factor
+
+use anyhow::{anyhow, Context, Result};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, Utc};
+use ciborium::Value;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+
+/// COSE algorithms accepted for new keys: ES256, EdDSA and RS256
+const COSE_ES256: i64 = -7;
+const COSE_EDDSA: i64 = -8;
+const COSE_RS256: i64 = -257;
+/// Milliseconds the browser gives the user to touch the key
+const CEREMONY_TIMEOUT_MS: u64 = 120_000;
+
+/// Authenticator data flags
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_ATTESTED_CREDENTIAL: u8 = 0x40;
+
+fn b64url(bytes: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn from_b64url(value: &str, what: &str) -> Result<Vec<u8>> {
+    URL_SAFE_NO_PAD.decode(value.trim_end_matches('='))
+        .with_context(|| format!("Invalid base64url in {}", what))
+}
+
+/// The site security keys are registered for
+#[derive(Clone, Debug)]
+pub struct RelyingParty {
+    /// Domain the admin UI is served from, e.g. `pool.example.com`
+    pub id: String,
+    pub name: String,
+    /// Origins allowed to run ceremonies, e.g. `https://pool.example.com`
+    pub origins: Vec<String>,
+}
+
+impl RelyingParty {
+    /// Read `WEBAUTHN_RP_ID`, `WEBAUTHN_RP_NAME` and `WEBAUTHN_ORIGINS`;
+    /// security keys are off when no relying party ID is set
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(id) = std::env::var("WEBAUTHN_RP_ID") else {
+            return Ok(None);
+        };
+        if id.is_empty() || id.contains('/') || id.contains(':') {
+            return Err(anyhow!("WEBAUTHN_RP_ID must be a bare domain: {}", id));
+        }
+        let origins = match std::env::var("WEBAUTHN_ORIGINS") {
+            Ok(origins) => origins.split(',')
+                .map(|o| o.trim().trim_end_matches('/').to_string())
+                .filter(|o| !o.is_empty())
+                .collect(),
+            Err(_) => vec![format!("https://{}", id)],
+        };
+        Ok(Some(Self {
+            name: std::env::var("WEBAUTHN_RP_NAME").unwrap_or_else(|_| "DMPool Admin".to_string()),
+            id,
+            origins,
+        }))
+    }
+
+    /// Options for `navigator.credentials.create()`
+    pub fn creation_options(&self, challenge: &[u8], username: &str, existing: &[SecurityKey]) -> CreationOptions {
+        CreationOptions {
+            challenge: b64url(challenge),
+            rp: RpEntity { id: self.id.clone(), name: self.name.clone() },
+            user: UserEntity {
+                id: b64url(&Sha256::digest(username.as_bytes())[..16]),
+                name: username.to_string(),
+                display_name: username.to_string(),
+            },
+            pub_key_cred_params: [COSE_ES256, COSE_EDDSA, COSE_RS256]
+                .into_iter()
+                .map(|alg| CredentialParameter { kind: "public-key".to_string(), alg })
+                .collect(),
+            exclude_credentials: credential_descriptors(existing),
+            timeout: CEREMONY_TIMEOUT_MS,
+            attestation: "none".to_string(),
+            authenticator_selection: AuthenticatorSelection {
+                resident_key: "discouraged".to_string(),
+                user_verification: "preferred".to_string(),
+            },
+        }
+    }
+
+    /// Options for `navigator.credentials.get()`
+    pub fn request_options(&self, challenge: &[u8], keys: &[SecurityKey]) -> RequestOptions {
+        RequestOptions {
+            challenge: b64url(challenge),
+            rp_id: self.id.clone(),
+            allow_credentials: credential_descriptors(keys),
+            timeout: CEREMONY_TIMEOUT_MS,
+            user_verification: "preferred".to_string(),
+        }
+    }
+
+    /// Check the `clientDataJSON` of a ceremony and return its SHA-256
+    fn verify_client_data(&self, encoded: &str, kind: &str, challenge: &[u8]) -> Result<[u8; 32]> {
+        let raw = from_b64url(encoded, "clientDataJSON")?;
+        let client_data: ClientData = serde_json::from_slice(&raw).context("Invalid clientDataJSON")?;
+        if client_data.kind != kind {
+            return Err(anyhow!("Unexpected ceremony type {}", client_data.kind));
+        }
+        if client_data.challenge.trim_end_matches('=') != b64url(challenge) {
+            return Err(anyhow!("Challenge mismatch"));
+        }
+        if !self.origins.contains(&client_data.origin) {
+            return Err(anyhow!("Origin {} is not allowed", client_data.origin));
+        }
+        Ok(Sha256::digest(&raw).into())
+    }
+
+    /// Parse authenticator data and check it was made for this relying party
+    /// with the user present
+    fn verify_authenticator_data<'a>(&self, data: &'a [u8]) -> Result<AuthenticatorData<'a>> {
+        let data = AuthenticatorData::parse(data)?;
+        if data.rp_id_hash != Sha256::digest(self.id.as_bytes()).as_slice() {
+            return Err(anyhow!("Security key response is for another site"));
+        }
+        if data.flags & FLAG_USER_PRESENT == 0 {
+            return Err(anyhow!("User presence was not confirmed"));
+        }
+        Ok(data)
+    }
+
+    /// Check a registration made with `challenge` and return the new key
+    ///
+    /// Only `none` attestation is requested, so attestation statements are
+    /// not checked: the key is trusted because the logged-in user added it.
+    pub fn verify_registration(
+        &self,
+        challenge: &[u8],
+        name: &str,
+        credential: &RegistrationCredential,
+    ) -> Result<SecurityKey> {
+        self.verify_client_data(&credential.response.client_data_json, "webauthn.create", challenge)?;
+
+        let attestation = from_b64url(&credential.response.attestation_object, "attestationObject")?;
+        let attestation: Value = ciborium::de::from_reader(attestation.as_slice())
+            .map_err(|e| anyhow!("Invalid attestationObject: {}", e))?;
+        let auth_data = map_entry(&attestation, |k| k.as_text() == Some("authData"))
+            .and_then(Value::as_bytes)
+            .ok_or_else(|| anyhow!("attestationObject has no authData"))?;
+
+        let data = self.verify_authenticator_data(auth_data)?;
+        let attested = data.credential
+            .ok_or_else(|| anyhow!("Registration has no credential"))?;
+        // Fail now rather than at the first login if the algorithm is unsupported
+        PublicKey::from_cose(attested.public_key)?;
+
+        Ok(SecurityKey {
+            id: b64url(attested.id),
+            name: name.to_string(),
+            public_key: b64url(attested.public_key),
+            sign_count: data.sign_count,
+            created_at: Utc::now(),
+            last_used_at: None,
+        })
+    }
+
+    /// Check an assertion made with `challenge` by `key`, returning its new
+    /// signature counter
+    pub fn verify_assertion(
+        &self,
+        challenge: &[u8],
+        key: &SecurityKey,
+        credential: &AssertionCredential,
+    ) -> Result<u32> {
+        let response = &credential.response;
+        let client_data_hash = self.verify_client_data(&response.client_data_json, "webauthn.get", challenge)?;
+        let auth_data = from_b64url(&response.authenticator_data, "authenticatorData")?;
+        let data = self.verify_authenticator_data(&auth_data)?;
+
+        let signature = from_b64url(&response.signature, "signature")?;
+        let signed = [auth_data.as_slice(), &client_data_hash].concat();
+        PublicKey::from_cose(&from_b64url(&key.public_key, "stored public key")?)?
+            .verify(&signed, &signature)?;
+
+        // A counter that stops increasing suggests the key was cloned
+        if (data.sign_count != 0 || key.sign_count != 0) && data.sign_count <= key.sign_count {
+            return Err(anyhow!("Signature counter of security key '{}' went backwards", key.name));
+        }
+        Ok(data.sign_count)
+    }
+}
+
+fn credential_descriptors(keys: &[SecurityKey]) -> Vec<CredentialDescriptor> {
+    keys.iter()
+        .map(|key| CredentialDescriptor { kind: "public-key".to_string(), id: key.id.clone() })
+        .collect()
+}
+
+fn map_entry(map: &Value, key: impl Fn(&Value) -> bool) -> Option<&Value> {
+    map.as_map()?.iter().find(|(k, _)| key(k)).map(|(_, v)| v)
+}
+
+/// Entry of a COSE key map by its integer label
+fn cose_entry(map: &Value, label: i64) -> Option<&Value> {
+    map_entry(map, |k| k.as_integer().is_some_and(|i| i128::from(i) == i128::from(label)))
+}
+
+fn cose_int(map: &Value, label: i64) -> Option<i128> {
+    cose_entry(map, label)?.as_integer().map(i128::from)
+}
+
+fn cose_bytes(map: &Value, label: i64) -> Result<&[u8]> {
+    cose_entry(map, label)
+        .and_then(Value::as_bytes)
+        .map(Vec::as_slice)
+        .ok_or_else(|| anyhow!("COSE key is missing parameter {}", label))
+}
+
+/// Credential public key, decoded from its COSE form
+enum PublicKey {
+    Es256(Vec<u8>),
+    EdDsa(Vec<u8>),
+    Rs256 { n: Vec<u8>, e: Vec<u8> },
+}
+
+impl PublicKey {
+    fn from_cose(bytes: &[u8]) -> Result<Self> {
+        let key: Value = ciborium::de::from_reader(bytes)
+            .map_err(|e| anyhow!("Invalid COSE key: {}", e))?;
+        // kty (1), alg (3), crv (-1)
+        match (cose_int(&key, 1), cose_int(&key, 3).map(|a| a as i64), cose_int(&key, -1)) {
+            (Some(2), Some(COSE_ES256), Some(1)) => {
+                let point = [&[0x04][..], cose_bytes(&key, -2)?, cose_bytes(&key, -3)?].concat();
+                Ok(Self::Es256(point))
+            }
+            (Some(1), Some(COSE_EDDSA), Some(6)) => Ok(Self::EdDsa(cose_bytes(&key, -2)?.to_vec())),
+            (Some(3), Some(COSE_RS256), _) => Ok(Self::Rs256 {
+                n: cose_bytes(&key, -1)?.to_vec(),
+                e: cose_bytes(&key, -2)?.to_vec(),
+            }),
+            (kty, alg, _) => Err(anyhow!("Unsupported security key algorithm (kty {:?}, alg {:?})", kty, alg)),
+        }
+    }
+
+    fn verify(&self, message: &[u8], signature: &[u8]) -> Result<()> {
+        use ring::signature;
+        let verified = match self {
+            Self::Es256(point) => signature::UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_ASN1, point)
+                .verify(message, signature),
+            Self::EdDsa(key) => signature::UnparsedPublicKey::new(&signature::ED25519, key)
+                .verify(message, signature),
+            Self::Rs256 { n, e } => signature::RsaPublicKeyComponents { n, e }
+                .verify(&signature::RSA_PKCS1_2048_8192_SHA256, message, signature),
+        };
+        verified.map_err(|_| anyhow!("Invalid security key signature"))
+    }
+}
+
+/// Credential added by a registration
+struct AttestedCredential<'a> {
+    id: &'a [u8],
+    /// COSE_Key encoding
+    public_key: &'a [u8],
+}
+
+/// Authenticator data of a registration or assertion
+struct AuthenticatorData<'a> {
+    rp_id_hash: &'a [u8],
+    flags: u8,
+    sign_count: u32,
+    credential: Option<AttestedCredential<'a>>,
+}
+
+impl<'a> AuthenticatorData<'a> {
+    fn parse(data: &'a [u8]) -> Result<Self> {
+        let too_short = || anyhow!("Authenticator data is truncated");
+        if data.len() < 37 {
+            return Err(too_short());
+        }
+        let flags = data[32];
+        let sign_count = u32::from_be_bytes([data[33], data[34], data[35], data[36]]);
+        let mut credential = None;
+        if flags & FLAG_ATTESTED_CREDENTIAL != 0 {
+            // AAGUID (16 bytes), then the credential ID with a 2-byte length
+            let rest = data.get(53..).ok_or_else(too_short)?;
+            let id_len = rest.get(..2)
+                .map(|len| u16::from_be_bytes([len[0], len[1]]) as usize)
+                .ok_or_else(too_short)?;
+            let id = rest.get(2..2 + id_len).ok_or_else(too_short)?;
+            let mut key = &rest[2 + id_len..];
+            let before = key.len();
+            let _: Value = ciborium::de::from_reader(&mut key)
+                .map_err(|e| anyhow!("Invalid credential public key: {}", e))?;
+            let key_len = before - key.len();
+            credential = Some(AttestedCredential {
+                id,
+                public_key: &rest[2 + id_len..2 + id_len + key_len],
+            });
+        }
+        Ok(Self { rp_id_hash: &data[..32], flags, sign_count, credential })
+    }
+}
+
+#[derive(Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    kind: String,
+    challenge: String,
+    origin: String,
+}
+
+/// Security key registered to a user
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct SecurityKey {
+    /// Credential ID, base64url
+    pub id: String,
+    pub name: String,
+    /// COSE public key, base64url
+    pub public_key: String,
+    pub sign_count: u32,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct RpEntity {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UserEntity {
+    pub id: String,
+    pub name: String,
+    pub display_name: String,
+}
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct CredentialParameter {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub alg: i64,
+}
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct CredentialDescriptor {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub id: String,
+}
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticatorSelection {
+    pub resident_key: String,
+    pub user_verification: String,
+}
+
+/// `publicKey` options for registering a security key; binary fields are base64url
+#[derive(Clone, Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreationOptions {
+    pub challenge: String,
+    pub rp: RpEntity,
+    pub user: UserEntity,
+    pub pub_key_cred_params: Vec<CredentialParameter>,
+    pub exclude_credentials: Vec<CredentialDescriptor>,
+    pub timeout: u64,
+    pub attestation: String,
+    pub authenticator_selection: AuthenticatorSelection,
+}
+
+/// `publicKey` options for logging in with a security key; binary fields are base64url
+#[derive(Clone, Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestOptions {
+    pub challenge: String,
+    pub rp_id: String,
+    pub allow_credentials: Vec<CredentialDescriptor>,
+    pub timeout: u64,
+    pub user_verification: String,
+}
+
+/// Response of `navigator.credentials.create()`, as `PublicKeyCredential.toJSON()` encodes it
+#[derive(Clone, Debug, Deserialize, ToSchema)]
+pub struct RegistrationCredential {
+    pub id: String,
+    pub response: AttestationResponse,
+}
+
+#[derive(Clone, Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AttestationResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    pub attestation_object: String,
+}
+
+/// Response of `navigator.credentials.get()`, as `PublicKeyCredential.toJSON()` encodes it
+#[derive(Clone, Debug, Deserialize, ToSchema)]
+pub struct AssertionCredential {
+    pub id: String,
+    pub response: AssertionResponse,
+}
+
+#[derive(Clone, Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AssertionResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    pub authenticator_data: String,
+    pub signature: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::rand::SystemRandom;
+    use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
+
+    fn cbor(value: &Value) -> Vec<u8> {
+        let mut out = Vec::new();
+        ciborium::ser::into_writer(value, &mut out).unwrap();
+        out
+    }
+
+    fn client_data(kind: &str, challenge: &[u8], origin: &str) -> String {
+        b64url(serde_json::json!({ "type": kind, "challenge": b64url(challenge), "origin": origin }).to_string().as_bytes())
+    }
+
+    fn auth_data(rp: &RelyingParty, flags: u8, sign_count: u32, attested: &[u8]) -> Vec<u8> {
+        [&Sha256::digest(rp.id.as_bytes())[..], &[flags], &sign_count.to_be_bytes(), attested].concat()
+    }
+
+    #[test]
+    fn test_register_and_assert_with_p256_key() {
+        let rp = RelyingParty {
+            id: "pool.example.com".to_string(),
+            name: "DMPool Admin".to_string(),
+            origins: vec!["https://pool.example.com".to_string()],
+        };
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+        let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &rng).unwrap();
+        let point = key_pair.public_key().as_ref();
+
+        let cose_key = cbor(&Value::Map(vec![
+            (1.into(), 2.into()),
+            (3.into(), COSE_ES256.into()),
+            ((-1).into(), 1.into()),
+            ((-2).into(), Value::Bytes(point[1..33].to_vec())),
+            ((-3).into(), Value::Bytes(point[33..].to_vec())),
+        ]));
+        let credential_id = [7u8; 16];
+        let attested = [&[0u8; 16][..], &16u16.to_be_bytes(), &credential_id, &cose_key].concat();
+        let attestation_object = |data: Vec<u8>| b64url(&cbor(&Value::Map(vec![
+            ("fmt".into(), "none".into()),
+            ("attStmt".into(), Value::Map(vec![])),
+            ("authData".into(), Value::Bytes(data)),
+        ])));
+
+        let challenge = [1u8; 32];
+        let registration = |origin: &str, data: Vec<u8>| RegistrationCredential {
+            id: b64url(&credential_id),
+            response: AttestationResponse {
+                client_data_json: client_data("webauthn.create", &challenge, origin),
+                attestation_object: attestation_object(data),
+            },
+        };
+        let flags = FLAG_USER_PRESENT | FLAG_ATTESTED_CREDENTIAL;
+        let key = rp.verify_registration(&challenge, "YubiKey", &registration(
+            "https://pool.example.com", auth_data(&rp, flags, 0, &attested),
+        )).unwrap();
+        assert_eq!(key.id, b64url(&credential_id));
+        assert!(rp.verify_registration(&[2u8; 32], "YubiKey", &registration(
+            "https://pool.example.com", auth_data(&rp, flags, 0, &attested),
+        )).is_err());
+        assert!(rp.verify_registration(&challenge, "YubiKey", &registration(
+            "https://evil.example.net", auth_data(&rp, flags, 0, &attested),
+        )).is_err());
+
+        let login_challenge = [3u8; 32];
+        let assertion = |sign_count: u32, origin: &str| {
+            let data = auth_data(&rp, FLAG_USER_PRESENT, sign_count, &[]);
+            let client_data_json = client_data("webauthn.get", &login_challenge, origin);
+            let hash = Sha256::digest(from_b64url(&client_data_json, "").unwrap());
+            let signature = key_pair.sign(&rng, &[data.as_slice(), &hash].concat()).unwrap();
+            AssertionCredential {
+                id: key.id.clone(),
+                response: AssertionResponse {
+                    client_data_json,
+                    authenticator_data: b64url(&data),
+                    signature: b64url(signature.as_ref()),
+                },
+            }
+        };
+        assert_eq!(rp.verify_assertion(&login_challenge, &key, &assertion(5, "https://pool.example.com")).unwrap(), 5);
+        assert!(rp.verify_assertion(&[4u8; 32], &key, &assertion(6, "https://pool.example.com")).is_err());
+        assert!(rp.verify_assertion(&login_challenge, &key, &assertion(6, "https://evil.example.net")).is_err());
+
+        // Tampered signatures and counters that went backwards are refused
+        let mut tampered = assertion(6, "https://pool.example.com");
+        tampered.response.authenticator_data = b64url(&auth_data(&rp, FLAG_USER_PRESENT, 7, &[]));
+        assert!(rp.verify_assertion(&login_challenge, &key, &tampered).is_err());
+        let used = SecurityKey { sign_count: 5, ..key.clone() };
+        assert!(rp.verify_assertion(&login_challenge, &used, &assertion(5, "https://pool.example.com")).is_err());
+    }
+}
@@ -0,0 +1,195 @@
+// Vardiff Simulation for DMPool
+// Projects how variable difficulty converges for a miner's hashrate under the pool's settings
+
+use crate::estimate::HASHES_PER_DIFFICULTY;
+use crate::share_stats::STUCK_SHARES_PER_MINUTE;
+use anyhow::Result;
+use serde::Serialize;
+
+/// Shares since the last change before vardiff reconsiders the difficulty
+pub const RETARGET_SHARES: u64 = 72;
+/// Seconds since the last change before vardiff reconsiders the difficulty
+pub const RETARGET_SECS: f64 = 240.0;
+/// Difficulty is kept while shares per second per unit of difficulty stay in this band
+pub const KEEP_RATE_BAND: (f64, f64) = (0.15, 0.4);
+/// Seconds per share vardiff aims for: the new difficulty is the measured
+/// difficulty per second times this
+pub const DEFAULT_MULTIPLIER: f64 = 3.33;
+/// Retargets simulated at most, in case settings never settle
+const MAX_RETARGETS: usize = 64;
+
+/// Settings the simulation runs with
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct VardiffParams {
+    pub start_difficulty: u64,
+    pub minimum_difficulty: u64,
+    pub multiplier: f64,
+}
+
+impl VardiffParams {
+    pub fn validate(&self) -> Result<()> {
+        if self.start_difficulty == 0 || self.minimum_difficulty == 0 {
+            return Err(anyhow::anyhow!("Start and minimum difficulty must be at least 1"));
+        }
+        if !self.multiplier.is_finite() || self.multiplier <= 0.0 {
+            return Err(anyhow::anyhow!("Multiplier must be a positive number"));
+        }
+        Ok(())
+    }
+}
+
+/// The difficulty a miner is given from `at_secs` on
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct VardiffStep {
+    /// Seconds since the miner connected
+    pub at_secs: f64,
+    pub difficulty: u64,
+    /// Expected seconds between shares at this difficulty
+    pub share_interval_secs: f64,
+    /// Shares submitted at the previous difficulty before this change
+    pub shares_before: u64,
+}
+
+/// Projected difficulty changes of a miner, from connecting until it settles
+#[derive(Clone, Debug, Serialize)]
+pub struct VardiffSimulation {
+    pub hashrate_ths: f64,
+    pub params: VardiffParams,
+    /// The start difficulty, then every retarget
+    pub steps: Vec<VardiffStep>,
+    pub final_difficulty: u64,
+    pub final_share_interval_secs: f64,
+    pub final_shares_per_minute: f64,
+    /// When the last change happened; `None` if the limit of retargets was hit first
+    pub converged_after_secs: Option<f64>,
+    /// The minimum difficulty keeps the miner above the share rate vardiff aims for
+    pub clamped_at_minimum: bool,
+    /// Settings worth revisiting for this hashrate
+    pub warnings: Vec<String>,
+}
+
+/// Simulate vardiff for a miner of `hashrate_ths` using expected share arrival
+/// times, so luck doesn't blur the result
+///
+/// Follows the ckpool-style algorithm: after `RETARGET_SHARES` shares or
+/// `RETARGET_SECS` seconds at a difficulty, a share rate outside
+/// `KEEP_RATE_BAND` sets the difficulty to the measured rate times the
+/// multiplier, no lower than the minimum.
+pub fn simulate(hashrate_ths: f64, params: VardiffParams) -> Result<VardiffSimulation> {
+    params.validate()?;
+    if !hashrate_ths.is_finite() || hashrate_ths <= 0.0 {
+        return Err(anyhow::anyhow!("Hashrate must be a positive number of TH/s"));
+    }
+    // Difficulty the miner clears per second
+    let difficulty_rate = hashrate_ths * 1e12 / HASHES_PER_DIFFICULTY;
+    let interval = |difficulty: u64| difficulty as f64 / difficulty_rate;
+
+    let mut difficulty = params.start_difficulty;
+    let mut steps = vec![VardiffStep {
+        at_secs: 0.0,
+        difficulty,
+        share_interval_secs: interval(difficulty),
+        shares_before: 0,
+    }];
+    let mut converged_after_secs = None;
+    let (mut now, mut changed_at, mut shares) = (0.0, 0.0, 0u64);
+
+    while steps.len() <= MAX_RETARGETS {
+        if shares < RETARGET_SHARES && now - changed_at < RETARGET_SECS {
+            // Jump to the share that reaches either retarget threshold
+            let to_count = (RETARGET_SHARES - shares) as f64;
+            let to_time = ((RETARGET_SECS - (now - changed_at)) / interval(difficulty)).ceil().max(1.0);
+            let next = to_count.min(to_time);
+            now += next * interval(difficulty);
+            shares += next as u64;
+        }
+        let rate = difficulty_rate / difficulty as f64;
+        let optimal = ((difficulty_rate * params.multiplier).round() as u64).max(params.minimum_difficulty).max(1);
+        if (rate > KEEP_RATE_BAND.0 && rate < KEEP_RATE_BAND.1) || optimal == difficulty {
+            converged_after_secs = Some(changed_at);
+            break;
+        }
+        difficulty = optimal;
+        steps.push(VardiffStep {
+            at_secs: now,
+            difficulty,
+            share_interval_secs: interval(difficulty),
+            shares_before: shares,
+        });
+        changed_at = now;
+        shares = 0;
+    }
+
+    let final_share_interval_secs = interval(difficulty);
+    let final_shares_per_minute = 60.0 / final_share_interval_secs;
+    let clamped_at_minimum = difficulty == params.minimum_difficulty
+        && difficulty_rate * params.multiplier < params.minimum_difficulty as f64;
+
+    let mut warnings = Vec::new();
+    if clamped_at_minimum && final_shares_per_minute > STUCK_SHARES_PER_MINUTE {
+        warnings.push(format!(
+            "Minimum difficulty {} keeps this miner at {:.1} shares a minute; vardiff would pick {:.0}",
+            params.minimum_difficulty,
+            final_shares_per_minute,
+            difficulty_rate * params.multiplier
+        ));
+    }
+    if steps[0].share_interval_secs > RETARGET_SECS {
+        warnings.push(format!(
+            "Start difficulty {} is too high for this hashrate: the first share takes {:.0} seconds",
+            params.start_difficulty,
+            steps[0].share_interval_secs
+        ));
+    }
+    if let Some(first_retarget) = steps.get(1).filter(|step| step.at_secs > 2.0 * RETARGET_SECS) {
+        warnings.push(format!(
+            "The first retarget takes {:.0} seconds; a start difficulty closer to {} settles sooner",
+            first_retarget.at_secs,
+            first_retarget.difficulty
+        ));
+    }
+
+    Ok(VardiffSimulation {
+        hashrate_ths,
+        params,
+        steps,
+        final_difficulty: difficulty,
+        final_share_interval_secs,
+        final_shares_per_minute,
+        converged_after_secs,
+        clamped_at_minimum,
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simulation_converges_and_clamps() {
+        let params = VardiffParams { start_difficulty: 32, minimum_difficulty: 16, multiplier: DEFAULT_MULTIPLIER };
+
+        // 100 TH/s floods shares at difficulty 32 until 72 are in, then settles
+        let sim = simulate(100.0, params).unwrap();
+        assert_eq!(sim.steps.len(), 2);
+        assert_eq!(sim.steps[1].shares_before, RETARGET_SHARES);
+        assert!((sim.final_share_interval_secs - DEFAULT_MULTIPLIER).abs() < 0.01);
+        assert_eq!(sim.converged_after_secs, Some(sim.steps[1].at_secs));
+        assert!(!sim.clamped_at_minimum && sim.warnings.is_empty());
+
+        // A tiny miner is held at the minimum and still submits too often
+        let sim = simulate(0.01, params).unwrap();
+        assert!(sim.clamped_at_minimum);
+        assert_eq!(sim.final_difficulty, 16);
+        assert!(sim.warnings.iter().any(|w| w.contains("Minimum difficulty 16")), "{:?}", sim.warnings);
+
+        // Starting far too high waits a long time for the first share, then retargets down
+        let sim = simulate(0.001, VardiffParams { start_difficulty: 1_000_000, ..params }).unwrap();
+        assert!(sim.final_difficulty < 1_000_000);
+        assert!(sim.warnings.iter().any(|w| w.contains("too high")), "{:?}", sim.warnings);
+
+        assert!(simulate(0.0, params).is_err());
+        assert!(simulate(1.0, VardiffParams { multiplier: 0.0, ..params }).is_err());
+    }
+}
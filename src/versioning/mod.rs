@@ -0,0 +1,114 @@
+// API Versioning for DMPool
+// Mounts each API version under its own prefix and keeps the unversioned paths as deprecated aliases
+
+use axum::{
+    extract::{OriginalUri, Request},
+    http::{header, HeaderValue},
+    middleware::{self, Next},
+    response::Response,
+    Router,
+};
+use std::borrow::Cow;
+
+/// Version of the HTTP API
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiVersion {
+    V1,
+}
+
+impl ApiVersion {
+    /// Every version, oldest first
+    pub const ALL: &'static [ApiVersion] = &[ApiVersion::V1];
+
+    /// Version the unversioned `/api/...` paths stay an alias of
+    ///
+    /// They predate versioning, so they keep serving v1 even once newer
+    /// versions exist.
+    pub const UNVERSIONED: ApiVersion = ApiVersion::V1;
+
+    /// Mount point of this version, e.g. `/api/v1`
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "/api/v1",
+        }
+    }
+
+    /// Version a request path was made under, `None` for unversioned paths
+    pub fn of_path(path: &str) -> Option<ApiVersion> {
+        Self::ALL.iter().copied().find(|version| {
+            path.strip_prefix(version.prefix())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+}
+
+/// Path with its version segment removed: `/api/v1/workers` becomes `/api/workers`
+///
+/// Checks written against API paths, such as rate limit rules, use this so
+/// they cover every version.
+pub fn unversioned_path(path: &str) -> Cow<'_, str> {
+    match ApiVersion::of_path(path) {
+        Some(version) => Cow::Owned(format!("/api{}", &path[version.prefix().len()..])),
+        None => Cow::Borrowed(path),
+    }
+}
+
+/// Path of a request as the client sent it, before any nesting stripped its prefix
+pub fn request_path(req: &Request) -> &str {
+    req.extensions()
+        .get::<OriginalUri>()
+        .map(|uri| uri.path())
+        .unwrap_or_else(|| req.uri().path())
+}
+
+/// Mark responses of unversioned aliases as deprecated
+///
+/// Adds `Deprecation: true` and a `Link` to the same route under its version.
+async fn deprecation_middleware(req: Request, next: Next) -> Response {
+    let successor = request_path(&req)
+        .strip_prefix("/api")
+        .map(|rest| format!("<{}{}>; rel=\"successor-version\"", ApiVersion::UNVERSIONED.prefix(), rest));
+    let mut response = next.run(req).await;
+    let headers = response.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static("true"));
+    if let Some(link) = successor.and_then(|link| HeaderValue::from_str(&link).ok()) {
+        headers.append(header::LINK, link);
+    }
+    response
+}
+
+/// Router serving each version's routes under its prefix
+///
+/// Routes are written relative to the version root (`/workers`, not
+/// `/api/v1/workers`). The [`ApiVersion::UNVERSIONED`] routes are also
+/// served under `/api`, marked deprecated.
+pub fn versioned_router<S>(versions: Vec<(ApiVersion, Router<S>)>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let mut router = Router::new();
+    for (version, routes) in versions {
+        if version == ApiVersion::UNVERSIONED {
+            router = router.nest("/api", routes.clone().layer(middleware::from_fn(deprecation_middleware)));
+        }
+        router = router.nest(version.prefix(), routes);
+    }
+    router
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_paths() {
+        assert_eq!(ApiVersion::of_path("/api/v1/workers"), Some(ApiVersion::V1));
+        assert_eq!(ApiVersion::of_path("/api/v1"), Some(ApiVersion::V1));
+        assert_eq!(ApiVersion::of_path("/api/v10/workers"), None);
+        assert_eq!(ApiVersion::of_path("/api/workers"), None);
+
+        assert_eq!(unversioned_path("/api/v1/backup/abc/restore"), "/api/backup/abc/restore");
+        assert_eq!(unversioned_path("/api/backup/abc/restore"), "/api/backup/abc/restore");
+        assert_eq!(unversioned_path("/"), "/");
+    }
+}
@@ -0,0 +1,404 @@
+// Payout Wallet for DMPool
+// Sends payout batches through Bitcoin Core's wallet and tracks their confirmations
+
+use crate::payout::{Payment, PayoutBatch, PayoutEngine, PayoutStatus};
+use anyhow::{Context, Result};
+use base64::Engine;
+use bitcoin::{Address, Psbt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+/// Bitcoin Core wallet calls needed to pay a batch
+pub trait WalletRpc: Send + Sync {
+    /// Pay all outputs in one transaction (`sendmany`); returns the txid
+    fn send_many(&self, payments: &[Payment], comment: &str) -> Result<String>;
+    /// Fund an unsigned transaction paying all outputs (`walletcreatefundedpsbt`)
+    fn create_psbt(&self, payments: &[Payment]) -> Result<String>;
+    /// Finalize a signed PSBT and broadcast it; returns the txid
+    fn broadcast_psbt(&self, psbt: &str) -> Result<String>;
+    /// Confirmations of a wallet transaction; negative if it conflicts with the chain
+    fn confirmations(&self, txid: &str) -> Result<i64>;
+}
+
+/// Run a wallet call on the blocking thread pool, as RPC clients block
+async fn blocking<T: Send + 'static>(
+    rpc: &Arc<dyn WalletRpc>,
+    call: impl FnOnce(&dyn WalletRpc) -> Result<T> + Send + 'static,
+) -> Result<T> {
+    let rpc = rpc.clone();
+    tokio::task::spawn_blocking(move || call(rpc.as_ref())).await
+        .context("Wallet call panicked")?
+}
+
+fn decode_psbt(psbt: &str) -> Result<Psbt> {
+    let bytes = base64::engine::general_purpose::STANDARD.decode(psbt.trim())
+        .context("PSBT is not valid base64")?;
+    Psbt::deserialize(&bytes).context("Invalid PSBT")
+}
+
+/// Check that `signed` is the transaction created for `batch`, so a PSBT
+/// with other outputs or amounts is never broadcast
+fn verify_signed_psbt(batch: &PayoutBatch, signed: &str) -> Result<()> {
+    let created = batch.psbt.as_deref()
+        .ok_or_else(|| anyhow::anyhow!("Payout batch {} has no PSBT", batch.id))?;
+    let created = decode_psbt(created).context("Invalid PSBT of the batch")?;
+    let signed = decode_psbt(signed)?;
+    if signed.unsigned_tx.compute_txid() != created.unsigned_tx.compute_txid() {
+        return Err(anyhow::anyhow!("Signed PSBT is not the transaction created for batch {}", batch.id));
+    }
+    for payment in &batch.payments {
+        let script = Address::from_str(&payment.address)
+            .with_context(|| format!("Invalid payout address {}", payment.address))?
+            .assume_checked()
+            .script_pubkey();
+        let paid: u64 = signed.unsigned_tx.output.iter()
+            .filter(|output| output.script_pubkey == script)
+            .map(|output| output.value.to_sat())
+            .sum();
+        if paid != payment.amount_sats {
+            return Err(anyhow::anyhow!(
+                "Signed PSBT pays {} sats to {}, not {}",
+                paid, payment.address, payment.amount_sats
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Outputs as an address to BTC amount map, as the wallet RPCs expect
+fn outputs(payments: &[Payment]) -> Value {
+    let outputs: Map<String, Value> = payments.iter()
+        .map(|p| (p.address.clone(), json!(p.amount_sats as f64 / 100_000_000.0)))
+        .collect();
+    Value::Object(outputs)
+}
+
+impl WalletRpc for bitcoincore_rpc::Client {
+    fn send_many(&self, payments: &[Payment], comment: &str) -> Result<String> {
+        use bitcoincore_rpc::RpcApi;
+        self.call("sendmany", &["".into(), outputs(payments), 1.into(), comment.into()])
+            .map_err(|e| anyhow::anyhow!("sendmany failed: {}", e))
+    }
+
+    fn create_psbt(&self, payments: &[Payment]) -> Result<String> {
+        use bitcoincore_rpc::RpcApi;
+        let funded: Value = self.call("walletcreatefundedpsbt", &[json!([]), json!([outputs(payments)])])
+            .map_err(|e| anyhow::anyhow!("walletcreatefundedpsbt failed: {}", e))?;
+        funded["psbt"].as_str()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow::anyhow!("walletcreatefundedpsbt returned no PSBT"))
+    }
+
+    fn broadcast_psbt(&self, psbt: &str) -> Result<String> {
+        use bitcoincore_rpc::RpcApi;
+        let finalized: Value = self.call("finalizepsbt", &[psbt.into()])
+            .map_err(|e| anyhow::anyhow!("finalizepsbt failed: {}", e))?;
+        if !finalized["complete"].as_bool().unwrap_or(false) {
+            return Err(anyhow::anyhow!("PSBT is not fully signed"));
+        }
+        let hex = finalized["hex"].as_str()
+            .ok_or_else(|| anyhow::anyhow!("finalizepsbt returned no transaction"))?;
+        self.call("sendrawtransaction", &[hex.into()])
+            .map_err(|e| anyhow::anyhow!("sendrawtransaction failed: {}", e))
+    }
+
+    fn confirmations(&self, txid: &str) -> Result<i64> {
+        use bitcoincore_rpc::RpcApi;
+        let tx: Value = self.call("gettransaction", &[txid.into()])
+            .map_err(|e| anyhow::anyhow!("gettransaction failed: {}", e))?;
+        tx["confirmations"].as_i64()
+            .ok_or_else(|| anyhow::anyhow!("gettransaction returned no confirmations"))
+    }
+}
+
+/// How pending batches are paid
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WalletMode {
+    /// Log what would be paid without touching the wallet
+    #[default]
+    DryRun,
+    /// Sign and broadcast with the node's hot wallet
+    SendMany,
+    /// Create a PSBT for an external signer; broadcast once it comes back signed
+    Psbt,
+}
+
+impl FromStr for WalletMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "dry_run" | "dry-run" | "dryrun" => Ok(WalletMode::DryRun),
+            "sendmany" | "send_many" => Ok(WalletMode::SendMany),
+            "psbt" => Ok(WalletMode::Psbt),
+            other => Err(anyhow::anyhow!("Unknown wallet mode: {}", other)),
+        }
+    }
+}
+
+/// Pays pending batches and follows sent ones until they confirm
+pub struct PayoutWallet {
+    mode: WalletMode,
+    required_confirmations: i64,
+}
+
+impl PayoutWallet {
+    pub fn new(mode: WalletMode) -> Self {
+        Self {
+            mode,
+            required_confirmations: 6,
+        }
+    }
+
+    /// Confirmations after which a sent batch counts as paid
+    pub fn with_required_confirmations(mut self, confirmations: i64) -> Self {
+        self.required_confirmations = confirmations.max(1);
+        self
+    }
+
+    pub fn mode(&self) -> WalletMode {
+        self.mode
+    }
+
+    /// Submit pending batches and refresh confirmations of sent ones
+    ///
+    /// Failures are recorded on the batch and don't stop other batches.
+    pub async fn process(&self, engine: &PayoutEngine, rpc: Arc<dyn WalletRpc>) -> Result<()> {
+        for batch in engine.batches().await {
+            let result = match batch.status {
+                PayoutStatus::Pending => self.submit(engine, &rpc, &batch).await,
+                PayoutStatus::Sent => self.track(engine, &rpc, &batch).await,
+                _ => Ok(()),
+            };
+            if let Err(e) = result {
+                error!("Payout batch {} failed: {:#}", batch.id, e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn submit(&self, engine: &PayoutEngine, rpc: &Arc<dyn WalletRpc>, batch: &PayoutBatch) -> Result<()> {
+        match self.mode {
+            WalletMode::DryRun => {
+                if batch.note.is_none() {
+                    let note = format!(
+                        "Dry run: would pay {} sats to {} address(es)",
+                        batch.total_sats, batch.payments.len()
+                    );
+                    info!("Payout batch {}: {}", batch.id, note);
+                    engine.set_note(&batch.id, &note).await?;
+                }
+            }
+            WalletMode::SendMany => {
+                engine.mark_sending(&batch.id).await?;
+                let payments = batch.payments.clone();
+                let comment = format!("dmpool payout {}", batch.id);
+                match blocking(rpc, move |rpc| rpc.send_many(&payments, &comment)).await {
+                    Ok(txid) => {
+                        engine.mark_sent(&batch.id, &txid).await?;
+                        info!("Payout batch {} sent in {}", batch.id, txid);
+                    }
+                    Err(e) => {
+                        engine.mark_failed(&batch.id, &format!("{:#}", e)).await?;
+                        return Err(e);
+                    }
+                }
+            }
+            WalletMode::Psbt => {
+                let payments = batch.payments.clone();
+                let psbt = blocking(rpc, move |rpc| rpc.create_psbt(&payments)).await?;
+                engine.attach_psbt(&batch.id, &psbt).await?;
+                info!("Payout batch {} is waiting for a signed PSBT", batch.id);
+            }
+        }
+        Ok(())
+    }
+
+    async fn track(&self, engine: &PayoutEngine, rpc: &Arc<dyn WalletRpc>, batch: &PayoutBatch) -> Result<()> {
+        let Some(txid) = batch.txid.clone() else {
+            return Ok(());
+        };
+        let lookup = txid.clone();
+        let confirmations = blocking(rpc, move |rpc| rpc.confirmations(&lookup)).await?;
+        if confirmations < 0 {
+            warn!("Payout transaction {} of batch {} conflicts with the chain", txid, batch.id);
+            engine.mark_failed(&batch.id, &format!("Transaction {} was replaced or double-spent", txid)).await?;
+        } else if confirmations != batch.confirmations {
+            engine.update_confirmations(&batch.id, confirmations, self.required_confirmations).await?;
+        }
+        Ok(())
+    }
+
+    /// Broadcast the externally signed PSBT of a batch awaiting its signature
+    ///
+    /// The PSBT must be the transaction created for the batch.
+    pub async fn broadcast_signed(
+        &self,
+        engine: &PayoutEngine,
+        rpc: Arc<dyn WalletRpc>,
+        batch_id: &str,
+        signed_psbt: &str,
+    ) -> Result<PayoutBatch> {
+        let batch = engine.batch(batch_id).await
+            .ok_or_else(|| anyhow::anyhow!("Payout batch not found: {}", batch_id))?;
+        if batch.status != PayoutStatus::AwaitingSignature {
+            return Err(anyhow::anyhow!("Payout batch {} is not awaiting a signature", batch_id));
+        }
+        verify_signed_psbt(&batch, signed_psbt)?;
+        let signed_psbt = signed_psbt.to_string();
+        let txid = blocking(&rpc, move |rpc| rpc.broadcast_psbt(&signed_psbt)).await?;
+        info!("Payout batch {} broadcast in {}", batch_id, txid);
+        engine.mark_sent(batch_id, &txid).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::FoundBlock;
+    use crate::payout::PayoutConfig;
+    use chrono::Utc;
+    use p2poolv2_lib::accounting::simple_pplns::SimplePplnsShare;
+    use std::sync::Mutex;
+
+    const MINER: &str = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+    /// Unsigned PSBT paying `payments` from a made-up input
+    fn psbt(payments: &[Payment]) -> String {
+        use bitcoin::{absolute, transaction, Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness};
+        let tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: payments.iter()
+                .map(|p| TxOut {
+                    value: Amount::from_sat(p.amount_sats),
+                    script_pubkey: Address::from_str(&p.address).unwrap().assume_checked().script_pubkey(),
+                })
+                .collect(),
+        };
+        base64::engine::general_purpose::STANDARD.encode(Psbt::from_unsigned_tx(tx).unwrap().serialize())
+    }
+
+    #[derive(Default)]
+    struct MockWallet {
+        sent: Mutex<Vec<Vec<Payment>>>,
+        broadcast: Mutex<Vec<String>>,
+        confirmations: Mutex<i64>,
+    }
+
+    impl WalletRpc for MockWallet {
+        fn send_many(&self, payments: &[Payment], _comment: &str) -> Result<String> {
+            self.sent.lock().unwrap().push(payments.to_vec());
+            Ok("txid1".to_string())
+        }
+
+        fn create_psbt(&self, payments: &[Payment]) -> Result<String> {
+            Ok(psbt(payments))
+        }
+
+        fn broadcast_psbt(&self, psbt: &str) -> Result<String> {
+            self.broadcast.lock().unwrap().push(psbt.to_string());
+            Ok("txid2".to_string())
+        }
+
+        fn confirmations(&self, _txid: &str) -> Result<i64> {
+            Ok(*self.confirmations.lock().unwrap())
+        }
+    }
+
+    async fn engine_with_batch(dir: &tempfile::TempDir) -> (PayoutEngine, String) {
+        let engine = PayoutEngine::new(dir.path().join("payouts.json"), PayoutConfig::default());
+        let share = SimplePplnsShare {
+            btcaddress: Some(MINER.to_string()),
+            workername: Some("rig".to_string()),
+            user_id: 1,
+            difficulty: 1,
+            n_time: 1_700_000_000,
+            job_id: "job".to_string(),
+            extranonce2: "00000001".to_string(),
+            nonce: "00000001".to_string(),
+        };
+        let block = FoundBlock {
+            height: 1,
+            hash: "hash1".to_string(),
+            timestamp: Utc::now(),
+            finder_address: None,
+            reward_sats: 1_000_000,
+            confirmations: 100,
+            orphaned: false,
+            state: Default::default(),
+            state_changes: Vec::new(),
+        };
+        engine.credit_block(&block, &[share], 0).await.unwrap();
+        let batch = engine.schedule_batch(None).await.unwrap().unwrap();
+        (engine, batch.id)
+    }
+
+    #[tokio::test]
+    async fn test_sendmany_records_txid_and_confirms() {
+        let dir = tempfile::tempdir().unwrap();
+        let (engine, id) = engine_with_batch(&dir).await;
+        let rpc = Arc::new(MockWallet::default());
+
+        // Dry run leaves the batch pending and the wallet untouched
+        PayoutWallet::new(WalletMode::DryRun).process(&engine, rpc.clone()).await.unwrap();
+        let batch = engine.batch(&id).await.unwrap();
+        assert_eq!(batch.status, PayoutStatus::Pending);
+        assert!(batch.note.unwrap().contains("1000000 sats"));
+        assert!(rpc.sent.lock().unwrap().is_empty());
+
+        let wallet = PayoutWallet::new(WalletMode::SendMany).with_required_confirmations(2);
+        wallet.process(&engine, rpc.clone()).await.unwrap();
+        let batch = engine.batch(&id).await.unwrap();
+        assert_eq!(batch.status, PayoutStatus::Sent);
+        assert_eq!(batch.txid.as_deref(), Some("txid1"));
+        assert_eq!(rpc.sent.lock().unwrap().len(), 1);
+
+        *rpc.confirmations.lock().unwrap() = 1;
+        wallet.process(&engine, rpc.clone()).await.unwrap();
+        assert_eq!(engine.batch(&id).await.unwrap().status, PayoutStatus::Sent);
+        *rpc.confirmations.lock().unwrap() = 2;
+        wallet.process(&engine, rpc.clone()).await.unwrap();
+        assert_eq!(engine.batch(&id).await.unwrap().status, PayoutStatus::Paid);
+        // Paid batches are never sent again
+        assert_eq!(rpc.sent.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_psbt_waits_for_signed_transaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let (engine, id) = engine_with_batch(&dir).await;
+        let rpc = Arc::new(MockWallet::default());
+        let wallet = PayoutWallet::new(WalletMode::Psbt);
+
+        wallet.process(&engine, rpc.clone()).await.unwrap();
+        let batch = engine.batch(&id).await.unwrap();
+        assert_eq!(batch.status, PayoutStatus::AwaitingSignature);
+        let created = batch.psbt.unwrap();
+        assert_eq!(created, psbt(&batch.payments));
+
+        // Only the transaction created for the batch is broadcast
+        let redirected = psbt(&[Payment { address: "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq".to_string(), amount_sats: 1_000_000 }]);
+        let inflated = psbt(&[Payment { address: MINER.to_string(), amount_sats: 2_000_000 }]);
+        for psbt in ["not a psbt", &redirected, &inflated] {
+            assert!(wallet.broadcast_signed(&engine, rpc.clone(), &id, psbt).await.is_err());
+        }
+        assert!(rpc.broadcast.lock().unwrap().is_empty());
+        assert_eq!(engine.batch(&id).await.unwrap().status, PayoutStatus::AwaitingSignature);
+
+        let batch = wallet.broadcast_signed(&engine, rpc.clone(), &id, &created).await.unwrap();
+        assert_eq!(batch.status, PayoutStatus::Sent);
+        assert_eq!(batch.txid.as_deref(), Some("txid2"));
+        assert!(batch.psbt.is_none());
+    }
+}
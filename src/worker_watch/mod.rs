@@ -0,0 +1,293 @@
+// Worker Down Detection for DMPool
+// Notices when a worker that was mining steadily stops submitting shares
+
+use crate::share_stats::worker_key;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tokio::sync::RwLock;
+use utoipa::ToSchema;
+
+/// Workers idle this long are forgotten
+const FORGET_AFTER_SECS: u64 = 7 * 24 * 3600;
+
+/// Thresholds of workers without their own settings
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct WatchDefaults {
+    /// How long a worker must have been submitting shares before it is watched
+    pub min_active_minutes: u64,
+    /// How long without shares before a watched worker is down
+    pub idle_minutes: u64,
+}
+
+impl Default for WatchDefaults {
+    fn default() -> Self {
+        Self {
+            min_active_minutes: 60,
+            idle_minutes: 30,
+        }
+    }
+}
+
+impl WatchDefaults {
+    /// Defaults from `WORKER_DOWN_ACTIVE_MINUTES` and `WORKER_DOWN_IDLE_MINUTES`
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            min_active_minutes: std::env::var("WORKER_DOWN_ACTIVE_MINUTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.min_active_minutes),
+            idle_minutes: std::env::var("WORKER_DOWN_IDLE_MINUTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|minutes| *minutes > 0)
+                .unwrap_or(defaults.idle_minutes),
+        }
+    }
+}
+
+/// Down detection settings of one worker; unset thresholds use the defaults
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct WorkerWatchSettings {
+    pub address: String,
+    pub worker: String,
+    /// `false` opts the worker out of down alerts and notifications
+    pub enabled: bool,
+    pub min_active_minutes: Option<u64>,
+    pub idle_minutes: Option<u64>,
+}
+
+/// A watched worker going down or coming back
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WorkerWatchEvent {
+    Down {
+        address: String,
+        worker: String,
+        /// Unix time of the first share of the worker's last active stretch
+        active_since: u64,
+        last_share_at: u64,
+    },
+    Recovered {
+        address: String,
+        worker: String,
+        /// Seconds between the last share before going down and the first after
+        down_secs: u64,
+    },
+}
+
+#[derive(Clone, Debug)]
+struct Activity {
+    address: String,
+    worker: String,
+    active_since: u64,
+    last_share_at: u64,
+    down: bool,
+}
+
+/// Share activity of every worker, with persistent per-worker settings
+pub struct WorkerWatch {
+    path: PathBuf,
+    defaults: WatchDefaults,
+    settings: RwLock<HashMap<String, WorkerWatchSettings>>,
+    activity: Mutex<HashMap<String, Activity>>,
+}
+
+impl WorkerWatch {
+    /// Create a watch whose settings are stored at `path`
+    pub fn new(path: PathBuf, defaults: WatchDefaults) -> Self {
+        Self {
+            path,
+            defaults,
+            settings: RwLock::new(HashMap::new()),
+            activity: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn defaults(&self) -> WatchDefaults {
+        self.defaults
+    }
+
+    /// Load worker settings from disk, if present
+    pub async fn load(&self) -> Result<usize> {
+        if !self.path.exists() {
+            return Ok(0);
+        }
+        let content = tokio::fs::read_to_string(&self.path).await
+            .context("Failed to read worker watch settings")?;
+        let settings: Vec<WorkerWatchSettings> = serde_json::from_str(&content)
+            .context("Failed to parse worker watch settings")?;
+        let count = settings.len();
+        *self.settings.write().await = settings.into_iter()
+            .map(|s| (worker_key(&s.address, &s.worker), s))
+            .collect();
+        Ok(count)
+    }
+
+    async fn save(&self, settings: &HashMap<String, WorkerWatchSettings>) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut list: Vec<&WorkerWatchSettings> = settings.values().collect();
+        list.sort_by(|a, b| (&a.address, &a.worker).cmp(&(&b.address, &b.worker)));
+        let tmp = self.path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, serde_json::to_string_pretty(&list)?).await
+            .context("Failed to write worker watch settings")?;
+        tokio::fs::rename(&tmp, &self.path).await
+            .context("Failed to replace worker watch settings")?;
+        Ok(())
+    }
+
+    /// Settings of the address's workers that have any
+    pub async fn settings(&self, address: &str) -> Vec<WorkerWatchSettings> {
+        let mut list: Vec<WorkerWatchSettings> = self.settings.read().await.values()
+            .filter(|s| s.address == address)
+            .cloned()
+            .collect();
+        list.sort_by(|a, b| a.worker.cmp(&b.worker));
+        list
+    }
+
+    /// Store a worker's settings; settings equal to the defaults are removed
+    pub async fn set(&self, settings: WorkerWatchSettings) -> Result<()> {
+        if settings.idle_minutes == Some(0) {
+            return Err(anyhow::anyhow!("idle_minutes must be at least 1"));
+        }
+        let mut all = self.settings.write().await;
+        let key = worker_key(&settings.address, &settings.worker);
+        if settings.enabled && settings.min_active_minutes.is_none() && settings.idle_minutes.is_none() {
+            all.remove(&key);
+        } else {
+            all.insert(key, settings);
+        }
+        self.save(&all).await
+    }
+
+    /// Record a share of a worker found at `n_time`
+    ///
+    /// Shares must be observed oldest first. A share after a gap longer than
+    /// the worker's idle threshold starts a new active stretch, and recovers
+    /// the worker if it was down.
+    pub async fn observe(&self, address: &str, worker: &str, n_time: u64) -> Option<WorkerWatchEvent> {
+        let (_, idle_secs) = self.thresholds(address, worker).await;
+        let mut activity = self.activity.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = activity.entry(worker_key(address, worker)).or_insert_with(|| Activity {
+            address: address.to_string(),
+            worker: worker.to_string(),
+            active_since: n_time,
+            last_share_at: n_time,
+            down: false,
+        });
+        if n_time <= entry.last_share_at {
+            return None;
+        }
+        let gap = n_time - entry.last_share_at;
+        let recovered = entry.down.then(|| WorkerWatchEvent::Recovered {
+            address: address.to_string(),
+            worker: worker.to_string(),
+            down_secs: gap,
+        });
+        if gap > idle_secs {
+            entry.active_since = n_time;
+        }
+        entry.last_share_at = n_time;
+        entry.down = false;
+        recovered
+    }
+
+    /// Workers that went down by `now`, each reported once until it recovers
+    pub async fn check(&self, now: u64) -> Vec<WorkerWatchEvent> {
+        let settings = self.settings.read().await.clone();
+        let mut activity = self.activity.lock().unwrap_or_else(|e| e.into_inner());
+        activity.retain(|_, a| now.saturating_sub(a.last_share_at) < FORGET_AFTER_SECS);
+
+        let mut events = Vec::new();
+        for (key, worker) in activity.iter_mut() {
+            let own = settings.get(key);
+            if worker.down || own.is_some_and(|s| !s.enabled) {
+                continue;
+            }
+            let (min_active_secs, idle_secs) = self.resolve(own);
+            let active_secs = worker.last_share_at - worker.active_since;
+            if active_secs >= min_active_secs && now.saturating_sub(worker.last_share_at) > idle_secs {
+                worker.down = true;
+                events.push(WorkerWatchEvent::Down {
+                    address: worker.address.clone(),
+                    worker: worker.worker.clone(),
+                    active_since: worker.active_since,
+                    last_share_at: worker.last_share_at,
+                });
+            }
+        }
+        events
+    }
+
+    async fn thresholds(&self, address: &str, worker: &str) -> (u64, u64) {
+        let settings = self.settings.read().await;
+        self.resolve(settings.get(&worker_key(address, worker)))
+    }
+
+    /// Minimum active and idle seconds of a worker
+    fn resolve(&self, settings: Option<&WorkerWatchSettings>) -> (u64, u64) {
+        let min_active = settings.and_then(|s| s.min_active_minutes).unwrap_or(self.defaults.min_active_minutes);
+        let idle = settings.and_then(|s| s.idle_minutes).unwrap_or(self.defaults.idle_minutes);
+        (min_active * 60, idle * 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_down_and_recovery() {
+        let dir = tempfile::tempdir().unwrap();
+        let watch = WorkerWatch::new(dir.path().join("worker_watch.json"), WatchDefaults::default());
+        // rig1 mines for two hours, rig2 only for ten minutes
+        for t in (0..=7200).step_by(60) {
+            assert!(watch.observe("bc1qminer", "rig1", t).await.is_none());
+        }
+        for t in (6600..=7200).step_by(60) {
+            watch.observe("bc1qminer", "rig2", t).await;
+        }
+
+        assert!(watch.check(7200 + 1800).await.is_empty());
+        let events = watch.check(7200 + 1801).await;
+        assert_eq!(events, vec![WorkerWatchEvent::Down {
+            address: "bc1qminer".to_string(),
+            worker: "rig1".to_string(),
+            active_since: 0,
+            last_share_at: 7200,
+        }]);
+        // Reported once
+        assert!(watch.check(7200 + 3600).await.is_empty());
+        assert_eq!(
+            watch.observe("bc1qminer", "rig1", 7200 + 4000).await,
+            Some(WorkerWatchEvent::Recovered {
+                address: "bc1qminer".to_string(),
+                worker: "rig1".to_string(),
+                down_secs: 4000,
+            }),
+        );
+
+        // Opted out workers are not reported
+        watch.set(WorkerWatchSettings {
+            address: "bc1qminer".to_string(),
+            worker: "rig1".to_string(),
+            enabled: false,
+            min_active_minutes: None,
+            idle_minutes: None,
+        }).await.unwrap();
+        for t in (11_200..=20_000).step_by(60) {
+            watch.observe("bc1qminer", "rig1", t).await;
+        }
+        assert!(watch.check(30_000).await.is_empty());
+
+        let reloaded = WorkerWatch::new(dir.path().join("worker_watch.json"), WatchDefaults::default());
+        assert_eq!(reloaded.load().await.unwrap(), 1);
+        assert!(!reloaded.settings("bc1qminer").await[0].enabled);
+    }
+}
@@ -0,0 +1,312 @@
+// ZMQ Monitor for DMPool
+// Subscribes to the Bitcoin node's hashblock notifications to verify ZMQ is delivering
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast, RwLock};
+use tokio::time::timeout;
+use tracing::{debug, info, warn};
+
+/// Topic published by bitcoind for `zmqpubhashblock`
+pub const HASHBLOCK_TOPIC: &str = "hashblock";
+
+/// Time allowed to connect and complete the ZMTP handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Delay before reconnecting after the subscription drops
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Frames larger than this are treated as a protocol error
+const MAX_FRAME_SIZE: u64 = 16 * 1024 * 1024;
+
+const FLAG_MORE: u8 = 0x01;
+const FLAG_LONG: u8 = 0x02;
+const FLAG_COMMAND: u8 = 0x04;
+
+/// A ZMTP frame
+#[derive(Debug)]
+struct Frame {
+    more: bool,
+    command: bool,
+    body: Vec<u8>,
+}
+
+/// ZMTP 3.0 greeting for the NULL mechanism, as a client
+fn greeting() -> [u8; 64] {
+    let mut greeting = [0u8; 64];
+    greeting[0] = 0xFF;
+    greeting[9] = 0x7F;
+    greeting[10] = 3;
+    greeting[12..16].copy_from_slice(b"NULL");
+    greeting
+}
+
+/// READY command body announcing `socket_type`
+fn ready_command(socket_type: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.push(5);
+    body.extend_from_slice(b"READY");
+    body.push(11);
+    body.extend_from_slice(b"Socket-Type");
+    body.extend_from_slice(&(socket_type.len() as u32).to_be_bytes());
+    body.extend_from_slice(socket_type.as_bytes());
+    body
+}
+
+async fn write_frame(stream: &mut TcpStream, flags: u8, body: &[u8]) -> Result<()> {
+    let mut frame = Vec::with_capacity(body.len() + 9);
+    if body.len() > u8::MAX as usize {
+        frame.push(flags | FLAG_LONG);
+        frame.extend_from_slice(&(body.len() as u64).to_be_bytes());
+    } else {
+        frame.push(flags);
+        frame.push(body.len() as u8);
+    }
+    frame.extend_from_slice(body);
+    stream.write_all(&frame).await?;
+    Ok(())
+}
+
+async fn read_frame(stream: &mut TcpStream) -> Result<Frame> {
+    let flags = stream.read_u8().await?;
+    let size = if flags & FLAG_LONG != 0 {
+        stream.read_u64().await?
+    } else {
+        stream.read_u8().await? as u64
+    };
+    if size > MAX_FRAME_SIZE {
+        return Err(anyhow::anyhow!("ZMQ frame too large: {} bytes", size));
+    }
+    let mut body = vec![0u8; size as usize];
+    stream.read_exact(&mut body).await?;
+    Ok(Frame {
+        more: flags & FLAG_MORE != 0,
+        command: flags & FLAG_COMMAND != 0,
+        body,
+    })
+}
+
+/// Name of a command frame (e.g. "READY", "ERROR")
+fn command_name(body: &[u8]) -> &[u8] {
+    let len = body.first().copied().unwrap_or(0) as usize;
+    body.get(1..1 + len).unwrap_or_default()
+}
+
+/// Strip the `tcp://` scheme from a ZMQ endpoint
+fn tcp_address(endpoint: &str) -> Result<&str> {
+    endpoint.strip_prefix("tcp://")
+        .filter(|address| !address.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("Invalid ZMQ URL format (expected tcp://host:port): {}", endpoint))
+}
+
+/// Connect, exchange greetings and READY commands, and subscribe to `topic`
+async fn subscribe(endpoint: &str, topic: &str) -> Result<TcpStream> {
+    let address = tcp_address(endpoint)?;
+    let mut stream = TcpStream::connect(address).await
+        .with_context(|| format!("ZMQ connection to {} failed", address))?;
+
+    stream.write_all(&greeting()).await?;
+    let mut peer = [0u8; 64];
+    stream.read_exact(&mut peer).await.context("No ZMTP greeting from peer")?;
+    if peer[0] != 0xFF || peer[9] != 0x7F {
+        return Err(anyhow::anyhow!("Peer is not a ZMQ endpoint"));
+    }
+    if peer[10] < 3 {
+        return Err(anyhow::anyhow!("Unsupported ZMTP version {}.{}", peer[10], peer[11]));
+    }
+    if &peer[12..16] != b"NULL" {
+        return Err(anyhow::anyhow!("Unsupported ZMQ security mechanism"));
+    }
+
+    write_frame(&mut stream, FLAG_COMMAND, &ready_command("SUB")).await?;
+    let ready = read_frame(&mut stream).await?;
+    if !ready.command || command_name(&ready.body) != b"READY" {
+        let reason = String::from_utf8_lossy(&ready.body).to_string();
+        return Err(anyhow::anyhow!("ZMQ handshake rejected: {}", reason));
+    }
+
+    // ZMTP 3.0 subscriptions are messages starting with 0x01
+    let mut subscription = vec![1u8];
+    subscription.extend_from_slice(topic.as_bytes());
+    write_frame(&mut stream, 0, &subscription).await?;
+    Ok(stream)
+}
+
+/// Read one multipart message, skipping commands; returns its frames
+async fn read_message(stream: &mut TcpStream) -> Result<Vec<Vec<u8>>> {
+    let mut frames = Vec::new();
+    loop {
+        let frame = read_frame(stream).await?;
+        if frame.command {
+            debug!("Ignoring ZMQ command {:?}", String::from_utf8_lossy(command_name(&frame.body)));
+            continue;
+        }
+        frames.push(frame.body);
+        if !frame.more {
+            return Ok(frames);
+        }
+    }
+}
+
+/// Complete a subscription handshake once, returning how long it took
+pub async fn probe(endpoint: &str) -> Result<Duration> {
+    let start = Instant::now();
+    timeout(HANDSHAKE_TIMEOUT, subscribe(endpoint, HASHBLOCK_TOPIC)).await
+        .map_err(|_| anyhow::anyhow!("ZMQ handshake timeout ({}s)", HANDSHAKE_TIMEOUT.as_secs()))??;
+    Ok(start.elapsed())
+}
+
+/// Current state of the subscription
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ZmqMonitorStatus {
+    pub connected: bool,
+    pub connected_at: Option<DateTime<Utc>>,
+    pub last_notification_at: Option<DateTime<Utc>>,
+    pub notifications: u64,
+    pub last_error: Option<String>,
+}
+
+/// Long-lived hashblock subscriber tracking when notifications arrive
+pub struct ZmqMonitor {
+    endpoint: String,
+    status: RwLock<ZmqMonitorStatus>,
+    blocks: broadcast::Sender<String>,
+}
+
+impl ZmqMonitor {
+    /// Monitor the `zmqpubhashblock` endpoint, e.g. `tcp://127.0.0.1:28332`
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        let (blocks, _) = broadcast::channel(16);
+        Self {
+            endpoint: endpoint.into(),
+            status: RwLock::new(ZmqMonitorStatus::default()),
+            blocks,
+        }
+    }
+
+    /// Receive the hash of each new chain tip as it is announced
+    pub fn subscribe_blocks(&self) -> broadcast::Receiver<String> {
+        self.blocks.subscribe()
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    pub async fn status(&self) -> ZmqMonitorStatus {
+        self.status.read().await.clone()
+    }
+
+    /// Keep subscribed, reconnecting whenever the connection drops
+    pub async fn run(self: Arc<Self>) {
+        loop {
+            if let Err(e) = self.session().await {
+                warn!("ZMQ subscription to {} lost: {:#}", self.endpoint, e);
+                let mut status = self.status.write().await;
+                status.connected = false;
+                status.last_error = Some(format!("{:#}", e));
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    }
+
+    async fn session(&self) -> Result<()> {
+        let mut stream = timeout(HANDSHAKE_TIMEOUT, subscribe(&self.endpoint, HASHBLOCK_TOPIC)).await
+            .map_err(|_| anyhow::anyhow!("ZMQ handshake timeout ({}s)", HANDSHAKE_TIMEOUT.as_secs()))??;
+        info!("Subscribed to {} notifications on {}", HASHBLOCK_TOPIC, self.endpoint);
+        {
+            let mut status = self.status.write().await;
+            status.connected = true;
+            status.connected_at = Some(Utc::now());
+            status.last_error = None;
+        }
+
+        loop {
+            let frames = read_message(&mut stream).await?;
+            if frames.first().is_some_and(|topic| topic.as_slice() == HASHBLOCK_TOPIC.as_bytes()) {
+                {
+                    let mut status = self.status.write().await;
+                    status.last_notification_at = Some(Utc::now());
+                    status.notifications += 1;
+                }
+                if let Some(hash) = frames.get(1) {
+                    let hash: String = hash.iter().map(|b| format!("{:02x}", b)).collect();
+                    // No receivers is not an error
+                    let _ = self.blocks.send(hash);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Minimal PUB peer: handshake, check the subscription, publish one block
+    async fn serve_one_block(listener: TcpListener) {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut peer = [0u8; 64];
+        stream.read_exact(&mut peer).await.unwrap();
+        assert_eq!(&peer[12..16], b"NULL");
+        stream.write_all(&greeting()).await.unwrap();
+
+        let ready = read_frame(&mut stream).await.unwrap();
+        assert!(ready.command);
+        assert!(String::from_utf8_lossy(&ready.body).contains("SUB"));
+        write_frame(&mut stream, FLAG_COMMAND, &ready_command("PUB")).await.unwrap();
+
+        let subscription = read_frame(&mut stream).await.unwrap();
+        assert_eq!(subscription.body, b"\x01hashblock");
+
+        write_frame(&mut stream, FLAG_MORE, b"hashblock").await.unwrap();
+        write_frame(&mut stream, FLAG_MORE, &[0xab; 32]).await.unwrap();
+        write_frame(&mut stream, 0, &1u32.to_le_bytes()).await.unwrap();
+        // Keep the connection open until the client goes away
+        let _ = stream.read_u8().await;
+    }
+
+    #[tokio::test]
+    async fn test_monitor_subscribes_and_records_notifications() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("tcp://{}", listener.local_addr().unwrap());
+        tokio::spawn(serve_one_block(listener));
+
+        let monitor = Arc::new(ZmqMonitor::new(endpoint));
+        let mut blocks = monitor.subscribe_blocks();
+        let task = tokio::spawn(monitor.clone().run());
+        let mut status = monitor.status().await;
+        for _ in 0..50 {
+            if status.notifications > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            status = monitor.status().await;
+        }
+        task.abort();
+
+        assert!(status.connected);
+        assert_eq!(status.notifications, 1);
+        assert!(status.last_notification_at.is_some());
+        assert_eq!(blocks.try_recv().unwrap(), "ab".repeat(32));
+    }
+
+    #[tokio::test]
+    async fn test_probe_rejects_non_zmq_peers() {
+        assert!(probe("http://127.0.0.1:1").await.is_err());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("tcp://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream.write_all(&[b'H'; 64]).await.unwrap();
+        });
+        assert!(probe(&endpoint).await.is_err());
+    }
+}